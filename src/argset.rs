@@ -64,6 +64,59 @@ pub(crate) fn push_conflicts_arg() -> clap::Arg {
         .action(clap::ArgAction::Set)
 }
 
+/// Options for overriding the author of new or rewritten patch commits.
+///
+/// The `--committer-date-is-author-date` option is included such that the committer
+/// date may follow an overridden author date. Commands apply these options using
+/// [`crate::ext::SignatureExtended::override_author()`].
+pub(crate) fn author_args() -> [Arg; 5] {
+    use crate::{
+        ext::TimeExtended,
+        patch::edit::{parse_email, parse_name, parse_name_email2},
+    };
+
+    [
+        Arg::new("author")
+            .long("author")
+            .help("Set the author \"name <email>\"")
+            .value_name("name-and-email")
+            .num_args(1)
+            .value_parser(clap::builder::ValueParser::new(parse_name_email2))
+            .value_hint(clap::ValueHint::Other),
+        Arg::new("authname")
+            .long("authname")
+            .help("Set the author name")
+            .value_name("name")
+            .num_args(1)
+            .value_hint(clap::ValueHint::Other)
+            .value_parser(clap::builder::ValueParser::new(parse_name))
+            .conflicts_with("author"),
+        Arg::new("authemail")
+            .long("authemail")
+            .help("Set the author email")
+            .value_name("email")
+            .num_args(1)
+            .value_hint(clap::ValueHint::EmailAddress)
+            .value_parser(clap::builder::ValueParser::new(parse_email))
+            .conflicts_with("author"),
+        Arg::new("authdate")
+            .long("authdate")
+            .help("Set the author date")
+            .long_help(
+                "Set the date the patch was authored.\n\
+                 \n\
+                 Use \"now\" to use the current time and date.",
+            )
+            .value_name("date")
+            .num_args(1)
+            .value_parser(clap::builder::ValueParser::new(
+                git_repository::actor::Time::parse_time,
+            ))
+            .value_hint(clap::ValueHint::Other),
+        committer_date_is_author_date_arg(),
+    ]
+}
+
 pub(crate) fn committer_date_is_author_date_arg() -> clap::Arg {
     Arg::new("committer-date-is-author-date")
         .long("committer-date-is-author-date")
@@ -76,6 +129,40 @@ pub(crate) fn committer_date_is_author_date_arg() -> clap::Arg {
         .action(clap::ArgAction::SetTrue)
 }
 
+pub(crate) fn stable_ids_arg() -> clap::Arg {
+    Arg::new("stable-ids")
+        .long("stable-ids")
+        .help("Keep the committer of rewritten patches")
+        .long_help(
+            "When a patch's commit is rewritten because the patch is pushed onto a \
+             different parent, keep the committer name, email, and date of the \
+             patch's existing commit instead of using the current committer and \
+             time. Repeating the same stack operations thus produces the same commit \
+             ids, which is useful for caching keyed on patch commit ids and for clean \
+             range-diffs.",
+        )
+        .action(clap::ArgAction::SetTrue)
+        .conflicts_with("committer-date-is-author-date")
+}
+
+/// The `--editor` option for overriding the configured editor.
+pub(crate) fn editor_arg() -> Arg {
+    Arg::new("editor")
+        .long("editor")
+        .visible_alias("edit-cmd")
+        .help("Use <cmd> as the editor")
+        .long_help(
+            "Use <cmd> as the editor for this invocation. This takes precedence over \
+             the `GIT_EDITOR` and `GIT_SEQUENCE_EDITOR` environment variables and the \
+             `stgit.editor`, `core.editor`, and `sequence.editor` configuration \
+             variables.",
+        )
+        .value_name("cmd")
+        .num_args(1)
+        .value_parser(clap::builder::NonEmptyStringValueParser::new())
+        .value_hint(clap::ValueHint::CommandString)
+}
+
 /// The `--diff-opt`/`-O` option for pass-through to subordinate `git` processes.
 pub(crate) fn diff_opts_arg() -> Arg {
     Arg::new("git-diff-opt")
@@ -87,7 +174,9 @@ pub(crate) fn diff_opts_arg() -> Arg {
             "Pass additional <option> to `git diff`.\n\
              \n\
              See the git-diff(1) man page. This option may be specified multiple \
-             times.",
+             times. The options are appended to those derived from the \
+             `stgit.diff.algorithm`, `stgit.diff.renames`, `stgit.diff.renameLimit`, \
+             and `stgit.diff-opts` configuration values.",
         )
         .num_args(1)
         .allow_hyphen_values(true)
@@ -96,6 +185,49 @@ pub(crate) fn diff_opts_arg() -> Arg {
         .value_hint(clap::ValueHint::Other)
 }
 
+/// The `--stat-width` option for fitting diffstat lines to a number of columns.
+pub(crate) fn stat_width_arg() -> Arg {
+    Arg::new("stat-width")
+        .long("stat-width")
+        .help("Limit diffstat lines to <n> columns")
+        .long_help(
+            "Limit diffstat lines to <n> columns. File names are truncated and the \
+             graph is scaled to fit. The default is 80 columns.",
+        )
+        .value_name("n")
+        .num_args(1)
+        .value_parser(parse_usize)
+        .requires("stat")
+}
+
+/// The `--stat-count` option for limiting the number of files in a diffstat.
+pub(crate) fn stat_count_arg() -> Arg {
+    Arg::new("stat-count")
+        .long("stat-count")
+        .help("List at most <n> files in the diffstat")
+        .long_help(
+            "List at most <n> files in the diffstat. The totals still account for \
+             all files.",
+        )
+        .value_name("n")
+        .num_args(1)
+        .value_parser(parse_usize)
+        .requires("stat")
+}
+
+/// Render the diffstat of a diff according to the `--stat-width` and `--stat-count`
+/// options.
+pub(crate) fn render_diffstat(matches: &clap::ArgMatches, diff: &[u8]) -> String {
+    crate::patch::diffstat::Diffstat::from_diff(diff).render(
+        matches
+            .get_one::<usize>("stat-width")
+            .copied()
+            .unwrap_or(crate::patch::diffstat::DEFAULT_WIDTH),
+        matches.get_one::<usize>("stat-count").copied(),
+        crate::color::use_color(matches),
+    )
+}
+
 /// For use with `clap::Arg::value_parser()` to ensure a branch name is valid.
 pub(crate) fn parse_branch_name(name: &str) -> anyhow::Result<String> {
     Ok(git_repository::refs::PartialName::try_from(name).map(|_| name.to_string())?)
@@ -124,10 +256,11 @@ pub(crate) fn parse_usize(s: &str) -> anyhow::Result<usize> {
 /// These options are meant to be passed to various subordinate `git` commands that take
 /// diff options.
 ///
-/// The base set of options come from `stgit.diff-opts` in the config. Additional
-/// options from `--diff-opt`/`-O` command line options are appended. And StGit
-/// command-specific policies for displaying the full object id (`--full-index`) and
-/// including binary diffs (`--binary`) are tacked on at the end.
+/// The base set of options come from the `stgit.diff.*` config values, as resolved by
+/// [`get_config_diff_opts()`], followed by the options from `stgit.diff-opts`.
+/// Additional options from `--diff-opt`/`-O` command line options are appended. And
+/// StGit command-specific policies for displaying the full object id (`--full-index`)
+/// and including binary diffs (`--binary`) are tacked on at the end.
 ///
 /// The returned `Vec<String>` is appropriate for inserting directly into the command
 /// line of subordinate `git` commands.
@@ -136,8 +269,8 @@ pub(crate) fn get_diff_opts(
     config: &git_repository::config::Snapshot,
     force_full_index: bool,
     force_binary: bool,
-) -> Vec<String> {
-    let mut opts = Vec::new();
+) -> anyhow::Result<Vec<String>> {
+    let mut opts = get_config_diff_opts(config)?;
 
     if let Some(value) = config.string("stgit.diff-opts") {
         if let Ok(value) = value.to_str() {
@@ -159,7 +292,65 @@ pub(crate) fn get_diff_opts(
         opts.push(String::from("--binary"));
     }
 
-    opts
+    Ok(opts)
+}
+
+/// Get git diff options from the `stgit.diff.*` config values.
+///
+/// `stgit.diff.algorithm` selects the diff algorithm, `stgit.diff.renames` enables
+/// (true), disables (false), or extends to copies ("copies") the detection of renames,
+/// and `stgit.diff.renameLimit` limits the number of files considered for rename
+/// detection. Applying these options to all StGit commands that produce diffs keeps
+/// the diffs consistent regardless of each command's own defaults.
+pub(crate) fn get_config_diff_opts(
+    config: &git_repository::config::Snapshot,
+) -> anyhow::Result<Vec<String>> {
+    let config_str = |key: &str| {
+        config
+            .string(key)
+            .map(|value| value.to_str_lossy().trim().to_lowercase())
+    };
+    let mut opts = Vec::new();
+
+    if let Some(algorithm) = config_str("stgit.diff.algorithm") {
+        match algorithm.as_str() {
+            "default" | "myers" | "minimal" | "patience" | "histogram" => {
+                opts.push(format!("--diff-algorithm={algorithm}"));
+            }
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "invalid `stgit.diff.algorithm` value `{algorithm}`; expected \
+                     `myers`, `minimal`, `patience`, or `histogram`"
+                ))
+            }
+        }
+    }
+
+    if let Some(renames) = config_str("stgit.diff.renames") {
+        if renames == "copies" || renames == "copy" {
+            opts.push(String::from("-C"));
+        } else {
+            match config.boolean("stgit.diff.renames") {
+                Some(true) => opts.push(String::from("-M")),
+                Some(false) => opts.push(String::from("--no-renames")),
+                None => {
+                    return Err(anyhow::anyhow!(
+                        "invalid `stgit.diff.renames` value `{renames}`; expected a \
+                         boolean or `copies`"
+                    ))
+                }
+            }
+        }
+    }
+
+    if let Some(limit) = config_str("stgit.diff.renameLimit") {
+        let limit = limit
+            .parse::<usize>()
+            .map_err(|_| anyhow::anyhow!("invalid `stgit.diff.renameLimit` value `{limit}`"))?;
+        opts.push(format!("-l{limit}"));
+    }
+
+    Ok(opts)
 }
 
 pub(crate) fn resolve_allow_push_conflicts(
@@ -0,0 +1,321 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg apply-plan` implementation.
+
+use std::{
+    collections::{HashMap, HashSet},
+    io::Read,
+    path::PathBuf,
+    str::FromStr,
+};
+
+use anyhow::{anyhow, Context, Result};
+use clap::{Arg, ArgMatches, ValueHint};
+
+use crate::{
+    color::get_color_stdout,
+    ext::{CommitExtended, RepositoryExtended},
+    patch::PatchName,
+    print_info_message,
+    stack::{InitializationPolicy, Stack, StackStateAccess},
+    stupid::Stupid,
+    wrap::Message,
+};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "apply-plan",
+    category: super::CommandCategory::StackManipulation,
+    make,
+    run,
+};
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Rearrange the stack according to a plan file")
+        .long_about(
+            "Rearrange the patches of the current stack to match the series described \
+             by a plan file. The plan describes the desired order of the applied, \
+             unapplied, and hidden patches and may also rename patches and change \
+             patch messages. This is intended for tools that generate stack layouts \
+             programmatically.\n\
+             \n\
+             The plan file is a JSON object with optional \"applied\", \"unapplied\", \
+             and \"hidden\" arrays. Each array element is either the name of an \
+             existing patch or an object with a \"patch\" field naming an existing \
+             patch, an optional \"name\" field with a new name for the patch, and an \
+             optional \"message\" field with a new message for the patch. For \
+             example:\n\
+             \n    \
+             {\n      \
+               \"applied\": [\"first\", {\"patch\": \"second\", \"name\": \"renamed\"}],\n      \
+               \"unapplied\": [{\"patch\": \"third\", \"message\": \"New subject\\n\"}],\n      \
+               \"hidden\": [\"fourth\"]\n    \
+             }\n\
+             \n\
+             Every patch in the stack must appear exactly once in the plan. Patches may \
+             not be added or deleted with a plan.\n\
+             \n\
+             All changes are made in a single stack transaction, popping and pushing \
+             only the patches necessary to reach the planned series. If pushing a \
+             patch results in merge conflicts, the operation halts with the conflicts \
+             left to be resolved; `stg undo --hard` restores the prior stack state.",
+        )
+        .arg(
+            Arg::new("file")
+                .help("Plan file, or '-' to read from stdin")
+                .required(true)
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(ValueHint::FilePath),
+        )
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Plan {
+    #[serde(default)]
+    applied: Vec<PlanEntry>,
+    #[serde(default)]
+    unapplied: Vec<PlanEntry>,
+    #[serde(default)]
+    hidden: Vec<PlanEntry>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum PlanEntry {
+    Name(String),
+    Patch(PlanPatch),
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PlanPatch {
+    patch: String,
+    name: Option<String>,
+    message: Option<String>,
+}
+
+/// A validated plan entry.
+struct PlannedPatch {
+    /// Name of the existing patch.
+    patchname: PatchName,
+
+    /// Name of the patch after the plan is applied.
+    new_patchname: PatchName,
+
+    /// Replacement message, only when it differs from the patch's current message.
+    message: Option<String>,
+}
+
+fn run(matches: &ArgMatches) -> Result<()> {
+    let repo = git_repository::Repository::open()?;
+    let stack = Stack::from_branch(&repo, None, InitializationPolicy::AllowUninitialized)?;
+    let stupid = repo.stupid();
+
+    let path = matches
+        .get_one::<PathBuf>("file")
+        .expect("file is a required argument");
+    let plan_bytes = if path.as_os_str() == "-" {
+        let mut buf = Vec::new();
+        std::io::stdin().read_to_end(&mut buf)?;
+        buf
+    } else {
+        std::fs::read(path).with_context(|| format!("reading `{}`", path.display()))?
+    };
+    let plan: Plan = serde_json::from_slice(&plan_bytes).context("parsing plan")?;
+
+    let mut seen: HashSet<PatchName> = HashSet::new();
+    let mut validate = |entries: Vec<PlanEntry>| -> Result<Vec<PlannedPatch>> {
+        let mut planned = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let (patch, name, message) = match entry {
+                PlanEntry::Name(patch) => (patch, None, None),
+                PlanEntry::Patch(PlanPatch {
+                    patch,
+                    name,
+                    message,
+                }) => (patch, name, message),
+            };
+            let patchname = PatchName::from_str(&patch)?;
+            if !stack.has_patch(&patchname) {
+                return Err(anyhow!("patch `{patchname}` does not exist"));
+            }
+            if !seen.insert(patchname.clone()) {
+                return Err(anyhow!(
+                    "patch `{patchname}` appears more than once in plan"
+                ));
+            }
+            let new_patchname = if let Some(name) = name {
+                PatchName::from_str(&name)?
+            } else {
+                patchname.clone()
+            };
+            let message = if let Some(message) = message {
+                let message = if message.trim().is_empty() {
+                    String::new()
+                } else {
+                    let mut message = message.trim_end().to_string();
+                    message.push('\n');
+                    message
+                };
+                let commit = stack.get_patch_commit(&patchname)?;
+                if commit.message_ex().decode()?.trim_end() == message.trim_end() {
+                    None
+                } else {
+                    Some(message)
+                }
+            } else {
+                None
+            };
+            planned.push(PlannedPatch {
+                patchname,
+                new_patchname,
+                message,
+            });
+        }
+        Ok(planned)
+    };
+
+    let applied = validate(plan.applied)?;
+    let unapplied = validate(plan.unapplied)?;
+    let hidden = validate(plan.hidden)?;
+
+    if let Some(patchname) = stack.all_patches().find(|pn| !seen.contains(*pn)) {
+        return Err(anyhow!("patch `{patchname}` is missing from plan"));
+    }
+
+    let planned: Vec<&PlannedPatch> = applied.iter().chain(&unapplied).chain(&hidden).collect();
+
+    for (i, p) in planned.iter().enumerate() {
+        if let Some(other) = planned[..i]
+            .iter()
+            .find(|other| other.new_patchname.collides(&p.new_patchname))
+        {
+            return Err(anyhow!(
+                "plan names both `{}` and `{}` as `{}`",
+                other.patchname,
+                p.patchname,
+                p.new_patchname,
+            ));
+        }
+    }
+
+    let config = repo.config_snapshot();
+    for p in planned.iter().filter(|p| p.new_patchname != p.patchname) {
+        if let Some(warning) = p.new_patchname.check_portability(&config)? {
+            crate::print_warning_message(matches, &warning);
+        }
+    }
+
+    let renames = order_renames(&planned)?;
+
+    let committer = repo.get_committer()?;
+    let mut message_updates: Vec<(PatchName, git_repository::ObjectId)> = Vec::new();
+    for p in &planned {
+        if let Some(message) = p.message.as_ref() {
+            let commit = stack.get_patch_commit(&p.patchname)?;
+            let commit_id = repo.commit_ex(
+                &commit.author_strict()?,
+                committer,
+                &Message::from(message.as_str()),
+                commit.tree_id()?.detach(),
+                commit.parent_ids().map(|id| id.detach()),
+            )?;
+            message_updates.push((p.new_patchname.clone(), commit_id));
+        }
+    }
+
+    // Applied patches below the first patch to be moved or modified stay in place.
+    let num_common = stack
+        .applied()
+        .iter()
+        .zip(&applied)
+        .take_while(|(pn, p)| *pn == &p.patchname && p.message.is_none())
+        .count();
+    let to_pop: Vec<PatchName> = stack.applied()[num_common..].to_vec();
+    let to_push: Vec<PatchName> = applied[num_common..]
+        .iter()
+        .map(|p| p.new_patchname.clone())
+        .collect();
+    let new_unapplied: Vec<PatchName> = to_push
+        .iter()
+        .cloned()
+        .chain(unapplied.iter().map(|p| p.new_patchname.clone()))
+        .collect();
+    let new_hidden: Vec<PatchName> = hidden.iter().map(|p| p.new_patchname.clone()).collect();
+
+    if to_pop.is_empty()
+        && to_push.is_empty()
+        && renames.is_empty()
+        && message_updates.is_empty()
+        && stack.unapplied() == &new_unapplied[..]
+        && stack.hidden() == &new_hidden[..]
+    {
+        print_info_message(matches, "stack already matches plan");
+        return Ok(());
+    }
+
+    if !to_pop.is_empty() || !to_push.is_empty() {
+        repo.check_repository_state()?;
+        let statuses = stupid.statuses(None)?;
+        statuses.check_conflicts()?;
+        statuses.check_index_and_worktree_clean()?;
+    }
+    stack.check_head_top_mismatch()?;
+
+    stack
+        .setup_transaction()
+        .use_index_and_worktree(true)
+        .with_output_stream(get_color_stdout(matches))
+        .transact(|trans| {
+            let popped_extra = trans.pop_patches(|pn| to_pop.contains(pn))?;
+            assert!(popped_extra.is_empty());
+            for (old_patchname, new_patchname) in &renames {
+                trans.rename_patch(old_patchname, new_patchname)?;
+            }
+            for (patchname, commit_id) in &message_updates {
+                trans.update_patch(patchname, *commit_id)?;
+            }
+            trans.reorder_patches(None, Some(&new_unapplied), Some(&new_hidden))?;
+            trans.push_patches(&to_push, false)
+        })
+        .execute("apply-plan")?;
+
+    Ok(())
+}
+
+/// Order renames such that no patch is renamed to the name of a patch that has not
+/// yet been renamed away.
+fn order_renames(planned: &[&PlannedPatch]) -> Result<Vec<(PatchName, PatchName)>> {
+    let mut pending: Vec<&PlannedPatch> = planned
+        .iter()
+        .filter(|p| p.new_patchname != p.patchname)
+        .copied()
+        .collect();
+    let mut current: HashMap<&PatchName, &PatchName> = planned
+        .iter()
+        .map(|p| (&p.patchname, &p.patchname))
+        .collect();
+    let mut renames = Vec::with_capacity(pending.len());
+
+    while !pending.is_empty() {
+        let pos = pending
+            .iter()
+            .position(|p| {
+                !current
+                    .iter()
+                    .any(|(orig, name)| *orig != &p.patchname && name.collides(&p.new_patchname))
+            })
+            .ok_or_else(|| {
+                anyhow!(
+                    "plan renames `{}` in a cycle; rename through an intermediate name",
+                    pending[0].patchname
+                )
+            })?;
+        let p = pending.remove(pos);
+        current.insert(&p.patchname, &p.new_patchname);
+        renames.push((p.patchname.clone(), p.new_patchname.clone()));
+    }
+
+    Ok(renames)
+}
@@ -0,0 +1,261 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg backport` implementation.
+
+use std::collections::HashSet;
+
+use anyhow::{anyhow, Result};
+use clap::Arg;
+
+use crate::{
+    argset,
+    color::get_color_stdout,
+    ext::{CommitExtended, RepositoryExtended},
+    patch::{meta, patchrange, PatchName},
+    print_info_message, print_warning_message,
+    stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
+    stupid::Stupid,
+    wrap::Message,
+};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "backport",
+    category: super::CommandCategory::StackManipulation,
+    make,
+    run,
+};
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Pick patches onto another branch's stack")
+        .long_about(
+            "Pick one or more patches from the current branch's stack onto the stack \
+             of another branch, such as a stable or release branch.\n\
+             \n\
+             Each backported patch keeps its patch name and author. A \"(cherry picked \
+             from commit <id>)\" line is appended to the backported patch's message, \
+             and the original \"<branch>:<patch>\" is recorded in the backported \
+             patch's metadata as \"Backport-Of\".\n\
+             \n\
+             Patches whose changes are already present on the target branch, either \
+             as a patch in its stack or as a commit in its history since it diverged \
+             from the source branch, are determined by patch id and skipped.\n\
+             \n\
+             Each remaining patch is pushed onto the target stack in turn. A patch \
+             that does not apply cleanly is reported and left unapplied on the target \
+             stack while the following patches are still backported. Such patches may \
+             be resolved later by checking out the target branch and pushing them.",
+        )
+        .override_usage("stg backport [OPTIONS] --onto <branch> <patch>...")
+        .arg(
+            Arg::new("patchranges")
+                .help("Patches to backport")
+                .value_name("patch")
+                .num_args(1..)
+                .required(true)
+                .value_parser(clap::value_parser!(patchrange::Specification)),
+        )
+        .arg(
+            Arg::new("branch")
+                .long("onto")
+                .short('o')
+                .help("Backport patches onto the stack of <branch>")
+                .value_name("branch")
+                .required(true)
+                .value_parser(clap::builder::NonEmptyStringValueParser::new()),
+        )
+        .arg(
+            Arg::new("ref-branch")
+                .long("ref-branch")
+                .short('B')
+                .help("Backport patches from <branch> instead of the current branch")
+                .value_name("branch"),
+        )
+        .arg(
+            Arg::new("noapply")
+                .long("noapply")
+                .help("Keep the backported patches unapplied")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(argset::committer_date_is_author_date_arg())
+}
+
+fn run(matches: &clap::ArgMatches) -> Result<()> {
+    let repo = git_repository::Repository::open()?;
+    let stupid = repo.stupid();
+    let source_stack = Stack::from_branch(
+        &repo,
+        argset::get_one_str(matches, "ref-branch"),
+        InitializationPolicy::AllowUninitialized,
+    )?;
+    let target_stack = Stack::from_branch(
+        &repo,
+        argset::get_one_str(matches, "branch"),
+        InitializationPolicy::AutoInitialize,
+    )?;
+    let source_branchname = source_stack.get_branch_name().to_string();
+    let target_branchname = target_stack.get_branch_name().to_string();
+
+    if source_branchname == target_branchname {
+        return Err(anyhow!(
+            "cannot backport patches onto their own branch `{target_branchname}`"
+        ));
+    }
+
+    let is_current = repo.get_branch(None)?.get_branch_name()? == target_branchname;
+    let noapply = matches.get_flag("noapply");
+
+    if is_current && !noapply {
+        repo.check_repository_state()?;
+        let statuses = stupid.statuses(None)?;
+        statuses.check_conflicts()?;
+        statuses.check_index_and_worktree_clean()?;
+    }
+    target_stack.check_head_top_mismatch()?;
+
+    let patchnames = patchrange::patches_from_specs(
+        matches
+            .get_many::<patchrange::Specification>("patchranges")
+            .expect("clap will ensure at least one patch is provided"),
+        &source_stack,
+        patchrange::Allow::VisibleWithAppliedBoundary,
+    )?;
+
+    let commit_patch_id = |commit: &git_repository::Commit| -> Result<_> {
+        let diff = stupid.diff_tree_patch(
+            commit.get_parent_commit()?.tree_id()?.detach(),
+            commit.tree_id()?.detach(),
+            <Option<Vec<&str>>>::None,
+            false,
+            <Vec<&str>>::new(),
+        )?;
+        stupid.patch_id(&diff)
+    };
+
+    // Patch ids of the changes already present on the target branch.
+    let mut present_patch_ids = HashSet::new();
+    for patchname in target_stack.all_patches() {
+        if let Some(patch_id) = commit_patch_id(target_stack.get_patch_commit(patchname)?)? {
+            present_patch_ids.insert(patch_id);
+        }
+    }
+    for commit_id in stupid.rev_list(
+        source_stack.base().id,
+        target_stack.base().id,
+        <Option<Vec<&str>>>::None,
+    )? {
+        let commit = repo.find_commit(commit_id)?;
+        if commit.parent_ids().count() == 1 {
+            if let Some(patch_id) = commit_patch_id(&commit)? {
+                present_patch_ids.insert(patch_id);
+            }
+        }
+    }
+
+    let default_committer = repo.get_committer()?;
+    let mut disallow: Vec<PatchName> = target_stack.all_patches().cloned().collect();
+    let mut new_patches: Vec<(PatchName, git_repository::ObjectId)> = Vec::new();
+
+    for patchname in &patchnames {
+        let commit = source_stack.get_patch_commit(patchname)?;
+        if let Some(patch_id) = commit_patch_id(commit)? {
+            if present_patch_ids.contains(&patch_id) {
+                print_info_message(
+                    matches,
+                    &format!("skipping `{patchname}`; already present on `{target_branchname}`"),
+                );
+                continue;
+            }
+        }
+
+        let new_patchname = patchname.clone().uniquify(&[], &disallow);
+        let message = format!(
+            "{}\n\n(cherry picked from commit {})\n",
+            commit.message_ex().decode()?.trim_end(),
+            commit.id
+        );
+        let author = commit.author_strict()?;
+        let committer = if matches.get_flag("committer-date-is-author-date") {
+            let mut committer = default_committer.to_owned();
+            committer.time = author.time;
+            committer
+        } else {
+            default_committer.to_owned()
+        };
+        let new_commit_id = repo.commit_ex(
+            &author,
+            &committer,
+            &Message::from(message),
+            commit.tree_id()?.detach(),
+            commit.parent_ids().map(|id| id.detach()),
+        )?;
+
+        let mut patch_meta = meta::PatchMeta::default();
+        patch_meta.set(
+            meta::BACKPORT_OF,
+            &format!("{source_branchname}:{patchname}"),
+        );
+        patch_meta.write(&repo, new_commit_id)?;
+
+        disallow.push(new_patchname.clone());
+        new_patches.push((new_patchname, new_commit_id));
+    }
+
+    if new_patches.is_empty() {
+        print_info_message(matches, "no patches to backport");
+        return Ok(());
+    }
+
+    // Determine which patches apply cleanly, in turn, onto the target stack so that
+    // patches that do not apply may be left unapplied.
+    let (to_push, conflicting) = if noapply {
+        (Vec::new(), Vec::new())
+    } else {
+        stupid.with_temp_index(|stupid_temp| {
+            let mut tree_id = target_stack.top()?.tree_id()?.detach();
+            stupid_temp.read_tree(tree_id)?;
+            let mut to_push: Vec<PatchName> = Vec::new();
+            let mut conflicting: Vec<PatchName> = Vec::new();
+            for (patchname, commit_id) in &new_patches {
+                let commit = repo.find_commit(*commit_id)?;
+                if stupid_temp.apply_treediff_to_index(
+                    commit.get_parent_commit()?.tree_id()?.detach(),
+                    commit.tree_id()?.detach(),
+                    true,
+                )? {
+                    tree_id = stupid_temp.write_tree()?;
+                    to_push.push(patchname.clone());
+                } else {
+                    stupid_temp.read_tree(tree_id)?;
+                    conflicting.push(patchname.clone());
+                }
+            }
+            Ok((to_push, conflicting))
+        })?
+    };
+
+    target_stack
+        .setup_transaction()
+        .use_index_and_worktree(is_current && !noapply)
+        .committer_date_is_author_date(matches.get_flag("committer-date-is-author-date"))
+        .with_output_stream(get_color_stdout(matches))
+        .transact(|trans| {
+            for (i, (patchname, commit_id)) in new_patches.iter().enumerate() {
+                trans.new_unapplied(patchname, *commit_id, i)?;
+            }
+            trans.push_patches(&to_push, false)
+        })
+        .execute(&format!("backport from {source_branchname}"))?;
+
+    for patchname in &conflicting {
+        print_warning_message(
+            matches,
+            &format!(
+                "`{patchname}` does not apply cleanly onto `{target_branchname}`; \
+                 left unapplied"
+            ),
+        );
+    }
+
+    Ok(())
+}
@@ -2,9 +2,9 @@
 
 //! `stg branch` implementation.
 
-use std::io::Write;
+use std::{io::Write, path::PathBuf};
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use bstr::ByteSlice;
 use clap::{Arg, ArgMatches};
 use termcolor::WriteColor;
@@ -14,7 +14,8 @@ use crate::{
     ext::RepositoryExtended,
     print_info_message,
     stack::{
-        state_refname_from_branch_name, InitializationPolicy, Stack, StackAccess, StackStateAccess,
+        patch_ref_prefix_from_branch_name, state_refname_from_branch_name, InitializationPolicy,
+        Stack, StackAccess, StackStateAccess,
     },
     stupid::Stupid,
     wrap::Branch,
@@ -47,7 +48,7 @@ fn make() -> clap::Command {
              \n       stg branch {--rename,-r} [old-name] <new-name>\
              \n       stg branch {--protect,-p} [branch]\
              \n       stg branch {--unprotect,-u} [branch]\
-             \n       stg branch --delete [--force] <branch>\
+             \n       stg branch --delete [--force | --force-backup] <branch>\
              \n       stg branch --cleanup [--force] [branch]\
              \n       stg branch {--describe,-d} <description> [branch]",
         )
@@ -113,6 +114,14 @@ fn make() -> clap::Command {
                 .short_flag('r')
                 .override_usage("stg branch {--rename,-r} [old-name] <new-name>")
                 .about("Rename an existing branch")
+                .long_about(
+                    "Rename an existing branch along with its StGit stack state, patch \
+                     references, and StGit configuration.\n\
+                     \n\
+                     If the branch was already renamed outside of StGit, e.g. with `git \
+                     branch -m`, the stack state left behind for <old-name> is migrated \
+                     to the existing <new-name> branch.",
+                )
                 .arg(
                     Arg::new("branch-any")
                         .help("Optional name of branch to rename and new branch name")
@@ -148,13 +157,19 @@ fn make() -> clap::Command {
         )
         .subcommand(
             clap::Command::new("--delete")
-                .override_usage("stg branch --delete [--force] <branch>")
+                .override_usage("stg branch --delete [--force | --force-backup] <branch>")
                 .about("Delete a branch")
                 .long_about(
                     "Delete a branch.\n\
                      \n\
                      The branch will not be deleted if there are any patches remaining \
-                     unless the '--force' option is provided.\n\
+                     unless the '--force' or '--force-backup' option is provided.\n\
+                     \n\
+                     With '--force-backup', the branch and its stack are first written \
+                     to a stack bundle file in the directory given by the \
+                     `stgit.branch.backup-dir` configuration variable, which defaults to \
+                     \"stgit-backups\" in the repository's git directory. The command to \
+                     restore the deleted branch with `stg bundle apply` is printed.\n\
                      \n\
                      A protected branch may not be deleted; it must be unprotected first.",
                 )
@@ -170,6 +185,12 @@ fn make() -> clap::Command {
                         .long("force")
                         .help("Force deletion even if branch has patches")
                         .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("force-backup")
+                        .long("force-backup")
+                        .help("Force deletion after writing a backup bundle of the stack")
+                        .action(clap::ArgAction::SetTrue),
                 ),
         )
         .subcommand(
@@ -469,10 +490,8 @@ fn create(repo: &git_repository::Repository, matches: &ArgMatches) -> Result<()>
             );
             None
         }
-    } else if let Ok(current_branch) = repo.get_branch(None) {
-        Some(current_branch)
     } else {
-        None
+        repo.get_branch(None).ok()
     };
 
     let (target_commit, target_name) = if let Some(parent_branch) = parent_branch.as_ref() {
@@ -622,8 +641,9 @@ fn clone(repo: &git_repository::Repository, matches: &ArgMatches) -> Result<()>
                 new: git_repository::refs::Target::Peeled(state_commit.id),
             },
             name: git_repository::refs::FullName::try_from(state_refname_from_branch_name(
+                repo,
                 &new_branchname,
-            ))?,
+            )?)?,
             deref: false,
         })?;
         stupid.branch_copy(None, &new_branchname)?;
@@ -656,13 +676,26 @@ fn rename(repo: &git_repository::Repository, matches: &ArgMatches) -> Result<()>
         .collect();
     let current_branch;
     let (old_branchname, new_branchname) = if names.len() == 2 {
-        repo.get_branch(Some(names[0]))?;
+        if let Err(e) = repo.get_branch(Some(names[0])) {
+            let old_state_refname = state_refname_from_branch_name(repo, names[0])?;
+            return if repo.find_reference(&old_state_refname).is_ok() {
+                migrate_orphaned_stack(repo, names[0], names[1])
+            } else {
+                Err(e)
+            };
+        }
         (names[0], names[1])
     } else {
         current_branch = repo.get_branch(None)?;
         (current_branch.get_branch_name()?, names[0])
     };
 
+    // Check before modifying any stack state so that a failed rename does not leave
+    // stack state half-migrated.
+    if repo.get_branch(Some(new_branchname)).is_ok() {
+        return Err(anyhow!("branch `{new_branchname}` already exists"));
+    }
+
     let stupid = repo.stupid();
     let parent_branchname = get_stgit_parent(&repo.config_snapshot(), old_branchname);
 
@@ -688,8 +721,9 @@ fn rename(repo: &git_repository::Repository, matches: &ArgMatches) -> Result<()>
                 new: git_repository::refs::Target::Peeled(state_commit.id),
             },
             name: git_repository::refs::FullName::try_from(state_refname_from_branch_name(
+                repo,
                 new_branchname,
-            ))?,
+            )?)?,
             deref: false,
         })?;
         stupid
@@ -707,6 +741,70 @@ fn rename(repo: &git_repository::Repository, matches: &ArgMatches) -> Result<()>
     Ok(())
 }
 
+/// Migrate stack state left behind by a branch renamed outside of StGit.
+fn migrate_orphaned_stack(
+    repo: &git_repository::Repository,
+    old_branchname: &str,
+    new_branchname: &str,
+) -> Result<()> {
+    repo.get_branch(Some(new_branchname))?;
+    let new_state_refname = state_refname_from_branch_name(repo, new_branchname)?;
+    if repo.find_reference(&new_state_refname).is_ok() {
+        return Err(anyhow!(
+            "StGit stack already initialized for branch `{new_branchname}`"
+        ));
+    }
+
+    let old_state_ref =
+        repo.find_reference(&state_refname_from_branch_name(repo, old_branchname)?)?;
+    let state_commit_id = old_state_ref.id().detach();
+    repo.edit_reference(git_repository::refs::transaction::RefEdit {
+        change: git_repository::refs::transaction::Change::Update {
+            log: git_repository::refs::transaction::LogChange {
+                mode: git_repository::refs::transaction::RefLog::AndReference,
+                force_create_reflog: false,
+                message: format!("rename {old_branchname} to {new_branchname}").into(),
+            },
+            expected: git_repository::refs::transaction::PreviousValue::MustNotExist,
+            new: git_repository::refs::Target::Peeled(state_commit_id),
+        },
+        name: git_repository::refs::FullName::try_from(new_state_refname)?,
+        deref: false,
+    })?;
+
+    let old_patch_ref_prefix = patch_ref_prefix_from_branch_name(repo, old_branchname)?;
+    for patch_reference in repo
+        .references()?
+        .all()?
+        .filter_map(Result::ok)
+        .filter(|reference| {
+            reference
+                .name()
+                .as_bstr()
+                .starts_with(old_patch_ref_prefix.as_bytes())
+        })
+    {
+        patch_reference.delete()?;
+    }
+    old_state_ref.delete()?;
+
+    repo.stupid()
+        .config_rename_section(
+            &format!("branch.{old_branchname}.stgit"),
+            &format!("branch.{new_branchname}.stgit"),
+        )
+        .ok();
+
+    // Loading the migrated stack creates its patch references.
+    Stack::from_branch(
+        repo,
+        Some(new_branchname),
+        InitializationPolicy::RequireInitialized,
+    )?;
+
+    Ok(())
+}
+
 fn protect(repo: &git_repository::Repository, matches: &ArgMatches) -> Result<()> {
     let stack = Stack::from_branch(
         repo,
@@ -736,6 +834,8 @@ fn delete(repo: &git_repository::Repository, matches: &ArgMatches) -> Result<()>
         return Err(anyhow!("cannot delete the current branch"));
     }
 
+    let force_backup = matches.get_flag("force-backup");
+    let mut backup_path = None;
     if let Ok(stack) = Stack::from_branch(
         repo,
         Some(target_branchname),
@@ -743,18 +843,50 @@ fn delete(repo: &git_repository::Repository, matches: &ArgMatches) -> Result<()>
     ) {
         if stack.is_protected(&repo.config_snapshot()) {
             return Err(anyhow!("delete not permitted: this branch is protected"));
-        } else if !matches.get_flag("force") && stack.all_patches().count() > 0 {
+        } else if !matches.get_flag("force") && !force_backup && stack.all_patches().count() > 0 {
             return Err(anyhow!(
                 "delete not permitted: the series still contains patches (override with --force)"
             ));
         }
+        if force_backup {
+            backup_path = Some(write_backup_bundle(repo, &stack)?);
+        }
         stack.deinitialize()?;
     }
 
     target_branch.delete()?;
+
+    if let Some(path) = backup_path {
+        let path = path.to_string_lossy();
+        print_info_message(
+            matches,
+            &format!(
+                "Backed up `{target_branchname}` to `{path}`; \
+                 restore with `stg bundle apply {path}`"
+            ),
+        );
+    }
     Ok(())
 }
 
+/// Write a stack bundle of the stack to the backup directory, returning its path.
+fn write_backup_bundle(repo: &git_repository::Repository, stack: &Stack) -> Result<PathBuf> {
+    let git_dir = repo.git_dir().canonicalize()?;
+    let backup_dir = repo
+        .config_snapshot()
+        .trusted_path("stgit.branch.backup-dir")
+        .transpose()?
+        .map_or_else(|| git_dir.join("stgit-backups"), |path| git_dir.join(path));
+    std::fs::create_dir_all(&backup_dir).with_context(|| format!("creating {backup_dir:?}"))?;
+    let suffix = chrono::Local::now().format("%Y%m%d-%H%M%S");
+    let path = backup_dir.join(format!(
+        "{}-{suffix}.bundle",
+        stack.get_branch_name().replace('/', "-")
+    ));
+    super::bundle::write_stack_bundle(stack, &path)?;
+    Ok(path)
+}
+
 fn cleanup(repo: &git_repository::Repository, matches: &ArgMatches) -> Result<()> {
     let stack = Stack::from_branch(
         repo,
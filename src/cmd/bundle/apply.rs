@@ -0,0 +1,133 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg bundle apply` implementation.
+
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use clap::Arg;
+
+use crate::{
+    ext::RepositoryExtended,
+    print_info_message,
+    stack::{
+        state_refname_from_branch_name, InitializationPolicy, Stack, StackState, StackStateAccess,
+    },
+    stupid::Stupid,
+};
+
+pub(super) fn command() -> clap::Command {
+    clap::Command::new("apply")
+        .about("Reconstruct a stack from a bundle file")
+        .long_about(
+            "Reconstruct a stack from a bundle file written by `stg bundle create`.\n\
+             \n\
+             The stack is reconstructed as a new branch, which by default has the \
+             same name as the bundled branch. The new branch's patches and stack \
+             history are the same as those of the bundled stack. The current branch \
+             is not changed.\n\
+             \n\
+             The repository must contain the commits preceding the bundled stack's \
+             base commit.",
+        )
+        .arg(
+            Arg::new("file")
+                .help("Bundle file to apply")
+                .required(true)
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(clap::ValueHint::FilePath),
+        )
+        .arg(
+            Arg::new("new-branch")
+                .help("Name of the branch to create")
+                .value_name("branch"),
+        )
+}
+
+pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
+    let repo = git_repository::Repository::open()?;
+    let path = matches
+        .get_one::<PathBuf>("file")
+        .expect("required argument");
+    let stupid = repo.stupid();
+
+    let mut branch_head = None;
+    let mut stack_head = None;
+    for (oid, refname) in stupid.bundle_list_heads(path)? {
+        if let Some(branchname) = refname.strip_prefix("refs/heads/") {
+            if branch_head.replace((oid, branchname.to_string())).is_some() {
+                branch_head = None;
+                break;
+            }
+        } else if (refname.starts_with("refs/stacks/") || refname.starts_with("refs/stacks-ns/"))
+            && stack_head.replace(oid).is_some()
+        {
+            stack_head = None;
+            break;
+        }
+    }
+    let ((branch_id, bundled_branchname), state_id) =
+        if let (Some(branch_head), Some(stack_head)) = (branch_head, stack_head) {
+            (branch_head, stack_head)
+        } else {
+            return Err(anyhow!(
+                "`{}` is not a StGit stack bundle",
+                path.to_string_lossy()
+            ));
+        };
+
+    let new_branchname = matches
+        .get_one::<String>("new-branch")
+        .cloned()
+        .unwrap_or(bundled_branchname);
+    let new_fullname =
+        git_repository::refs::FullName::try_from(format!("refs/heads/{new_branchname}"))?;
+    if repo.try_find_reference(&new_fullname)?.is_some() {
+        return Err(anyhow!("branch `{new_branchname}` already exists"));
+    }
+
+    stupid.bundle_unbundle(path)?;
+    StackState::from_commit(&repo, &repo.find_commit(state_id)?)?;
+
+    let reflog_msg = format!("bundle apply from {}", path.to_string_lossy());
+    for (name, id) in [
+        (new_fullname, branch_id),
+        (
+            git_repository::refs::FullName::try_from(state_refname_from_branch_name(
+                &repo,
+                &new_branchname,
+            )?)?,
+            state_id,
+        ),
+    ] {
+        repo.edit_reference(git_repository::refs::transaction::RefEdit {
+            change: git_repository::refs::transaction::Change::Update {
+                log: git_repository::refs::transaction::LogChange {
+                    mode: git_repository::refs::transaction::RefLog::AndReference,
+                    force_create_reflog: false,
+                    message: reflog_msg.as_str().into(),
+                },
+                expected: git_repository::refs::transaction::PreviousValue::MustNotExist,
+                new: git_repository::refs::Target::Peeled(id),
+            },
+            name,
+            deref: false,
+        })?;
+    }
+
+    let stack = Stack::from_branch(
+        &repo,
+        Some(&new_branchname),
+        InitializationPolicy::RequireInitialized,
+    )?;
+    let num_patches = stack.all_patches().count();
+    print_info_message(
+        matches,
+        &format!(
+            "Created branch `{new_branchname}` with {num_patches} patch{}",
+            if num_patches == 1 { "" } else { "es" }
+        ),
+    );
+
+    Ok(())
+}
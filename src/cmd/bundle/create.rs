@@ -0,0 +1,61 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg bundle create` implementation.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use clap::Arg;
+
+use crate::{
+    argset,
+    ext::RepositoryExtended,
+    stack::{InitializationPolicy, Stack, StackAccess},
+    stupid::Stupid,
+};
+
+pub(super) fn command() -> clap::Command {
+    clap::Command::new("create")
+        .about("Write a stack to a bundle file")
+        .long_about(
+            "Write the stack of the current or specified branch to a bundle file.\n\
+             \n\
+             The bundle contains the branch, the stack state reference, and all \
+             commits needed to reconstruct the stack and its history. Commits \
+             preceding the stack's base commit are not included; the repository the \
+             bundle is applied to must already contain them.",
+        )
+        .arg(argset::branch_arg())
+        .arg(
+            Arg::new("file")
+                .help("Bundle file to write")
+                .required(true)
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(clap::ValueHint::FilePath),
+        )
+}
+
+pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
+    let repo = git_repository::Repository::open()?;
+    let stack = Stack::from_branch(
+        &repo,
+        argset::get_one_str(matches, "branch"),
+        InitializationPolicy::RequireInitialized,
+    )?;
+    let path = matches
+        .get_one::<PathBuf>("file")
+        .expect("required argument");
+    write_stack_bundle(&stack, path)
+}
+
+/// Write a stack's branch, stack state, and the commits they need to a bundle file.
+pub(crate) fn write_stack_bundle(stack: &Stack, path: &Path) -> Result<()> {
+    let branch_refname = stack.get_branch_refname().as_bstr().to_string();
+    let exclude_revs: Vec<String> = stack.base().parent_ids().map(|id| id.to_string()).collect();
+
+    stack.repo.stupid().bundle_create(
+        path,
+        &[branch_refname.as_str(), stack.get_stack_refname()],
+        exclude_revs,
+    )
+}
@@ -0,0 +1,46 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg bundle` implementation.
+
+mod apply;
+mod create;
+
+pub(super) use create::write_stack_bundle;
+
+use anyhow::Result;
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "bundle",
+    category: super::CommandCategory::Administration,
+    make,
+    run,
+};
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Share stacks using git bundle files")
+        .long_about(
+            "Share entire stacks, including their metadata, using git bundle files.\n\
+             \n\
+             A stack bundle contains the stack's branch, its stack state, all of its \
+             patches, and the stack's history, as would be shown by `stg log`. Such a \
+             bundle may be transferred by any means, e.g. by email or removable media, \
+             and then applied in another clone of the repository to reconstruct the \
+             stack there.\n\
+             \n\
+             The `create` subcommand writes a stack bundle for a branch and the \
+             `apply` subcommand reconstructs the stack from a bundle as a new branch. \
+             Refer to git-bundle(1) for more details about bundle files.",
+        )
+        .subcommand_required(true)
+        .subcommand(apply::command())
+        .subcommand(create::command())
+}
+
+fn run(matches: &clap::ArgMatches) -> Result<()> {
+    match matches.subcommand() {
+        Some(("apply", sub_matches)) => apply::dispatch(sub_matches),
+        Some(("create", sub_matches)) => create::dispatch(sub_matches),
+        _ => panic!("valid subcommand is expected"),
+    }
+}
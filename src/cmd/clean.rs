@@ -61,7 +61,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
     if clean_applied {
         let applied = stack.applied();
         for (i, pn) in applied.iter().enumerate() {
-            if stack.get_patch_commit(pn).is_no_change()? {
+            if stack.get_patch_commit(pn)?.is_no_change()? {
                 if i + 1 == applied.len() {
                     // Do not clean the topmost patch if there are outstanding
                     // conflicts. The patch is only empty because the conflicts caused
@@ -78,7 +78,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
 
     if clean_unapplied {
         for pn in stack.unapplied() {
-            if stack.get_patch_commit(pn).is_no_change()? {
+            if stack.get_patch_commit(pn)?.is_no_change()? {
                 to_delete.push(pn.clone());
             }
         }
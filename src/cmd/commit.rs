@@ -3,13 +3,16 @@
 //! `stg commit` implementation.
 
 use anyhow::{anyhow, Result};
+use bstr::ByteSlice;
 use clap::{Arg, ArgMatches};
 
 use crate::{
     color::get_color_stdout,
     ext::{CommitExtended, RepositoryExtended},
     patch::{patchrange, PatchName},
-    stack::{Error, InitializationPolicy, Stack, StackStateAccess},
+    stack::{Error, InitializationPolicy, Stack, StackAccess, StackStateAccess},
+    stupid::Stupid,
+    wrap::Message,
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
@@ -34,7 +37,26 @@ fn make() -> clap::Command {
              \n\
              The -n/--number option specifies the number of applied patches to \
              commit (counting from the bottom of the stack). If -a/--all is given, \
-             all applied patches are committed.",
+             all applied patches are committed.\n\
+             \n\
+             With --annotate, an annotated tag is created pointing at the last \
+             committed patch, i.e. the new stack base. The tag's message records the \
+             names and subjects of the committed patches. The tag name may be given \
+             as the option's value or is otherwise made from the \
+             `stgit.commit.tag-template` configuration value, in which the following \
+             placeholders are expanded: '%b' the branch name, '%p' the name of the \
+             last committed patch, '%n' the number of committed patches, '%d' the \
+             current date as YYYY-MM-DD, and '%%' a literal `%`. The default template \
+             is `%b-%d`.\n\
+             \n\
+             With --squash, the committed patches are replaced by a single commit on \
+             the stack base with the same tree as the last committed patch. With \
+             --merge, a merge commit of the stack base and the last committed patch is \
+             made instead, preserving the committed patches on the merged side of the \
+             history. These options suit projects whose mainline policy is to \
+             squash-merge or merge topic branches, while the stack continues to be \
+             used for development. The commit's message may be given with --message; \
+             by default it lists the names and subjects of the committed patches.",
         )
         .override_usage(
             "stg commit [OPTIONS] [patch]...\n       \
@@ -71,6 +93,48 @@ fn make() -> clap::Command {
                 .help("Allow empty patches to be committed")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("squash")
+                .long("squash")
+                .help("Commit the patches as a single squashed commit")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("merge"),
+        )
+        .arg(
+            Arg::new("merge")
+                .long("merge")
+                .help("Commit the patches with a merge commit")
+                .long_help(
+                    "Commit the patches with a merge commit whose parents are the \
+                     stack base and the last committed patch.",
+                )
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("message")
+                .long("message")
+                .short('m')
+                .help("Use <message> for the squashed or merge commit")
+                .value_name("message")
+                .value_parser(clap::builder::NonEmptyStringValueParser::new())
+                .requires("squash-or-merge"),
+        )
+        .group(clap::ArgGroup::new("squash-or-merge").args(["squash", "merge"]))
+        .arg(
+            Arg::new("annotate")
+                .long("annotate")
+                .help("Create an annotated tag for the committed patches")
+                .long_help(
+                    "Create an annotated tag named <tag> pointing at the last committed \
+                     patch and recording the committed patches' names. When <tag> is \
+                     not given, the name is made from the `stgit.commit.tag-template` \
+                     configuration value.",
+                )
+                .value_name("tag")
+                .num_args(0..=1)
+                .default_missing_value("")
+                .require_equals(true),
+        )
 }
 
 fn run(matches: &ArgMatches) -> Result<()> {
@@ -113,7 +177,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
     if !matches.get_flag("allow-empty") {
         let mut empty_patches: Vec<&PatchName> = Vec::new();
         for pn in &patches {
-            let patch_commit = stack.get_patch_commit(pn);
+            let patch_commit = stack.get_patch_commit(pn)?;
             let parent = patch_commit.get_parent_commit()?;
             if patch_commit.tree_id()? == parent.tree_id()? {
                 empty_patches.push(pn);
@@ -132,15 +196,147 @@ fn run(matches: &ArgMatches) -> Result<()> {
         }
     }
 
+    let squash_message = if matches.get_flag("squash") || matches.get_flag("merge") {
+        Some(
+            if let Some(message) = matches.get_one::<String>("message") {
+                message.clone()
+            } else {
+                let verb = if matches.get_flag("merge") {
+                    "Merge"
+                } else {
+                    "Squash"
+                };
+                summarize_patches(&stack, &patches, verb)?
+            },
+        )
+    } else {
+        None
+    };
+
+    let tag = if let Some(tag) = matches.get_one::<String>("annotate") {
+        let tag = if tag.is_empty() {
+            let config = repo.config_snapshot();
+            let template = config
+                .string("stgit.commit.tag-template")
+                .map(|template| template.to_str_lossy().to_string())
+                .unwrap_or_else(|| "%b-%d".to_string());
+            let date = repo
+                .get_committer()?
+                .time
+                .format(git_repository::date::time::format::ISO8601);
+            expand_tag_template(
+                &template,
+                stack.get_branch_name(),
+                patches.last().expect("patches to commit is not empty"),
+                patches.len(),
+                &date[..10],
+            )?
+        } else {
+            tag.clone()
+        };
+        let refname = format!("refs/tags/{tag}");
+        if git_repository::refs::FullName::try_from(refname.as_str()).is_err() {
+            return Err(anyhow!("invalid tag name `{tag}`"));
+        }
+        if repo.try_find_reference(refname.as_str())?.is_some() {
+            return Err(anyhow!("tag `{tag}` already exists"));
+        }
+        Some((tag, summarize_patches(&stack, &patches, "Commit")?))
+    } else {
+        None
+    };
+
     stack.check_head_top_mismatch()?;
 
+    let base_id = stack.base().id;
+    let merge_flag = matches.get_flag("merge");
     stack
         .setup_transaction()
         .use_index_and_worktree(true)
         .allow_conflicts_if_same_top(true)
         .with_output_stream(get_color_stdout(matches))
-        .transact(|trans| trans.commit_patches(&patches))
+        .transact(|trans| {
+            if let Some(message) = squash_message.as_ref() {
+                trans.commit_patches_squashed(&patches, |top_commit| {
+                    let parents = if merge_flag {
+                        vec![base_id, top_commit.id]
+                    } else {
+                        vec![base_id]
+                    };
+                    repo.commit_ex(
+                        repo.get_author()?,
+                        repo.get_committer()?,
+                        &Message::from(message.as_str()),
+                        top_commit.tree_id()?.detach(),
+                        parents,
+                    )
+                })
+            } else {
+                trans.commit_patches(&patches)
+            }
+        })
         .execute("commit")?;
 
+    if let Some((tag, message)) = tag {
+        // The last committed patch is the new stack base.
+        let stack = Stack::from_branch(&repo, None, InitializationPolicy::AllowUninitialized)?;
+        repo.stupid()
+            .tag_annotated(&tag, stack.base().id, message.as_bytes())?;
+    }
+
     Ok(())
 }
+
+/// Make a message listing the names and subjects of the given patches.
+fn summarize_patches(stack: &Stack, patches: &[PatchName], verb: &str) -> Result<String> {
+    let mut message = format!(
+        "{verb} {} patch{} from {}\n\n",
+        patches.len(),
+        if patches.len() == 1 { "" } else { "es" },
+        stack.get_branch_name(),
+    );
+    for pn in patches {
+        let commit = stack.get_patch_commit(pn)?;
+        let commit_message = commit.message_ex();
+        let message_str = commit_message.decode()?;
+        let subject = message_str.lines().next().unwrap_or_default().trim();
+        message.push_str(&format!("{pn}: {subject}\n"));
+    }
+    Ok(message)
+}
+
+/// Expand the placeholders of the `stgit.commit.tag-template` config value.
+fn expand_tag_template(
+    template: &str,
+    branch_name: &str,
+    last_patchname: &PatchName,
+    num_patches: usize,
+    date: &str,
+) -> Result<String> {
+    let mut tag = String::with_capacity(template.len());
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c == '%' {
+            match chars.next() {
+                Some('b') => tag.push_str(branch_name),
+                Some('p') => tag.push_str(last_patchname.as_ref()),
+                Some('n') => tag.push_str(&num_patches.to_string()),
+                Some('d') => tag.push_str(date),
+                Some('%') => tag.push('%'),
+                Some(c) => {
+                    return Err(anyhow!(
+                        "unknown placeholder `%{c}` in `stgit.commit.tag-template`"
+                    ))
+                }
+                None => {
+                    return Err(anyhow!(
+                        "incomplete placeholder in `stgit.commit.tag-template`"
+                    ))
+                }
+            }
+        } else {
+            tag.push(c);
+        }
+    }
+    Ok(tag)
+}
@@ -32,7 +32,10 @@ pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
     let mut stg = crate::get_full_command(&crate::alias::Aliases::new(), None);
     stg.build();
 
-    for command in stg.get_subcommands() {
+    for command in stg
+        .get_subcommands()
+        .filter(|command| !command.is_hide_set())
+    {
         write_command_func(&mut script, &f!("_stg-{}", command.get_name()), command);
     }
 
@@ -227,11 +230,6 @@ fn insert_compreply(script: &mut ShStream, arg: &clap::Arg) {
                 script
                     .line("mapfile -t COMPREPLY < <(compgen -W \"$(_git_diff_opts)\" -- \"$cur\")");
             }
-            "git-format-patch-opt" => {
-                script.line(
-                "mapfile -t COMPREPLY < <(compgen -W \"$(_git_format_patch_opts)\" -- \"$cur\")",
-            );
-            }
             "git-send-email-opt" => {
                 script.line(
                     "mapfile -t COMPREPLY < <(compgen -W \"$(_git_send_email_opts)\" -- \"$cur\")",
@@ -607,11 +605,6 @@ _git_diff_opts ()
     __git diff-tree --git-completion-helper
 }
 
-_git_format_patch_opts ()
-{
-    __git format-patch --git-completion-helper
-}
-
 _git_send_email_opts()
 {
     __git send-email --git-completion-helper
@@ -32,7 +32,10 @@ pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
     let mut stg = crate::get_full_command(&crate::alias::Aliases::new(), None);
     stg.build();
 
-    for command in stg.get_subcommands() {
+    for command in stg
+        .get_subcommands()
+        .filter(|command| !command.is_hide_set())
+    {
         write_command_completions(&mut script, command, None);
     }
 
@@ -180,12 +183,6 @@ function __fish_stg_git_diff_opts
     | string match --regex '\--.+'
 end
 
-function __fish_stg_git_format_patch_opts
-    __fish_stg_git format-patch --git-completion-helper \
-    | string split ' ' \
-    | string match --regex '\--.+'
-end
-
 function __fish_stg_git_send_email_opts
     __fish_stg_git send-email --git-completion-helper \
     | string split ' ' \
@@ -364,7 +361,6 @@ fn get_arg_completion_params(arg: &clap::Arg) -> ShStream {
             "branch-any" => params.word("-xa '(__fish_stg_all_branches)'"),
             "committish" => params.word("-xa '(__fish_stg_commit)'"),
             "git-diff-opt" => params.word("-xa '(__fish_stg_git_diff_opts)'"),
-            "git-format-patch-opt" => params.word("-xa '(__fish_stg_git_format_patch_opts)'"),
             "git-send-email-opt" => params.word("-xa '(__fish_stg_git_send_email_opts)'"),
             "patch" | "patchranges" => params.word("-kxa '(__fish_stg_patches -A -U)'"),
             "patchranges-all" | "set-tree" | "stgit-revision" => {
@@ -125,7 +125,10 @@ fn list_commands(output: &mut Box<dyn std::io::Write>, style: OutputStyle) -> Re
 
     if matches!(style, OutputStyle::AsciiDoc) {
         let mut subcmd_cats: Vec<(CommandCategory, &clap::Command)> = Vec::new();
-        for cmd in stg_command.get_subcommands() {
+        for cmd in stg_command
+            .get_subcommands()
+            .filter(|cmd| !cmd.is_hide_set())
+        {
             let name = cmd.get_name();
             if let Some(stgit_cmd) = STGIT_COMMANDS.iter().find(|command| command.name == name) {
                 subcmd_cats.push((stgit_cmd.category, cmd));
@@ -190,7 +193,10 @@ fn list_commands(output: &mut Box<dyn std::io::Write>, style: OutputStyle) -> Re
         return Ok(());
     }
 
-    for cmd in stg_command.get_subcommands() {
+    for cmd in stg_command
+        .get_subcommands()
+        .filter(|cmd| !cmd.is_hide_set())
+    {
         let name = cmd.get_name();
         let about = cmd.get_about().unwrap_or_default();
         match style {
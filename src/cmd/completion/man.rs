@@ -47,7 +47,10 @@ pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
     let mut stg = crate::get_full_command(&crate::alias::Aliases::new(), None);
     stg.build();
 
-    for command in stg.get_subcommands_mut() {
+    for command in stg
+        .get_subcommands_mut()
+        .filter(|command| !command.is_hide_set())
+    {
         let asciidoc = generate_asciidoc(command);
         let path = output_dir.join(format!("stg-{}.txt", command.get_name()));
         if std::fs::read_to_string(&path).ok().as_ref() != Some(&asciidoc) {
@@ -301,39 +304,7 @@ fn add_options(
         if i == 0 {
             write_underlined(section, header_name, header_underline);
         }
-        let value_str = if arg.get_action().takes_values() {
-            if let Some(value_names) = arg.get_value_names() {
-                let mut value_str = String::new();
-                for (i, name) in value_names.iter().enumerate() {
-                    if i > 0 {
-                        value_str.push(' ');
-                    }
-                    value_str.push('<');
-                    value_str.push_str(name);
-                    value_str.push('>');
-                }
-                value_str
-            } else {
-                let possible_values = arg
-                    .get_value_parser()
-                    .possible_values()
-                    .expect("arg that takes value has either value names or possible values");
-                let mut value_str = String::new();
-                value_str.push('(');
-                for (i, possible_value) in
-                    possible_values.filter(|pv| !pv.is_hide_set()).enumerate()
-                {
-                    if i > 0 {
-                        value_str.push('|');
-                    }
-                    value_str.push_str(possible_value.get_name());
-                }
-                value_str.push(')');
-                value_str
-            }
-        } else {
-            String::new()
-        };
+        let value_str = get_value_str(arg);
         if let Some(shorts) = arg.get_short_and_visible_aliases() {
             for short in shorts {
                 if value_str.is_empty() {
@@ -387,9 +358,46 @@ fn add_options(
     }
 }
 
+/// Get the placeholder for an option's value, e.g. `<path>` or `(auto|never)`.
+///
+/// An empty string is returned for options that do not take a value.
+pub(super) fn get_value_str(arg: &clap::Arg) -> String {
+    if arg.get_action().takes_values() {
+        if let Some(value_names) = arg.get_value_names() {
+            let mut value_str = String::new();
+            for (i, name) in value_names.iter().enumerate() {
+                if i > 0 {
+                    value_str.push(' ');
+                }
+                value_str.push('<');
+                value_str.push_str(name);
+                value_str.push('>');
+            }
+            value_str
+        } else {
+            let possible_values = arg
+                .get_value_parser()
+                .possible_values()
+                .expect("arg that takes value has either value names or possible values");
+            let mut value_str = String::new();
+            value_str.push('(');
+            for (i, possible_value) in possible_values.filter(|pv| !pv.is_hide_set()).enumerate() {
+                if i > 0 {
+                    value_str.push('|');
+                }
+                value_str.push_str(possible_value.get_name());
+            }
+            value_str.push(')');
+            value_str
+        }
+    } else {
+        String::new()
+    }
+}
+
 fn make_links(text: &str) -> String {
     let mut output = String::new();
-    let mut words = text.split_inclusive(|c| c == ' ' || c == '\n');
+    let mut words = text.split_inclusive([' ', '\n']);
 
     while let Some(word) = words.next() {
         if let Some(remainder) = word.strip_prefix("git-") {
@@ -444,7 +452,7 @@ fn write_underlined(stream: &mut String, line: &str, underline_char: char) {
     stream.push('\n');
 }
 
-struct Paragraphs<'a> {
+pub(super) struct Paragraphs<'a> {
     text: &'a str,
 }
 
@@ -465,7 +473,7 @@ impl<'a> Iterator for Paragraphs<'a> {
     }
 }
 
-fn paragraphs(text: &str) -> Paragraphs {
+pub(super) fn paragraphs(text: &str) -> Paragraphs<'_> {
     Paragraphs { text }
 }
 
@@ -507,6 +515,6 @@ impl<'a> Iterator for WrappedLines<'a> {
     }
 }
 
-fn wrap(text: &str, width: usize) -> WrappedLines {
+fn wrap(text: &str, width: usize) -> WrappedLines<'_> {
     WrappedLines { text, width }
 }
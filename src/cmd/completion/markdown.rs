@@ -0,0 +1,166 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg completion markdown` implementation
+
+use std::{fmt::Write, path::PathBuf};
+
+use anyhow::Result;
+
+use super::man::{get_value_str, paragraphs};
+
+pub(super) fn command() -> clap::Command {
+    clap::Command::new("markdown")
+        .about("Generate markdown command reference")
+        .long_about(
+            "Generate a command reference document in markdown format. The document \
+             contains a section for each `stg` command and subcommand with its usage, \
+             description, and options, all derived from the same definitions used for \
+             `stg <command> --help`.",
+        )
+        .arg(
+            clap::Arg::new("output")
+                .long("output")
+                .short('o')
+                .help("Output markdown to <path>")
+                .value_name("path")
+                .value_hint(clap::ValueHint::FilePath)
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+}
+
+pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
+    let mut stream = super::get_output_stream(matches)?;
+
+    let mut stg = crate::get_full_command(&crate::alias::Aliases::new(), None);
+    stg.build();
+
+    let mut doc = String::new();
+    doc.push_str("# StGit Command Reference\n\n");
+    doc.push_str("<!-- This file is autogenerated by `stg completion markdown`. -->\n\n");
+
+    for command in stg
+        .get_subcommands()
+        .filter(|command| command.get_name() != "help" && !command.is_hide_set())
+    {
+        let name = command.get_name();
+        let about = escape_angle_brackets(&command.get_about().unwrap_or_default().to_string());
+        writeln!(doc, "- [stg {name}](#stg-{name}): {about}").unwrap();
+    }
+    doc.push('\n');
+
+    for command in stg
+        .get_subcommands_mut()
+        .filter(|command| command.get_name() != "help" && !command.is_hide_set())
+    {
+        write_command_section(&mut doc, command, "stg");
+    }
+
+    stream.write_all(doc.as_bytes())?;
+
+    Ok(())
+}
+
+fn write_command_section(doc: &mut String, command: &mut clap::Command, parent_name: &str) {
+    let full_name = format!("{parent_name} {}", command.get_name());
+    let usage = command.render_usage().to_string();
+    let usage = usage.strip_prefix("Usage: ").unwrap_or(&usage);
+
+    writeln!(doc, "## {full_name}\n").unwrap();
+    if let Some(about) = command.get_about() {
+        writeln!(doc, "{}\n", escape_angle_brackets(&about.to_string())).unwrap();
+    }
+
+    doc.push_str("```text\n");
+    for line in usage.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        doc.push_str(line);
+        doc.push('\n');
+    }
+    doc.push_str("```\n\n");
+
+    if let Some(long_about) = command.get_long_about() {
+        write_paragraphs(doc, &long_about.to_string(), "");
+    }
+
+    let mut has_options = false;
+    for arg in command.get_arguments().filter(|arg| {
+        !["help", "color"].contains(&arg.get_id().as_str())
+            && !arg.is_hide_set()
+            && !arg.is_positional()
+    }) {
+        if !has_options {
+            doc.push_str("### Options\n\n");
+            has_options = true;
+        }
+        let value_str = get_value_str(arg);
+        let mut flags = Vec::new();
+        if let Some(shorts) = arg.get_short_and_visible_aliases() {
+            for short in shorts {
+                flags.push(if value_str.is_empty() {
+                    format!("`-{short}`")
+                } else if arg.is_require_equals_set() {
+                    format!("`-{short}[={value_str}]`")
+                } else {
+                    format!("`-{short} {value_str}`")
+                });
+            }
+        }
+        if let Some(longs) = arg.get_long_and_visible_aliases() {
+            for long in longs {
+                flags.push(if value_str.is_empty() {
+                    format!("`--{long}`")
+                } else if arg.is_require_equals_set() {
+                    format!("`--{long}[={value_str}]`")
+                } else {
+                    format!("`--{long}={value_str}`")
+                });
+            }
+        }
+        writeln!(doc, "- {}\n", flags.join(", ")).unwrap();
+        if let Some(help) = arg.get_long_help().or_else(|| arg.get_help()) {
+            write_paragraphs(doc, &help.to_string(), "  ");
+        }
+    }
+
+    for subcommand in command
+        .get_subcommands_mut()
+        .filter(|subcommand| subcommand.get_name() != "help")
+    {
+        write_command_section(doc, subcommand, &full_name);
+    }
+}
+
+fn write_paragraphs(doc: &mut String, text: &str, indent: &str) {
+    for para in paragraphs(text.trim_end()) {
+        if para.starts_with(' ') {
+            writeln!(doc, "{indent}```text").unwrap();
+            for line in para.lines() {
+                writeln!(doc, "{indent}{line}").unwrap();
+            }
+            writeln!(doc, "{indent}```").unwrap();
+        } else {
+            for line in para.lines() {
+                writeln!(doc, "{indent}{}", escape_angle_brackets(line)).unwrap();
+            }
+        }
+        doc.push('\n');
+    }
+}
+
+/// Escape `<` and `>` outside of code spans so that placeholders such as `<dir>` are
+/// not interpreted as HTML tags.
+fn escape_angle_brackets(line: &str) -> String {
+    let mut escaped = String::with_capacity(line.len());
+    let mut in_code = false;
+    for c in line.chars() {
+        match c {
+            '`' => {
+                in_code = !in_code;
+                escaped.push(c);
+            }
+            '<' if !in_code => escaped.push_str("&lt;"),
+            '>' if !in_code => escaped.push_str("&gt;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
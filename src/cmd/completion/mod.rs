@@ -6,6 +6,9 @@ mod bash;
 mod fish;
 mod list;
 mod man;
+mod markdown;
+mod nushell;
+mod powershell;
 mod shstream;
 mod zsh;
 
@@ -24,16 +27,23 @@ fn make() -> clap::Command {
     clap::Command::new(STGIT_COMMAND.name)
         .about("Support for shell completions")
         .long_about(
-            "Support completions for bash, fish, and zsh. Also provides 'stg \
-             completion list' command for dynamically introspecting StGit's \
-             commands and aliases.",
+            "Support completions for bash, fish, nushell, powershell, and zsh. Also \
+             provides 'stg completion list' command for dynamically introspecting \
+             StGit's commands and aliases.\n\
+             \n\
+             The 'stg completion man' and 'stg completion markdown' commands generate \
+             documentation from the same command definitions used for the help \
+             output of each command.",
         )
         .subcommand_required(true)
         .subcommand(bash::command())
         .subcommand(fish::command())
+        .subcommand(nushell::command())
+        .subcommand(powershell::command())
         .subcommand(zsh::command())
         .subcommand(list::command())
         .subcommand(man::command())
+        .subcommand(markdown::command())
         .arg(
             clap::Arg::new("output")
                 .long("output")
@@ -50,14 +60,17 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
     match matches.subcommand() {
         Some(("bash", sub_matches)) => bash::dispatch(sub_matches),
         Some(("fish", sub_matches)) => fish::dispatch(sub_matches),
+        Some(("nushell", sub_matches)) => nushell::dispatch(sub_matches),
+        Some(("powershell", sub_matches)) => powershell::dispatch(sub_matches),
         Some(("zsh", sub_matches)) => zsh::dispatch(sub_matches),
         Some(("list", sub_matches)) => list::dispatch(sub_matches),
         Some(("man", sub_matches)) => man::dispatch(sub_matches),
+        Some(("markdown", sub_matches)) => markdown::dispatch(sub_matches),
         _ => panic!("valid subcommand is required"),
     }
 }
 
-pub(self) fn get_output_stream(matches: &clap::ArgMatches) -> Result<Box<dyn std::io::Write>> {
+fn get_output_stream(matches: &clap::ArgMatches) -> Result<Box<dyn std::io::Write>> {
     Ok(match matches.get_one::<PathBuf>("output") {
         Some(path) => Box::new(
             std::fs::OpenOptions::new()
@@ -0,0 +1,250 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg completion nushell` implementation
+
+use std::{format as f, path::PathBuf};
+
+use anyhow::Result;
+
+use super::shstream::ShStream;
+
+pub(super) fn command() -> clap::Command {
+    clap::Command::new("nushell")
+        .about("Generate nushell completion script")
+        .arg(
+            clap::Arg::new("output")
+                .long("output")
+                .short('o')
+                .help("Output completion script to <path>")
+                .value_name("path")
+                .value_hint(clap::ValueHint::FilePath)
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+}
+
+pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
+    let mut stream = super::get_output_stream(matches)?;
+
+    let mut completers = ShStream::new();
+    let mut externs = ShStream::new();
+
+    let mut stg = crate::get_full_command(&crate::alias::Aliases::new(), None);
+    stg.build();
+
+    for command in stg
+        .get_subcommands()
+        .filter(|command| command.get_name() != "help" && !command.is_hide_set())
+    {
+        write_command_extern(&mut completers, &mut externs, command, "stg");
+    }
+
+    let mut script = ShStream::new();
+    script.raw(HEADER);
+    script.raw(completers.as_ref());
+    script.ensure_blank_line();
+    script.raw(externs.as_ref());
+
+    stream.write_all(script.as_bytes())?;
+
+    Ok(())
+}
+
+const HEADER: &str = r#"# SPDX-License-Identifier: GPL-2.0-only
+#
+# Nushell completion for StGit (stg)
+#
+# To use, copy this file to a directory of your choice and source it from
+# your config.nu, e.g.:
+#
+#   source ~/.config/nushell/completions/stg.nu
+#
+# This file is autogenerated.
+
+def "nu-complete stg commands" [] {
+    ^stg completion list commands-and-aliases --style=fish
+    | lines
+    | parse "{value}\t{description}"
+}
+
+def "nu-complete stg patches" [] {
+    ^stg series --no-prefix --applied --unapplied | lines
+}
+
+def "nu-complete stg patches all" [] {
+    ^stg series --no-prefix --all | lines
+}
+
+def "nu-complete stg patches applied" [] {
+    ^stg series --no-prefix --applied | lines
+}
+
+def "nu-complete stg patches unapplied" [] {
+    ^stg series --no-prefix --unapplied | lines
+}
+
+def "nu-complete stg patches hidden" [] {
+    ^stg series --no-prefix --hidden | lines
+}
+
+def "nu-complete stg branches" [] {
+    ^git for-each-ref --format='%(refname:short)' refs/heads/ | lines
+}
+
+def "nu-complete stg all branches" [] {
+    ^git for-each-ref --format='%(refname:short)' refs/heads/ refs/remotes/ | lines
+}
+
+def "nu-complete stg commits" [] {
+    ^git for-each-ref --format='%(refname:short)' refs/heads/ refs/remotes/ refs/tags/
+    | lines
+}
+
+def "nu-complete stg color" [] {
+    [auto always ansi never]
+}
+
+export extern "stg" [
+    command?: string@"nu-complete stg commands"
+    -C: path                                     # Run as if started in <path>
+    --color: string@"nu-complete stg color"      # When to colorize output
+    --version                                    # Print version information
+    --help(-h)                                   # Print help information
+]
+"#;
+
+fn write_command_extern(
+    completers: &mut ShStream,
+    externs: &mut ShStream,
+    command: &clap::Command,
+    parent_path: &str,
+) {
+    let path = f!("{parent_path} {}", command.get_name());
+
+    let mut params = ShStream::new();
+    params.indent();
+
+    let mut seen_optional = false;
+    let mut seen_rest = false;
+    for arg in command.get_positionals().filter(|arg| !arg.is_hide_set()) {
+        if seen_rest {
+            break;
+        }
+        let name = arg.get_id().as_str().replace('-', "_");
+        let num_args = arg.get_num_args().expect("num_args is some for built arg");
+        let param_type = get_param_type(completers, arg, &path);
+        let param = if num_args.max_values() > 1 {
+            seen_rest = true;
+            f!("...{name}: {param_type}")
+        } else if arg.is_required_set() && !seen_optional {
+            f!("{name}: {param_type}")
+        } else {
+            seen_optional = true;
+            f!("{name}?: {param_type}")
+        };
+        params.line(&with_comment(&param, arg.get_help()));
+    }
+
+    for arg in command
+        .get_arguments()
+        .filter(|arg| !arg.is_positional() && !arg.is_hide_set())
+    {
+        let flag = match (arg.get_long(), arg.get_short()) {
+            (Some(long), Some(short)) => f!("--{long}(-{short})"),
+            (Some(long), None) => f!("--{long}"),
+            (None, Some(short)) => f!("-{short}"),
+            (None, None) => continue,
+        };
+        let flag = if arg
+            .get_num_args()
+            .expect("num_args is some for built arg")
+            .takes_values()
+        {
+            f!("{flag}: {}", get_param_type(completers, arg, &path))
+        } else {
+            flag
+        };
+        params.line(&with_comment(&flag, arg.get_help()));
+    }
+
+    // Subcommands that are spelled like flags, e.g. `stg branch --delete`.
+    for subcommand in command
+        .get_subcommands()
+        .filter(|subcommand| subcommand.get_name().starts_with("--"))
+    {
+        let flag = if let Some(short) = subcommand.get_short_flag() {
+            f!("{}(-{short})", subcommand.get_name())
+        } else {
+            subcommand.get_name().to_string()
+        };
+        params.line(&with_comment(&flag, subcommand.get_about()));
+    }
+
+    externs.ensure_blank_line();
+    externs.line(&f!("export extern \"{path}\" ["));
+    externs.raw(params.as_ref());
+    externs.line("]");
+
+    for subcommand in command.get_subcommands().filter(|subcommand| {
+        !subcommand.get_name().starts_with('-') && subcommand.get_name() != "help"
+    }) {
+        write_command_extern(completers, externs, subcommand, &path);
+    }
+}
+
+fn get_param_type(completers: &mut ShStream, arg: &clap::Arg, path: &str) -> String {
+    if arg.get_id() == "color" {
+        return "string@\"nu-complete stg color\"".to_string();
+    }
+
+    if let Some(possible_values) = arg.get_value_parser().possible_values() {
+        let completer_name = f!("nu-complete {path} {}", arg.get_id());
+        let mut values = ShStream::new();
+        for pv in possible_values.filter(|pv| !pv.is_hide_set()) {
+            values.word(&quote(pv.get_name()));
+        }
+        completers.ensure_blank_line();
+        completers.line(&f!("def \"{completer_name}\" [] {{"));
+        completers.indent();
+        completers.line(&f!("[{values}]"));
+        completers.dedent();
+        completers.line("}");
+        return f!("string@\"{completer_name}\"");
+    }
+
+    match arg.get_value_hint() {
+        clap::ValueHint::AnyPath
+        | clap::ValueHint::DirPath
+        | clap::ValueHint::ExecutablePath
+        | clap::ValueHint::FilePath => return "path".to_string(),
+        _ => {}
+    }
+
+    let completer_name = match arg.get_id().as_str() {
+        "branch" | "ref-branch" => "nu-complete stg branches",
+        "branch-any" => "nu-complete stg all branches",
+        "committish" => "nu-complete stg commits",
+        "patch" | "patchranges" => "nu-complete stg patches",
+        "patchranges-all" | "set-tree" | "stgit-revision" => "nu-complete stg patches all",
+        "patchranges-applied" => "nu-complete stg patches applied",
+        "patchranges-hidden" => "nu-complete stg patches hidden",
+        "patchranges-unapplied" => "nu-complete stg patches unapplied",
+        "pathspecs" => return "path".to_string(),
+        "subcommand" => "nu-complete stg commands",
+        _ => return "string".to_string(),
+    };
+    f!("string@\"{completer_name}\"")
+}
+
+fn with_comment(param: &str, help: Option<&clap::builder::StyledStr>) -> String {
+    if let Some(help) = help {
+        let help = help.to_string();
+        let help = help.lines().next().unwrap_or_default();
+        f!("{param:44} # {help}")
+    } else {
+        param.to_string()
+    }
+}
+
+fn quote(s: &str) -> String {
+    f!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
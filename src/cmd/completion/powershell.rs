@@ -0,0 +1,294 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg completion powershell` implementation
+
+use std::{format as f, path::PathBuf};
+
+use anyhow::Result;
+
+use super::shstream::ShStream;
+
+pub(super) fn command() -> clap::Command {
+    clap::Command::new("powershell")
+        .about("Generate powershell completion script")
+        .arg(
+            clap::Arg::new("output")
+                .long("output")
+                .short('o')
+                .help("Output completion script to <path>")
+                .value_name("path")
+                .value_hint(clap::ValueHint::FilePath)
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+}
+
+pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
+    let mut stream = super::get_output_stream(matches)?;
+
+    let mut stg = crate::get_full_command(&crate::alias::Aliases::new(), None);
+    stg.build();
+
+    let mut tables = Tables {
+        completions: ShStream::new(),
+        values: ShStream::new(),
+    };
+    for _ in 0..2 {
+        tables.completions.indent();
+        tables.values.indent();
+    }
+    write_command_tables(&mut tables, &stg, "stg");
+
+    let mut script = ShStream::new();
+    script.raw(HEADER);
+    script.line("    $completions = @{");
+    script.raw(tables.completions.as_ref());
+    script.line("    }");
+    script.line("");
+    script.line("    $values = @{");
+    script.raw(tables.values.as_ref());
+    script.line("    }");
+    script.raw(FOOTER);
+
+    stream.write_all(script.as_bytes())?;
+
+    Ok(())
+}
+
+const HEADER: &str = r#"# SPDX-License-Identifier: GPL-2.0-only
+#
+# PowerShell completion for StGit (stg)
+#
+# To use, source this file from your PowerShell profile, e.g.:
+#
+#   . ~/.config/powershell/stg.ps1
+#
+# This file is autogenerated.
+
+using namespace System.Management.Automation
+using namespace System.Management.Automation.Language
+
+Register-ArgumentCompleter -Native -CommandName 'stg' -ScriptBlock {
+    param($wordToComplete, $commandAst, $cursorPosition)
+
+    function Get-StgPatches([string[]] $SeriesArgs) {
+        & stg series --no-prefix @SeriesArgs 2>$null
+    }
+
+    function Get-StgRefs([string[]] $RefPrefixes) {
+        & git for-each-ref '--format=%(refname:short)' @RefPrefixes 2>$null
+    }
+
+"#;
+
+const FOOTER: &str = r#"
+    $commandElements = $commandAst.CommandElements
+    $command = 'stg'
+    $previous = $null
+    for ($i = 1; $i -lt $commandElements.Count; $i++) {
+        $element = $commandElements[$i]
+        if ($element.Extent.StartOffset -ge $cursorPosition) {
+            break
+        }
+        if ($element -isnot [StringConstantExpressionAst] -or
+            $element.StringConstantType -ne [StringConstantType]::BareWord) {
+            $previous = $null
+            continue
+        }
+        $value = $element.Value
+        if ($value -eq $wordToComplete -and $element.Extent.EndOffset -ge $cursorPosition) {
+            break
+        }
+        $candidate = "$command;$value"
+        if ($completions.ContainsKey($candidate)) {
+            $command = $candidate
+            $previous = $null
+        } else {
+            $previous = $value
+        }
+    }
+
+    $kind = $null
+    if ($previous -and $values.ContainsKey("$command;$previous")) {
+        $kind = $values["$command;$previous"]
+    } elseif (-not $wordToComplete.StartsWith('-')) {
+        $kind = $values[$command]
+    }
+
+    $results = @()
+    if ($kind -is [array]) {
+        $results = $kind
+    } elseif ($kind -eq 'patches') {
+        $results = Get-StgPatches @('--applied', '--unapplied')
+    } elseif ($kind -eq 'patches-all') {
+        $results = Get-StgPatches @('--all')
+    } elseif ($kind -eq 'patches-applied') {
+        $results = Get-StgPatches @('--applied')
+    } elseif ($kind -eq 'patches-unapplied') {
+        $results = Get-StgPatches @('--unapplied')
+    } elseif ($kind -eq 'patches-hidden') {
+        $results = Get-StgPatches @('--hidden')
+    } elseif ($kind -eq 'branches') {
+        $results = Get-StgRefs @('refs/heads/')
+    } elseif ($kind -eq 'all-branches') {
+        $results = Get-StgRefs @('refs/heads/', 'refs/remotes/')
+    } elseif ($kind -eq 'commits') {
+        $results = Get-StgRefs @('refs/heads/', 'refs/remotes/', 'refs/tags/')
+    } elseif ($kind -eq 'default') {
+        return
+    }
+
+    $completionResults = @(
+        $results | ForEach-Object {
+            [CompletionResult]::new($_, $_, [CompletionResultType]::ParameterValue, $_)
+        }
+    )
+    if ($null -eq $kind -or $wordToComplete.StartsWith('-')) {
+        $completionResults += $completions[$command]
+    }
+
+    $completionResults |
+        Where-Object { $_.CompletionText -like "$wordToComplete*" } |
+        Sort-Object -Property ListItemText
+}
+"#;
+
+/// Lookup tables emitted into the completion script.
+struct Tables {
+    /// Entries mapping a command path to its subcommand and option completions.
+    completions: ShStream,
+
+    /// Entries mapping a command path, optionally followed by an option, to the kind of
+    /// value expected as an argument.
+    values: ShStream,
+}
+
+fn write_command_tables(tables: &mut Tables, command: &clap::Command, path: &str) {
+    tables.completions.line(&f!("{} = @(", quote(path)));
+    tables.completions.indent();
+
+    for subcommand in command
+        .get_subcommands()
+        .filter(|subcommand| subcommand.get_name() != "help" && !subcommand.is_hide_set())
+    {
+        let name = subcommand.get_name();
+        let about = subcommand.get_about().map(|s| s.to_string());
+        let about = about.as_deref().unwrap_or(name);
+        if let Some(long) = name.strip_prefix("--") {
+            write_result(&mut tables.completions, &f!("--{long}"), about, true);
+            if let Some(short) = subcommand.get_short_flag() {
+                write_result(&mut tables.completions, &f!("-{short}"), about, true);
+            }
+        } else {
+            write_result(&mut tables.completions, name, about, false);
+        }
+    }
+
+    for arg in command
+        .get_arguments()
+        .filter(|arg| !arg.is_positional() && !arg.is_hide_set())
+    {
+        let help = arg.get_help().map(|s| s.to_string()).unwrap_or_default();
+        let help = help.lines().next().unwrap_or_default();
+        let takes_values = arg
+            .get_num_args()
+            .expect("num_args is some for built arg")
+            .takes_values();
+        let kind = if takes_values {
+            Some(get_value_kind(arg).unwrap_or_else(|| quote("default")))
+        } else {
+            None
+        };
+
+        if let Some(longs) = arg.get_long_and_visible_aliases() {
+            for long in longs {
+                let flag = f!("--{long}");
+                write_result(&mut tables.completions, &flag, help, true);
+                if let Some(kind) = kind.as_ref() {
+                    tables
+                        .values
+                        .line(&f!("{} = {kind}", quote(&f!("{path};{flag}"))));
+                }
+            }
+        }
+        if let Some(shorts) = arg.get_short_and_visible_aliases() {
+            for short in shorts {
+                let flag = f!("-{short}");
+                write_result(&mut tables.completions, &flag, help, true);
+                if let Some(kind) = kind.as_ref() {
+                    tables
+                        .values
+                        .line(&f!("{} = {kind}", quote(&f!("{path};{flag}"))));
+                }
+            }
+        }
+    }
+
+    tables.completions.dedent();
+    tables.completions.line(")");
+
+    if let Some(kind) = command
+        .get_positionals()
+        .filter(|arg| !arg.is_hide_set())
+        .find_map(get_value_kind)
+    {
+        tables.values.line(&f!("{} = {kind}", quote(path)));
+    }
+
+    for subcommand in command.get_subcommands().filter(|subcommand| {
+        !subcommand.get_name().starts_with('-') && subcommand.get_name() != "help"
+    }) {
+        write_command_tables(tables, subcommand, &f!("{path};{}", subcommand.get_name()));
+    }
+}
+
+fn write_result(stream: &mut ShStream, text: &str, tooltip: &str, is_parameter: bool) {
+    let result_type = if is_parameter {
+        "ParameterName"
+    } else {
+        "ParameterValue"
+    };
+    let text = quote(text);
+    let tooltip = quote(if tooltip.is_empty() { " " } else { tooltip });
+    stream.line(&f!(
+        "[CompletionResult]::new({text}, {text}, [CompletionResultType]::{result_type}, {tooltip})"
+    ));
+}
+
+/// Get the kind of value expected by the argument, as a powershell expression.
+///
+/// The kind is either an array of the possible values or a string naming one of the
+/// dynamic value kinds understood by the completion script. The `default` kind defers
+/// to powershell's default (path) completion.
+fn get_value_kind(arg: &clap::Arg) -> Option<String> {
+    if let Some(possible_values) = arg.get_value_parser().possible_values() {
+        let values: Vec<String> = possible_values
+            .filter(|pv| !pv.is_hide_set())
+            .map(|pv| quote(pv.get_name()))
+            .collect();
+        return Some(f!("@({})", values.join(", ")));
+    }
+
+    let kind = match arg.get_value_hint() {
+        clap::ValueHint::AnyPath
+        | clap::ValueHint::DirPath
+        | clap::ValueHint::ExecutablePath
+        | clap::ValueHint::FilePath => "default",
+        _ => match arg.get_id().as_str() {
+            "branch" | "ref-branch" => "branches",
+            "branch-any" => "all-branches",
+            "committish" => "commits",
+            "patch" | "patchranges" => "patches",
+            "patchranges-all" | "set-tree" | "stgit-revision" => "patches-all",
+            "patchranges-applied" => "patches-applied",
+            "patchranges-hidden" => "patches-hidden",
+            "patchranges-unapplied" => "patches-unapplied",
+            "pathspecs" => "default",
+            _ => return None,
+        },
+    };
+    Some(quote(kind))
+}
+
+fn quote(s: &str) -> String {
+    f!("'{}'", s.replace('\'', "''"))
+}
@@ -0,0 +1,166 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg doctor` implementation.
+
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgMatches};
+
+use crate::{
+    argset,
+    color::get_color_stdout,
+    ext::RepositoryExtended,
+    patch::PatchName,
+    print_info_message,
+    stack::{InitializationPolicy, Stack, StackStateAccess},
+};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "doctor",
+    category: super::CommandCategory::Administration,
+    make,
+    run,
+};
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Check patch names for portability problems and expired hidden patches")
+        .long_about(
+            "Check the names of the patches in the stack for problems that may \
+             cause breakage on some platforms or filesystems.\n\
+             \n\
+             Patch names are stored as git references, which in turn may be stored \
+             as files. A patch name is considered problematic if it is not in Unicode \
+             NFC form, which macOS may alter; if it contains characters that are \
+             invalid in Windows file names or non-ASCII whitespace; if it is a \
+             reserved Windows device name such as \"con\" or \"aux\"; or if it \
+             differs only in case from another patch's name, which collide on \
+             case-insensitive filesystems.\n\
+             \n\
+             Each problematic patch is reported along with a portable replacement \
+             name. With '--fix', all problematic patches are renamed to their \
+             replacement names in a single operation that may be reverted with \
+             'stg undo'.\n\
+             \n\
+             When \"stgit.hidden.expire-days\" is set, hidden patches that have been \
+             hidden for longer than that many days are also reported. With '--fix', \
+             such expired hidden patches are moved onto a new shelf named \
+             \"hidden/<branch>/<date>-<time>\", from which they may be restored with \
+             `stg unshelve`.",
+        )
+        .arg(argset::branch_arg())
+        .arg(
+            Arg::new("fix")
+                .long("fix")
+                .help("Rename problematic patches and shelve expired hidden patches")
+                .action(clap::ArgAction::SetTrue),
+        )
+}
+
+fn run(matches: &ArgMatches) -> Result<()> {
+    let repo = git_repository::Repository::open()?;
+    let stack = Stack::from_branch(
+        &repo,
+        argset::get_one_str(matches, "branch"),
+        InitializationPolicy::RequireInitialized,
+    )?;
+
+    let all_patches: Vec<&PatchName> = stack.all_patches().collect();
+    let mut renames: Vec<(PatchName, PatchName)> = Vec::new();
+    let mut disallow: Vec<PatchName> = Vec::new();
+
+    for (i, &patchname) in all_patches.iter().enumerate() {
+        let issue = patchname.portability_issue().or_else(|| {
+            all_patches[..i]
+                .iter()
+                .any(|earlier| earlier.collides(patchname))
+                .then_some("differs only in case from another patch name")
+        });
+        if let Some(issue) = issue {
+            let unavailable: Vec<&PatchName> = all_patches
+                .iter()
+                .copied()
+                .filter(|pn| *pn != patchname)
+                .chain(disallow.iter())
+                .collect();
+            let new_patchname = patchname.to_portable().uniquify(&[], &unavailable);
+            println!("patch `{patchname}` {issue} (portable name `{new_patchname}`)");
+            disallow.push(new_patchname.clone());
+            renames.push((patchname.clone(), new_patchname));
+        }
+    }
+
+    let expired: Vec<PatchName> = if let Some(expire_days) = super::hide::expire_days(&repo) {
+        super::hide::expired_hidden_patches(&stack, expire_days)?
+            .into_iter()
+            .map(|(patchname, days)| {
+                println!("patch `{patchname}` has been hidden for {days} days");
+                patchname
+            })
+            .collect()
+    } else {
+        vec![]
+    };
+
+    if renames.is_empty() && expired.is_empty() {
+        return Ok(());
+    } else if !matches.get_flag("fix") {
+        let plural = |n: usize, singular: &str, plural: &str| {
+            format!("{n} {}", if n == 1 { singular } else { plural })
+        };
+        return Err(match (renames.len(), expired.len()) {
+            (n, 0) => anyhow!(
+                "found {}; use `stg doctor --fix` to rename",
+                plural(n, "problematic patch name", "problematic patch names")
+            ),
+            (0, m) => anyhow!(
+                "found {}; use `stg doctor --fix` to shelve",
+                plural(m, "expired hidden patch", "expired hidden patches")
+            ),
+            (n, m) => anyhow!(
+                "found {} and {}; use `stg doctor --fix` to rename and shelve",
+                plural(n, "problematic patch name", "problematic patch names"),
+                plural(m, "expired hidden patch", "expired hidden patches")
+            ),
+        });
+    }
+
+    let stack = if renames.is_empty() {
+        stack
+    } else {
+        stack
+            .setup_transaction()
+            .allow_conflicts(true)
+            .with_output_stream(get_color_stdout(matches))
+            .transact(|trans| {
+                for (old_patchname, new_patchname) in &renames {
+                    trans.rename_patch(old_patchname, new_patchname)?;
+                }
+                Ok(())
+            })
+            .execute("doctor --fix")?
+    };
+
+    if !expired.is_empty() {
+        // Expired patches may have just been renamed.
+        let expired: Vec<PatchName> = expired
+            .into_iter()
+            .map(|patchname| {
+                renames
+                    .iter()
+                    .find(|(old_patchname, _)| *old_patchname == patchname)
+                    .map_or(patchname, |(_, new_patchname)| new_patchname.clone())
+            })
+            .collect();
+        let shelf_name = super::shelve::shelve_hidden(stack, &expired, matches, "doctor --fix")?;
+        print_info_message(
+            matches,
+            &format!(
+                "shelved {} expired hidden patch{} onto `{shelf_name}`",
+                expired.len(),
+                if expired.len() == 1 { "" } else { "es" }
+            ),
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,200 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg duplicate` implementation.
+
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgMatches};
+
+use crate::{
+    argset,
+    color::get_color_stdout,
+    ext::{CommitExtended, RepositoryExtended},
+    patch::PatchName,
+    stack::{Error, InitializationPolicy, Stack, StackStateAccess},
+    stupid::Stupid,
+};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "duplicate",
+    category: super::CommandCategory::PatchManipulation,
+    make,
+    run,
+};
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Make a copy of a patch")
+        .long_about(
+            "Make a copy of a patch, including its diff, commit message, and author, \
+             under a new name. If no patch is specified, the topmost patch is \
+             duplicated.\n\
+             \n\
+             By default, the duplicate is named after the original patch with a \
+             \"-copy\" suffix and is placed unapplied, either directly after the \
+             original if it is unapplied or at the front of the unapplied patches. \
+             The '--to' option may be used to place the duplicate after any other \
+             patch in the series.\n\
+             \n\
+             With '--swap', the duplicate takes the original's place in the series and \
+             the original patch is hidden. This is useful for trying an alternative \
+             version of a patch while keeping the original version at hand.",
+        )
+        .arg(
+            Arg::new("patch")
+                .help("Patch to duplicate")
+                .value_parser(clap::value_parser!(PatchName)),
+        )
+        .arg(argset::branch_arg())
+        .arg(
+            Arg::new("name")
+                .long("name")
+                .short('n')
+                .help("Use <name> for the duplicate patch")
+                .value_name("name")
+                .value_parser(clap::value_parser!(PatchName)),
+        )
+        .arg(
+            Arg::new("target")
+                .long("to")
+                .short('t')
+                .help("Place the duplicate after <target> patch")
+                .long_help(
+                    "Place the duplicate after <target> patch. If <target> is applied, \
+                     the duplicate is pushed after it, which may lead to conflicts \
+                     since the duplicated changes may already be applied.",
+                )
+                .value_name("target")
+                .value_parser(clap::value_parser!(PatchName)),
+        )
+        .arg(
+            Arg::new("swap")
+                .long("swap")
+                .short('s')
+                .help("Replace the original with the duplicate and hide the original")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("target"),
+        )
+}
+
+fn run(matches: &ArgMatches) -> Result<()> {
+    let repo = git_repository::Repository::open()?;
+    let stack = Stack::from_branch(
+        &repo,
+        argset::get_one_str(matches, "branch"),
+        InitializationPolicy::AllowUninitialized,
+    )?;
+
+    let patchname = if let Some(patchname) = matches.get_one::<PatchName>("patch") {
+        if !stack.has_patch(patchname) {
+            return Err(anyhow!("patch `{patchname}` does not exist"));
+        }
+        patchname.clone()
+    } else if let Some(patchname) = stack.applied().last() {
+        patchname.clone()
+    } else {
+        return Err(Error::NoAppliedPatches.into());
+    };
+
+    let opt_target = matches.get_one::<PatchName>("target");
+    if let Some(target) = opt_target {
+        if !stack.has_patch(target) {
+            return Err(anyhow!("target patch `{target}` does not exist"));
+        } else if stack.is_hidden(target) {
+            return Err(anyhow!(
+                "cannot place duplicate after hidden patch `{target}`"
+            ));
+        }
+    }
+
+    let swap = matches.get_flag("swap");
+    if swap && stack.is_hidden(&patchname) {
+        return Err(anyhow!("cannot swap hidden patch `{patchname}`"));
+    }
+
+    let all_patches: Vec<&PatchName> = stack.all_patches().collect();
+    let new_patchname = if let Some(name) = matches.get_one::<PatchName>("name") {
+        if all_patches.iter().any(|pn| pn.collides(name)) {
+            return Err(anyhow!("patch `{name}` already exists"));
+        }
+        if let Some(warning) = name.check_portability(&repo.config_snapshot())? {
+            crate::print_warning_message(matches, &warning);
+        }
+        name.clone()
+    } else {
+        PatchName::from_str(&format!("{patchname}-copy"))?.uniquify(&[], &all_patches)
+    };
+
+    let reorders_applied = stack.is_applied(&patchname) && swap
+        || opt_target.map_or(false, |target| stack.is_applied(target));
+    if reorders_applied {
+        repo.check_repository_state()?;
+        let statuses = repo.stupid().statuses(None)?;
+        statuses.check_conflicts()?;
+        statuses.check_index_and_worktree_clean()?;
+        stack.check_head_top_mismatch()?;
+    }
+
+    let commit = stack.get_patch_commit(&patchname)?;
+    let new_commit_id = repo.commit_ex(
+        &commit.author_strict()?,
+        repo.get_committer()?,
+        &commit.message_ex(),
+        commit.tree_id()?.detach(),
+        commit.parent_ids().map(|id| id.detach()),
+    )?;
+
+    let replace = |patches: &[PatchName]| -> Vec<PatchName> {
+        patches
+            .iter()
+            .map(|pn| {
+                if pn == &patchname {
+                    new_patchname.clone()
+                } else {
+                    pn.clone()
+                }
+            })
+            .collect()
+    };
+
+    stack
+        .setup_transaction()
+        .use_index_and_worktree(reorders_applied)
+        .with_output_stream(get_color_stdout(matches))
+        .transact(|trans| {
+            if swap {
+                let applied = replace(trans.applied());
+                let unapplied = replace(trans.unapplied());
+                let mut hidden = trans.hidden().to_vec();
+                hidden.push(patchname.clone());
+                trans.new_unapplied(&new_patchname, new_commit_id, 0)?;
+                trans.reorder_patches(Some(&applied), Some(&unapplied), Some(&hidden))
+            } else if let Some(target) = opt_target {
+                if let Some(pos) = trans.applied().iter().position(|pn| pn == target) {
+                    let mut applied = trans.applied().to_vec();
+                    applied.insert(pos + 1, new_patchname.clone());
+                    let unapplied = trans.unapplied().to_vec();
+                    trans.new_unapplied(&new_patchname, new_commit_id, 0)?;
+                    trans.reorder_patches(Some(&applied), Some(&unapplied), None)
+                } else {
+                    let pos = trans
+                        .unapplied()
+                        .iter()
+                        .position(|pn| pn == target)
+                        .expect("target is unapplied");
+                    trans.new_unapplied(&new_patchname, new_commit_id, pos + 1)
+                }
+            } else {
+                let pos = trans
+                    .unapplied()
+                    .iter()
+                    .position(|pn| pn == &patchname)
+                    .map_or(0, |pos| pos + 1);
+                trans.new_unapplied(&new_patchname, new_commit_id, pos)
+            }
+        })
+        .execute(&format!("duplicate {patchname} {new_patchname}"))?;
+
+    Ok(())
+}
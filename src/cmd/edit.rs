@@ -7,7 +7,7 @@ use clap::{Arg, ArgMatches, ValueHint};
 
 use crate::{
     color::get_color_stdout,
-    ext::RepositoryExtended,
+    ext::{CommitExtended, RepositoryExtended},
     patch::{patchedit, patchrange, PatchName},
     stack::{Error, InitializationPolicy, Stack, StackStateAccess},
 };
@@ -38,7 +38,41 @@ fn make() -> clap::Command {
              message). The StGit attempts to apply the modified diff to the patch's \
              parent tree. If the updated diff does not apply, no changes are made to \
              the patch and the edited patch is saved to a file which may be corrected \
-             and then fed-back into `stg edit --file`.",
+             and then fed-back into `stg edit --file`.\n\
+             \n\
+             Patch metadata, such as labels, dependencies, and email recipients, is \
+             presented in a metadata section of the patch description. The section \
+             starts with the line \"# ---------------------- metadata \
+             ----------------------\" and continues up to the diff or the end of the \
+             description. Each line in the section is a \"Key: value\" field, e.g. \
+             \"Label: wip\" or \"Depends: other-patch\"; keys may be repeated and \
+             unknown keys are preserved. The section is omitted for patches without \
+             metadata, but may be added to a description to record metadata. \
+             Removing fields from the section removes them from the patch's metadata, \
+             while a description without a metadata section leaves the patch's \
+             metadata unchanged.\n\
+             \n\
+             The '--reuse-message' option replaces the patch's message with the full \
+             message of another commit or patch, which is useful when the message text \
+             is maintained outside of the stack. The '--reset-message' option \
+             replaces the patch's message with the \"patchdescr.tmpl\" template, or \
+             with an empty message if there is no such template, and then edits the \
+             patch interactively unless other patch edit options are specified. As \
+             with any changed message, the commit-msg hook is run on the new \
+             message unless '--no-verify' is specified.\n\
+             \n\
+             The '--json-io' option is meant for editor integrations. Instead of \
+             launching an editor, the patch description is written to stdout as a \
+             single line JSON object with \"patchname\", \"author\", and \
+             \"message\" fields, a \"diff\" field when '--diff' is specified, and a \
+             \"metadata\" field with the patch metadata as \"Key: value\" lines. \
+             The author is an object with \"name\", \"email\", and \"date\" \
+             fields. The edited patch description is then read from stdin as a JSON \
+             object with the same fields. Fields absent from the edited description \
+             retain their original values. A null \"patchname\" causes the patch name \
+             to be generated from the message and a null \"author\" resets the \
+             author to the default author. Any further output, such as from updating \
+             the stack, follows the JSON object on stdout.",
         )
         .arg(
             Arg::new("patch")
@@ -46,22 +80,50 @@ fn make() -> clap::Command {
                 .value_parser(clap::value_parser!(PatchName))
                 .value_hint(ValueHint::Other),
         );
-    patchedit::add_args(app, true, true).arg(
-        Arg::new("set-tree")
-            .long("set-tree")
-            .short('t')
-            .help("Set patch's tree to treeish")
-            .long_help(
-                "Set the patch's git tree to the specified treeish without changing \
+    patchedit::add_args(app, true, true)
+        .arg(
+            Arg::new("json-io")
+                .long("json-io")
+                .help("Edit patch description as JSON over stdin and stdout")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["edit", "file", "message", "save-template"]),
+        )
+        .arg(
+            Arg::new("reuse-message")
+                .long("reuse-message")
+                .help("Reuse message from another commit or patch")
+                .long_help(
+                    "Use the full message of <committish>, which may be any commit \
+                     or patch, as the patch's message.",
+                )
+                .value_name("committish")
+                .num_args(1)
+                .value_hint(ValueHint::Other)
+                .conflicts_with_all(["file", "message", "reset-message"]),
+        )
+        .arg(
+            Arg::new("reset-message")
+                .long("reset-message")
+                .help("Reset message to the patch description template")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["file", "message"]),
+        )
+        .arg(
+            Arg::new("set-tree")
+                .long("set-tree")
+                .short('t')
+                .help("Set patch's tree to treeish")
+                .long_help(
+                    "Set the patch's git tree to the specified treeish without changing \
                  the tree of any other patches. When used on the top patch, the index \
                  and work tree will be updated to match the new tree. This low-level \
                  option is primarily meant to be used by tools built on top of StGit, \
                  such as the Emacs mode. See also the '--set-tree' flag of 'stg \
                  push'.",
-            )
-            .num_args(1)
-            .value_name("treeish"),
-    )
+                )
+                .num_args(1)
+                .value_name("treeish"),
+        )
 }
 
 fn run(matches: &ArgMatches) -> Result<()> {
@@ -77,7 +139,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
         return Err(Error::NoAppliedPatches.into());
     };
 
-    let patch_commit = stack.get_patch_commit(&patchname);
+    let patch_commit = stack.get_patch_commit(&patchname)?;
 
     let tree_id = if let Some(treeish) = matches.get_one::<String>("set-tree") {
         crate::revspec::parse_stgit_revision(&repo, Some(treeish), None)
@@ -88,12 +150,32 @@ fn run(matches: &ArgMatches) -> Result<()> {
         patch_commit.tree_id()?.detach()
     };
 
-    match patchedit::EditBuilder::default()
+    let replacement_message = if let Some(committish) = matches.get_one::<String>("reuse-message") {
+        let commit = crate::revspec::parse_stgit_revision(&repo, Some(committish), None)
+            .context("parsing `--reuse-message` value")?
+            .try_into_commit()?;
+        Some(commit.message_ex().decode()?.to_string())
+    } else if matches.get_flag("reset-message") {
+        Some(crate::templates::get_template(&repo, "patchdescr.tmpl")?.unwrap_or_default())
+    } else {
+        None
+    };
+
+    let mut edit_builder = patchedit::EditBuilder::default();
+    if let Some(message) = replacement_message {
+        edit_builder = edit_builder.default_message(message);
+    }
+
+    match edit_builder
         .original_patchname(Some(&patchname))
         .existing_patch_commit(patch_commit)
         .allow_diff_edit(true)
-        .allow_implicit_edit(!matches.contains_id("set-tree"))
+        .allow_implicit_edit(
+            !matches.contains_id("set-tree") && !matches.contains_id("reuse-message"),
+        )
+        .allow_meta_edit(true)
         .allow_template_save(true)
+        .json_io(matches.get_flag("json-io"))
         .override_tree_id(tree_id)
         .edit(&stack, &repo, matches)?
     {
@@ -101,8 +183,9 @@ fn run(matches: &ArgMatches) -> Result<()> {
         patchedit::EditOutcome::Edited {
             new_patchname,
             new_commit_id,
+            new_meta,
         } => {
-            if new_patchname.is_some() || new_commit_id.is_some() {
+            let stack = if new_patchname.is_some() || new_commit_id.is_some() {
                 stack
                     .setup_transaction()
                     .allow_conflicts(true)
@@ -138,7 +221,16 @@ fn run(matches: &ArgMatches) -> Result<()> {
                             trans.push_patches(&popped, false)
                         }
                     })
-                    .execute(&format!("edit: {patchname}"))?;
+                    .execute(&format!("edit: {patchname}"))?
+            } else {
+                stack
+            };
+
+            // Metadata is recorded after the transaction since the transaction copies
+            // the original patch's metadata to any updated patch commit.
+            if let Some(meta) = new_meta {
+                let patchname = new_patchname.as_ref().unwrap_or(&patchname);
+                meta.write(&repo, stack.get_patch_commit_id(patchname))?;
             }
             Ok(())
         }
@@ -0,0 +1,289 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Email address alias and group expansion.
+//!
+//! Aliases are read from the file(s) named by `sendemail.aliasesFile` when
+//! `sendemail.aliasFileType` is either `mutt` or `mailrc`. Groups are read from
+//! `stgit.email.group.<name>` configuration variables, each value of which may be one
+//! or more comma-separated addresses or the names of other aliases or groups.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::PathBuf,
+};
+
+use anyhow::{anyhow, Context, Result};
+use bstr::ByteSlice;
+
+/// Mapping of alias and group names to the addresses they expand to.
+#[derive(Debug, Default)]
+pub(super) struct AddressBook {
+    aliases: BTreeMap<String, Vec<String>>,
+}
+
+impl AddressBook {
+    /// Load aliases and groups from the repository's configuration.
+    pub(super) fn from_config(config: &git_repository::config::Snapshot) -> Result<Self> {
+        let mut book = Self::default();
+
+        let file_type = config
+            .string("sendemail.aliasFileType")
+            .map(|value| value.to_str_lossy().to_ascii_lowercase());
+        if let Some(file_type) = file_type.as_deref() {
+            if matches!(file_type, "mutt" | "mailrc") {
+                for path in config
+                    .plumbing()
+                    .strings_by_key("sendemail.aliasesFile")
+                    .unwrap_or_default()
+                {
+                    let path = expand_home(&path.to_str_lossy());
+                    let content = std::fs::read(&path).with_context(|| {
+                        format!("reading alias file `{}`", path.to_string_lossy())
+                    })?;
+                    let content = content.to_str_lossy();
+                    if file_type == "mutt" {
+                        book.parse_mutt(&content);
+                    } else {
+                        book.parse_mailrc(&content);
+                    }
+                }
+            }
+        }
+
+        if let Some(sections) = config.plumbing().sections_by_name("stgit") {
+            for section in sections.filter(|section| {
+                section.header().subsection_name() == Some(b"email.group".as_bstr())
+            }) {
+                let names = section
+                    .body()
+                    .keys()
+                    .map(|key| key.to_string())
+                    .collect::<BTreeSet<_>>();
+                for name in names {
+                    let mut addresses = Vec::new();
+                    for value in section.body().values(&name) {
+                        let value = value.to_str().map_err(|_| {
+                            anyhow!("`stgit.email.group.{name}` is not valid UTF-8")
+                        })?;
+                        addresses.extend(split_addresses(value));
+                    }
+                    book.aliases.insert(name.to_ascii_lowercase(), addresses);
+                }
+            }
+        }
+
+        Ok(book)
+    }
+
+    /// Names of all known aliases and groups.
+    pub(super) fn names(&self) -> impl Iterator<Item = &str> {
+        self.aliases.keys().map(String::as_str)
+    }
+
+    /// Expand a recipient value into the addresses it refers to.
+    ///
+    /// The value may contain several comma-separated addresses. If none of them are
+    /// an alias or group name, the value is returned unchanged.
+    pub(super) fn expand(&self, value: &str) -> Result<Vec<String>> {
+        let addresses = split_addresses(value);
+        if addresses
+            .iter()
+            .all(|address| self.lookup(address).is_none())
+        {
+            return Ok(vec![value.to_string()]);
+        }
+        let mut expanded = Vec::new();
+        for address in &addresses {
+            self.expand_address(address, &mut Vec::new(), &mut expanded)?;
+        }
+        Ok(expanded)
+    }
+
+    fn expand_address(
+        &self,
+        address: &str,
+        seen: &mut Vec<String>,
+        expanded: &mut Vec<String>,
+    ) -> Result<()> {
+        if let Some((name, addresses)) = self.lookup(address) {
+            if seen.iter().any(|seen_name| seen_name == name) {
+                return Err(anyhow!("alias `{name}` expands to itself"));
+            }
+            seen.push(name.to_string());
+            for address in addresses {
+                self.expand_address(address, seen, expanded)?;
+            }
+            seen.pop();
+        } else if !expanded.iter().any(|existing| existing == address) {
+            expanded.push(address.to_string());
+        }
+        Ok(())
+    }
+
+    fn lookup(&self, name: &str) -> Option<(&str, &[String])> {
+        self.aliases
+            .get_key_value(name)
+            .or_else(|| self.aliases.get_key_value(&name.to_ascii_lowercase()))
+            .map(|(name, addresses)| (name.as_str(), addresses.as_slice()))
+    }
+
+    /// Parse mutt `alias [-group <name>]... <alias> <address>[, <address>]...` lines.
+    fn parse_mutt(&mut self, content: &str) {
+        for line in content.lines() {
+            let mut rest = line.trim_start();
+            if let Some(after) = rest.strip_prefix("alias") {
+                if !after.starts_with(char::is_whitespace) {
+                    continue;
+                }
+                rest = after.trim_start();
+            } else {
+                continue;
+            }
+            while let Some(after) = rest.strip_prefix("-group") {
+                let after = after.trim_start();
+                rest = after
+                    .split_once(char::is_whitespace)
+                    .map_or("", |(_, rest)| rest.trim_start());
+            }
+            if let Some((name, addresses)) = rest.split_once(char::is_whitespace) {
+                let addresses = addresses.split_once('#').map_or(addresses, |(a, _)| a);
+                let addresses = split_addresses(addresses)
+                    .into_iter()
+                    .map(|address| address.replace("\\\"", "\""))
+                    .collect();
+                self.aliases.insert(name.to_string(), addresses);
+            }
+        }
+    }
+
+    /// Parse mailrc `alias <alias> <address> [<address>]...` lines.
+    fn parse_mailrc(&mut self, content: &str) {
+        for line in content.lines() {
+            if let Some(rest) = line.strip_prefix("alias") {
+                if !rest.starts_with(char::is_whitespace) {
+                    continue;
+                }
+                if let Some((name, addresses)) = rest.trim().split_once(char::is_whitespace) {
+                    self.aliases
+                        .insert(name.to_string(), split_words(addresses.trim()));
+                }
+            }
+        }
+    }
+}
+
+/// Expand a leading `~/` to the user's home directory.
+fn expand_home(path: &str) -> PathBuf {
+    if let (Some(rest), Some(home)) = (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+        PathBuf::from(home).join(rest)
+    } else {
+        PathBuf::from(path)
+    }
+}
+
+/// Split comma-separated addresses.
+///
+/// Commas within double-quoted display names and angle-bracketed addresses do not
+/// separate addresses.
+fn split_addresses(value: &str) -> Vec<String> {
+    let mut addresses = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut in_angle = false;
+    let mut escaped = false;
+    for c in value.chars() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' && in_quotes {
+            escaped = true;
+        } else if c == '"' {
+            in_quotes = !in_quotes;
+        } else if !in_quotes && c == '<' {
+            in_angle = true;
+        } else if !in_quotes && c == '>' {
+            in_angle = false;
+        } else if !in_quotes && !in_angle && c == ',' {
+            addresses.push(std::mem::take(&mut current));
+            continue;
+        }
+        current.push(c);
+    }
+    addresses.push(current);
+    addresses
+        .into_iter()
+        .map(|address| address.trim().to_string())
+        .filter(|address| !address.is_empty())
+        .collect()
+}
+
+/// Split whitespace-separated words, where double-quoted words may contain spaces.
+fn split_words(value: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    for c in value.chars() {
+        if c == '"' {
+            in_quotes = !in_quotes;
+        } else if c.is_whitespace() && !in_quotes {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+        } else {
+            current.push(c);
+        }
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn split_quoted_addresses() {
+        assert_eq!(
+            split_addresses(r#""Doe, Jane" <jane@example.com>, bob@example.com,"#),
+            vec![r#""Doe, Jane" <jane@example.com>"#, "bob@example.com"]
+        );
+    }
+
+    #[test]
+    fn parse_alias_files() {
+        let mut book = AddressBook::default();
+        book.parse_mutt(
+            "# comment\n\
+             alias -group dev alice Alice <alice@example.com> # friend\n\
+             alias team alice, \"Bob \\\"B\\\" Smith\" <bob@example.com>\n",
+        );
+        book.parse_mailrc("alias carol carol@example.com \"Carol C <cc@example.com>\"\n");
+        assert_eq!(
+            book.expand("team").unwrap(),
+            vec![
+                "Alice <alice@example.com>",
+                r#""Bob "B" Smith" <bob@example.com>"#
+            ]
+        );
+        assert_eq!(
+            book.expand("carol").unwrap(),
+            vec!["carol@example.com", "Carol C <cc@example.com>"]
+        );
+        assert_eq!(
+            book.expand("dave@example.com, x@example.com").unwrap(),
+            vec!["dave@example.com, x@example.com"]
+        );
+    }
+
+    #[test]
+    fn recursive_expansion() {
+        let mut book = AddressBook::default();
+        book.parse_mutt("alias a b, a@example.com\nalias b b@example.com\nalias c d\nalias d c\n");
+        assert_eq!(
+            book.expand("a, b").unwrap(),
+            vec!["b@example.com", "a@example.com"]
+        );
+        assert!(book.expand("c").is_err());
+    }
+}
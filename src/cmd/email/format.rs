@@ -2,14 +2,26 @@
 
 //! `stg email format` implementation.
 
-use anyhow::{anyhow, Result};
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, Context, Result};
+use bstr::ByteSlice;
 use clap::Arg;
 
+use super::aliases::AddressBook;
 use crate::{
     argset,
     ext::{CommitExtended, RepositoryExtended},
-    patch::patchrange,
-    stack::{Error, InitializationPolicy, Stack, StackStateAccess},
+    patch::{
+        meta::{self, PatchMeta},
+        patchrange, PatchName,
+    },
+    revspec::parse_stgit_revision,
+    stack::{Error, InitializationPolicy, Stack, StackAccess, StackStateAccess},
     stupid::Stupid,
 };
 
@@ -25,13 +37,19 @@ pub(super) fn command() -> clap::Command {
              \n\
              The patches to format may be specified as individual patch names or patch \
              ranges of the form 'p0..p3', or '--all' may be used to format all applied \
-             patches. Note that the specified patches must be contiguous within the \
-             patch series.\n\
+             patches. The specified patches need not be contiguous within the patch \
+             series; they are always formatted in series order. Empty patches cannot \
+             be formatted unless '--skip-empty' is used to leave them out.\n\
              \n\
              By default, the email files will be output to the current directory, \
              however use of the -o/--output-directory option is recommended since \
              sending the email with `stg email send <dir>` is simpler than specifying \
-             all the email files individually.\n\
+             all the email files individually. Alternatively, '--stdout' or '--mbox' \
+             output the whole series as a single mbox, e.g. to be piped to `git am`, \
+             and '--maildir' delivers the emails to a Maildir for mail clients. \
+             With '--auto-dir', each version of the series is kept in its own \
+             `outgoing/<branch>/v<n>` directory, the newest of which is sent by `stg \
+             email send` when no patches are given.\n\
              \n\
              A cover letter template may also be generated by specifying \
              '--cover-letter'. A cover letter is recommended when sending multiple \
@@ -42,9 +60,23 @@ pub(super) fn command() -> clap::Command {
              Recipients may be specified using the '--to' and '--cc', or setting \
              recipients may be deferred to `stg email send`.\n\
              \n\
-             Many aspects of the format behavior may be controlled via `format.*` \
-             configuration values. Refer to the git-config(1) and git-format-patch(1) \
-             man pages for more details.",
+             The Message-Id of each formatted patch email is recorded in the \
+             patch's metadata so that later rerolls of the series can refer to the \
+             original emails. The Message-Id of the first email of the series is also \
+             recorded for the branch so that '--reply-to-previous' may thread a \
+             reroll of the series to its previous version. Set \
+             `stgit.email.record-message-id` to false to disable this recording.\n\
+             \n\
+             The emails are formatted the same way as by `git format-patch` and the \
+             following `format.*` configuration values are honored: \
+             `format.coverFromDescription`, `format.coverLetter`, `format.headers`, \
+             `format.numbered`, `format.outputDirectory`, `format.signOff`, \
+             `format.signature`, `format.signatureFile`, `format.subjectPrefix`, \
+             `format.suffix`, `format.thread`, `format.to`, and `format.cc`. Refer to the git-config(1) \
+             and git-format-patch(1) man pages for more details. Like other StGit \
+             commands that show diffs, the `stgit.diff.*` and `stgit.diff-opts` \
+             configuration values and '--diff-opt' options are passed to `git diff-tree` when generating the \
+             diffs and diffstats.",
         )
         .override_usage(
             "stg email format [OPTIONS] <patch>...\n       \
@@ -67,29 +99,72 @@ pub(super) fn command() -> clap::Command {
                 .help("Format all applied patches")
                 .action(clap::ArgAction::SetTrue),
         )
+        .next_help_heading("Format Options")
+        .args(format_options())
         .arg(
-            Arg::new("git-format-patch-opt")
-                .long("git-opt")
-                .short('G')
-                .help("Pass additional <option> to `git format-patch`")
+            Arg::new("filename-template")
+                .long("filename-template")
+                .help("Name output files according to <template>")
                 .long_help(
-                    "Pass additional <option> to `git format-patch`.\n\
+                    "Name the output files according to <template> instead of the \
+                     default names derived from the message number and subject. The \
+                     following \
+                     placeholders are expanded:\n\
                      \n\
-                     See the git-format-patch(1) man page. This option may be \
-                     specified multiple times.",
+                     '%n' the zero-padded message number, e.g. `0001`. The cover \
+                     letter is number `0000`.\n\
+                     '%s' the sanitized subject of the patch, e.g. `add-makefile`.\n\
+                     '%v' the reroll count from '--reroll-count', e.g. `v2`, or empty.\n\
+                     '%p' the patch name, or `cover-letter` for the cover letter.\n\
+                     '%%' a literal `%`.\n\
+                     \n\
+                     The template must include the file name suffix, if any. For \
+                     example, '--filename-template=%n-%p.patch'. The template may also \
+                     be set with the `stgit.email.filename-template` configuration \
+                     value.",
                 )
-                .allow_hyphen_values(true)
-                .action(clap::ArgAction::Append)
-                .value_name("option"),
+                .num_args(1)
+                .value_name("template")
+                .value_parser(clap::builder::NonEmptyStringValueParser::new()),
         )
-        .next_help_heading("Format Options")
-        .args(format_options())
         .next_help_heading("Message Options")
         .args(message_options())
-    // DIFF OPTIONS ???
+        .next_help_heading("Diff Options")
+        .arg(argset::diff_opts_arg())
+        .arg(
+            Arg::new("git-format-patch-opt")
+                .long("git-opt")
+                .short('G')
+                .help("No longer supported; use the equivalent `stg email format` options")
+                .allow_hyphen_values(true)
+                .action(clap::ArgAction::Append)
+                .value_name("option")
+                .hide(true),
+        )
 }
 
-fn format_options() -> Vec<Arg> {
+/// Placeholder cover letter subject, to be replaced before sending.
+pub(super) const COVER_SUBJECT_PLACEHOLDER: &str = "*** SUBJECT HERE ***";
+
+/// Placeholder cover letter text, to be replaced before sending.
+pub(super) const COVER_BLURB_PLACEHOLDER: &str = "*** BLURB HERE ***";
+
+/// Ids of the arguments that only control how formatted emails are output.
+///
+/// These arguments are not applicable to `stg email preview`.
+pub(super) const OUTPUT_ARG_IDS: [&str; 9] = [
+    "output-directory",
+    "auto-dir",
+    "stdout",
+    "mbox",
+    "maildir",
+    "quiet",
+    "numbered-files",
+    "suffix",
+    "filename-template",
+];
+
+pub(super) fn format_options() -> Vec<Arg> {
     vec![
         Arg::new("output-directory")
             .long("output-directory")
@@ -103,15 +178,156 @@ fn format_options() -> Vec<Arg> {
             .value_name("dir")
             .value_hint(clap::ValueHint::DirPath)
             .value_parser(clap::builder::NonEmptyStringValueParser::new()),
+        Arg::new("auto-dir")
+            .long("auto-dir")
+            .help("Store output files in a versioned directory")
+            .long_help(
+                "Store the output files in `outgoing/<branch>/v<n>/`, relative to the \
+                 top of the worktree, such that each version of the series remains \
+                 archived. The version <n> is taken from '--reroll-count' when \
+                 specified. Otherwise it is one more than the newest version \
+                 previously formatted for the branch, as recorded by `stg email \
+                 format` or found in the existing `v<n>` directories. A derived \
+                 version greater than one is also used as the reroll count.\n\
+                 \n\
+                 `stg email send` sends the newest of these directories when no \
+                 patches or files are given.",
+            )
+            .action(clap::ArgAction::SetTrue)
+            .conflicts_with("output-directory"),
+        Arg::new("stdout")
+            .long("stdout")
+            .help("Print all emails to stdout as a single mbox")
+            .long_help(
+                "Print all emails to standard output as a single mbox stream instead \
+                 of writing one file per email. The output may be piped directly to \
+                 `git am`, `b4 shazam`, or a mail client.",
+            )
+            .action(clap::ArgAction::SetTrue)
+            .conflicts_with_all([
+                "output-directory",
+                "auto-dir",
+                "mbox",
+                "numbered-files",
+                "suffix",
+                "filename-template",
+            ]),
+        Arg::new("mbox")
+            .long("mbox")
+            .help("Write all emails to <file> as a single mbox")
+            .long_help(
+                "Write all emails to <file> as a single mbox instead of writing one \
+                 file per email.",
+            )
+            .num_args(1)
+            .value_name("file")
+            .value_hint(clap::ValueHint::FilePath)
+            .value_parser(clap::value_parser!(PathBuf))
+            .conflicts_with_all([
+                "output-directory",
+                "auto-dir",
+                "numbered-files",
+                "suffix",
+                "filename-template",
+            ]),
+        Arg::new("maildir")
+            .long("maildir")
+            .help("Deliver all emails to the Maildir <dir>")
+            .long_help(
+                "Deliver each email as a new message in the Maildir <dir> instead of \
+                 writing one file per email. The `cur`, `new`, and `tmp` \
+                 subdirectories are created as needed and the messages are delivered \
+                 to `new`, such that the series may be reviewed and sent with \
+                 Maildir-based mail clients such as mutt or notmuch.",
+            )
+            .num_args(1)
+            .value_name("dir")
+            .value_hint(clap::ValueHint::DirPath)
+            .value_parser(clap::value_parser!(PathBuf))
+            .conflicts_with_all([
+                "output-directory",
+                "auto-dir",
+                "stdout",
+                "mbox",
+                "numbered-files",
+                "suffix",
+                "filename-template",
+            ]),
         Arg::new("cover-letter")
             .long("cover-letter")
             .help("Generate a cover letter")
             .long_help(
                 "In addition to the patches, generate a cover letter file containing \
-                 the branch description, shortlog and the overall diffstat. You can \
-                 fill in a description in the file before sending it out.",
+                 the branch description, a summary line for each patch, shortlog and \
+                 the overall diffstat. You can fill in a description in the file \
+                 before sending it out.\n\
+                 \n\
+                 Notes recorded in a patch's metadata with the `Cover-Note` key are \
+                 listed below the patch's summary line. Patch metadata is stored as \
+                 notes in `refs/notes/stgit`, so a cover note may be added with, e.g., \
+                 `git notes --ref=refs/notes/stgit append -m \"Cover-Note: <note>\" \
+                 $(stg id <patch>)`.\n\
+                 \n\
+                 The cover letter is sent to the union of the patches' recipients. When \
+                 patches have recipients of their own, from the `To` and `Cc` keys of \
+                 their metadata, '--to-cmd', '--cc-cmd', or their trailers, these \
+                 per-patch recipients are also listed in the cover letter's body.",
             )
             .action(clap::ArgAction::SetTrue),
+        Arg::new("cover-from-description")
+            .long("cover-from-description")
+            .help("Generate parts of the cover letter based on the branch description")
+            .long_help(
+                "Controls which parts of the cover letter will be automatically \
+                 populated using the branch's description.\n\
+                 \n\
+                 If <mode> is `message` or `default`, the cover letter subject will be \
+                 populated with placeholder text. The body of the cover letter will be \
+                 populated with the branch's description. This is the default mode \
+                 when no configuration nor command line option is specified.\n\
+                 \n\
+                 If <mode> is `subject`, the first paragraph of the branch description \
+                 will populate the cover letter subject. The remainder of the \
+                 description will populate the body of the cover letter.\n\
+                 \n\
+                 If <mode> is `auto`, if the first paragraph of the branch description \
+                 is greater than 100 bytes, then the mode will be `message`, otherwise \
+                 `subject` will be used.\n\
+                 \n\
+                 If <mode> is `none`, both the cover letter subject and body will be \
+                 populated with placeholder text.\n\
+                 \n\
+                 The `format.coverFromDescription` configuration value provides the \
+                 default mode.",
+            )
+            .value_name("mode")
+            .num_args(1)
+            .value_parser(["message", "default", "subject", "auto", "none"]),
+        Arg::new("cover-template")
+            .long("cover-template")
+            .help("Use the contents of <file> in place of the branch description")
+            .long_help(
+                "Populate the cover letter from the contents of <file> instead of the \
+                 branch's description, e.g. to start every version of a series from \
+                 the same introduction. The '--cover-from-description' mode determines \
+                 how the subject and body are taken from the file. Implies \
+                 '--cover-letter'.",
+            )
+            .num_args(1)
+            .value_name("file")
+            .value_hint(clap::ValueHint::FilePath)
+            .value_parser(clap::value_parser!(PathBuf)),
+        Arg::new("reuse-cover")
+            .long("reuse-cover")
+            .help("Reuse the subject and body of the previously sent cover letter")
+            .long_help(
+                "Populate the cover letter with the subject and introductory text of \
+                 the cover letter most recently sent for this branch with `stg email \
+                 send`, e.g. when sending a reroll of the series. The patch summary, \
+                 shortlog, and diffstat are generated anew. Implies '--cover-letter'.",
+            )
+            .action(clap::ArgAction::SetTrue)
+            .conflicts_with_all(["cover-template", "cover-from-description"]),
         Arg::new("numbered")
             .long("numbered")
             .short('n')
@@ -148,11 +364,12 @@ fn format_options() -> Vec<Arg> {
             .num_args(1),
         Arg::new("rfc")
             .long("rfc")
-            .help("Use [RFC PATCH] instead of [PATCH]")
+            .help("Prepend \"RFC\" to the subject prefix")
             .long_help(
-                "Alias for '--subject-prefix=\"RFC PATCH\"'. RFC means \"Request For \
-                 Comments\"; use this when sending an experimental patch for \
-                 discussion rather than application.",
+                "Prepend \"RFC\" to the subject prefix, e.g. \"[RFC PATCH]\" by default \
+                 or \"[RFC PATCH net-next]\" with a \"PATCH net-next\" prefix. RFC \
+                 means \"Request For Comments\"; use this when sending an experimental \
+                 patch for discussion rather than application.",
             )
             .action(clap::ArgAction::SetTrue),
         Arg::new("subject-prefix")
@@ -161,7 +378,12 @@ fn format_options() -> Vec<Arg> {
             .long_help(
                 "Instead of the standard `[PATCH]` prefix in the subject line, instead \
                  use `[<prefix>]`. This allows for useful naming of a patch series, \
-                 and can be combined with the '--numbered' option.",
+                 and can be combined with the '--numbered' option.\n\
+                 \n\
+                 Without this option, the prefix is taken from the \
+                 `branch.<name>.stgit.subjectprefix` configuration variable of the \
+                 stack's branch, or else from `format.subjectPrefix`. The '--rfc' \
+                 option prepends \"RFC\" to whichever prefix is used.",
             )
             .value_name("prefix")
             .num_args(1),
@@ -206,6 +428,7 @@ fn format_options() -> Vec<Arg> {
                 "Do not strip/add `[PATCH]` from the first line of the commit log \
                  message.",
             )
+            .conflicts_with_all(["rfc", "subject-prefix"])
             .action(clap::ArgAction::SetTrue),
         Arg::new("no-binary")
             .long("no-binary")
@@ -225,13 +448,33 @@ fn format_options() -> Vec<Arg> {
                  hash of the commit.",
             )
             .action(clap::ArgAction::SetTrue),
+        Arg::new("patch-names")
+            .long("patch-names")
+            .help("Embed the StGit patch names in the emails")
+            .long_help(
+                "Add an `X-StGit-Patch: <name>` header with the StGit patch name to \
+                 each patch email and derive the output file names from the patch \
+                 names instead of the patches' subjects, e.g. \
+                 `0001-add-makefile.patch` for a patch named `add-makefile`. When the \
+                 emails are imported with `stg import`, the patches are given their \
+                 original names. The `stgit.email.patch-names` configuration value \
+                 may be set true to always embed the patch names.",
+            )
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("skip-empty")
+            .long("skip-empty")
+            .help("Skip empty patches")
+            .long_help(
+                "Leave out selected patches that make no changes instead of failing. \
+                 An informational message is printed for each skipped patch.",
+            )
+            .action(clap::ArgAction::SetTrue),
         // NO --filename-max-length
-        // NO --cover-from-description
         // NO --ignore-if-in-upstream
     ]
 }
 
-fn message_options() -> Vec<Arg> {
+pub(super) fn message_options() -> Vec<Arg> {
     vec![
         Arg::new("to")
             .long("to")
@@ -240,7 +483,13 @@ fn message_options() -> Vec<Arg> {
                 "Add a `To:` header to the email headers. This is in addition to any \
                  configured headers, and may be used multiple times. The negated form \
                  '--no-to' discards all `To:` headers added so far (from config or \
-                 command line).",
+                 command line).\n\
+                 \n\
+                 Alias names from `sendemail.aliasesFile` and group names from \
+                 `stgit.email.group.<name>` are expanded to their addresses.\n\
+                 \n\
+                 Addresses recorded with the `To` and `Cc` keys of a patch's metadata \
+                 are added to that patch's email only.",
             )
             .value_name("address")
             .num_args(1)
@@ -259,7 +508,7 @@ fn message_options() -> Vec<Arg> {
                 "Add a `Cc:` header to the email headers. This is in addition to any \
                  configured headers, and may be used multiple times. The negated form \
                  '--no-cc' discards all `Cc:` headers added so far (from config or \
-                 command line).",
+                 command line). Aliases and groups are expanded as for '--to'.",
             )
             .value_name("address")
             .num_args(1)
@@ -271,6 +520,69 @@ fn message_options() -> Vec<Arg> {
             .help("Discard all Cc: addresses added so far")
             .long_help("Discard all `Cc:` addresses added so far from config or command line.")
             .action(clap::ArgAction::SetTrue),
+        Arg::new("no-auto-cc")
+            .long("no-auto-cc")
+            .help("Do not Cc addresses from patch trailers")
+            .long_help(
+                "By default, the addresses in each patch's `Cc:` trailers and trailers \
+                 ending in `-by`, such as `Acked-by:`, `Reviewed-by:`, or \
+                 `Signed-off-by:`, are added to the `Cc:` header of that patch's \
+                 email. The cover letter is Cc'd to the addresses from all the \
+                 patches' trailers. The committer's own address is never added.\n\
+                 \n\
+                 This option disables adding these addresses. Setting \
+                 `stgit.email.auto-cc` to false also disables this behavior.",
+            )
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("suppress-cc")
+            .long("suppress-cc")
+            .help("Do not Cc addresses from <trailer> trailers")
+            .long_help(
+                "Do not add the addresses from each patch's <trailer> trailers, e.g. \
+                 `Reported-by`, to the `Cc:` header of that patch's email. Trailer \
+                 names are case-insensitive. As with `git send-email`, the categories \
+                 'sob' (`Signed-off-by:` trailers), 'misc-by' (other trailers ending \
+                 in `-by`), 'bodycc' (`Cc:` trailers), and 'all' may also be given. \
+                 This option may be specified multiple times.\n\
+                 \n\
+                 The default is the values of `stgit.email.suppress-cc`.",
+            )
+            .value_name("trailer")
+            .action(clap::ArgAction::Append)
+            .value_parser(clap::builder::NonEmptyStringValueParser::new()),
+        Arg::new("to-cmd")
+            .long("to-cmd")
+            .help("Add To: addresses output by <command> for each patch")
+            .long_help(
+                "Run <command> for each patch to determine additional `To:` \
+                 addresses for the patch's email. The command is run by the shell with \
+                 the path to a file containing the patch as its argument and must \
+                 output one address per line. A trailing parenthesized comment on an \
+                 output line, such as the role annotations output by the Linux \
+                 kernel's `scripts/get_maintainer.pl`, is ignored. The cover letter \
+                 is addressed to the addresses output for all the patches.\n\
+                 \n\
+                 The default is the value of `stgit.email.tocmd`.",
+            )
+            .value_name("command")
+            .num_args(1)
+            .value_parser(clap::builder::NonEmptyStringValueParser::new())
+            .value_hint(clap::ValueHint::CommandString),
+        Arg::new("cc-cmd")
+            .long("cc-cmd")
+            .help("Add Cc: addresses output by <command> for each patch")
+            .long_help(
+                "Run <command> for each patch to determine additional `Cc:` \
+                 addresses for the patch's email, in the same manner as '--to-cmd'. \
+                 The cover letter is Cc'd to the addresses output for all the \
+                 patches.\n\
+                 \n\
+                 The default is the value of `stgit.email.cccmd`.",
+            )
+            .value_name("command")
+            .num_args(1)
+            .value_parser(clap::builder::NonEmptyStringValueParser::new())
+            .value_hint(clap::ValueHint::CommandString),
         Arg::new("in-reply-to")
             .long("in-reply-to")
             .help("Make first mail a reply to <message-id>")
@@ -282,20 +594,42 @@ fn message_options() -> Vec<Arg> {
             .value_name("message-id")
             .num_args(1)
             .value_parser(clap::builder::NonEmptyStringValueParser::new()),
+        Arg::new("reply-to-previous")
+            .long("reply-to-previous")
+            .help("Make first mail a reply to the previous version of the series")
+            .long_help(
+                "Make the first mail appear as a reply to the first mail of the \
+                 previously formatted version of the series. The Message-Id of the \
+                 first mail of each formatted version of the series, i.e. of each \
+                 '--reroll-count', is recorded for the branch. The most recently \
+                 formatted version other than the current \
+                 '--reroll-count' is replied to.",
+            )
+            .conflicts_with("in-reply-to")
+            .action(clap::ArgAction::SetTrue),
         Arg::new("add-header")
             .long("add-header")
             .help("Add an arbitrary email header")
             .long_help(
                 "Add an arbitrary header to the email headers. This is in addition to \
                  any configured headers, and may be used multiple times. For example, \
-                 '--add-header=\"Organization: git-foo\"'.",
-                // "The negated form '--no-add-header' discards all (`To:`, `Cc:`, \
-                //  and custom)  headers added so far from config or command line."
+                 '--add-header=\"Organization: git-foo\"'. The negated form \
+                 '--no-add-header' discards all (`To:`, `Cc:`, and custom) headers \
+                 added so far from config or command line.",
             )
             .value_name("header")
             .num_args(1)
             .value_parser(clap::builder::NonEmptyStringValueParser::new())
             .action(clap::ArgAction::Append),
+        Arg::new("no-add-header")
+            .long("no-add-header")
+            .help("Discard all headers added so far")
+            .long_help(
+                "Discard all `To:`, `Cc:`, and custom headers added so far from config \
+                 or command line. Headers added by subsequent '--to', '--cc', and \
+                 '--add-header' options are kept.",
+            )
+            .action(clap::ArgAction::SetTrue),
         // N.B. not supporting the optional mime-boundary value
         Arg::new("attach")
             .long("attach")
@@ -315,6 +649,7 @@ fn message_options() -> Vec<Arg> {
                  commit message and the patch itself in the second part, with \
                  `Content-Disposition: inline`.",
             )
+            .conflicts_with("attach")
             .action(clap::ArgAction::SetTrue),
         Arg::new("thread")
             .long("thread")
@@ -336,7 +671,7 @@ fn message_options() -> Vec<Arg> {
                  `shallow`.\n\
                  \n\
                  Beware that the default for `git send-email` is to thread emails \
-                 itself. If you want `git format-patch` to take care of threading, you \
+                 itself. If you want `stg email format` to take care of threading, you \
                  will want to ensure that threading is disabled for `git send-email`.",
             )
             .value_name("style")
@@ -392,8 +727,8 @@ fn message_options() -> Vec<Arg> {
                  differences between the previous version of the patch series and the \
                  series currently being formatted. <rev> is a single revision naming \
                  the tip of the previous series which shares a common base with the \
-                 series being formatted (for example `git format-patch --cover-letter \
-                 --interdiff=feature/v1 -3 feature/v2`).",
+                 series being formatted (for example `stg email format --cover-letter \
+                 --interdiff=feature-v1 --all`).",
             )
             .num_args(1)
             .value_name("rev")
@@ -408,15 +743,10 @@ fn message_options() -> Vec<Arg> {
                  version of the patch series and the series currently being formatted. \
                  <refspec> can be a single revision naming the tip of the previous \
                  series if it shares a common base with the series being formatted \
-                 (for example `git format-patch --cover-letter --range-diff=feature/v1 \
-                 -3 feature/v2`), or a revision range if the two versions of the \
-                 series are disjoint (for example `git format-patch --cover-letter \
-                 --range-diff=feature/v1~3..feature/v1 -3 feature/v2`).\n\
-                 \n\
-                 Note that diff options passed to the command affect how the primary \
-                 product of `format-patch` is generated, and they are not passed to \
-                 the underlying `range-diff` machinery used to generate the \
-                 cover-letter material (this may change in the future).",
+                 (for example `stg email format --cover-letter --range-diff=feature-v1 \
+                 --all`), or a revision range if the two versions of the series are \
+                 disjoint (for example `stg email format --cover-letter \
+                 --range-diff=feature-v1~3..feature-v1 --all`).",
             )
             .num_args(1)
             .value_name("refspec")
@@ -430,32 +760,223 @@ fn message_options() -> Vec<Arg> {
                  adjusting the creation/deletion cost fudge factor. See \
                  git-range-diff(1)) for details.",
             )
+            .requires("range-diff")
             .num_args(1)
             .value_name("n")
             .value_parser(clap::builder::NonEmptyStringValueParser::new()),
         // NO --from
-        // NO --no-add-header
     ]
 }
 
 pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
+    if let Some(mut values) = matches.get_many::<String>("git-format-patch-opt") {
+        let value = values.next().expect("at least one value is present");
+        return Err(anyhow!(
+            "`--git-opt {value}`: passing options to `git format-patch` is no longer \
+             supported since emails are formatted natively; use the equivalent `stg email \
+             format` option, e.g. `--thread`, `--reroll-count`, or `--subject-prefix`, \
+             or '--diff-opt' for diff options"
+        ));
+    }
+
     let repo = git_repository::Repository::open()?;
+    let (stack, options, emails) = format_selected(matches, &repo)?;
+
+    let mut stdout = std::io::stdout();
+    if matches.get_flag("stdout") || matches.contains_id("mbox") {
+        let mbox = concat_mbox(&emails);
+        if let Some(path) = matches.get_one::<PathBuf>("mbox") {
+            std::fs::write(path, &mbox)
+                .with_context(|| format!("writing `{}`", path.to_string_lossy()))?;
+            if !options.quiet {
+                writeln!(stdout, "{}", path.to_string_lossy())?;
+            }
+        } else {
+            stdout.write_all(&mbox)?;
+        }
+    } else if let Some(maildir) = matches.get_one::<PathBuf>("maildir") {
+        for path in deliver_to_maildir(maildir, &emails)? {
+            if !options.quiet {
+                writeln!(stdout, "{}", path.to_string_lossy())?;
+            }
+        }
+    } else {
+        let mut seen = std::collections::HashSet::new();
+        for email in &emails {
+            if !seen.insert(email.file_name.as_str()) {
+                return Err(anyhow!(
+                    "output file name `{}` is used for more than one email",
+                    email.file_name
+                ));
+            }
+        }
+
+        if let Some(output_dir) = options.output_dir.as_ref() {
+            std::fs::create_dir_all(output_dir).with_context(|| {
+                format!(
+                    "creating output directory `{}`",
+                    output_dir.to_string_lossy()
+                )
+            })?;
+        }
+
+        for email in &emails {
+            let path = if let Some(output_dir) = options.output_dir.as_ref() {
+                output_dir.join(&email.file_name)
+            } else {
+                PathBuf::from(&email.file_name)
+            };
+            std::fs::write(&path, &email.content)
+                .with_context(|| format!("writing `{}`", path.to_string_lossy()))?;
+            if !options.quiet {
+                writeln!(stdout, "{}", path.to_string_lossy())?;
+            }
+        }
+    }
+
+    if repo
+        .config_snapshot()
+        .boolean("stgit.email.record-message-id")
+        .unwrap_or(true)
+    {
+        record_message_ids(&stack, &emails)?;
+        if let Some(email) = emails.first() {
+            stack.record_series_message_id(options.series_version(), &email.message_id)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Directory in which `--auto-dir` stores each formatted version of a branch's series.
+pub(super) fn outgoing_dir(
+    repo: &git_repository::Repository,
+    branch_name: &str,
+) -> Result<PathBuf> {
+    let work_dir = repo
+        .work_dir()
+        .ok_or_else(|| anyhow!("versioned output directories require a worktree"))?;
+    Ok(work_dir.join("outgoing").join(branch_name))
+}
+
+/// Find the newest `v<n>` version directory in `outgoing_dir`, if any.
+pub(super) fn latest_outgoing_version(outgoing_dir: &Path) -> Result<Option<(usize, PathBuf)>> {
+    let entries = match std::fs::read_dir(outgoing_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("reading `{}`", outgoing_dir.display())),
+    };
+    let mut latest: Option<(usize, PathBuf)> = None;
+    for entry in entries {
+        let entry = entry?;
+        let version = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.strip_prefix('v'))
+            .and_then(|version| version.parse::<usize>().ok());
+        if let Some(version) = version {
+            if entry.path().is_dir() && latest.as_ref().map_or(true, |(v, _)| version > *v) {
+                latest = Some((version, entry.path()));
+            }
+        }
+    }
+    Ok(latest)
+}
+
+/// Format the patches selected by the command line as a single mbox.
+///
+/// Unlike `stg email format`, no files are written and no Message-Ids are recorded in
+/// the patches' metadata.
+pub(super) fn format_mbox(matches: &clap::ArgMatches) -> Result<Vec<u8>> {
+    let repo = git_repository::Repository::open()?;
+    let (_, _, emails) = format_selected(matches, &repo)?;
+    Ok(concat_mbox(&emails))
+}
+
+/// Concatenate emails into a single mbox, separating each email with a blank line.
+fn concat_mbox(emails: &[Email]) -> Vec<u8> {
+    let mut mbox: Vec<u8> = Vec::new();
+    for email in emails {
+        mbox.extend_from_slice(&email.content);
+        if !email.content.ends_with(b"\n\n") {
+            mbox.push(b'\n');
+        }
+    }
+    mbox
+}
+
+/// Deliver emails as new messages in a Maildir, returning the paths of the messages.
+///
+/// Each message is first written to the Maildir's `tmp` directory and then moved to
+/// `new`. The message file names follow the Maildir convention of
+/// `<seconds>.<unique>.<hostname>` and sort in the order of the emails.
+fn deliver_to_maildir(maildir: &Path, emails: &[Email]) -> Result<Vec<PathBuf>> {
+    for subdir in ["cur", "new", "tmp"] {
+        let path = maildir.join(subdir);
+        std::fs::create_dir_all(&path)
+            .with_context(|| format!("creating Maildir directory `{}`", path.display()))?;
+    }
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    let hostname = std::env::var("HOSTNAME")
+        .ok()
+        .filter(|hostname| !hostname.is_empty())
+        .unwrap_or_else(|| "localhost".to_string())
+        .replace('/', "\\057")
+        .replace(':', "\\072");
+    let pid = std::process::id();
+
+    let mut paths = Vec::with_capacity(emails.len());
+    for (i, email) in emails.iter().enumerate() {
+        let name = format!(
+            "{}.M{}P{pid}Q{:04}.{hostname}",
+            now.as_secs(),
+            now.subsec_micros(),
+            i + 1
+        );
+        let tmp_path = maildir.join("tmp").join(&name);
+        let new_path = maildir.join("new").join(&name);
+
+        // Maildir messages do not have the mbox "From " separator line.
+        let content = if email.content.starts_with(b"From ") {
+            email
+                .content
+                .find_byte(b'\n')
+                .map_or(&email.content[..], |pos| &email.content[pos + 1..])
+        } else {
+            &email.content[..]
+        };
+        std::fs::write(&tmp_path, content)
+            .with_context(|| format!("writing `{}`", tmp_path.display()))?;
+        std::fs::rename(&tmp_path, &new_path)
+            .with_context(|| format!("delivering `{}`", new_path.display()))?;
+        paths.push(new_path);
+    }
+    Ok(paths)
+}
+
+/// Format emails for the patches selected by the command line.
+fn format_selected<'repo>(
+    matches: &clap::ArgMatches,
+    repo: &'repo git_repository::Repository,
+) -> Result<(Stack<'repo>, FormatOptions, Vec<Email>)> {
     let stack = Stack::from_branch(
-        &repo,
+        repo,
         argset::get_one_str(matches, "branch"),
         InitializationPolicy::AllowUninitialized,
     )?;
 
     let patches =
         if let Some(range_specs) = matches.get_many::<patchrange::Specification>("patchranges") {
-            let patches = patchrange::contiguous_patches_from_specs(
+            let mut patches = patchrange::patches_from_specs(
                 range_specs,
                 &stack,
                 patchrange::Allow::VisibleWithAppliedBoundary,
             )?;
-            if patches.is_empty() {
-                return Err(anyhow!("no patches to format"));
-            }
+            let series: Vec<&PatchName> = stack.all_patches().collect();
+            patches.sort_by_key(|pn| series.iter().position(|&other| other == pn));
             patches
         } else if matches.get_flag("all") {
             let applied = stack.applied();
@@ -467,64 +988,1795 @@ pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
             panic!("expect either patchranges or -a/--all")
         };
 
-    for patchname in &patches {
-        if stack.get_patch_commit(patchname).is_no_change()? {
-            return Err(anyhow!("cannot format empty patch `{patchname}`"));
+    let mut selected = Vec::with_capacity(patches.len());
+    for patchname in patches {
+        if !stack.get_patch_commit(&patchname)?.is_no_change()? {
+            selected.push(patchname);
+        } else if matches.get_flag("skip-empty") {
+            crate::print_info_message(matches, &format!("skipping empty patch `{patchname}`"));
+        } else {
+            return Err(anyhow!(
+                "cannot format empty patch `{patchname}`; use '--skip-empty' to leave it out"
+            ));
         }
     }
+    let patches = selected;
+    if patches.is_empty() {
+        return Err(anyhow!("no patches to format"));
+    }
 
-    let mut format_args: Vec<(usize, String)> = Vec::new();
+    let config = repo.config_snapshot();
+    let git_version = repo.stupid().version()?;
+    let git_version = git_version
+        .strip_prefix("git version ")
+        .unwrap_or(&git_version);
 
-    // This dummy command is constructed with just the Args that are to be
-    // passed-through directly to `git format-patch`.
-    let mut dummy_command = clap::Command::new("dummy")
-        .args(format_options())
-        .args(message_options());
-    dummy_command.build();
-
-    for arg in dummy_command.get_arguments() {
-        let arg_id = arg.get_id().as_str();
-        if matches!(
-            matches.value_source(arg_id),
-            Some(clap::parser::ValueSource::CommandLine)
-        ) {
-            let num_args = arg.get_num_args().expect("built Arg's num_args is Some");
-            let long = arg.get_long().expect("passthrough arg has long option");
-            let indices = matches.indices_of(arg_id).expect("value source is cmdline");
-            if num_args.takes_values() {
-                let values = matches.get_many::<String>(arg_id).unwrap();
-                assert!(indices.len() == values.len());
-                indices.into_iter().zip(values).for_each(|(index, value)| {
-                    if arg_id == "thread" && value.is_empty() {
-                        format_args.push((index, format!("--{long}")));
-                    } else {
-                        format_args.push((index, format!("--{long}={value}")));
+    // The auto-dir argument is not defined for `stg email preview`.
+    let mut reroll_count = argset::get_one_str(matches, "reroll-count").map(String::from);
+    let auto_dir = if matches.try_get_one::<bool>("auto-dir").ok().flatten() == Some(&true) {
+        let outgoing_dir = outgoing_dir(repo, stack.get_branch_name())?;
+        let version = if let Some(reroll_count) = reroll_count.as_ref() {
+            reroll_count.clone()
+        } else {
+            let recorded = stack
+                .series_message_ids()?
+                .into_iter()
+                .filter_map(|(version, _)| version.parse::<usize>().ok())
+                .max();
+            let archived = latest_outgoing_version(&outgoing_dir)?.map(|(version, _)| version);
+            let version = recorded.max(archived).unwrap_or(0) + 1;
+            if version > 1 {
+                reroll_count = Some(version.to_string());
+            }
+            version.to_string()
+        };
+        Some(outgoing_dir.join(format!("v{version}")))
+    } else {
+        None
+    };
+
+    let mut options = FormatOptions::new(
+        matches,
+        &config,
+        stack.get_branch_name(),
+        &patches,
+        reroll_count,
+        git_version,
+    )?;
+    if auto_dir.is_some() {
+        options.output_dir = auto_dir;
+    }
+    if matches.get_flag("reply-to-previous") {
+        let version = options.series_version();
+        let (_, message_id) = stack
+            .series_message_ids()?
+            .into_iter()
+            .rev()
+            .find(|(v, _)| v != version)
+            .ok_or_else(|| anyhow!("no previously formatted version of the series to reply to"))?;
+        options.in_reply_to = Some(message_id);
+    }
+    let emails = SeriesFormatter::new(&stack, &patches, &options, git_version)?.format()?;
+
+    Ok((stack, options, emails))
+}
+
+/// Threading style for the formatted emails.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ThreadStyle {
+    /// Every email is a reply to the head of the series.
+    Shallow,
+
+    /// Every email is a reply to the previous email.
+    Deep,
+}
+
+/// How the branch description populates the cover letter.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CoverFromDescription {
+    /// The description is the cover letter body; the subject is a placeholder.
+    Message,
+
+    /// The description's first paragraph is the subject and the rest is the body.
+    Subject,
+
+    /// Like `Subject` if the first paragraph is short enough, else like `Message`.
+    Auto,
+
+    /// Both the subject and body are placeholders.
+    None,
+}
+
+impl FromStr for CoverFromDescription {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "message" | "default" => Ok(Self::Message),
+            "subject" => Ok(Self::Subject),
+            "auto" => Ok(Self::Auto),
+            "none" => Ok(Self::None),
+            _ => Err(anyhow!("invalid cover-from-description mode `{s}`")),
+        }
+    }
+}
+
+/// Email formatting options gathered from the command line and `format.*` config.
+struct FormatOptions {
+    output_dir: Option<PathBuf>,
+    cover_letter: bool,
+    cover_from_description: CoverFromDescription,
+    /// Contents of the '--cover-template' file, used in place of the branch description.
+    cover_template: Option<String>,
+    reuse_cover: bool,
+    numbered: bool,
+    start_number: usize,
+    reroll_count: Option<String>,
+    /// Subject prefix, e.g. "PATCH v2", or `None` when the subject is to be kept as-is.
+    subject_prefix: Option<String>,
+    signoff: bool,
+    numbered_files: bool,
+    /// Whether to add `X-StGit-Patch` headers and name files after the patches.
+    patch_names: bool,
+    suffix: String,
+    filename_template: Option<String>,
+    binary: bool,
+    zero_commit: bool,
+    /// Additional headers from `format.headers` and '--add-header', one per line.
+    extra_headers: String,
+    to: Vec<String>,
+    cc: Vec<String>,
+    /// Whether to Cc the addresses from each patch's trailers.
+    auto_cc: bool,
+    /// Lower-case trailer names or categories whose addresses are not Cc'd.
+    suppress_cc: Vec<String>,
+    /// Command run for each patch to determine additional To: addresses.
+    to_cmd: Option<String>,
+    /// Command run for each patch to determine additional Cc: addresses.
+    cc_cmd: Option<String>,
+    in_reply_to: Option<String>,
+    /// Content-Disposition of the patch attachment when the patch is to be attached.
+    attach: Option<&'static str>,
+    thread: Option<ThreadStyle>,
+    signature: Option<String>,
+    base: Option<String>,
+    progress: bool,
+    interdiff: Option<String>,
+    range_diff: Option<String>,
+    creation_factor: Option<String>,
+    rename_opt: Option<&'static str>,
+    /// Options from `stgit.diff.*`, `stgit.diff-opts`, and '--diff-opt' passed to
+    /// `git diff-tree`.
+    diff_opts: Vec<String>,
+    quiet: bool,
+}
+
+impl FormatOptions {
+    fn new(
+        matches: &clap::ArgMatches,
+        config: &git_repository::config::Snapshot,
+        branch_name: &str,
+        patches: &[PatchName],
+        reroll_count: Option<String>,
+        git_version: &str,
+    ) -> Result<Self> {
+        let config_string = |key: &str| {
+            config
+                .string(key)
+                .and_then(|value| value.to_str().ok().map(String::from))
+        };
+
+        let cover_template = matches
+            .get_one::<PathBuf>("cover-template")
+            .map(|path| {
+                std::fs::read_to_string(path)
+                    .with_context(|| format!("reading cover template `{}`", path.display()))
+            })
+            .transpose()?;
+        let reuse_cover = matches.get_flag("reuse-cover");
+
+        let cover_letter = matches.get_flag("cover-letter")
+            || cover_template.is_some()
+            || reuse_cover
+            || match config_string("format.coverLetter").as_deref() {
+                Some("auto") => patches.len() > 1,
+                Some(_) => config.boolean("format.coverLetter").unwrap_or(false),
+                None => false,
+            };
+
+        let cover_from_description =
+            if let Some(mode) = argset::get_one_str(matches, "cover-from-description") {
+                mode.parse()?
+            } else if let Some(mode) = config_string("format.coverFromDescription") {
+                mode.parse().context("format.coverFromDescription")?
+            } else {
+                CoverFromDescription::Message
+            };
+
+        let numbered = if matches.get_flag("numbered") {
+            true
+        } else if matches.get_flag("no-numbered") {
+            false
+        } else {
+            match config_string("format.numbered").as_deref() {
+                Some("auto") | None => None,
+                Some(_) => config.boolean("format.numbered"),
+            }
+            .unwrap_or(cover_letter || patches.len() > 1)
+        };
+
+        let start_number = if let Some(start_number) = matches.get_one::<String>("start-number") {
+            argset::parse_usize(start_number)?
+        } else {
+            1
+        };
+
+        let subject_prefix = if matches.get_flag("keep-subject") {
+            None
+        } else {
+            let mut prefix = argset::get_one_str(matches, "subject-prefix")
+                .map(String::from)
+                .or_else(|| config_string(&format!("branch.{branch_name}.stgit.subjectprefix")))
+                .or_else(|| config_string("format.subjectPrefix"))
+                .unwrap_or_else(|| "PATCH".to_string());
+            if matches.get_flag("rfc") {
+                prefix = format!("RFC {prefix}");
+            }
+            if let Some(reroll_count) = reroll_count.as_ref() {
+                if !prefix.is_empty() {
+                    prefix.push(' ');
+                }
+                prefix.push('v');
+                prefix.push_str(reroll_count);
+            }
+            Some(prefix)
+        };
+
+        let mut extra_headers = String::new();
+        let mut add_header = |header: &str| {
+            let header = header.trim_end();
+            if !header.is_empty() {
+                extra_headers.push_str(header);
+                extra_headers.push('\n');
+            }
+        };
+        let discard_index = discard_index(matches, "no-add-header");
+        if discard_index.is_none() {
+            if let Some(headers) = config.plumbing().strings_by_key("format.headers") {
+                for header in headers {
+                    for line in header.to_str_lossy().lines() {
+                        add_header(line);
                     }
-                });
+                }
+            }
+        }
+        if let (Some(indices), Some(headers)) = (
+            matches.indices_of("add-header"),
+            matches.get_many::<String>("add-header"),
+        ) {
+            for (index, header) in indices.zip(headers) {
+                if discard_index.map_or(true, |discard_index| index > discard_index) {
+                    add_header(header);
+                }
+            }
+        }
+        let address_book = AddressBook::from_config(config)?;
+        let to = get_recipients(matches, config, &address_book, "to")?;
+        let cc = get_recipients(matches, config, &address_book, "cc")?;
+        let auto_cc = !matches.get_flag("no-auto-cc")
+            && config.boolean("stgit.email.auto-cc").unwrap_or(true);
+        let suppress_cc = if let Some(values) = matches.get_many::<String>("suppress-cc") {
+            values.map(|value| value.to_ascii_lowercase()).collect()
+        } else if let Some(values) = config.plumbing().strings_by_key("stgit.email.suppress-cc") {
+            values
+                .iter()
+                .map(|value| {
+                    value
+                        .to_str()
+                        .map(|value| value.trim().to_ascii_lowercase())
+                        .map_err(|_| anyhow!("`stgit.email.suppress-cc` is not valid UTF-8"))
+                })
+                .collect::<Result<_>>()?
+        } else {
+            Vec::new()
+        };
+
+        let in_reply_to = argset::get_one_str(matches, "in-reply-to").map(|message_id| {
+            let message_id = message_id.trim();
+            let message_id = message_id.strip_prefix('<').unwrap_or(message_id);
+            let message_id = message_id.strip_suffix('>').unwrap_or(message_id);
+            format!("<{message_id}>")
+        });
+
+        let attach = if matches.get_flag("inline") {
+            Some("inline")
+        } else if matches.get_flag("attach") {
+            Some("attachment")
+        } else {
+            None
+        };
+
+        let thread = if matches.get_flag("no-thread") {
+            None
+        } else {
+            let config_thread = config_string("format.thread").and_then(|value| {
+                match value.to_ascii_lowercase().as_str() {
+                    "shallow" => Some(ThreadStyle::Shallow),
+                    "deep" => Some(ThreadStyle::Deep),
+                    _ => config
+                        .boolean("format.thread")
+                        .and_then(|thread| thread.then_some(ThreadStyle::Shallow)),
+                }
+            });
+            match argset::get_one_str(matches, "thread") {
+                Some("shallow") => Some(ThreadStyle::Shallow),
+                Some("deep") => Some(ThreadStyle::Deep),
+                Some(_) => config_thread.or(Some(ThreadStyle::Shallow)),
+                None => config_thread,
+            }
+        };
+
+        let read_signature_file = |path: &str| {
+            std::fs::read_to_string(path)
+                .with_context(|| format!("reading signature file `{path}`"))
+        };
+        let signature = if matches.get_flag("no-signature") {
+            None
+        } else if let Some(path) = argset::get_one_str(matches, "signature-file") {
+            Some(read_signature_file(path)?)
+        } else if let Some(signature) = argset::get_one_str(matches, "signature") {
+            Some(signature.to_string())
+        } else if let Some(path) = config_string("format.signatureFile") {
+            Some(read_signature_file(&path)?)
+        } else if let Some(signature) = config_string("format.signature") {
+            Some(signature)
+        } else {
+            Some(git_version.to_string())
+        }
+        .filter(|signature| !signature.is_empty());
+
+        // The output arguments are not defined for `stg email preview`.
+        let output_flag = |id: &str| {
+            matches
+                .try_get_one::<bool>(id)
+                .ok()
+                .flatten()
+                .copied()
+                .unwrap_or(false)
+        };
+        let output_str = |id: &str| matches.try_get_one::<String>(id).ok().flatten().cloned();
+
+        let rename_opt = match config_string("diff.renames").as_deref() {
+            Some("copies" | "copy") => Some("-C"),
+            Some(_) if config.boolean("diff.renames") == Some(false) => None,
+            _ => Some("-M"),
+        };
+
+        Ok(Self {
+            output_dir: output_str("output-directory")
+                .or_else(|| config_string("format.outputDirectory"))
+                .map(PathBuf::from),
+            cover_letter,
+            cover_from_description,
+            cover_template,
+            reuse_cover,
+            numbered,
+            start_number,
+            reroll_count,
+            subject_prefix,
+            signoff: matches.get_flag("signoff")
+                || config.boolean("format.signOff").unwrap_or(false),
+            numbered_files: output_flag("numbered-files"),
+            patch_names: matches.get_flag("patch-names")
+                || config.boolean("stgit.email.patch-names").unwrap_or(false),
+            suffix: output_str("suffix")
+                .or_else(|| config_string("format.suffix"))
+                .unwrap_or_else(|| ".patch".to_string()),
+            filename_template: output_str("filename-template")
+                .or_else(|| config_string("stgit.email.filename-template")),
+            binary: !matches.get_flag("no-binary"),
+            zero_commit: matches.get_flag("zero-commit"),
+            extra_headers,
+            to,
+            cc,
+            auto_cc,
+            suppress_cc,
+            to_cmd: argset::get_one_str(matches, "to-cmd")
+                .map(String::from)
+                .or_else(|| config_string("stgit.email.tocmd")),
+            cc_cmd: argset::get_one_str(matches, "cc-cmd")
+                .map(String::from)
+                .or_else(|| config_string("stgit.email.cccmd")),
+            in_reply_to,
+            attach,
+            thread,
+            signature,
+            base: argset::get_one_str(matches, "base").map(String::from),
+            progress: matches.get_flag("progress"),
+            interdiff: argset::get_one_str(matches, "interdiff").map(String::from),
+            range_diff: argset::get_one_str(matches, "range-diff").map(String::from),
+            creation_factor: argset::get_one_str(matches, "creation-factor").map(String::from),
+            rename_opt,
+            diff_opts: argset::get_diff_opts(matches, config, false, false)?,
+            quiet: output_flag("quiet"),
+        })
+    }
+
+    /// Version of the series being formatted, from '--reroll-count'.
+    fn series_version(&self) -> &str {
+        self.reroll_count.as_deref().unwrap_or("1")
+    }
+
+    /// Label for interdiff and range-diff sections, e.g. "Interdiff against v1".
+    fn reroll_label(&self, label: &str) -> String {
+        match self
+            .reroll_count
+            .as_deref()
+            .and_then(|reroll_count| reroll_count.parse::<usize>().ok())
+        {
+            Some(reroll_count) if reroll_count > 1 => {
+                format!("{label} against v{}:\n", reroll_count - 1)
+            }
+            _ => format!("{label}:\n"),
+        }
+    }
+}
+
+/// Get `--to` or `--cc` recipients, including those from `format.to` or `format.cc`.
+///
+/// The corresponding `--no-to` or `--no-cc` option discards all recipients from
+/// config and those specified on the command line before it.
+fn get_recipients(
+    matches: &clap::ArgMatches,
+    config: &git_repository::config::Snapshot,
+    address_book: &AddressBook,
+    id: &str,
+) -> Result<Vec<String>> {
+    let discard_index =
+        discard_index(matches, &format!("no-{id}")).max(discard_index(matches, "no-add-header"));
+
+    let mut recipients = Vec::new();
+    if discard_index.is_none() {
+        if let Some(values) = config
+            .plumbing()
+            .strings_by_key(format!("format.{id}").as_str())
+        {
+            for value in values {
+                let value = value
+                    .to_str()
+                    .map_err(|_| anyhow!("`format.{id}` is not valid UTF-8"))?;
+                recipients.extend(address_book.expand(value)?);
+            }
+        }
+    }
+    if let (Some(indices), Some(values)) = (matches.indices_of(id), matches.get_many::<String>(id))
+    {
+        for (index, value) in indices.zip(values) {
+            if discard_index.map_or(true, |discard_index| index > discard_index) {
+                recipients.extend(address_book.expand(value)?);
+            }
+        }
+    }
+    Ok(recipients)
+}
+
+/// Get the command line index of a discarding flag such as '--no-to', if given.
+///
+/// Values of the corresponding option appearing before this index are discarded, as
+/// are any values from config.
+fn discard_index(matches: &clap::ArgMatches, id: &str) -> Option<usize> {
+    if matches.get_flag(id) {
+        matches.indices_of(id).and_then(|indices| indices.max())
+    } else {
+        None
+    }
+}
+
+/// A formatted email.
+struct Email {
+    /// Name of the patch formatted in this email, or `None` for the cover letter.
+    patchname: Option<PatchName>,
+
+    /// File name for the email, relative to the output directory.
+    file_name: String,
+
+    /// Message-Id of the email, including angle brackets.
+    message_id: String,
+
+    /// Content of the email in mbox format.
+    content: Vec<u8>,
+}
+
+/// Tracks `In-Reply-To` and `References` headers for a series of emails.
+struct Thread {
+    style: Option<ThreadStyle>,
+    references: Vec<String>,
+}
+
+impl Thread {
+    fn new(style: Option<ThreadStyle>, in_reply_to: Option<&str>) -> Self {
+        Self {
+            style,
+            references: in_reply_to.map(String::from).into_iter().collect(),
+        }
+    }
+
+    /// Get the threading headers for the next email in the series.
+    fn headers(&self) -> String {
+        let mut headers = String::new();
+        if let Some(parent) = self.references.last() {
+            headers.push_str(&format!("In-Reply-To: {parent}\n"));
+            headers.push_str(&format!("References: {}\n", self.references.join("\n\t")));
+        }
+        headers
+    }
+
+    /// Account for the Message-Id of an email that has been added to the series.
+    fn push(&mut self, message_id: &str, is_cover: bool) {
+        match self.style {
+            Some(ThreadStyle::Deep) => self.references.push(message_id.to_string()),
+            Some(ThreadStyle::Shallow) if is_cover || self.references.is_empty() => {
+                self.references.push(message_id.to_string());
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Formats emails for a series of patches.
+struct SeriesFormatter<'a, 'repo> {
+    stack: &'a Stack<'repo>,
+    patches: &'a [PatchName],
+    options: &'a FormatOptions,
+    contiguous: bool,
+    tree_id: git_repository::ObjectId,
+    committer: git_repository::actor::Signature,
+    timestamp: u64,
+    git_version: &'a str,
+}
+
+impl<'a, 'repo> SeriesFormatter<'a, 'repo> {
+    fn new(
+        stack: &'a Stack<'repo>,
+        patches: &'a [PatchName],
+        options: &'a FormatOptions,
+        git_version: &'a str,
+    ) -> Result<Self> {
+        let committer = stack.repo.get_committer()?.to_owned();
+        let (contiguous, tree_id) = series_tree(stack, patches)?;
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        Ok(Self {
+            stack,
+            patches,
+            options,
+            contiguous,
+            tree_id,
+            committer,
+            timestamp,
+            git_version,
+        })
+    }
+
+    /// Format the cover letter, if any, and an email for each patch.
+    fn format(&self) -> Result<Vec<Email>> {
+        let mut thread = Thread::new(self.options.thread, self.options.in_reply_to.as_deref());
+        let mut emails = Vec::with_capacity(self.patches.len() + 1);
+        let recipients = self.cmd_recipients()?;
+        if self.options.cover_letter {
+            let mut cover_recipients = CmdRecipients::default();
+            for patch_recipients in &recipients {
+                cover_recipients.extend(patch_recipients);
+            }
+            let email = self.cover_letter(&thread, &cover_recipients, &recipients)?;
+            thread.push(&email.message_id, true);
+            emails.push(email);
+        }
+        let mut stderr = std::io::stderr();
+        for (i, patchname) in self.patches.iter().enumerate() {
+            if self.options.progress {
+                write!(
+                    stderr,
+                    "\rGenerating patches: {}/{}",
+                    i + 1,
+                    self.patches.len()
+                )?;
+            }
+            let email = self.patch_email(i, patchname, &thread, &recipients[i])?;
+            thread.push(&email.message_id, false);
+            emails.push(email);
+        }
+        if self.options.progress {
+            writeln!(stderr, ", done.")?;
+        }
+        Ok(emails)
+    }
+
+    /// Determine the additional recipients for each patch.
+    ///
+    /// A patch's additional recipients are taken from the `To` and `Cc` fields of its
+    /// metadata and from the output of the '--to-cmd' and '--cc-cmd' commands.
+    /// Addresses that are already recipients are excluded.
+    fn cmd_recipients(&self) -> Result<Vec<CmdRecipients>> {
+        let mut all_recipients = Vec::with_capacity(self.patches.len());
+        let repo = self.stack.repo;
+        let stupid = repo.stupid();
+        let temp_dir = tempfile::tempdir()?;
+        let address_book = AddressBook::from_config(&repo.config_snapshot())?;
+        for patchname in self.patches {
+            let commit = self.stack.get_patch_commit(patchname)?;
+            let patch_meta = PatchMeta::read(repo, commit.id)?;
+            let mut recipients = CmdRecipients::default();
+            for value in patch_meta.get_all(meta::TO) {
+                recipients.to.extend(address_book.expand(value)?);
+            }
+            for value in patch_meta.get_all(meta::CC) {
+                recipients.cc.extend(address_book.expand(value)?);
+            }
+
+            if self.options.to_cmd.is_none() && self.options.cc_cmd.is_none() {
+                all_recipients.push(self.filter_recipients(&recipients));
+                continue;
+            }
+
+            let mut content = commit.message_ex().decode()?.trim_end().as_bytes().to_vec();
+            content.extend_from_slice(b"\n---\n");
+            content.extend_from_slice(&stupid.diff_tree_patch(
+                commit.get_parent_commit()?.tree_id()?.detach(),
+                commit.tree_id()?.detach(),
+                <Option<Vec<&str>>>::None,
+                false,
+                self.diff_opts(),
+            )?);
+            let patch_path = temp_dir.path().join(format!("{patchname}.patch"));
+            std::fs::write(&patch_path, content)?;
+
+            if let Some(to_cmd) = self.options.to_cmd.as_ref() {
+                recipients
+                    .to
+                    .extend(run_recipients_cmd(to_cmd, &patch_path)?);
+            }
+            if let Some(cc_cmd) = self.options.cc_cmd.as_ref() {
+                recipients
+                    .cc
+                    .extend(run_recipients_cmd(cc_cmd, &patch_path)?);
+            }
+            all_recipients.push(self.filter_recipients(&recipients));
+        }
+        Ok(all_recipients)
+    }
+
+    /// Deduplicate a patch's additional recipients, excluding the global recipients.
+    fn filter_recipients(&self, recipients: &CmdRecipients) -> CmdRecipients {
+        let mut filtered = CmdRecipients::default();
+        filtered.extend(recipients);
+        let existing: Vec<String> = self
+            .options
+            .to
+            .iter()
+            .chain(&self.options.cc)
+            .map(|mailbox| mailbox_address(mailbox).to_lowercase())
+            .collect();
+        let is_new =
+            |mailbox: &String| !existing.contains(&mailbox_address(mailbox).to_lowercase());
+        filtered.to.retain(is_new);
+        filtered.cc.retain(is_new);
+        filtered
+    }
+
+    fn base_commit(&self) -> Result<&git_repository::Commit<'repo>> {
+        Ok(self.stack.get_patch_commit(&self.patches[0])?)
+    }
+
+    fn last_commit(&self) -> Result<&git_repository::Commit<'repo>> {
+        Ok(self
+            .stack
+            .get_patch_commit(self.patches.last().expect("patches is not empty"))?)
+    }
+
+    fn base_id(&self) -> Result<git_repository::ObjectId> {
+        Ok(self.base_commit()?.get_parent_commit()?.id)
+    }
+
+    fn diff_opts(&self) -> Vec<&str> {
+        let mut diff_opts = Vec::new();
+        if self.options.binary {
+            diff_opts.push("--binary");
+        }
+        diff_opts.extend(self.stat_opts());
+        diff_opts
+    }
+
+    /// Options for diffstats, which are also used for diffs.
+    fn stat_opts(&self) -> Vec<&str> {
+        let mut stat_opts = Vec::new();
+        if let Some(rename_opt) = self.options.rename_opt {
+            stat_opts.push(rename_opt);
+        }
+        stat_opts.extend(self.options.diff_opts.iter().map(String::as_str));
+        stat_opts
+    }
+
+    /// Build the subject prefix, e.g. "[PATCH v2 3/7] ", for the given message number.
+    fn subject_prefix(&self, number: usize) -> String {
+        if let Some(prefix) = self.options.subject_prefix.as_ref() {
+            let total = self.options.start_number + self.patches.len() - 1;
+            let numbering = if self.options.numbered {
+                format!("{number}/{total}")
             } else {
-                indices.for_each(|index| format_args.push((index, format!("--{long}"))));
+                String::new()
+            };
+            let sep = if prefix.is_empty() || numbering.is_empty() {
+                ""
+            } else {
+                " "
+            };
+            if prefix.is_empty() && numbering.is_empty() {
+                String::new()
+            } else {
+                format!("[{prefix}{sep}{numbering}] ")
             }
+        } else {
+            String::new()
         }
     }
 
-    format_args.sort_by_key(|(index, _)| *index);
+    /// Determine the output file name for an email.
+    fn file_name(&self, number: usize, subject: &str, patchname: &str) -> Result<String> {
+        if let Some(template) = self.options.filename_template.as_ref() {
+            expand_filename_template(
+                template,
+                number,
+                subject,
+                self.options.reroll_count.as_deref(),
+                patchname,
+            )
+        } else if self.options.numbered_files {
+            Ok(number.to_string())
+        } else {
+            // Like `git format-patch`, the name, not including the suffix, is limited to
+            // this many characters.
+            const MAX_LEN: usize = 64;
+            let suffix = &self.options.suffix;
+            let mut file_name = String::new();
+            if let Some(reroll_count) = self.options.reroll_count.as_ref() {
+                file_name.push_str(&sanitize_subject(&format!("v{reroll_count}"), MAX_LEN));
+                file_name.push('-');
+            }
+            let name = if self.options.patch_names {
+                patchname
+            } else {
+                subject
+            };
+            file_name.push_str(&format!(
+                "{number:04}-{}",
+                sanitize_subject(name, usize::MAX)
+            ));
+            file_name.truncate(MAX_LEN.saturating_sub(suffix.len() + 1));
+            file_name.push_str(suffix);
+            Ok(file_name)
+        }
+    }
 
-    let mut format_args = format_args.drain(..).map(|(_, s)| s).collect::<Vec<_>>();
+    fn message_id(&self, unique: &str) -> String {
+        format!(
+            "<{unique}.{}.git.{}>",
+            self.timestamp,
+            self.committer.email.to_str_lossy()
+        )
+    }
 
-    if let Some(values) = matches.get_many::<String>("git-format-patch-opt") {
-        format_args.extend(values.cloned());
+    /// Build the common leading headers of an email.
+    #[allow(clippy::too_many_arguments)]
+    fn headers(
+        &self,
+        commit_id: git_repository::ObjectId,
+        message_id: &str,
+        thread: &Thread,
+        author: &git_repository::actor::Signature,
+        subject_prefix: &str,
+        subject: &str,
+        is_8bit: bool,
+        recipients: &CmdRecipients,
+        auto_cc: &[String],
+    ) -> String {
+        let commit_id = if self.options.zero_commit {
+            git_repository::ObjectId::null(commit_id.kind())
+        } else {
+            commit_id
+        };
+        let mut headers = format!("From {commit_id} Mon Sep 17 00:00:00 2001\n");
+        headers.push_str(&format!("Message-Id: {message_id}\n"));
+        headers.push_str(&thread.headers());
+        headers.push_str(&format!(
+            "From: {}\n",
+            format_mailbox(&author.name.to_str_lossy(), &author.email.to_str_lossy())
+        ));
+        headers.push_str(&format!(
+            "Date: {}\n",
+            author
+                .time
+                .format(git_repository::date::time::format::GIT_RFC2822)
+        ));
+        headers.push_str(&format_subject(subject_prefix, subject));
+        if self.options.attach.is_none() && is_8bit {
+            headers.push_str(
+                "MIME-Version: 1.0\n\
+                 Content-Type: text/plain; charset=UTF-8\n\
+                 Content-Transfer-Encoding: 8bit\n",
+            );
+        }
+        headers.push_str(&self.options.extra_headers);
+        let to: Vec<&str> = self
+            .options
+            .to
+            .iter()
+            .chain(&recipients.to)
+            .map(String::as_str)
+            .collect();
+        if !to.is_empty() {
+            headers.push_str(&format!("To: {}\n", to.join(",\n    ")));
+        }
+        let cc: Vec<&str> = self
+            .options
+            .cc
+            .iter()
+            .chain(&recipients.cc)
+            .chain(auto_cc)
+            .map(String::as_str)
+            .collect();
+        if !cc.is_empty() {
+            headers.push_str(&format!("Cc: {}\n", cc.join(",\n    ")));
+        }
+        headers
     }
 
-    {
-        let base = stack
-            .get_patch_commit(&patches[0])
-            .parent_ids()
-            .next()
-            .unwrap()
-            .detach();
-        let last = stack.get_patch_commit(patches.last().unwrap()).id;
-        format_args.push(format!("{base}..{last}"));
+    /// Get addresses to Cc from the trailers of the given commit messages.
+    ///
+    /// Addresses that are already recipients and the committer's own address are
+    /// excluded.
+    fn auto_cc<'m>(
+        &self,
+        messages: impl IntoIterator<Item = &'m str>,
+        recipients: &CmdRecipients,
+    ) -> Vec<String> {
+        let mut auto_cc = Vec::new();
+        if !self.options.auto_cc {
+            return auto_cc;
+        }
+        let mut seen: std::collections::HashSet<String> = self
+            .options
+            .to
+            .iter()
+            .chain(&self.options.cc)
+            .chain(&recipients.to)
+            .chain(&recipients.cc)
+            .map(|mailbox| mailbox_address(mailbox).to_lowercase())
+            .collect();
+        seen.insert(self.committer.email.to_str_lossy().to_lowercase());
+        for message in messages {
+            for mailbox in trailer_mailboxes(message, &self.options.suppress_cc) {
+                if seen.insert(mailbox_address(&mailbox).to_lowercase()) {
+                    auto_cc.push(mailbox);
+                }
+            }
+        }
+        auto_cc
+    }
+
+    fn signature_block(&self) -> String {
+        if let Some(signature) = self.options.signature.as_ref() {
+            let newline = if signature.ends_with('\n') { "" } else { "\n" };
+            format!("-- \n{signature}{newline}\n")
+        } else {
+            String::new()
+        }
+    }
+
+    /// Build the base tree information block, if requested.
+    fn base_info(&self) -> Result<Option<String>> {
+        let base_spec = if let Some(base_spec) = self.options.base.as_ref() {
+            base_spec
+        } else {
+            return Ok(None);
+        };
+        let repo = self.stack.repo;
+        let base_commit_id = if base_spec == "auto" {
+            self.stack.base().id
+        } else {
+            parse_stgit_revision(repo, Some(base_spec), None)?
+                .try_into_commit()?
+                .id
+        };
+        let series_base_id = self.base_id()?;
+        let stupid = repo.stupid();
+        let mut info = format!("\nbase-commit: {base_commit_id}\n");
+        if base_commit_id != series_base_id {
+            if !stupid
+                .merge_bases(base_commit_id, series_base_id)?
+                .contains(&base_commit_id)
+            {
+                return Err(anyhow!(
+                    "base commit `{base_spec}` is not an ancestor of the patches to format"
+                ));
+            }
+            let mut prerequisites =
+                stupid.rev_list(base_commit_id, series_base_id, <Option<Vec<&str>>>::None)?;
+            prerequisites.reverse();
+            for commit_id in prerequisites {
+                let commit = repo.find_commit(commit_id)?;
+                let diff = stupid.diff_tree_patch(
+                    commit.get_parent_commit()?.tree_id()?.detach(),
+                    commit.tree_id()?.detach(),
+                    <Option<Vec<&str>>>::None,
+                    false,
+                    <Vec<&str>>::new(),
+                )?;
+                if let Some(patch_id) = stupid.patch_id(&diff)? {
+                    info.push_str(&format!("prerequisite-patch-id: {patch_id}\n"));
+                }
+            }
+        }
+        Ok(Some(info))
+    }
+
+    /// Generate interdiff and range-diff sections, if requested.
+    ///
+    /// When `indent` is true, the interdiff is indented as is appropriate for
+    /// commentary in a patch email.
+    fn series_comparison(&self, indent: bool) -> Result<Vec<u8>> {
+        let repo = self.stack.repo;
+        let mut comparison = Vec::new();
+        if let Some(rev) = self.options.interdiff.as_ref() {
+            let old_tree_id = parse_stgit_revision(repo, Some(rev), None)?
+                .try_into_commit()?
+                .tree_id()?
+                .detach();
+            let diff = repo.stupid().diff_tree_patch(
+                old_tree_id,
+                self.tree_id,
+                <Option<Vec<&str>>>::None,
+                false,
+                self.diff_opts(),
+            )?;
+            comparison.extend_from_slice(self.options.reroll_label("Interdiff").as_bytes());
+            if indent {
+                for line in diff.lines_with_terminator() {
+                    comparison.extend_from_slice(b"  ");
+                    comparison.extend_from_slice(line);
+                }
+                comparison.push(b'\n');
+            } else {
+                comparison.extend_from_slice(&diff);
+            }
+        }
+        if let Some(rev) = self.options.range_diff.as_ref() {
+            if !self.contiguous {
+                return Err(anyhow!(
+                    "'--range-diff' requires the formatted patches to be contiguous"
+                ));
+            }
+            let old_range = if rev.contains("..") {
+                rev.clone()
+            } else {
+                format!("{}..{rev}", self.base_id()?)
+            };
+            let new_range = format!("{}..{}", self.base_id()?, self.last_commit()?.id);
+            let range_diff = repo.stupid().range_diff(
+                &old_range,
+                &new_range,
+                self.options.creation_factor.as_deref(),
+            )?;
+            comparison.extend_from_slice(self.options.reroll_label("Range-diff").as_bytes());
+            comparison.extend_from_slice(&range_diff);
+            if indent {
+                comparison.push(b'\n');
+            }
+        }
+        Ok(comparison)
+    }
+
+    /// Format the cover letter.
+    ///
+    /// The cover letter is sent to the union of the patches' recipients, given by
+    /// `recipients`. The per-patch recipients in `patch_recipients` are also listed in
+    /// the cover letter's body.
+    fn cover_letter(
+        &self,
+        thread: &Thread,
+        recipients: &CmdRecipients,
+        patch_recipients: &[CmdRecipients],
+    ) -> Result<Email> {
+        let repo = self.stack.repo;
+        let description = if let Some(template) = self.options.cover_template.as_ref() {
+            Some(template.trim_end().to_string())
+        } else {
+            repo.config_snapshot()
+                .plumbing()
+                .string(
+                    "branch",
+                    Some(self.stack.get_branch_name().into()),
+                    "description",
+                )
+                .map(|description| description.to_str_lossy().trim_end().to_string())
+        }
+        .filter(|description| !description.is_empty());
+
+        let (subject, blurb) = match (self.options.cover_from_description, description) {
+            _ if self.options.reuse_cover => {
+                let (subject, blurb) = self.stack.previous_cover_letter().ok_or_else(|| {
+                    anyhow!(
+                        "no previously sent cover letter recorded for branch `{}`",
+                        self.stack.get_branch_name()
+                    )
+                })?;
+                (subject, Some(blurb))
+            }
+            (CoverFromDescription::None, _) | (_, None) => (None, None),
+            (CoverFromDescription::Message, Some(description)) => (None, Some(description)),
+            (mode, Some(description)) => {
+                let (subject, body) = split_message(&description);
+                if mode == CoverFromDescription::Auto && subject.len() > COVER_SUBJECT_MAX {
+                    (None, Some(description))
+                } else {
+                    let body = body.trim_end().to_string();
+                    (Some(subject), Some(body).filter(|body| !body.is_empty()))
+                }
+            }
+        };
+
+        let mut body = String::new();
+        body.push_str(blurb.as_deref().unwrap_or(COVER_BLURB_PLACEHOLDER));
+        body.push_str("\n\n");
+
+        let total = self.options.start_number + self.patches.len() - 1;
+        for (i, patchname) in self.patches.iter().enumerate() {
+            let commit = self.stack.get_patch_commit(patchname)?;
+            let (subject, _) = split_message(&commit.message_ex().decode()?);
+            let label = format!("  [{}/{total}] ", self.options.start_number + i);
+            let indent = label.chars().count();
+            body.push_str(&label);
+            body.push_str(&wrap_words(&subject, indent, indent, SHORTLOG_WRAP));
+            body.push('\n');
+            for note in PatchMeta::read(repo, commit.id)?.get_all(meta::COVER_NOTE) {
+                body.push_str(&" ".repeat(indent));
+                body.push_str(&wrap_words(note, indent, indent, SHORTLOG_WRAP));
+                body.push('\n');
+            }
+        }
+        body.push('\n');
+
+        let mut recipients_block = String::new();
+        for (i, patchname) in self.patches.iter().enumerate() {
+            let message = self
+                .stack
+                .get_patch_commit(patchname)?
+                .message_ex()
+                .decode()?
+                .to_string();
+            let auto_cc = self.auto_cc([message.as_str()], &patch_recipients[i]);
+            let mailboxes: Vec<&str> = patch_recipients[i]
+                .to
+                .iter()
+                .chain(&patch_recipients[i].cc)
+                .chain(&auto_cc)
+                .map(String::as_str)
+                .collect();
+            if !mailboxes.is_empty() {
+                let label = format!("  [{}/{total}] ", self.options.start_number + i);
+                let indent = " ".repeat(label.chars().count());
+                for (j, mailbox) in mailboxes.iter().enumerate() {
+                    recipients_block.push_str(if j == 0 { &label } else { &indent });
+                    recipients_block.push_str(mailbox);
+                    recipients_block.push('\n');
+                }
+            }
+        }
+        if !recipients_block.is_empty() {
+            body.push_str("Per-patch recipients:\n");
+            body.push_str(&recipients_block);
+            body.push('\n');
+        }
+
+        let mut shortlog: std::collections::BTreeMap<String, Vec<String>> = Default::default();
+        for patchname in self.patches {
+            let commit = self.stack.get_patch_commit(patchname)?;
+            let author = commit.author_strict()?;
+            let message = commit.message_ex();
+            let (subject, _) = split_message(&message.decode()?);
+            shortlog
+                .entry(author.name.to_str_lossy().to_string())
+                .or_default()
+                .push(subject);
+        }
+        for (author, subjects) in &shortlog {
+            body.push_str(&format!("{author} ({}):\n", subjects.len()));
+            for subject in subjects {
+                body.push_str("  ");
+                body.push_str(&wrap_words(subject, 2, 4, SHORTLOG_WRAP));
+                body.push('\n');
+            }
+            body.push('\n');
+        }
+        let is_8bit = !body.is_ascii() || !self.committer.name.is_ascii();
+
+        let mut messages = Vec::with_capacity(self.patches.len());
+        for patchname in self.patches {
+            messages.push(
+                self.stack
+                    .get_patch_commit(patchname)?
+                    .message_ex()
+                    .decode()?
+                    .to_string(),
+            );
+        }
+        let auto_cc = self.auto_cc(messages.iter().map(String::as_str), recipients);
+
+        let commit_id = self.last_commit()?.id;
+        let message_id = self.message_id("cover");
+        let subject = subject.as_deref().unwrap_or(COVER_SUBJECT_PLACEHOLDER);
+        let mut content = self
+            .headers(
+                commit_id,
+                &message_id,
+                thread,
+                &self.committer,
+                &self.subject_prefix(0),
+                subject,
+                is_8bit,
+                recipients,
+                &auto_cc,
+            )
+            .into_bytes();
+        content.push(b'\n');
+        content.extend_from_slice(body.as_bytes());
+        content.extend_from_slice(&repo.stupid().diff_tree_stat(
+            self.base_commit()?.get_parent_commit()?.tree_id()?.detach(),
+            self.tree_id,
+            STAT_WIDTH,
+            self.stat_opts(),
+        )?);
+        content.push(b'\n');
+        content.extend_from_slice(&self.series_comparison(false)?);
+        if let Some(base_info) = self.base_info()? {
+            content.extend_from_slice(base_info.as_bytes());
+        }
+        content.extend_from_slice(self.signature_block().as_bytes());
+
+        Ok(Email {
+            patchname: None,
+            file_name: self.file_name(0, "cover-letter", "cover-letter")?,
+            message_id,
+            content,
+        })
+    }
+
+    fn patch_email(
+        &self,
+        index: usize,
+        patchname: &PatchName,
+        thread: &Thread,
+        recipients: &CmdRecipients,
+    ) -> Result<Email> {
+        let repo = self.stack.repo;
+        let stupid = repo.stupid();
+        let commit = self.stack.get_patch_commit(patchname)?;
+        let parent_tree_id = commit.get_parent_commit()?.tree_id()?.detach();
+        let tree_id = commit.tree_id()?.detach();
+        let number = self.options.start_number + index;
+
+        let message = commit.message_ex();
+        let message = message.decode()?;
+        let message =
+            if self.options.signoff {
+                let committer = &self.committer;
+                let signoff = format!("{} <{}>", committer.name, committer.email);
+                String::from_utf8(stupid.interpret_trailers(
+                    message.as_bytes(),
+                    [("Signed-off-by", signoff.as_str())],
+                )?)
+                .map_err(|_| anyhow!("could not decode message after adding trailers"))?
+            } else {
+                message.to_string()
+            };
+        let (subject, body) = split_message(&message);
+        let file_name = self.file_name(number, &subject, patchname.as_ref())?;
+
+        let message_id = self.message_id(&commit.id.to_string());
+        let author = commit.author_strict()?;
+        let mut content = self
+            .headers(
+                commit.id,
+                &message_id,
+                thread,
+                &author,
+                &self.subject_prefix(number),
+                &subject,
+                !message.is_ascii(),
+                recipients,
+                &self.auto_cc([message.as_str()], recipients),
+            )
+            .into_bytes();
+        if self.options.patch_names {
+            content.extend_from_slice(format!("X-StGit-Patch: {patchname}\n").as_bytes());
+        }
+
+        let boundary = format!("------------{}", self.git_version);
+        if self.options.attach.is_some() {
+            content.extend_from_slice(
+                format!(
+                    "MIME-Version: 1.0\n\
+                     Content-Type: multipart/mixed; boundary=\"{boundary}\"\n\
+                     \n\
+                     This is a multi-part message in MIME format.\n\
+                     --{boundary}\n\
+                     Content-Type: text/plain; charset=UTF-8; format=fixed\n\
+                     Content-Transfer-Encoding: 8bit\n"
+                )
+                .as_bytes(),
+            );
+        }
+
+        content.push(b'\n');
+        if self.options.attach.is_some() && !body.is_empty() {
+            content.push(b'\n');
+        }
+        content.extend_from_slice(body.as_bytes());
+        content.extend_from_slice(b"---\n");
+        if self.patches.len() == 1 && !self.options.cover_letter {
+            content.extend_from_slice(&self.series_comparison(true)?);
+        }
+        content.extend_from_slice(&stupid.diff_tree_stat(
+            parent_tree_id,
+            tree_id,
+            STAT_WIDTH,
+            self.stat_opts(),
+        )?);
+        content.push(b'\n');
+
+        if let Some(disposition) = self.options.attach {
+            content.extend_from_slice(
+                format!(
+                    "\n--{boundary}\n\
+                     Content-Type: text/x-patch; name=\"{file_name}\"\n\
+                     Content-Transfer-Encoding: 8bit\n\
+                     Content-Disposition: {disposition}; filename=\"{file_name}\"\n\
+                     \n"
+                )
+                .as_bytes(),
+            );
+        }
+
+        content.extend_from_slice(&stupid.diff_tree_patch(
+            parent_tree_id,
+            tree_id,
+            <Option<Vec<&str>>>::None,
+            false,
+            self.diff_opts(),
+        )?);
+
+        if index == 0 && !self.options.cover_letter {
+            if let Some(base_info) = self.base_info()? {
+                content.extend_from_slice(base_info.as_bytes());
+            }
+        }
+
+        if self.options.attach.is_some() {
+            content.extend_from_slice(format!("\n--{boundary}--\n\n\n").as_bytes());
+        } else {
+            content.extend_from_slice(self.signature_block().as_bytes());
+        }
+
+        Ok(Email {
+            patchname: Some(patchname.clone()),
+            file_name,
+            message_id,
+            content,
+        })
+    }
+}
+
+/// Additional recipients of an email from the '--to-cmd' and '--cc-cmd' commands.
+#[derive(Default)]
+struct CmdRecipients {
+    to: Vec<String>,
+    cc: Vec<String>,
+}
+
+impl CmdRecipients {
+    /// Add the addresses from `other` that are not already recipients.
+    ///
+    /// An address that is a `To:` recipient is never also a `Cc:` recipient.
+    fn extend(&mut self, other: &CmdRecipients) {
+        let contains = |mailboxes: &[String], mailbox: &str| {
+            let address = mailbox_address(mailbox).to_lowercase();
+            mailboxes
+                .iter()
+                .any(|other| mailbox_address(other).to_lowercase() == address)
+        };
+        for mailbox in &other.to {
+            if !contains(&self.to, mailbox) {
+                let address = mailbox_address(mailbox).to_lowercase();
+                self.cc
+                    .retain(|cc| mailbox_address(cc).to_lowercase() != address);
+                self.to.push(mailbox.clone());
+            }
+        }
+        for mailbox in &other.cc {
+            if !contains(&self.to, mailbox) && !contains(&self.cc, mailbox) {
+                self.cc.push(mailbox.clone());
+            }
+        }
+    }
+}
+
+/// Determine whether `patches` are contiguous and the tree resulting from the series.
+///
+/// For a contiguous series this is simply the tree of the last patch. Otherwise the
+/// selected patches are applied in order onto the parent of the first patch, such that
+/// the tree reflects only the changes of the formatted patches.
+fn series_tree(stack: &Stack, patches: &[PatchName]) -> Result<(bool, git_repository::ObjectId)> {
+    let mut commits = Vec::with_capacity(patches.len());
+    for patchname in patches {
+        commits.push(stack.get_patch_commit(patchname)?);
     }
+    let last = commits.last().expect("patches is not empty");
+    let contiguous = commits
+        .windows(2)
+        .all(|pair| pair[1].parent_ids().next().map(|id| id.detach()) == Some(pair[0].id));
+    if contiguous {
+        return Ok((true, last.tree_id()?.detach()));
+    }
+
+    let stupid = stack.repo.stupid();
+    let tree_id = stupid.with_temp_index(|stupid_temp| {
+        stupid_temp.read_tree(commits[0].get_parent_commit()?.tree_id()?.detach())?;
+        for (patchname, commit) in patches.iter().zip(&commits) {
+            let parent_tree_id = commit.get_parent_commit()?.tree_id()?.detach();
+            if !stupid_temp.apply_treediff_to_index(
+                parent_tree_id,
+                commit.tree_id()?.detach(),
+                false,
+            )? {
+                return Err(anyhow!(
+                    "patch `{patchname}` does not apply without the patches that are \
+                     not selected"
+                ));
+            }
+        }
+        stupid_temp.write_tree()
+    })?;
+    Ok((false, tree_id))
+}
+
+/// Run a '--to-cmd' or '--cc-cmd' command for the patch file at `patch_path`.
+///
+/// The command's output is one address per line. Any trailing parenthesized comment,
+/// such as the role annotations output by `get_maintainer.pl`, is removed.
+fn run_recipients_cmd(cmd: &str, patch_path: &Path) -> Result<Vec<String>> {
+    let shell = if cfg!(target_os = "windows") {
+        "sh"
+    } else {
+        "/bin/sh"
+    };
+    let output = std::process::Command::new(shell)
+        .arg("-c")
+        .arg(format!("{cmd} \"$@\""))
+        .arg(cmd)
+        .arg(patch_path)
+        .stdin(std::process::Stdio::null())
+        .stderr(std::process::Stdio::inherit())
+        .output()
+        .with_context(|| format!("running `{cmd}`"))?;
+    if !output.status.success() {
+        return Err(anyhow!("`{cmd}` failed with {}", output.status));
+    }
+    let mut mailboxes = Vec::new();
+    for line in output.stdout.lines() {
+        let line = line.to_str_lossy();
+        let mut mailbox = line.trim();
+        if mailbox.ends_with(')') {
+            if let Some(pos) = mailbox.rfind(" (") {
+                mailbox = mailbox[..pos].trim_end();
+            }
+        }
+        if !mailbox.is_empty() {
+            mailboxes.push(mailbox.to_string());
+        }
+    }
+    Ok(mailboxes)
+}
+
+/// Width of diffstats, matching `git format-patch`.
+const STAT_WIDTH: usize = 72;
+
+/// Maximum length of a branch description's first paragraph to be used as the cover
+/// letter subject with `--cover-from-description=auto`.
+const COVER_SUBJECT_MAX: usize = 100;
+
+/// Wrap width of the cover letter shortlog.
+const SHORTLOG_WRAP: usize = 72;
+
+/// Maximum length of a header line before it is folded.
+const HEADER_WRAP: usize = 78;
+
+/// Record the Message-Id of each patch email in the patch's metadata.
+fn record_message_ids(stack: &Stack, emails: &[Email]) -> Result<()> {
+    for email in emails {
+        if let Some(patchname) = &email.patchname {
+            let message_id = &email.message_id;
+            let commit_id = stack.get_patch_commit_id(patchname);
+            let mut patch_meta = PatchMeta::read(stack.repo, commit_id)?;
+            if patch_meta.get(meta::MESSAGE_ID) != Some(message_id.as_str()) {
+                patch_meta.set(meta::MESSAGE_ID, message_id);
+                patch_meta.write(stack.repo, commit_id)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Split a commit message into its subject and body.
+///
+/// Like git, the subject is the first paragraph of the message with its lines joined
+/// by spaces. The body, if not empty, ends with a newline.
+fn split_message(message: &str) -> (String, String) {
+    let mut lines = message.lines();
+    let mut subject = String::new();
+    for line in lines.by_ref() {
+        let line = line.trim();
+        if line.is_empty() {
+            if subject.is_empty() {
+                continue;
+            } else {
+                break;
+            }
+        }
+        if !subject.is_empty() {
+            subject.push(' ');
+        }
+        subject.push_str(line);
+    }
+    let mut body = String::new();
+    for line in lines.skip_while(|line| line.trim().is_empty()) {
+        body.push_str(line.trim_end());
+        body.push('\n');
+    }
+    let body_len = body.trim_end().len();
+    body.truncate(body_len);
+    if !body.is_empty() {
+        body.push('\n');
+    }
+    (subject, body)
+}
 
-    repo.stupid().format_patch(format_args)
+/// Format the `Subject:` header line, folding or encoding as needed.
+fn format_subject(prefix: &str, subject: &str) -> String {
+    let mut line = format!("Subject: {prefix}");
+    if needs_rfc2047(subject) {
+        let encoded = encode_rfc2047(subject, line.len(), false);
+        line.push_str(&encoded);
+    } else {
+        let wrapped = wrap_words(subject, line.len(), 1, HEADER_WRAP);
+        line.push_str(&wrapped);
+    }
+    line.push('\n');
+    line
+}
+
+/// Format a mailbox as `name <email>`, encoding or quoting the name as needed.
+fn format_mailbox(name: &str, email: &str) -> String {
+    const RFC822_SPECIALS: &[char] = &[
+        '(', ')', '<', '>', '[', ']', ':', ';', '@', '\\', ',', '.', '"',
+    ];
+    if needs_rfc2047(name) {
+        format!("{} <{email}>", encode_rfc2047(name, "From: ".len(), true))
+    } else if name.contains(RFC822_SPECIALS) {
+        let quoted = name.replace('\\', "\\\\").replace('"', "\\\"");
+        format!("\"{quoted}\" <{email}>")
+    } else {
+        format!("{name} <{email}>")
+    }
+}
+
+/// Get the mailboxes from a commit message's `Cc:` and `*-by:` trailers.
+///
+/// Only the message's final paragraph is considered to be trailers. Comments
+/// following the mailbox, e.g. `Cc: <stable@vger.kernel.org> # 5.10.x`, are
+/// discarded, as are values that do not look like email addresses. Trailers
+/// matching any of the lower-case names or categories in `suppress_cc` are skipped.
+fn trailer_mailboxes(message: &str, suppress_cc: &[String]) -> Vec<String> {
+    let message = message.trim_end();
+    let trailers = if let Some(pos) = message.rfind("\n\n") {
+        &message[pos + 2..]
+    } else {
+        return Vec::new();
+    };
+    let mut mailboxes = Vec::new();
+    for line in trailers.lines() {
+        let (key, value) = if let Some((key, value)) = line.split_once(':') {
+            (key.trim(), value.trim())
+        } else {
+            continue;
+        };
+        let key = key.to_ascii_lowercase();
+        if key != "cc" && !key.ends_with("-by") || is_suppressed(&key, suppress_cc) {
+            continue;
+        }
+        let value = if let Some(end) = value.find('>') {
+            &value[..=end]
+        } else {
+            value.split_whitespace().next().unwrap_or_default()
+        };
+        let value = value.trim();
+        if mailbox_address(value).contains('@') {
+            mailboxes.push(value.to_string());
+        }
+    }
+    mailboxes
+}
+
+/// Whether the lower-case trailer `key` is suppressed by a '--suppress-cc' value.
+fn is_suppressed(key: &str, suppress_cc: &[String]) -> bool {
+    suppress_cc.iter().any(|suppress| match suppress.as_str() {
+        "all" => true,
+        "sob" => key == "signed-off-by",
+        "misc-by" => key != "signed-off-by" && key.ends_with("-by"),
+        "bodycc" => key == "cc",
+        name => name == key,
+    })
+}
+
+/// Get the bare address from a mailbox of the form `Name <address>` or `address`.
+fn mailbox_address(mailbox: &str) -> &str {
+    if let (Some(start), Some(end)) = (mailbox.rfind('<'), mailbox.rfind('>')) {
+        if start < end {
+            return mailbox[start + 1..end].trim();
+        }
+    }
+    mailbox.trim()
+}
+
+/// Determine whether header text must be encoded according to RFC 2047.
+fn needs_rfc2047(text: &str) -> bool {
+    !text.is_ascii() || text.contains('\n') || text.contains("=?")
+}
+
+/// Encode header text as RFC 2047 "Q"-encoded words.
+///
+/// Encoded words are folded onto continuation lines to keep lines within 76
+/// columns, given that the text starts at `column`. When `is_phrase` is true, the
+/// more restrictive encoding for words in an address's display name is used.
+fn encode_rfc2047(text: &str, column: usize, is_phrase: bool) -> String {
+    const MAX_ENCODED_LEN: usize = 76;
+    const START: &str = "=?UTF-8?q?";
+    let is_special = |b: u8| {
+        !b.is_ascii_graphic()
+            || b == b'='
+            || b == b'?'
+            || b == b'_'
+            || (is_phrase && !b.is_ascii_alphanumeric() && !b"!*+-/".contains(&b))
+    };
+
+    let mut encoded = String::from(START);
+    let mut line_len = column + START.len();
+    let mut buf = [0u8; 4];
+    for c in text.chars() {
+        let bytes = c.encode_utf8(&mut buf).as_bytes();
+        let special = bytes.len() > 1 || is_special(bytes[0]);
+        let encoded_len = if special { 3 * bytes.len() } else { 1 };
+        if line_len + encoded_len + 2 > MAX_ENCODED_LEN {
+            encoded.push_str("?=\n ");
+            encoded.push_str(START);
+            line_len = START.len() + 1;
+        }
+        if special {
+            for b in bytes {
+                encoded.push_str(&format!("={b:02X}"));
+            }
+        } else {
+            encoded.push(c);
+        }
+        line_len += encoded_len;
+    }
+    encoded.push_str("?=");
+    encoded
+}
+
+/// Wrap words of text to fit within `width` columns.
+///
+/// The text is assumed to start at `column`. Continuation lines are indented by
+/// `indent` spaces.
+fn wrap_words(text: &str, column: usize, indent: usize, width: usize) -> String {
+    let mut wrapped = String::with_capacity(text.len());
+    let mut column = column;
+    for (i, word) in text.split(' ').enumerate() {
+        let word_width = word.chars().count();
+        if i > 0 {
+            if column + 1 + word_width > width {
+                wrapped.push('\n');
+                wrapped.push_str(&" ".repeat(indent));
+                column = indent;
+            } else {
+                wrapped.push(' ');
+                column += 1;
+            }
+        }
+        wrapped.push_str(word);
+        column += word_width;
+    }
+    wrapped
+}
+
+/// Expand the placeholders of a `--filename-template`.
+fn expand_filename_template(
+    template: &str,
+    number: usize,
+    subject: &str,
+    reroll: Option<&str>,
+    patchname: &str,
+) -> Result<String> {
+    let mut filename = String::with_capacity(template.len() + patchname.len());
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c == '%' {
+            match chars.next() {
+                Some('n') => filename.push_str(&format!("{number:04}")),
+                Some('s') => filename.push_str(&sanitize_subject(subject, 52)),
+                Some('v') => {
+                    if let Some(reroll) = reroll {
+                        filename.push('v');
+                        filename.push_str(reroll);
+                    }
+                }
+                Some('p') => filename.push_str(patchname),
+                Some('%') => filename.push('%'),
+                Some(c) => {
+                    return Err(anyhow!(
+                        "unknown placeholder `%{c}` in filename template `{template}`"
+                    ))
+                }
+                None => {
+                    return Err(anyhow!(
+                        "incomplete placeholder in filename template `{template}`"
+                    ))
+                }
+            }
+        } else if std::path::is_separator(c) {
+            return Err(anyhow!(
+                "filename template `{template}` may not contain path separators"
+            ));
+        } else {
+            filename.push(c);
+        }
+    }
+    Ok(filename)
+}
+
+/// Sanitize a patch subject for use in a file name.
+///
+/// Like `git format-patch`, runs of characters other than alphanumerics, `.`, and `_`
+/// are replaced with a single `-`. At most `max_len` characters are retained.
+pub(crate) fn sanitize_subject(subject: &str, max_len: usize) -> String {
+    let mut slug = String::with_capacity(subject.len());
+    for c in subject.trim().chars() {
+        if c.is_ascii_alphanumeric() || c == '.' || c == '_' {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+        if slug.len() >= max_len {
+            break;
+        }
+    }
+    slug.trim_start_matches('.')
+        .trim_end_matches(['.', '-'])
+        .to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn encode_subject() {
+        assert_eq!(
+            format_subject(
+                "[PATCH 2/3] ",
+                "Sëcond patch with a very long subject line that goes on and on and on beyond \
+                 limits"
+            ),
+            "Subject: [PATCH 2/3] =?UTF-8?q?S=C3=ABcond=20patch=20with=20a=20very=20lon?=\n \
+             =?UTF-8?q?g=20subject=20line=20that=20goes=20on=20and=20on=20and=20on=20b?=\n \
+             =?UTF-8?q?eyond=20limits?=\n"
+        );
+    }
+
+    #[test]
+    fn fold_subject() {
+        assert_eq!(
+            format_subject(
+                "[PATCH] ",
+                "This is a very long plain ascii subject line that definitely exceeds \
+                 seventy-eight columns wide"
+            ),
+            "Subject: [PATCH] This is a very long plain ascii subject line that definitely\n \
+             exceeds seventy-eight columns wide\n"
+        );
+    }
+
+    #[test]
+    fn mailbox_names() {
+        assert_eq!(
+            format_mailbox("A U Thor", "author@example.com"),
+            "A U Thor <author@example.com>"
+        );
+        assert_eq!(
+            format_mailbox("J. R. Doe, Jr", "jr@example.com"),
+            "\"J. R. Doe, Jr\" <jr@example.com>"
+        );
+        assert_eq!(
+            format_mailbox("Jöhn Dœ", "jd@example.com"),
+            "=?UTF-8?q?J=C3=B6hn=20D=C5=93?= <jd@example.com>"
+        );
+    }
+
+    #[test]
+    fn trailer_cc_mailboxes() {
+        assert_eq!(
+            trailer_mailboxes(
+                "Subject\n\nBody mentions Cc: body@example.com\n\n\
+                 Reported-by: R Porter <reporter@example.com>\n\
+                 Link: https://example.com/bug\n\
+                 Cc: <stable@vger.kernel.org> # 5.10.x\n\
+                 cc: plain@example.com # comment\n\
+                 Acked-by: Nobody\n\
+                 Signed-off-by: A U Thor <author@example.com>\n",
+                &[]
+            ),
+            vec![
+                "R Porter <reporter@example.com>".to_string(),
+                "<stable@vger.kernel.org>".to_string(),
+                "plain@example.com".to_string(),
+                "A U Thor <author@example.com>".to_string(),
+            ]
+        );
+        let message = "Subject\n\n\
+                       Reported-by: R Porter <reporter@example.com>\n\
+                       Suggested-by: S Uggester <suggester@example.com>\n\
+                       Cc: cc@example.com\n\
+                       Signed-off-by: A U Thor <author@example.com>\n";
+        assert_eq!(
+            trailer_mailboxes(message, &["reported-by".to_string(), "sob".to_string()]),
+            vec![
+                "S Uggester <suggester@example.com>".to_string(),
+                "cc@example.com".to_string(),
+            ]
+        );
+        assert_eq!(
+            trailer_mailboxes(message, &["misc-by".to_string(), "bodycc".to_string()]),
+            vec!["A U Thor <author@example.com>".to_string()]
+        );
+        assert!(trailer_mailboxes(message, &["all".to_string()]).is_empty());
+        assert!(trailer_mailboxes("Cc: subject@example.com\n", &[]).is_empty());
+        assert_eq!(mailbox_address("Name <a@example.com>"), "a@example.com");
+        assert_eq!(mailbox_address(" a@example.com "), "a@example.com");
+    }
+
+    #[test]
+    fn split_subject_and_body() {
+        assert_eq!(
+            split_message("Subject\ncontinued\n\n\nBody line\n\nTrailer: x\n\n"),
+            (
+                "Subject continued".to_string(),
+                "Body line\n\nTrailer: x\n".to_string()
+            )
+        );
+        assert_eq!(
+            split_message("Subject only\n"),
+            ("Subject only".to_string(), String::new())
+        );
+    }
 }
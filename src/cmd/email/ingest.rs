@@ -0,0 +1,339 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg email ingest` implementation.
+
+use std::{io::Write, path::PathBuf};
+
+use anyhow::{anyhow, Result};
+use bstr::ByteSlice;
+use clap::{Arg, ValueHint};
+
+use crate::{
+    color::get_color_stdout,
+    ext::{CommitExtended, RepositoryExtended},
+    patch::{mailinfo::MailInfo, mbox::Mbox, meta, PatchName},
+    print_info_message, print_warning_message,
+    stack::{InitializationPolicy, Stack, StackStateAccess},
+    stupid::Stupid,
+    wrap::Message,
+};
+
+/// Review trailers recognized in replies.
+const REVIEW_TRAILERS: [&str; 3] = ["Reviewed-by", "Acked-by", "Tested-by"];
+
+pub(super) fn command() -> clap::Command {
+    clap::Command::new("ingest")
+        .about("Collect review trailers from email replies")
+        .long_about(
+            "Collect review trailers from replies to patch emails and show the review \
+             status of each patch. With '--apply', the collected trailers are added to \
+             the patches' messages.\n\
+             \n\
+             Each email in the given mbox is searched for \"Reviewed-by:\", \
+             \"Acked-by:\", and \"Tested-by:\" trailers, ignoring quoted lines. A reply \
+             is matched to a patch using the Message-Id recorded in the patch's \
+             metadata by `stg email format`: the reply's \"In-Reply-To\" and \
+             \"References\" headers are checked, nearest first. Replies that cannot be \
+             matched by Message-Id, such as replies to emails formatted elsewhere, are \
+             matched by subject against the patches' subjects.\n\
+             \n\
+             Trailers already present in a patch's message are reported as such and \
+             are not added again. Replies with trailers that do not match any patch \
+             are reported and otherwise ignored.",
+        )
+        .arg(
+            Arg::new("mbox")
+                .help("Mbox file containing review replies")
+                .required(true)
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(ValueHint::FilePath),
+        )
+        .arg(
+            Arg::new("apply")
+                .long("apply")
+                .help("Add the collected trailers to the patches")
+                .action(clap::ArgAction::SetTrue),
+        )
+}
+
+/// A review reply parsed from the mbox.
+struct Reply {
+    subject: String,
+    raw_subject: String,
+    message_ids: Vec<String>,
+    trailers: Vec<(String, String)>,
+}
+
+pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
+    let repo = git_repository::Repository::open()?;
+    let stack = Stack::from_branch(&repo, None, InitializationPolicy::AllowUninitialized)?;
+    let stupid = repo.stupid();
+
+    let mbox_path = matches
+        .get_one::<PathBuf>("mbox")
+        .expect("mbox is a required argument");
+    if !mbox_path.is_file() {
+        return Err(anyhow!("mbox `{}` not found", mbox_path.display()));
+    }
+
+    let mbox_file = std::fs::File::open(mbox_path)?;
+    let mut replies = Vec::new();
+    for raw in Mbox::new(std::io::BufReader::new(mbox_file), false, true) {
+        let raw = raw?;
+        let reply = parse_reply(&raw, &MailInfo::parse(&raw));
+        if !reply.trailers.is_empty() {
+            replies.push(reply);
+        }
+    }
+
+    // Message-Ids and subjects of all patches, in stack order.
+    let mut patches: Vec<(PatchName, Option<String>, String)> = Vec::new();
+    for patchname in stack.all_patches() {
+        let commit = stack.get_patch_commit(patchname)?;
+        let patch_meta = meta::PatchMeta::read(&repo, commit.id)?;
+        let message = commit.message_ex().decode()?.to_string();
+        let subject = message
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+        patches.push((
+            patchname.clone(),
+            patch_meta.get(meta::MESSAGE_ID).map(String::from),
+            subject,
+        ));
+    }
+
+    let mut collected: Vec<(PatchName, Vec<(String, String)>)> = Vec::new();
+    for reply in &replies {
+        let by_message_id = reply.message_ids.iter().rev().find_map(|message_id| {
+            patches
+                .iter()
+                .find(|(_, patch_message_id, _)| patch_message_id.as_ref() == Some(message_id))
+        });
+        let by_subject = || {
+            if is_reply_subject(&reply.raw_subject) && !reply.subject.is_empty() {
+                patches
+                    .iter()
+                    .find(|(_, _, subject)| subject == &reply.subject)
+            } else {
+                None
+            }
+        };
+        if let Some((patchname, _, _)) = by_message_id.or_else(by_subject) {
+            let pos = if let Some(pos) = collected.iter().position(|(pn, _)| pn == patchname) {
+                pos
+            } else {
+                collected.push((patchname.clone(), Vec::new()));
+                collected.len() - 1
+            };
+            let trailers = &mut collected[pos].1;
+            for trailer in &reply.trailers {
+                if !trailers.contains(trailer) {
+                    trailers.push(trailer.clone());
+                }
+            }
+        } else {
+            print_warning_message(
+                matches,
+                &format!("no patch found for reply `{}`", reply.raw_subject),
+            );
+        }
+    }
+
+    if collected.is_empty() {
+        print_info_message(matches, "no review trailers found");
+        return Ok(());
+    }
+
+    // Keep stack order for display and updates.
+    collected.sort_by_key(|(patchname, _)| {
+        patches
+            .iter()
+            .position(|(pn, _, _)| pn == patchname)
+            .expect("collected patch is in stack")
+    });
+
+    let mut stdout = std::io::stdout();
+    let mut updates: Vec<(PatchName, Vec<(String, String)>)> = Vec::new();
+    for (patchname, trailers) in collected {
+        let message = stack
+            .get_patch_commit(&patchname)?
+            .message_ex()
+            .decode()?
+            .to_string();
+        writeln!(stdout, "{patchname}")?;
+        let mut new_trailers = Vec::new();
+        for (key, value) in trailers {
+            if has_trailer(&message, &key, &value) {
+                writeln!(stdout, "  {key}: {value} (already present)")?;
+            } else {
+                writeln!(stdout, "  {key}: {value}")?;
+                new_trailers.push((key, value));
+            }
+        }
+        if !new_trailers.is_empty() {
+            updates.push((patchname, new_trailers));
+        }
+    }
+
+    if !matches.get_flag("apply") || updates.is_empty() {
+        return Ok(());
+    }
+
+    stack.check_head_top_mismatch()?;
+
+    let committer = repo.get_committer()?;
+    let mut commit_updates: Vec<(PatchName, git_repository::ObjectId)> = Vec::new();
+    for (patchname, trailers) in &updates {
+        let commit = stack.get_patch_commit(patchname)?;
+        let message = stupid.interpret_trailers(
+            commit.message_ex().decode()?.as_bytes(),
+            trailers
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.as_str())),
+        )?;
+        let message = String::from_utf8(message)
+            .map_err(|_| anyhow!("could not decode message after adding trailers"))?;
+        let commit_id = repo.commit_ex(
+            &commit.author_strict()?,
+            committer,
+            &Message::from(message),
+            commit.tree_id()?.detach(),
+            commit.parent_ids().map(|id| id.detach()),
+        )?;
+        commit_updates.push((patchname.clone(), commit_id));
+    }
+
+    stack
+        .setup_transaction()
+        .use_index_and_worktree(true)
+        .with_output_stream(get_color_stdout(matches))
+        .transact(|trans| {
+            let lowest_applied = trans
+                .applied()
+                .iter()
+                .position(|pn| commit_updates.iter().any(|(updated, _)| updated == pn));
+            let popped = if let Some(pos) = lowest_applied {
+                let to_pop = trans.applied()[pos..].to_vec();
+                let popped_extra = trans.pop_patches(|pn| to_pop.contains(pn))?;
+                assert!(popped_extra.is_empty());
+                to_pop
+            } else {
+                vec![]
+            };
+            for (patchname, commit_id) in &commit_updates {
+                trans.update_patch(patchname, *commit_id)?;
+            }
+            trans.push_patches(&popped, false)
+        })
+        .execute("email ingest")?;
+
+    Ok(())
+}
+
+/// Parse a reply from its raw content and the information split from it.
+fn parse_reply(raw: &[u8], info: &MailInfo) -> Reply {
+    let mut raw_subject = String::new();
+    let mut in_reply_to = Vec::new();
+    let mut references = Vec::new();
+    for (name, value) in parse_headers(raw) {
+        if name.eq_ignore_ascii_case("subject") {
+            raw_subject = value;
+        } else if name.eq_ignore_ascii_case("in-reply-to") {
+            in_reply_to = extract_message_ids(&value);
+        } else if name.eq_ignore_ascii_case("references") {
+            references = extract_message_ids(&value);
+        }
+    }
+
+    // Message-Ids are ordered from most distant to nearest ancestor.
+    let mut message_ids = references;
+    for message_id in in_reply_to {
+        message_ids.retain(|id| id != &message_id);
+        message_ids.push(message_id);
+    }
+
+    let subject = info
+        .subject
+        .as_deref()
+        .map(|subject| subject.trim().to_string())
+        .unwrap_or_default();
+
+    let mut trailers: Vec<(String, String)> = Vec::new();
+    for line in info.message.lines() {
+        let line = line.to_str_lossy();
+        let line = line.trim_end();
+        if let Some((key, value)) = line.split_once(':') {
+            if let Some(trailer) = REVIEW_TRAILERS
+                .iter()
+                .find(|trailer| trailer.eq_ignore_ascii_case(key))
+            {
+                let value = value.trim();
+                let trailer = (trailer.to_string(), value.to_string());
+                if !value.is_empty() && !trailers.contains(&trailer) {
+                    trailers.push(trailer);
+                }
+            }
+        }
+    }
+
+    Reply {
+        subject,
+        raw_subject,
+        message_ids,
+        trailers,
+    }
+}
+
+/// Parse the header section of an email, unfolding continuation lines.
+pub(super) fn parse_headers(raw: &[u8]) -> Vec<(String, String)> {
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for line in raw.lines() {
+        if line.is_empty() {
+            break;
+        }
+        let line = line.to_str_lossy();
+        if line.starts_with(char::is_whitespace) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    headers
+}
+
+/// Extract the `<...>` Message-Ids from a header value.
+pub(super) fn extract_message_ids(value: &str) -> Vec<String> {
+    let mut message_ids = Vec::new();
+    let mut rest = value;
+    while let Some(start) = rest.find('<') {
+        if let Some(len) = rest[start..].find('>') {
+            message_ids.push(rest[start..start + len + 1].to_string());
+            rest = &rest[start + len + 1..];
+        } else {
+            break;
+        }
+    }
+    message_ids
+}
+
+/// Determine whether a raw subject is that of a reply.
+pub(super) fn is_reply_subject(subject: &str) -> bool {
+    subject
+        .get(..3)
+        .map_or(false, |prefix| prefix.eq_ignore_ascii_case("re:"))
+}
+
+/// Determine whether the message already has the given trailer.
+fn has_trailer(message: &str, key: &str, value: &str) -> bool {
+    message.lines().any(|line| {
+        line.split_once(':').map_or(false, |(k, v)| {
+            k.trim().eq_ignore_ascii_case(key) && v.trim() == value
+        })
+    })
+}
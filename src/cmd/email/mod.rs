@@ -2,11 +2,18 @@
 
 //! `stg email` implementation.
 
+mod aliases;
 mod format;
+mod ingest;
+mod preview;
+mod retrieve;
 mod send;
 
 use anyhow::Result;
 
+pub(super) use format::sanitize_subject;
+pub(super) use retrieve::{encode_message_id, DEFAULT_INBOX_URL};
+
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "email",
     category: super::CommandCategory::StackInspection,
@@ -26,19 +33,31 @@ fn make() -> clap::Command {
              send`. This workflow may be condensed to one step by specifying patch \
              names to `stg email send` instead of email files.\n\
              \n\
-             The `format` and `send` subcommands are thin wrappers over `git \
-             format-patch` and `git send-email`, respectively. Refer to the \
-             git-format-patch(1) and git-send-email(1) manpages for more details about \
-             configuration and options.",
+             The `format` subcommand formats emails the same way as `git \
+             format-patch` and the `send` subcommand is a thin wrapper over `git \
+             send-email`. Refer to the git-format-patch(1) and git-send-email(1) \
+             manpages for more details about configuration and options.\n\
+             \n\
+             The `preview` subcommand shows the emails that would be formatted, \
+             without writing or sending anything. The `ingest` subcommand collects \
+             review trailers from replies to the sent emails. The `retrieve` \
+             subcommand imports a patch series from a public-inbox archive such as \
+             lore.kernel.org.",
         )
         .subcommand_required(true)
         .subcommand(format::command())
+        .subcommand(ingest::command())
+        .subcommand(preview::command())
+        .subcommand(retrieve::command())
         .subcommand(send::command())
 }
 
 fn run(matches: &clap::ArgMatches) -> Result<()> {
     match matches.subcommand() {
         Some(("format", sub_matches)) => format::dispatch(sub_matches),
+        Some(("ingest", sub_matches)) => ingest::dispatch(sub_matches),
+        Some(("preview", sub_matches)) => preview::dispatch(sub_matches),
+        Some(("retrieve", sub_matches)) => retrieve::dispatch(sub_matches),
         Some(("send", sub_matches)) => send::dispatch(sub_matches),
         _ => panic!("valid subcommand is expected"),
     }
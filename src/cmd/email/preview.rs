@@ -0,0 +1,118 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg email preview` implementation.
+
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use anyhow::{anyhow, Context, Result};
+use clap::Arg;
+use is_terminal::IsTerminal;
+
+use super::format;
+use crate::{argset, ext::RepositoryExtended, patch::patchrange, stupid::Stupid};
+
+pub(super) fn command() -> clap::Command {
+    clap::Command::new("preview")
+        .about("Preview formatted patch emails")
+        .long_about(
+            "Show the emails for the selected patches exactly as `stg email format` \
+             would format them, including headers, the optional cover letter, and \
+             diffs, without writing any files or sending anything. This allows the \
+             subjects, recipients, and threading of a series to be checked before \
+             using `stg email send`.\n\
+             \n\
+             The emails are shown as a single mbox in the pager configured by \
+             `GIT_PAGER`, `core.pager`, or `PAGER`. When standard output is not a \
+             terminal, the emails are written directly to standard output.\n\
+             \n\
+             All of the formatting options of `stg email format` are accepted, and the \
+             same `format.*` configuration values are honored. Note that Message-Ids \
+             are not recorded in the patches' metadata by this command.",
+        )
+        .override_usage(
+            "stg email preview [OPTIONS] <patch>...\n       \
+             stg email preview [OPTIONS] --all",
+        )
+        .arg(
+            Arg::new("patchranges")
+                .help("Patches to preview")
+                .value_name("patch")
+                .num_args(1..)
+                .value_parser(clap::value_parser!(patchrange::Specification))
+                .conflicts_with("all")
+                .required_unless_present_any(["all"]),
+        )
+        .arg(argset::branch_arg())
+        .arg(
+            Arg::new("all")
+                .long("all")
+                .short('a')
+                .help("Preview all applied patches")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .next_help_heading("Format Options")
+        .args(
+            format::format_options()
+                .into_iter()
+                .filter(|arg| !format::OUTPUT_ARG_IDS.contains(&arg.get_id().as_str())),
+        )
+        .next_help_heading("Message Options")
+        .args(format::message_options())
+        .next_help_heading("Diff Options")
+        .arg(argset::diff_opts_arg())
+}
+
+pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
+    let mbox = format::format_mbox(matches)?;
+
+    let mut stdout = std::io::stdout();
+    if !stdout.is_terminal() {
+        stdout.write_all(&mbox)?;
+        return Ok(());
+    }
+
+    let repo = git_repository::Repository::open()?;
+    let pager = repo.stupid().var_pager()?;
+    if pager.is_empty() || pager == *"cat" {
+        stdout.write_all(&mbox)?;
+        return Ok(());
+    }
+
+    // Like git, default to options for less and lv that retain colors and quit when
+    // the output fits on one screen.
+    let mut command = Command::new(if cfg!(target_os = "windows") {
+        "sh"
+    } else {
+        "/bin/sh"
+    });
+    command.arg("-c").arg(&pager).stdin(Stdio::piped());
+    if std::env::var_os("LESS").is_none() {
+        command.env("LESS", "FRX");
+    }
+    if std::env::var_os("LV").is_none() {
+        command.env("LV", "-c");
+    }
+    let mut child = command
+        .spawn()
+        .with_context(|| format!("could not execute pager `{}`", pager.to_string_lossy()))?;
+
+    let mut stdin = child.stdin.take().expect("pager stdin is piped");
+    match stdin.write_all(&mbox) {
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => {}
+        result => result?,
+    }
+    drop(stdin);
+
+    let status = child.wait()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "pager `{}` exited with {status}",
+            pager.to_string_lossy()
+        ))
+    }
+}
@@ -0,0 +1,352 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg email retrieve` implementation.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use bstr::{BString, ByteSlice};
+use clap::Arg;
+
+use super::ingest::{extract_message_ids, is_reply_subject, parse_headers};
+use crate::{
+    color::get_color_stdout,
+    ext::{RepositoryExtended, TimeExtended},
+    patch::{mailinfo::MailInfo, mbox::Mbox, PatchName},
+    print_warning_message,
+    stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
+    stupid::Stupid,
+    wrap::Message,
+};
+
+/// Public-inbox archive used when none is specified or configured.
+pub(crate) const DEFAULT_INBOX_URL: &str = "https://lore.kernel.org/all";
+
+pub(super) fn command() -> clap::Command {
+    clap::Command::new("retrieve")
+        .about("Retrieve a patch series from a public-inbox archive")
+        .long_about(
+            "Download the email thread containing the given Message-Id from a \
+             public-inbox archive, such as lore.kernel.org, and import the patch \
+             series found in the thread as new patches on top of the current stack.\n\
+             \n\
+             The thread may be specified by a Message-Id, with or without angle \
+             brackets, or by the URL of a message in the archive, e.g. \
+             \"https://lore.kernel.org/all/<message-id>/\". When a Message-Id is \
+             given, the archive is determined by the '--inbox-url' option, the \
+             \"stgit.email.inbox-url\" configuration variable, or defaults to \
+             \"https://lore.kernel.org/all\".\n\
+             \n\
+             Patch emails are recognized by their \"[PATCH ...]\" subject prefix and \
+             diff; cover letters and replies are ignored. When the thread contains \
+             more than one version of the series, the newest version is retrieved. \
+             The patches are imported in series order with their original author, \
+             and a \"Link:\" trailer with each patch email's archive URL is added to \
+             each patch's message.",
+        )
+        .arg(
+            Arg::new("message-id")
+                .help("Message-Id or archive URL of an email in the thread")
+                .value_name("message-id|url")
+                .required(true)
+                .value_parser(clap::builder::NonEmptyStringValueParser::new()),
+        )
+        .arg(
+            Arg::new("inbox-url")
+                .long("inbox-url")
+                .help("Retrieve from the public-inbox archive at <url>")
+                .value_name("url")
+                .value_parser(clap::builder::NonEmptyStringValueParser::new()),
+        )
+        .arg(
+            Arg::new("no-link")
+                .long("no-link")
+                .help("Do not add \"Link:\" trailers to the patches")
+                .action(clap::ArgAction::SetTrue),
+        )
+}
+
+/// A patch email found in the retrieved thread.
+struct PatchEmail {
+    version: usize,
+    index: usize,
+    total: usize,
+    message_id: Option<String>,
+    info: MailInfo,
+}
+
+pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
+    let repo = git_repository::Repository::open()?;
+    let stack = Stack::from_branch(&repo, None, InitializationPolicy::AutoInitialize)?;
+    let stupid = repo.stupid();
+    let config = repo.config_snapshot();
+
+    repo.check_repository_state()?;
+    let statuses = stupid.statuses(None)?;
+    statuses.check_conflicts()?;
+    statuses.check_index_and_worktree_clean()?;
+    stack.check_head_top_mismatch()?;
+
+    let spec = matches
+        .get_one::<String>("message-id")
+        .expect("message-id is a required argument");
+    let (inbox_url, message_id) = if spec.contains("://") {
+        split_archive_url(spec)?
+    } else {
+        let message_id = spec.trim();
+        let message_id = message_id.strip_prefix('<').unwrap_or(message_id);
+        let message_id = message_id.strip_suffix('>').unwrap_or(message_id);
+        let inbox_url = if let Some(url) = matches.get_one::<String>("inbox-url") {
+            url.clone()
+        } else if let Some(url) = config.string("stgit.email.inbox-url") {
+            url.to_str_lossy().to_string()
+        } else {
+            DEFAULT_INBOX_URL.to_string()
+        };
+        (
+            inbox_url.trim_end_matches('/').to_string(),
+            message_id.to_string(),
+        )
+    };
+
+    let temp_dir = tempfile::tempdir()?;
+    let mbox_path = download_thread(
+        &format!("{inbox_url}/{}/t.mbox.gz", encode_message_id(&message_id)),
+        temp_dir.path(),
+    )
+    .with_context(|| format!("retrieving thread for <{message_id}>"))?;
+
+    let mbox_file = std::fs::File::open(&mbox_path)?;
+    let mut emails: Vec<PatchEmail> = Vec::new();
+    for raw in Mbox::new(std::io::BufReader::new(mbox_file), false, true) {
+        let raw = raw?;
+        let headers = parse_headers(&raw);
+        let raw_subject = headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("subject"))
+            .map(|(_, value)| value.as_str())
+            .unwrap_or_default();
+        if is_reply_subject(raw_subject) {
+            continue;
+        }
+        let (version, index, total) = if let Some(numbering) = parse_patch_prefix(raw_subject) {
+            numbering
+        } else {
+            continue;
+        };
+        let info = MailInfo::parse(&raw);
+        if info.diff.trim().is_empty() {
+            continue;
+        }
+        let message_id = headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("message-id"))
+            .and_then(|(_, value)| extract_message_ids(value).into_iter().next());
+        emails.push(PatchEmail {
+            version,
+            index,
+            total,
+            message_id,
+            info,
+        });
+    }
+
+    let version = emails
+        .iter()
+        .map(|email| email.version)
+        .max()
+        .ok_or_else(|| anyhow!("no patches found in thread for <{message_id}>"))?;
+    let mut series: Vec<PatchEmail> = Vec::new();
+    for email in emails.into_iter().filter(|email| email.version == version) {
+        // A resent patch replaces the earlier email with the same index.
+        series.retain(|other| other.index != email.index);
+        series.push(email);
+    }
+    series.sort_by_key(|email| email.index);
+    let total = series.iter().map(|email| email.total).max().unwrap_or(1);
+    if series.len() < total {
+        print_warning_message(
+            matches,
+            &format!("only {} of {total} patches found in thread", series.len()),
+        );
+    }
+
+    let committer = repo.get_committer()?;
+    let name_len_limit = PatchName::get_length_limit(&config);
+    let mut disallow: Vec<PatchName> = stack.all_patches().cloned().collect();
+    let new_patches = stupid.with_temp_index(|stupid_temp| {
+        let mut parent_id = stack.get_branch_head().id;
+        stupid_temp.read_tree(stack.get_branch_head().tree_id()?.detach())?;
+        let mut new_patches: Vec<(PatchName, git_repository::ObjectId)> = Vec::new();
+        for email in &series {
+            let info = &email.info;
+            let subject = info.subject.clone().unwrap_or_default();
+
+            stupid_temp
+                .apply_to_index(&info.diff)
+                .with_context(|| format!("applying patch \"{subject}\""))?;
+            let tree_id = stupid_temp.write_tree()?;
+
+            let default_author = repo.get_author()?;
+            let author = git_repository::actor::Signature {
+                name: info
+                    .author_name
+                    .as_deref()
+                    .map_or_else(|| default_author.name.into(), BString::from),
+                email: info
+                    .author_email
+                    .as_deref()
+                    .map_or_else(|| default_author.email.into(), BString::from),
+                time: info
+                    .date
+                    .as_deref()
+                    .and_then(|date| git_repository::actor::Time::parse_time(date).ok())
+                    .unwrap_or(default_author.time),
+            };
+
+            let body = info.message.to_str_lossy();
+            let mut message = if body.trim().is_empty() {
+                format!("{subject}\n")
+            } else {
+                format!("{subject}\n\n{}\n", body.trim_end())
+            };
+            if let (false, Some(message_id)) = (matches.get_flag("no-link"), &email.message_id) {
+                let link = format!(
+                    "{inbox_url}/{}",
+                    encode_message_id(message_id.trim_start_matches('<').trim_end_matches('>'))
+                );
+                message = String::from_utf8(
+                    stupid_temp
+                        .interpret_trailers(message.as_bytes(), [("Link", link.as_str())])?,
+                )
+                .map_err(|_| anyhow!("could not decode message after adding trailers"))?;
+            }
+
+            let commit_id = repo.commit_ex(
+                &author,
+                committer,
+                &Message::from(message.as_str()),
+                tree_id,
+                [parent_id],
+            )?;
+            let patchname =
+                PatchName::make(&message, true, name_len_limit).uniquify(&[], &disallow);
+            disallow.push(patchname.clone());
+            new_patches.push((patchname, commit_id));
+            parent_id = commit_id;
+        }
+        Ok(new_patches)
+    })?;
+
+    stack
+        .setup_transaction()
+        .use_index_and_worktree(true)
+        .with_output_stream(get_color_stdout(matches))
+        .transact(|trans| {
+            for (patchname, commit_id) in &new_patches {
+                trans.new_applied(patchname, *commit_id)?;
+            }
+            Ok(())
+        })
+        .execute(&format!("email retrieve: <{message_id}>"))?;
+
+    Ok(())
+}
+
+/// Split a public-inbox message URL into the inbox URL and the Message-Id.
+///
+/// The Message-Id is the first path component containing "@"; the components before
+/// it form the inbox URL.
+fn split_archive_url(url: &str) -> Result<(String, String)> {
+    let (scheme, rest) = url.split_once("://").expect("url contains scheme");
+    let components: Vec<&str> = rest.split('/').collect();
+    if let Some(pos) = components
+        .iter()
+        .skip(1)
+        .position(|component| component.contains('@'))
+    {
+        let pos = pos + 1;
+        Ok((
+            format!("{scheme}://{}", components[..pos].join("/")),
+            decode_message_id(components[pos]),
+        ))
+    } else {
+        Err(anyhow!("could not determine Message-Id from `{url}`"))
+    }
+}
+
+/// Percent-encode the characters of a Message-Id that are special in URL paths.
+pub(crate) fn encode_message_id(message_id: &str) -> String {
+    let mut encoded = String::with_capacity(message_id.len());
+    for c in message_id.chars() {
+        match c {
+            '%' | '/' | '?' | '#' | ' ' => encoded.push_str(&format!("%{:02X}", c as u32)),
+            _ => encoded.push(c),
+        }
+    }
+    encoded
+}
+
+/// Decode percent-encoded characters of a Message-Id taken from a URL.
+fn decode_message_id(component: &str) -> String {
+    let bytes = component.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let Ok(b) = u8::from_str_radix(&component[i + 1..i + 3], 16) {
+                decoded.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    decoded.to_str_lossy().to_string()
+}
+
+/// Parse the "[PATCH ...]" prefix of an email subject.
+///
+/// Returns the series version, the patch's index in the series, and the number of
+/// patches in the series. Subjects without a "PATCH" prefix yield `None`.
+fn parse_patch_prefix(subject: &str) -> Option<(usize, usize, usize)> {
+    let prefix = subject.strip_prefix('[')?.split_once(']')?.0;
+    let mut is_patch = false;
+    let mut version = 1;
+    let mut index = 1;
+    let mut total = 1;
+    for token in prefix.split_whitespace() {
+        if token.eq_ignore_ascii_case("patch") {
+            is_patch = true;
+        } else if let Some(n) = token
+            .strip_prefix(['v', 'V'])
+            .and_then(|n| n.parse::<usize>().ok())
+        {
+            version = n;
+        } else if let Some((n, m)) = token.split_once('/') {
+            if let (Ok(n), Ok(m)) = (n.parse::<usize>(), m.parse::<usize>()) {
+                index = n;
+                total = m;
+            }
+        }
+    }
+    is_patch.then_some((version, index, total))
+}
+
+#[cfg(all(feature = "import-url", feature = "import-compressed"))]
+fn download_thread(url: &str, dir: &Path) -> Result<PathBuf> {
+    let gz_path = super::super::import::download_url(url, dir)?;
+    let mbox_path = dir.join("thread.mbox");
+    let mut decoder = flate2::read::GzDecoder::new(std::fs::File::open(gz_path)?);
+    let mut mbox_file = std::fs::File::create(&mbox_path)?;
+    std::io::copy(&mut decoder, &mut mbox_file).context("decompressing thread mbox")?;
+    Ok(mbox_path)
+}
+
+#[cfg(not(all(feature = "import-url", feature = "import-compressed")))]
+fn download_thread(_: &str, _: &Path) -> Result<PathBuf> {
+    Err(anyhow!(
+        "StGit not built with support for retrieving email threads"
+    ))
+}
@@ -2,15 +2,34 @@
 
 //! `stg email send` implementation.
 
-use std::{path::Path, str::FromStr};
+use std::{
+    collections::BTreeSet,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    str::FromStr,
+};
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
+use bstr::ByteSlice;
 use clap::Arg;
+use is_terminal::IsTerminal;
+use termcolor::WriteColor;
 
+use super::{
+    aliases::AddressBook,
+    format::{COVER_BLURB_PLACEHOLDER, COVER_SUBJECT_PLACEHOLDER},
+};
 use crate::{
     argset,
+    color::get_color_stdout,
     ext::{CommitExtended, RepositoryExtended},
-    patch::patchrange,
+    patch::{
+        diffstat::{self, Diffstat},
+        mailinfo::MailInfo,
+        patchrange,
+    },
+    print_info_message, print_warning_message,
     stack::{Error, InitializationPolicy, Stack, StackAccess, StackStateAccess},
     stupid::Stupid,
 };
@@ -27,7 +46,8 @@ pub(super) fn command() -> clap::Command {
              The patches to send may be specified as files or directories generated by \
              `stg email format`, or as patch names/ranges as would be supplied to `stg \
              email format`. Specifying a directory will send all files in that \
-             directory.\n\
+             directory. When nothing to send is specified, the newest version \
+             directory written by `stg email format --auto-dir` is sent.\n\
              \n\
              The header of the email is configurable via command line options. The \
              user will be prompted for any necessary information not specified on the \
@@ -37,10 +57,16 @@ pub(super) fn command() -> clap::Command {
              configuration options. In particular, it is recommended to statically \
              configure SMTP details such as `sendemail.smtpServer`, \
              `sendemail.smtpUser`, etc. Refer to git-config(1) and git-send-email(1) \
-             man pages for more detail on all the available configuration options.",
+             man pages for more detail on all the available configuration options.\n\
+             \n\
+             SMTP servers that require OAuth2 authentication, such as those of Gmail \
+             and Office 365, are supported with '--oauth-token-cmd' or the \
+             `stgit.email.oauth-token-cmd` configuration variable. See \
+             '--oauth-token-cmd' below.",
         )
         .override_usage(
-            "stg email send [OPTIONS] <file|directory>...\n       \
+            "stg email send [OPTIONS]\n       \
+             stg email send [OPTIONS] <file|directory>...\n       \
              stg email send [OPTIONS] <patch>...\n       \
              stg email send [OPTIONS] --all\n       \
              stg email send --dump-aliases",
@@ -57,13 +83,16 @@ pub(super) fn command() -> clap::Command {
                      specified patches must be contiguous.\n\
                      \n\
                      If file or directory names are ambiguous with patch names, the \
-                     file or directory names will be used.",
+                     file or directory names will be used.\n\
+                     \n\
+                     When no sources are specified and '--all' is not used, the newest \
+                     `outgoing/<branch>/v<n>` directory written by `stg email format \
+                     --auto-dir` is sent.",
                 )
                 .value_name("source")
                 .num_args(1..)
                 .value_parser(clap::builder::NonEmptyStringValueParser::new())
-                .conflicts_with_all(["all", "dump-aliases"])
-                .required_unless_present_any(["all", "dump-aliases"]),
+                .conflicts_with_all(["all", "dump-aliases"]),
         )
         .arg(argset::branch_arg())
         .arg(
@@ -91,7 +120,25 @@ pub(super) fn command() -> clap::Command {
         )
         .next_help_heading("Compose Options")
         .args(compose_options())
+        .arg(
+            Arg::new("no-auto-cc")
+                .long("no-auto-cc")
+                .help("Do not Cc addresses from patch trailers")
+                .long_help(
+                    "Do not add the addresses from each patch's `Cc:` trailers and \
+                     trailers ending in `-by`, such as `Acked-by:` or \
+                     `Signed-off-by:`, to the recipients of that patch's email. This \
+                     is equivalent to passing '--suppress-cc=body' to `git \
+                     send-email`. Setting `stgit.email.auto-cc` to false also disables \
+                     adding these addresses.\n\
+                     \n\
+                     Note that `Cc:` headers already present in patch files, e.g. those \
+                     added by `stg email format`, are still honored.",
+                )
+                .action(clap::ArgAction::SetTrue),
+        )
         .next_help_heading("Send Options")
+        .args(send_options())
         .next_help_heading("Automate Options")
         .args(automate_options())
         .next_help_heading("Administer Options")
@@ -162,6 +209,33 @@ fn compose_options() -> Vec<Arg> {
             .value_parser(clap::builder::NonEmptyStringValueParser::new())
             .action(clap::ArgAction::Append)
             .value_hint(clap::ValueHint::EmailAddress),
+        Arg::new("to-cmd")
+            .long("to-cmd")
+            .help("Add \"To:\" addresses output by <command> for each patch")
+            .long_help(
+                "Specify a command to execute once per patch file which should \
+                 generate patch file specific \"To:\" entries, such as the Linux \
+                 kernel's `scripts/get_maintainer.pl`. Output of this command must be \
+                 a single email address per line. Default is the value of the \
+                 `stgit.email.tocmd` or `sendemail.toCmd` configuration value.",
+            )
+            .value_name("command")
+            .num_args(1)
+            .value_parser(clap::builder::NonEmptyStringValueParser::new())
+            .value_hint(clap::ValueHint::CommandString),
+        Arg::new("cc-cmd")
+            .long("cc-cmd")
+            .help("Add \"Cc:\" addresses output by <command> for each patch")
+            .long_help(
+                "Specify a command to execute once per patch file which should \
+                 generate patch file specific \"Cc:\" entries. Output of this command \
+                 must be a single email address per line. Default is the value of the \
+                 `stgit.email.cccmd` or `sendemail.ccCmd` configuration value.",
+            )
+            .value_name("command")
+            .num_args(1)
+            .value_parser(clap::builder::NonEmptyStringValueParser::new())
+            .value_hint(clap::ValueHint::CommandString),
         Arg::new("subject")
             .long("subject")
             .help("Specify email \"Subject:\"")
@@ -243,6 +317,106 @@ fn compose_options() -> Vec<Arg> {
     ]
 }
 
+fn send_options() -> Vec<Arg> {
+    vec![
+        Arg::new("oauth-token-cmd")
+            .long("oauth-token-cmd")
+            .help("Authenticate with an OAuth2 token output by <cmd>")
+            .long_help(
+                "Authenticate to the SMTP server using XOAUTH2 with an OAuth2 access token \
+                 obtained by running <cmd>. The command is run by the shell and must \
+                 output the access token on the first line of its standard output. Any \
+                 refreshing of tokens, e.g. using a stored refresh token, is up to the \
+                 command. The command is not run with '--dry-run'.\n\
+                 \n\
+                 The access token is provided to `git send-email` as the SMTP password, \
+                 via the environment instead of the command line, along with \
+                 '--smtp-auth=XOAUTH2'. The SMTP user must still be configured, e.g. \
+                 with `sendemail.smtpUser`. Note that `git send-email` requires a \
+                 version of the Authen::SASL Perl module that supports XOAUTH2.\n\
+                 \n\
+                 The `stgit.email.oauth-token-cmd` configuration variable provides the \
+                 default command.",
+            )
+            .value_name("cmd")
+            .num_args(1)
+            .value_parser(clap::builder::NonEmptyStringValueParser::new())
+            .value_hint(clap::ValueHint::CommandString),
+        Arg::new("batch-size")
+            .long("batch-size")
+            .help("Send at most <n> emails per SMTP connection")
+            .long_help(
+                "Send at most <n> emails per SMTP connection. After each batch of \
+                 emails, `git send-email` logs out of the SMTP server and logs in \
+                 again, optionally after the pause given by '--relogin-delay'. This \
+                 helps with SMTP servers and mailing lists that limit or greylist \
+                 large numbers of emails sent over one connection.\n\
+                 \n\
+                 Default is the value of the `sendemail.smtpBatchSize` configuration \
+                 value.",
+            )
+            .value_name("n")
+            .num_args(1)
+            .value_parser(clap::value_parser!(u32).range(1..))
+            .conflicts_with("send-delay"),
+        Arg::new("relogin-delay")
+            .long("relogin-delay")
+            .help("Wait <seconds> before reconnecting between batches")
+            .long_help(
+                "Wait <seconds> before reconnecting to the SMTP server after each \
+                 batch of emails sent with '--batch-size'.\n\
+                 \n\
+                 Default is the value of the `sendemail.smtpReloginDelay` \
+                 configuration value.",
+            )
+            .value_name("seconds")
+            .num_args(1)
+            .value_parser(clap::value_parser!(u32))
+            .conflicts_with("send-delay"),
+        Arg::new("send-delay")
+            .long("send-delay")
+            .visible_alias("smtp-pause")
+            .help("Wait <seconds> between sending each email")
+            .long_help(
+                "Wait <seconds> between sending each email. Each email is sent with \
+                 a separate invocation of `git send-email`, and thus a separate SMTP \
+                 connection. This requires the emails to be given as files or \
+                 directories, e.g. as output by `stg email format`.\n\
+                 \n\
+                 When threading is enabled, the first email is given a Message-Id, if \
+                 it does not already have one, and the following emails that are not \
+                 already replies are made replies to the first email.\n\
+                 \n\
+                 Default is the value of the `stgit.email.send-delay` configuration \
+                 value.",
+            )
+            .value_name("seconds")
+            .num_args(1)
+            .value_parser(clap::value_parser!(u64))
+            .conflicts_with("compose"),
+        Arg::new("retries")
+            .long("retries")
+            .help("Retry emails failing with a transient SMTP error <n> times")
+            .long_help(
+                "Retry sending an email up to <n> times when the SMTP server rejects it \
+                 with a transient error, i.e. a 4xx reply code such as \"421\" or \
+                 \"451 4.7.1\", as used by servers enforcing rate limits. Before the \
+                 first retry, the delay given by '--send-delay' is doubled, and the \
+                 delay is doubled again before each further retry.\n\
+                 \n\
+                 Only emails sent one at a time with '--send-delay' are retried, since \
+                 a failed invocation of `git send-email` sending several emails may \
+                 have already sent some of them.\n\
+                 \n\
+                 Default is the value of the `stgit.email.retries` configuration \
+                 value, or 3.",
+            )
+            .value_name("n")
+            .num_args(1)
+            .value_parser(clap::value_parser!(u32)),
+    ]
+}
+
 fn automate_options() -> Vec<Arg> {
     vec![
         Arg::new("identity")
@@ -301,12 +475,22 @@ fn administer_options() -> Vec<Arg> {
                  \n    automatically added addresses from the patch to the Cc list\
                  \n  - 'compose' will confirm before sending the first message\
                  \n    when using --compose\
-                 \n  - 'auto' is equivalent to cc + compose",
+                 \n  - 'auto' is equivalent to cc + compose\
+                 \n  - 'interactive' shows each email's headers and diffstat and\
+                 \n    asks whether to send it, edit its recipients, skip it, or\
+                 \n    abort before any email is sent\
+                 \n\
+                 \n\
+                 The 'interactive' mode is handled by StGit and requires the emails \
+                 to be given as files or directories, e.g. as output by `stg email \
+                 format`. Edited recipients only apply to the emails being sent; the \
+                 files are not modified. Recipients given with '--to', '--cc', and \
+                 '--bcc' are added by git send-email in addition to those shown.",
             )
             .hide_possible_values(true)
             .num_args(1)
             .value_name("mode")
-            .value_parser(["always", "never", "cc", "compose", "auto"]),
+            .value_parser(["always", "never", "cc", "compose", "auto", "interactive"]),
         Arg::new("quiet")
             .long("quiet")
             .help("Output one line of info per email")
@@ -323,6 +507,11 @@ fn administer_options() -> Vec<Arg> {
         Arg::new("dump-aliases")
             .long("dump-aliases")
             .help("Dump configured aliases and exit")
+            .long_help(
+                "Output the names of the aliases known to git-send-email along with \
+                 the groups defined by `stgit.email.group.<name>` configuration \
+                 variables, one per line, and exit.",
+            )
             .action(clap::ArgAction::SetTrue),
     ]
 }
@@ -366,8 +555,20 @@ fn format_options() -> Vec<Arg> {
 pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
     let repo = git_repository::Repository::open()?;
 
+    let address_book = AddressBook::from_config(&repo.config_snapshot())?;
+
     if matches.get_flag("dump-aliases") {
-        return repo.stupid().send_email_dump_aliases();
+        let output = repo.stupid().send_email_dump_aliases()?;
+        let mut names = output
+            .lines()
+            .map(|line| line.to_str_lossy().to_string())
+            .filter(|line| !line.is_empty())
+            .collect::<BTreeSet<_>>();
+        names.extend(address_book.names().map(String::from));
+        for name in names {
+            println!("{name}");
+        }
+        return Ok(());
     }
 
     let stack = Stack::from_branch(
@@ -400,18 +601,18 @@ pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
                 return Err(anyhow!("no patches to send"));
             } else {
                 for patchname in &patches {
-                    if stack.get_patch_commit(patchname).is_no_change()? {
+                    if stack.get_patch_commit(patchname)?.is_no_change()? {
                         return Err(anyhow!("cannot send empty patch `{patchname}`"));
                     }
                 }
             }
             let base = stack
-                .get_patch_commit(&patches[0])
+                .get_patch_commit(&patches[0])?
                 .parent_ids()
                 .next()
                 .unwrap()
                 .detach();
-            let last = stack.get_patch_commit(patches.last().unwrap()).id;
+            let last = stack.get_patch_commit_id(patches.last().unwrap());
             vec![format!("{base}..{last}")]
         }
     } else if matches.get_flag("all") {
@@ -420,16 +621,24 @@ pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
             return Err(Error::NoAppliedPatches.into());
         } else {
             for patchname in applied {
-                if stack.get_patch_commit(patchname).is_no_change()? {
+                if stack.get_patch_commit(patchname)?.is_no_change()? {
                     return Err(anyhow!("cannot send empty patch `{patchname}`"));
                 }
             }
         }
         let base = stack.base().id();
-        let last = stack.get_patch_commit(applied.last().unwrap()).id();
+        let last = stack.get_patch_commit_id(applied.last().unwrap());
         vec![format!("{base}..{last}")]
     } else {
-        panic!("expect either patchranges or -a/--all")
+        let outgoing_dir = super::format::outgoing_dir(&repo, stack.get_branch_name())?;
+        let (_, latest_dir) =
+            super::format::latest_outgoing_version(&outgoing_dir)?.ok_or_else(|| {
+                anyhow!(
+                    "nothing to send and no versioned output directory found in `{}`",
+                    outgoing_dir.display()
+                )
+            })?;
+        vec![latest_dir.to_string_lossy().to_string()]
     };
 
     let mut send_args = Vec::new();
@@ -453,9 +662,18 @@ pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
             if num_args.takes_values() {
                 let values = matches.get_many::<String>(arg_id).unwrap();
                 assert!(indices.len() == values.len());
-                indices.into_iter().zip(values).for_each(|(index, value)| {
-                    send_args.push((index, format!("--{long}={value}")));
-                });
+                for (index, value) in indices.into_iter().zip(values) {
+                    if arg_id == "confirm" && value == "interactive" {
+                        // The emails are confirmed before invoking git send-email.
+                        send_args.push((index, format!("--{long}=never")));
+                    } else if matches!(arg_id, "to" | "cc" | "bcc") {
+                        for address in address_book.expand(value)? {
+                            send_args.push((index, format!("--{long}={address}")));
+                        }
+                    } else {
+                        send_args.push((index, format!("--{long}={value}")));
+                    }
+                }
             } else {
                 indices.for_each(|index| send_args.push((index, format!("--{long}"))));
             }
@@ -466,12 +684,539 @@ pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
 
     let mut send_args = send_args.drain(..).map(|(_, s)| s).collect::<Vec<_>>();
 
+    if matches.get_flag("no-auto-cc")
+        || !repo
+            .config_snapshot()
+            .boolean("stgit.email.auto-cc")
+            .unwrap_or(true)
+    {
+        send_args.push("--suppress-cc=body".to_string());
+    }
+
+    let config = repo.config_snapshot();
+    for (id, key) in [
+        ("to-cmd", "stgit.email.tocmd"),
+        ("cc-cmd", "stgit.email.cccmd"),
+    ] {
+        if !matches.contains_id(id) {
+            if let Some(cmd) = config.string(key) {
+                send_args.push(format!("--{id}={}", cmd.to_str_lossy()));
+            }
+        }
+    }
+
+    let mut extra_config = Vec::new();
+    let oauth_token_cmd = argset::get_one_str(matches, "oauth-token-cmd")
+        .map(String::from)
+        .or_else(|| {
+            config
+                .string("stgit.email.oauth-token-cmd")
+                .map(|cmd| cmd.to_str_lossy().to_string())
+        });
+    // No authentication is performed for a dry run.
+    if let Some(oauth_token_cmd) = oauth_token_cmd.filter(|_| !matches.get_flag("dry-run")) {
+        let token = get_oauth_token(&oauth_token_cmd)?;
+        send_args.push("--smtp-auth=XOAUTH2".to_string());
+        extra_config.push(("sendemail.smtpPass".to_string(), token.clone()));
+        let identity = argset::get_one_str(matches, "identity")
+            .map(String::from)
+            .or_else(|| {
+                config
+                    .string("sendemail.identity")
+                    .map(|identity| identity.to_str_lossy().to_string())
+            });
+        if let Some(identity) = identity {
+            extra_config.push((format!("sendemail.{identity}.smtpPass"), token));
+        }
+    }
+
+    if let Some(batch_size) = matches.get_one::<u32>("batch-size") {
+        send_args.push(format!("--batch-size={batch_size}"));
+    }
+    if let Some(relogin_delay) = matches.get_one::<u32>("relogin-delay") {
+        send_args.push(format!("--relogin-delay={relogin_delay}"));
+    }
+    let send_delay = if let Some(send_delay) = matches.get_one::<u64>("send-delay") {
+        *send_delay
+    } else if matches.contains_id("batch-size") || matches.contains_id("relogin-delay") {
+        0
+    } else if let Some(send_delay) = config.integer("stgit.email.send-delay") {
+        u64::try_from(send_delay)
+            .map_err(|_| anyhow!("invalid `stgit.email.send-delay` value `{send_delay}`"))?
+    } else {
+        0
+    };
+    let retries = if let Some(retries) = matches.get_one::<u32>("retries") {
+        *retries
+    } else if let Some(retries) = config.integer("stgit.email.retries") {
+        u32::try_from(retries)
+            .map_err(|_| anyhow!("invalid `stgit.email.retries` value `{retries}`"))?
+    } else {
+        3
+    };
+    if send_delay > 0 && !sources.iter().all(|s| Path::new(s).exists()) {
+        return Err(anyhow!(
+            "sending with a delay requires emails formatted as files or directories"
+        ));
+    }
+
     if let Some(values) = matches.get_many::<String>("git-send-email-opt") {
         send_args.extend(values.cloned());
     }
 
-    let mut sources = sources;
-    send_args.append(&mut sources);
+    let confirm_dir;
+    let sources = if argset::get_one_str(matches, "confirm") == Some("interactive") {
+        if !sources.iter().all(|s| Path::new(s).exists()) {
+            return Err(anyhow!(
+                "`--confirm=interactive` requires emails formatted as files or directories"
+            ));
+        }
+        confirm_dir = tempfile::tempdir()?;
+        let confirmed = confirm_emails(
+            matches,
+            &repo,
+            &address_book,
+            &email_paths(&sources)?,
+            confirm_dir.path(),
+        )?;
+        if confirmed.is_empty() {
+            print_info_message(matches, "no emails to send");
+            return Ok(());
+        }
+        confirmed
+    } else {
+        sources
+    };
+
+    let cover_letter = if matches.get_flag("dry-run") {
+        None
+    } else {
+        find_cover_letter(&sources)?
+    };
+
+    if send_delay > 0 {
+        let delay_dir = tempfile::tempdir()?;
+        let thread =
+            !matches.get_flag("no-thread") && config.boolean("sendemail.thread").unwrap_or(true);
+        let paths = thread_delayed_emails(&email_paths(&sources)?, thread, delay_dir.path())?;
+        for (i, path) in paths.iter().enumerate() {
+            if i > 0 && !matches.get_flag("dry-run") {
+                std::thread::sleep(std::time::Duration::from_secs(send_delay));
+            }
+            let mut retry_delay = send_delay;
+            let mut attempt = 0;
+            loop {
+                match repo
+                    .stupid()
+                    .send_email(send_args.iter().chain([path]), &extra_config)
+                {
+                    Err(e) if attempt < retries && is_transient_smtp_error(&e) => {
+                        attempt += 1;
+                        retry_delay = retry_delay.saturating_mul(2);
+                        print_warning_message(
+                            matches,
+                            &format!(
+                                "transient SMTP error sending `{path}`; retrying in \
+                                 {retry_delay} seconds ({attempt} of {retries})"
+                            ),
+                        );
+                        if !matches.get_flag("dry-run") {
+                            std::thread::sleep(std::time::Duration::from_secs(retry_delay));
+                        }
+                    }
+                    result => break result?,
+                }
+            }
+        }
+    } else {
+        let mut sources = sources;
+        send_args.append(&mut sources);
+        repo.stupid().send_email(send_args, &extra_config)?;
+    }
+
+    if let Some((subject, blurb)) = cover_letter {
+        stack.record_cover_letter(subject.as_deref(), &blurb)?;
+    }
+    Ok(())
+}
+
+/// Determine whether `git send-email` failed due to a transient SMTP error.
+///
+/// Transient errors are recognized by a 4xx SMTP reply code or a 4.x.x enhanced status
+/// code starting one of the lines of the error output.
+fn is_transient_smtp_error(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        cause.to_string().lines().any(|line| {
+            let line = line.trim_start();
+            let line = line.strip_prefix("Result:").unwrap_or(line).trim_start();
+            let code = line
+                .split(|c: char| c.is_whitespace() || c == '-')
+                .next()
+                .unwrap_or_default();
+            let mut parts = code.split('.');
+            match (parts.next(), parts.next(), parts.next(), parts.next()) {
+                (Some(reply), None, None, None) => {
+                    reply.len() == 3
+                        && reply.starts_with('4')
+                        && reply.chars().all(|c| c.is_ascii_digit())
+                }
+                (Some("4"), Some(subject), Some(detail), None) => [subject, detail]
+                    .iter()
+                    .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit())),
+                _ => false,
+            }
+        })
+    })
+}
+
+/// Get the paths of the email files to send, expanding directories to their files.
+fn email_paths(sources: &[String]) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for source in sources {
+        let path = Path::new(source);
+        if path.is_dir() {
+            let mut dir_paths = std::fs::read_dir(path)?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<std::io::Result<Vec<_>>>()?;
+            dir_paths.sort();
+            paths.extend(dir_paths.into_iter().filter(|path| path.is_file()));
+        } else if path.is_file() {
+            paths.push(path.to_path_buf());
+        }
+    }
+    Ok(paths)
+}
+
+/// Prepare emails to be sent with separate invocations of `git send-email`.
+///
+/// Copies of the emails are written to `delay_dir` and their paths are returned in
+/// sending order. Since `git send-email` can only thread the emails of a single
+/// invocation, threading is done here when enabled: the first email is given a
+/// Message-Id if it lacks one and the other emails, unless already replies, are made
+/// replies to the first email.
+fn thread_delayed_emails(paths: &[PathBuf], thread: bool, delay_dir: &Path) -> Result<Vec<String>> {
+    let mut first_message_id: Option<String> = None;
+    let mut delayed = Vec::with_capacity(paths.len());
+
+    for (i, path) in paths.iter().enumerate() {
+        let mut content =
+            std::fs::read(path).with_context(|| format!("reading `{}`", path.to_string_lossy()))?;
+        if thread {
+            let headers = super::ingest::parse_headers(&content);
+            let find_header = |header: &str| {
+                headers
+                    .iter()
+                    .find(|(name, _)| name.eq_ignore_ascii_case(header))
+                    .map(|(_, value)| value.trim().to_string())
+            };
+            let new_headers = if let Some(first_message_id) = first_message_id.as_ref() {
+                if find_header("in-reply-to").is_none() {
+                    format!("In-Reply-To: {first_message_id}\nReferences: {first_message_id}\n")
+                } else {
+                    String::new()
+                }
+            } else if let Some(message_id) = find_header("message-id") {
+                first_message_id = Some(message_id);
+                String::new()
+            } else {
+                let message_id = make_message_id();
+                let new_headers = format!("Message-Id: {message_id}\n");
+                first_message_id = Some(message_id);
+                new_headers
+            };
+            if let Some(pos) = content.find(b"\n\n") {
+                content.splice(pos + 1..pos + 1, new_headers.bytes());
+            }
+        }
+        let delayed_path = delay_dir.join(format!("{:04}", i + 1));
+        std::fs::write(&delayed_path, &content)?;
+        delayed.push(delayed_path.to_string_lossy().to_string());
+    }
+
+    Ok(delayed)
+}
+
+/// Make a new, unique Message-Id, including angle brackets.
+fn make_message_id() -> String {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    let hostname = std::env::var("HOSTNAME")
+        .ok()
+        .filter(|hostname| !hostname.is_empty())
+        .unwrap_or_else(|| "localhost".to_string());
+    format!(
+        "<{}.{}.{}.stgit@{hostname}>",
+        now.as_secs(),
+        now.subsec_micros(),
+        std::process::id()
+    )
+}
+
+/// Interactively confirm each email before any email is sent.
+///
+/// The confirmed emails, including any edits to their recipients, are written to
+/// `confirm_dir` and their paths are returned in sending order.
+fn confirm_emails(
+    matches: &clap::ArgMatches,
+    repo: &git_repository::Repository,
+    address_book: &AddressBook,
+    paths: &[PathBuf],
+    confirm_dir: &Path,
+) -> Result<Vec<String>> {
+    use std::io::BufRead;
+
+    let mut stdout = get_color_stdout(matches);
+    let stdin = std::io::stdin();
+    let is_interactive = stdin.is_terminal();
+    let mut stdin = stdin.lock();
+    let mut confirmed = Vec::new();
+
+    for (i, path) in paths.iter().enumerate() {
+        let mut content =
+            std::fs::read(path).with_context(|| format!("reading `{}`", path.to_string_lossy()))?;
+        let diff = MailInfo::parse(&content).diff;
+        let diffstat = if diff.is_empty() {
+            Vec::new()
+        } else {
+            Diffstat::from_diff(&diff)
+                .render(diffstat::DEFAULT_WIDTH, None, false)
+                .into_bytes()
+        };
+
+        loop {
+            stdout.set_color(termcolor::ColorSpec::new().set_bold(true))?;
+            writeln!(
+                stdout,
+                "Email {} of {}: {}",
+                i + 1,
+                paths.len(),
+                path.to_string_lossy()
+            )?;
+            stdout.reset()?;
+            for line in ByteSlice::lines(content.as_slice()) {
+                if line.is_empty() {
+                    break;
+                } else if !line.starts_with(b"From ") {
+                    stdout.write_all(line)?;
+                    writeln!(stdout)?;
+                }
+            }
+            if !diffstat.is_empty() {
+                writeln!(stdout)?;
+                stdout.write_all(&diffstat)?;
+            }
+
+            write!(
+                stdout,
+                "Send this email? [y]es, [e]dit recipients, [s]kip, [a]bort: "
+            )?;
+            stdout.flush()?;
+            let mut answer = String::new();
+            let is_eof = stdin.read_line(&mut answer)? == 0;
+            if is_eof || !is_interactive {
+                // The answer was not echoed by a terminal.
+                writeln!(stdout)?;
+            }
+            match answer.trim() {
+                _ if is_eof => return Err(anyhow!("sending aborted; no emails were sent")),
+                "y" | "Y" | "yes" => {
+                    let confirmed_path = confirm_dir.join(format!("{:04}", i + 1));
+                    std::fs::write(&confirmed_path, &content)?;
+                    confirmed.push(confirmed_path.to_string_lossy().to_string());
+                    break;
+                }
+                "e" | "E" | "edit" => {
+                    content = edit_recipients(matches, repo, address_book, &content)?;
+                }
+                "s" | "S" | "skip" => break,
+                "a" | "A" | "abort" => return Err(anyhow!("sending aborted; no emails were sent")),
+                _ => {}
+            }
+        }
+    }
+
+    Ok(confirmed)
+}
+
+/// Edit the `To` and `Cc` recipients of an email in the user's editor.
+fn edit_recipients(
+    matches: &clap::ArgMatches,
+    repo: &git_repository::Repository,
+    address_book: &AddressBook,
+    content: &[u8],
+) -> Result<Vec<u8>> {
+    let headers = super::ingest::parse_headers(content);
+    let mut recipients = String::from(
+        "# Edit the recipients of this email, one per line, each prefixed by\n\
+         # \"To:\" or \"Cc:\". Lines starting with '#' are ignored.\n",
+    );
+    for (name, value) in &headers {
+        if name.eq_ignore_ascii_case("to") || name.eq_ignore_ascii_case("cc") {
+            let name = if name.eq_ignore_ascii_case("to") {
+                "To"
+            } else {
+                "Cc"
+            };
+            for address in split_addresses(value) {
+                recipients.push_str(&format!("{name}: {address}\n"));
+            }
+        }
+    }
+
+    let config = repo.config_snapshot();
+    let editor = crate::editor::get_editor(matches, &config)?;
+    let filename = ".stgit-recipients.txt";
+    std::fs::write(filename, recipients)?;
+    let edited = crate::editor::call_editor(filename, &editor, &config)?;
+
+    let mut to = Vec::new();
+    let mut cc = Vec::new();
+    for line in edited.lines() {
+        let line = line.to_str_lossy();
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| anyhow!("invalid recipient line `{line}`"))?;
+        let list = if name.trim().eq_ignore_ascii_case("to") {
+            &mut to
+        } else if name.trim().eq_ignore_ascii_case("cc") {
+            &mut cc
+        } else {
+            return Err(anyhow!("invalid recipient line `{line}`"));
+        };
+        for address in split_addresses(value) {
+            list.extend(address_book.expand(&address)?);
+        }
+    }
+
+    Ok(replace_recipients(content, &to, &cc))
+}
+
+/// Split a comma-separated list of addresses.
+fn split_addresses(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|address| !address.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Replace the `To` and `Cc` headers of an email.
+///
+/// The new headers take the place of the first original recipient header or, if there
+/// were none, precede the `Subject` header.
+fn replace_recipients(content: &[u8], to: &[String], cc: &[String]) -> Vec<u8> {
+    let mut new_headers = Vec::new();
+    if !to.is_empty() {
+        new_headers.extend_from_slice(format!("To: {}\n", to.join(",\n    ")).as_bytes());
+    }
+    if !cc.is_empty() {
+        new_headers.extend_from_slice(format!("Cc: {}\n", cc.join(",\n    ")).as_bytes());
+    }
+
+    let mut result = Vec::with_capacity(content.len() + new_headers.len());
+    let mut inserted = false;
+    let mut in_recipient_header = false;
+    let mut rest = content;
+    while !rest.is_empty() {
+        let line_len = rest.find_byte(b'\n').map_or(rest.len(), |pos| pos + 1);
+        let (line, remainder) = rest.split_at(line_len);
+        if line == b"\n" {
+            if !inserted {
+                result.extend_from_slice(&new_headers);
+            }
+            result.extend_from_slice(rest);
+            return result;
+        }
+        if line.starts_with(b" ") || line.starts_with(b"\t") {
+            if !in_recipient_header {
+                result.extend_from_slice(line);
+            }
+        } else {
+            let name = line.split_str(":").next().unwrap_or_default();
+            in_recipient_header =
+                name.eq_ignore_ascii_case(b"to") || name.eq_ignore_ascii_case(b"cc");
+            if !inserted && (in_recipient_header || name.eq_ignore_ascii_case(b"subject")) {
+                result.extend_from_slice(&new_headers);
+                inserted = true;
+            }
+            if !in_recipient_header {
+                result.extend_from_slice(line);
+            }
+        }
+        rest = remainder;
+    }
+    result
+}
 
-    repo.stupid().send_email(send_args)
+/// Find the cover letter, as formatted by `stg email format`, among the files to send.
+///
+/// Returns the cover letter's subject, without its prefix, and its introductory text
+/// preceding the patch summary. Placeholders that were not replaced are omitted.
+fn find_cover_letter(sources: &[String]) -> Result<Option<(Option<String>, String)>> {
+    for path in email_paths(sources)? {
+        let content = std::fs::read(&path)?;
+        if content.find(b"\ndiff --git ").is_some() {
+            continue;
+        }
+        let info = MailInfo::parse(&content);
+        let body = format!("\n{}", info.message.to_str_lossy());
+        let summary_pos = if let Some(pos) = body.find("\n  [").filter(|&pos| {
+            body[pos + 4..]
+                .split_once('/')
+                .map_or(false, |(number, _)| number.parse::<usize>().is_ok())
+        }) {
+            pos
+        } else {
+            continue;
+        };
+        let blurb = body[..summary_pos].trim();
+        let subject = info
+            .subject
+            .map(|subject| subject.trim().to_string())
+            .filter(|subject| !subject.is_empty() && subject != COVER_SUBJECT_PLACEHOLDER);
+        return Ok((!blurb.is_empty() && blurb != COVER_BLURB_PLACEHOLDER)
+            .then(|| (subject, blurb.to_string())));
+    }
+    Ok(None)
+}
+
+/// Run the OAuth2 token command and get the access token from its output.
+fn get_oauth_token(oauth_token_cmd: &str) -> Result<String> {
+    let shell = if cfg!(target_os = "windows") {
+        "sh"
+    } else {
+        "/bin/sh"
+    };
+    let output = Command::new(shell)
+        .arg("-c")
+        .arg(oauth_token_cmd)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()
+        .with_context(|| format!("running OAuth2 token command `{oauth_token_cmd}`"))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "OAuth2 token command `{oauth_token_cmd}` failed with {}",
+            output.status
+        ));
+    }
+    let token = output
+        .stdout
+        .lines()
+        .next()
+        .map(|line| line.to_str_lossy().trim().to_string())
+        .unwrap_or_default();
+    if token.is_empty() {
+        Err(anyhow!(
+            "OAuth2 token command `{oauth_token_cmd}` did not output a token"
+        ))
+    } else {
+        Ok(token)
+    }
 }
@@ -10,14 +10,20 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use clap::Arg;
 
 use crate::{
     argset,
     ext::{CommitExtended, RepositoryExtended},
-    patch::patchrange,
-    stack::{Error, InitializationPolicy, Stack, StackAccess, StackStateAccess},
+    patch::{
+        diffstat::{self, Diffstat},
+        patchrange, PatchName,
+    },
+    stack::{
+        state_refname_from_branch_name, Error, InitializationPolicy, Stack, StackAccess,
+        StackState, StackStateAccess,
+    },
     stupid::Stupid,
 };
 
@@ -38,6 +44,52 @@ fn make() -> clap::Command {
              Patches are exported to 'patches-<branch>' by default. The '--dir' option \
              may be used to specify a different output directory.\n\
              \n\
+             The '--stdout' and '--combined' options write all patches to standard \
+             output or a single file, respectively, e.g. to attach a whole series to a \
+             ticket. Each patch is preceded by a header with the patch's file name, \
+             and the patches are preceded by a \"series\" section listing them in \
+             order. Only the patch itself is written to standard output when a single \
+             patch is exported.\n\
+             \n\
+             The '--worktree' option exports a snapshot of each patch's tree instead \
+             of a diff. Each patch's files are written to a subdirectory of the given \
+             directory named after the patch. With '--changed-only', only the files \
+             added or modified by each patch are written. Such snapshots are useful \
+             with review and diff tools that operate on file trees rather than patch \
+             files.\n\
+             \n\
+             The '--filename-template' option names the patch files according to a \
+             template, e.g. to match the names expected by downstream tooling. A \
+             template containing \"/\" lays the patch files out in subdirectories of \
+             the output directory, which are listed with their paths in the \"series\" \
+             file. The '--number-width' option sets the number of digits that patch \
+             numbers are zero-padded to, both for '--numbered' and the template's \
+             '%n' placeholder.\n\
+             \n\
+             The '--archive' option packages the patch files and the \"series\" \
+             file into a single compressed archive instead, e.g. to attach a series \
+             to a bug tracker. The archive is either a \"tar.gz\" or a \"zip\" file, \
+             and its files are placed in a top-level directory named like the \
+             default output directory. A \"tar.gz\" archive may be imported with \
+             `stg import --series`.\n\
+             \n\
+             The '--bundle' option writes the patches to a git bundle file instead. \
+             The bundle contains a branch and a stack state with only the exported \
+             patches, which must be consecutive applied patches, and may be applied \
+             with `stg bundle apply` in another clone of the repository that contains \
+             the commit the first exported patch applies on. This allows a series to be \
+             moved between machines without a common remote.\n\
+             \n\
+             The '--quilt' option exports the patches as a quilt series suitable for \
+             quilt and Debian source packages: patch files with a \".patch\" suffix \
+             are written to 'patches' by default, along with a 'series' file listing \
+             them in order. The '--strip-level' option selects whether the patches \
+             are applied with '-p1', the default, or '-p0'. The '--quilt-header' \
+             option selects the header written at the start of each patch file: \
+             \"template\" uses the template described below, \"dep3\" writes a \
+             Debian DEP-3 header with the patch description and author, and \
+             \"none\" writes only the diff.\n\
+             \n\
              The patch file output may be customized via a template file found at \
              \"$GIT_DIR/patchexport.tmpl\", \"~/.stgit/templates/patchexport.tmpl\", \
              or \"$(prefix)/share/stgit/templates\". The following variables are \
@@ -98,6 +150,40 @@ fn make() -> clap::Command {
                 .help("Prefix patch file names with order numbers.")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("number-width")
+                .long("number-width")
+                .help("Zero-pad patch numbers to <n> digits")
+                .long_help(
+                    "Zero-pad patch numbers to <n> digits. The default is the number of \
+                     digits in the number of exported patches, but at least 2.",
+                )
+                .value_name("n")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("filename-template")
+                .long("filename-template")
+                .help("Name patch files according to <template>")
+                .long_help(
+                    "Name the patch files according to <template> instead of after the \
+                     patch names. The following placeholders are expanded:\n\
+                     \n\
+                     '%n' the zero-padded patch number, e.g. `01`.\n\
+                     '%p' the patch name.\n\
+                     '%s' the sanitized subject of the patch, e.g. `add-makefile`.\n\
+                     '%%' a literal `%`.\n\
+                     \n\
+                     The template must include the file name suffix, if any. For \
+                     example, '--filename-template=%n-%p.patch'. The template may \
+                     contain \"/\" to write the patch files to subdirectories of the \
+                     output directory, e.g. '--filename-template=%p/%p.diff'.",
+                )
+                .value_name("template")
+                .num_args(1)
+                .value_parser(clap::builder::NonEmptyStringValueParser::new())
+                .conflicts_with_all(["patch", "extension", "numbered"]),
+        )
         .arg(
             Arg::new("template")
                 .long("template")
@@ -115,6 +201,123 @@ fn make() -> clap::Command {
                 .conflicts_with("dir")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("combined")
+                .long("combined")
+                .help("Export all patches to a single <file>")
+                .value_name("file")
+                .value_hint(clap::ValueHint::FilePath)
+                .value_parser(clap::value_parser!(PathBuf))
+                .conflicts_with_all(["dir", "stdout"]),
+        )
+        .arg(
+            Arg::new("archive")
+                .long("archive")
+                .help("Export patches to a compressed archive <file>")
+                .long_help(
+                    "Export the patches and the series file to a compressed archive \
+                     <file>. The <format> is either \"tar.gz\" or \"zip\".",
+                )
+                .num_args(2)
+                .value_names(["format", "file"])
+                .value_parser(clap::value_parser!(OsString))
+                .conflicts_with_all(["dir", "stdout", "combined"]),
+        )
+        .arg(
+            Arg::new("worktree")
+                .long("worktree")
+                .help("Export a snapshot of each patch's tree to a subdirectory of <dir>")
+                .value_name("dir")
+                .value_hint(clap::ValueHint::DirPath)
+                .value_parser(clap::value_parser!(PathBuf))
+                .conflicts_with_all([
+                    "dir",
+                    "stdout",
+                    "combined",
+                    "archive",
+                    "template",
+                    "patch",
+                    "extension",
+                    "filename-template",
+                ]),
+        )
+        .arg(
+            Arg::new("changed-only")
+                .long("changed-only")
+                .help("Only write files changed by each patch to the snapshots")
+                .requires("worktree")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("bundle")
+                .long("bundle")
+                .help("Export patches to a git bundle <file>")
+                .value_name("file")
+                .value_hint(clap::ValueHint::FilePath)
+                .value_parser(clap::value_parser!(PathBuf))
+                .conflicts_with_all([
+                    "dir",
+                    "stdout",
+                    "combined",
+                    "archive",
+                    "worktree",
+                    "template",
+                    "patch",
+                    "extension",
+                    "numbered",
+                    "number-width",
+                    "filename-template",
+                    "quilt",
+                ]),
+        )
+        .arg(
+            Arg::new("quilt")
+                .long("quilt")
+                .help("Export patches as a quilt series")
+                .conflicts_with_all(["stdout", "combined", "worktree"])
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("strip-level")
+                .long("strip-level")
+                .help("Export quilt patches to be applied with -p<n>")
+                .value_name("n")
+                .requires("quilt")
+                .value_parser(["0", "1"]),
+        )
+        .arg(
+            Arg::new("quilt-header")
+                .long("quilt-header")
+                .help("Header style of exported quilt patches")
+                .value_name("style")
+                .requires("quilt")
+                .value_parser(["template", "dep3", "none"]),
+        )
+        .arg(
+            Arg::new("binary")
+                .long("binary")
+                .help("Output binary diffs (default)")
+                .long_help(
+                    "Output the contents of changes to binary files, such that the \
+                     exported patches may be imported into any repository. This is \
+                     the default.",
+                )
+                .overrides_with("no-binary")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no-binary")
+                .long("no-binary")
+                .help("Do not output binary diffs")
+                .long_help(
+                    "Do not output the contents of changes to binary files. Instead, a \
+                     notice that the files differ is output. Patches with such notices \
+                     may only be imported into repositories that already contain the \
+                     changed files' contents.",
+                )
+                .overrides_with("binary")
+                .action(clap::ArgAction::SetTrue),
+        )
         .arg(argset::diff_opts_arg())
 }
 
@@ -152,11 +355,56 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
         return Err(Error::NoAppliedPatches.into());
     }
 
+    if let Some(bundle_path) = matches.get_one::<PathBuf>("bundle") {
+        return export_bundle(&stack, &patches, bundle_path);
+    }
+
+    let archive = if let Some(mut values) = matches.get_many::<OsString>("archive") {
+        let format = values.next().expect("archive takes a format and a file");
+        let path = Path::new(values.next().expect("archive takes a format and a file"));
+        let format = match format.to_str() {
+            Some("tar.gz") => ArchiveFormat::TarGz,
+            Some("zip") => ArchiveFormat::Zip,
+            _ => {
+                return Err(anyhow!(
+                    "unsupported archive format `{}`; use `tar.gz` or `zip`",
+                    format.to_string_lossy()
+                ))
+            }
+        };
+        Some((format, path))
+    } else {
+        None
+    };
+
+    let num_width = matches
+        .get_one::<usize>("number-width")
+        .copied()
+        .unwrap_or_else(|| std::cmp::max(patches.len().to_string().len(), 2));
+
+    if let Some(worktree_dir) = matches.get_one::<PathBuf>("worktree") {
+        return export_worktrees(
+            &stack,
+            &patches,
+            worktree_dir,
+            matches.get_flag("numbered").then_some(num_width),
+            matches.get_flag("changed-only"),
+        );
+    }
+
+    let quilt_flag = matches.get_flag("quilt");
+    let strip_level = argset::get_one_str(matches, "strip-level").unwrap_or("1");
+    let header_style = argset::get_one_str(matches, "quilt-header").unwrap_or("template");
+
     let default_output_dir;
     let output_dir = if let Some(dir) = matches.get_one::<PathBuf>("dir").map(PathBuf::as_path) {
         dir
     } else {
-        default_output_dir = format!("patches-{}", stack.get_branch_name());
+        default_output_dir = if quilt_flag {
+            "patches".to_string()
+        } else {
+            format!("patches-{}", stack.get_branch_name())
+        };
         Path::new(default_output_dir.as_str())
     };
 
@@ -164,16 +412,21 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
     let extension = if let Some(custom_ext) = matches.get_one::<String>("extension") {
         custom_extension = format!(".{custom_ext}");
         custom_extension.as_str()
-    } else if matches.get_flag("patch") {
+    } else if matches.get_flag("patch") || quilt_flag {
         ".patch"
     } else {
         ""
     };
 
     let numbered_flag = matches.get_flag("numbered");
-    let num_width = std::cmp::max(patches.len().to_string().len(), 2);
 
-    let diff_opts = argset::get_diff_opts(matches, &repo.config_snapshot(), false, true);
+    // Full object ids are needed to apply binary diffs and, without binary diffs, to
+    // apply changes to binary files in repositories that already contain them.
+    let binary = !matches.get_flag("no-binary");
+    let mut diff_opts = argset::get_diff_opts(matches, &repo.config_snapshot(), true, binary)?;
+    if strip_level == "0" {
+        diff_opts.push("--no-prefix".to_string());
+    }
 
     let template = if let Some(template_file) = matches.get_one::<PathBuf>("template") {
         Cow::Owned(std::fs::read_to_string(template_file)?)
@@ -185,30 +438,81 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
         }
     };
 
-    let need_diffstat = template.contains("%(diffstat)");
+    let need_diffstat = header_style == "template" && template.contains("%(diffstat)");
+
+    let filename_template = argset::get_one_str(matches, "filename-template");
+    let patchfile_names: Vec<String> = patches
+        .iter()
+        .enumerate()
+        .map(|(i, patchname)| {
+            let patch_number = i + 1;
+            if let Some(template) = filename_template {
+                let message = stack.get_patch_commit(patchname)?.message_ex();
+                let message = message.decode()?;
+                let subject = message.lines().next().unwrap_or_default();
+                expand_filename_template(template, patch_number, num_width, patchname, subject)
+            } else if numbered_flag {
+                Ok(format!("{patch_number:0num_width$}-{patchname}{extension}"))
+            } else {
+                Ok(format!("{patchname}{extension}"))
+            }
+        })
+        .collect::<Result<_>>()?;
+
+    if let Some(template) = filename_template {
+        let mut seen: HashMap<&str, &PatchName> = HashMap::new();
+        for (patchname, patchfile_name) in patches.iter().zip(&patchfile_names) {
+            if let Some(other) = seen.insert(patchfile_name, patchname) {
+                return Err(anyhow!(
+                    "filename template `{template}` names both `{other}` and \
+                     `{patchname}` `{patchfile_name}`"
+                ));
+            } else if patchfile_name == "series" {
+                return Err(anyhow!(
+                    "filename template `{template}` names `{patchname}` `series`"
+                ));
+            }
+        }
+    }
 
-    let stdout_flag = matches.get_flag("stdout");
     let mut series = format!(
         "# This series applies on Git commit {}\n",
         stack.base().id()
     );
-
-    if !stdout_flag {
-        std::fs::create_dir_all(output_dir).with_context(|| format!("creating {output_dir:?}"))?;
+    for patchfile_name in &patchfile_names {
+        series.push_str(patchfile_name);
+        if strip_level != "1" {
+            series.push_str(" -p");
+            series.push_str(strip_level);
+        }
+        series.push('\n');
     }
 
-    for (i, patchname) in patches.iter().enumerate() {
-        let patchfile_name = if numbered_flag {
-            let patch_number = i + 1;
-            format!("{patch_number:0num_width$}-{patchname}{extension}")
-        } else {
-            format!("{patchname}{extension}")
-        };
+    // Patches are either written to a combined output, i.e. stdout or a single file, or
+    // to separate files in the output directory.
+    // Archives are assembled in memory before being compressed.
+    let combined_path = matches.get_one::<PathBuf>("combined");
+    let mut archive_files: Vec<(String, Vec<u8>)> = Vec::new();
+    let mut combined: Option<Box<dyn Write>> = if matches.get_flag("stdout") {
+        Some(Box::new(std::io::stdout().lock()))
+    } else if let Some(path) = combined_path {
+        let file = std::fs::File::create(path).with_context(|| format!("creating {path:?}"))?;
+        Some(Box::new(std::io::BufWriter::new(file)))
+    } else if archive.is_none() {
+        std::fs::create_dir_all(output_dir).with_context(|| format!("creating {output_dir:?}"))?;
+        None
+    } else {
+        None
+    };
+    let section_headers = combined_path.is_some() || patches.len() > 1;
 
-        series.push_str(&patchfile_name);
-        series.push('\n');
+    if let (Some(output), true) = (combined.as_mut(), section_headers) {
+        write_section_header(output, "series")?;
+        output.write_all(series.as_bytes())?;
+    }
 
-        let patch_commit = stack.get_patch_commit(patchname);
+    for (patchname, patchfile_name) in patches.iter().zip(patchfile_names.iter()) {
+        let patch_commit = stack.get_patch_commit(patchname)?;
         let parent_commit = patch_commit.get_parent_commit()?;
 
         let mut replacements: HashMap<&str, Cow<'_, [u8]>> = HashMap::new();
@@ -264,39 +568,63 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
                 if parent_commit.tree_id()? == patch_commit.tree_id()? {
                     Cow::Borrowed(b"")
                 } else {
-                    Cow::Owned(stupid.diffstat(&diff)?)
+                    Cow::Owned(
+                        Diffstat::from_diff(&diff)
+                            .render(diffstat::DEFAULT_WIDTH, None, false)
+                            .into_bytes(),
+                    )
                 },
             );
         }
 
-        let specialized = crate::templates::specialize_template(&template, &replacements);
-
-        if stdout_flag {
-            let stdout = std::io::stdout();
-            let mut stdout = stdout.lock();
-            if patches.len() > 1 {
-                write!(
-                    stdout,
-                    "{0:->79}\n\
-                     {patchfile_name}\n\
-                     {0:->79}\n",
-                    '-'
-                )?;
+        let specialized = match header_style {
+            "dep3" => dep3_header(shortdescr, longdescr, &author),
+            "none" => Vec::new(),
+            _ => crate::templates::specialize_template(&template, &replacements),
+        };
+
+        if let Some(output) = combined.as_mut() {
+            if section_headers {
+                write_section_header(output, patchfile_name)?;
             }
-            stdout.write_all(&specialized)?;
-            stdout.write_all(&diff)?;
+            output.write_all(&specialized)?;
+            output.write_all(&diff)?;
+        } else if archive.is_some() {
+            let mut content = specialized;
+            content.extend_from_slice(&diff);
+            archive_files.push((patchfile_name.clone(), content));
         } else {
+            let patchfile_path = output_dir.join(patchfile_name);
+            if let Some(parent_dir) = patchfile_path.parent() {
+                std::fs::create_dir_all(parent_dir)
+                    .with_context(|| format!("creating {parent_dir:?}"))?;
+            }
             let mut file = std::fs::File::options()
                 .write(true)
                 .create(true)
-                .open(output_dir.join(&patchfile_name))
+                .truncate(true)
+                .open(patchfile_path)
                 .with_context(|| format!("opening {patchfile_name}"))?;
             file.write_all(&specialized)?;
             file.write_all(&diff)?;
         }
     }
 
-    if !stdout_flag {
+    if let Some(mut output) = combined {
+        output.flush()?;
+    } else if let Some((format, path)) = archive {
+        archive_files.push(("series".to_string(), series.into_bytes()));
+        let top_dir = output_dir.to_string_lossy();
+        for (name, _) in archive_files.iter_mut() {
+            *name = format!("{top_dir}/{name}");
+        }
+        let file = std::fs::File::create(path).with_context(|| format!("creating {path:?}"))?;
+        match format {
+            ArchiveFormat::TarGz => write_tar_gz(file, &archive_files),
+            ArchiveFormat::Zip => write_zip(file, &archive_files),
+        }
+        .with_context(|| format!("writing {path:?}"))?;
+    } else {
         let series_path = output_dir.join("series");
         std::fs::write(&series_path, series.as_str())
             .with_context(|| format!("writing {series_path:?}"))?;
@@ -304,3 +632,313 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
 
     Ok(())
 }
+
+/// Expand the placeholders of a `--filename-template` for a patch file name.
+///
+/// The expanded name may contain `/` separators to lay out the patch files in
+/// subdirectories, but must remain within the output directory.
+fn expand_filename_template(
+    template: &str,
+    number: usize,
+    num_width: usize,
+    patchname: &PatchName,
+    subject: &str,
+) -> Result<String> {
+    let mut filename = String::with_capacity(template.len() + patchname.len());
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c == '%' {
+            match chars.next() {
+                Some('n') => filename.push_str(&format!("{number:0num_width$}")),
+                Some('p') => filename.push_str(patchname.as_ref()),
+                Some('s') => filename.push_str(&super::email::sanitize_subject(subject, 52)),
+                Some('%') => filename.push('%'),
+                Some(c) => {
+                    return Err(anyhow!(
+                        "unknown placeholder `%{c}` in filename template `{template}`"
+                    ))
+                }
+                None => {
+                    return Err(anyhow!(
+                        "incomplete placeholder in filename template `{template}`"
+                    ))
+                }
+            }
+        } else {
+            filename.push(c);
+        }
+    }
+    if filename
+        .split('/')
+        .any(|component| matches!(component, "" | "." | ".."))
+    {
+        return Err(anyhow!(
+            "filename template `{template}` expands to invalid file name `{filename}`"
+        ));
+    }
+    Ok(filename)
+}
+
+/// Compressed archive formats supported by `--archive`.
+#[derive(Clone, Copy)]
+enum ArchiveFormat {
+    TarGz,
+    Zip,
+}
+
+/// Write files to a gzip-compressed tar archive.
+#[cfg(feature = "export-archive")]
+fn write_tar_gz(file: std::fs::File, files: &[(String, Vec<u8>)]) -> Result<()> {
+    let mtime = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let encoder = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+    for (name, content) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Regular);
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(mtime);
+        builder.append_data(&mut header, name, content.as_slice())?;
+    }
+    builder.into_inner()?.finish()?;
+    Ok(())
+}
+
+/// Write files to a zip archive, compressing each file with deflate.
+#[cfg(feature = "export-archive")]
+fn write_zip(file: std::fs::File, files: &[(String, Vec<u8>)]) -> Result<()> {
+    use chrono::{Datelike, Timelike};
+
+    const LOCAL_FILE_HEADER: u32 = 0x04034b50;
+    const CENTRAL_DIRECTORY_HEADER: u32 = 0x02014b50;
+    const END_OF_CENTRAL_DIRECTORY: u32 = 0x06054b50;
+    const VERSION: u16 = 20;
+    const UTF8_NAMES: u16 = 0x0800;
+    const DEFLATE: u16 = 8;
+
+    let too_large = || anyhow!("too many or too large patches for a zip archive");
+    let now = chrono::Local::now();
+    let dos_time = ((now.hour() << 11) | (now.minute() << 5) | (now.second() / 2)) as u16;
+    let dos_date =
+        (((now.year().max(1980) - 1980) as u32) << 9 | (now.month() << 5) | now.day()) as u16;
+
+    let mut output = std::io::BufWriter::new(file);
+    let mut central_directory: Vec<u8> = Vec::new();
+    let mut offset: u32 = 0;
+    for (name, content) in files {
+        let mut crc = flate2::Crc::new();
+        crc.update(content);
+        let mut encoder =
+            flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(content)?;
+        let compressed = encoder.finish()?;
+        let compressed_size = u32::try_from(compressed.len()).map_err(|_| too_large())?;
+        let size = u32::try_from(content.len()).map_err(|_| too_large())?;
+        let name_len = u16::try_from(name.len()).map_err(|_| too_large())?;
+
+        let mut common = Vec::with_capacity(26);
+        for field in [VERSION, UTF8_NAMES, DEFLATE, dos_time, dos_date] {
+            common.extend_from_slice(&field.to_le_bytes());
+        }
+        for field in [crc.sum(), compressed_size, size] {
+            common.extend_from_slice(&field.to_le_bytes());
+        }
+        common.extend_from_slice(&name_len.to_le_bytes());
+
+        let mut local_header = LOCAL_FILE_HEADER.to_le_bytes().to_vec();
+        local_header.extend_from_slice(&common);
+        local_header.extend_from_slice(&0u16.to_le_bytes()); // extra field length
+        local_header.extend_from_slice(name.as_bytes());
+        output.write_all(&local_header)?;
+        output.write_all(&compressed)?;
+
+        // Version made by Unix, such that the external attributes hold the file mode.
+        central_directory.extend_from_slice(&CENTRAL_DIRECTORY_HEADER.to_le_bytes());
+        central_directory.extend_from_slice(&(0x0300 | VERSION).to_le_bytes());
+        central_directory.extend_from_slice(&common);
+        for field in [0u16; 4] {
+            // Extra field and comment lengths, disk number, and internal attributes.
+            central_directory.extend_from_slice(&field.to_le_bytes());
+        }
+        central_directory.extend_from_slice(&(0o100644u32 << 16).to_le_bytes());
+        central_directory.extend_from_slice(&offset.to_le_bytes());
+        central_directory.extend_from_slice(name.as_bytes());
+
+        offset = u32::try_from(local_header.len() + compressed.len())
+            .ok()
+            .and_then(|len| offset.checked_add(len))
+            .ok_or_else(too_large)?;
+    }
+
+    let count = u16::try_from(files.len()).map_err(|_| too_large())?;
+    let central_directory_size = u32::try_from(central_directory.len()).map_err(|_| too_large())?;
+    output.write_all(&central_directory)?;
+    output.write_all(&END_OF_CENTRAL_DIRECTORY.to_le_bytes())?;
+    for field in [0u16, 0u16, count, count] {
+        output.write_all(&field.to_le_bytes())?;
+    }
+    output.write_all(&central_directory_size.to_le_bytes())?;
+    output.write_all(&offset.to_le_bytes())?;
+    output.write_all(&0u16.to_le_bytes())?; // comment length
+    output.flush()?;
+    Ok(())
+}
+
+#[cfg(not(feature = "export-archive"))]
+fn write_tar_gz(_: std::fs::File, _: &[(String, Vec<u8>)]) -> Result<()> {
+    Err(anyhow!("StGit not built with support for archive export"))
+}
+
+#[cfg(not(feature = "export-archive"))]
+fn write_zip(_: std::fs::File, _: &[(String, Vec<u8>)]) -> Result<()> {
+    Err(anyhow!("StGit not built with support for archive export"))
+}
+
+/// Write the header separating sections of combined output.
+fn write_section_header(output: &mut dyn Write, name: &str) -> Result<()> {
+    write!(
+        output,
+        "{0:->79}\n\
+         {name}\n\
+         {0:->79}\n",
+        '-'
+    )?;
+    Ok(())
+}
+
+/// Write the given consecutive applied patches to a stack bundle file.
+///
+/// The bundle's stack state only contains the given patches and has no history. The
+/// bundle's branch is the topmost given patch.
+fn export_bundle(stack: &Stack, patches: &[PatchName], path: &Path) -> Result<()> {
+    let first_pos = stack
+        .applied()
+        .iter()
+        .position(|pn| pn == &patches[0])
+        .filter(|&pos| stack.applied()[pos..].starts_with(patches))
+        .ok_or_else(|| anyhow!("only consecutive applied patches may be exported to a bundle"))?;
+    let patches = &stack.applied()[first_pos..first_pos + patches.len()];
+
+    let repo = stack.repo;
+    let top = stack.get_patch_commit(patches.last().expect("patches is not empty"))?;
+    let state = StackState {
+        prev: None,
+        head: top.clone(),
+        applied: patches.to_vec(),
+        unapplied: vec![],
+        hidden: vec![],
+        patches: patches
+            .iter()
+            .map(|pn| (pn.clone(), stack.get_patch(pn).clone()))
+            .collect(),
+    };
+    let state_id = state.commit(repo, None, "export bundle")?;
+
+    let branchname = stack.get_branch_name();
+    let branch_refname = format!("refs/heads/{branchname}");
+    let state_refname = state_refname_from_branch_name(repo, branchname)?;
+    let base_id = stack
+        .get_patch_commit(&patches[0])?
+        .parent_ids()
+        .next()
+        .expect("patch commit has a parent")
+        .to_string();
+
+    repo.stupid().bundle_create_with_refs(
+        path,
+        &[
+            (branch_refname.as_str(), top.id),
+            (state_refname.as_str(), state_id),
+        ],
+        [base_id],
+    )
+}
+
+/// Make a DEP-3 patch header, as used for patches in Debian source packages.
+fn dep3_header(
+    shortdescr: &str,
+    longdescr: &str,
+    author: &git_repository::actor::SignatureRef<'_>,
+) -> Vec<u8> {
+    let mut header = format!("Description: {shortdescr}\n");
+    for line in longdescr.lines() {
+        if line.trim().is_empty() {
+            header.push_str(" .\n");
+        } else {
+            header.push(' ');
+            header.push_str(line);
+            header.push('\n');
+        }
+    }
+    header.push_str(&format!("Author: {} <{}>\n", author.name, author.email));
+    header.push_str(&format!(
+        "Last-Update: {}\n",
+        author
+            .time
+            .format(git_repository::date::time::format::SHORT)
+    ));
+    header.push_str("---\n");
+    header.into_bytes()
+}
+
+/// Write a snapshot of each patch's tree to a per-patch subdirectory of `output_dir`.
+///
+/// When `num_width` is given, the subdirectory names are prefixed with the patch
+/// numbers zero-padded to that many digits.
+fn export_worktrees(
+    stack: &Stack,
+    patches: &[PatchName],
+    output_dir: &Path,
+    num_width: Option<usize>,
+    changed_only: bool,
+) -> Result<()> {
+    let stupid = stack.repo.stupid();
+    let mut series = format!(
+        "# This series applies on Git commit {}\n",
+        stack.base().id()
+    );
+
+    std::fs::create_dir_all(output_dir).with_context(|| format!("creating {output_dir:?}"))?;
+
+    for (i, patchname) in patches.iter().enumerate() {
+        let snapshot_name = if let Some(num_width) = num_width {
+            let patch_number = i + 1;
+            format!("{patch_number:0num_width$}-{patchname}")
+        } else {
+            patchname.to_string()
+        };
+
+        series.push_str(&snapshot_name);
+        series.push('\n');
+
+        let snapshot_dir = output_dir.join(&snapshot_name);
+        if snapshot_dir.exists() {
+            std::fs::remove_dir_all(&snapshot_dir)
+                .with_context(|| format!("removing {snapshot_dir:?}"))?;
+        }
+        std::fs::create_dir(&snapshot_dir).with_context(|| format!("creating {snapshot_dir:?}"))?;
+
+        let patch_commit = stack.get_patch_commit(patchname)?;
+        let tree_id = patch_commit.tree_id()?.detach();
+        let changed_files = if changed_only {
+            let parent_tree_id = patch_commit.get_parent_commit()?.tree_id()?.detach();
+            Some(stupid.diff_tree_files_present(parent_tree_id, tree_id)?)
+        } else {
+            None
+        };
+
+        stupid.with_temp_index(|stupid_temp| {
+            stupid_temp.read_tree(tree_id)?;
+            stupid_temp
+                .checkout_index_to_dir(&snapshot_dir, changed_files.as_ref().map(|f| f.iter()))
+        })?;
+    }
+
+    let series_path = output_dir.join("series");
+    std::fs::write(&series_path, series.as_str())
+        .with_context(|| format!("writing {series_path:?}"))?;
+
+    Ok(())
+}
@@ -0,0 +1,194 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg fetch-stack` implementation.
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use bstr::ByteSlice;
+use clap::{Arg, ArgMatches};
+
+use crate::{
+    ext::{CommitExtended, RepositoryExtended},
+    patch::PatchName,
+    stack::{StackState, StackStateAccess},
+    stupid::Stupid,
+};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "fetch-stack",
+    category: super::CommandCategory::StackInspection,
+    make,
+    run,
+};
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Fetch published stacks from a remote repository")
+        .long_about(
+            "Fetch the StGit stacks published in a remote repository.\n\
+             \n\
+             The remote's stack state references, `refs/stacks/*`, are mirrored \
+             locally as `refs/remote-stacks/<remote>/*` and its patch references, \
+             `refs/patches/*`, as `refs/remote-patches/<remote>/*`. The mirrored refs \
+             may be inspected with the usual git commands, e.g. to review another \
+             developer's patches.\n\
+             \n\
+             The remote repository may be specified on the command line, but defaults \
+             to branch.<name>.remote from the git configuration, or \"origin\" if not \
+             configured.\n\
+             \n\
+             After fetching, the differences between the previously and newly \
+             mirrored stacks are reported: new stacks, removed stacks, and the patches \
+             added to ('+'), removed from ('-'), or modified in ('~') each updated \
+             stack. A patch is considered modified if its commit message or its diff \
+             changed; patches that were merely rebased are not reported.",
+        )
+        .arg(Arg::new("repository").help("Repository to fetch from"))
+        .arg(
+            Arg::new("prune")
+                .long("prune")
+                .short('p')
+                .help("Remove mirrors of stacks and patches deleted from the remote")
+                .action(clap::ArgAction::SetTrue),
+        )
+}
+
+fn run(matches: &ArgMatches) -> Result<()> {
+    let repo = git_repository::Repository::open()?;
+    let config = repo.config_snapshot();
+
+    let remote_name = if let Some(remote_name) = matches.get_one::<String>("repository") {
+        remote_name.clone()
+    } else {
+        repo.get_branch(None)
+            .ok()
+            .and_then(|branch| branch.get_branch_name().ok().map(str::to_string))
+            .and_then(|branch_name| {
+                config
+                    .plumbing()
+                    .string("branch", Some(branch_name.as_str().into()), "remote")
+                    .and_then(|bs| bs.to_str().map(str::to_string).ok())
+            })
+            .unwrap_or_else(|| "origin".to_string())
+    };
+
+    let stacks_prefix = format!("refs/remote-stacks/{remote_name}/");
+    let patches_prefix = format!("refs/remote-patches/{remote_name}/");
+
+    let before = get_mirrored_stacks(&repo, &stacks_prefix)?;
+
+    repo.stupid().fetch_refspecs(
+        &remote_name,
+        &[
+            format!("+refs/stacks/*:{stacks_prefix}*"),
+            format!("+refs/patches/*:{patches_prefix}*"),
+        ],
+        matches.get_flag("prune"),
+    )?;
+
+    let after = get_mirrored_stacks(&repo, &stacks_prefix)?;
+
+    for (stack_name, old_id) in &before {
+        if !after.contains_key(stack_name) {
+            println!("pruned stack `{stack_name}`");
+        } else if after[stack_name] != *old_id {
+            println!("updated stack `{stack_name}`");
+            let old_patches = get_stack_patches(&repo, *old_id);
+            let new_patches = get_stack_patches(&repo, after[stack_name]);
+            if let (Some(old_patches), Some(new_patches)) = (old_patches, new_patches) {
+                for (patchname, new_commit_id) in &new_patches {
+                    match old_patches.get(patchname) {
+                        None => println!("  + {patchname}"),
+                        Some(old_commit_id)
+                            if old_commit_id != new_commit_id
+                                && is_patch_modified(&repo, *old_commit_id, *new_commit_id)? =>
+                        {
+                            println!("  ~ {patchname}")
+                        }
+                        _ => {}
+                    }
+                }
+                for patchname in old_patches.keys() {
+                    if !new_patches.contains_key(patchname) {
+                        println!("  - {patchname}");
+                    }
+                }
+            }
+        }
+    }
+
+    for (stack_name, new_id) in &after {
+        if !before.contains_key(stack_name) {
+            let num_patches = get_stack_patches(&repo, *new_id).map_or(0, |p| p.len());
+            let plural = if num_patches == 1 { "" } else { "es" };
+            println!("new stack `{stack_name}` ({num_patches} patch{plural})");
+        }
+    }
+
+    Ok(())
+}
+
+/// Map mirrored stack names to their stack state commit ids.
+fn get_mirrored_stacks(
+    repo: &git_repository::Repository,
+    prefix: &str,
+) -> Result<BTreeMap<String, git_repository::ObjectId>> {
+    let mut stacks = BTreeMap::new();
+    for reference in repo.references()?.all()?.filter_map(Result::ok) {
+        if let Some(stack_name) = reference
+            .name()
+            .as_bstr()
+            .to_str()
+            .ok()
+            .and_then(|name| name.strip_prefix(prefix))
+        {
+            if let Some(id) = reference.target().try_id() {
+                stacks.insert(stack_name.to_string(), id.to_owned());
+            }
+        }
+    }
+    Ok(stacks)
+}
+
+/// Map patch names to patch commit ids for the given stack state commit.
+///
+/// Returns `None` if the stack state cannot be read.
+fn get_stack_patches(
+    repo: &git_repository::Repository,
+    state_commit_id: git_repository::ObjectId,
+) -> Option<BTreeMap<PatchName, git_repository::ObjectId>> {
+    let state_commit = repo.find_commit(state_commit_id).ok()?;
+    let state = StackState::from_commit(repo, &state_commit).ok()?;
+    Some(
+        state
+            .all_patches()
+            .map(|patchname| (patchname.clone(), state.get_patch(patchname).commit_id()))
+            .collect(),
+    )
+}
+
+/// Determine whether a patch's message or diff differs between two patch commits.
+fn is_patch_modified(
+    repo: &git_repository::Repository,
+    old_commit_id: git_repository::ObjectId,
+    new_commit_id: git_repository::ObjectId,
+) -> Result<bool> {
+    let stupid = repo.stupid();
+    let mut messages = Vec::with_capacity(2);
+    let mut patch_ids = Vec::with_capacity(2);
+    for commit_id in [old_commit_id, new_commit_id] {
+        let commit = repo.find_commit(commit_id)?;
+        let parent_tree_id = commit.get_parent_commit()?.tree_id()?.detach();
+        let diff = stupid.diff_tree_patch(
+            parent_tree_id,
+            commit.tree_id()?.detach(),
+            <Option<Vec<&str>>>::None,
+            false,
+            <Vec<&str>>::new(),
+        )?;
+        messages.push(commit.message_raw()?.to_owned());
+        patch_ids.push(stupid.patch_id(&diff)?);
+    }
+    Ok(messages[0] != messages[1] || patch_ids[0] != patch_ids[1])
+}
@@ -2,13 +2,14 @@
 
 //! `stg files` implementation.
 
-use std::io::Write;
+use std::{ffi::OsString, io::Write};
 
 use anyhow::Result;
 use bstr::ByteSlice;
 use clap::{Arg, ArgMatches};
 
 use crate::{
+    argset,
     ext::{CommitExtended, RepositoryExtended},
     revspec::parse_stgit_revision,
     stupid::Stupid,
@@ -27,10 +28,15 @@ fn make() -> clap::Command {
         .long_about(
             "Show the files modified by a patch. The files of the topmost \
              patch are shown by default. Passing the '--stat' option shows \
-             the diff statistics for the given patch. Note that this command \
+             the diff statistics for the given patch, which may be fit to a \
+             width with '--stat-width' and limited to a number of files with \
+             '--stat-count'. Note that this command \
              does not show the files modified in the working tree and not yet \
              included in the patch by a 'refresh' command. Use the 'diff' or \
-             'status' commands to show these files.",
+             'status' commands to show these files.\n\
+             \n\
+             Rename detection follows the `stgit.diff.renames` and \
+             `stgit.diff.renameLimit` configuration values.",
         )
         .arg(
             Arg::new("stgit-revision")
@@ -44,6 +50,8 @@ fn make() -> clap::Command {
                 .help("Show patch's diffstat")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(argset::stat_width_arg())
+        .arg(argset::stat_count_arg())
         .arg(
             Arg::new("bare")
                 .long("bare")
@@ -59,16 +67,33 @@ fn run(matches: &ArgMatches) -> Result<()> {
     let opt_spec = crate::argset::get_one_str(matches, "stgit-revision");
     let commit = parse_stgit_revision(&repo, opt_spec, None)?.try_into_commit()?;
     let parent = commit.get_parent_commit()?;
+    let parent_tree_id = parent.tree_id()?.detach();
+    let tree_id = commit.tree_id()?.detach();
+    let diff_opts = argset::get_config_diff_opts(&repo.config_snapshot())?;
+
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+
+    if matches.get_flag("stat") {
+        let diff = repo.stupid().diff_tree_patch(
+            parent_tree_id,
+            tree_id,
+            <Option<Vec<OsString>>>::None,
+            false,
+            diff_opts,
+        )?;
+        stdout.write_all(argset::render_diffstat(matches, &diff).as_bytes())?;
+        return Ok(());
+    }
+
     let mut output = repo.stupid().diff_tree_files_status(
-        parent.tree_id()?.detach(),
-        commit.tree_id()?.detach(),
-        matches.get_flag("stat"),
+        parent_tree_id,
+        tree_id,
         matches.get_flag("bare"),
         crate::color::use_color(matches),
+        diff_opts,
     )?;
 
-    let stdout = std::io::stdout();
-    let mut stdout = stdout.lock();
     for line in output.split_inclusive_mut(|b| *b == b'\t') {
         // Replace tab separator with space between status and filename.
         // This is done for compatibility with StGit <2.0.
@@ -55,6 +55,15 @@ fn make() -> clap::Command {
             Arg::new("noapply")
                 .long("noapply")
                 .help("Reorder patches without reapplying any patches")
+                .long_help(
+                    "Reorder patches without reapplying any patches. The floated \
+                     patches are placed at the bottom of the unapplied patches, i.e. \
+                     they will be the next patches pushed. Any applied patches to \
+                     float are popped, which requires a clean index and worktree. \
+                     When all patches to float are unapplied, only the order of the \
+                     series is changed; the index and worktree are not touched and \
+                     need not be clean.",
+                )
                 .action(clap::ArgAction::SetTrue),
         )
         .arg(
@@ -68,6 +77,7 @@ fn make() -> clap::Command {
         )
         .arg(argset::keep_arg())
         .arg(argset::committer_date_is_author_date_arg())
+        .arg(argset::stable_ids_arg())
 }
 
 fn run(matches: &ArgMatches) -> Result<()> {
@@ -135,6 +145,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
         .setup_transaction()
         .use_index_and_worktree(true)
         .committer_date_is_author_date(matches.get_flag("committer-date-is-author-date"))
+        .stable_ids(matches.get_flag("stable-ids"))
         .with_output_stream(get_color_stdout(matches))
         .transact(|trans| trans.reorder_patches(Some(&applied), Some(&unapplied), None))
         .execute("float")?;
@@ -109,7 +109,7 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
     stupid.update_index_refresh()?;
 
     let base_commit = if matches.get_flag("three-way") {
-        Some(stack.top().get_parent_commit()?)
+        Some(stack.top()?.get_parent_commit()?)
     } else if let Some(base_spec) = matches.get_one::<String>("base") {
         Some(parse_stgit_revision(&repo, Some(base_spec), None)?.try_into_commit()?)
     } else {
@@ -127,6 +127,7 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
             strip_level,
             None,
             context_lines,
+            &[],
         ) {
             stupid.read_tree_checkout_hard(orig_head_tree_id)?;
             return Err(e);
@@ -146,6 +147,7 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
             strip_level,
             None,
             context_lines,
+            &[],
         )
     }
 }
@@ -0,0 +1,109 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg git` implementation.
+
+use std::ffi::OsString;
+
+use anyhow::Result;
+use clap::{Arg, ArgMatches};
+
+use crate::{
+    ext::RepositoryExtended,
+    print_info_message,
+    stack::{InitializationPolicy, Stack},
+    stupid::Stupid,
+};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "git",
+    category: super::CommandCategory::StackManipulation,
+    make,
+    run,
+};
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Run a git command and record its effect on the stack")
+        .long_about(
+            "Run an arbitrary git command and record any modifications it makes to the \
+             current branch in the stack log.\n\
+             \n\
+             Before running the git command, the stack is checked for consistency, \
+             i.e. that HEAD and the stack top are the same. After the git command \
+             completes, if the branch head was moved by the git command, the new head \
+             is recorded as an external modification in the stack log. This allows \
+             the git command's effect on the stack to be reverted with `stg undo`. \
+             If the git command added commits on top of the stack, use `stg repair` \
+             to turn them into patches.\n\
+             \n\
+             Modifications are recorded even if the git command fails. The exit code \
+             of the git command is used as the exit code of this command.",
+        )
+        .override_usage("stg git <git-command> [<args>...]")
+        .trailing_var_arg(true)
+        .arg(
+            Arg::new("args")
+                .help("Git command and its arguments")
+                .value_name("git-command")
+                .required(true)
+                .num_args(1..)
+                .allow_hyphen_values(true)
+                .value_parser(clap::value_parser!(OsString)),
+        )
+}
+
+fn run(matches: &ArgMatches) -> Result<()> {
+    let repo = git_repository::Repository::open()?;
+    let args: Vec<OsString> = matches
+        .get_many::<OsString>("args")
+        .expect("required argument")
+        .cloned()
+        .collect();
+
+    let branch_name = repo
+        .get_branch(None)
+        .ok()
+        .and_then(|branch| branch.get_branch_name().ok().map(str::to_string));
+
+    if let Some(branch_name) = branch_name.as_deref() {
+        if let Ok(stack) = Stack::from_branch(
+            &repo,
+            Some(branch_name),
+            InitializationPolicy::RequireInitialized,
+        ) {
+            stack.check_head_top_mismatch()?;
+        }
+    }
+
+    let status = repo.stupid().user_git(&args)?;
+
+    if let Some(branch_name) = branch_name.as_deref() {
+        // Reopen the repository to observe reference changes made by the git command.
+        let repo = git_repository::Repository::open()?;
+        if repo.get_branch(Some(branch_name)).is_ok() {
+            if let Ok(stack) = Stack::from_branch(
+                &repo,
+                Some(branch_name),
+                InitializationPolicy::RequireInitialized,
+            ) {
+                if !stack.is_head_top() {
+                    let command_str = std::iter::once("git".into())
+                        .chain(args.iter().map(|arg| arg.to_string_lossy()))
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    stack.log_external_mods(Some(&command_str))?;
+                    print_info_message(
+                        matches,
+                        &format!("Recorded modifications to `{branch_name}` by `{command_str}`"),
+                    );
+                }
+            }
+        }
+    }
+
+    if status.success() {
+        Ok(())
+    } else {
+        std::process::exit(status.code().unwrap_or(-1))
+    }
+}
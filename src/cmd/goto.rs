@@ -2,13 +2,15 @@
 
 //! `stg goto` implementation.
 
+use std::path::PathBuf;
+
 use anyhow::{anyhow, Result};
 use clap::{Arg, ArgMatches};
 
 use crate::{
     argset,
     color::get_color_stdout,
-    ext::RepositoryExtended,
+    ext::{CommitExtended, RepositoryExtended},
     patch::{patchrange, PatchName},
     stack::{InitializationPolicy, Stack, StackStateAccess},
     stupid::Stupid,
@@ -24,16 +26,40 @@ pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
 fn make() -> clap::Command {
     clap::Command::new(STGIT_COMMAND.name)
         .about("Go to patch by pushing or popping as necessary")
+        .long_about(
+            "Go to patch by pushing or popping as necessary.\n\
+             \n\
+             With '--touching', the target patch is the topmost applied patch that \
+             modifies any of the given paths. If no applied patch modifies the \
+             paths, the target is the first unapplied patch that does. The \
+             '--touching' option may be repeated to give multiple paths.",
+        )
+        .override_usage(
+            "stg goto [OPTIONS] <patch>\n       \
+             stg goto [OPTIONS] --touching <path>...",
+        )
         .arg(argset::keep_arg())
         .arg(argset::merged_arg())
         .arg(argset::committer_date_is_author_date_arg())
+        .arg(argset::stable_ids_arg())
         .arg(argset::push_conflicts_arg())
         .arg(
             Arg::new("patch")
                 .help("Patch to go to")
-                .required(true)
+                .required_unless_present("touching")
                 .value_parser(clap::value_parser!(PatchName)),
         )
+        .arg(
+            Arg::new("touching")
+                .long("touching")
+                .help("Go to the topmost patch modifying <path>")
+                .value_name("path")
+                .num_args(1)
+                .action(clap::ArgAction::Append)
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(clap::ValueHint::AnyPath)
+                .conflicts_with("patch"),
+        )
 }
 
 fn run(matches: &ArgMatches) -> Result<()> {
@@ -41,7 +67,6 @@ fn run(matches: &ArgMatches) -> Result<()> {
     let stack = Stack::from_branch(&repo, None, InitializationPolicy::AllowUninitialized)?;
     let stupid = repo.stupid();
 
-    let patch_arg = matches.get_one::<PatchName>("patch").unwrap();
     let keep_flag = matches.get_flag("keep");
     let merged_flag = matches.get_flag("merged");
     let allow_push_conflicts =
@@ -56,7 +81,46 @@ fn run(matches: &ArgMatches) -> Result<()> {
         statuses.check_index_and_worktree_clean()?;
     }
 
-    let patchname = match patchrange::parse_single(patch_arg, &stack, patchrange::Allow::Visible) {
+    let patchname = if let Some(paths) = matches.get_many::<PathBuf>("touching") {
+        let paths: Vec<&PathBuf> = paths.collect();
+        find_touching_patch(&stack, &paths)?
+    } else {
+        find_patch(&stack, matches.get_one::<PatchName>("patch").unwrap())?
+    };
+
+    stack
+        .setup_transaction()
+        .use_index_and_worktree(true)
+        .allow_push_conflicts(allow_push_conflicts)
+        .committer_date_is_author_date(committer_date_is_author_date)
+        .stable_ids(matches.get_flag("stable-ids"))
+        .with_output_stream(get_color_stdout(matches))
+        .transact(|trans| {
+            if let Some(pos) = trans.applied().iter().position(|pn| pn == &patchname) {
+                let applied = trans.applied()[0..=pos].to_vec();
+                let mut unapplied = trans.applied()[pos + 1..].to_vec();
+                unapplied.extend(trans.unapplied().iter().cloned());
+                trans.reorder_patches(Some(&applied), Some(&unapplied), None)
+            } else {
+                let pos = trans
+                    .unapplied()
+                    .iter()
+                    .position(|pn| pn == &patchname)
+                    .expect("already determined patch exists and not hidden or applied");
+
+                let to_apply: Vec<PatchName> = trans.unapplied()[0..=pos].to_vec();
+                trans.push_patches(&to_apply, merged_flag)?;
+                Ok(())
+            }
+        })
+        .execute("goto")?;
+
+    Ok(())
+}
+
+/// Find the patch named by `patch_arg`, which may also be a patch commit id prefix.
+fn find_patch(stack: &Stack, patch_arg: &PatchName) -> Result<PatchName> {
+    match patchrange::parse_single(patch_arg, stack, patchrange::Allow::Visible) {
         Ok(patchname) => Ok(patchname),
         Err(e @ patchrange::Error::PatchNotKnown { .. }) => {
             let oid_prefix: &str = patch_arg.as_ref();
@@ -65,9 +129,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
                     .all_patches()
                     .filter(|pn| {
                         stack
-                            .get_patch(pn)
-                            .commit
-                            .id()
+                            .get_patch_commit_id(pn)
                             .to_string()
                             .chars()
                             .zip(oid_prefix.chars())
@@ -91,33 +153,28 @@ fn run(matches: &ArgMatches) -> Result<()> {
             }
         }
         Err(e) => Err(e.into()),
-    }?;
+    }
+}
 
-    stack
-        .setup_transaction()
-        .use_index_and_worktree(true)
-        .allow_push_conflicts(allow_push_conflicts)
-        .committer_date_is_author_date(committer_date_is_author_date)
-        .with_output_stream(get_color_stdout(matches))
-        .transact(|trans| {
-            if let Some(pos) = trans.applied().iter().position(|pn| pn == &patchname) {
-                let applied = trans.applied()[0..=pos].to_vec();
-                let mut unapplied = trans.applied()[pos + 1..].to_vec();
-                unapplied.extend(trans.unapplied().iter().cloned());
-                trans.reorder_patches(Some(&applied), Some(&unapplied), None)
-            } else {
-                let pos = trans
-                    .unapplied()
-                    .iter()
-                    .position(|pn| pn == &patchname)
-                    .expect("already determined patch exists and not hidden or applied");
+/// Find the topmost applied patch modifying any of `paths`, or, failing that, the
+/// first unapplied patch modifying any of `paths`.
+fn find_touching_patch(stack: &Stack, paths: &[&PathBuf]) -> Result<PatchName> {
+    let stupid = stack.repo.stupid();
+    let touches = |patchname: &PatchName| -> Result<bool> {
+        let patch_commit = stack.get_patch_commit(patchname)?;
+        let parent_tree_id = patch_commit.get_parent_commit()?.tree_id()?.detach();
+        stupid.diff_tree_quiet(parent_tree_id, patch_commit.tree_id()?.detach(), paths)
+    };
 
-                let to_apply: Vec<PatchName> = trans.unapplied()[0..=pos].to_vec();
-                trans.push_patches(&to_apply, merged_flag)?;
-                Ok(())
-            }
-        })
-        .execute("goto")?;
+    for patchname in stack.applied().iter().rev().chain(stack.unapplied()) {
+        if touches(patchname)? {
+            return Ok(patchname.clone());
+        }
+    }
 
-    Ok(())
+    Err(if let [path] = paths {
+        anyhow!("no patch modifies `{}`", path.display())
+    } else {
+        anyhow!("no patch modifies the given paths")
+    })
 }
@@ -9,7 +9,11 @@ use crate::{
     argset,
     color::get_color_stdout,
     ext::RepositoryExtended,
-    patch::{patchrange, PatchName},
+    patch::{
+        meta::{self, PatchMeta},
+        patchrange, PatchName,
+    },
+    print_info_message,
     stack::{InitializationPolicy, Stack, StackStateAccess},
 };
 
@@ -26,7 +30,14 @@ fn make() -> clap::Command {
         .long_about(
             "Hide patches in the series.\n\
              \n\
-             Hidden patches are no longer shown in the plain 'series' output.",
+             Hidden patches are no longer shown in the plain 'series' output.\n\
+             \n\
+             The time each patch is hidden is recorded in the patch's metadata. When \
+             \"stgit.hidden.expire-days\" is set, patches that have been hidden for \
+             longer than that many days are considered expired and are reported by \
+             'stg series' and 'stg doctor'. When \"stgit.hidden.auto-shelve\" is \
+             also set, expired hidden patches are moved onto a new shelf, see `stg \
+             shelve`, each time patches are hidden.",
         )
         .arg(
             Arg::new("patchranges")
@@ -64,11 +75,81 @@ fn run(matches: &ArgMatches) -> Result<()> {
         .cloned()
         .collect();
 
-    stack
+    let stack = stack
         .setup_transaction()
         .with_output_stream(get_color_stdout(matches))
         .transact(|trans| trans.hide_patches(&to_hide))
         .execute("hide")?;
 
+    let config = repo.config_snapshot();
+    if config.boolean("stgit.hidden.auto-shelve").unwrap_or(false) {
+        if let Some(expire_days) = expire_days(&repo) {
+            let expired: Vec<PatchName> = expired_hidden_patches(&stack, expire_days)?
+                .into_iter()
+                .map(|(patchname, _)| patchname)
+                .collect();
+            if !expired.is_empty() {
+                let shelf_name = super::shelve::shelve_hidden(stack, &expired, matches, "hide")?;
+                print_info_message(
+                    matches,
+                    &format!(
+                        "shelved {} expired hidden patch{} onto `{shelf_name}`",
+                        expired.len(),
+                        if expired.len() == 1 { "" } else { "es" }
+                    ),
+                );
+            }
+        }
+    }
+
     Ok(())
 }
+
+/// Get the number of days after which hidden patches expire.
+///
+/// Hidden patches do not expire unless `stgit.hidden.expire-days` is set to a positive
+/// number of days.
+pub(super) fn expire_days(repo: &git_repository::Repository) -> Option<i64> {
+    repo.config_snapshot()
+        .integer("stgit.hidden.expire-days")
+        .filter(|&days| days > 0)
+}
+
+/// Find the hidden patches that have been hidden for more than `expire_days` days.
+///
+/// Each expired patch is returned along with the number of whole days it has been
+/// hidden. The time a patch was hidden is taken from its [`meta::HIDDEN_AT`] metadata,
+/// which stack transactions record when patches are hidden. Patches hidden without
+/// that metadata being recorded, e.g. by older versions of StGit, are taken to have
+/// been hidden just now, and that time is recorded unless in read-only mode.
+pub(super) fn expired_hidden_patches(
+    stack: &Stack,
+    expire_days: i64,
+) -> Result<Vec<(PatchName, i64)>> {
+    const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+    let now = chrono::Utc::now().timestamp();
+    let record = !super::is_readonly();
+    let mut expired = Vec::new();
+    for patchname in stack.hidden() {
+        let commit_id = stack.get_patch_commit_id(patchname);
+        let mut patch_meta = PatchMeta::read(stack.repo, commit_id)?;
+        let hidden_at = match patch_meta
+            .get(meta::HIDDEN_AT)
+            .and_then(|value| value.parse::<i64>().ok())
+        {
+            Some(hidden_at) => hidden_at,
+            None => {
+                if record {
+                    patch_meta.set(meta::HIDDEN_AT, &now.to_string());
+                    patch_meta.write(stack.repo, commit_id)?;
+                }
+                now
+            }
+        };
+        let hidden_secs = now - hidden_at;
+        if hidden_secs > expire_days * SECONDS_PER_DAY {
+            expired.push((patchname.clone(), hidden_secs / SECONDS_PER_DAY));
+        }
+    }
+    Ok(expired)
+}
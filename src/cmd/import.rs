@@ -3,7 +3,7 @@
 //! `stg import` implementation.
 
 use std::{
-    io::Read,
+    io::{Read, Write},
     path::{Path, PathBuf},
 };
 
@@ -13,10 +13,13 @@ use clap::{Arg, ArgGroup};
 
 use crate::{
     color::get_color_stdout,
-    ext::{RepositoryExtended, TimeExtended},
-    patch::{patchedit, PatchName},
-    stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
-    stupid::{Stupid, StupidContext},
+    ext::{CommitExtended, RepositoryExtended, TimeExtended},
+    patch::{mailinfo::MailInfo, mbox::Mbox, patchedit, PatchName},
+    stack::{
+        state_refname_from_branch_name, InitializationPolicy, Stack, StackAccess, StackState,
+        StackStateAccess,
+    },
+    stupid::Stupid,
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
@@ -37,32 +40,54 @@ fn make() -> clap::Command {
              can be overridden with '--name'. The patch can either be a normal file \
              with the description at the top, or it can have standard mail format. The \
              \"Subject\", \"From\", and \"Date\" headers will be used for the imported \
-             patch's author details.\n\
+             patch's author details. The patch name recorded in an \"X-StGit-Patch\" \
+             header, as added by `stg email format --patch-names`, is used in \
+             preference to the file name.\n\
              \n\
              Patches may also be imported from a mail file (-m/--mail), an mbox \
-             (-M/--mbox), or a series (-S/--series). Furthermore, the -u/--url option \
-             allows the patches source to be fetched from a url instead of from a \
-             local file.\n\
+             (-M/--mbox), a Maildir (--maildir), a series (-S/--series), a directory \
+             tree of patch files \
+             (-R/--recursive), a GitHub pull request (--github), or a GitLab merge \
+             request (--gitlab). Furthermore, the -u/--url option allows the patches \
+             source to be fetched from a url instead of from a local file.\n\
              \n\
              If a patch does not apply cleanly, the failed diff is written to a \
              .stgit-failed.patch file and an empty patch is added to the stack.\n\
              \n\
+             When a patch email from a mail, mbox, or Maildir import fails to \
+             apply, the import stops and the remaining emails are saved in \
+             `.git/stgit-sequencer`. After resolving the failure, staging the \
+             changes for the failed patch in the index, the import is resumed with \
+             '--continue'. The failed email may instead be skipped with '--skip', or \
+             the import abandoned with '--abort', which restores the stack to its \
+             state before the import.\n\
+             \n\
              The patch description must be separated from the diff with a \"---\" line.",
         )
         .override_usage(if cfg!(feature = "import-url") {
             "stg import [OPTIONS] <diff-path>\n       \
              stg import [OPTIONS] -m [<mail-path>|<Maildir-path>]\n       \
              stg import [OPTIONS] -M [<mbox-path>]\n       \
+             stg import [OPTIONS] --maildir <dir>\n       \
              stg import [OPTIONS] -S [<series-path>]\n       \
+             stg import [OPTIONS] -R <directory>\n       \
+             stg import [OPTIONS] --github <pr>\n       \
+             stg import [OPTIONS] --gitlab <mr>\n       \
              stg import [OPTIONS] -u <diff-url>\n       \
              stg import [OPTIONS] -u -m <mail-url>\n       \
              stg import [OPTIONS] -u -M <mbox-url>\n       \
-             stg import [OPTIONS] -u -S <series-url>"
+             stg import [OPTIONS] -u -S <series-url>\n       \
+             stg import (--continue | --skip | --abort)"
         } else {
             "stg import [OPTIONS] <diff-path>\n       \
              stg import [OPTIONS] -m [<mail-path>|<Maildir-path>]\n       \
              stg import [OPTIONS] -M [<mbox-path>]\n       \
-             stg import [OPTIONS] -S [<series-path>]"
+             stg import [OPTIONS] --maildir <dir>\n       \
+             stg import [OPTIONS] -S [<series-path>]\n       \
+             stg import [OPTIONS] -R <directory>\n       \
+             stg import [OPTIONS] --github <pr>\n       \
+             stg import [OPTIONS] --gitlab <mr>\n       \
+             stg import (--continue | --skip | --abort)"
         })
         .arg(
             Arg::new("source")
@@ -75,6 +100,45 @@ fn make() -> clap::Command {
                 .value_parser(clap::value_parser!(PathBuf))
                 .value_hint(clap::ValueHint::AnyPath),
         )
+        .arg(
+            Arg::new("continue")
+                .long("continue")
+                .help("Resume an interrupted mail import")
+                .long_help(
+                    "Resume a mail import that stopped because a patch failed to \
+                     apply. A patch is created for the failed email from the changes \
+                     staged in the index, which must not have any conflicts, and the \
+                     remaining emails are then imported using the options of the \
+                     interrupted import.",
+                )
+                .action(clap::ArgAction::SetTrue)
+                .exclusive(true),
+        )
+        .arg(
+            Arg::new("skip")
+                .long("skip")
+                .help("Skip the failed email and resume an interrupted mail import")
+                .long_help(
+                    "Skip the email that failed to apply, discarding any changes to the \
+                     index and worktree, and resume the interrupted mail import with \
+                     the remaining emails.",
+                )
+                .action(clap::ArgAction::SetTrue)
+                .exclusive(true),
+        )
+        .arg(
+            Arg::new("abort")
+                .long("abort")
+                .help("Abandon an interrupted mail import")
+                .long_help(
+                    "Abandon an interrupted mail import, discarding the remaining \
+                     emails and any changes to the index and worktree. The stack is \
+                     restored to its state before the import started.",
+                )
+                .action(clap::ArgAction::SetTrue)
+                .exclusive(true),
+        )
+        .group(ArgGroup::new("sequencer").args(["continue", "skip", "abort"]))
         .next_help_heading("Source Options")
         .arg(
             Arg::new("mail")
@@ -90,6 +154,23 @@ fn make() -> clap::Command {
                 .help("Import patch series from an mbox file")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("maildir")
+                .long("maildir")
+                .help("Import patch series from the Maildir <dir>")
+                .long_help(
+                    "Import the patch emails found in the `new` and `cur` \
+                     subdirectories of the Maildir <dir>, one patch per email. The \
+                     emails are imported in the order of the \"n/m\" numbering of their \
+                     subjects, such as \"[PATCH 2/3]\", or, if not every email is \
+                     numbered, in the order of their \"Date\" headers. Emails without a \
+                     diff, such as cover letters, and replies are skipped.",
+                )
+                .value_name("dir")
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(clap::ValueHint::DirPath)
+                .conflicts_with("source"),
+        )
         .arg(
             Arg::new("series")
                 .long("series")
@@ -98,7 +179,114 @@ fn make() -> clap::Command {
                 .long_help("Import patch series from a series file are tar archive.")
                 .action(clap::ArgAction::SetTrue),
         )
-        .group(ArgGroup::new("whence").args(["mail", "mbox", "series"]));
+        .arg(
+            Arg::new("recursive")
+                .long("recursive")
+                .short('R')
+                .help("Import all patch files in a directory tree")
+                .long_help(
+                    "Import all of the patch files found in the directory tree rooted at \
+                     the source directory, such as the patch directories of vendor \
+                     layers. By default, files named \"*.patch\" or \"*.diff\" are \
+                     imported; see '--include' and '--exclude'.\n\
+                     \n\
+                     The files and subdirectories of each directory are visited in \
+                     natural order, with numbers compared by value, such that \
+                     \"2-fix.patch\" is imported before \"10-feature.patch\". A \
+                     directory containing a \"series\" file instead contributes the \
+                     patches listed in its series file, in the listed order, and its \
+                     subdirectories are not searched. Hidden files and directories are \
+                     ignored.",
+                )
+                .action(clap::ArgAction::SetTrue)
+                .requires("source"),
+        )
+        .arg(
+            Arg::new("github")
+                .long("github")
+                .help("Import the commits of a GitHub pull request")
+                .long_help(
+                    "Import the commits of a GitHub pull request as patches, one patch \
+                     per commit, preserving the commits' authorship. The pull request \
+                     may be given as a URL, such as \
+                     \"https://github.com/<owner>/<repo>/pull/<number>\", or as a \
+                     number, in which case the pull request is taken from the current \
+                     branch's remote (branch.<name>.remote), or \"origin\", which must \
+                     be a GitHub repository.\n\
+                     \n\
+                     The pull request's commits are fetched from its \
+                     `refs/pull/<number>/head` reference. The commits not already \
+                     reachable from the current branch are imported, and a \"Link:\" \
+                     trailer with the pull request's URL is added to each patch's \
+                     message. Pull requests containing merge commits are not supported.",
+                )
+                .value_name("pr")
+                .value_parser(clap::builder::NonEmptyStringValueParser::new())
+                .conflicts_with("source"),
+        )
+        .arg(
+            Arg::new("gitlab")
+                .long("gitlab")
+                .help("Import the commits of a GitLab merge request")
+                .long_help(
+                    "Import the commits of a GitLab merge request as patches, one patch \
+                     per commit, preserving the commits' authorship. The merge request \
+                     may be given as a URL, such as \
+                     \"https://gitlab.com/<group>/<project>/-/merge_requests/<number>\", \
+                     or as a number, in which case the merge request is taken from the \
+                     current branch's remote (branch.<name>.remote), or \"origin\".\n\
+                     \n\
+                     The merge request's commits are fetched from its \
+                     `refs/merge-requests/<number>/head` reference. As with '--github', \
+                     the commits not already reachable from the current branch are \
+                     imported, and a \"Link:\" trailer with the merge request's URL is \
+                     added to each patch's message. Merge requests containing merge \
+                     commits are not supported.",
+                )
+                .value_name("mr")
+                .value_parser(clap::builder::NonEmptyStringValueParser::new())
+                .conflicts_with("source"),
+        )
+        .group(ArgGroup::new("whence").args([
+            "mail",
+            "mbox",
+            "maildir",
+            "series",
+            "recursive",
+            "github",
+            "gitlab",
+        ]))
+        .arg(
+            Arg::new("include")
+                .long("include")
+                .help("Import only patch tree files matching <glob>")
+                .long_help(
+                    "Import only the files of the patch tree that match <glob>, instead \
+                     of the default of files named \"*.patch\" or \"*.diff\". As with \
+                     gitignore patterns, a glob without a slash is matched against file \
+                     names and other globs are matched against paths relative to the \
+                     source directory, where \"**\" matches any number of directories. \
+                     This option may be specified multiple times.",
+                )
+                .value_name("glob")
+                .action(clap::ArgAction::Append)
+                .value_parser(clap::builder::NonEmptyStringValueParser::new())
+                .requires("recursive"),
+        )
+        .arg(
+            Arg::new("exclude")
+                .long("exclude")
+                .help("Do not import patch tree files matching <glob>")
+                .long_help(
+                    "Do not import the files of the patch tree that match <glob>, even \
+                     if listed in a series file. Globs are matched as with \
+                     '--include'. This option may be specified multiple times.",
+                )
+                .value_name("glob")
+                .action(clap::ArgAction::Append)
+                .value_parser(clap::builder::NonEmptyStringValueParser::new())
+                .requires("recursive"),
+        );
 
     let app = if cfg!(feature = "import-url") {
         app.arg(
@@ -107,7 +295,8 @@ fn make() -> clap::Command {
                 .short('u')
                 .help("Retrieve source from a url instead of local file")
                 .action(clap::ArgAction::SetTrue)
-                .requires("source"),
+                .requires("source")
+                .conflicts_with("recursive"),
         )
     } else {
         app
@@ -143,6 +332,36 @@ fn make() -> clap::Command {
                 .value_parser(clap::value_parser!(PathBuf))
                 .value_hint(clap::ValueHint::DirPath),
         )
+        .arg(
+            Arg::new("include-path")
+                .long("include-path")
+                .help("Apply changes only to paths matching <glob>")
+                .long_help(
+                    "Apply the changes of the imported diffs only to the paths that \
+                     match <glob>. Paths are matched after '--strip' and '--directory' \
+                     are applied. When globs are given with both '--include-path' and \
+                     '--exclude-path', the first glob on the command line that matches \
+                     a path determines whether it is included, as with the '--include' \
+                     and '--exclude' options of git-apply(1). This option may be \
+                     specified multiple times.",
+                )
+                .value_name("glob")
+                .action(clap::ArgAction::Append)
+                .value_parser(clap::builder::NonEmptyStringValueParser::new()),
+        )
+        .arg(
+            Arg::new("exclude-path")
+                .long("exclude-path")
+                .help("Do not apply changes to paths matching <glob>")
+                .long_help(
+                    "Do not apply the changes of the imported diffs to the paths that \
+                     match <glob>, which are matched as with '--include-path'. This \
+                     option may be specified multiple times.",
+                )
+                .value_name("glob")
+                .action(clap::ArgAction::Append)
+                .value_parser(clap::builder::NonEmptyStringValueParser::new()),
+        )
         .arg(
             Arg::new("stripname")
                 .long("stripname")
@@ -181,6 +400,29 @@ fn make() -> clap::Command {
                 .help("Replace the unapplied patches in the series")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("on-duplicate")
+                .long("on-duplicate")
+                .help("Policy for patches duplicating existing patches")
+                .long_help(
+                    "Policy for imported patches that duplicate patches already in the \
+                     stack. An imported patch is a duplicate of an existing patch when \
+                     both have the same subject or the same patch id, as computed by \
+                     git-patch-id(1). This is useful when re-importing a series that \
+                     overlaps patches already in the stack.\n\
+                     \n\
+                     With \"skip\", duplicate patches are not imported. With \
+                     \"replace\", the existing patch is deleted and the imported patch \
+                     takes its name; an applied existing patch is deleted before the \
+                     imported patch is applied. With \"new-name\", duplicate patches are \
+                     imported with a new, unique patch name.\n\
+                     \n\
+                     Without this option, duplicate patches are not detected.",
+                )
+                .value_name("policy")
+                .value_parser(["skip", "replace", "new-name"])
+                .conflicts_with_all(["ignore", "replace"]),
+        )
         .arg(
             Arg::new("base")
                 .long("base")
@@ -192,7 +434,15 @@ fn make() -> clap::Command {
             Arg::new("reject")
                 .long("reject")
                 .help("Leave rejected hunks in \".rej\" files")
-                .action(clap::ArgAction::SetTrue),
+                .long_help(
+                    "Apply the hunks of the patch that apply cleanly and leave the \
+                     rejected hunks in corresponding \".rej\" files. The patch is created \
+                     with the hunks that applied and the import then stops, allowing \
+                     the rejected hunks to be applied by hand before running `stg \
+                     refresh`.",
+                )
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("3way"),
         )
         .arg(
             Arg::new("keep-cr")
@@ -200,6 +450,16 @@ fn make() -> clap::Command {
                 .help("Do not remove \"\\r\" from email lines ending with \"\\r\\n\"")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("progress")
+                .long("progress")
+                .help("Show progress while importing emails")
+                .long_help(
+                    "Show progress reports on stderr as the emails of a '--mail', \
+                     '--mbox', or '--maildir' import are imported.",
+                )
+                .action(clap::ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("message-id")
                 .long("message-id")
@@ -207,10 +467,28 @@ fn make() -> clap::Command {
                 .long_help(
                     "Create Message-Id trailer in patch description based on the \
                     Message-ID email header. This option is applicable when importing \
-                    with '--mail' or '--mbox'. This behavior may also be enabled via \
+                    with '--mail', '--mbox', or '--maildir'. This behavior may also be enabled via \
                     the \"stgit.import.message-id\" configuration option.",
                 )
                 .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("link")
+                .long("link")
+                .help("Create Link trailer with the email's archive URL")
+                .long_help(
+                    "Create a \"Link:\" trailer in the patch description with the URL of \
+                     the imported email in a public-inbox archive, formed by appending \
+                     the email's Message-ID to <url>. When <url> is not given, the \
+                     \"stgit.email.inbox-url\" configuration variable is used, defaulting \
+                     to \"https://lore.kernel.org/all\". This option is applicable when \
+                     importing with '--mail', '--mbox', or '--maildir'. This behavior may \
+                     also be enabled via the \"stgit.import.link\" configuration option.",
+                )
+                .value_name("url")
+                .num_args(0..=1)
+                .default_missing_value("")
+                .require_equals(true),
         );
     patchedit::add_args(app, false, false)
 }
@@ -229,6 +507,15 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
         None
     };
 
+    if matches.contains_id("sequencer") {
+        return resume_import(stack, matches);
+    } else if sequencer_dir(&repo).exists() {
+        return Err(anyhow!(
+            "a mail import is in progress; use `stg import --continue`, `--skip`, or \
+             `--abort`"
+        ));
+    }
+
     let statuses = stupid.statuses(None)?;
     statuses.check_index_and_worktree_clean()?;
     stack.check_head_top_mismatch()?;
@@ -238,8 +525,17 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
         import_url(stack, matches)
     } else if matches.get_flag("series") {
         import_series(stack, matches, source_path.as_deref())
+    } else if let Some(pull_request) = matches.get_one::<String>("github") {
+        import_github(stack, matches, pull_request)
+    } else if let Some(merge_request) = matches.get_one::<String>("gitlab") {
+        import_gitlab(stack, matches, merge_request)
+    } else if matches.get_flag("recursive") {
+        let source_path = source_path.expect("recursive import requires source");
+        import_tree(stack, matches, &source_path)
     } else if matches.get_flag("mail") || matches.get_flag("mbox") {
         import_mail(stack, matches, source_path.as_deref())
+    } else if let Some(maildir) = matches.get_one::<PathBuf>("maildir") {
+        import_maildir(stack, matches, maildir)
     } else {
         import_file(stack, matches, source_path.as_deref(), None)?;
         Ok(())
@@ -255,8 +551,6 @@ fn import_url(_stack: Stack, _matches: &clap::ArgMatches) -> Result<()> {
 
 #[cfg(feature = "import-url")]
 fn import_url(stack: Stack, matches: &clap::ArgMatches) -> Result<()> {
-    use std::io::Write;
-
     let url_osstr = matches
         .get_one::<PathBuf>("source")
         .expect("source url must be present")
@@ -265,16 +559,37 @@ fn import_url(stack: Stack, matches: &clap::ArgMatches) -> Result<()> {
     let url_str = url_osstr
         .to_str()
         .ok_or_else(|| anyhow!("source url is not UTF-8 encoded"))?;
+    let download_dir = tempfile::tempdir()?;
+    let download_path = download_url(url_str, download_dir.path())?;
+
+    if matches.get_flag("series") {
+        import_series(stack, matches, Some(download_path.as_path()))
+    } else if matches.get_flag("mail") || matches.get_flag("mbox") {
+        import_mail(stack, matches, Some(download_path.as_path()))
+    } else {
+        import_file(stack, matches, Some(download_path.as_path()), None)?;
+        Ok(())
+    }
+}
+
+/// Download the content at `url_str` into a file in `download_dir`.
+///
+/// The downloaded file is named after the last component of the URL's path. Returns
+/// the path to the downloaded file.
+#[cfg(feature = "import-url")]
+pub(crate) fn download_url(url_str: &str, download_dir: &Path) -> Result<PathBuf> {
+    use std::io::Write;
+
     let mut handle = curl::easy::Easy::new();
     handle.url(url_str)?;
+    handle.fail_on_error(true)?;
     let url_decoded = handle.url_decode(url_str);
     let filename = url_decoded
         .rsplit_str(b"/")
         .next()
         .and_then(|b| b.to_str().ok())
         .unwrap_or("patch");
-    let download_dir = tempfile::tempdir()?;
-    let download_path = download_dir.path().join(filename);
+    let download_path = download_dir.join(filename);
     let mut download_file = std::fs::OpenOptions::new()
         .create_new(true)
         .write(true)
@@ -295,14 +610,7 @@ fn import_url(stack: Stack, matches: &clap::ArgMatches) -> Result<()> {
         e @ Err(_) => e?,
     }
 
-    if matches.get_flag("series") {
-        import_series(stack, matches, Some(download_path.as_path()))
-    } else if matches.get_flag("mail") || matches.get_flag("mbox") {
-        import_mail(stack, matches, Some(download_path.as_path()))
-    } else {
-        import_file(stack, matches, Some(download_path.as_path()), None)?;
-        Ok(())
-    }
+    Ok(download_path)
 }
 
 #[cfg(feature = "import-compressed")]
@@ -312,7 +620,7 @@ fn import_tgz_series(stack: Stack, matches: &clap::ArgMatches, source_path: &Pat
     let temp_dir = tempfile::tempdir()?;
     archive.unpack(temp_dir.path())?;
     let series_path = find_series_path(temp_dir.path())?;
-    return import_series(stack, matches, Some(series_path.as_path()));
+    import_series(stack, matches, Some(series_path.as_path()))
 }
 
 #[cfg(feature = "import-compressed")]
@@ -322,7 +630,7 @@ fn import_tbz2_series(stack: Stack, matches: &clap::ArgMatches, source_path: &Pa
     let temp_dir = tempfile::tempdir()?;
     archive.unpack(temp_dir.path())?;
     let series_path = find_series_path(temp_dir.path())?;
-    return import_series(stack, matches, Some(series_path.as_path()));
+    import_series(stack, matches, Some(series_path.as_path()))
 }
 
 #[cfg(feature = "import-compressed")]
@@ -332,7 +640,7 @@ fn import_tar_series(stack: Stack, matches: &clap::ArgMatches, source_path: &Pat
     let temp_dir = tempfile::tempdir()?;
     archive.unpack(temp_dir.path())?;
     let series_path = find_series_path(temp_dir.path())?;
-    return import_series(stack, matches, Some(series_path.as_path()));
+    import_series(stack, matches, Some(series_path.as_path()))
 }
 
 #[cfg(not(feature = "import-compressed"))]
@@ -383,6 +691,20 @@ fn import_series(
 
     let mut stack = stack;
 
+    for (raw_patchname, strip_level) in parse_series(&series)? {
+        let patch_path = source_path.map_or_else(
+            || raw_patchname.clone(),
+            |p| p.with_file_name(&raw_patchname),
+        );
+        stack = import_file(stack, matches, Some(patch_path.as_path()), strip_level)?;
+    }
+    Ok(())
+}
+
+/// Parse the content of a series file into patch file names and their strip levels.
+fn parse_series(series: &[u8]) -> Result<Vec<(PathBuf, Option<usize>)>> {
+    let mut entries = Vec::new();
+
     for line in series.lines() {
         let line = line
             .find_char('#')
@@ -398,10 +720,6 @@ fn import_series(
             .expect("non-empty line must have first field")
             .to_os_str()
             .context("converting patch name from series to file name")?;
-        let patch_path = source_path.map_or_else(
-            || PathBuf::from(raw_patchname),
-            |p| p.with_file_name(raw_patchname),
-        );
 
         let strip_level = if let Some(extra) = fields.next() {
             if extra.starts_with_str("-p") {
@@ -421,11 +739,408 @@ fn import_series(
             None
         };
 
+        entries.push((PathBuf::from(raw_patchname), strip_level));
+    }
+
+    Ok(entries)
+}
+
+/// Import the commits of a GitHub pull request, given as a URL or number.
+fn import_github(stack: Stack, matches: &clap::ArgMatches, pull_request: &str) -> Result<()> {
+    let (remote, repo_url, number) = if let Some(number) = parse_pr_number(pull_request) {
+        let (remote_name, remote_url) = branch_remote(&stack);
+        let repo_url = remote_url
+            .as_deref()
+            .and_then(github_repo_url)
+            .ok_or_else(|| anyhow!("remote `{remote_name}` is not a GitHub repository"))?;
+        (remote_name, repo_url, number)
+    } else {
+        let (repo_url, number) = pull_request
+            .trim_end_matches('/')
+            .split_once("/pull/")
+            .and_then(|(repo_url, rest)| {
+                let number = parse_pr_number(rest.split('/').next().unwrap_or_default())?;
+                Some((github_repo_url(repo_url)?, number))
+            })
+            .ok_or_else(|| anyhow!("invalid GitHub pull request `{pull_request}`"))?;
+        (format!("{repo_url}.git"), repo_url, number)
+    };
+    import_review_commits(
+        stack,
+        matches,
+        &remote,
+        &format!("refs/pull/{number}/head"),
+        &format!("pull request #{number}"),
+        &format!("{repo_url}/pull/{number}"),
+    )
+}
+
+/// Import the commits of a GitLab merge request, given as a URL or number.
+fn import_gitlab(stack: Stack, matches: &clap::ArgMatches, merge_request: &str) -> Result<()> {
+    let (remote, project_url, number) = if let Some(number) = parse_mr_number(merge_request) {
+        let (remote_name, remote_url) = branch_remote(&stack);
+        let project_url = remote_url
+            .as_deref()
+            .and_then(gitlab_project_url)
+            .ok_or_else(|| anyhow!("remote `{remote_name}` does not have a web URL"))?;
+        (remote_name, project_url, number)
+    } else {
+        let (project_url, number) = merge_request
+            .trim_end_matches('/')
+            .split_once("/-/merge_requests/")
+            .and_then(|(project_url, rest)| {
+                let number = parse_mr_number(rest.split('/').next().unwrap_or_default())?;
+                Some((gitlab_project_url(project_url)?, number))
+            })
+            .ok_or_else(|| anyhow!("invalid GitLab merge request `{merge_request}`"))?;
+        (format!("{project_url}.git"), project_url, number)
+    };
+    import_review_commits(
+        stack,
+        matches,
+        &remote,
+        &format!("refs/merge-requests/{number}/head"),
+        &format!("merge request !{number}"),
+        &format!("{project_url}/-/merge_requests/{number}"),
+    )
+}
+
+/// Get the name and URL of the current branch's remote.
+///
+/// The remote is taken from `branch.<name>.remote`, defaulting to "origin".
+fn branch_remote(stack: &Stack) -> (String, Option<String>) {
+    let config = stack.repo.config_snapshot();
+    let remote_name = config
+        .plumbing()
+        .string("branch", Some(stack.get_branch_name().into()), "remote")
+        .and_then(|bs| bs.to_str().map(str::to_string).ok())
+        .unwrap_or_else(|| "origin".to_string());
+    let remote_url = config
+        .string(format!("remote.{remote_name}.url").as_str())
+        .map(|url| url.to_str_lossy().to_string());
+    (remote_name, remote_url)
+}
+
+/// Import the commits of a pull or merge request as patches.
+///
+/// The request's head commit is fetched from `refname` of `remote` and the commits
+/// not reachable from the current branch are imported with a "Link:" trailer
+/// referring to `link`. The `description`, e.g. "pull request #7", is used in
+/// messages.
+fn import_review_commits(
+    stack: Stack,
+    matches: &clap::ArgMatches,
+    remote: &str,
+    refname: &str,
+    description: &str,
+    link: &str,
+) -> Result<()> {
+    let repo = stack.repo;
+    let stupid = repo.stupid();
+    let head = stupid
+        .fetch_commit(remote, refname)
+        .with_context(|| format!("fetching {description}"))?;
+    let base = stupid
+        .merge_bases(stack.get_branch_head().id, head)?
+        .first()
+        .copied()
+        .ok_or_else(|| anyhow!("{description} has no history in common with the current branch"))?;
+    let mut commit_ids = stupid.rev_list(base, head, None::<Vec<&str>>)?;
+    if commit_ids.is_empty() {
+        return Err(anyhow!("{description} has no commits to import"));
+    }
+    commit_ids.reverse();
+    let mut commits = Vec::with_capacity(commit_ids.len());
+    for commit_id in commit_ids {
+        let commit = repo.find_commit(commit_id)?;
+        if commit.parent_ids().count() != 1 {
+            return Err(anyhow!(
+                "{description} contains merge commit `{}`",
+                commit_id.to_hex_with_len(7)
+            ));
+        }
+        commits.push(commit);
+    }
+
+    let mut stack = stack;
+    for commit in commits {
+        let parent = commit.get_parent_commit()?;
+        let diff = stupid.diff_tree_patch(
+            parent.tree_id()?.detach(),
+            commit.tree_id()?.detach(),
+            None::<Vec<&str>>,
+            false,
+            ["--binary"],
+        )?;
+        let author = commit.author_strict()?;
+        let time = author.time;
+        let offset = time.offset_in_seconds.unsigned_abs();
+        let headers = Headers {
+            patchname: None,
+            author_name: Some(author.name.to_str_lossy().to_string()),
+            author_email: Some(author.email.to_str_lossy().to_string()),
+            author_date: Some(format!(
+                "{} {}{:02}{:02}",
+                time.seconds_since_unix_epoch,
+                if time.offset_in_seconds < 0 { '-' } else { '+' },
+                offset / 3600,
+                offset % 3600 / 60,
+            )),
+            subject: None,
+            message_id: None,
+        };
+        let message = stupid
+            .interpret_trailers(commit.message_ex().decode()?.as_bytes(), [("Link", link)])?;
+        stack = create_patch(stack, matches, None, headers, &message, Some(&diff), None)?;
+    }
+    Ok(())
+}
+
+/// Parse a pull request number, optionally prefixed with '#'.
+fn parse_pr_number(s: &str) -> Option<u64> {
+    s.strip_prefix('#')
+        .unwrap_or(s)
+        .parse::<u64>()
+        .ok()
+        .filter(|&number| number > 0)
+}
+
+/// Parse a merge request number, optionally prefixed with '!'.
+fn parse_mr_number(s: &str) -> Option<u64> {
+    parse_pr_number(s.strip_prefix('!').unwrap_or(s))
+}
+
+/// Get the web URL of a GitLab project from its repository URL.
+///
+/// GitLab instances may be hosted anywhere, so any HTTP(S) or SSH repository URL with
+/// a host and a project path is accepted. SSH URLs map to HTTPS web URLs; for
+/// example, "git@gitlab.example.com:group/project.git" maps to
+/// "https://gitlab.example.com/group/project".
+fn gitlab_project_url(url: &str) -> Option<String> {
+    let (scheme, host, path) = if let Some((scheme, rest)) = url
+        .split_once("://")
+        .filter(|(scheme, _)| matches!(*scheme, "https" | "http" | "ssh"))
+    {
+        let (host, path) = rest.split_once('/')?;
+        let host = host.rsplit_once('@').map_or(host, |(_, host)| host);
+        if scheme == "ssh" {
+            (
+                "https",
+                host.split_once(':').map_or(host, |(host, _)| host),
+                path,
+            )
+        } else {
+            (scheme, host, path)
+        }
+    } else {
+        let (user_host, path) = url.split_once(':')?;
+        let (_, host) = user_host.rsplit_once('@')?;
+        ("https", host, path)
+    };
+    let path = path.trim_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    if host.is_empty() || !path.contains('/') || path.split('/').any(str::is_empty) {
+        None
+    } else {
+        Some(format!("{scheme}://{host}/{path}"))
+    }
+}
+
+/// Get the `https://github.com/<owner>/<repo>` URL of a GitHub repository URL.
+///
+/// Both HTTPS and SSH repository URLs are recognized.
+fn github_repo_url(url: &str) -> Option<String> {
+    let path = [
+        "https://github.com/",
+        "http://github.com/",
+        "ssh://git@github.com/",
+        "git@github.com:",
+    ]
+    .iter()
+    .find_map(|prefix| url.strip_prefix(prefix))?;
+    let path = path.trim_end_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    let (owner, name) = path.split_once('/')?;
+    if owner.is_empty() || name.is_empty() || name.contains('/') {
+        None
+    } else {
+        Some(format!("https://github.com/{owner}/{name}"))
+    }
+}
+
+/// Import all of the patch files in the directory tree rooted at `source_path`.
+fn import_tree(stack: Stack, matches: &clap::ArgMatches, source_path: &Path) -> Result<()> {
+    if !source_path.is_dir() {
+        return Err(anyhow!(
+            "`{}` is not a directory",
+            source_path.to_string_lossy()
+        ));
+    }
+    let filter = TreeFilter {
+        include: matches
+            .get_many::<String>("include")
+            .map(|globs| globs.cloned().collect())
+            .unwrap_or_default(),
+        exclude: matches
+            .get_many::<String>("exclude")
+            .map(|globs| globs.cloned().collect())
+            .unwrap_or_default(),
+    };
+
+    let mut patches = Vec::new();
+    collect_tree_patches(source_path, source_path, &filter, &mut patches)?;
+    if patches.is_empty() {
+        return Err(anyhow!(
+            "no patch files found in `{}`",
+            source_path.to_string_lossy()
+        ));
+    }
+
+    let mut stack = stack;
+    for (patch_path, strip_level) in patches {
         stack = import_file(stack, matches, Some(patch_path.as_path()), strip_level)?;
     }
     Ok(())
 }
 
+/// Include and exclude glob patterns for selecting files in a patch tree.
+struct TreeFilter {
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+impl TreeFilter {
+    /// File name patterns of patch files selected when no include patterns are given.
+    const DEFAULT_INCLUDE: [&'static str; 2] = ["*.patch", "*.diff"];
+
+    /// Determine whether a file is selected for import.
+    ///
+    /// Files listed in a series file are selected unless excluded or, when include
+    /// patterns are given, not included. Other files must also match the default
+    /// include patterns when no include patterns are given.
+    fn is_selected(&self, relative_path: &Path, in_series: bool) -> bool {
+        if globs_match(&self.exclude, relative_path) {
+            false
+        } else if !self.include.is_empty() {
+            globs_match(&self.include, relative_path)
+        } else {
+            in_series || globs_match(&Self::DEFAULT_INCLUDE, relative_path)
+        }
+    }
+}
+
+/// Determine whether any of the globs match a path relative to the root of a patch tree.
+///
+/// Like gitignore patterns, a glob without a slash is matched against the file name
+/// and other globs are matched against the whole relative path.
+fn globs_match<S: AsRef<str>>(globs: &[S], relative_path: &Path) -> bool {
+    let path = relative_path
+        .to_string_lossy()
+        .replace(std::path::MAIN_SEPARATOR, "/");
+    let file_name = path.rsplit('/').next().unwrap_or_default();
+    globs.iter().any(|glob| {
+        let glob = glob.as_ref();
+        let value = if glob.contains('/') {
+            path.as_str()
+        } else {
+            file_name
+        };
+        git_repository::glob::wildmatch(
+            glob.into(),
+            value.into(),
+            git_repository::glob::wildmatch::Mode::NO_MATCH_SLASH_LITERAL,
+        )
+    })
+}
+
+/// Collect the patch files of the directory `dir` within the patch tree at `root`.
+///
+/// When `dir` contains a `series` file, the patches listed in it are used in the
+/// listed order and `dir` is not searched further. Otherwise, the files and
+/// subdirectories of `dir` are visited in natural order, such that, e.g., `2-fix.patch`
+/// precedes `10-feature.patch`.
+fn collect_tree_patches(
+    root: &Path,
+    dir: &Path,
+    filter: &TreeFilter,
+    patches: &mut Vec<(PathBuf, Option<usize>)>,
+) -> Result<()> {
+    let relative_path = |path: &Path| path.strip_prefix(root).unwrap_or(path).to_path_buf();
+
+    let series_path = dir.join("series");
+    if series_path.is_file() {
+        for (name, strip_level) in parse_series(&std::fs::read(&series_path)?)? {
+            let patch_path = dir.join(name);
+            if filter.is_selected(&relative_path(&patch_path), true) {
+                patches.push((patch_path, strip_level));
+            }
+        }
+        return Ok(());
+    }
+
+    let mut entries = std::fs::read_dir(dir)
+        .with_context(|| format!("reading directory `{}`", dir.to_string_lossy()))?
+        .collect::<std::io::Result<Vec<_>>>()?;
+    entries.retain(|entry| !entry.file_name().to_string_lossy().starts_with('.'));
+    entries.sort_by(|a, b| {
+        natural_cmp(
+            &a.file_name().to_string_lossy(),
+            &b.file_name().to_string_lossy(),
+        )
+    });
+
+    for entry in entries {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_tree_patches(root, &path, filter, patches)?;
+        } else if path.is_file() && filter.is_selected(&relative_path(&path), false) {
+            patches.push((path, None));
+        }
+    }
+    Ok(())
+}
+
+/// Compare strings such that runs of digits are compared by their numeric value.
+fn natural_cmp(a: &str, b: &str) -> std::cmp::Ordering {
+    let mut a = a.as_bytes();
+    let mut b = b.as_bytes();
+    loop {
+        match (a.first(), b.first()) {
+            (None, None) => return std::cmp::Ordering::Equal,
+            (None, Some(_)) => return std::cmp::Ordering::Less,
+            (Some(_), None) => return std::cmp::Ordering::Greater,
+            (Some(ca), Some(cb)) if ca.is_ascii_digit() && cb.is_ascii_digit() => {
+                let a_len = a.iter().take_while(|c| c.is_ascii_digit()).count();
+                let b_len = b.iter().take_while(|c| c.is_ascii_digit()).count();
+                let a_digits = trim_leading_zeros(&a[..a_len]);
+                let b_digits = trim_leading_zeros(&b[..b_len]);
+                let ordering = a_digits
+                    .len()
+                    .cmp(&b_digits.len())
+                    .then_with(|| a_digits.cmp(b_digits))
+                    .then_with(|| a_len.cmp(&b_len));
+                if ordering != std::cmp::Ordering::Equal {
+                    return ordering;
+                }
+                a = &a[a_len..];
+                b = &b[b_len..];
+            }
+            (Some(ca), Some(cb)) => {
+                if ca != cb {
+                    return ca.cmp(cb);
+                }
+                a = &a[1..];
+                b = &b[1..];
+            }
+        }
+    }
+}
+
+fn trim_leading_zeros(digits: &[u8]) -> &[u8] {
+    let zeros = digits.iter().take_while(|&&c| c == b'0').count();
+    &digits[zeros..]
+}
+
 #[cfg(feature = "import-compressed")]
 fn find_series_path(base: &Path) -> Result<PathBuf> {
     for entry in base.read_dir()? {
@@ -446,61 +1161,475 @@ fn use_message_id(matches: &clap::ArgMatches, config: &git_repository::config::S
     matches.get_flag("message-id") || config.boolean("stgit.import.message-id").unwrap_or(false)
 }
 
+/// Get the archive URL for "Link:" trailers, if they are to be added.
+///
+/// The URL given with '--link' takes precedence, followed by `stgit.email.inbox-url`
+/// and the default public-inbox archive.
+fn link_url(
+    matches: &clap::ArgMatches,
+    config: &git_repository::config::Snapshot,
+) -> Option<String> {
+    let url = if let Some(url) = matches.get_one::<String>("link") {
+        if url.is_empty() {
+            None
+        } else {
+            Some(url.clone())
+        }
+    } else if config.boolean("stgit.import.link").unwrap_or(false) {
+        None
+    } else {
+        return None;
+    };
+    let url = url
+        .or_else(|| {
+            config
+                .string("stgit.email.inbox-url")
+                .map(|url| url.to_str_lossy().into_owned())
+        })
+        .unwrap_or_else(|| super::email::DEFAULT_INBOX_URL.to_string());
+    Some(url.trim_end_matches('/').to_string())
+}
+
+/// Import the emails of a mail or mbox file, or of stdin.
+///
+/// The emails are read one at a time, each patch being created before the next email
+/// is read.
 fn import_mail(stack: Stack, matches: &clap::ArgMatches, source_path: Option<&Path>) -> Result<()> {
-    let out_dir = tempfile::tempdir()?;
-    let missing_from_ok = matches.get_flag("mail");
+    let allow_bare = matches.get_flag("mail");
     let keep_cr = matches.get_flag("keep-cr");
-    let message_id = use_message_id(matches, &stack.repo.config_snapshot());
-    let stupid = stack.repo.stupid();
-    let num_patches = stupid.mailsplit(source_path, out_dir.path(), keep_cr, missing_from_ok)?;
+    if let Some(source_path) = source_path {
+        let source_file = std::fs::File::open(source_path)
+            .with_context(|| format!("opening `{}`", source_path.to_string_lossy()))?;
+        let reader = std::io::BufReader::new(source_file);
+        import_messages(stack, matches, Mbox::new(reader, keep_cr, allow_bare), None)
+    } else {
+        let stdin = std::io::stdin();
+        let reader = stdin.lock();
+        import_messages(stack, matches, Mbox::new(reader, keep_cr, allow_bare), None)
+    }
+}
+
+/// Import the patch emails of a Maildir in series order.
+fn import_maildir(stack: Stack, matches: &clap::ArgMatches, maildir: &Path) -> Result<()> {
+    let subdirs = [maildir.join("new"), maildir.join("cur")];
+    if !subdirs.iter().any(|subdir| subdir.is_dir()) {
+        return Err(anyhow!("`{}` is not a Maildir", maildir.to_string_lossy()));
+    }
+
+    let keep_cr = matches.get_flag("keep-cr");
+    let mut emails = Vec::new();
+    for subdir in subdirs.iter().filter(|subdir| subdir.is_dir()) {
+        let entries = std::fs::read_dir(subdir)
+            .with_context(|| format!("reading directory `{}`", subdir.to_string_lossy()))?;
+        for entry in entries {
+            let entry = entry?;
+            if entry.file_name().to_string_lossy().starts_with('.') || !entry.path().is_file() {
+                continue;
+            }
+            let mut raw = std::fs::read(entry.path())?;
+            if !keep_cr {
+                raw = raw.replace(b"\r\n", b"\n");
+            }
+            let subject = raw_header(&raw, "subject").unwrap_or_default();
+            if subject
+                .get(..3)
+                .map_or(false, |prefix| prefix.eq_ignore_ascii_case("re:"))
+            {
+                continue;
+            }
+            let time = raw_header(&raw, "date")
+                .and_then(|date| git_repository::actor::Time::parse_time(&date).ok())
+                .map(|time| time.seconds_since_unix_epoch);
+            emails.push(MaildirEmail {
+                index: subject_index(&subject),
+                time,
+                file_name: entry.file_name(),
+                raw,
+            });
+        }
+    }
+
+    let mut patches = Vec::new();
+    for email in emails {
+        let info = MailInfo::parse(&email.raw);
+        if !info.diff.trim().is_empty() {
+            patches.push((email, info));
+        }
+    }
+    if patches.is_empty() {
+        return Err(anyhow!(
+            "no patch emails found in `{}`",
+            maildir.to_string_lossy()
+        ));
+    }
+
+    if patches.iter().all(|(email, ..)| email.index.is_some()) {
+        patches.sort_by(|(a, ..), (b, ..)| {
+            (a.index, a.time, &a.file_name).cmp(&(b.index, b.time, &b.file_name))
+        });
+    } else {
+        patches.sort_by(|(a, ..), (b, ..)| (a.time, &a.file_name).cmp(&(b.time, &b.file_name)));
+    }
+
+    let messages = patches.into_iter().map(|(email, _)| Ok(email.raw));
+    import_messages(stack, matches, messages, None)
+}
+
+/// Directory, within the git directory, holding the state of an interrupted mail import.
+const SEQUENCER_DIR: &str = "stgit-sequencer";
+
+/// State of a mail import interrupted by a patch that failed to apply.
+///
+/// The raw emails yet to be imported are stored alongside, in the `messages`
+/// subdirectory of [`SEQUENCER_DIR`].
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Sequencer {
+    /// Branch the emails are being imported to.
+    branch: String,
+    /// Stack state commit before the import started, restored by '--abort'.
+    orig_state: String,
+    /// Command line options of the interrupted import.
+    options: Vec<String>,
+    /// Whether the patch for the first remaining email is yet to be created.
+    pending: bool,
+}
+
+fn sequencer_dir(repo: &git_repository::Repository) -> PathBuf {
+    repo.git_dir().join(SEQUENCER_DIR)
+}
+
+/// Import patch emails in order, saving the sequencer state if one fails to apply.
+///
+/// Each email is imported as it is taken from `messages`, such that the emails of a
+/// large mbox need not all be held in memory.
+///
+/// The `orig_state` of a resumed import is that of the interrupted import. Otherwise,
+/// the current stack state is used, with any external modifications logged first.
+fn import_messages(
+    stack: Stack,
+    matches: &clap::ArgMatches,
+    messages: impl Iterator<Item = Result<Vec<u8>>>,
+    orig_state: Option<String>,
+) -> Result<()> {
+    let repo = stack.repo;
+    let branch = stack.get_branch_name().to_string();
     let mut stack = stack;
-    for i in 1..=num_patches {
-        let patch_path = out_dir.path().join(format!("{i:04}"));
-        let patch_file = std::fs::File::open(patch_path)?;
-        let (mailinfo, message, diff) = stupid.mailinfo(Some(patch_file), message_id)?;
-        let headers = Headers::parse_mailinfo(&mailinfo).unwrap_or_default();
-        stack = create_patch(stack, matches, None, headers, &message, &diff, None)?;
+    let orig_state = if let Some(orig_state) = orig_state {
+        orig_state
+    } else {
+        if !stack.is_head_top() {
+            stack = stack.log_external_mods(None)?;
+        }
+        let refname = state_refname_from_branch_name(repo, &branch)?;
+        repo.find_reference(refname.as_str())?
+            .into_fully_peeled_id()?
+            .to_string()
+    };
+    let progress = matches.get_flag("progress");
+    let mut stderr = std::io::stderr();
+    let mut messages = messages.peekable();
+    let mut count = 0;
+    while let Some(raw) = messages.next() {
+        let raw = raw?;
+        count += 1;
+        if progress {
+            write!(stderr, "\rImporting emails: {count}")?;
+        }
+        stack = match import_message(stack, matches, &raw, true) {
+            Ok(stack) => stack,
+            Err(e) => {
+                if progress {
+                    writeln!(stderr)?;
+                }
+                let pending = e.downcast_ref::<PartiallyApplied>().is_none();
+                if pending || messages.peek().is_some() {
+                    let sequencer = Sequencer {
+                        branch,
+                        orig_state,
+                        options: recorded_options(matches),
+                        pending,
+                    };
+                    let remaining = pending.then_some(Ok(raw)).into_iter().chain(messages);
+                    let num_remaining = write_sequencer(repo, &sequencer, remaining)?;
+                    crate::print_info_message(
+                        matches,
+                        &format!(
+                            "import stopped with {num_remaining} email{} remaining; resolve \
+                             and run `stg import --continue`, or use `--skip` or `--abort`",
+                            if num_remaining == 1 { "" } else { "s" },
+                        ),
+                    );
+                }
+                return Err(e);
+            }
+        };
+    }
+    if progress && count > 0 {
+        writeln!(stderr, ", done.")?;
     }
     Ok(())
 }
 
+/// Create a patch from a raw patch email.
+///
+/// When `apply` is false, the email's diff is not applied and the patch is instead
+/// created from the tree in the index.
+fn import_message<'repo>(
+    stack: Stack<'repo>,
+    matches: &clap::ArgMatches,
+    raw: &[u8],
+    apply: bool,
+) -> Result<Stack<'repo>> {
+    let info = MailInfo::parse(raw);
+    let mut headers = Headers::from_mailinfo(&info).unwrap_or_default();
+    headers.use_stgit_patch_header(matches, raw);
+    create_patch(
+        stack,
+        matches,
+        None,
+        headers,
+        &info.message,
+        apply.then_some(info.diff.as_slice()),
+        None,
+    )
+}
+
+/// Get the options of an import from the command line, to be used when resuming it.
+///
+/// The source options are omitted since the emails to import are saved separately.
+fn recorded_options(matches: &clap::ArgMatches) -> Vec<String> {
+    let mut options: Vec<(usize, String)> = Vec::new();
+    for arg in make().get_arguments() {
+        let id = arg.get_id().as_str();
+        let long = if let Some(long) = arg.get_long() {
+            long
+        } else {
+            continue;
+        };
+        if matches!(id, "mail" | "mbox" | "maildir" | "url")
+            || matches.value_source(id) != Some(clap::parser::ValueSource::CommandLine)
+        {
+            continue;
+        }
+        let indices = matches.indices_of(id).expect("option is present");
+        if matches!(arg.get_action(), clap::ArgAction::SetTrue) {
+            options.extend(indices.map(|index| (index, format!("--{long}"))));
+        } else if let Some(values) = matches.get_raw(id) {
+            for (index, value) in indices.zip(values) {
+                if value.is_empty()
+                    && arg
+                        .get_num_args()
+                        .map_or(false, |range| range.min_values() == 0)
+                {
+                    options.push((index, format!("--{long}")));
+                } else {
+                    options.push((index, format!("--{long}={}", value.to_string_lossy())));
+                }
+            }
+        }
+    }
+    options.sort_by_key(|(index, _)| *index);
+    options.into_iter().map(|(_, option)| option).collect()
+}
+
+/// Save the state of an interrupted mail import along with its remaining emails.
+///
+/// Returns the number of emails saved.
+fn write_sequencer(
+    repo: &git_repository::Repository,
+    sequencer: &Sequencer,
+    messages: impl Iterator<Item = Result<Vec<u8>>>,
+) -> Result<usize> {
+    let dir = sequencer_dir(repo);
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir)?;
+    }
+    let messages_dir = dir.join("messages");
+    std::fs::create_dir_all(&messages_dir)?;
+    let mut count = 0;
+    for raw in messages {
+        count += 1;
+        std::fs::write(messages_dir.join(format!("{count:04}")), raw?)?;
+    }
+    std::fs::write(dir.join("state"), serde_json::to_string_pretty(sequencer)?)?;
+    Ok(count)
+}
+
+/// Read the state of an interrupted mail import and the file names of its emails.
+///
+/// The file names, relative to the `messages` subdirectory, are in import order.
+fn read_sequencer(
+    repo: &git_repository::Repository,
+) -> Result<(Sequencer, Vec<std::ffi::OsString>)> {
+    let dir = sequencer_dir(repo);
+    let state_path = dir.join("state");
+    if !state_path.is_file() {
+        return Err(anyhow!("no mail import in progress"));
+    }
+    let sequencer: Sequencer = serde_json::from_slice(&std::fs::read(&state_path)?)
+        .context("reading mail import state")?;
+    let mut message_names = std::fs::read_dir(dir.join("messages"))?
+        .map(|entry| entry.map(|entry| entry.file_name()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    message_names.sort_by(|a, b| (a.len(), a).cmp(&(b.len(), b)));
+    Ok((sequencer, message_names))
+}
+
+/// Handle '--continue', '--skip', and '--abort' for an interrupted mail import.
+fn resume_import(stack: Stack, matches: &clap::ArgMatches) -> Result<()> {
+    let repo = stack.repo;
+    let (sequencer, mut message_names) = read_sequencer(repo)?;
+    if stack.get_branch_name() != sequencer.branch {
+        return Err(anyhow!(
+            "the interrupted mail import is for branch `{}`",
+            sequencer.branch
+        ));
+    }
+    let stupid = repo.stupid();
+
+    if matches.get_flag("abort") {
+        let orig_state_id = git_repository::ObjectId::from_hex(sequencer.orig_state.as_bytes())
+            .context("reading mail import state")?;
+        stack
+            .setup_transaction()
+            .use_index_and_worktree(true)
+            .discard_changes(true)
+            .allow_bad_head(true)
+            .with_output_stream(get_color_stdout(matches))
+            .transact(|trans| {
+                let commit = trans.repo().find_commit(orig_state_id)?;
+                let orig_state = StackState::from_commit(trans.repo(), &commit)?;
+                trans.reset_to_state(orig_state)
+            })
+            .execute("import: abort")?;
+        std::fs::remove_dir_all(sequencer_dir(repo))?;
+        return Ok(());
+    }
+
+    let options = make()
+        .arg(crate::color::get_color_arg())
+        .no_binary_name(true)
+        .try_get_matches_from(&sequencer.options)
+        .context("reading mail import options")?;
+
+    let mut stack = stack;
+    if matches.get_flag("skip") {
+        if sequencer.pending {
+            let head_tree_id = stack.get_branch_head().tree_id()?.detach();
+            stupid.read_tree_checkout_hard(head_tree_id)?;
+            message_names.remove(0);
+        }
+    } else if sequencer.pending {
+        let statuses = stupid.statuses(None)?;
+        statuses.check_conflicts()?;
+        statuses.check_worktree_clean()?;
+        stack.check_head_top_mismatch()?;
+        if stupid.write_tree()? == stack.get_branch_head().tree_id()? {
+            return Err(anyhow!(
+                "no changes staged for the failed patch; stage the resolution with \
+                 `git add` or use `stg import --skip`"
+            ));
+        }
+        let messages_dir = sequencer_dir(repo).join("messages");
+        let raw = std::fs::read(messages_dir.join(&message_names[0]))?;
+        stack = import_message(stack, &options, &raw, false)?;
+        message_names.remove(0);
+    }
+
+    stupid.statuses(None)?.check_index_and_worktree_clean()?;
+    stack.check_head_top_mismatch()?;
+    // The remaining emails are moved aside such that they may be read as they are
+    // imported, while a new sequencer state is saved should the import stop again.
+    let messages_dir = tempfile::tempdir_in(repo.git_dir())?;
+    std::fs::rename(
+        sequencer_dir(repo).join("messages"),
+        messages_dir.path().join("messages"),
+    )?;
+    std::fs::remove_dir_all(sequencer_dir(repo))?;
+    let messages = message_names.iter().map(|name| {
+        std::fs::read(messages_dir.path().join("messages").join(name)).map_err(Into::into)
+    });
+    import_messages(stack, &options, messages, Some(sequencer.orig_state))
+}
+
+/// Error for a patch created with hunks rejected by '--reject'.
+///
+/// The mail import sequencer uses this to know that the patch has been created.
+#[derive(thiserror::Error, Debug)]
+#[error(
+    "patch `{patchname}` only partially applied; rejected hunks left in {reject_list}; \
+     fix up the patch and run `stg refresh`"
+)]
+struct PartiallyApplied {
+    patchname: PatchName,
+    reject_list: String,
+}
+
+/// An email read from a Maildir along with the information used to order it.
+struct MaildirEmail {
+    index: Option<usize>,
+    time: Option<u32>,
+    file_name: std::ffi::OsString,
+    raw: Vec<u8>,
+}
+
+/// Get the unfolded value of the first header of a raw email with the given name.
+fn raw_header(raw: &[u8], name: &str) -> Option<String> {
+    let mut value: Option<String> = None;
+    for line in raw.lines() {
+        if line.is_empty() {
+            break;
+        }
+        let line = line.to_str_lossy();
+        if line.starts_with(char::is_whitespace) {
+            if let Some(value) = value.as_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if value.is_some() {
+            break;
+        } else if let Some((header, header_value)) = line.split_once(':') {
+            if header.trim().eq_ignore_ascii_case(name) {
+                value = Some(header_value.trim().to_string());
+            }
+        }
+    }
+    value
+}
+
+/// Get the patch's index in its series from the "[PATCH n/m]" prefix of a subject.
+fn subject_index(subject: &str) -> Option<usize> {
+    let prefix = subject.strip_prefix('[')?.split_once(']')?.0;
+    prefix.split_whitespace().find_map(|token| {
+        let (n, m) = token.split_once('/')?;
+        m.parse::<usize>().ok()?;
+        n.parse::<usize>().ok()
+    })
+}
+
 #[cfg(feature = "import-compressed")]
-fn get_gz_mailinfo(
-    stupid: &StupidContext,
-    source_file: std::fs::File,
-    message_id: bool,
-) -> Result<(Vec<u8>, Vec<u8>, Vec<u8>)> {
-    let stream = flate2::read::GzDecoder::new(source_file);
-    stupid.mailinfo_stream(stream, message_id)
+fn read_gz(source_file: std::fs::File) -> Result<Vec<u8>> {
+    let mut content = Vec::new();
+    flate2::read::GzDecoder::new(source_file).read_to_end(&mut content)?;
+    Ok(content)
 }
 
 #[cfg(feature = "import-compressed")]
-fn get_bz2_mailinfo(
-    stupid: &StupidContext,
-    source_file: std::fs::File,
-    message_id: bool,
-) -> Result<(Vec<u8>, Vec<u8>, Vec<u8>)> {
-    let stream = bzip2::read::BzDecoder::new(source_file);
-    stupid.mailinfo_stream(stream, message_id)
+fn read_bz2(source_file: std::fs::File) -> Result<Vec<u8>> {
+    let mut content = Vec::new();
+    bzip2::read::BzDecoder::new(source_file).read_to_end(&mut content)?;
+    Ok(content)
 }
 
 #[cfg(not(feature = "import-compressed"))]
-fn get_gz_mailinfo(
-    _: &StupidContext,
-    _: std::fs::File,
-    _: bool,
-) -> Result<(Vec<u8>, Vec<u8>, Vec<u8>)> {
+fn read_gz(_: std::fs::File) -> Result<Vec<u8>> {
     Err(anyhow!(
         "StGit not built with support for compressed patches"
     ))
 }
 
 #[cfg(not(feature = "import-compressed"))]
-fn get_bz2_mailinfo(
-    _: &StupidContext,
-    _: std::fs::File,
-    _: bool,
-) -> Result<(Vec<u8>, Vec<u8>, Vec<u8>)> {
+fn read_bz2(_: std::fs::File) -> Result<Vec<u8>> {
     Err(anyhow!(
         "StGit not built with support for compressed patches"
     ))
@@ -512,37 +1641,26 @@ fn import_file<'repo>(
     source_path: Option<&Path>,
     strip_level: Option<usize>,
 ) -> Result<Stack<'repo>> {
-    let message_id = use_message_id(matches, &stack.repo.config_snapshot());
-    let stupid = stack.repo.stupid();
-
-    let (mailinfo, message, diff) = if let Some(source_path) = source_path {
+    let raw = if let Some(source_path) = source_path {
         let source_file = std::fs::File::open(source_path)?;
         match source_path.extension().and_then(std::ffi::OsStr::to_str) {
-            Some("gz") => get_gz_mailinfo(&stupid, source_file, message_id),
-            Some("bz2") => get_bz2_mailinfo(&stupid, source_file, message_id),
-            _ => stupid.mailinfo(Some(source_file), message_id),
+            Some("gz") => read_gz(source_file)?,
+            Some("bz2") => read_bz2(source_file)?,
+            _ => std::fs::read(source_path)?,
         }
     } else {
-        stupid.mailinfo(None, message_id)
-    }
-    .or_else(|e| {
-        if e.chain()
-            .last()
-            .unwrap()
-            .to_string()
-            .contains("error: empty patch")
-        {
-            Ok((vec![], vec![], vec![]))
-        } else {
-            Err(e)
-        }
-    })?;
+        let mut raw = Vec::new();
+        std::io::stdin().read_to_end(&mut raw)?;
+        raw
+    };
+    let info = MailInfo::parse(&raw);
 
-    let (headers, message) = if let Some(headers) = Headers::parse_mailinfo(&mailinfo) {
-        (headers, message)
+    let (mut headers, message) = if let Some(headers) = Headers::from_mailinfo(&info) {
+        (headers, info.message)
     } else {
-        Headers::parse_message(&message)?
+        Headers::parse_message(&info.message)?
     };
+    headers.use_stgit_patch_header(matches, &raw);
 
     create_patch(
         stack,
@@ -550,7 +1668,7 @@ fn import_file<'repo>(
         source_path,
         headers,
         &message,
-        &diff,
+        Some(&info.diff),
         strip_level,
     )
 }
@@ -561,7 +1679,7 @@ fn create_patch<'repo>(
     source_path: Option<&Path>,
     headers: Headers,
     message: &[u8],
-    diff: &[u8],
+    diff: Option<&[u8]>,
     strip_level: Option<usize>,
 ) -> Result<Stack<'repo>> {
     let config = stack.repo.config_snapshot();
@@ -572,6 +1690,7 @@ fn create_patch<'repo>(
         author_email,
         author_date,
         subject,
+        message_id,
     } = headers;
 
     let message = if let Some(mut subject) = subject {
@@ -582,6 +1701,39 @@ fn create_patch<'repo>(
         message.to_str_lossy().to_string()
     };
 
+    let message = if let Some(message_id) = message_id.as_deref() {
+        let mut trailers: Vec<(&str, String)> = Vec::new();
+        if use_message_id(matches, &config) {
+            trailers.push(("Message-Id", message_id.to_string()));
+        }
+        if let Some(link_url) = link_url(matches, &config) {
+            trailers.push((
+                "Link",
+                format!(
+                    "{link_url}/{}",
+                    super::email::encode_message_id(
+                        message_id.trim_start_matches('<').trim_end_matches('>')
+                    )
+                ),
+            ));
+        }
+        if trailers.is_empty() {
+            message
+        } else {
+            stack
+                .repo
+                .stupid()
+                .interpret_trailers(
+                    message.as_bytes(),
+                    trailers.iter().map(|(key, value)| (*key, value.as_str())),
+                )?
+                .to_str_lossy()
+                .into_owned()
+        }
+    } else {
+        message
+    };
+
     let patchname = if patchname.is_some() {
         patchname.as_deref()
     } else if let Some(name) = matches.get_one::<PatchName>("name") {
@@ -606,10 +1758,57 @@ fn create_patch<'repo>(
         PatchName::make(&message, true, name_len_limit)
     };
 
+    let mut stack = stack;
+    let mut replace_duplicate: Option<PatchName> = None;
+
+    let patchname = if let (Some(policy), Some(diff)) =
+        (matches.get_one::<String>("on-duplicate"), diff)
+    {
+        if let Some(duplicate) = find_duplicate(&stack, &message, diff)? {
+            match policy.as_str() {
+                "skip" => {
+                    eprintln!("info: skipping duplicate of patch `{duplicate}`");
+                    return Ok(stack);
+                }
+                "replace" => {
+                    eprintln!("info: replacing duplicate patch `{duplicate}`");
+                    if stack.is_applied(&duplicate) {
+                        stack = stack
+                            .setup_transaction()
+                            .with_output_stream(get_color_stdout(matches))
+                            .use_index_and_worktree(true)
+                            .allow_conflicts(false)
+                            .transact(|trans| {
+                                let to_push = trans.delete_patches(|pn| pn == &duplicate)?;
+                                trans.push_patches(&to_push, false)
+                            })
+                            .execute(&format!("import: delete {duplicate}"))?;
+                    } else {
+                        replace_duplicate = Some(duplicate.clone());
+                    }
+                    duplicate
+                }
+                "new-name" => {
+                    let disallow_patchnames: Vec<&PatchName> = stack.all_patches().collect();
+                    let patchname = patchname.uniquify(&[], &disallow_patchnames);
+                    eprintln!("info: importing duplicate of patch `{duplicate}` as `{patchname}`");
+                    patchname
+                }
+                _ => panic!("unhandled on-duplicate policy `{policy}`"),
+            }
+        } else {
+            patchname
+        }
+    } else {
+        patchname
+    };
+
     let ignore_flag = matches.get_flag("ignore");
     let replace_flag = matches.get_flag("replace");
 
-    let patchname = if !ignore_flag && !replace_flag {
+    let patchname = if let Some(duplicate) = replace_duplicate.as_ref() {
+        duplicate.clone()
+    } else if !ignore_flag && !replace_flag {
         let disallow_patchnames: Vec<&PatchName> = stack.all_patches().collect();
         patchname.uniquify(&[], &disallow_patchnames)
     } else if ignore_flag && stack.applied().contains(&patchname) {
@@ -644,24 +1843,60 @@ fn create_patch<'repo>(
 
     let strip_level = strip_level.or_else(|| matches.get_one::<usize>("strip").copied());
 
-    let trimmed_diff = diff.trim_end_with(|c| c.is_ascii_whitespace());
+    let trimmed_diff = diff.map(|diff| diff.trim_end_with(|c| c.is_ascii_whitespace()));
+
+    let directory = matches
+        .get_one::<PathBuf>("directory")
+        .map(|path_buf| path_buf.as_path());
+    let context_lines = matches.get_one::<usize>("context-lines").copied();
+    let path_filters = path_filters(matches);
+
+    // Paths of the `.rej` files left by hunks that did not apply with '--reject'.
+    let mut reject_paths: Vec<PathBuf> = Vec::new();
+
+    let tree_id = if let (Some(diff), Some(trimmed_diff)) = (diff, trimmed_diff) {
+        if trimmed_diff.is_empty() || trimmed_diff == b"---" {
+            stack.get_branch_head().tree_id()?.detach()
+        } else if matches.get_flag("reject") {
+            let stupid = stack.repo.stupid();
+            let (applied_cleanly, paths) = stupid.apply_to_worktree_with_reject(
+                diff,
+                strip_level,
+                directory,
+                context_lines,
+                &path_filters,
+            )?;
+            stupid.update_index(Some(&paths))?;
+            if !applied_cleanly {
+                let work_dir = stack.repo.work_dir().expect("import requires a worktree");
+                for path in &paths {
+                    let mut reject_path = path.clone();
+                    reject_path.push(".rej");
+                    let reject_path = PathBuf::from(reject_path);
+                    if work_dir.join(&reject_path).is_file() && !reject_paths.contains(&reject_path)
+                    {
+                        reject_paths.push(reject_path);
+                    }
+                }
+            }
 
-    let tree_id = if trimmed_diff.is_empty() || trimmed_diff == b"---" {
-        stack.get_branch_head().tree_id()?.detach()
+            stupid.write_tree()?
+        } else {
+            let stupid = stack.repo.stupid();
+            stupid.apply_to_worktree_and_index(
+                diff,
+                false,
+                matches.get_flag("3way"),
+                strip_level,
+                directory,
+                context_lines,
+                &path_filters,
+            )?;
+
+            stupid.write_tree()?
+        }
     } else {
-        let stupid = stack.repo.stupid();
-        stupid.apply_to_worktree_and_index(
-            diff,
-            matches.get_flag("reject"),
-            matches.get_flag("3way"),
-            strip_level,
-            matches
-                .get_one::<PathBuf>("directory")
-                .map(|path_buf| path_buf.as_path()),
-            matches.get_one::<usize>("context-lines").copied(),
-        )?;
-
-        stupid.write_tree()?
+        stack.repo.stupid().write_tree()?
     };
 
     let (new_patchname, commit_id) = match crate::patch::edit::EditBuilder::default()
@@ -680,13 +1915,14 @@ fn create_patch<'repo>(
         patchedit::EditOutcome::Edited {
             new_patchname,
             new_commit_id,
+            ..
         } => (
             new_patchname.unwrap_or(patchname),
             new_commit_id.expect("must have new commit id because no original patch commit"),
         ),
     };
 
-    stack
+    let stack = stack
         .setup_transaction()
         .with_output_stream(get_color_stdout(matches))
         .use_index_and_worktree(false)
@@ -694,10 +1930,95 @@ fn create_patch<'repo>(
         .transact(|trans| {
             if replace_flag && trans.unapplied().contains(&new_patchname) {
                 trans.delete_patches(|pn| pn == &new_patchname)?;
+            } else if let Some(duplicate) = replace_duplicate.as_ref() {
+                trans.delete_patches(|pn| pn == duplicate)?;
             }
             trans.new_applied(&new_patchname, commit_id)
         })
-        .execute(&format!("import: {new_patchname}"))
+        .execute(&format!("import: {new_patchname}"))?;
+
+    if reject_paths.is_empty() {
+        Ok(stack)
+    } else {
+        let reject_list: Vec<String> = reject_paths
+            .iter()
+            .map(|path| format!("`{}`", path.display()))
+            .collect();
+        Err(PartiallyApplied {
+            patchname: new_patchname,
+            reject_list: reject_list.join(", "),
+        }
+        .into())
+    }
+}
+
+/// Get the `git apply` options limiting the paths a diff is applied to.
+///
+/// The globs of '--include-path' and '--exclude-path' are passed to `git apply` in
+/// command line order since the first matching glob determines whether a path is
+/// included.
+fn path_filters(matches: &clap::ArgMatches) -> Vec<String> {
+    let mut filters: Vec<(usize, String)> = Vec::new();
+    for (id, option) in [("include-path", "--include"), ("exclude-path", "--exclude")] {
+        if let (Some(indices), Some(globs)) =
+            (matches.indices_of(id), matches.get_many::<String>(id))
+        {
+            filters.extend(
+                indices
+                    .zip(globs)
+                    .map(|(index, glob)| (index, format!("{option}={glob}"))),
+            );
+        }
+    }
+    filters.sort_by_key(|(index, _)| *index);
+    filters.into_iter().map(|(_, filter)| filter).collect()
+}
+
+/// Find an existing patch that duplicates a patch being imported.
+///
+/// An existing patch is a duplicate if its subject or patch id matches the imported
+/// patch's subject or patch id.
+fn find_duplicate(stack: &Stack, message: &str, diff: &[u8]) -> Result<Option<PatchName>> {
+    let stupid = stack.repo.stupid();
+    let subject = message
+        .lines()
+        .next()
+        .map(str::trim)
+        .filter(|subject| !subject.is_empty());
+    let trimmed_diff = diff.trim_end_with(|c| c.is_ascii_whitespace());
+    let patch_id = if trimmed_diff.is_empty() || trimmed_diff == b"---" {
+        None
+    } else {
+        stupid.patch_id(diff)?
+    };
+    // Binary changes only have the same patch id when both diffs include their data.
+    let diff_opts: &[&str] = if diff.contains_str("\nGIT binary patch\n") {
+        &["--binary"]
+    } else {
+        &[]
+    };
+
+    for patchname in stack.all_patches() {
+        let commit = stack.get_patch_commit(patchname)?;
+        let existing_subject = commit.message_raw()?.lines().next().map(|line| line.trim());
+        if subject.is_some() && existing_subject == subject.map(str::as_bytes) {
+            return Ok(Some(patchname.clone()));
+        }
+        if patch_id.is_some() {
+            let diff = stupid.diff_tree_patch(
+                commit.get_parent_commit()?.tree_id()?.detach(),
+                commit.tree_id()?.detach(),
+                <Option<Vec<&str>>>::None,
+                false,
+                diff_opts,
+            )?;
+            if stupid.patch_id(&diff)? == patch_id {
+                return Ok(Some(patchname.clone()));
+            }
+        }
+    }
+
+    Ok(None)
 }
 
 fn stripname(name: &str) -> &str {
@@ -716,52 +2037,54 @@ struct Headers {
     author_email: Option<String>,
     author_date: Option<String>,
     subject: Option<String>,
+    message_id: Option<String>,
 }
 
 impl Headers {
-    fn parse_mailinfo(headers: &[u8]) -> Option<Headers> {
-        let mut author_name = None;
-        let mut author_email = None;
-        let mut author_date = None;
-        let mut subject = None;
-
-        for line in headers.lines().filter(|line| !line.is_empty()) {
-            let mut parts = line.splitn_str(2, b": ");
-            let header = parts.next().expect("mailinfo header line has header");
-            let value = parts.next().expect("mailinfo header line has value");
-            if let Ok(value) = value.to_str() {
-                let value = Some(value.to_string());
-                match header {
-                    b"Author" => author_name = value,
-                    b"Email" => author_email = value,
-                    b"Date" => author_date = value,
-                    b"Subject" => subject = value,
-                    _ => panic!(
-                        "unexpected mailinfo header \"{}\" with value \"{}\"",
-                        header.to_str_lossy(),
-                        value.unwrap()
-                    ),
-                }
-            }
-        }
-
-        if author_name.is_some()
-            || author_email.is_some()
-            || author_date.is_some()
-            || subject.is_some()
+    /// Get the headers determined from a patch email, if any.
+    fn from_mailinfo(info: &MailInfo) -> Option<Headers> {
+        if info.author_name.is_some()
+            || info.author_email.is_some()
+            || info.date.is_some()
+            || info.subject.is_some()
         {
             Some(Headers {
                 patchname: None,
-                author_name,
-                author_email,
-                author_date,
-                subject,
+                author_name: info.author_name.clone(),
+                author_email: info.author_email.clone(),
+                author_date: info.date.clone(),
+                subject: info.subject.clone(),
+                message_id: info.message_id.clone(),
             })
         } else {
             None
         }
     }
 
+    /// Use the patch name from the raw email's `X-StGit-Patch` header, if any.
+    ///
+    /// A name given with '--name' or a "Patch:" line in the message takes precedence.
+    fn use_stgit_patch_header(&mut self, matches: &clap::ArgMatches, raw: &[u8]) {
+        if self.patchname.is_some() || matches.contains_id("name") {
+            return;
+        }
+        for line in raw.lines() {
+            if line.is_empty() {
+                break;
+            }
+            if let Some((header, value)) = line.split_once_str(b":") {
+                if header.eq_ignore_ascii_case(b"x-stgit-patch") {
+                    if let Ok(value) = value.trim().to_str() {
+                        if !value.is_empty() {
+                            self.patchname = Some(value.to_string());
+                        }
+                    }
+                    break;
+                }
+            }
+        }
+    }
+
     fn parse_message(message: &[u8]) -> Result<(Headers, Vec<u8>)> {
         let mut headers = Headers::default();
         let mut dedent = "";
@@ -9,7 +9,7 @@ use crate::{
     argset,
     ext::RepositoryExtended,
     patch::patchrange,
-    stack::{InitializationPolicy, Stack, StackAccess},
+    stack::{InitializationPolicy, Stack, StackAccess, StackState},
     stupid::Stupid,
 };
 
@@ -31,6 +31,20 @@ fn make() -> clap::Command {
              through historical stack states. The 'stg reset' command may be used to \
              reset the stack directly to a historic state.\n\
              \n\
+             Each log entry shows summary statistics for its operation, when \
+             available: the number of patches pushed, popped, and renamed, the \
+             number of files changed in the stack head, and the number of patches \
+             that conflicted. These statistics are recorded as trailers in the stack \
+             state commit message, which is shown with '--full'.\n\
+             \n\
+             The '--files' option limits the history to the operations that changed \
+             the given paths in the stack's head, i.e. in the topmost applied patch. \
+             This may be used to find when the stack's treatment of a file changed.\n\
+             \n\
+             The '--before' option starts the history at the most recent stack state \
+             recorded at or before the given date. Any date format accepted by git may \
+             be used, including relative dates such as \"2 hours ago\".\n\
+             \n\
              The '--clear' option may be used to delete the stack's change history. \
              Undo and redo are unavailable on a stack without change history. Clearing \
              the stack state history cannot be undone.",
@@ -62,6 +76,20 @@ fn make() -> clap::Command {
                 .value_name("n")
                 .value_parser(argset::parse_usize),
         )
+        .arg(
+            Arg::new("files")
+                .long("files")
+                .help("Only show operations that changed <pathspec> in the stack head")
+                .value_name("pathspec")
+                .action(clap::ArgAction::Append)
+                .value_hint(clap::ValueHint::AnyPath),
+        )
+        .arg(
+            Arg::new("before")
+                .long("before")
+                .help("Show history starting at the stack state as of <date>")
+                .value_name("date"),
+        )
         .arg(
             Arg::new("full")
                 .long("full")
@@ -75,7 +103,7 @@ fn make() -> clap::Command {
                 .short('g')
                 .help("Run gitk instead of printing to stdout")
                 .action(clap::ArgAction::SetTrue)
-                .conflicts_with_all(["diff", "number", "full"]),
+                .conflicts_with_all(["diff", "number", "full", "files"]),
         )
         .arg(
             Arg::new("clear")
@@ -83,7 +111,15 @@ fn make() -> clap::Command {
                 .help("Clear the stack history")
                 // .exclusive(true),
                 .action(clap::ArgAction::SetTrue)
-                .conflicts_with_all(["patchranges-all", "diff", "number", "full", "graphical"]),
+                .conflicts_with_all([
+                    "patchranges-all",
+                    "diff",
+                    "number",
+                    "full",
+                    "graphical",
+                    "files",
+                    "before",
+                ]),
         )
 }
 
@@ -111,24 +147,42 @@ fn run(matches: &ArgMatches) -> Result<()> {
             None
         };
 
-        let simplified_parent_id = stack
-            .repo
-            .find_reference(stack.get_stack_refname())?
-            .into_fully_peeled_id()?
-            .object()?
-            .to_commit_ref()
-            .parents()
-            .next()
-            .ok_or_else(|| anyhow!("`{}` does not have any parents", stack.get_stack_refname()))?;
+        let simplified_parent_id = if let Some(date) = argset::get_one_str(matches, "before") {
+            stack.state_commit_at_date(date)?.id
+        } else {
+            stack
+                .repo
+                .find_reference(stack.get_stack_refname())?
+                .into_fully_peeled_id()?
+                .object()?
+                .to_commit_ref()
+                .parents()
+                .next()
+                .ok_or_else(|| {
+                    anyhow!("`{}` does not have any parents", stack.get_stack_refname())
+                })?
+        };
 
         let stupid = repo.stupid();
 
         if matches.get_flag("graphical") {
             stupid.gitk(simplified_parent_id, pathspecs)
         } else {
+            let (commit_ids, no_walk) =
+                if let Some(file_specs) = matches.get_many::<String>("files") {
+                    let file_specs: Vec<&String> = file_specs.collect();
+                    let commit_ids = find_file_changes(&repo, simplified_parent_id, &file_specs)?;
+                    if commit_ids.is_empty() {
+                        return Ok(());
+                    }
+                    (commit_ids, true)
+                } else {
+                    (vec![simplified_parent_id], false)
+                };
             let num_commits = matches.get_one::<usize>("number").copied();
             stupid.log(
-                simplified_parent_id,
+                &commit_ids,
+                no_walk,
                 pathspecs,
                 num_commits,
                 crate::color::use_color(matches),
@@ -138,3 +192,41 @@ fn run(matches: &ArgMatches) -> Result<()> {
         }
     }
 }
+
+/// Find stack log commits whose stack head changed the given paths.
+///
+/// The stack log is walked from `commit_id` and the head tree of each stack state is
+/// compared with the head tree of the preceding state.
+fn find_file_changes(
+    repo: &git_repository::Repository,
+    commit_id: git_repository::ObjectId,
+    file_specs: &[&String],
+) -> Result<Vec<git_repository::ObjectId>> {
+    let stupid = repo.stupid();
+    let mut commit_ids = Vec::new();
+    let mut commit = repo.find_commit(commit_id)?;
+    let mut head_tree_id = StackState::from_commit(repo, &commit)?
+        .head
+        .tree_id()?
+        .detach();
+    loop {
+        let parent_id = commit.parent_ids().next().map(|id| id.detach());
+        let parent = if let Some(parent_id) = parent_id {
+            repo.find_commit(parent_id)?
+        } else {
+            break;
+        };
+        let parent_head_tree_id = StackState::from_commit(repo, &parent)?
+            .head
+            .tree_id()?
+            .detach();
+        if parent_head_tree_id != head_tree_id
+            && stupid.diff_tree_quiet(parent_head_tree_id, head_tree_id, file_specs)?
+        {
+            commit_ids.push(commit.id);
+        }
+        commit = parent;
+        head_tree_id = parent_head_tree_id;
+    }
+    Ok(commit_ids)
+}
@@ -0,0 +1,287 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg mirror` implementation.
+
+use std::{collections::BTreeMap, path::PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use bstr::ByteSlice;
+use clap::{Arg, ArgMatches};
+
+use crate::{ext::RepositoryExtended, patch::meta::NOTES_REF, stupid::Stupid};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "mirror",
+    category: super::CommandCategory::Administration,
+    make,
+    run,
+};
+
+/// Namespaces of the references mirrored to the backup remote.
+const MIRRORED_PREFIXES: [&str; 5] = [
+    "refs/stacks/",
+    "refs/stacks-ns/",
+    "refs/patches/",
+    "refs/patches-ns/",
+    "refs/shelves/",
+];
+
+/// File, within the git directory, recording the references last mirrored to each
+/// remote.
+const MIRROR_STATE_FILE: &str = "stgit-mirror.json";
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Back up stacks to a remote repository")
+        .long_about(
+            "Back up the StGit stacks of all branches by pushing them to a remote \
+             repository.\n\
+             \n\
+             The stack state references, `refs/stacks/*`, the patch references, \
+             `refs/patches/*`, their namespaced equivalents, `refs/stacks-ns/*` and \
+             `refs/patches-ns/*`, the shelves, `refs/shelves/*`, and the patch metadata \
+             notes, `refs/notes/stgit`, are pushed to the same reference names in the \
+             remote. Since the stack state references record the stack's history, \
+             the history may later be inspected or restored, e.g. with `stg log` and \
+             `stg reset`, after fetching the references back.\n\
+             \n\
+             The mirror is incremental: the references pushed to each remote are \
+             recorded, and only the references created or updated since the previous \
+             mirror are pushed. References deleted since the previous mirror are also \
+             deleted from the remote. Use '--full' to push all references regardless, \
+             e.g. after the remote was replaced.\n\
+             \n\
+             The remote defaults to `stgit.mirror.remote` from the git configuration. \
+             When `stgit.mirror.auto` is set to 'true', the stacks are also mirrored \
+             after each StGit command that modifies the repository. A failure to \
+             mirror after another command is reported as a warning.",
+        )
+        .arg(
+            Arg::new("repository")
+                .help("Remote repository to mirror to")
+                .value_parser(clap::builder::NonEmptyStringValueParser::new()),
+        )
+        .arg(
+            Arg::new("full")
+                .long("full")
+                .help("Push all references, not only those changed since the last mirror")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .short('n')
+                .help("Show the references that would be pushed")
+                .action(clap::ArgAction::SetTrue),
+        )
+}
+
+fn run(matches: &ArgMatches) -> Result<()> {
+    let repo = git_repository::Repository::open()?;
+    let remote_name = if let Some(remote_name) = matches.get_one::<String>("repository") {
+        remote_name.clone()
+    } else {
+        configured_remote(&repo).ok_or_else(|| {
+            anyhow!("no mirror remote; specify a remote or set `stgit.mirror.remote`")
+        })?
+    };
+
+    let changes = mirror(
+        &repo,
+        &remote_name,
+        matches.get_flag("full"),
+        matches.get_flag("dry-run"),
+    )?;
+
+    if changes.is_empty() {
+        println!("mirror `{remote_name}` is up to date");
+    }
+    for change in &changes {
+        let sigil = match change {
+            RefChange::Create(..) => '+',
+            RefChange::Update(..) => '~',
+            RefChange::Delete(_) => '-',
+        };
+        println!("{sigil} {}", change.refname());
+    }
+    Ok(())
+}
+
+/// Mirror the stacks to the configured remote when `stgit.mirror.auto` is enabled.
+///
+/// Called after StGit commands that may have modified the repository. Failures are
+/// reported as warnings since the command itself succeeded.
+pub(super) fn auto_mirror(matches: &ArgMatches) {
+    let Ok(repo) = git_repository::Repository::open() else {
+        return;
+    };
+    if !repo
+        .config_snapshot()
+        .boolean("stgit.mirror.auto")
+        .unwrap_or(false)
+    {
+        return;
+    }
+    let result = if let Some(remote_name) = configured_remote(&repo) {
+        mirror(&repo, &remote_name, false, false)
+            .map(|_| ())
+            .with_context(|| format!("mirroring stacks to `{remote_name}`"))
+    } else {
+        Err(anyhow!(
+            "`stgit.mirror.auto` is enabled, but `stgit.mirror.remote` is not set"
+        ))
+    };
+    if let Err(e) = result {
+        crate::print_warning_message(matches, &format!("{e:#}"));
+    }
+}
+
+fn configured_remote(repo: &git_repository::Repository) -> Option<String> {
+    repo.config_snapshot()
+        .string("stgit.mirror.remote")
+        .and_then(|name| name.to_str().ok().map(str::to_string))
+        .filter(|name| !name.is_empty())
+}
+
+/// A change to a mirrored reference.
+enum RefChange {
+    Create(String, git_repository::ObjectId),
+    Update(String, git_repository::ObjectId),
+    Delete(String),
+}
+
+impl RefChange {
+    fn refname(&self) -> &str {
+        match self {
+            RefChange::Create(refname, _)
+            | RefChange::Update(refname, _)
+            | RefChange::Delete(refname) => refname,
+        }
+    }
+
+    fn refspec(&self) -> String {
+        match self {
+            RefChange::Create(refname, id) | RefChange::Update(refname, id) => {
+                format!("+{id}:{refname}")
+            }
+            RefChange::Delete(refname) => format!(":{refname}"),
+        }
+    }
+}
+
+/// Push the references changed since the last mirror to `remote_name`.
+///
+/// With `full`, all references are pushed. The mirrored references are recorded unless
+/// `dry_run` is set, in which case nothing is pushed.
+fn mirror(
+    repo: &git_repository::Repository,
+    remote_name: &str,
+    full: bool,
+    dry_run: bool,
+) -> Result<Vec<RefChange>> {
+    let current = mirrored_refs(repo)?;
+    let mut state = read_mirror_state(repo)?;
+    let empty = BTreeMap::new();
+    let previous = if full {
+        &empty
+    } else {
+        state.get(remote_name).unwrap_or(&empty)
+    };
+
+    let mut changes = Vec::new();
+    for (refname, id) in &current {
+        match previous.get(refname) {
+            None => changes.push(RefChange::Create(refname.clone(), *id)),
+            Some(previous_id) if previous_id != id => {
+                changes.push(RefChange::Update(refname.clone(), *id))
+            }
+            Some(_) => {}
+        }
+    }
+    for refname in previous.keys() {
+        if !current.contains_key(refname) {
+            changes.push(RefChange::Delete(refname.clone()));
+        }
+    }
+    changes.sort_by(|a, b| a.refname().cmp(b.refname()));
+
+    if !changes.is_empty() && !dry_run {
+        let refspecs: Vec<String> = changes.iter().map(RefChange::refspec).collect();
+        repo.stupid().push_refspecs(remote_name, &refspecs)?;
+        state.insert(remote_name.to_string(), current);
+        write_mirror_state(repo, &state)?;
+    }
+
+    Ok(changes)
+}
+
+/// Map the names of the references to be mirrored to their ids.
+fn mirrored_refs(
+    repo: &git_repository::Repository,
+) -> Result<BTreeMap<String, git_repository::ObjectId>> {
+    let mut refs = BTreeMap::new();
+    let mut add_ref = |reference: git_repository::Reference| {
+        if let (Ok(refname), Some(id)) = (
+            reference.name().as_bstr().to_str(),
+            reference.target().try_id(),
+        ) {
+            refs.insert(refname.to_string(), id.to_owned());
+        }
+    };
+    for prefix in MIRRORED_PREFIXES {
+        for reference in repo.references()?.prefixed(prefix)?.filter_map(Result::ok) {
+            add_ref(reference);
+        }
+    }
+    if let Some(reference) = repo.try_find_reference(NOTES_REF)? {
+        add_ref(reference);
+    }
+    Ok(refs)
+}
+
+type MirrorState = BTreeMap<String, BTreeMap<String, git_repository::ObjectId>>;
+
+fn mirror_state_path(repo: &git_repository::Repository) -> PathBuf {
+    repo.common_dir().join(MIRROR_STATE_FILE)
+}
+
+fn read_mirror_state(repo: &git_repository::Repository) -> Result<MirrorState> {
+    let path = mirror_state_path(repo);
+    if !path.is_file() {
+        return Ok(MirrorState::new());
+    }
+    let state: BTreeMap<String, BTreeMap<String, String>> =
+        serde_json::from_slice(&std::fs::read(&path)?).context("reading mirror state")?;
+    state
+        .into_iter()
+        .map(|(remote_name, refs)| {
+            let refs = refs
+                .into_iter()
+                .map(|(refname, id)| {
+                    git_repository::ObjectId::from_hex(id.as_bytes())
+                        .map(|id| (refname, id))
+                        .context("reading mirror state")
+                })
+                .collect::<Result<_>>()?;
+            Ok((remote_name, refs))
+        })
+        .collect()
+}
+
+fn write_mirror_state(repo: &git_repository::Repository, state: &MirrorState) -> Result<()> {
+    let state: BTreeMap<&String, BTreeMap<&String, String>> = state
+        .iter()
+        .map(|(remote_name, refs)| {
+            let refs = refs
+                .iter()
+                .map(|(refname, id)| (refname, id.to_string()))
+                .collect();
+            (remote_name, refs)
+        })
+        .collect();
+    std::fs::write(
+        mirror_state_path(repo),
+        serde_json::to_string_pretty(&state)?,
+    )?;
+    Ok(())
+}
@@ -5,27 +5,36 @@
 //! Each subcommand is in its own module. The [`STGIT_COMMANDS`] slice constant contains
 //! a [`StGitCommand`] instance for each subcommand.
 
+pub(crate) mod apply_plan;
+pub(crate) mod backport;
 pub(crate) mod branch;
+pub(crate) mod bundle;
 pub(crate) mod clean;
 pub(crate) mod commit;
 pub(crate) mod completion;
 pub(crate) mod delete;
 pub(crate) mod diff;
+pub(crate) mod doctor;
+pub(crate) mod duplicate;
 pub(crate) mod edit;
 pub(crate) mod email;
 pub(crate) mod export;
+pub(crate) mod fetch_stack;
 pub(crate) mod files;
 pub(crate) mod float;
 pub(crate) mod fold;
+pub(crate) mod git;
 pub(crate) mod goto;
 pub(crate) mod hide;
 pub(crate) mod id;
 pub(crate) mod import;
 pub(crate) mod init;
 pub(crate) mod log;
+pub(crate) mod mirror;
 pub(crate) mod new;
 pub(crate) mod next;
 pub(crate) mod patches;
+pub(crate) mod patchwork;
 pub(crate) mod pick;
 pub(crate) mod pop;
 pub(crate) mod prev;
@@ -37,18 +46,28 @@ pub(crate) mod refresh;
 pub(crate) mod rename;
 pub(crate) mod repair;
 pub(crate) mod reset;
+pub(crate) mod resolve;
+pub(crate) mod selftest;
 pub(crate) mod series;
+pub(crate) mod shelve;
 pub(crate) mod show;
 pub(crate) mod sink;
 pub(crate) mod spill;
 pub(crate) mod squash;
+pub(crate) mod switch;
 pub(crate) mod sync;
 pub(crate) mod top;
 pub(crate) mod uncommit;
 pub(crate) mod undo;
 pub(crate) mod unhide;
+pub(crate) mod unshelve;
+pub(crate) mod verify;
 pub(crate) mod version;
 
+use anyhow::{anyhow, Result};
+
+use crate::ext::RepositoryExtended;
+
 /// Command categories for use in, e.g. man pages.
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub(crate) enum CommandCategory {
@@ -74,32 +93,119 @@ pub(crate) struct StGitCommand {
     pub run: fn(&clap::ArgMatches) -> anyhow::Result<()>,
 }
 
+/// Determine whether running a command with the given matches may modify the repository.
+///
+/// Commands in the inspection categories only read the repository, except for a few
+/// options and subcommands that record state. Likewise, some invocations of commands
+/// in the other categories only read the repository.
+fn is_mutating(command: &StGitCommand, matches: &clap::ArgMatches) -> bool {
+    match command.name {
+        "branch" => match matches.subcommand_name() {
+            Some("--list") => false,
+            Some(_) => true,
+            None => matches.contains_id("branch-any"),
+        },
+        "bundle" => matches.subcommand_name() != Some("create"),
+        "completion" | "selftest" | "version" => false,
+        "doctor" => matches.get_flag("fix"),
+        "email" => matches.subcommand_name() != Some("preview"),
+        "fetch-stack" => true,
+        "log" => matches.get_flag("clear"),
+        "mirror" => false,
+        "series" => matches.subcommand_name() == Some("init"),
+        _ => !matches!(
+            command.category,
+            CommandCategory::PatchInspection | CommandCategory::StackInspection
+        ),
+    }
+}
+
+/// Refuse to run commands that may modify the repository in read-only mode.
+///
+/// Read-only mode is enabled by setting the `STG_READONLY` environment variable to a
+/// value other than "0" or "false", or otherwise by the `stgit.readonly` configuration
+/// variable.
+pub(crate) fn check_readonly(command: &StGitCommand, matches: &clap::ArgMatches) -> Result<()> {
+    if !is_mutating(command, matches) {
+        return Ok(());
+    }
+
+    if let Some(source) = readonly_source() {
+        Err(anyhow!(
+            "`stg {}` may modify the repository and is not allowed in read-only mode; \
+             read-only mode is enabled by `{source}`",
+            command.name,
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Determine whether read-only mode is enabled.
+///
+/// Commands that are otherwise read-only use this to avoid recording incidental state.
+pub(crate) fn is_readonly() -> bool {
+    readonly_source().is_some()
+}
+
+/// Get the name of the environment or configuration variable enabling read-only mode.
+fn readonly_source() -> Option<&'static str> {
+    if let Some(value) = std::env::var_os("STG_READONLY") {
+        (!value.is_empty() && value != "0" && value != "false").then_some("STG_READONLY")
+    } else if let Ok(repo) = git_repository::Repository::open() {
+        repo.config_snapshot()
+            .boolean("stgit.readonly")
+            .unwrap_or(false)
+            .then_some("stgit.readonly")
+    } else {
+        None
+    }
+}
+
+/// Mirror the stacks after a command that may have modified the repository.
+///
+/// See [`mirror::auto_mirror()`].
+pub(crate) fn auto_mirror(command: &StGitCommand, matches: &clap::ArgMatches) {
+    if command.name != mirror::STGIT_COMMAND.name && is_mutating(command, matches) {
+        mirror::auto_mirror(matches);
+    }
+}
+
 /// Builtin [`StGitCommand`]'s.
 ///
 /// This is used in [`crate::main`] for command line argument parsing and
 /// eventual dispatch of a subcommand.
 pub(crate) const STGIT_COMMANDS: &[StGitCommand] = &[
+    apply_plan::STGIT_COMMAND,
+    backport::STGIT_COMMAND,
     branch::STGIT_COMMAND,
+    bundle::STGIT_COMMAND,
     clean::STGIT_COMMAND,
     commit::STGIT_COMMAND,
     completion::STGIT_COMMAND,
     delete::STGIT_COMMAND,
     diff::STGIT_COMMAND,
+    doctor::STGIT_COMMAND,
+    duplicate::STGIT_COMMAND,
     edit::STGIT_COMMAND,
     email::STGIT_COMMAND,
     export::STGIT_COMMAND,
+    fetch_stack::STGIT_COMMAND,
     files::STGIT_COMMAND,
     float::STGIT_COMMAND,
     fold::STGIT_COMMAND,
+    git::STGIT_COMMAND,
     goto::STGIT_COMMAND,
     hide::STGIT_COMMAND,
     id::STGIT_COMMAND,
     import::STGIT_COMMAND,
     init::STGIT_COMMAND,
     log::STGIT_COMMAND,
+    mirror::STGIT_COMMAND,
     new::STGIT_COMMAND,
     next::STGIT_COMMAND,
     patches::STGIT_COMMAND,
+    patchwork::STGIT_COMMAND,
     pick::STGIT_COMMAND,
     pop::STGIT_COMMAND,
     prev::STGIT_COMMAND,
@@ -111,15 +217,21 @@ pub(crate) const STGIT_COMMANDS: &[StGitCommand] = &[
     rename::STGIT_COMMAND,
     repair::STGIT_COMMAND,
     reset::STGIT_COMMAND,
+    resolve::STGIT_COMMAND,
+    selftest::STGIT_COMMAND,
     series::STGIT_COMMAND,
+    shelve::STGIT_COMMAND,
     show::STGIT_COMMAND,
     sink::STGIT_COMMAND,
     spill::STGIT_COMMAND,
     squash::STGIT_COMMAND,
+    switch::STGIT_COMMAND,
     sync::STGIT_COMMAND,
     top::STGIT_COMMAND,
     uncommit::STGIT_COMMAND,
     undo::STGIT_COMMAND,
     unhide::STGIT_COMMAND,
+    unshelve::STGIT_COMMAND,
+    verify::STGIT_COMMAND,
     version::STGIT_COMMAND,
 ];
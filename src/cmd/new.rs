@@ -4,7 +4,7 @@
 
 use std::path::PathBuf;
 
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 use clap::{Arg, ArgGroup, ArgMatches};
 
 use super::refresh;
@@ -167,14 +167,20 @@ fn run(matches: &ArgMatches) -> Result<()> {
         .or_else(|| matches.get_one::<PatchName>("name"))
         .cloned()
     {
-        if let Some(colliding_patchname) = stack.collides(&patchname) {
-            Err(anyhow!("patch `{colliding_patchname}` already exists"))
-        } else {
-            Ok(Some(patchname))
+        let disambiguated = stack.disambiguate_patchname(&patchname, None)?;
+        if disambiguated != patchname {
+            crate::print_info_message(
+                matches,
+                &format!(
+                    "patch name `{patchname}` differs only in case from an existing patch; \
+                     using `{disambiguated}`"
+                ),
+            );
         }
+        Some(disambiguated)
     } else {
-        Ok(None)
-    }?;
+        None
+    };
 
     let is_refreshing = matches.get_flag("refresh") || matches.contains_id("pathspecs");
 
@@ -201,6 +207,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
         patchedit::EditOutcome::Edited {
             new_patchname,
             new_commit_id,
+            ..
         } => (
             new_patchname
                 .or(patchname)
@@ -209,6 +216,10 @@ fn run(matches: &ArgMatches) -> Result<()> {
         ),
     };
 
+    if let Some(warning) = patchname.check_portability(&repo.config_snapshot())? {
+        crate::print_warning_message(matches, &warning);
+    }
+
     if let Some(template_path) = matches.get_one::<PathBuf>("save-template") {
         let patch_commit = repo.find_commit(commit_id)?;
         std::fs::write(template_path, patch_commit.message_raw()?)?;
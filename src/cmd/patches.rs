@@ -102,15 +102,15 @@ fn run(matches: &ArgMatches) -> Result<()> {
         return Err(anyhow!("no local changes and no paths specified"));
     }
 
-    let revs = stupid.rev_list(stack.base().id, stack.top().id, Some(&pathspecs))?;
+    let revs = stupid.rev_list(stack.base().id, stack.top()?.id, Some(&pathspecs))?;
 
     if diff_flag {
         // TODO: pager?
         let stdout = std::io::stdout();
         let mut stdout = stdout.lock();
-        let diff_opts = argset::get_diff_opts(matches, &repo.config_snapshot(), false, false);
+        let diff_opts = argset::get_diff_opts(matches, &repo.config_snapshot(), false, false)?;
         for patchname in stack.applied() {
-            let patch_commit = stack.get_patch_commit(patchname);
+            let patch_commit = stack.get_patch_commit(patchname)?;
             let parent_commit = patch_commit.get_parent_commit()?;
             if revs.contains(&patch_commit.id) {
                 write!(
@@ -133,7 +133,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
         }
     } else {
         for patchname in stack.applied() {
-            let patch_commit = stack.get_patch_commit(patchname);
+            let patch_commit = stack.get_patch_commit(patchname)?;
             if revs.contains(&patch_commit.id) {
                 println!("{patchname}");
             }
@@ -0,0 +1,124 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Minimal client for the Patchwork REST API.
+
+use anyhow::{anyhow, Context, Result};
+use bstr::ByteSlice;
+use serde::Deserialize;
+
+/// A series as described by the `/api/series/<id>/` endpoint.
+#[derive(Deserialize)]
+pub(super) struct Series {
+    pub(super) name: Option<String>,
+    pub(super) version: u64,
+    pub(super) patches: Vec<SeriesPatch>,
+}
+
+/// A patch entry of a [`Series`].
+#[derive(Deserialize)]
+pub(super) struct SeriesPatch {
+    pub(super) id: u64,
+    pub(super) name: String,
+    /// URL of the patch's mbox.
+    pub(super) mbox: String,
+    pub(super) web_url: Option<String>,
+}
+
+/// Connection to a Patchwork instance.
+pub(super) struct Client {
+    /// Base URL of the instance, without a trailing '/'.
+    url: String,
+    token: Option<String>,
+}
+
+impl Client {
+    /// Make client for the instance given by '--url' or `stgit.patchwork.url`.
+    pub(super) fn new(
+        matches: &clap::ArgMatches,
+        config: &git_repository::config::Snapshot,
+    ) -> Result<Self> {
+        let url = if let Some(url) = matches.get_one::<String>("url") {
+            url.clone()
+        } else if let Some(url) = config.string("stgit.patchwork.url") {
+            url.to_str_lossy().to_string()
+        } else {
+            return Err(anyhow!(
+                "no Patchwork instance specified; use `--url` or set `stgit.patchwork.url`"
+            ));
+        };
+        let token = config
+            .string("stgit.patchwork.token")
+            .map(|token| token.to_str_lossy().trim().to_string())
+            .filter(|token| !token.is_empty());
+        Ok(Self {
+            url: url.trim_end_matches('/').to_string(),
+            token,
+        })
+    }
+
+    /// Get the description of the series with the given id.
+    pub(super) fn series(&self, series_id: u64) -> Result<Series> {
+        let url = format!("{}/api/series/{series_id}/", self.url);
+        let content = request(&url, None, None)
+            .with_context(|| format!("fetching Patchwork series {series_id}"))?;
+        serde_json::from_slice(&content)
+            .with_context(|| format!("parsing Patchwork series {series_id}"))
+    }
+
+    /// Download the mbox of a patch.
+    pub(super) fn patch_mbox(&self, patch: &SeriesPatch) -> Result<Vec<u8>> {
+        request(&patch.mbox, None, None)
+            .with_context(|| format!("downloading Patchwork patch {}", patch.id))
+    }
+
+    /// Set the state of a patch, e.g. "accepted" or "superseded".
+    pub(super) fn set_state(&self, patch_id: u64, state: &str) -> Result<()> {
+        let token = self.token.as_deref().ok_or_else(|| {
+            anyhow!("no Patchwork API token configured; set `stgit.patchwork.token`")
+        })?;
+        let url = format!("{}/api/patches/{patch_id}/", self.url);
+        let body = serde_json::json!({ "state": state }).to_string();
+        request(&url, Some(body.as_bytes()), Some(token))
+            .with_context(|| format!("updating Patchwork patch {patch_id}"))?;
+        Ok(())
+    }
+}
+
+/// Perform a request, returning the response body.
+///
+/// A `PATCH` request with a JSON body is made when `body` is provided; otherwise a
+/// `GET` request is made.
+#[cfg(feature = "import-url")]
+fn request(url: &str, body: Option<&[u8]>, token: Option<&str>) -> Result<Vec<u8>> {
+    let mut handle = curl::easy::Easy::new();
+    handle.url(url)?;
+    handle.fail_on_error(true)?;
+    handle.follow_location(true)?;
+    let mut headers = curl::easy::List::new();
+    headers.append("Accept: application/json")?;
+    if let Some(token) = token {
+        headers.append(&format!("Authorization: Token {token}"))?;
+    }
+    if let Some(body) = body {
+        headers.append("Content-Type: application/json")?;
+        handle.custom_request("PATCH")?;
+        handle.post_fields_copy(body)?;
+    }
+    handle.http_headers(headers)?;
+
+    let mut content = Vec::new();
+    {
+        let mut transfer = handle.transfer();
+        transfer.write_function(|data| {
+            content.extend_from_slice(data);
+            Ok(data.len())
+        })?;
+        transfer.perform()?;
+    }
+    Ok(content)
+}
+
+#[cfg(not(feature = "import-url"))]
+fn request(_url: &str, _body: Option<&[u8]>, _token: Option<&str>) -> Result<Vec<u8>> {
+    Err(anyhow!("StGit not built with support for Patchwork"))
+}
@@ -0,0 +1,159 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg patchwork download` implementation.
+
+use anyhow::{anyhow, Context, Result};
+use bstr::{BString, ByteSlice};
+use clap::Arg;
+
+use super::api::Client;
+use crate::{
+    color::get_color_stdout,
+    ext::{RepositoryExtended, TimeExtended},
+    patch::{mailinfo::MailInfo, mbox::Mbox, meta, PatchName},
+    stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
+    stupid::Stupid,
+    wrap::Message,
+};
+
+pub(super) fn command() -> clap::Command {
+    clap::Command::new("download")
+        .about("Import a series from Patchwork")
+        .long_about(
+            "Import the patches of a Patchwork series as new patches on top of the \
+             current stack, in series order and with their original authors.\n\
+             \n\
+             A \"Link:\" trailer with each patch's Patchwork URL is added to the \
+             patch's message, and the patch's Patchwork id is recorded in the \
+             patch's metadata for use by `stg patchwork update`.",
+        )
+        .arg(
+            Arg::new("series-id")
+                .help("Id of the Patchwork series to download")
+                .value_name("series-id")
+                .required(true)
+                .value_parser(clap::value_parser!(u64)),
+        )
+        .arg(super::url_arg())
+        .arg(
+            Arg::new("no-link")
+                .long("no-link")
+                .help("Do not add \"Link:\" trailers to the patches")
+                .action(clap::ArgAction::SetTrue),
+        )
+}
+
+pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
+    let repo = git_repository::Repository::open()?;
+    let stack = Stack::from_branch(&repo, None, InitializationPolicy::AutoInitialize)?;
+    let stupid = repo.stupid();
+    let config = repo.config_snapshot();
+
+    repo.check_repository_state()?;
+    let statuses = stupid.statuses(None)?;
+    statuses.check_conflicts()?;
+    statuses.check_index_and_worktree_clean()?;
+    stack.check_head_top_mismatch()?;
+
+    let series_id = *matches
+        .get_one::<u64>("series-id")
+        .expect("series-id is a required argument");
+    let client = Client::new(matches, &config)?;
+    let series = client.series(series_id)?;
+    if series.patches.is_empty() {
+        return Err(anyhow!("Patchwork series {series_id} has no patches"));
+    }
+
+    let committer = repo.get_committer()?;
+    let default_author = repo.get_author()?;
+    let name_len_limit = PatchName::get_length_limit(&config);
+    let mut disallow: Vec<PatchName> = stack.all_patches().cloned().collect();
+    let new_patches = stupid.with_temp_index(|stupid_temp| {
+        let mut parent_id = stack.get_branch_head().id;
+        stupid_temp.read_tree(stack.get_branch_head().tree_id()?.detach())?;
+        let mut new_patches: Vec<(PatchName, git_repository::ObjectId)> = Vec::new();
+        for patch in &series.patches {
+            let mbox = client.patch_mbox(patch)?;
+            let raw = Mbox::new(mbox.as_slice(), false, true)
+                .next()
+                .transpose()?
+                .ok_or_else(|| anyhow!("Patchwork patch {} has an empty mbox", patch.id))?;
+            let info = MailInfo::parse(&raw);
+            let subject = info.subject.clone().unwrap_or_else(|| patch.name.clone());
+
+            stupid_temp
+                .apply_to_index(&info.diff)
+                .with_context(|| format!("applying Patchwork patch {} \"{subject}\"", patch.id))?;
+            let tree_id = stupid_temp.write_tree()?;
+
+            let author = git_repository::actor::Signature {
+                name: info
+                    .author_name
+                    .as_deref()
+                    .map_or_else(|| default_author.name.into(), BString::from),
+                email: info
+                    .author_email
+                    .as_deref()
+                    .map_or_else(|| default_author.email.into(), BString::from),
+                time: info
+                    .date
+                    .as_deref()
+                    .and_then(|date| git_repository::actor::Time::parse_time(date).ok())
+                    .unwrap_or(default_author.time),
+            };
+
+            let body = info.message.to_str_lossy();
+            let mut message = if body.trim().is_empty() {
+                format!("{subject}\n")
+            } else {
+                format!("{subject}\n\n{}\n", body.trim_end())
+            };
+            if let (false, Some(web_url)) = (matches.get_flag("no-link"), &patch.web_url) {
+                message = String::from_utf8(
+                    stupid_temp
+                        .interpret_trailers(message.as_bytes(), [("Link", web_url.as_str())])?,
+                )
+                .map_err(|_| anyhow!("could not decode message after adding trailers"))?;
+            }
+
+            let commit_id = repo.commit_ex(
+                &author,
+                committer,
+                &Message::from(message.as_str()),
+                tree_id,
+                [parent_id],
+            )?;
+
+            let mut patch_meta = meta::PatchMeta::default();
+            patch_meta.set(meta::PATCHWORK_ID, &patch.id.to_string());
+            patch_meta.write(&repo, commit_id)?;
+
+            let patchname =
+                PatchName::make(&message, true, name_len_limit).uniquify(&[], &disallow);
+            disallow.push(patchname.clone());
+            new_patches.push((patchname, commit_id));
+            parent_id = commit_id;
+        }
+        Ok(new_patches)
+    })?;
+
+    let description = if let Some(name) = series.name.as_deref() {
+        format!("{name} (v{})", series.version)
+    } else {
+        format!("series {series_id}")
+    };
+
+    stack
+        .setup_transaction()
+        .use_index_and_worktree(true)
+        .with_output_stream(get_color_stdout(matches))
+        .transact(|trans| {
+            for (patchname, commit_id) in &new_patches {
+                trans.new_applied(patchname, *commit_id)?;
+            }
+            Ok(())
+        })
+        .execute(&format!("patchwork download: {description}"))?;
+
+    Ok(())
+}
@@ -0,0 +1,62 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg patchwork` implementation.
+
+mod api;
+mod download;
+mod update;
+
+use anyhow::Result;
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "patchwork",
+    category: super::CommandCategory::StackManipulation,
+    make,
+    run,
+};
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Exchange patches with a Patchwork instance")
+        .long_about(
+            "Exchange patches with a Patchwork instance using its REST API.\n\
+             \n\
+             The `download` subcommand imports a series from Patchwork as new \
+             patches on top of the current stack. Each downloaded patch's Patchwork \
+             id is recorded in the patch's metadata, which follows the patch through \
+             refreshes and renames. The `update` subcommand uses the recorded ids to \
+             change the state of the corresponding Patchwork patches, e.g. to mark \
+             them superseded after a reroll of the series has been sent.\n\
+             \n\
+             The Patchwork instance is specified with the '--url' option or the \
+             \"stgit.patchwork.url\" configuration variable, e.g. \
+             \"https://patchwork.kernel.org\". Updating patches requires an API token, \
+             which is taken from the \"stgit.patchwork.token\" configuration \
+             variable.",
+        )
+        .subcommand_required(true)
+        .subcommand(download::command())
+        .subcommand(update::command())
+}
+
+fn run(matches: &clap::ArgMatches) -> Result<()> {
+    match matches.subcommand() {
+        Some(("download", sub_matches)) => download::dispatch(sub_matches),
+        Some(("update", sub_matches)) => update::dispatch(sub_matches),
+        _ => panic!("valid subcommand is expected"),
+    }
+}
+
+/// Make the '--url' argument shared by the subcommands.
+fn url_arg() -> clap::Arg {
+    clap::Arg::new("url")
+        .long("url")
+        .help("Use the Patchwork instance at <url>")
+        .long_help(
+            "Use the Patchwork instance at <url> instead of the one configured with \
+             \"stgit.patchwork.url\".",
+        )
+        .value_name("url")
+        .value_parser(clap::builder::NonEmptyStringValueParser::new())
+        .value_hint(clap::ValueHint::Url)
+}
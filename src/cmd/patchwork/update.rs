@@ -0,0 +1,99 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg patchwork update` implementation.
+
+use std::io::Write;
+
+use anyhow::{anyhow, Result};
+use clap::Arg;
+
+use super::api::Client;
+use crate::{
+    argset,
+    color::get_color_stdout,
+    ext::RepositoryExtended,
+    patch::{meta, patchrange, PatchName},
+    print_info_message,
+    stack::{InitializationPolicy, Stack, StackStateAccess},
+};
+
+pub(super) fn command() -> clap::Command {
+    clap::Command::new("update")
+        .about("Change the state of patches on Patchwork")
+        .long_about(
+            "Change the state of the Patchwork patches corresponding to the given \
+             patches, or to all patches in the stack when no patches are given. Only \
+             patches downloaded with `stg patchwork download` have corresponding \
+             Patchwork patches; other patches are ignored.\n\
+             \n\
+             The default state is \"superseded\", which is appropriate after a reroll \
+             of the series has been sent. Other common states are \"accepted\", \
+             \"rejected\", and \"changes-requested\", although the available states \
+             depend on the Patchwork instance.",
+        )
+        .arg(
+            Arg::new("patchranges-all")
+                .help("Patches whose Patchwork patches to update")
+                .value_name("patch")
+                .num_args(1..)
+                .value_parser(clap::value_parser!(patchrange::Specification)),
+        )
+        .arg(argset::branch_arg())
+        .arg(super::url_arg())
+        .arg(
+            Arg::new("state")
+                .long("state")
+                .short('s')
+                .help("Set the Patchwork patches to <state>")
+                .value_name("state")
+                .default_value("superseded")
+                .value_parser(clap::builder::NonEmptyStringValueParser::new()),
+        )
+}
+
+pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
+    let repo = git_repository::Repository::open()?;
+    let stack = Stack::from_branch(
+        &repo,
+        argset::get_one_str(matches, "branch"),
+        InitializationPolicy::AllowUninitialized,
+    )?;
+    let config = repo.config_snapshot();
+
+    let patchnames: Vec<PatchName> = if let Some(range_specs) =
+        matches.get_many::<patchrange::Specification>("patchranges-all")
+    {
+        patchrange::patches_from_specs(range_specs, &stack, patchrange::Allow::All)?
+    } else {
+        stack.all_patches().cloned().collect()
+    };
+
+    let mut updates: Vec<(PatchName, u64)> = Vec::new();
+    for patchname in patchnames {
+        let commit_id = stack.get_patch_commit_id(&patchname);
+        if let Some(patch_id) = meta::PatchMeta::read(&repo, commit_id)?.get(meta::PATCHWORK_ID) {
+            let patch_id = patch_id.parse::<u64>().map_err(|_| {
+                anyhow!("invalid Patchwork id `{patch_id}` recorded for `{patchname}`")
+            })?;
+            updates.push((patchname, patch_id));
+        }
+    }
+
+    if updates.is_empty() {
+        print_info_message(matches, "no patches with Patchwork ids to update");
+        return Ok(());
+    }
+
+    let state = argset::get_one_str(matches, "state").expect("state has a default value");
+    let client = Client::new(matches, &config)?;
+    let mut stdout = get_color_stdout(matches);
+    for (patchname, patch_id) in updates {
+        client.set_state(patch_id, state)?;
+        writeln!(
+            stdout,
+            "{patchname}: Patchwork patch {patch_id} set to {state}"
+        )?;
+    }
+
+    Ok(())
+}
@@ -15,7 +15,7 @@ use clap::Arg;
 use crate::{
     argset,
     color::get_color_stdout,
-    ext::{CommitExtended, RepositoryExtended},
+    ext::{CommitExtended, RepositoryExtended, SignatureExtended},
     patch::{patchrange, PatchName},
     revspec::{parse_branch_and_spec, parse_stgit_revision},
     stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
@@ -91,6 +91,27 @@ fn make() -> clap::Command {
                 .action(clap::ArgAction::SetTrue)
                 .conflicts_with_all(["fold", "update"]),
         )
+        .arg(
+            Arg::new("after")
+                .long("after")
+                .help("Place the unapplied patches after <patch>")
+                .long_help(
+                    "Place the picked patches in the unapplied list immediately after \
+                     <patch>, which must be unapplied. By default, picked patches are \
+                     placed at the top of the unapplied list.",
+                )
+                .value_name("patch")
+                .value_parser(PatchName::from_str)
+                .requires("noapply"),
+        )
+        .arg(
+            Arg::new("last")
+                .long("last")
+                .help("Place the unapplied patches at the end of the unapplied list")
+                .action(clap::ArgAction::SetTrue)
+                .requires("noapply")
+                .conflicts_with("after"),
+        )
         .arg(
             Arg::new("name")
                 .long("name")
@@ -109,19 +130,21 @@ fn make() -> clap::Command {
                 .value_parser(clap::value_parser!(PathBuf))
                 .conflicts_with_all(["fold", "update"]),
         )
-        .arg(argset::committer_date_is_author_date_arg())
+        .args(argset::author_args())
         .arg(
             Arg::new("fold")
                 .long("fold")
                 .help("Fold the commit object into the current patch")
-                .action(clap::ArgAction::SetTrue),
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["author", "authname", "authemail", "authdate"]),
         )
         .arg(
             Arg::new("update")
                 .long("update")
                 .help("Like fold but only update the current patch's files")
                 .action(clap::ArgAction::SetTrue)
-                .conflicts_with("fold"),
+                .conflicts_with("fold")
+                .conflicts_with_all(["author", "authname", "authemail", "authdate"]),
         )
         .arg(
             Arg::new("file")
@@ -149,6 +172,16 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
         return Err(crate::stack::Error::NoAppliedPatches.into());
     }
 
+    if let Some(after) = matches.get_one::<PatchName>("after") {
+        if !stack.has_patch(after) {
+            return Err(anyhow!("patch `{after}` does not exist"));
+        } else if !stack.is_unapplied(after) {
+            return Err(anyhow!(
+                "cannot place picked patches after `{after}`; it is not unapplied"
+            ));
+        }
+    }
+
     if !matches.get_flag("noapply") {
         repo.stupid()
             .statuses(None)?
@@ -184,8 +217,8 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
         if let Some(patches) = source_patches {
             patches
                 .iter()
-                .map(|pn| (Some(pn.clone()), ref_stack.get_patch_commit(pn).clone()))
-                .collect()
+                .map(|pn| Ok((Some(pn.clone()), ref_stack.get_patch_commit(pn)?.clone())))
+                .collect::<Result<_>>()?
         } else {
             let mut picks = Vec::new();
             for source in matches
@@ -207,7 +240,7 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
                             patchrange::Allow::VisibleWithAppliedBoundary,
                         )?;
                         for pn in &patchnames {
-                            picks.push((Some(pn.clone()), ref_stack.get_patch_commit(pn).clone()));
+                            picks.push((Some(pn.clone()), ref_stack.get_patch_commit(pn)?.clone()));
                         }
                     } else {
                         return Err(crate::revspec::Error::InvalidRevision(
@@ -238,6 +271,11 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
                 return Err(anyhow!("--parent can only be specified with one patch"));
             }
         }
+        if let Some(name) = matches.get_one::<PatchName>("name") {
+            if let Some(warning) = name.check_portability(&repo.config_snapshot())? {
+                crate::print_warning_message(matches, &warning);
+            }
+        }
         let opt_parent = if let Some(parent_committish) = matches.get_one::<String>("parent") {
             let commit = parse_stgit_revision(
                 stack.repo,
@@ -378,7 +416,7 @@ fn pick_picks(
             commit_ref.message.to_str_lossy().to_string()
         };
         let message = &crate::wrap::Message::String(message);
-        let author = commit.author_strict()?;
+        let author = commit.author_strict()?.override_author(matches);
         let default_committer = stack.repo.get_committer()?;
         let committer = if matches.get_flag("committer-date-is-author-date") {
             let mut committer = default_committer.to_owned();
@@ -413,10 +451,23 @@ fn pick_picks(
         .setup_transaction()
         .with_output_stream(get_color_stdout(matches))
         .use_index_and_worktree(true)
+        .committer_date_is_author_date(matches.get_flag("committer-date-is-author-date"))
         .transact(|trans| {
             let mut to_push = Vec::new();
+            let insert_pos = if let Some(after) = matches.get_one::<PatchName>("after") {
+                trans
+                    .unapplied()
+                    .iter()
+                    .position(|pn| pn == after)
+                    .expect("after patch is unapplied")
+                    + 1
+            } else if matches.get_flag("last") {
+                trans.unapplied().len()
+            } else {
+                0
+            };
             for (i, (patchname, commit_id)) in new_patches.iter().enumerate() {
-                trans.new_unapplied(patchname, *commit_id, i)?;
+                trans.new_unapplied(patchname, *commit_id, insert_pos + i)?;
                 to_push.push(patchname);
             }
             if !matches.get_flag("noapply") {
@@ -34,12 +34,20 @@ fn make() -> clap::Command {
              while pushing a patch, the conflicts are written to the work tree \
              and the push command halts. Conflicts may then be resolved using \
              the normal Git methods, or alternatively the push may be undone \
-             using 'stg undo'.",
+             using 'stg undo'.\n\
+             \n\
+             With '--until-conflict', patches are pushed one by one until a patch \
+             that would result in merge conflicts is reached. That patch and any \
+             patches after it are left unapplied, the patches pushed so far remain \
+             applied, and the command completes successfully with a summary of \
+             where it stopped. This is useful for pushing as much of the series as \
+             possible, e.g. after a rebase.",
         )
         .override_usage(
             "stg push [OPTIONS] [patch]...\n       \
              stg push [OPTIONS] -n <number>\n       \
-             stg push [OPTIONS] --all",
+             stg push [OPTIONS] --all\n       \
+             stg push [OPTIONS] --until-conflict [patch]...",
         )
         .arg(
             Arg::new("patchranges-unapplied")
@@ -57,6 +65,21 @@ fn make() -> clap::Command {
                 .action(clap::ArgAction::SetTrue)
                 .conflicts_with("number"),
         )
+        .arg(
+            Arg::new("until-conflict")
+                .long("until-conflict")
+                .help("Push patches until the first conflicting patch")
+                .long_help(
+                    "Push patches one by one, stopping cleanly before the first \
+                     patch that would result in merge conflicts.\n\
+                     \n\
+                     All unapplied patches are pushed unless patches are \
+                     specified, e.g. '..<patch>' to push up to and including \
+                     <patch>.",
+                )
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["all", "number", "noapply", "set-tree", "merged"]),
+        )
         .arg(
             Arg::new("number")
                 .long("number")
@@ -110,6 +133,7 @@ fn make() -> clap::Command {
         .arg(argset::keep_arg())
         .arg(argset::merged_arg())
         .arg(argset::committer_date_is_author_date_arg())
+        .arg(argset::stable_ids_arg())
         .arg(argset::push_conflicts_arg())
 }
 
@@ -146,7 +170,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
         )?
     } else if stack.unapplied().is_empty() {
         return Err(anyhow!("no unapplied patches"));
-    } else if matches.get_flag("all") {
+    } else if matches.get_flag("all") || matches.get_flag("until-conflict") {
         stack.unapplied().to_vec()
     } else if let Some(number) = opt_number {
         let num_unapplied = stack.unapplied().len();
@@ -176,6 +200,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
     let settree_flag = matches.get_flag("set-tree");
     let merged_flag = matches.get_flag("merged");
     let keep_flag = matches.get_flag("keep");
+    let until_conflict_flag = matches.get_flag("until-conflict");
 
     repo.check_repository_state()?;
     let statuses = stupid.statuses(None)?;
@@ -194,6 +219,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
         .use_index_and_worktree(true)
         .allow_push_conflicts(allow_push_conflicts)
         .committer_date_is_author_date(matches.get_flag("committer-date-is-author-date"))
+        .stable_ids(matches.get_flag("stable-ids"))
         .with_output_stream(get_color_stdout(matches))
         .transact(|trans| {
             if settree_flag {
@@ -212,6 +238,8 @@ fn run(matches: &ArgMatches) -> Result<()> {
                         .cloned(),
                 );
                 trans.reorder_patches(None, Some(&unapplied), None)
+            } else if until_conflict_flag {
+                trans.push_patches_until_conflict(&patches)
             } else {
                 trans.push_patches(&patches, merged_flag)
             }
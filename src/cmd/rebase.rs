@@ -11,6 +11,7 @@ use clap::{Arg, ArgMatches};
 use crate::{
     argset,
     color::get_color_stdout,
+    editor,
     ext::RepositoryExtended,
     patch::{patchedit, PatchName},
     print_info_message,
@@ -77,6 +78,7 @@ fn make() -> clap::Command {
              empty after the rebase operation.",
         ))
         .arg(argset::committer_date_is_author_date_arg())
+        .arg(argset::stable_ids_arg())
         .arg(
             Arg::new("autostash")
                 .long("autostash")
@@ -90,6 +92,13 @@ fn make() -> clap::Command {
                 .action(clap::ArgAction::SetTrue),
         )
         .arg(argset::push_conflicts_arg())
+        .arg(argset::editor_arg().long_help(
+            "Use <cmd> as the editor for this invocation, both for the interactive \
+             instructions and for editing patches. This takes precedence over the \
+             `GIT_SEQUENCE_EDITOR` and `GIT_EDITOR` environment variables and the \
+             `sequence.editor`, `stgit.editor`, and `core.editor` configuration \
+             variables.",
+        ))
 }
 
 fn run(matches: &ArgMatches) -> Result<()> {
@@ -207,6 +216,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
             .use_index_and_worktree(true)
             .allow_push_conflicts(allow_push_conflicts)
             .committer_date_is_author_date(committer_date_is_author_date)
+            .stable_ids(matches.get_flag("stable-ids"))
             .with_output_stream(get_color_stdout(matches))
             .transact(|trans| trans.push_patches(&applied, check_merged))
             .execute("rebase (reapply)")?;
@@ -259,9 +269,9 @@ enum Action {
     Delete,
 }
 
-fn interactive_pushback(
-    stack: Stack,
-    repo: &git_repository::Repository,
+fn interactive_pushback<'repo>(
+    stack: Stack<'repo>,
+    repo: &'repo git_repository::Repository,
     config: &git_repository::config::Snapshot,
     matches: &ArgMatches,
     previously_applied: &[PatchName],
@@ -277,10 +287,14 @@ fn interactive_pushback(
     let filename = ".stgit-rebase-interactive.txt";
     std::fs::write(
         filename,
-        make_instructions_template(&stack, previously_applied),
+        make_instructions_template(&stack, previously_applied)?,
     )?;
 
-    let buf = patchedit::call_editor(filename, config)?;
+    let buf = editor::call_editor(
+        filename,
+        &editor::get_sequence_editor(matches, config)?,
+        config,
+    )?;
     let buf = buf
         .to_str()
         .map_err(|_| anyhow!("`{filename}` is not valid UTF-8"))?;
@@ -347,12 +361,16 @@ fn interactive_pushback(
             Action::Edit => {
                 let dummy_edit_command = clap::Command::new("dummy-edit");
                 let dummy_edit_command = patchedit::add_args(dummy_edit_command, false, false);
+                let mut edit_args = vec!["dummy-edit", "--edit", "--diff"];
+                if let Some(editor) = argset::get_one_str(matches, "editor") {
+                    edit_args.extend(["--editor", editor]);
+                }
                 let edit_matches = dummy_edit_command
-                    .try_get_matches_from(["dummy-edit", "--edit", "--diff"])
+                    .try_get_matches_from(edit_args)
                     .expect("dummy command has valid arguments");
                 match patchedit::EditBuilder::default()
                     .original_patchname(Some(patchname))
-                    .existing_patch_commit(stack.get_patch_commit(patchname))
+                    .existing_patch_commit(stack.get_patch_commit(patchname)?)
                     .allow_diff_edit(true)
                     .edit(&stack, repo, &edit_matches)?
                 {
@@ -360,11 +378,13 @@ fn interactive_pushback(
                     patchedit::EditOutcome::Edited {
                         new_patchname,
                         new_commit_id,
+                        ..
                     } => {
                         if new_patchname.is_some() || new_commit_id.is_some() {
                             stack = stack
                                 .setup_transaction()
                                 .committer_date_is_author_date(committer_date_is_author_date)
+                                .stable_ids(matches.get_flag("stable-ids"))
                                 .with_output_stream(get_color_stdout(matches))
                                 .transact(|trans| {
                                     let patchname =
@@ -422,10 +442,14 @@ fn interactive_pushback(
                 let dummy_squash_command = patchedit::add_args(dummy_squash_command, true, false);
                 let squash_matches = match instruction.action {
                     Action::Squash => {
-                        dummy_squash_command.try_get_matches_from(["dummy-squash", "--edit"])
+                        let mut squash_args = vec!["dummy-squash", "--edit"];
+                        if let Some(editor) = argset::get_one_str(matches, "editor") {
+                            squash_args.extend(["--editor", editor]);
+                        }
+                        dummy_squash_command.try_get_matches_from(squash_args)
                     }
                     Action::Fixup => {
-                        let commit = stack.get_patch_commit(target_patchname);
+                        let commit = stack.get_patch_commit(target_patchname)?;
                         let message = commit.message_raw()?.to_str().map_err(|_| {
                             anyhow!("fixup target patch `{target_patchname}` has non-UTF-8 message")
                         })?;
@@ -480,6 +504,7 @@ fn interactive_pushback(
         .use_index_and_worktree(true)
         .allow_push_conflicts(allow_push_conflicts)
         .committer_date_is_author_date(committer_date_is_author_date)
+        .stable_ids(matches.get_flag("stable-ids"))
         .with_output_stream(get_color_stdout(matches))
         .transact(|trans| trans.push_patches(&to_push, check_merged))
         .execute("rebase (reapply)")?;
@@ -487,7 +512,7 @@ fn interactive_pushback(
     Ok(())
 }
 
-fn make_instructions_template(stack: &Stack, previously_applied: &[PatchName]) -> String {
+fn make_instructions_template(stack: &Stack, previously_applied: &[PatchName]) -> Result<String> {
     let name_width = stack.all_patches().map(PatchName::len).max().unwrap();
     let mut template = String::with_capacity(4096);
     let mut found_apply_boundary = false;
@@ -496,7 +521,7 @@ fn make_instructions_template(stack: &Stack, previously_applied: &[PatchName]) -
             writeln!(template, "{INTERACTIVE_APPLY_LINE}").unwrap();
             found_apply_boundary = true;
         }
-        let commit = stack.get_patch_commit(patchname);
+        let commit = stack.get_patch_commit(patchname)?;
         let subject = commit
             .message()
             .map(|message_ref| message_ref.title.to_str_lossy())
@@ -507,7 +532,7 @@ fn make_instructions_template(stack: &Stack, previously_applied: &[PatchName]) -
         writeln!(template, "{INTERACTIVE_APPLY_LINE}").unwrap();
     }
     template.push_str(INTERACTIVE_HELP_LINES);
-    template
+    Ok(template)
 }
 
 fn parse_instructions(buf: &str) -> Result<Vec<Instruction>> {
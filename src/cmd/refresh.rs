@@ -3,12 +3,14 @@
 //! `stg refresh` implementation.
 
 use std::{
+    collections::{HashMap, HashSet},
     path::{Path, PathBuf},
     rc::Rc,
     str::FromStr,
 };
 
 use anyhow::{anyhow, Result};
+use bstr::ByteSlice;
 use clap::{Arg, ArgGroup, ArgMatches, ValueHint};
 use indexmap::IndexSet;
 
@@ -18,6 +20,7 @@ use crate::{
     ext::{CommitExtended, RepositoryExtended, SignatureExtended},
     hook::run_pre_commit_hook,
     patch::{patchedit, PatchName},
+    print_info_message,
     stack::{Error, InitializationPolicy, Stack, StackAccess, StackStateAccess},
     stupid::{
         status::{Status, StatusEntryKind, StatusOptions, Statuses},
@@ -62,7 +65,22 @@ fn make() -> clap::Command {
              separate entry in the patch stack log; this means that one \
              undo step will undo the merge between the other patch and \
              the temp patch, and two undo steps will additionally get \
-             rid of the temp patch.",
+             rid of the temp patch.\n\
+             \n\
+             With '--only-owned', only the worktree changes that modify or \
+             adjoin lines introduced by the topmost patch are refreshed into \
+             the topmost patch. Changes to code introduced by other patches or \
+             present in the stack base are left in the worktree, where they \
+             may be refreshed into their respective patches later.\n\
+             \n\
+             By default, the refreshed patch keeps its author date and gets the \
+             current time as its committer date. The '--when' option, or the \
+             \"stgit.refresh.when\" configuration variable, selects other date \
+             handling: \"committer\" keeps both the author and committer dates of \
+             the patch, \"now\" sets both dates to the current time, and any other \
+             value is a commit from which both dates are taken. Keeping dates stable \
+             avoids churn in tools that compare patch versions, such as \
+             git-range-diff(1) and Patchwork.",
         )
         .arg(
             Arg::new("pathspecs")
@@ -104,6 +122,18 @@ fn make() -> clap::Command {
                 )
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("only-owned")
+                .long("only-owned")
+                .help("Only refresh changes to lines introduced by the top patch")
+                .long_help(
+                    "Only refresh the worktree hunks that modify or adjoin lines \
+                     introduced by the topmost patch. All other hunks are left in the \
+                     worktree. The index must be clean when using this option.",
+                )
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["index", "update", "force", "patch", "submodules"]),
+        )
         .arg(argset::push_conflicts_arg())
         .arg(
             Arg::new("patch")
@@ -115,6 +145,27 @@ fn make() -> clap::Command {
                 .value_hint(ValueHint::Other)
                 .value_parser(PatchName::from_str),
         )
+        .arg(
+            Arg::new("when")
+                .long("when")
+                .help("Set patch dates from <when>: author, committer, now, or a commit")
+                .long_help(
+                    "Select how the dates of the refreshed patch are set. With \
+                     \"author\", the default, the author date is kept and the \
+                     committer date is the current time. With \"committer\", both the \
+                     author and committer dates are kept. With \"now\", both dates are \
+                     set to the current time. Any other value is taken as a commit \
+                     whose author and committer dates are used.\n\
+                     \n\
+                     The default may be changed with the \"stgit.refresh.when\" \
+                     configuration variable.",
+                )
+                .num_args(1)
+                .value_name("when")
+                .value_hint(ValueHint::Other)
+                .value_parser(clap::builder::NonEmptyStringValueParser::new())
+                .conflicts_with_all(["authdate", "committer-date-is-author-date"]),
+        )
         .arg(
             Arg::new("annotate")
                 .long("annotate")
@@ -175,11 +226,18 @@ fn run(matches: &ArgMatches) -> Result<()> {
         return Err(Error::NoAppliedPatches.into());
     };
 
-    let tree_id = assemble_refresh_tree(
-        &stack,
-        matches,
-        matches.get_flag("update").then_some(&patchname),
-    )?;
+    let tree_id = if matches.get_flag("only-owned") {
+        assemble_owned_refresh_tree(&stack, matches, &patchname)?
+    } else {
+        assemble_refresh_tree(
+            &stack,
+            matches,
+            matches.get_flag("update").then_some(&patchname),
+        )?
+    };
+
+    let (author_time, committer_time) =
+        refresh_times(&repo, matches, stack.get_patch_commit(&patchname)?)?;
 
     let mut log_msg = "refresh ".to_string();
     let opt_annotate = matches.get_one::<String>("annotate");
@@ -227,7 +285,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
                     trans.push_patches(&[&temp_patchname], false)?;
                 }
 
-                let temp_commit = trans.get_patch_commit(&temp_patchname);
+                let temp_commit = trans.get_patch_commit(&temp_patchname)?;
 
                 let mut to_pop = to_pop;
                 let top_name = to_pop.pop();
@@ -235,8 +293,10 @@ fn run(matches: &ArgMatches) -> Result<()> {
 
                 let (new_patchname, new_commit_id) = match patchedit::EditBuilder::default()
                     .original_patchname(Some(&patchname))
-                    .existing_patch_commit(trans.get_patch_commit(&patchname))
+                    .existing_patch_commit(trans.get_patch_commit(&patchname)?)
                     .override_tree_id(temp_commit.tree_id()?.detach())
+                    .override_author_time(author_time)
+                    .override_committer_time(committer_time)
                     .allow_diff_edit(false)
                     .allow_implicit_edit(false)
                     .allow_template_save(false)
@@ -245,6 +305,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
                     patchedit::EditOutcome::Edited {
                         new_patchname,
                         new_commit_id,
+                        ..
                     } => (new_patchname, new_commit_id),
                     patchedit::EditOutcome::TemplateSaved(_) => {
                         panic!("not allowed for refresh")
@@ -278,8 +339,8 @@ fn run(matches: &ArgMatches) -> Result<()> {
                 // but without a worktree to spill conflicts to; so if the simple
                 // merge fails, the refresh must be aborted.
 
-                let patch_commit = trans.get_patch_commit(&patchname);
-                let temp_commit = trans.get_patch_commit(&temp_patchname);
+                let patch_commit = trans.get_patch_commit(&patchname)?;
+                let temp_commit = trans.get_patch_commit(&temp_patchname)?;
                 let base = temp_commit.get_parent_commit()?.tree_id()?.detach();
                 let ours = patch_commit.tree_id()?.detach();
                 let theirs = temp_commit.tree_id()?.detach();
@@ -295,8 +356,10 @@ fn run(matches: &ArgMatches) -> Result<()> {
                 })? {
                     let (new_patchname, new_commit_id) = match patchedit::EditBuilder::default()
                         .original_patchname(Some(&patchname))
-                        .existing_patch_commit(trans.get_patch_commit(&patchname))
+                        .existing_patch_commit(trans.get_patch_commit(&patchname)?)
                         .override_tree_id(tree_id)
+                        .override_author_time(author_time)
+                        .override_committer_time(committer_time)
                         .allow_diff_edit(false)
                         .allow_template_save(false)
                         .edit(trans, &repo, matches)?
@@ -304,6 +367,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
                         patchedit::EditOutcome::Edited {
                             new_patchname,
                             new_commit_id,
+                            ..
                         } => (new_patchname, new_commit_id),
                         patchedit::EditOutcome::TemplateSaved(_) => {
                             panic!("not allowed for refresh")
@@ -342,6 +406,45 @@ fn run(matches: &ArgMatches) -> Result<()> {
     Ok(())
 }
 
+/// Determine the author and committer times for the refreshed patch.
+///
+/// The times are determined by the '--when' option or the `stgit.refresh.when`
+/// configuration variable. A `None` time means the edit's default is used, i.e. the
+/// patch's existing author time and the current time for the committer.
+fn refresh_times(
+    repo: &git_repository::Repository,
+    matches: &ArgMatches,
+    patch_commit: &git_repository::Commit<'_>,
+) -> Result<(
+    Option<git_repository::actor::Time>,
+    Option<git_repository::actor::Time>,
+)> {
+    let when = if let Some(when) = matches.get_one::<String>("when") {
+        when.clone()
+    } else if let Some(when) = repo.config_snapshot().string("stgit.refresh.when") {
+        when.to_str_lossy().trim().to_string()
+    } else {
+        return Ok((None, None));
+    };
+
+    match when.as_str() {
+        "" | "author" => Ok((None, None)),
+        "committer" => Ok((None, Some(patch_commit.committer()?.time))),
+        "now" => Ok((Some(repo.get_committer()?.time), None)),
+        spec => {
+            let commit = crate::revspec::parse_stgit_revision(repo, Some(spec), None)
+                .and_then(|object| Ok(object.try_into_commit()?))
+                .map_err(|_| {
+                    anyhow!(
+                        "invalid refresh date source `{spec}`: \
+                         not author, committer, now, or a commit"
+                    )
+                })?;
+            Ok((Some(commit.author()?.time), Some(commit.committer()?.time)))
+        }
+    }
+}
+
 fn determine_refresh_paths(
     stupid: &StupidContext,
     statuses: &Statuses,
@@ -447,7 +550,9 @@ pub(crate) fn assemble_refresh_tree(
         assert!(!is_path_limiting);
         IndexSet::new()
     } else {
-        let maybe_patch_commit = limit_to_patchname.map(|pn| stack.get_patch_commit(pn));
+        let maybe_patch_commit = limit_to_patchname
+            .map(|pn| stack.get_patch_commit(pn))
+            .transpose()?;
         let submodules_flag = matches.get_flag("submodules");
         let nosubmodules_flag = matches.get_flag("no-submodules");
         let use_submodules = if !submodules_flag && !nosubmodules_flag {
@@ -488,3 +593,210 @@ pub(crate) fn assemble_refresh_tree(
 
     Ok(tree_id)
 }
+
+/// Assemble the refresh tree from only the worktree hunks owned by the given patch.
+///
+/// A hunk is owned by the patch if any of the lines it modifies, or the lines
+/// immediately surrounding it, were introduced by the patch. The owned hunks are
+/// staged in the index while all other hunks are left in the worktree.
+fn assemble_owned_refresh_tree(
+    stack: &Stack,
+    matches: &ArgMatches,
+    patchname: &PatchName,
+) -> Result<git_repository::ObjectId> {
+    let stupid = stack.repo.stupid();
+    let opt_pathspecs = matches.get_many::<PathBuf>("pathspecs");
+
+    let mut status_opts = StatusOptions::default();
+    if let Some(pathspecs) = opt_pathspecs.clone() {
+        status_opts.pathspecs(pathspecs);
+    }
+    let statuses = stupid.statuses(Some(&status_opts))?;
+    statuses.check_conflicts()?;
+    if statuses
+        .iter()
+        .any(|entry| !matches!(entry.index_status(), Status::Unmodified))
+    {
+        return Err(anyhow!(
+            "the index is dirty; `--only-owned` only refreshes worktree changes"
+        ));
+    }
+
+    let patch_commit = stack.get_patch_commit(patchname)?;
+    let patch_diff = stupid.diff_tree_patch(
+        patch_commit.get_parent_commit()?.tree_id()?.detach(),
+        patch_commit.tree_id()?.detach(),
+        <Option<Vec<&str>>>::None,
+        false,
+        ["-U0"],
+    )?;
+    let owned_lines = introduced_lines(&patch_diff);
+
+    let worktree_diff = stupid
+        .diff_index_worktree_zero(stack.get_branch_head().tree_id()?.detach(), opt_pathspecs)?;
+    let (owned_diff, num_foreign) = filter_owned_hunks(&worktree_diff, &owned_lines);
+
+    if num_foreign > 0 {
+        print_info_message(
+            matches,
+            &format!(
+                "leaving {num_foreign} hunk{} not owned by `{patchname}` in the worktree",
+                if num_foreign == 1 { "" } else { "s" }
+            ),
+        );
+    }
+
+    if !owned_diff.is_empty() {
+        stupid.apply_unidiff_zero_to_index(&owned_diff)?;
+    }
+
+    if !matches.get_flag("no-verify") {
+        run_pre_commit_hook(stack.repo, matches.get_flag("edit"))?;
+    }
+
+    stupid.write_tree()
+}
+
+/// Parse the path from a `--- a/<path>` or `+++ b/<path>` diff header line.
+///
+/// Returns `None` for `/dev/null`. Quoted paths are left quoted.
+fn diff_header_path(line: &[u8]) -> Option<Vec<u8>> {
+    let path = line[4..].trim_end_with(|c| c == '\n' || c == '\r' || c == '\t');
+    if path == b"/dev/null" {
+        return None;
+    }
+    let (quote, path) = if let Some(path) = path.strip_prefix(b"\"") {
+        (&b"\""[..], path)
+    } else {
+        (&b""[..], path)
+    };
+    let path = path.get(2..).unwrap_or_default();
+    Some([quote, path].concat())
+}
+
+/// Parse a `@@ -<start>,<count> +<start>,<count> @@` hunk header.
+///
+/// Returns the old start and count, the new start and count, and the remainder of the
+/// line following the closing `@@`.
+fn parse_hunk_header(line: &[u8]) -> Option<(usize, usize, usize, usize, &[u8])> {
+    let rest = line.strip_prefix(b"@@ -")?;
+    let end = rest.find(b" @@")?;
+    let (ranges, tail) = (rest[..end].to_str().ok()?, &rest[end + 3..]);
+    let (old, new) = ranges.split_once(" +")?;
+    let parse_range = |range: &str| -> Option<(usize, usize)> {
+        if let Some((start, count)) = range.split_once(',') {
+            Some((start.parse().ok()?, count.parse().ok()?))
+        } else {
+            Some((range.parse().ok()?, 1))
+        }
+    };
+    let (old_start, old_count) = parse_range(old)?;
+    let (new_start, new_count) = parse_range(new)?;
+    Some((old_start, old_count, new_start, new_count, tail))
+}
+
+/// Get the line numbers introduced by a zero-context diff, by path.
+fn introduced_lines(diff: &[u8]) -> HashMap<Vec<u8>, HashSet<usize>> {
+    let mut introduced: HashMap<Vec<u8>, HashSet<usize>> = HashMap::new();
+    let mut path: Option<Vec<u8>> = None;
+    for line in diff.lines_with_terminator() {
+        if line.starts_with(b"+++ ") {
+            path = diff_header_path(line);
+        } else if let (Some(path), Some((_, _, new_start, new_count, _))) =
+            (path.as_ref(), parse_hunk_header(line))
+        {
+            introduced
+                .entry(path.clone())
+                .or_default()
+                .extend(new_start..new_start + new_count);
+        }
+    }
+    introduced
+}
+
+/// Filter a zero-context diff to only the hunks that touch or adjoin owned lines.
+///
+/// Returns the filtered diff, suitable for `git apply --unidiff-zero`, and the number
+/// of hunks that were filtered out.
+fn filter_owned_hunks(
+    diff: &[u8],
+    owned_lines: &HashMap<Vec<u8>, HashSet<usize>>,
+) -> (Vec<u8>, usize) {
+    struct Hunk<'a> {
+        header: (usize, usize, usize, usize, &'a [u8]),
+        body: Vec<&'a [u8]>,
+    }
+
+    struct FileDiff<'a> {
+        header: Vec<&'a [u8]>,
+        path: Option<Vec<u8>>,
+        hunks: Vec<Hunk<'a>>,
+    }
+
+    let mut files: Vec<FileDiff> = Vec::new();
+    for line in diff.lines_with_terminator() {
+        if line.starts_with(b"diff --git ") {
+            files.push(FileDiff {
+                header: vec![line],
+                path: None,
+                hunks: Vec::new(),
+            });
+        } else if let Some(file) = files.last_mut() {
+            if let Some(header) = parse_hunk_header(line) {
+                file.hunks.push(Hunk {
+                    header,
+                    body: Vec::new(),
+                });
+            } else if let Some(hunk) = file.hunks.last_mut() {
+                hunk.body.push(line);
+            } else {
+                if line.starts_with(b"--- ") || (line.starts_with(b"+++ ") && file.path.is_none()) {
+                    file.path = diff_header_path(line);
+                }
+                file.header.push(line);
+            }
+        }
+    }
+
+    let no_lines = HashSet::new();
+    let mut filtered: Vec<u8> = Vec::new();
+    let mut num_foreign = 0;
+    for file in &files {
+        let owned = file
+            .path
+            .as_ref()
+            .and_then(|path| owned_lines.get(path))
+            .unwrap_or(&no_lines);
+        let mut foreign_offset: isize = 0;
+        let mut file_filtered: Vec<u8> = Vec::new();
+        for hunk in &file.hunks {
+            let (old_start, old_count, new_start, new_count, tail) = hunk.header;
+            let mut surrounding = if old_count > 0 {
+                old_start.saturating_sub(1)..=old_start + old_count
+            } else {
+                old_start..=old_start + 1
+            };
+            if surrounding.any(|line_num| owned.contains(&line_num)) {
+                let new_start = (new_start as isize - foreign_offset).max(0);
+                file_filtered.extend_from_slice(
+                    format!("@@ -{old_start},{old_count} +{new_start},{new_count} @@").as_bytes(),
+                );
+                file_filtered.extend_from_slice(tail);
+                hunk.body
+                    .iter()
+                    .for_each(|line| file_filtered.extend_from_slice(line));
+            } else {
+                foreign_offset += new_count as isize - old_count as isize;
+                num_foreign += 1;
+            }
+        }
+        if !file_filtered.is_empty() {
+            file.header
+                .iter()
+                .for_each(|line| filtered.extend_from_slice(line));
+            filtered.extend(file_filtered);
+        }
+    }
+
+    (filtered, num_foreign)
+}
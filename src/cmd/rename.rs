@@ -63,9 +63,23 @@ fn run(matches: &ArgMatches) -> Result<()> {
         return Err(Error::NoAppliedPatches.into());
     };
 
-    if old_patchname.collides(&new_patchname) {
+    if old_patchname == new_patchname {
         return Err(anyhow!("patch `{old_patchname}` already exists"));
     }
+    let disambiguated = stack.disambiguate_patchname(&new_patchname, Some(&old_patchname))?;
+    if disambiguated != new_patchname {
+        crate::print_info_message(
+            matches,
+            &format!(
+                "patch name `{new_patchname}` differs only in case from an existing patch; \
+                 using `{disambiguated}`"
+            ),
+        );
+    }
+    let new_patchname = disambiguated;
+    if let Some(warning) = new_patchname.check_portability(&repo.config_snapshot())? {
+        crate::print_warning_message(matches, &warning);
+    }
 
     stack
         .setup_transaction()
@@ -9,6 +9,7 @@ use crate::{
     color::get_color_stdout,
     ext::RepositoryExtended,
     patch::patchrange,
+    revspec::parse_stgit_revision,
     stack::{InitializationPolicy, Stack, StackState},
     stupid::Stupid,
 };
@@ -29,17 +30,23 @@ fn make() -> clap::Command {
              \n\
              The state is specified with a commit id from the stack log, which may be \
              viewed with 'stg log'. Patch name arguments may optionally be provided to \
-             limit which patches are reset.",
+             limit which patches are reset.\n\
+             \n\
+             A stack state may also be selected by date, either with '--before' or \
+             with a '{state}@{<date>}' revision such as '{state}@{2 hours ago}'. The \
+             most recent stack state recorded at or before the date is used. Any date \
+             format accepted by git may be used, including relative dates.",
         )
         .override_usage(
             "stg reset [--hard] [<committish> [<patchname>...]]\n       \
+             stg reset [--hard] --before <date>\n       \
              stg reset --hard",
         )
         .trailing_var_arg(true)
         .arg(
             Arg::new("committish")
                 .help("Stack state committish")
-                .required_unless_present("hard"),
+                .required_unless_present_any(["hard", "before"]),
         )
         .arg(
             Arg::new("patchranges-all")
@@ -48,6 +55,13 @@ fn make() -> clap::Command {
                 .num_args(1..)
                 .value_parser(clap::value_parser!(patchrange::Specification)),
         )
+        .arg(
+            Arg::new("before")
+                .long("before")
+                .help("Reset to the stack state recorded at or before <date>")
+                .value_name("date")
+                .conflicts_with_all(["committish", "patchranges-all"]),
+        )
         .arg(
             Arg::new("hard")
                 .long("hard")
@@ -58,15 +72,25 @@ fn make() -> clap::Command {
 
 fn run(matches: &clap::ArgMatches) -> Result<()> {
     let repo = git_repository::Repository::open()?;
-    if let Some(committish) = crate::argset::get_one_str(matches, "committish") {
+    let committish = crate::argset::get_one_str(matches, "committish");
+    let before = crate::argset::get_one_str(matches, "before");
+    if committish.is_some() || before.is_some() {
         let stack = Stack::from_branch(&repo, None, InitializationPolicy::RequireInitialized)?;
-        let commit_id = repo
-            .rev_parse_single(committish)
-            .map_err(|_| anyhow!("invalid committish `{committish}`"))?
-            .object()?
+        let commit_id = if let Some(date) = before {
+            stack.state_commit_at_date(date)?.id
+        } else {
+            let committish = committish.unwrap();
+            if committish.starts_with("{state}") {
+                parse_stgit_revision(&repo, Some(committish), None)?
+            } else {
+                repo.rev_parse_single(committish)
+                    .map_err(|_| anyhow!("invalid committish `{committish}`"))?
+                    .object()?
+            }
             .try_into_commit()
             .map_err(|_| anyhow!("target `{committish}` is not a commit"))?
-            .id;
+            .id
+        };
         stack
             .setup_transaction()
             .use_index_and_worktree(true)
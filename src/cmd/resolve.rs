@@ -0,0 +1,288 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg resolve` implementation.
+
+use std::{
+    ffi::OsString,
+    io::{BufRead, Write},
+    path::PathBuf,
+};
+
+use anyhow::{anyhow, Result};
+use bstr::ByteSlice;
+use clap::{Arg, ArgMatches, ValueHint};
+use is_terminal::IsTerminal;
+use termcolor::WriteColor;
+
+use crate::{color::get_color_stdout, ext::RepositoryExtended, print_info_message, stupid::Stupid};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "resolve",
+    category: super::CommandCategory::PatchManipulation,
+    make,
+    run,
+};
+
+/// Total width of `--side-by-side` output.
+const SIDE_BY_SIDE_WIDTH: usize = 80;
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Show and mark resolved merge conflicts")
+        .long_about(
+            "Mark conflicted files as resolved, or show the conflicting versions of \
+             each conflicted file.\n\
+             \n\
+             When a push, float, or other operation halts due to merge conflicts, the \
+             index records the common ancestor (base), our (stack), and their (patch) \
+             versions of each conflicted file. After editing a conflicted file to \
+             resolve the conflict, mark it resolved with `stg resolve <path>`, which \
+             adds the file to the index just as `git add` does. Files still containing \
+             conflict markers are not marked resolved unless '--force' is used.\n\
+             \n\
+             With '--show', the base, ours, and theirs versions of each conflicted file \
+             are shown in turn and, after each file, a prompt asks whether to mark the \
+             file resolved. Answer 'y' to mark the file resolved, 'n' to leave it \
+             conflicted, or 'q' to stop. Use '--side-by-side' to show the ours and \
+             theirs versions next to each other.",
+        )
+        .override_usage(
+            "stg resolve [--force] <path>...\n       \
+             stg resolve [--force] --all\n       \
+             stg resolve --show [--side-by-side] [--force] [<path>...]",
+        )
+        .arg(
+            Arg::new("pathspecs")
+                .help("Conflicted files to resolve or show")
+                .value_name("path")
+                .num_args(1..)
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(ValueHint::AnyPath),
+        )
+        .arg(
+            Arg::new("all")
+                .long("all")
+                .short('a')
+                .help("Mark all conflicted files resolved")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["pathspecs", "show"]),
+        )
+        .arg(
+            Arg::new("show")
+                .long("show")
+                .short('s')
+                .help("Show the conflicting versions of each conflicted file")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("side-by-side")
+                .long("side-by-side")
+                .short('y')
+                .help("Show ours and theirs versions side-by-side")
+                .action(clap::ArgAction::SetTrue)
+                .requires("show"),
+        )
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .short('f')
+                .help("Mark files resolved even if they contain conflict markers")
+                .action(clap::ArgAction::SetTrue),
+        )
+}
+
+fn run(matches: &ArgMatches) -> Result<()> {
+    let repo = git_repository::Repository::open()?;
+    let stupid = repo.stupid();
+    let work_dir = repo
+        .work_dir()
+        .ok_or_else(|| anyhow!("not supported in a bare repository"))?;
+    let pathspecs = matches.get_many::<PathBuf>("pathspecs");
+    let force = matches.get_flag("force");
+
+    if !matches.get_flag("show") && !matches.get_flag("all") && pathspecs.is_none() {
+        return Err(anyhow!(
+            "no files specified; use `--all` to resolve all files"
+        ));
+    }
+
+    let entries = stupid.ls_files_unmerged(pathspecs.clone())?;
+
+    if let Some(pathspecs) = pathspecs {
+        for pathspec in pathspecs {
+            let matched = !stupid.ls_files_unmerged(Some([pathspec]))?.is_empty();
+            if !matched {
+                return Err(anyhow!("`{}` is not in conflict", pathspec.display()));
+            }
+        }
+    }
+
+    if entries.is_empty() {
+        print_info_message(matches, "no conflicts to resolve");
+        return Ok(());
+    }
+
+    let has_markers = |path: &OsString| -> bool {
+        std::fs::read(work_dir.join(path))
+            .map(|content| {
+                ByteSlice::lines(content.as_slice())
+                    .any(|line| line.starts_with(b"<<<<<<< ") || line.starts_with(b">>>>>>> "))
+            })
+            .unwrap_or(false)
+    };
+
+    if !matches.get_flag("show") {
+        if !force {
+            if let Some((path, _)) = entries.iter().find(|(path, _)| has_markers(path)) {
+                return Err(anyhow!(
+                    "`{}` still contains conflict markers; use `--force` to mark it \
+                     resolved anyway",
+                    path.to_string_lossy()
+                ));
+            }
+        }
+        stupid.update_index(Some(entries.iter().map(|(path, _)| path)))?;
+        return Ok(());
+    }
+
+    let side_by_side = matches.get_flag("side-by-side");
+    let mut stdout = get_color_stdout(matches);
+    let stdin = std::io::stdin();
+    let is_interactive = stdin.is_terminal();
+    let mut stdin = stdin.lock();
+
+    for (path, stages) in &entries {
+        let path_str = path.to_string_lossy();
+        let contents = stages
+            .iter()
+            .map(|stage| {
+                stage
+                    .map(|blob_id| -> Result<Vec<u8>> {
+                        Ok(repo.find_object(blob_id)?.detach().data)
+                    })
+                    .transpose()
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        stdout.set_color(termcolor::ColorSpec::new().set_bold(true))?;
+        writeln!(stdout, "Conflicts in `{path_str}`")?;
+        stdout.reset()?;
+
+        if side_by_side {
+            write_section_header(&mut stdout, "base")?;
+            write_content(&mut stdout, contents[0].as_deref())?;
+            write_section_header(&mut stdout, "ours | theirs")?;
+            write_side_by_side(&mut stdout, contents[1].as_deref(), contents[2].as_deref())?;
+        } else {
+            for (label, content) in ["base", "ours", "theirs"].iter().zip(contents.iter()) {
+                write_section_header(&mut stdout, label)?;
+                write_content(&mut stdout, content.as_deref())?;
+            }
+        }
+
+        write!(stdout, "Mark `{path_str}` resolved? [y/N/q] ")?;
+        stdout.flush()?;
+        let mut answer = String::new();
+        let is_eof = stdin.read_line(&mut answer)? == 0;
+        if is_eof || !is_interactive {
+            // The answer was not echoed by a terminal.
+            writeln!(stdout)?;
+        }
+        if is_eof {
+            break;
+        }
+        match answer.trim() {
+            "y" | "Y" | "yes" => {
+                if !force && has_markers(path) {
+                    print_info_message(
+                        matches,
+                        &format!("`{path_str}` still contains conflict markers; not resolved"),
+                    );
+                } else {
+                    stupid.update_index(Some([path]))?;
+                    print_info_message(matches, &format!("`{path_str}` resolved"));
+                }
+            }
+            "q" | "Q" => break,
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn write_section_header(stdout: &mut termcolor::StandardStream, label: &str) -> Result<()> {
+    stdout.set_color(termcolor::ColorSpec::new().set_fg(Some(termcolor::Color::Cyan)))?;
+    writeln!(stdout, "--- {label}")?;
+    stdout.reset()?;
+    Ok(())
+}
+
+fn write_content(stdout: &mut termcolor::StandardStream, content: Option<&[u8]>) -> Result<()> {
+    match content {
+        None => writeln!(stdout, "(absent)")?,
+        Some(content) if content.contains(&0) => writeln!(stdout, "(binary)")?,
+        Some(content) => {
+            stdout.write_all(content)?;
+            if !content.is_empty() && !content.ends_with(b"\n") {
+                writeln!(stdout)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_side_by_side(
+    stdout: &mut termcolor::StandardStream,
+    left: Option<&[u8]>,
+    right: Option<&[u8]>,
+) -> Result<()> {
+    let column_width = (SIDE_BY_SIDE_WIDTH - 3) / 2;
+    let column_lines = |content: Option<&[u8]>| -> Vec<String> {
+        match content {
+            None => vec!["(absent)".to_string()],
+            Some(content) if content.contains(&0) => vec!["(binary)".to_string()],
+            Some(content) => ByteSlice::lines(content)
+                .map(|line| fit_column(&expand_tabs(&line.to_str_lossy()), column_width))
+                .collect(),
+        }
+    };
+    let left_lines = column_lines(left);
+    let right_lines = column_lines(right);
+    for i in 0..left_lines.len().max(right_lines.len()) {
+        let left = left_lines.get(i).map_or("", String::as_str);
+        let right = right_lines.get(i).map_or("", String::as_str);
+        let marker = if left == right { ' ' } else { '|' };
+        writeln!(
+            stdout,
+            "{}",
+            format!("{left:column_width$} {marker} {right}").trim_end()
+        )?;
+    }
+    Ok(())
+}
+
+fn expand_tabs(line: &str) -> String {
+    let mut expanded = String::with_capacity(line.len());
+    for c in line.chars() {
+        if c == '\t' {
+            expanded.push(' ');
+            while expanded.chars().count() % 8 != 0 {
+                expanded.push(' ');
+            }
+        } else {
+            expanded.push(c);
+        }
+    }
+    expanded
+}
+
+fn fit_column(line: &str, width: usize) -> String {
+    if line.chars().count() > width {
+        let mut fitted: String = line.chars().take(width - 1).collect();
+        fitted.push('>');
+        fitted
+    } else {
+        line.to_string()
+    }
+}
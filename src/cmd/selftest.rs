@@ -0,0 +1,163 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg selftest` implementation.
+
+use std::{
+    ffi::OsStr,
+    path::Path,
+    process::{Command, Output},
+};
+
+use anyhow::{anyhow, Context, Result};
+use clap::ArgMatches;
+
+use crate::stack::FAILPOINTS;
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "selftest",
+    category: super::CommandCategory::Administration,
+    make,
+    run,
+};
+
+/// Commands run against the scratch stack with each of the [`FAILPOINTS`] enabled.
+const OPERATIONS: [&[&str]; 3] = [&["push"], &["pop"], &["delete", "p2"]];
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Run internal self-tests")
+        .long_about(
+            "Run internal self-tests of StGit on this platform.\n\
+             \n\
+             The `transactions` subcommand creates a scratch repository and stack in \
+             a temporary directory and runs stack-modifying commands in it with a \
+             failure injected at each of the points where a stack transaction may \
+             fail, including after the branch reference is updated and before the \
+             stack reference is updated. After each injected failure, the references, \
+             index, and worktree of the scratch repository are checked to have been \
+             fully rolled back to their prior state.",
+        )
+        .hide(true)
+        .subcommand_required(true)
+        .subcommand(
+            clap::Command::new("transactions")
+                .about("Check that failed stack transactions are rolled back"),
+        )
+}
+
+fn run(matches: &ArgMatches) -> Result<()> {
+    match matches.subcommand() {
+        Some(("transactions", _)) => run_transactions(),
+        _ => panic!("valid subcommand is expected"),
+    }
+}
+
+fn run_transactions() -> Result<()> {
+    let stg = std::env::current_exe().context("finding stg executable")?;
+    let scratch_dir = tempfile::tempdir()?;
+    let dir = scratch_dir.path();
+    setup_scratch_stack(dir, stg.as_os_str())?;
+
+    let mut failures = 0;
+    for operation in OPERATIONS {
+        let description = format!("stg {}", operation.join(" "));
+        for point in FAILPOINTS {
+            let before = snapshot(dir)?;
+            let output = command(dir, stg.as_os_str(), operation)
+                .env("STGIT_FAILPOINT", point)
+                .output()
+                .with_context(|| format!("running `{description}`"))?;
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let problem = if output.status.success() {
+                Some("command succeeded despite injected failure")
+            } else if !stderr.contains(&format!("injected failure at `{point}`")) {
+                Some("failure point was not reached")
+            } else if !stderr.contains("all changes rolled back") {
+                Some("rollback was not reported")
+            } else if snapshot(dir)? != before {
+                Some("repository state was not rolled back")
+            } else {
+                None
+            };
+            if let Some(problem) = problem {
+                failures += 1;
+                println!("FAIL {point}: {description}: {problem}");
+            } else {
+                println!("ok   {point}: {description}");
+            }
+        }
+    }
+
+    if failures == 0 {
+        Ok(())
+    } else {
+        Err(anyhow!("{failures} transaction self-test(s) failed"))
+    }
+}
+
+/// Make a command that runs in the scratch repository, isolated from the environment
+/// of any repository `stg selftest` itself is run in.
+fn command(dir: &Path, program: &OsStr, args: &[&str]) -> Command {
+    let mut command = Command::new(program);
+    command
+        .args(args)
+        .current_dir(dir)
+        .env_remove("GIT_DIR")
+        .env_remove("GIT_WORK_TREE")
+        .env_remove("GIT_INDEX_FILE")
+        .env_remove("GIT_CEILING_DIRECTORIES")
+        .env_remove("STGIT_FAILPOINT");
+    command
+}
+
+/// Run a command in the scratch repository, failing if the command fails.
+fn run_command(dir: &Path, program: &OsStr, args: &[&str]) -> Result<Output> {
+    let output = command(dir, program, args)
+        .output()
+        .with_context(|| format!("running `{} {}`", program.to_string_lossy(), args.join(" ")))?;
+    if output.status.success() {
+        Ok(output)
+    } else {
+        Err(anyhow!(
+            "`{} {}` failed: {}",
+            program.to_string_lossy(),
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim_end(),
+        ))
+    }
+}
+
+/// Create a repository with an initialized stack of two patches, `p1` applied and `p2`
+/// unapplied, both modifying the same file.
+fn setup_scratch_stack(dir: &Path, stg: &OsStr) -> Result<()> {
+    let git = OsStr::new("git");
+    run_command(dir, git, &["init", "--quiet"])?;
+    run_command(dir, git, &["config", "user.name", "StGit Self-Test"])?;
+    run_command(dir, git, &["config", "user.email", "selftest@example.com"])?;
+    std::fs::write(dir.join("file"), "a\n")?;
+    run_command(dir, git, &["add", "file"])?;
+    run_command(dir, git, &["commit", "--quiet", "--message", "initial"])?;
+    run_command(dir, stg, &["init"])?;
+    for (patchname, contents) in [("p1", "a\nb\n"), ("p2", "a\nb\nc\n")] {
+        run_command(dir, stg, &["new", "--message", patchname, patchname])?;
+        std::fs::write(dir.join("file"), contents)?;
+        run_command(dir, stg, &["refresh"])?;
+    }
+    run_command(dir, stg, &["pop"])?;
+    Ok(())
+}
+
+/// Capture the references, index, and worktree state of the scratch repository.
+fn snapshot(dir: &Path) -> Result<Vec<u8>> {
+    let git = OsStr::new("git");
+    let mut snapshot = Vec::new();
+    for args in [
+        &["symbolic-ref", "HEAD"][..],
+        &["for-each-ref", "--format=%(refname) %(objectname)"],
+        &["status", "--porcelain", "--untracked-files=all"],
+        &["diff", "HEAD"],
+    ] {
+        snapshot.extend(run_command(dir, git, args)?.stdout);
+    }
+    Ok(snapshot)
+}
@@ -0,0 +1,167 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg series --duplicates` implementation.
+
+use std::{collections::HashMap, io::Write};
+
+use anyhow::Result;
+use termcolor::WriteColor;
+
+use crate::{
+    ext::CommitExtended,
+    patch::PatchName,
+    stack::{Stack, StackStateAccess},
+    stupid::Stupid,
+};
+
+/// A patch belonging to a group of duplicates.
+struct Member {
+    patchname: PatchName,
+    sigil: char,
+    /// Full diff of the patch, used to distinguish identical from near-identical patches.
+    diff: Vec<u8>,
+    message: Vec<u8>,
+}
+
+impl Member {
+    fn is_identical_to(&self, other: &Member) -> bool {
+        self.diff == other.diff && self.message == other.message
+    }
+}
+
+/// Report the patches of the stack, including hidden patches, that share a patch id.
+///
+/// Patches with the same patch id, as computed by git-patch-id(1), make the same
+/// changes modulo whitespace and line numbers. Such patches are reported as identical
+/// when their diffs and messages are byte-for-byte equal. A list of suggested commands
+/// to remove the duplicates follows the report.
+pub(super) fn report(
+    repo: &git_repository::Repository,
+    stack: &Stack,
+    matches: &clap::ArgMatches,
+) -> Result<()> {
+    let stupid = repo.stupid();
+    let top_patchname = stack.applied().last();
+
+    let mut groups: Vec<(git_repository::ObjectId, Vec<Member>)> = Vec::new();
+    let mut group_indices: HashMap<git_repository::ObjectId, usize> = HashMap::new();
+
+    for patchname in stack.all_patches() {
+        let commit = stack.get_patch_commit(patchname)?;
+        let diff = stupid.diff_tree_patch(
+            commit.get_parent_commit()?.tree_id()?.detach(),
+            commit.tree_id()?.detach(),
+            <Option<Vec<&str>>>::None,
+            false,
+            <Vec<&str>>::new(),
+        )?;
+        let Some(patch_id) = stupid.patch_id(&diff)? else {
+            continue;
+        };
+        let sigil = if Some(patchname) == top_patchname {
+            '>'
+        } else if stack.is_applied(patchname) {
+            '+'
+        } else if stack.is_unapplied(patchname) {
+            '-'
+        } else {
+            '!'
+        };
+        let member = Member {
+            patchname: patchname.clone(),
+            sigil,
+            diff,
+            message: commit.decode()?.message.to_vec(),
+        };
+        if let Some(&index) = group_indices.get(&patch_id) {
+            groups[index].1.push(member);
+        } else {
+            group_indices.insert(patch_id, groups.len());
+            groups.push((patch_id, vec![member]));
+        }
+    }
+
+    groups.retain(|(_, members)| members.len() > 1);
+    if groups.is_empty() {
+        return Ok(());
+    }
+
+    let mut stdout = crate::color::get_color_stdout(matches);
+    let mut color_spec = termcolor::ColorSpec::new();
+    let mut remediation: Vec<String> = Vec::new();
+    let mut to_delete: Vec<&PatchName> = Vec::new();
+
+    for (patch_id, members) in &groups {
+        let (keep, duplicates) = members
+            .split_first()
+            .expect("groups have two or more members");
+
+        stdout.set_color(color_spec.set_fg(Some(termcolor::Color::Yellow)))?;
+        write!(stdout, "{}", &patch_id.to_hex_with_len(12))?;
+        stdout.set_color(color_spec.set_fg(None))?;
+        writeln!(stdout)?;
+
+        for member in members {
+            let sigil_color = match member.sigil {
+                '+' => Some(termcolor::Color::Green),
+                '>' => Some(termcolor::Color::Blue),
+                '-' => Some(termcolor::Color::Magenta),
+                '!' => Some(termcolor::Color::Red),
+                _ => None,
+            };
+            write!(stdout, "  ")?;
+            stdout.set_color(color_spec.set_fg(sigil_color))?;
+            write!(stdout, "{} ", member.sigil)?;
+            stdout.set_color(color_spec.set_fg(None))?;
+            write!(stdout, "{}", member.patchname)?;
+            if member.patchname != keep.patchname {
+                let kind = if member.is_identical_to(keep) {
+                    "identical to"
+                } else {
+                    "same changes as"
+                };
+                stdout.set_color(color_spec.set_fg(Some(termcolor::Color::Black)))?;
+                write!(stdout, " # {kind} {}", keep.patchname)?;
+                stdout.set_color(color_spec.set_fg(None))?;
+            }
+            writeln!(stdout)?;
+        }
+
+        // Identical and hidden duplicates are simply deleted. Visible duplicates with
+        // different messages are squashed into the kept patch so that no part of
+        // their messages is lost.
+        let mut to_squash: Vec<&PatchName> = Vec::new();
+        for member in duplicates {
+            if member.sigil == '!' || keep.sigil == '!' || member.is_identical_to(keep) {
+                to_delete.push(&member.patchname);
+            } else {
+                to_squash.push(&member.patchname);
+            }
+        }
+        if !to_squash.is_empty() {
+            let mut command = format!("stg squash --name={0} {0}", keep.patchname);
+            for patchname in to_squash {
+                command.push(' ');
+                command.push_str(patchname.as_ref());
+            }
+            remediation.push(command);
+        }
+    }
+
+    if !to_delete.is_empty() {
+        let mut command = "stg delete".to_string();
+        for patchname in to_delete {
+            command.push(' ');
+            command.push_str(patchname.as_ref());
+        }
+        remediation.insert(0, command);
+    }
+
+    writeln!(stdout)?;
+    writeln!(stdout, "Suggested remediation:")?;
+    for command in remediation {
+        writeln!(stdout, "  {command}")?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,171 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg series init` implementation.
+
+use std::{path::PathBuf, str::FromStr};
+
+use anyhow::{anyhow, Context, Result};
+use clap::Arg;
+
+use crate::{
+    color::get_color_stdout,
+    ext::RepositoryExtended,
+    patch::PatchName,
+    stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
+    stupid::Stupid,
+    wrap::Message,
+};
+
+pub(super) fn command() -> clap::Command {
+    clap::Command::new("init")
+        .about("Create a skeleton of empty patches from a template")
+        .long_about(
+            "Create a skeleton of empty patches from a series template file. This \
+             allows stacks with a standardized structure to be bootstrapped \
+             consistently.\n\
+             \n\
+             Each non-blank line of the template names one patch, optionally followed \
+             by whitespace and the subject line to use for the patch's commit message. \
+             When no subject is given, the patch name is used as the subject. Lines \
+             starting with '#' are ignored. For example:\n\
+             \n    \
+             # Standard feature series\n    \
+             prep-cleanup Preparatory cleanups\n    \
+             feature-core\n    \
+             docs Update documentation\n    \
+             tests Add tests\n\
+             \n\
+             The patches are created on top of the current branch's stack, which is \
+             initialized if necessary, in the order they appear in the template.",
+        )
+        .arg(
+            Arg::new("from-template")
+                .long("from-template")
+                .short('t')
+                .help("Read patch names and subjects from <file>")
+                .value_name("file")
+                .required(true)
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(clap::ValueHint::FilePath),
+        )
+        .arg(
+            Arg::new("noapply")
+                .long("noapply")
+                .help("Keep the new patches unapplied")
+                .action(clap::ArgAction::SetTrue),
+        )
+}
+
+pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
+    let repo = git_repository::Repository::open()?;
+    let stack = Stack::from_branch(&repo, None, InitializationPolicy::AutoInitialize)?;
+
+    let template_path = matches
+        .get_one::<PathBuf>("from-template")
+        .expect("required argument");
+    let template = std::fs::read_to_string(template_path)
+        .with_context(|| format!("reading template `{}`", template_path.display()))?;
+    let mut entries = parse_template(&template)?;
+    if entries.is_empty() {
+        return Err(anyhow!(
+            "template `{}` does not name any patches",
+            template_path.display()
+        ));
+    }
+
+    for i in 0..entries.len() {
+        let patchname = &entries[i].0;
+        let disambiguated = stack.disambiguate_patchname(patchname, None)?;
+        if &disambiguated != patchname {
+            if entries.iter().any(|(pn, _)| pn.collides(&disambiguated)) {
+                return Err(anyhow!(
+                    "patch name `{patchname}` differs only in case from an existing \
+                     patch and its disambiguated name `{disambiguated}` appears in the \
+                     template"
+                ));
+            }
+            crate::print_info_message(
+                matches,
+                &format!(
+                    "patch name `{patchname}` differs only in case from an existing patch; \
+                     using `{disambiguated}`"
+                ),
+            );
+            entries[i].0 = disambiguated;
+        }
+        let patchname = &entries[i].0;
+        if let Some(warning) = patchname.check_portability(&repo.config_snapshot())? {
+            crate::print_warning_message(matches, &warning);
+        }
+    }
+
+    let noapply = matches.get_flag("noapply");
+    if !noapply {
+        repo.check_repository_state()?;
+        repo.stupid().statuses(None)?.check_conflicts()?;
+        stack.check_head_top_mismatch()?;
+    }
+
+    let author = repo.get_author()?;
+    let committer = repo.get_committer()?;
+    let tree_id = stack.get_branch_head().tree_id()?.detach();
+    let mut parent_id = stack.get_branch_head().id;
+    let mut new_patches = Vec::with_capacity(entries.len());
+    for (patchname, subject) in entries {
+        let message = format!("{subject}\n");
+        let commit_id = repo.commit_ex(
+            author,
+            committer,
+            &Message::String(message),
+            tree_id,
+            [parent_id],
+        )?;
+        if !noapply {
+            parent_id = commit_id;
+        }
+        new_patches.push((patchname, commit_id));
+    }
+
+    stack
+        .setup_transaction()
+        .with_output_stream(get_color_stdout(matches))
+        .transact(|trans| {
+            for (i, (patchname, commit_id)) in new_patches.iter().enumerate() {
+                if noapply {
+                    trans.new_unapplied(patchname, *commit_id, i)?;
+                } else {
+                    trans.new_applied(patchname, *commit_id)?;
+                }
+            }
+            Ok(())
+        })
+        .execute("series init")?;
+
+    Ok(())
+}
+
+/// Parse series template lines into patch names and subjects.
+fn parse_template(template: &str) -> Result<Vec<(PatchName, String)>> {
+    let mut entries: Vec<(PatchName, String)> = Vec::new();
+    for line in template.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (name, subject) = line
+            .split_once(char::is_whitespace)
+            .map_or((line, ""), |(name, subject)| (name, subject.trim()));
+        let patchname = PatchName::from_str(name)?;
+        if entries.iter().any(|(pn, _)| pn.collides(&patchname)) {
+            return Err(anyhow!(
+                "patch `{patchname}` appears more than once in template"
+            ));
+        }
+        let subject = if subject.is_empty() {
+            patchname.to_string()
+        } else {
+            subject.to_string()
+        };
+        entries.push((patchname, subject));
+    }
+    Ok(entries)
+}
@@ -2,6 +2,9 @@
 
 //! `stg series` implementation.
 
+mod duplicates;
+mod init;
+
 use std::{io::Write, str::FromStr};
 
 use anyhow::{anyhow, Result};
@@ -12,8 +15,11 @@ use termcolor::WriteColor;
 use crate::{
     argset,
     ext::{CommitExtended, RepositoryExtended},
-    patch::{patchrange, PatchName},
+    patch::{diffstat::Diffstat, patchrange, PatchName},
+    print_warning_message,
+    revspec::parse_branch_and_spec,
     stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
+    stupid::Stupid,
 };
 
 const UNPRINTABLE: &str = "???";
@@ -37,20 +43,57 @@ fn make() -> clap::Command {
              unapplied patches with a '-', and the hidden patches with \
              a '!'.\n\
              \n\
-             Empty patches are prefixed with a '0'.",
+             Empty patches are prefixed with a '0'.\n\
+             \n\
+             When \"stgit.hidden.expire-days\" is set, a warning is printed for each \
+             hidden patch that has been hidden for longer than that many days.\n\
+             \n\
+             The '--short' option limits the displayed patches to a window around \
+             the topmost applied patch. The window includes up to \
+             \"stgit.shortnr\" (default 5) patches before and after the anchor \
+             patch. The window size and anchor may be given explicitly as \
+             '--short=<n>', '--short=<n>@<anchor>', or '--short=@<anchor>', where \
+             the anchor is either \"top\" or a patch name. For example, \
+             '--short=3@p42' shows patch \"p42\" along with up to three patches \
+             before and after it.\n\
+             \n\
+             The '--duplicates' option reports groups of patches, including hidden \
+             patches, that make the same changes as determined by their patch ids \
+             (see git-patch-id(1)). Such duplicates often creep in after picks and \
+             imports. Duplicates whose diffs and messages are byte-for-byte equal \
+             are reported as identical. The report is followed by suggested `stg \
+             delete` and `stg squash` commands that remove the duplicates while \
+             keeping the first patch of each group.\n\
+             \n\
+             The '--preview' option gives a quick overview of the patches by \
+             displaying the first lines of each patch's diffstat, or of its message \
+             body with '--preview-body', beneath the patch's series line.\n\
+             \n\
+             The `init` subcommand creates a skeleton of empty patches from a series \
+             template file. To display a patch named \"init\", use `stg series -- \
+             init`.",
         )
         .override_usage(
             "stg series [OPTIONS] [-A] [-U] [-H]\n       \
              stg series [OPTIONS] --all\n       \
-             stg series [OPTIONS] --short\n       \
-             stg series [OPTIONS] [patch]...",
+             stg series [OPTIONS] --short[=[<n>][@<anchor>]]\n       \
+             stg series [OPTIONS] [patch]...\n       \
+             stg series [OPTIONS] --duplicates\n       \
+             stg series init --from-template <file>",
         )
+        .subcommand(init::command())
+        .args_conflicts_with_subcommands(true)
         .arg(
             Arg::new("patchranges-all")
                 .help("Patches to display")
+                .long_help(
+                    "Patches to display. Patches of another branch may be specified \
+                     as '<branch>:<patch>' or '<branch>:[begin-patch]..[end-patch]', \
+                     which is equivalent to using '--branch'.",
+                )
                 .value_name("patch")
                 .num_args(1..)
-                .value_parser(clap::value_parser!(patchrange::Specification))
+                .value_parser(clap::builder::NonEmptyStringValueParser::new())
                 .conflicts_with_all(["all", "applied", "unapplied", "hidden", "short"]),
         )
         .arg(argset::branch_arg())
@@ -67,8 +110,12 @@ fn make() -> clap::Command {
             Arg::new("short")
                 .long("short")
                 .short('s')
-                .help("Select patches around the topmost patch only")
-                .action(clap::ArgAction::SetTrue),
+                .help("Select patches around the topmost or <anchor> patch only")
+                .value_name("[n][@anchor]")
+                .num_args(0..=1)
+                .default_missing_value("")
+                .require_equals(true)
+                .value_parser(clap::value_parser!(ShortWindow)),
         )
         .group(ArgGroup::new("all-short-group").args(["all", "short"]))
         .arg(
@@ -101,6 +148,23 @@ fn make() -> clap::Command {
                 .value_name("branch")
                 .value_hint(ValueHint::Other),
         )
+        .arg(
+            Arg::new("duplicates")
+                .long("duplicates")
+                .help("Report patches that duplicate the changes of other patches")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "patchranges-all",
+                    "all",
+                    "short",
+                    "applied",
+                    "unapplied",
+                    "hidden",
+                    "missing",
+                    "count",
+                    "preview",
+                ]),
+        )
         .next_help_heading("Display Options")
         .arg(
             Arg::new("author")
@@ -177,6 +241,67 @@ fn make() -> clap::Command {
                 .help("Display the branch name with the listed patches")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("preview")
+                .long("preview")
+                .help("Display up to <n> lines of each patch's diffstat")
+                .long_help(
+                    "Display up to <n> lines of each patch's diffstat, indented beneath \
+                     the patch's series line. With '--preview-body', the first <n> lines \
+                     of the patch's message body, i.e. its commit message following the \
+                     subject, are displayed instead.",
+                )
+                .value_name("n")
+                .value_parser(clap::value_parser!(usize))
+                .conflicts_with("count"),
+        )
+        .arg(
+            Arg::new("preview-body")
+                .long("preview-body")
+                .help("Preview the message body instead of the diffstat")
+                .action(clap::ArgAction::SetTrue)
+                .requires("preview"),
+        )
+}
+
+/// Width of the diffstats displayed with `--preview`.
+const PREVIEW_STAT_WIDTH: usize = 72;
+
+/// Get up to `n` lines previewing the given patch commit for `--preview`.
+fn preview_lines(
+    repo: &git_repository::Repository,
+    commit: &git_repository::Commit,
+    n: usize,
+    body: bool,
+) -> Result<Vec<String>> {
+    let content = if body {
+        let commit_ref = commit.decode()?;
+        let message = commit_ref.message();
+        message
+            .body
+            .map(|body| {
+                body.trim_start_with(|c| c == '\n')
+                    .to_str_lossy()
+                    .into_owned()
+            })
+            .unwrap_or_default()
+    } else {
+        let parent_tree_id = commit.get_parent_commit()?.tree_id()?.detach();
+        let tree_id = commit.tree_id()?.detach();
+        let diff = repo.stupid().diff_tree_patch(
+            parent_tree_id,
+            tree_id,
+            <Option<Vec<&str>>>::None,
+            false,
+            std::iter::empty::<&str>(),
+        )?;
+        Diffstat::from_diff(&diff).render(PREVIEW_STAT_WIDTH, None, false)
+    };
+    Ok(content
+        .lines()
+        .take(n)
+        .map(|line| line.trim_end().to_string())
+        .collect())
 }
 
 #[derive(Clone)]
@@ -207,11 +332,75 @@ impl FromStr for CommitIdLength {
     }
 }
 
+/// Window of patches selected by `--short`.
+#[derive(Clone)]
+struct ShortWindow {
+    /// Number of patches shown before and after the anchor patch.
+    ///
+    /// The "stgit.shortnr" config value is used when not specified.
+    size: Option<usize>,
+
+    /// Patch around which the window is centered; `None` for the topmost patch.
+    anchor: Option<PatchName>,
+}
+
+impl FromStr for ShortWindow {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (size_str, anchor_str) = s.split_once('@').unwrap_or((s, ""));
+        let size = if size_str.is_empty() {
+            None
+        } else {
+            Some(usize::from_str(size_str).map_err(|_| {
+                anyhow!("window size must be a non-negative integer, not `{size_str}`")
+            })?)
+        };
+        let anchor = match anchor_str {
+            "" | "top" => None,
+            _ => Some(PatchName::from_str(anchor_str)?),
+        };
+        Ok(ShortWindow { size, anchor })
+    }
+}
+
 fn run(matches: &ArgMatches) -> Result<()> {
+    if let Some(("init", sub_matches)) = matches.subcommand() {
+        return init::dispatch(sub_matches);
+    }
+
     let repo = git_repository::Repository::open()?;
-    let opt_branch = argset::get_one_str(matches, "branch");
+    let mut opt_branch = argset::get_one_str(matches, "branch");
     let opt_missing = argset::get_one_str(matches, "missing");
 
+    // A branch embedded in the patch ranges, e.g. `branch:p1..p3`, takes precedence
+    // over '--branch'.
+    let mut range_specs: Option<Vec<patchrange::Specification>> = None;
+    if let Some(spec_strs) = matches.get_many::<String>("patchranges-all") {
+        let mut specs = Vec::new();
+        let mut spec_branch = None;
+        for spec_str in spec_strs {
+            let (branch, spec) = parse_branch_and_spec(None, Some(spec_str));
+            if branch.is_some() {
+                if opt_missing.is_some() {
+                    return Err(anyhow!(
+                        "patch range `{spec_str}` with a branch may not be used with \
+                         `--missing`"
+                    ));
+                } else if spec_branch.is_some() && spec_branch != branch {
+                    return Err(anyhow!("patch ranges must be from a single branch"));
+                }
+                spec_branch = branch;
+            }
+            let spec = spec.ok_or_else(|| anyhow!("no patch specified in `{spec_str}`"))?;
+            specs.push(patchrange::Specification::from_str(spec)?);
+        }
+        if spec_branch.is_some() {
+            opt_branch = spec_branch;
+        }
+        range_specs = Some(specs);
+    }
+
     let (stack, ref_stack) = if let Some(ref_branch) = opt_missing {
         (
             Stack::from_branch(
@@ -232,21 +421,28 @@ fn run(matches: &ArgMatches) -> Result<()> {
         )
     };
 
+    if matches.get_flag("duplicates") {
+        return duplicates::report(&repo, &stack, matches);
+    }
+
     let all_flag = matches.get_flag("all");
     let applied_flag = matches.get_flag("applied");
     let unapplied_flag = matches.get_flag("unapplied");
     let hidden_flag = matches.get_flag("hidden");
 
+    // Only patch commit ids are gathered. Patch commit objects are looked up only when
+    // an option needs the commit's content, e.g. '--description' or '--author', such
+    // that listing patch names does not read each patch's commit.
     let mut patches: Vec<(PatchName, git_repository::ObjectId, char)> = vec![];
 
-    if let Some(range_specs) = matches.get_many::<patchrange::Specification>("patchranges-all") {
+    if let Some(range_specs) = range_specs.as_ref() {
         let top_patchname = stack.applied().last();
         for patchname in patchrange::contiguous_patches_from_specs(
             range_specs,
             &stack,
             patchrange::Allow::AllWithAppliedBoundary,
         )? {
-            let commit_id = stack.get_patch_commit(&patchname).id;
+            let commit_id = stack.get_patch_commit_id(&patchname);
             let sigil = if Some(&patchname) == top_patchname {
                 '>'
             } else if stack.is_applied(&patchname) {
@@ -266,24 +462,24 @@ fn run(matches: &ArgMatches) -> Result<()> {
         if show_applied {
             if let Some((last_patchname, rest)) = stack.applied().split_last() {
                 for patchname in rest {
-                    let commit_id = stack.get_patch_commit(patchname).id;
+                    let commit_id = stack.get_patch_commit_id(patchname);
                     patches.push((patchname.clone(), commit_id, '+'));
                 }
-                let last_oid = stack.get_patch_commit(last_patchname).id;
-                patches.push((last_patchname.clone(), last_oid, '>'));
+                let last_commit_id = stack.get_patch_commit_id(last_patchname);
+                patches.push((last_patchname.clone(), last_commit_id, '>'));
             }
         }
 
         if show_unapplied {
             for patchname in stack.unapplied() {
-                let commit_id = stack.get_patch_commit(patchname).id;
+                let commit_id = stack.get_patch_commit_id(patchname);
                 patches.push((patchname.clone(), commit_id, '-'));
             }
         }
 
         if show_hidden {
             for patchname in stack.hidden() {
-                let commit_id = stack.get_patch_commit(patchname).id;
+                let commit_id = stack.get_patch_commit_id(patchname);
                 patches.push((patchname.clone(), commit_id, '!'));
             }
         }
@@ -297,16 +493,38 @@ fn run(matches: &ArgMatches) -> Result<()> {
         });
     }
 
-    if matches.get_flag("short") {
-        let shortnr = repo.config_snapshot().integer("stgit.shortnr").unwrap_or(5);
-        let shortnr: usize = if shortnr < 0 { 0 } else { shortnr as usize };
+    if let Some(window) = matches.get_one::<ShortWindow>("short") {
+        let shortnr = if let Some(size) = window.size {
+            size
+        } else {
+            let shortnr = repo.config_snapshot().integer("stgit.shortnr").unwrap_or(5);
+            if shortnr < 0 {
+                0
+            } else {
+                shortnr as usize
+            }
+        };
+
+        let anchor_pos = if let Some(anchor) = window.anchor.as_ref() {
+            let anchor_pos = patches.iter().position(|(pn, _, _)| pn == anchor);
+            if anchor_pos.is_none() {
+                return Err(if stack.has_patch(anchor) {
+                    anyhow!("patch `{anchor}` is not among the selected patches")
+                } else {
+                    anyhow!("patch `{anchor}` does not exist")
+                });
+            }
+            anchor_pos
+        } else {
+            patches.iter().position(|(_, _, sigil)| *sigil == '>')
+        };
 
-        if let Some(top_pos) = patches.iter().position(|(_, _, sigil)| *sigil == '>') {
-            if patches.len() - top_pos > shortnr {
-                patches.drain(top_pos + 1 + shortnr..);
+        if let Some(anchor_pos) = anchor_pos {
+            if patches.len() - anchor_pos > shortnr {
+                patches.drain(anchor_pos + 1 + shortnr..);
             }
-            if top_pos > shortnr {
-                patches.drain(0..top_pos - shortnr);
+            if anchor_pos > shortnr {
+                patches.drain(0..anchor_pos - shortnr);
             }
         } else {
             patches.drain(shortnr.min(patches.len())..);
@@ -336,38 +554,33 @@ fn run(matches: &ArgMatches) -> Result<()> {
     };
 
     let author_width: usize = if author_flag && description_flag {
-        patches
-            .iter()
-            .map(|(_, commit_id, _)| -> usize {
-                if let Ok(commit) = repo.find_commit(*commit_id) {
-                    commit
-                        .author()
-                        .ok()
-                        .and_then(|author| author.name.to_str().ok())
-                        .unwrap_or(UNPRINTABLE)
-                        .len()
-                } else {
-                    0
-                }
-            })
-            .max()
-            .unwrap_or(0)
+        let mut author_width = 0;
+        for (patchname, _, _) in &patches {
+            let commit = stack.get_patch_commit(patchname)?;
+            let width = commit
+                .author()
+                .ok()
+                .and_then(|author| author.name.to_str().ok())
+                .unwrap_or(UNPRINTABLE)
+                .len();
+            author_width = author_width.max(width);
+        }
+        author_width
     } else {
         0
     };
 
     let no_prefix_flag = matches.get_flag("no-prefix");
     let empty_flag = matches.get_flag("empty");
+    let opt_preview = matches.get_one::<usize>("preview");
+    let preview_body_flag = matches.get_flag("preview-body");
 
     let mut stdout = crate::color::get_color_stdout(matches);
     let mut color_spec = termcolor::ColorSpec::new();
 
     for (patchname, commit_id, sigil) in patches {
-        let commit = repo.find_commit(commit_id)?;
-        let commit_ref = commit.decode()?;
-
         if empty_flag {
-            if commit.is_no_change()? {
+            if stack.get_patch_commit(&patchname)?.is_no_change()? {
                 stdout.set_color(color_spec.set_fg(Some(termcolor::Color::Cyan)))?;
                 write!(stdout, "0")?;
                 stdout.set_color(color_spec.set_fg(None))?;
@@ -418,16 +631,18 @@ fn run(matches: &ArgMatches) -> Result<()> {
             stdout.set_color(color_spec.set_fg(Some(termcolor::Color::Black)))?;
             write!(stdout, " # ")?;
             stdout.set_color(color_spec.set_fg(Some(termcolor::Color::Blue)))?;
+            let commit = stack.get_patch_commit(&patchname)?;
             if let Ok(author) = commit.author_strict() {
                 write!(stdout, "{:author_width$}", &author.name.to_str().unwrap())?;
             } else {
-                let name = commit_ref.author().name.to_str_lossy();
+                let name = commit.decode()?.author().name.to_str_lossy().into_owned();
                 write!(stdout, "{name:author_width$}")?;
             }
         }
         if description_flag {
             stdout.set_color(color_spec.set_fg(Some(termcolor::Color::Black)))?;
             write!(stdout, " #")?;
+            let commit_ref = stack.get_patch_commit(&patchname)?.decode()?;
             let summary = commit_ref.message_summary();
             if !summary.is_empty() {
                 if let Ok(summary) = summary.to_str() {
@@ -439,6 +654,29 @@ fn run(matches: &ArgMatches) -> Result<()> {
         color_spec.clear();
         stdout.set_color(&color_spec)?;
         writeln!(stdout)?;
+
+        if let Some(&n) = opt_preview {
+            let commit = stack.get_patch_commit(&patchname)?;
+            for line in preview_lines(&repo, commit, n, preview_body_flag)? {
+                if line.is_empty() {
+                    writeln!(stdout)?;
+                } else {
+                    writeln!(stdout, "    {line}")?;
+                }
+            }
+        }
+    }
+
+    if let Some(expire_days) = super::hide::expire_days(&repo) {
+        for (patchname, days) in super::hide::expired_hidden_patches(&stack, expire_days)? {
+            print_warning_message(
+                matches,
+                &format!(
+                    "patch `{patchname}` has been hidden for {days} days; consider `stg \
+                     delete` or `stg doctor --fix` to shelve it"
+                ),
+            );
+        }
     }
 
     Ok(())
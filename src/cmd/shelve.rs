@@ -0,0 +1,317 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg shelve` implementation.
+
+use std::io::Write;
+
+use anyhow::{anyhow, Context, Result};
+use clap::{Arg, ArgMatches};
+
+use crate::{
+    argset,
+    color::get_color_stdout,
+    ext::RepositoryExtended,
+    patch::{patchrange, PatchName},
+    stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
+    stupid::Stupid,
+    wrap::Message,
+};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "shelve",
+    category: super::CommandCategory::StackManipulation,
+    make,
+    run,
+};
+
+/// Name of the blob in a shelf commit's tree that describes the shelved patches.
+const SHELF_JSON: &str = "shelf.json";
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Move patches out of the stack onto a named shelf")
+        .long_about(
+            "Move patches out of the stack onto a named shelf, from which they may \
+             later be restored with `stg unshelve`.\n\
+             \n\
+             Shelved patches are removed from the series entirely, unlike hidden \
+             patches, which makes shelves suitable for parking half-finished \
+             sub-series. Each shelf is recorded in a `refs/shelves/<shelf>` reference \
+             along with the names and order of its patches. Shelves are not specific \
+             to a branch, so shelved patches may be restored onto another branch or \
+             onto a stack whose base has since changed.\n\
+             \n\
+             Use '--list' to show the existing shelves and their patches.",
+        )
+        .override_usage(
+            "stg shelve [OPTIONS] <shelf> <patch>...\n       \
+             stg shelve --list",
+        )
+        .arg(
+            Arg::new("shelf")
+                .help("Name of the shelf to create")
+                .value_name("shelf")
+                .value_parser(clap::builder::NonEmptyStringValueParser::new())
+                .required_unless_present("list"),
+        )
+        .arg(
+            Arg::new("patchranges-all")
+                .help("Patches to shelve")
+                .value_name("patch")
+                .num_args(1..)
+                .value_parser(clap::value_parser!(patchrange::Specification))
+                .required_unless_present("list"),
+        )
+        .arg(
+            Arg::new("list")
+                .long("list")
+                .short('l')
+                .help("List shelves and their patches")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["shelf", "patchranges-all", "branch"]),
+        )
+        .arg(argset::branch_arg())
+        .arg(argset::push_conflicts_arg())
+}
+
+/// A set of patches moved out of a stack by `stg shelve`.
+#[derive(serde::Serialize, serde::Deserialize)]
+pub(super) struct Shelf {
+    /// Name of the branch the patches were shelved from.
+    pub(super) branch: String,
+
+    /// Shelved patches, in stack order.
+    pub(super) patches: Vec<ShelvedPatch>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub(super) struct ShelvedPatch {
+    pub(super) name: PatchName,
+    pub(super) commit: String,
+}
+
+/// Get the reference name of a shelf, checking that the shelf name is valid.
+pub(super) fn shelf_refname(shelf_name: &str) -> Result<git_repository::refs::FullName> {
+    git_repository::refs::FullName::try_from(format!("refs/shelves/{shelf_name}"))
+        .map_err(|_| anyhow!("invalid shelf name `{shelf_name}`"))
+}
+
+/// Read the shelf with the given name.
+pub(super) fn read_shelf(repo: &git_repository::Repository, shelf_name: &str) -> Result<Shelf> {
+    let refname = shelf_refname(shelf_name)?;
+    let mut reference = repo
+        .try_find_reference(refname.as_ref())?
+        .ok_or_else(|| anyhow!("shelf `{shelf_name}` not found"))?;
+    let commit = reference
+        .peel_to_id_in_place()?
+        .object()?
+        .try_into_commit()?;
+    let entry = commit
+        .tree()?
+        .lookup_entry_by_path(SHELF_JSON)?
+        .ok_or_else(|| anyhow!("shelf `{shelf_name}` is missing `{SHELF_JSON}`"))?;
+    let shelf_json = entry.object()?.detach().data;
+    serde_json::from_slice(&shelf_json).with_context(|| format!("reading shelf `{shelf_name}`"))
+}
+
+/// Get the names of all shelves.
+pub(super) fn shelf_names(repo: &git_repository::Repository) -> Result<Vec<String>> {
+    let mut names: Vec<String> = repo
+        .references()?
+        .prefixed("refs/shelves/")?
+        .filter_map(Result::ok)
+        .filter_map(|reference| {
+            reference
+                .name()
+                .as_bstr()
+                .strip_prefix(b"refs/shelves/")
+                .and_then(|name| std::str::from_utf8(name).ok())
+                .map(String::from)
+        })
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+/// Write a shelf commit recording the given patches and point the shelf's ref at it.
+///
+/// The shelf commit's parents are the shelved patch commits, which keeps them reachable.
+fn write_shelf(
+    stack: &Stack,
+    refname: &git_repository::refs::FullName,
+    shelf_name: &str,
+    patches: &[PatchName],
+) -> Result<()> {
+    let repo = stack.repo;
+    let shelf = Shelf {
+        branch: stack.get_branch_name().to_string(),
+        patches: patches
+            .iter()
+            .map(|patchname| ShelvedPatch {
+                name: patchname.clone(),
+                commit: stack.get_patch_commit_id(patchname).to_string(),
+            })
+            .collect(),
+    };
+    let shelf_json_id = repo.write_blob(serde_json::to_string_pretty(&shelf)?.as_bytes())?;
+    let tree = git_repository::objs::Tree {
+        entries: vec![git_repository::objs::tree::Entry {
+            mode: git_repository::objs::tree::EntryMode::Blob,
+            filename: SHELF_JSON.into(),
+            oid: shelf_json_id.detach(),
+        }],
+    };
+    let tree_id = repo.write_object(tree)?.detach();
+
+    let mut message = format!("shelf: {shelf_name}\n\n");
+    for patchname in patches {
+        message.push_str(patchname.as_ref());
+        message.push('\n');
+    }
+    let mut parent_ids = indexmap::IndexSet::new();
+    for patchname in patches {
+        parent_ids.insert(stack.get_patch_commit_id(patchname));
+    }
+    let committer = repo.get_committer()?;
+    let commit_id = repo.commit_ex(
+        repo.get_author()?,
+        committer,
+        &Message::from(message),
+        tree_id,
+        parent_ids,
+    )?;
+
+    repo.edit_reference(git_repository::refs::transaction::RefEdit {
+        change: git_repository::refs::transaction::Change::Update {
+            log: git_repository::refs::transaction::LogChange {
+                mode: git_repository::refs::transaction::RefLog::AndReference,
+                force_create_reflog: false,
+                message: format!("stgit: shelve {shelf_name}").into(),
+            },
+            expected: git_repository::refs::transaction::PreviousValue::MustNotExist,
+            new: git_repository::refs::Target::Peeled(commit_id),
+        },
+        name: refname.clone(),
+        deref: false,
+    })?;
+    Ok(())
+}
+
+/// Move hidden patches onto a new shelf, returning the name of the shelf.
+///
+/// This archives hidden patches that have expired per `stgit.hidden.expire-days`. The
+/// shelf is named `hidden/<branch>/<date>-<time>`, made unique if necessary.
+pub(super) fn shelve_hidden(
+    stack: Stack,
+    patches: &[PatchName],
+    matches: &ArgMatches,
+    reflog_msg: &str,
+) -> Result<String> {
+    let repo = stack.repo;
+    let base_name = format!(
+        "hidden/{}/{}",
+        stack.get_branch_name(),
+        chrono::Local::now().format("%Y%m%d-%H%M%S")
+    );
+    let mut shelf_name = base_name.clone();
+    let mut suffix = 0;
+    while repo
+        .try_find_reference(shelf_refname(&shelf_name)?.as_ref())?
+        .is_some()
+    {
+        suffix += 1;
+        shelf_name = format!("{base_name}-{suffix}");
+    }
+    let refname = shelf_refname(&shelf_name)?;
+
+    write_shelf(&stack, &refname, &shelf_name, patches)?;
+
+    stack
+        .setup_transaction()
+        .with_output_stream(get_color_stdout(matches))
+        .transact(|trans| {
+            trans.delete_patches(|pn| patches.contains(pn))?;
+            Ok(())
+        })
+        .execute(reflog_msg)?;
+
+    Ok(shelf_name)
+}
+
+fn run(matches: &ArgMatches) -> Result<()> {
+    let repo = git_repository::Repository::open()?;
+
+    if matches.get_flag("list") {
+        let mut stdout = std::io::stdout();
+        for shelf_name in shelf_names(&repo)? {
+            let shelf = read_shelf(&repo, &shelf_name)?;
+            write!(stdout, "{shelf_name} ({}):", shelf.branch)?;
+            for patch in &shelf.patches {
+                write!(stdout, " {}", patch.name)?;
+            }
+            writeln!(stdout)?;
+        }
+        return Ok(());
+    }
+
+    let opt_branch = argset::get_one_str(matches, "branch");
+    let stack = Stack::from_branch(&repo, opt_branch, InitializationPolicy::AllowUninitialized)?;
+    let allow_push_conflicts =
+        argset::resolve_allow_push_conflicts(&repo.config_snapshot(), matches);
+
+    let shelf_name = matches
+        .get_one::<String>("shelf")
+        .expect("shelf is required without --list");
+    let refname = shelf_refname(shelf_name)?;
+    if repo.try_find_reference(refname.as_ref())?.is_some() {
+        return Err(anyhow!("shelf `{shelf_name}` already exists"));
+    }
+
+    let requested = patchrange::patches_from_specs(
+        matches
+            .get_many::<patchrange::Specification>("patchranges-all")
+            .expect("patches are required without --list"),
+        &stack,
+        patchrange::Allow::AllWithAppliedBoundary,
+    )?;
+    let patches: Vec<PatchName> = stack
+        .all_patches()
+        .filter(|pn| requested.contains(pn))
+        .cloned()
+        .collect();
+    if patches.is_empty() {
+        return Ok(());
+    }
+
+    repo.check_repository_state()?;
+    repo.stupid().statuses(None)?.check_conflicts()?;
+    stack.check_head_top_mismatch()?;
+
+    write_shelf(&stack, &refname, shelf_name, &patches)?;
+
+    stack
+        .setup_transaction()
+        .use_index_and_worktree(opt_branch.is_none())
+        .allow_push_conflicts(allow_push_conflicts)
+        .with_output_stream(get_color_stdout(matches))
+        .transact(|trans| {
+            let to_push = trans.delete_patches(|pn| patches.contains(pn))?;
+            trans.push_patches(&to_push, false)?;
+            Ok(())
+        })
+        .execute("shelve")
+        .map_err(|e| {
+            // Keep the shelf only if its patches were removed from the stack.
+            let still_present =
+                Stack::from_branch(&repo, opt_branch, InitializationPolicy::AllowUninitialized)
+                    .map_or(true, |stack| patches.iter().any(|pn| stack.has_patch(pn)));
+            if still_present {
+                if let Ok(Some(reference)) = repo.try_find_reference(refname.as_ref()) {
+                    reference.delete().ok();
+                }
+            }
+            e
+        })?;
+
+    Ok(())
+}
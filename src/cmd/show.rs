@@ -2,7 +2,7 @@
 
 //! `stg show` implementation.
 
-use std::path::PathBuf;
+use std::{io::Write, path::PathBuf, str::FromStr};
 
 use anyhow::{anyhow, Result};
 use clap::{Arg, ArgMatches};
@@ -11,7 +11,7 @@ use crate::{
     argset,
     ext::RepositoryExtended,
     patch::patchrange,
-    revspec::Error as RevError,
+    revspec::{parse_branch_and_spec, parse_stgit_revision, Error as RevError},
     stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
     stupid::Stupid,
 };
@@ -45,11 +45,14 @@ fn make() -> clap::Command {
                      \n\
                      A patch name, patch range of the form \
                      '[begin-patch]..[end-patch]', or any valid Git revision \
-                     may be specified.",
+                     may be specified. Patches and patch ranges of another branch \
+                     may be specified as '<branch>:<patch>' or \
+                     '<branch>:[begin-patch]..[end-patch]', including hidden \
+                     patches.",
                 )
                 .value_name("patch-or-rev")
                 .num_args(1..)
-                .value_parser(clap::value_parser!(patchrange::Specification))
+                .value_parser(clap::builder::NonEmptyStringValueParser::new())
                 .conflicts_with_all(["applied", "unapplied", "hidden"]),
         )
         .arg(
@@ -67,7 +70,7 @@ fn make() -> clap::Command {
                 .help("Patch or revision to show")
                 .action(clap::ArgAction::Append)
                 .value_name("patch-or-rev")
-                .value_parser(clap::value_parser!(patchrange::Specification))
+                .value_parser(clap::builder::NonEmptyStringValueParser::new())
                 .allow_hyphen_values(true)
                 .conflicts_with("patchranges-all"),
         )
@@ -79,6 +82,8 @@ fn make() -> clap::Command {
                 .help("Show a diffstat summary instead of the full diff")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(argset::stat_width_arg())
+        .arg(argset::stat_count_arg())
         .arg(argset::diff_opts_arg())
         .next_help_heading("Selection Options")
         .arg(
@@ -118,80 +123,139 @@ fn run(matches: &ArgMatches) -> Result<()> {
 
     if applied_flag {
         for patchname in stack.applied() {
-            oids.push(stack.get_patch(patchname).commit.id);
+            oids.push(stack.get_patch(patchname).commit_id());
         }
     }
     if unapplied_flag {
         for patchname in stack.unapplied() {
-            oids.push(stack.get_patch(patchname).commit.id);
+            oids.push(stack.get_patch(patchname).commit_id());
         }
     }
     if hidden_flag {
         for patchname in stack.hidden() {
-            oids.push(stack.get_patch(patchname).commit.id);
+            oids.push(stack.get_patch(patchname).commit_id());
         }
     }
     if let Some(range_specs) = matches
-        .get_many::<patchrange::Specification>("patchranges-all")
-        .or_else(|| matches.get_many::<patchrange::Specification>("patchranges"))
+        .get_many::<String>("patchranges-all")
+        .or_else(|| matches.get_many::<String>("patchranges"))
     {
-        for spec in range_specs {
-            match patchrange::patches_from_specs(
-                [spec],
-                &stack,
-                patchrange::Allow::AllWithAppliedBoundary,
-            ) {
-                Ok(patchnames) => {
-                    for patchname in &patchnames {
-                        oids.push(stack.get_patch(patchname).commit.id);
+        for spec_str in range_specs {
+            let (spec_branch, spec) = parse_branch_and_spec(opt_branch, Some(spec_str));
+            let branch_stack = if spec_branch != opt_branch {
+                Some(Stack::from_branch(
+                    &repo,
+                    spec_branch,
+                    InitializationPolicy::AllowUninitialized,
+                )?)
+            } else {
+                None
+            };
+            let spec_stack = branch_stack.as_ref().unwrap_or(&stack);
+            match spec.map(patchrange::Specification::from_str) {
+                Some(Ok(spec)) => match patchrange::patches_from_specs(
+                    [&spec],
+                    spec_stack,
+                    patchrange::Allow::AllWithAppliedBoundary,
+                ) {
+                    Ok(patchnames) => {
+                        for patchname in &patchnames {
+                            oids.push(spec_stack.get_patch(patchname).commit_id());
+                        }
                     }
-                }
-                Err(patchrange::Error::PatchNotKnown { patchname: _ }) => {
-                    let spec_str = spec.to_string();
-                    let oid =
-                        crate::revspec::parse_stgit_revision(&repo, Some(&spec_str), opt_branch)
+                    Err(patchrange::Error::PatchNotKnown { patchname: _ }) => {
+                        let oid = parse_stgit_revision(&repo, Some(spec_str), opt_branch)
                             .map_err(|rev_err| match rev_err.downcast_ref::<RevError>() {
                                 Some(RevError::InvalidRevision(spec, context)) => {
                                     anyhow!("invalid revision spec `{spec}`: {context}")
                                 }
-                                Some(RevError::RevisionNotFound(spec)) => {
-                                    anyhow!("patch or revision `{spec}` not found")
-                                }
-                                _ => rev_err,
-                            })?
-                            .id;
-                    oids.push(oid);
-                }
-                Err(patchrange::Error::PatchName(_)) => {
-                    let spec_str = spec.to_string();
-                    let oid =
-                        crate::revspec::parse_stgit_revision(&repo, Some(&spec_str), opt_branch)
-                            .map_err(|rev_err| match rev_err.downcast_ref::<RevError>() {
-                                Some(RevError::InvalidRevision(spec, context)) => {
-                                    anyhow!("invalid patch or revision spec `{spec}`: {context}")
-                                }
-                                Some(RevError::RevisionNotFound(spec)) => {
-                                    anyhow!("invalid patch or revision `{spec}` not found",)
+                                Some(RevError::RevisionNotFound(_)) => {
+                                    anyhow!("patch or revision `{spec_str}` not found")
                                 }
                                 _ => rev_err,
                             })?
                             .id;
+                        oids.push(oid);
+                    }
+                    Err(e) => {
+                        return Err(e.into());
+                    }
+                },
+                _ => {
+                    let oid = parse_stgit_revision(&repo, Some(spec_str), opt_branch)
+                        .map_err(|rev_err| match rev_err.downcast_ref::<RevError>() {
+                            Some(RevError::InvalidRevision(spec, context)) => {
+                                anyhow!("invalid patch or revision spec `{spec}`: {context}")
+                            }
+                            Some(RevError::RevisionNotFound(_)) => {
+                                anyhow!("invalid patch or revision `{spec_str}` not found",)
+                            }
+                            _ => rev_err,
+                        })?
+                        .id;
                     oids.push(oid);
                 }
-                Err(e) => {
-                    return Err(e.into());
-                }
             }
         }
     } else if !applied_flag && !unapplied_flag && !hidden_flag {
         oids.push(stack.get_branch_head().id);
     }
 
-    repo.stupid().show(
-        oids,
-        matches.get_many::<PathBuf>("pathspecs"),
-        stat_flag,
-        crate::color::use_color(matches),
-        argset::get_diff_opts(matches, &repo.config_snapshot(), false, false),
-    )
+    let diff_opts = argset::get_diff_opts(matches, &repo.config_snapshot(), false, false)?;
+    if stat_flag {
+        show_stat(&repo, matches, oids, &diff_opts)
+    } else {
+        repo.stupid().show(
+            oids,
+            matches.get_many::<PathBuf>("pathspecs"),
+            crate::color::use_color(matches),
+            diff_opts,
+        )
+    }
+}
+
+/// Show each object's log message followed by the diffstat of the commit it peels to.
+fn show_stat(
+    repo: &git_repository::Repository,
+    matches: &ArgMatches,
+    oids: Vec<git_repository::ObjectId>,
+    diff_opts: &[String],
+) -> Result<()> {
+    let stupid = repo.stupid();
+    let use_color = crate::color::use_color(matches);
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+
+    for (i, oid) in oids.into_iter().enumerate() {
+        if i > 0 {
+            stdout.write_all(b"\n")?;
+        }
+        stdout.write_all(&stupid.show_no_patch(oid, use_color)?)?;
+
+        let Ok(commit) = repo
+            .find_object(oid)?
+            .peel_to_kind(git_repository::objs::Kind::Commit)
+            .map(git_repository::Object::into_commit)
+        else {
+            continue;
+        };
+        let parent_tree_id = if let Some(parent_id) = commit.parent_ids().next() {
+            parent_id.object()?.into_commit().tree_id()?.detach()
+        } else {
+            git_repository::ObjectId::empty_tree(repo.object_hash())
+        };
+        let diff = stupid.diff_tree_patch(
+            parent_tree_id,
+            commit.tree_id()?.detach(),
+            matches.get_many::<PathBuf>("pathspecs"),
+            false,
+            diff_opts,
+        )?;
+        let diffstat = argset::render_diffstat(matches, &diff);
+        if !diffstat.is_empty() {
+            write!(stdout, "\n{diffstat}")?;
+        }
+    }
+
+    Ok(())
 }
@@ -40,6 +40,13 @@ fn make() -> clap::Command {
              of the stack where they less likely to be impacted by the push of another \
              patch, and from where they can be more easily committed or pushed to \
              another repository.\n\
+             \n\
+             With '--noapply', unapplied patches are reordered amongst the unapplied \
+             patches without pushing or popping any patches. The patches to sink are \
+             placed at the bottom of the unapplied patches, i.e. they will be the next \
+             patches pushed, or below the unapplied '--to' target patch. Since the \
+             index and worktree are not involved, this is fast and works with a dirty \
+             worktree.\n\
              ",
         )
         .arg(
@@ -60,6 +67,18 @@ fn make() -> clap::Command {
                 )
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("noapply")
+                .long("noapply")
+                .help("Reorder unapplied patches without pushing any patches")
+                .long_help(
+                    "Reorder unapplied patches without pushing or popping any \
+                     patches. All patches to sink and the '--to' target patch, if \
+                     specified, must be unapplied.",
+                )
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("nopush"),
+        )
         .arg(
             Arg::new("target")
                 .long("to")
@@ -76,6 +95,7 @@ fn make() -> clap::Command {
         )
         .arg(argset::keep_arg())
         .arg(argset::committer_date_is_author_date_arg())
+        .arg(argset::stable_ids_arg())
 }
 
 fn run(matches: &ArgMatches) -> Result<()> {
@@ -85,20 +105,30 @@ fn run(matches: &ArgMatches) -> Result<()> {
 
     let opt_target: Option<PatchName> = matches.get_one::<PatchName>("target").cloned();
     let nopush_flag = matches.get_flag("nopush");
+    let noapply_flag = matches.get_flag("noapply");
     let keep_flag = matches.contains_id("keep");
 
     repo.check_repository_state()?;
     let statuses = stupid.statuses(None)?;
     statuses.check_conflicts()?;
     stack.check_head_top_mismatch()?;
-    if !keep_flag {
+    if !keep_flag && !noapply_flag {
         statuses.check_index_and_worktree_clean()?;
     }
 
     if let Some(target_patch) = &opt_target {
         if !stack.has_patch(target_patch) {
             return Err(anyhow!("target patch `{target_patch}` does not exist"));
-        } else if !stack.is_applied(target_patch) {
+        } else if noapply_flag && stack.is_applied(target_patch) {
+            return Err(anyhow!(
+                "cannot sink below applied `{target_patch}` with --noapply; \
+                 pushing and popping patches is required"
+            ));
+        } else if noapply_flag && stack.is_hidden(target_patch) {
+            return Err(anyhow!(
+                "cannot sink below `{target_patch}` since it is hidden"
+            ));
+        } else if !noapply_flag && !stack.is_applied(target_patch) {
             return Err(anyhow!(
                 "cannot sink below `{target_patch}` since it is not applied"
             ));
@@ -108,12 +138,28 @@ fn run(matches: &ArgMatches) -> Result<()> {
     let patches: Vec<PatchName> =
         if let Some(range_specs) = matches.get_many::<patchrange::Specification>("patchranges") {
             patchrange::patches_from_specs(range_specs, &stack, patchrange::Allow::All)?
+        } else if noapply_flag {
+            return Err(anyhow!("patches to sink must be specified with --noapply"));
         } else if let Some(patchname) = stack.applied().last() {
             vec![patchname.clone()]
         } else {
             return Err(Error::NoAppliedPatches.into());
         };
 
+    if noapply_flag {
+        if let Some(patchname) = patches.iter().find(|pn| !stack.is_unapplied(pn)) {
+            return Err(anyhow!(
+                "cannot sink {} patch `{patchname}` with --noapply; \
+                 pushing and popping patches is required",
+                if stack.is_applied(patchname) {
+                    "applied"
+                } else {
+                    "hidden"
+                }
+            ));
+        }
+    }
+
     if let Some(target_patch) = &opt_target {
         if patches.contains(target_patch) {
             return Err(anyhow!(
@@ -136,7 +182,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
         .cloned()
         .collect();
 
-    let target_pos = if let Some(target_patch) = &opt_target {
+    let target_pos = if let Some(target_patch) = opt_target.as_ref().filter(|_| !noapply_flag) {
         remaining_applied
             .iter()
             .position(|pn| pn == target_patch)
@@ -147,7 +193,22 @@ fn run(matches: &ArgMatches) -> Result<()> {
 
     let mut patches = patches;
 
-    let (applied, unapplied) = if nopush_flag {
+    let (applied, unapplied) = if noapply_flag {
+        let target_pos = if let Some(target_patch) = &opt_target {
+            remaining_unapplied
+                .iter()
+                .position(|pn| pn == target_patch)
+                .expect("already validated that target is unapplied")
+        } else {
+            0
+        };
+        let mut unapplied: Vec<PatchName> =
+            Vec::with_capacity(remaining_unapplied.len() + patches.len());
+        unapplied.extend(remaining_unapplied.drain(0..target_pos));
+        unapplied.append(&mut patches);
+        unapplied.append(&mut remaining_unapplied);
+        (remaining_applied, unapplied)
+    } else if nopush_flag {
         let mut applied: Vec<PatchName> = Vec::with_capacity(target_pos + patches.len());
         applied.extend(remaining_applied.drain(0..target_pos));
         applied.append(&mut patches);
@@ -167,8 +228,9 @@ fn run(matches: &ArgMatches) -> Result<()> {
 
     stack
         .setup_transaction()
-        .use_index_and_worktree(true)
+        .use_index_and_worktree(!noapply_flag)
         .committer_date_is_author_date(matches.get_flag("committer-date-is-author-date"))
+        .stable_ids(matches.get_flag("stable-ids"))
         .with_output_stream(get_color_stdout(matches))
         .transact(|trans| trans.reorder_patches(Some(&applied), Some(&unapplied), None))
         .execute("sink")?;
@@ -4,13 +4,14 @@
 
 use std::path::PathBuf;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use clap::{Arg, ArgMatches};
 
 use crate::{
     argset,
     color::get_color_stdout,
     ext::{CommitExtended, RepositoryExtended},
+    patch::{patchrange, PatchName},
     stack::{Error, InitializationPolicy, Stack, StackStateAccess},
     stupid::Stupid,
 };
@@ -24,13 +25,25 @@ pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
 
 fn make() -> clap::Command {
     clap::Command::new(STGIT_COMMAND.name)
-        .about("Spill changes from the topmost patch")
+        .about("Spill changes from the topmost patches")
         .long_about(
             "Spill changes from the topmost patch. Changes are removed from the patch, \
              but remain in the index and worktree.\n\
              \n\
              Spilling a patch may be useful for reselecting the files/hunks to be \
-             included in the patch.",
+             included in the patch.\n\
+             \n\
+             Use '--patches' to spill a range of patches instead of only the topmost \
+             patch. The selected patches must be the topmost applied patches. Each \
+             selected patch is left in place with its changes removed, such that the \
+             combined changes of the selected patches remain in the index and \
+             worktree where they may be redistributed among the patches, e.g. with \
+             `stg refresh --patch`. The patches are spilled in a single transaction \
+             which may be reverted with `stg undo`.",
+        )
+        .override_usage(
+            "stg spill [OPTIONS] [path]...\n       \
+             stg spill [OPTIONS] --patches <patch-range> [path]...",
         )
         .arg(
             Arg::new("annotate")
@@ -52,6 +65,22 @@ fn make() -> clap::Command {
                 )
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("patches")
+                .long("patches")
+                .short('p')
+                .help("Spill changes from the given patches")
+                .long_help(
+                    "Spill changes from the given patches instead of only the topmost \
+                     patch. This option may be repeated and each value may be a \
+                     patch range such as \"p1..p3\" or \"p1..\". The selected \
+                     patches must be the topmost applied patches.",
+                )
+                .value_name("patch-range")
+                .num_args(1)
+                .action(clap::ArgAction::Append)
+                .value_parser(clap::value_parser!(patchrange::Specification)),
+        )
         .arg(argset::committer_date_is_author_date_arg())
         .arg(
             Arg::new("pathspecs")
@@ -73,62 +102,113 @@ fn run(matches: &ArgMatches) -> Result<()> {
     statuses.check_index_clean()?;
     stack.check_head_top_mismatch()?;
 
-    let patchname = stack
-        .applied()
-        .last()
-        .ok_or(Error::NoAppliedPatches)?
-        .clone();
-    let patch_commit = stack.get_patch_commit(&patchname);
-    let patch_commit_ref = patch_commit.decode()?;
-    let parent = patch_commit.get_parent_commit()?;
-    let parent_commit_ref = parent.decode()?;
-
-    let tree_id = if let Some(pathspecs) = matches.get_many::<PathBuf>("pathspecs") {
-        stupid.with_temp_index(|stupid_temp| {
-            stupid_temp.read_tree(patch_commit_ref.tree())?;
-            stupid_temp.apply_pathlimited_treediff_to_index(
-                patch_commit_ref.tree(),
-                parent_commit_ref.tree(),
-                true,
-                pathspecs,
-            )?;
-            stupid_temp.write_tree()
-        })?
-    } else {
-        parent_commit_ref.tree()
-    };
+    let patchnames: Vec<PatchName> =
+        if let Some(range_specs) = matches.get_many::<patchrange::Specification>("patches") {
+            let patchnames =
+                patchrange::patches_from_specs(range_specs, &stack, patchrange::Allow::Applied)?;
+            let applied = stack.applied();
+            let n_topmost = applied
+                .iter()
+                .rev()
+                .take_while(|pn| patchnames.contains(pn))
+                .count();
+            let topmost = &applied[applied.len() - n_topmost..];
+            if patchnames.iter().any(|pn| !topmost.contains(pn)) {
+                return Err(anyhow!("can only spill topmost applied patches"));
+            }
+            topmost.to_vec()
+        } else {
+            vec![stack
+                .applied()
+                .last()
+                .ok_or(Error::NoAppliedPatches)?
+                .clone()]
+        };
+
+    if patchnames.is_empty() {
+        return Ok(());
+    }
 
-    let author = patch_commit.author_strict()?;
+    let base_tree_id = stack
+        .get_patch_commit(&patchnames[0])?
+        .get_parent_commit()?
+        .tree_id()?
+        .detach();
+    let pathspecs: Option<Vec<&PathBuf>> = matches
+        .get_many::<PathBuf>("pathspecs")
+        .map(|pathspecs| pathspecs.collect());
     let default_committer = repo.get_committer()?;
-    let committer = if matches.get_flag("committer-date-is-author-date") {
-        let mut committer = default_committer.to_owned();
-        committer.time = author.time;
-        committer
-    } else {
-        default_committer.to_owned()
-    };
-
-    let commit_id = repo.commit_ex(
-        &author,
-        &committer,
-        &patch_commit.message_ex(),
-        tree_id,
-        patch_commit_ref.parents(),
-    )?;
 
-    drop(patch_commit_ref);
+    let mut parent_id = stack
+        .get_patch_commit(&patchnames[0])?
+        .parent_ids()
+        .next()
+        .unwrap()
+        .detach();
+    let mut tree_id = base_tree_id;
+    let mut updates: Vec<(PatchName, git_repository::ObjectId)> =
+        Vec::with_capacity(patchnames.len());
+
+    for patchname in &patchnames {
+        let patch_commit = stack.get_patch_commit(patchname)?;
+        let patch_tree_id = patch_commit.tree_id()?.detach();
+
+        tree_id = if let Some(pathspecs) = pathspecs.as_ref() {
+            stupid.with_temp_index(|stupid_temp| {
+                stupid_temp.read_tree(patch_tree_id)?;
+                stupid_temp.apply_pathlimited_treediff_to_index(
+                    patch_tree_id,
+                    base_tree_id,
+                    true,
+                    pathspecs.iter().copied(),
+                )?;
+                stupid_temp.write_tree()
+            })?
+        } else {
+            base_tree_id
+        };
+
+        let author = patch_commit.author_strict()?;
+        let committer = if matches.get_flag("committer-date-is-author-date") {
+            let mut committer = default_committer.to_owned();
+            committer.time = author.time;
+            committer
+        } else {
+            default_committer.to_owned()
+        };
+
+        let commit_id = repo.commit_ex(
+            &author,
+            &committer,
+            &patch_commit.message_ex(),
+            tree_id,
+            [parent_id],
+        )?;
+        updates.push((patchname.clone(), commit_id));
+        parent_id = commit_id;
+    }
 
+    let spilled = patchnames
+        .iter()
+        .map(|pn| pn.to_string())
+        .collect::<Vec<_>>()
+        .join(" ");
     let reflog_msg = if let Some(annotation) = matches.get_one::<String>("annotate") {
-        format!("spill {patchname}\n\n{annotation}")
+        format!("spill {spilled}\n\n{annotation}")
     } else {
-        format!("spill {patchname}")
+        format!("spill {spilled}")
     };
 
     stack
         .setup_transaction()
         .use_index_and_worktree(false)
         .with_output_stream(get_color_stdout(matches))
-        .transact(|trans| trans.update_patch(&patchname, commit_id))
+        .transact(|trans| {
+            for (patchname, commit_id) in &updates {
+                trans.update_patch(patchname, *commit_id)?;
+            }
+            Ok(())
+        })
         .execute(&reflog_msg)?;
 
     if matches.get_flag("reset") {
@@ -102,7 +102,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
     }
 
     if matches.contains_id("save-template") {
-        let first_patch_commit = stack.get_patch_commit(&squash_patchnames[0]);
+        let first_patch_commit = stack.get_patch_commit(&squash_patchnames[0])?;
         if let patchedit::EditOutcome::TemplateSaved(template_path) =
             patchedit::EditBuilder::default()
                 .existing_patch_commit(first_patch_commit) // Dummy commit
@@ -155,7 +155,7 @@ fn prepare_message<'repo>(
 ) -> Result<String> {
     let mut squash_message = String::new();
     for (i, patchname) in patchnames.iter().enumerate() {
-        let commit = stack_state.get_patch_commit(patchname);
+        let commit = stack_state.get_patch_commit(patchname)?;
         let message = commit.message_ex();
         let message = message.decode()?;
         let message = message.trim_end();
@@ -221,11 +221,12 @@ fn try_squash(
     patchname: Option<&PatchName>,
 ) -> Result<Option<(PatchName, git_repository::ObjectId)>> {
     let repo = trans.repo();
-    let base_commit = trans.get_patch_commit(&patchnames[0]);
+    let base_commit = trans.get_patch_commit(&patchnames[0])?;
     let base_commit_ref = base_commit.decode()?;
     if let Some(tree_id) = repo.stupid().with_temp_index(|stupid_temp| {
         stupid_temp.read_tree(base_commit_ref.tree())?;
-        for commit in patchnames[1..].iter().map(|pn| trans.get_patch_commit(pn)) {
+        for patchname in &patchnames[1..] {
+            let commit = trans.get_patch_commit(patchname)?;
             let commit_ref = commit.decode()?;
             let parent = commit.get_parent_commit()?;
             let parent_commit_ref = parent.decode()?;
@@ -246,6 +247,7 @@ fn try_squash(
         if let patchedit::EditOutcome::Edited {
             new_patchname,
             new_commit_id,
+            ..
         } = patchedit::EditBuilder::default()
             .override_parent_id(
                 base_commit_ref
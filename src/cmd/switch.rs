@@ -0,0 +1,158 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg switch` implementation.
+
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgMatches};
+
+use crate::{
+    argset,
+    color::get_color_stdout,
+    ext::RepositoryExtended,
+    patch::PatchName,
+    print_info_message,
+    stack::{InitializationPolicy, Stack, StackStateAccess},
+    stupid::Stupid,
+};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "switch",
+    category: super::CommandCategory::StackManipulation,
+    make,
+    run,
+};
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Switch to another branch, checking both stacks")
+        .long_about(
+            "Switch to another branch after checking that the switch is safe.\n\
+             \n\
+             Unlike `stg branch <branch>`, the index and worktree must be clean and \
+             free of conflicts, and the target branch's stack, if any, must pass the \
+             checks of `stg verify`. Nothing is changed if any check fails.\n\
+             \n\
+             With '--pop-all', all patches applied on the current branch are popped \
+             before switching and the set of popped patches is remembered for the \
+             branch. When later switching back to the branch with `stg switch`, the \
+             remembered patches are pushed again, unless '--no-restore' is used.",
+        )
+        .arg(
+            Arg::new("branch")
+                .help("Branch to switch to")
+                .value_name("branch")
+                .required(true)
+                .value_parser(argset::parse_branch_name),
+        )
+        .arg(
+            Arg::new("pop-all")
+                .long("pop-all")
+                .short('a')
+                .help("Pop all applied patches before switching")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no-restore")
+                .long("no-restore")
+                .help("Do not push patches popped by a previous '--pop-all'")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(argset::push_conflicts_arg())
+}
+
+fn run(matches: &ArgMatches) -> Result<()> {
+    let repo = git_repository::Repository::open()?;
+    let current_branch = repo.get_branch(None)?;
+    let current_branchname = current_branch.get_branch_name()?.to_string();
+    let target_branchname = argset::get_one_str(matches, "branch").expect("required argument");
+
+    if target_branchname == current_branchname {
+        return Err(anyhow!("{target_branchname} is already the current branch"));
+    }
+
+    repo.check_repository_state()?;
+    let stupid = repo.stupid();
+    let statuses = stupid.statuses(None)?;
+    statuses.check_conflicts()?;
+    statuses.check_index_and_worktree_clean()?;
+
+    let target_branch = repo.get_branch(Some(target_branchname))?;
+    let target_branchname = target_branch.get_branch_name()?.to_string();
+    if let Some(violations) = super::verify::verify_stack(&repo, &target_branchname)? {
+        if let Some(violation) = violations.first() {
+            return Err(anyhow!(
+                "stack on branch `{target_branchname}` is inconsistent: {}: {}; \
+                 see `stg verify --branch {target_branchname}`",
+                violation.code,
+                violation.description,
+            ));
+        }
+    }
+
+    let stack = Stack::from_branch(&repo, None, InitializationPolicy::AllowUninitialized)?;
+    let popped: Vec<PatchName> = if matches.get_flag("pop-all") && !stack.applied().is_empty() {
+        stack.check_head_top_mismatch()?;
+        let popped = stack.applied().to_vec();
+        stack.record_switch_applied(&popped)?;
+        stack
+            .setup_transaction()
+            .use_index_and_worktree(true)
+            .with_output_stream(get_color_stdout(matches))
+            .transact(|trans| {
+                let mut unapplied = popped.clone();
+                unapplied.extend(trans.unapplied().iter().cloned());
+                trans.reorder_patches(Some(&[]), Some(&unapplied), None)
+            })
+            .execute(&format!("switch: pop all for {target_branchname}"))?;
+        popped
+    } else {
+        Vec::new()
+    };
+
+    if let Err(e) = stupid.checkout(&target_branchname) {
+        if !popped.is_empty() {
+            let stack = Stack::from_branch(&repo, None, InitializationPolicy::RequireInitialized)?;
+            stack.record_switch_applied(&[])?;
+            stack
+                .setup_transaction()
+                .use_index_and_worktree(true)
+                .with_output_stream(get_color_stdout(matches))
+                .transact(|trans| trans.push_patches(&popped, false))
+                .execute("switch: restore popped patches")?;
+        }
+        return Err(e);
+    }
+
+    let target_stack = Stack::from_branch(
+        &repo,
+        Some(&target_branchname),
+        InitializationPolicy::AllowUninitialized,
+    )?;
+    let remembered = target_stack.switch_applied()?;
+    if remembered.is_empty() || matches.get_flag("no-restore") {
+        return Ok(());
+    }
+
+    let to_push: Vec<PatchName> = remembered
+        .into_iter()
+        .filter(|patchname| target_stack.is_unapplied(patchname))
+        .collect();
+    target_stack.record_switch_applied(&[])?;
+    if to_push.is_empty() {
+        print_info_message(matches, "no remembered patches to push");
+        return Ok(());
+    }
+
+    let allow_push_conflicts =
+        argset::resolve_allow_push_conflicts(&repo.config_snapshot(), matches);
+    target_stack.check_head_top_mismatch()?;
+    target_stack
+        .setup_transaction()
+        .use_index_and_worktree(true)
+        .allow_push_conflicts(allow_push_conflicts)
+        .with_output_stream(get_color_stdout(matches))
+        .transact(|trans| trans.push_patches(&to_push, false))
+        .execute("switch: restore popped patches")?;
+
+    Ok(())
+}
@@ -199,7 +199,7 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
         .with_output_stream(get_color_stdout(matches))
         .transact(|trans| {
             for pn in pushed.iter().chain(popped.iter()) {
-                let parent_id = trans.top().id;
+                let parent_id = trans.top()?.id;
 
                 if popped.contains(pn) {
                     trans.push_patches(&[pn], false)?;
@@ -209,7 +209,7 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
                     continue;
                 }
 
-                let commit = trans.get_patch_commit(pn);
+                let commit = trans.get_patch_commit(pn)?;
 
                 let maybe_tree_id = if let Some(ref_stack) = ref_stack.as_ref() {
                     branch_merge_patch(ref_stack, trans, pn, commit)?
@@ -253,7 +253,7 @@ fn branch_merge_patch(
     commit: &git_repository::Commit,
 ) -> Result<Option<git_repository::ObjectId>> {
     let commit_ref = commit.decode()?;
-    let ref_commit = ref_stack.get_patch_commit(patchname);
+    let ref_commit = ref_stack.get_patch_commit(patchname)?;
     let ref_commit_ref = ref_commit.decode()?;
     let ref_parent = ref_commit.get_parent_commit()?;
     let ref_parent_ref = ref_parent.decode()?;
@@ -303,7 +303,7 @@ fn series_merge_patch(
     stupid.update_index_refresh()?;
     stupid.read_tree_checkout(trans_head_tree_id, parent_commit_ref.tree())?;
     stupid
-        .apply_to_worktree_and_index(&diff, false, false, None, None, None)
+        .apply_to_worktree_and_index(&diff, false, false, None, None, None, &[])
         .with_context(|| format!("applying {patchname} from series"))?;
     stupid.update_index_refresh()?;
 
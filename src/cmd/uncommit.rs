@@ -2,10 +2,16 @@
 
 //! `stg uncommit` implementation.
 
-use std::rc::Rc;
+use std::{
+    io::{BufRead, Write},
+    rc::Rc,
+};
 
 use anyhow::{anyhow, Result};
+use bstr::ByteSlice;
 use clap::{Arg, ArgMatches};
+use is_terminal::IsTerminal;
+use termcolor::WriteColor;
 
 use crate::{
     argset,
@@ -46,13 +52,22 @@ fn make() -> clap::Command {
              given commit should be uncommitted. The -x/--exclusive option may be \
              used to exclude the \"to\" commit.\n\
              \n\
+             The -i/--interactive option lists the commits that may be uncommitted \
+             and prompts for the number of commits to uncommit.\n\
+             \n\
              Only commits with exactly one parent can be uncommitted; in other words, \
-             merge commits may not be uncommitted.",
+             merge commits may not be uncommitted.\n\
+             \n\
+             Commits that are already in the branch's configured upstream, i.e. that \
+             have been published, are not uncommitted unless the --force option is \
+             given. The commits listed by --interactive are marked as \"local\" or as \
+             \"upstream\" accordingly.",
         )
         .override_usage(
             "stg uncommit <patchname-1> [<patchname-2> ...]\n       \
              stg uncommit -n number [<patchname-prefix>]\n       \
-             stg uncommit -t <committish> [-x]",
+             stg uncommit -t <committish> [-x]\n       \
+             stg uncommit -i",
         )
         .arg(
             Arg::new("patchname")
@@ -85,8 +100,25 @@ fn make() -> clap::Command {
                 .help("Exclude the commit specified by the '--to' option")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("interactive")
+                .long("interactive")
+                .short('i')
+                .help("Choose the commits to uncommit from a list")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["patchname", "number", "to", "exclusive"]),
+        )
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .help("Uncommit commits that are already in the upstream branch")
+                .action(clap::ArgAction::SetTrue),
+        )
 }
 
+/// Maximum number of commits listed by '--interactive'.
+const PICKER_LIMIT: usize = 20;
+
 fn run(matches: &ArgMatches) -> Result<()> {
     let repo = git_repository::Repository::open()?;
     let stack = Stack::from_branch(&repo, None, InitializationPolicy::AutoInitialize)?;
@@ -95,8 +127,17 @@ fn run(matches: &ArgMatches) -> Result<()> {
     let opt_number = matches.get_one::<usize>("number").copied();
 
     let patchname_len_limit = PatchName::get_length_limit(&config);
+    let force = matches.get_flag("force");
+    let upstream = get_upstream(&stack)?;
 
-    let (commits, patchnames) = if let Some(committish) = matches.get_one::<String>("to") {
+    let (commits, patchnames) = if matches.get_flag("interactive") {
+        let commits = pick_commits(&stack, matches, upstream.as_ref(), force)?;
+        if commits.is_empty() {
+            return Ok(());
+        }
+        let patchnames = make_patchnames(&stack, &commits, patchname_len_limit);
+        (commits, patchnames)
+    } else if let Some(committish) = matches.get_one::<String>("to") {
         let mut target_commit = repo
             .rev_parse_single(committish.as_str())
             .map_err(|_| anyhow!("invalid committish `{committish}`"))?
@@ -187,6 +228,21 @@ fn run(matches: &ArgMatches) -> Result<()> {
 
     assert_eq!(commits.len(), patchnames.len());
 
+    if let (Some(upstream), false) = (upstream.as_ref(), force) {
+        // Commits are ordered newest first, so if any commit is in the upstream, the
+        // oldest one is.
+        if let Some(oldest) = commits.last() {
+            if repo.stupid().is_ancestor(oldest.id, upstream.id)? {
+                return Err(anyhow!(
+                    "cannot uncommit `{}` which is already in upstream `{}`; use \
+                     `--force` to uncommit it anyway",
+                    oldest.id(),
+                    upstream.name,
+                ));
+            }
+        }
+    }
+
     stack
         .setup_transaction()
         .use_index_and_worktree(false)
@@ -206,6 +262,139 @@ fn run(matches: &ArgMatches) -> Result<()> {
     Ok(())
 }
 
+/// Upstream branch that uncommitted commits must not already be in.
+struct Upstream {
+    /// Short name of the upstream branch, e.g. "origin/master".
+    name: String,
+    id: git_repository::ObjectId,
+}
+
+/// Get the configured upstream of the stack's branch, if it exists.
+fn get_upstream(stack: &Stack) -> Result<Option<Upstream>> {
+    let repo = stack.repo;
+    let Some(refname) = repo
+        .stupid()
+        .branch_upstream_refname(stack.get_branch_name())?
+    else {
+        return Ok(None);
+    };
+    let Some(reference) = repo.try_find_reference(refname.as_str())? else {
+        return Ok(None);
+    };
+    let name = reference.name().shorten().to_string();
+    let id = reference.into_fully_peeled_id()?.detach();
+    Ok(Some(Upstream { name, id }))
+}
+
+/// List the commits below the stack base and prompt for the number to uncommit.
+///
+/// Returns the chosen commits, newest first.
+fn pick_commits<'repo>(
+    stack: &Stack<'repo>,
+    matches: &ArgMatches,
+    upstream: Option<&Upstream>,
+    force: bool,
+) -> Result<Vec<Rc<git_repository::Commit<'repo>>>> {
+    let stupid = stack.repo.stupid();
+    let base = stack.base().clone();
+
+    let mut candidates = Vec::new();
+    let mut next_commit = base.clone();
+    while candidates.len() < PICKER_LIMIT && next_commit.parent_ids().count() == 1 {
+        let parent = next_commit.get_parent_commit()?;
+        candidates.push(std::mem::replace(&mut next_commit, Rc::new(parent)));
+    }
+    if candidates.is_empty() {
+        check_commit(&next_commit)?;
+    }
+
+    // Once a commit is in the upstream, so are all of its ancestors. The number of
+    // local commits is thus found by bisecting the candidates.
+    let num_local = if let Some(upstream) = upstream {
+        let (mut lo, mut hi) = (0, candidates.len());
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            if stupid.is_ancestor(candidates[mid].id, upstream.id)? {
+                hi = mid;
+            } else {
+                lo = mid + 1;
+            }
+        }
+        lo
+    } else {
+        candidates.len()
+    };
+
+    let mut stdout = get_color_stdout(matches);
+    if let Some(upstream) = upstream {
+        let ahead = stupid.rev_list(upstream.id, base.id, <Option<Vec<&str>>>::None)?;
+        let behind = stupid.rev_list(base.id, upstream.id, <Option<Vec<&str>>>::None)?;
+        writeln!(
+            stdout,
+            "Branch `{}` is {} ahead of and {} behind upstream `{}`",
+            stack.get_branch_name(),
+            ahead.len(),
+            behind.len(),
+            upstream.name,
+        )?;
+    } else {
+        writeln!(
+            stdout,
+            "Branch `{}` has no upstream",
+            stack.get_branch_name()
+        )?;
+    }
+
+    let num_width = candidates.len().to_string().len();
+    for (i, commit) in candidates.iter().enumerate() {
+        let (status, color) = if i < num_local {
+            ("local", termcolor::Color::Green)
+        } else {
+            ("upstream", termcolor::Color::Yellow)
+        };
+        write!(
+            stdout,
+            "{:>num_width$} {} ",
+            i + 1,
+            commit.id.to_hex_with_len(7),
+        )?;
+        stdout.set_color(termcolor::ColorSpec::new().set_fg(Some(color)))?;
+        write!(stdout, "{status:<8}")?;
+        stdout.reset()?;
+        writeln!(
+            stdout,
+            " {}",
+            commit.decode()?.message_summary().to_str_lossy()
+        )?;
+    }
+
+    let max = if force { candidates.len() } else { num_local };
+    write!(stdout, "Uncommit how many commits? [0-{max}] ")?;
+    stdout.flush()?;
+
+    let stdin = std::io::stdin();
+    let is_interactive = stdin.is_terminal();
+    let mut answer = String::new();
+    let is_eof = stdin.lock().read_line(&mut answer)? == 0;
+    if is_eof || !is_interactive {
+        // The answer was not echoed by a terminal.
+        writeln!(stdout)?;
+    }
+
+    let answer = answer.trim();
+    let number = if answer.is_empty() {
+        0
+    } else {
+        answer
+            .parse::<usize>()
+            .ok()
+            .filter(|&number| number <= candidates.len())
+            .ok_or_else(|| anyhow!("`{answer}` is not a number from 0 to {max}"))?
+    };
+    candidates.truncate(number);
+    Ok(candidates)
+}
+
 fn check_commit(commit: &git_repository::Commit) -> Result<()> {
     if commit.parent_ids().count() == 1 {
         Ok(())
@@ -120,8 +120,12 @@ enum URState {
     Redo(isize),
 }
 
+/// Parse the subject line of a stack state commit message.
+///
+/// Any trailers following the subject are ignored.
 fn parse_undo_redo_message(msg: &[u8]) -> Option<URState> {
-    let fields: Vec<_> = msg.fields_with(|c| c.is_ascii_whitespace()).collect();
+    let subject = msg.lines().next().unwrap_or_default();
+    let fields: Vec<_> = subject.fields_with(|c| c.is_ascii_whitespace()).collect();
     if fields.len() == 2 {
         if let Some(n) = fields[1]
             .to_str()
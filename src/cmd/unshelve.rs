@@ -0,0 +1,111 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg unshelve` implementation.
+
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgMatches};
+
+use super::shelve::{read_shelf, shelf_refname};
+use crate::{
+    argset,
+    color::get_color_stdout,
+    ext::RepositoryExtended,
+    patch::PatchName,
+    stack::{InitializationPolicy, Stack},
+    stupid::Stupid,
+};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "unshelve",
+    category: super::CommandCategory::StackManipulation,
+    make,
+    run,
+};
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Restore patches from a shelf")
+        .long_about(
+            "Restore the patches of a shelf created by `stg shelve` to the stack and \
+             remove the shelf.\n\
+             \n\
+             The patches are pushed, in their original order, on top of the current \
+             stack, which need not be the stack or base the patches were shelved \
+             from. With '--noapply', the patches are instead added to the stack as \
+             unapplied patches.",
+        )
+        .arg(
+            Arg::new("shelf")
+                .help("Name of the shelf to restore")
+                .value_name("shelf")
+                .value_parser(clap::builder::NonEmptyStringValueParser::new())
+                .required(true),
+        )
+        .arg(
+            Arg::new("noapply")
+                .long("noapply")
+                .help("Restore the patches as unapplied")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("keep")
+                .long("keep")
+                .short('k')
+                .help("Keep the shelf after restoring its patches")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(argset::branch_arg())
+        .arg(argset::push_conflicts_arg())
+}
+
+fn run(matches: &ArgMatches) -> Result<()> {
+    let repo = git_repository::Repository::open()?;
+    let opt_branch = argset::get_one_str(matches, "branch");
+    let stack = Stack::from_branch(&repo, opt_branch, InitializationPolicy::AllowUninitialized)?;
+    let allow_push_conflicts =
+        argset::resolve_allow_push_conflicts(&repo.config_snapshot(), matches);
+
+    let shelf_name = matches
+        .get_one::<String>("shelf")
+        .expect("shelf is a required argument");
+    let refname = shelf_refname(shelf_name)?;
+    let shelf = read_shelf(&repo, shelf_name)?;
+
+    let mut patches: Vec<(PatchName, git_repository::ObjectId)> = Vec::new();
+    for patch in &shelf.patches {
+        let commit_id = git_repository::ObjectId::from_hex(patch.commit.as_bytes())
+            .map_err(|_| anyhow!("invalid commit id for patch `{}` in shelf", patch.name))?;
+        patches.push((patch.name.clone(), commit_id));
+    }
+
+    repo.check_repository_state()?;
+    repo.stupid().statuses(None)?.check_conflicts()?;
+    stack.check_head_top_mismatch()?;
+
+    let noapply = matches.get_flag("noapply");
+    stack
+        .setup_transaction()
+        .use_index_and_worktree(opt_branch.is_none())
+        .allow_push_conflicts(allow_push_conflicts)
+        .with_output_stream(get_color_stdout(matches))
+        .transact(|trans| {
+            let mut to_push = Vec::new();
+            for (i, (patchname, commit_id)) in patches.iter().enumerate() {
+                trans.new_unapplied(patchname, *commit_id, i)?;
+                to_push.push(patchname);
+            }
+            if !noapply {
+                trans.push_patches(&to_push, false)?;
+            }
+            Ok(())
+        })
+        .execute("unshelve")?;
+
+    if !matches.get_flag("keep") {
+        if let Some(reference) = repo.try_find_reference(refname.as_ref())? {
+            reference.delete()?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,299 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg verify` implementation.
+
+use std::{collections::BTreeMap, str::FromStr};
+
+use anyhow::{anyhow, Result};
+use bstr::ByteSlice;
+use clap::{Arg, ArgMatches};
+
+use crate::{
+    argset,
+    ext::RepositoryExtended,
+    patch::PatchName,
+    stack::{patch_ref_prefix_from_branch_name, state_refname_from_branch_name, StackState},
+    wrap::Branch,
+};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "verify",
+    category: super::CommandCategory::StackInspection,
+    make,
+    run,
+};
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Verify stack invariants")
+        .long_about(
+            "Verify that the recorded stack state is consistent with the repository.\n\
+             \n\
+             Unlike other StGit commands, which silently repair some inconsistencies \
+             when they open a stack, this command only inspects the stack and reports \
+             each violation it finds on a separate line of the form:\n\
+             \n    \
+             <branch>: <code>: <description>\n\
+             \n\
+             The following violation codes are reported:\n\
+             \n\
+             state-unreadable: the stack state cannot be read.\n\
+             patch-unknown: a patch is listed in the series, but has no recorded \
+             commit.\n\
+             patch-unlisted: a patch has a recorded commit, but is not listed in the \
+             series.\n\
+             patch-duplicate: a patch is listed more than once in the series.\n\
+             applied-parent: an applied patch's parent is not the preceding applied \
+             patch.\n\
+             state-head: the recorded stack head is not the topmost applied patch.\n\
+             head-mismatch: the recorded stack head is not the branch head.\n\
+             patch-ref-missing: a patch does not have a patch reference.\n\
+             patch-ref-mismatch: a patch reference does not point to the patch's \
+             commit.\n\
+             patch-ref-stray: a patch reference does not correspond to any patch.\n\
+             prev-unreachable: a previous stack state in the stack's history cannot \
+             be read.\n\
+             \n\
+             The command exits with a non-zero status if any violations are found, \
+             which makes it suitable for use in continuous integration jobs that guard \
+             shared stack repositories.",
+        )
+        .arg(argset::branch_arg().conflicts_with("all"))
+        .arg(
+            Arg::new("all")
+                .long("all")
+                .short('a')
+                .help("Verify the stacks of all local branches")
+                .action(clap::ArgAction::SetTrue),
+        )
+}
+
+/// A violated stack invariant.
+pub(super) struct Violation {
+    pub(super) code: &'static str,
+    pub(super) description: String,
+}
+
+impl Violation {
+    fn new(code: &'static str, description: String) -> Self {
+        Self { code, description }
+    }
+}
+
+fn run(matches: &ArgMatches) -> Result<()> {
+    let repo = git_repository::Repository::open()?;
+
+    let mut num_violations = 0;
+    if matches.get_flag("all") {
+        let mut branchnames = Vec::new();
+        for local_branch in repo.references()?.local_branches()?.filter_map(Result::ok) {
+            let local_branch = Branch::wrap(local_branch);
+            if let Ok(branchname) = local_branch.get_branch_name() {
+                branchnames.push(branchname.to_string());
+            }
+        }
+        branchnames.sort();
+        for branchname in &branchnames {
+            if let Some(violations) = verify_stack(&repo, branchname)? {
+                num_violations += print_violations(branchname, &violations);
+            }
+        }
+    } else {
+        let branch = repo.get_branch(argset::get_one_str(matches, "branch"))?;
+        let branchname = branch.get_branch_name()?;
+        if let Some(violations) = verify_stack(&repo, branchname)? {
+            num_violations += print_violations(branchname, &violations);
+        } else {
+            return Err(crate::stack::Error::StackNotInitialized(branchname.to_string()).into());
+        }
+    }
+
+    if num_violations == 0 {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "found {num_violations} stack invariant violation{}",
+            if num_violations == 1 { "" } else { "s" }
+        ))
+    }
+}
+
+fn print_violations(branchname: &str, violations: &[Violation]) -> usize {
+    for violation in violations {
+        println!(
+            "{branchname}: {}: {}",
+            violation.code, violation.description
+        );
+    }
+    violations.len()
+}
+
+/// Check the invariants of the stack associated with the given branch.
+///
+/// Returns `None` if the branch does not have an initialized stack.
+pub(super) fn verify_stack(
+    repo: &git_repository::Repository,
+    branchname: &str,
+) -> Result<Option<Vec<Violation>>> {
+    let stack_refname = state_refname_from_branch_name(repo, branchname)?;
+    let state_commit = if let Ok(state_ref) = repo.find_reference(stack_refname.as_str()) {
+        state_ref
+            .into_fully_peeled_id()?
+            .object()?
+            .try_into_commit()?
+    } else {
+        return Ok(None);
+    };
+
+    let mut violations = Vec::new();
+
+    let state = match StackState::from_commit(repo, &state_commit) {
+        Ok(state) => state,
+        Err(e) => {
+            violations.push(Violation::new(
+                "state-unreadable",
+                format!("stack state `{}` cannot be read: {e:#}", state_commit.id),
+            ));
+            return Ok(Some(violations));
+        }
+    };
+
+    let mut listed: BTreeMap<&PatchName, usize> = BTreeMap::new();
+    for patchname in state.all_patches() {
+        *listed.entry(patchname).or_default() += 1;
+    }
+    for (patchname, count) in &listed {
+        if !state.patches.contains_key(*patchname) {
+            violations.push(Violation::new(
+                "patch-unknown",
+                format!("patch `{patchname}` is listed without a recorded commit"),
+            ));
+        }
+        if *count > 1 {
+            violations.push(Violation::new(
+                "patch-duplicate",
+                format!("patch `{patchname}` is listed {count} times"),
+            ));
+        }
+    }
+    for patchname in state.patches.keys() {
+        if !listed.contains_key(patchname) {
+            violations.push(Violation::new(
+                "patch-unlisted",
+                format!("patch `{patchname}` is not listed in the series"),
+            ));
+        }
+    }
+
+    let mut prev_applied: Option<(&PatchName, git_repository::ObjectId)> = None;
+    for patchname in &state.applied {
+        if let Some(patch) = state.patches.get(patchname) {
+            let parent_ids: Vec<_> = patch.commit()?.parent_ids().map(|id| id.detach()).collect();
+            if parent_ids.len() != 1 {
+                violations.push(Violation::new(
+                    "applied-parent",
+                    format!(
+                        "patch `{patchname}` has {} parents instead of one",
+                        parent_ids.len()
+                    ),
+                ));
+            } else if let Some((prev_patchname, prev_commit_id)) = prev_applied {
+                if parent_ids[0] != prev_commit_id {
+                    violations.push(Violation::new(
+                        "applied-parent",
+                        format!(
+                            "parent of patch `{patchname}` is {}, not patch \
+                             `{prev_patchname}` ({prev_commit_id})",
+                            parent_ids[0]
+                        ),
+                    ));
+                }
+            }
+            prev_applied = Some((patchname, patch.commit_id()));
+        }
+    }
+
+    if let Some((top_patchname, top_commit_id)) = prev_applied {
+        if state.head.id != top_commit_id {
+            violations.push(Violation::new(
+                "state-head",
+                format!(
+                    "stack head {} is not topmost applied patch `{top_patchname}` \
+                     ({top_commit_id})",
+                    state.head.id
+                ),
+            ));
+        }
+    }
+
+    let branch_head_id = repo.get_branch(Some(branchname))?.get_commit()?.id;
+    if state.head.id != branch_head_id {
+        violations.push(Violation::new(
+            "head-mismatch",
+            format!(
+                "stack head {} is not branch head {branch_head_id}",
+                state.head.id
+            ),
+        ));
+    }
+
+    let patch_ref_prefix = patch_ref_prefix_from_branch_name(repo, branchname)?;
+    let mut patch_refs: BTreeMap<String, Option<git_repository::ObjectId>> = BTreeMap::new();
+    for reference in repo.references()?.all()?.filter_map(Result::ok) {
+        let refname = reference.name().as_bstr();
+        if let Some(patchname) = refname.strip_prefix(patch_ref_prefix.as_bytes()) {
+            patch_refs.insert(
+                patchname.to_str_lossy().to_string(),
+                reference.target().try_id().map(ToOwned::to_owned),
+            );
+        }
+    }
+    for (patchname, patch) in &state.patches {
+        match patch_refs.remove(patchname.as_ref() as &str) {
+            None => violations.push(Violation::new(
+                "patch-ref-missing",
+                format!("patch `{patchname}` does not have a patch reference"),
+            )),
+            Some(ref_id) if ref_id != Some(patch.commit_id()) => violations.push(Violation::new(
+                "patch-ref-mismatch",
+                format!(
+                    "patch reference for `{patchname}` does not point to {}",
+                    patch.commit_id()
+                ),
+            )),
+            _ => {}
+        }
+    }
+    for refname in patch_refs.keys() {
+        if PatchName::from_str(refname).is_ok() {
+            violations.push(Violation::new(
+                "patch-ref-stray",
+                format!("patch reference `{patch_ref_prefix}{refname}` has no patch"),
+            ));
+        } else {
+            violations.push(Violation::new(
+                "patch-ref-stray",
+                format!("patch reference `{patch_ref_prefix}{refname}` has an invalid name"),
+            ));
+        }
+    }
+
+    let mut prev = state.prev.clone();
+    while let Some(prev_commit) = prev {
+        match StackState::from_commit(repo, &prev_commit) {
+            Ok(prev_state) => prev = prev_state.prev,
+            Err(e) => {
+                violations.push(Violation::new(
+                    "prev-unreachable",
+                    format!(
+                        "previous stack state `{}` cannot be read: {e:#}",
+                        prev_commit.id
+                    ),
+                ));
+                break;
+            }
+        }
+    }
+
+    Ok(Some(violations))
+}
@@ -0,0 +1,149 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Determine and launch the user's editor of choice.
+
+use std::{
+    ffi::{OsStr, OsString},
+    io::Write,
+    path::Path,
+};
+
+use anyhow::{anyhow, Context, Result};
+use clap::ArgMatches;
+
+/// Determine the user's editor of choice for editing patch descriptions.
+///
+/// In order of precedence, the editor is determined from the '--editor' option, the
+/// `GIT_EDITOR` environment variable, the `stgit.editor` and `core.editor` config
+/// variables, and finally the `VISUAL` and `EDITOR` environment variables.
+pub(crate) fn get_editor(
+    matches: &ArgMatches,
+    config: &git_repository::config::Snapshot,
+) -> Result<OsString> {
+    if let Some(editor) = editor_override(matches) {
+        return Ok(editor);
+    }
+    let editor = if let Some(editor) = std::env::var_os("GIT_EDITOR") {
+        editor
+    } else if let Some(editor) = config_editor(config, "stgit.editor")? {
+        editor
+    } else if let Some(editor) = config_editor(config, "core.editor")? {
+        editor
+    } else if let Some(editor) = std::env::var_os("VISUAL") {
+        editor
+    } else if let Some(editor) = std::env::var_os("EDITOR") {
+        editor
+    } else {
+        OsString::from("vi")
+    };
+    Ok(editor)
+}
+
+/// Determine the user's editor of choice for editing sequences of instructions.
+///
+/// Like git, the `GIT_SEQUENCE_EDITOR` environment variable and `sequence.editor`
+/// config variable are used in preference to the regular editor, which is used when
+/// neither is set.
+pub(crate) fn get_sequence_editor(
+    matches: &ArgMatches,
+    config: &git_repository::config::Snapshot,
+) -> Result<OsString> {
+    if let Some(editor) = editor_override(matches) {
+        Ok(editor)
+    } else if let Some(editor) = std::env::var_os("GIT_SEQUENCE_EDITOR") {
+        Ok(editor)
+    } else if let Some(editor) = config_editor(config, "sequence.editor")? {
+        Ok(editor)
+    } else {
+        get_editor(matches, config)
+    }
+}
+
+fn editor_override(matches: &ArgMatches) -> Option<OsString> {
+    matches
+        .try_get_one::<String>("editor")
+        .ok()
+        .flatten()
+        .map(OsString::from)
+}
+
+fn config_editor(config: &git_repository::config::Snapshot, key: &str) -> Result<Option<OsString>> {
+    Ok(config
+        .trusted_path(key)
+        .transpose()?
+        .map(|p| p.as_os_str().to_os_string()))
+}
+
+/// Make determination about whether terminal is dumb.
+///
+/// Dumb terminals do not allow moving the cursor backwards.
+fn is_terminal_dumb() -> bool {
+    if let Some(value) = std::env::var_os("TERM") {
+        value == *"dumb"
+    } else {
+        true
+    }
+}
+
+/// Run `editor` to edit the file at `path`.
+///
+/// The editor command is run with the shell, as git does, such that it may contain
+/// arguments. The path is passed to the shell as a separate positional argument so
+/// that it never needs to be quoted, regardless of any spaces, backslashes, or other
+/// special characters it may contain.
+///
+/// Upon successfully reading back the file's content, the file is deleted.
+pub(crate) fn call_editor<P: AsRef<Path>>(
+    path: P,
+    editor: &OsStr,
+    config: &git_repository::config::Snapshot,
+) -> Result<Vec<u8>> {
+    if editor != ":" {
+        let use_advice = config.boolean("advice.waitingForEditor").unwrap_or(true);
+        let is_dumb = cfg!(target_os = "windows") || is_terminal_dumb();
+
+        if use_advice {
+            let mut stderr = std::io::stderr();
+            write!(
+                stderr,
+                "hint: Waiting for your editor to close the file...{}",
+                if is_dumb { '\n' } else { ' ' }
+            )?;
+            stderr.flush()?;
+        }
+
+        let mut script = OsString::new();
+        script.push(editor);
+        script.push(" \"$@\"");
+
+        let shell = if cfg!(target_os = "windows") {
+            "sh"
+        } else {
+            "/bin/sh"
+        };
+        let status = std::process::Command::new(shell)
+            .arg("-c")
+            .arg(script)
+            .arg(editor)
+            .arg(path.as_ref().as_os_str())
+            .status()
+            .with_context(|| format!("running editor `{}`", editor.to_string_lossy()))?;
+
+        if !status.success() {
+            return Err(anyhow!(
+                "problem with the editor `{}`",
+                editor.to_string_lossy()
+            ));
+        }
+
+        if use_advice && !is_dumb {
+            let mut stderr = std::io::stderr();
+            stderr.write_all("\r\x1b[K".as_bytes()).unwrap_or(());
+            stderr.flush()?;
+        }
+    }
+
+    let buf = std::fs::read(&path)?;
+    std::fs::remove_file(&path)?;
+    Ok(buf)
+}
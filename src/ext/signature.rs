@@ -12,7 +12,7 @@ pub(crate) trait SignatureExtended {
     /// replaced based on command line options.
     ///
     /// The provided `matches` must come from a [`clap::Command`] setup with
-    /// [`crate::patch::edit::add_args()`].
+    /// [`crate::argset::author_args()`].
     fn override_author(self, matches: &clap::ArgMatches) -> git_repository::actor::Signature;
 }
 
@@ -11,6 +11,7 @@ mod alias;
 mod argset;
 mod cmd;
 mod color;
+mod editor;
 mod ext;
 mod hook;
 mod patch;
@@ -41,6 +42,12 @@ const COMMAND_ERROR: i32 = 2;
 /// Process exit code for when a command halts due to merge conflicts.
 const CONFLICT_ERROR: i32 = 3;
 
+/// Process exit code for when the branch head does not match the stack's top patch.
+const HEAD_TOP_MISMATCH_ERROR: i32 = 4;
+
+/// Process exit code for when a command requires an initialized stack.
+const UNINITIALIZED_ERROR: i32 = 5;
+
 /// Create base [`clap::Command`] instance.
 ///
 /// The base [`clap::Command`] returned by this function is intended to be supplemented
@@ -229,7 +236,8 @@ fn main() -> ! {
 /// Exit the program based on the provided [`Result`].
 ///
 /// Error results from conflicts trigger merge conflicts to be printed and an exit code
-/// of [`CONFLICT_ERROR`].
+/// of [`CONFLICT_ERROR`]. Other well-known stack errors map to their own exit codes so
+/// that scripts may distinguish them without parsing the error message.
 fn exit_with_result(result: Result<()>, color_choice: Option<termcolor::ColorChoice>) -> ! {
     let code = match result {
         Ok(()) => 0,
@@ -244,6 +252,9 @@ fn exit_with_result(result: Result<()>, color_choice: Option<termcolor::ColorCho
                 }
                 Some(stack::Error::CheckoutConflicts(_))
                 | Some(stack::Error::CausedConflicts(_)) => CONFLICT_ERROR,
+                Some(stack::Error::HeadTopMismatch) => HEAD_TOP_MISMATCH_ERROR,
+                Some(stack::Error::StackNotInitialized(_))
+                | Some(stack::Error::OrphanedStack { .. }) => UNINITIALIZED_ERROR,
                 _ => COMMAND_ERROR,
             }
         }
@@ -314,7 +325,12 @@ fn execute_command(
             let (_sub_name, sub_matches) = top_matches
                 .subcommand()
                 .expect("this subcommand is already known to be in argv");
-            exit_with_result((command.run)(sub_matches), color_choice)
+            let result =
+                cmd::check_readonly(command, sub_matches).and_then(|()| (command.run)(sub_matches));
+            if result.is_ok() {
+                cmd::auto_mirror(command, sub_matches);
+            }
+            exit_with_result(result, color_choice)
         }
 
         Err(err) => {
@@ -351,7 +367,7 @@ fn execute_shell_alias(
         if user_args.is_empty() {
             command.arg(&alias.command);
         } else {
-            command.arg(&format!("{} \"$@\"", &alias.command));
+            command.arg(format!("{} \"$@\"", &alias.command));
             command.arg(&alias.command);
         }
         command
@@ -371,7 +387,7 @@ fn execute_shell_alias(
                 command.env("GIT_PREFIX", prefix);
             }
             if let Ok(rel_dir) = repo.git_dir().strip_prefix(workdir) {
-                if rel_dir == PathBuf::from(".git") {
+                if rel_dir == ".git" {
                     command.env_remove("GIT_DIR");
                 } else {
                     command.env("GIT_DIR", rel_dir);
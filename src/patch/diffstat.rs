@@ -0,0 +1,720 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Compute and render diffstats.
+//!
+//! This is a native implementation of the `--stat --summary` output of git-diff(1),
+//! computed from a diff in git's format such as output by `git diff-tree -p`. File
+//! names are measured by their display width rather than their length in bytes, such
+//! that the graph column stays aligned for names containing non-ASCII characters.
+
+use bstr::ByteSlice;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Default total width of rendered diffstat lines.
+pub(crate) const DEFAULT_WIDTH: usize = 80;
+
+/// Line counts for one file of a diff.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct FileStat {
+    /// Name to display, with renames and copies shown as `old => new`.
+    name: String,
+    added: usize,
+    deleted: usize,
+    binary: bool,
+}
+
+/// Statistics of a diff, as shown by `git diff --stat --summary`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Diffstat {
+    files: Vec<FileStat>,
+    /// Summary lines for created, deleted, renamed, and mode-changed files.
+    summary: Vec<String>,
+}
+
+impl Diffstat {
+    /// Compute the diffstat of a diff.
+    ///
+    /// Lines outside of file sections, such as a commit message or diffstat
+    /// preceding the diff in a patch email, are ignored.
+    pub(crate) fn from_diff(diff: &[u8]) -> Self {
+        let mut diffstat = Self::default();
+        let mut section: Option<Section> = None;
+        let mut hunk: Option<(usize, usize)> = None;
+
+        for line in diff.lines() {
+            if let Some((old_remaining, new_remaining)) = hunk.as_mut() {
+                let section = section.as_mut().expect("hunks are within a file section");
+                match line.first() {
+                    Some(b'+') => {
+                        section.added += 1;
+                        *new_remaining = new_remaining.saturating_sub(1);
+                    }
+                    Some(b'-') => {
+                        section.deleted += 1;
+                        *old_remaining = old_remaining.saturating_sub(1);
+                    }
+                    Some(b'\\') => {}
+                    _ => {
+                        *old_remaining = old_remaining.saturating_sub(1);
+                        *new_remaining = new_remaining.saturating_sub(1);
+                    }
+                }
+                if *old_remaining == 0 && *new_remaining == 0 {
+                    hunk = None;
+                }
+                continue;
+            }
+
+            if let Some(rest) = line.strip_prefix(b"diff --git ") {
+                if let Some(finished) = section.take() {
+                    finished.finish(&mut diffstat);
+                }
+                section = Some(Section {
+                    git_names: parse_git_names(rest),
+                    ..Default::default()
+                });
+                continue;
+            }
+
+            if let Some(rest) = line.strip_prefix(b"--- ") {
+                // A plain unified diff has no "diff --git" line to start each file.
+                if section
+                    .as_ref()
+                    .map_or(true, |section| section.has_hunks || section.has_paths)
+                {
+                    if let Some(finished) = section.take() {
+                        finished.finish(&mut diffstat);
+                    }
+                    section = Some(Section::default());
+                }
+                let section = section.as_mut().unwrap();
+                section.has_paths = true;
+                section.old_path = parse_path(rest);
+                continue;
+            }
+
+            let Some(section) = section.as_mut() else {
+                continue;
+            };
+
+            if let Some(rest) = line.strip_prefix(b"+++ ") {
+                section.new_path = parse_path(rest);
+            } else if line.starts_with(b"@@ ") {
+                if let Some(counts) = parse_hunk_counts(line) {
+                    section.has_hunks = true;
+                    if counts != (0, 0) {
+                        hunk = Some(counts);
+                    }
+                }
+            } else if line.starts_with(b"Binary files ") || line == b"GIT binary patch" {
+                section.binary = true;
+            } else if let Some(mode) = line.strip_prefix(b"new file mode ") {
+                section.created_mode = Some(mode.to_str_lossy().into_owned());
+            } else if let Some(mode) = line.strip_prefix(b"deleted file mode ") {
+                section.deleted_mode = Some(mode.to_str_lossy().into_owned());
+            } else if let Some(mode) = line.strip_prefix(b"old mode ") {
+                section.old_mode = Some(mode.to_str_lossy().into_owned());
+            } else if let Some(mode) = line.strip_prefix(b"new mode ") {
+                section.new_mode = Some(mode.to_str_lossy().into_owned());
+            } else if let Some(path) = line.strip_prefix(b"rename from ") {
+                section.from = Some(unquote_path(path));
+            } else if let Some(path) = line.strip_prefix(b"rename to ") {
+                section.to = Some(unquote_path(path));
+            } else if let Some(path) = line.strip_prefix(b"copy from ") {
+                section.from = Some(unquote_path(path));
+                section.copy = true;
+            } else if let Some(path) = line.strip_prefix(b"copy to ") {
+                section.to = Some(unquote_path(path));
+                section.copy = true;
+            } else if let Some(score) = line.strip_prefix(b"similarity index ") {
+                section.score = parse_score(score);
+            } else if let Some(score) = line.strip_prefix(b"dissimilarity index ") {
+                section.score = parse_score(score);
+            }
+        }
+
+        if let Some(finished) = section.take() {
+            finished.finish(&mut diffstat);
+        }
+
+        diffstat
+    }
+
+    /// Render the diffstat and summary as `git diff --stat=<width> --summary` would.
+    ///
+    /// The lines of the diffstat are fit within `width` columns, truncating file
+    /// names from the left and scaling the graph as needed. When `count` is given,
+    /// only the first `count` files are listed, but the totals line accounts for all
+    /// files. An empty diff renders as an empty string.
+    pub(crate) fn render(&self, width: usize, count: Option<usize>, use_color: bool) -> String {
+        let mut out = String::new();
+        if self.files.is_empty() {
+            return out;
+        }
+
+        let shown =
+            &self.files[..count.map_or(self.files.len(), |count| count.min(self.files.len()))];
+
+        let mut max_len = 0;
+        let mut max_change = 0;
+        let mut number_width = 0;
+        let mut bin_width = 0;
+        for file in shown {
+            max_len = max_len.max(file.name.width());
+            if file.binary {
+                // Room for "Bin XXX -> YYY bytes", as git reserves it.
+                bin_width = bin_width.max(16);
+                number_width = 3;
+            } else {
+                max_change = max_change.max(file.added + file.deleted);
+            }
+        }
+
+        let number_width = number_width.max(decimal_width(max_change)) as isize;
+        let width = (width as isize).max(16 + 6 + number_width);
+        let mut graph_width = if max_change + 4 > bin_width {
+            max_change as isize
+        } else {
+            bin_width as isize - 4
+        };
+        let mut name_width = max_len as isize;
+        if name_width + number_width + 6 + graph_width > width {
+            if graph_width > width * 3 / 8 - number_width - 6 {
+                graph_width = (width * 3 / 8 - number_width - 6).max(6);
+            }
+            if name_width > width - number_width - 6 - graph_width {
+                name_width = width - number_width - 6 - graph_width;
+            } else {
+                graph_width = width - number_width - 6 - name_width;
+            }
+        }
+        let name_width = name_width.max(0) as usize;
+        let graph_width = graph_width.max(0) as usize;
+        let number_width = number_width as usize;
+
+        for file in shown {
+            let (prefix, name, padding) = fit_name(&file.name, name_width);
+            out.push_str(&format!(" {prefix}{name}{:padding$} | ", ""));
+            if file.binary {
+                out.push_str(&format!("{:>number_width$}\n", "Bin"));
+                continue;
+            }
+
+            let changes = file.added + file.deleted;
+            let (mut added, mut deleted) = (file.added, file.deleted);
+            if graph_width <= max_change {
+                let mut total = scale_linear(added + deleted, graph_width, max_change);
+                if total < 2 && added > 0 && deleted > 0 {
+                    total = 2;
+                }
+                if added < deleted {
+                    added = scale_linear(added, graph_width, max_change);
+                    deleted = total - added;
+                } else {
+                    deleted = scale_linear(deleted, graph_width, max_change);
+                    added = total - deleted;
+                }
+            }
+            out.push_str(&format!("{changes:>number_width$}"));
+            if changes > 0 {
+                out.push(' ');
+            }
+            push_graph(&mut out, '+', added, use_color.then_some("\x1b[32m"));
+            push_graph(&mut out, '-', deleted, use_color.then_some("\x1b[31m"));
+            out.push('\n');
+        }
+
+        if shown.len() < self.files.len() {
+            out.push_str(" ...\n");
+        }
+
+        let insertions: usize = self.files.iter().map(|file| file.added).sum();
+        let deletions: usize = self.files.iter().map(|file| file.deleted).sum();
+        let files = self.files.len();
+        out.push_str(&format!(
+            " {files} file{} changed",
+            if files == 1 { "" } else { "s" }
+        ));
+        if insertions > 0 || deletions == 0 {
+            out.push_str(&format!(
+                ", {insertions} insertion{}(+)",
+                if insertions == 1 { "" } else { "s" }
+            ));
+        }
+        if deletions > 0 || insertions == 0 {
+            out.push_str(&format!(
+                ", {deletions} deletion{}(-)",
+                if deletions == 1 { "" } else { "s" }
+            ));
+        }
+        out.push('\n');
+
+        for line in &self.summary {
+            out.push(' ');
+            out.push_str(line);
+            out.push('\n');
+        }
+
+        out
+    }
+}
+
+/// Headers and line counts gathered for one file section of a diff.
+#[derive(Default)]
+struct Section {
+    git_names: Option<(Vec<u8>, Vec<u8>)>,
+    has_paths: bool,
+    old_path: Option<Vec<u8>>,
+    new_path: Option<Vec<u8>>,
+    from: Option<Vec<u8>>,
+    to: Option<Vec<u8>>,
+    copy: bool,
+    score: Option<u32>,
+    created_mode: Option<String>,
+    deleted_mode: Option<String>,
+    old_mode: Option<String>,
+    new_mode: Option<String>,
+    has_hunks: bool,
+    binary: bool,
+    added: usize,
+    deleted: usize,
+}
+
+impl Section {
+    fn finish(self, diffstat: &mut Diffstat) {
+        let (git_old, git_new) = match self.git_names {
+            Some((old, new)) => (Some(old), Some(new)),
+            None => (None, None),
+        };
+        let old_name = self.from.clone().or(self.old_path).or(git_old);
+        let new_name = self
+            .to
+            .clone()
+            .or(self.new_path)
+            .or(git_new)
+            .or_else(|| old_name.clone());
+        let Some(new_name) = new_name else {
+            return;
+        };
+        let new_name = new_name.to_str_lossy().into_owned();
+
+        let name = if let (Some(from), Some(to)) = (&self.from, &self.to) {
+            pprint_rename(&from.to_str_lossy(), &to.to_str_lossy())
+        } else {
+            new_name.clone()
+        };
+
+        let mode_change = match (&self.old_mode, &self.new_mode) {
+            (Some(old_mode), Some(new_mode)) if old_mode != new_mode => {
+                Some(format!("mode change {old_mode} => {new_mode}"))
+            }
+            _ => None,
+        };
+        if let Some(mode) = &self.deleted_mode {
+            let old_name =
+                old_name.map_or(new_name.clone(), |name| name.to_str_lossy().into_owned());
+            diffstat
+                .summary
+                .push(format!("delete mode {mode} {old_name}"));
+        } else if let Some(mode) = &self.created_mode {
+            diffstat
+                .summary
+                .push(format!("create mode {mode} {new_name}"));
+        } else if self.from.is_some() && self.to.is_some() {
+            let kind = if self.copy { "copy" } else { "rename" };
+            let score = self.score.unwrap_or(0);
+            diffstat.summary.push(format!("{kind} {name} ({score}%)"));
+            diffstat.summary.extend(mode_change);
+        } else if let Some(score) = self.score {
+            diffstat
+                .summary
+                .push(format!("rewrite {new_name} ({score}%)"));
+            diffstat.summary.extend(mode_change);
+        } else if let Some(mode_change) = mode_change {
+            diffstat.summary.push(format!("{mode_change} {new_name}"));
+        }
+
+        diffstat.files.push(FileStat {
+            name,
+            added: self.added,
+            deleted: self.deleted,
+            binary: self.binary,
+        });
+    }
+}
+
+/// Get the old and new names from the remainder of a "diff --git" line.
+///
+/// Unless the names are quoted, they are only known to be separated by a space, so
+/// names are only found when both sides name the same file. Renames and copies
+/// otherwise get their names from the "rename from" and "rename to" lines.
+fn parse_git_names(rest: &[u8]) -> Option<(Vec<u8>, Vec<u8>)> {
+    if rest.starts_with(b"\"") {
+        let (old, after) = unquote(rest)?;
+        let after = after.strip_prefix(b" ")?;
+        let new = if after.starts_with(b"\"") {
+            unquote(after)?.0
+        } else {
+            after.to_vec()
+        };
+        Some((strip_prefix_component(&old), strip_prefix_component(&new)))
+    } else if let Some(pos) = rest.find(b" \"") {
+        let (new, _) = unquote(&rest[pos + 1..])?;
+        Some((
+            strip_prefix_component(&rest[..pos]),
+            strip_prefix_component(&new),
+        ))
+    } else if rest.len() % 2 == 1 && rest[rest.len() / 2] == b' ' {
+        let old = strip_prefix_component(&rest[..rest.len() / 2]);
+        let new = strip_prefix_component(&rest[rest.len() / 2 + 1..]);
+        (old == new).then_some((old, new))
+    } else {
+        None
+    }
+}
+
+/// Parse the path of a "---" or "+++" line, which is `None` for "/dev/null".
+fn parse_path(rest: &[u8]) -> Option<Vec<u8>> {
+    let path = if rest.starts_with(b"\"") {
+        unquote(rest)?.0
+    } else {
+        // Non-git diffs may follow the path with a tab and timestamp.
+        rest.split_str(b"\t").next().unwrap_or_default().to_vec()
+    };
+    if path == b"/dev/null" {
+        None
+    } else {
+        Some(strip_prefix_component(&path))
+    }
+}
+
+/// Remove the leading directory, e.g. `a/` or `b/`, from a diff path.
+fn strip_prefix_component(path: &[u8]) -> Vec<u8> {
+    path.find_byte(b'/')
+        .map_or(path, |pos| &path[pos + 1..])
+        .to_vec()
+}
+
+/// Unquote a path that may be quoted, as in "rename from" and "rename to" lines.
+fn unquote_path(path: &[u8]) -> Vec<u8> {
+    if path.starts_with(b"\"") {
+        if let Some((unquoted, _)) = unquote(path) {
+            return unquoted;
+        }
+    }
+    path.to_vec()
+}
+
+/// Unquote a C-style quoted string, returning it along with the remaining input.
+fn unquote(input: &[u8]) -> Option<(Vec<u8>, &[u8])> {
+    let mut unquoted = Vec::new();
+    let mut pos = 1;
+    loop {
+        match *input.get(pos)? {
+            b'"' => return Some((unquoted, &input[pos + 1..])),
+            b'\\' => {
+                pos += 1;
+                let c = *input.get(pos)?;
+                let byte = match c {
+                    b'a' => 0x07,
+                    b'b' => 0x08,
+                    b't' => b'\t',
+                    b'n' => b'\n',
+                    b'v' => 0x0b,
+                    b'f' => 0x0c,
+                    b'r' => b'\r',
+                    b'0'..=b'3' => {
+                        let digits = input.get(pos..pos + 3)?;
+                        if !digits.iter().all(|d| (b'0'..=b'7').contains(d)) {
+                            return None;
+                        }
+                        pos += 2;
+                        digits.iter().fold(0u8, |n, d| (n << 3) | (d - b'0'))
+                    }
+                    _ => c,
+                };
+                unquoted.push(byte);
+            }
+            c => unquoted.push(c),
+        }
+        pos += 1;
+    }
+}
+
+/// Get the old and new line counts from a hunk header of the form
+/// "@@ -l[,s] +l[,s] @@".
+fn parse_hunk_counts(line: &[u8]) -> Option<(usize, usize)> {
+    let mut ranges = line.strip_prefix(b"@@ ")?.split_str(b" ");
+    let old = ranges.next()?.strip_prefix(b"-")?;
+    let new = ranges.next()?.strip_prefix(b"+")?;
+    let count = |range: &[u8]| -> Option<usize> {
+        match range.split_once_str(b",") {
+            Some((_, count)) => count.to_str().ok()?.parse().ok(),
+            None => Some(1),
+        }
+    };
+    Some((count(old)?, count(new)?))
+}
+
+/// Parse a similarity score such as "90%".
+fn parse_score(score: &[u8]) -> Option<u32> {
+    score.strip_suffix(b"%")?.to_str().ok()?.parse().ok()
+}
+
+/// Format a rename or copy, abbreviating the common leading directories and common
+/// trailing path components as `dir/{old => new}/name`.
+fn pprint_rename(old: &str, new: &str) -> String {
+    let (a, b) = (old.as_bytes(), new.as_bytes());
+
+    let mut pfx_length = 0;
+    for (i, (ca, cb)) in a.iter().zip(b).enumerate() {
+        if ca != cb {
+            break;
+        }
+        if *ca == b'/' {
+            pfx_length = i + 1;
+        }
+    }
+
+    // The common suffix must start with a slash. When there is a common prefix, it
+    // ends with a slash which the suffix may share.
+    let byte_at = |s: &[u8], i: isize| s.get(i as usize).copied().unwrap_or(0);
+    let adjust = isize::from(pfx_length > 0);
+    let lower = pfx_length as isize - adjust;
+    let mut sfx_length = 0;
+    let (mut i, mut j) = (a.len() as isize, b.len() as isize);
+    while lower <= i && lower <= j && byte_at(a, i) == byte_at(b, j) {
+        if byte_at(a, i) == b'/' {
+            sfx_length = a.len() - i as usize;
+        }
+        i -= 1;
+        j -= 1;
+    }
+
+    let a_midlen = a.len().saturating_sub(pfx_length + sfx_length);
+    let b_midlen = b.len().saturating_sub(pfx_length + sfx_length);
+    let a_mid = String::from_utf8_lossy(&a[pfx_length..pfx_length + a_midlen]);
+    let b_mid = String::from_utf8_lossy(&b[pfx_length..pfx_length + b_midlen]);
+    if pfx_length + sfx_length > 0 {
+        format!(
+            "{}{{{a_mid} => {b_mid}}}{}",
+            String::from_utf8_lossy(&a[..pfx_length]),
+            String::from_utf8_lossy(&a[a.len() - sfx_length..]),
+        )
+    } else {
+        format!("{a_mid} => {b_mid}")
+    }
+}
+
+/// Fit a name within `width` columns.
+///
+/// Names that are too wide lose characters from the left, up to the next directory
+/// separator, and are marked with a "..." prefix. Returns the prefix, the possibly
+/// truncated name, and the padding needed to fill `width` columns.
+fn fit_name(name: &str, width: usize) -> (&'static str, &str, usize) {
+    let mut name_width = name.width();
+    if name_width <= width {
+        return ("", name, width - name_width);
+    }
+
+    let width = width.saturating_sub(3);
+    let mut start = name.len();
+    for (pos, c) in name.char_indices() {
+        if name_width <= width {
+            start = pos;
+            break;
+        }
+        name_width -= c.width().unwrap_or(0);
+    }
+    let mut truncated = &name[start..];
+    if let Some(slash) = truncated.find('/') {
+        truncated = &truncated[slash..];
+    }
+    ("...", truncated, width.saturating_sub(truncated.width()))
+}
+
+fn scale_linear(n: usize, width: usize, max_change: usize) -> usize {
+    if n == 0 {
+        0
+    } else {
+        1 + n * (width - 1) / max_change
+    }
+}
+
+fn decimal_width(n: usize) -> usize {
+    n.to_string().len()
+}
+
+fn push_graph(out: &mut String, c: char, count: usize, color: Option<&str>) {
+    if count > 0 {
+        if let Some(color) = color {
+            out.push_str(color);
+        }
+        out.extend(std::iter::repeat(c).take(count));
+        if color.is_some() {
+            out.push_str("\x1b[m");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modified_and_created_files() {
+        let diff = b"\
+diff --git a/a.txt b/a.txt
+index 1111111..2222222 100644
+--- a/a.txt
++++ b/a.txt
+@@ -1,3 +1,3 @@
+ one
+-two
++TWO
+ three
+diff --git a/new file.txt b/new file.txt
+new file mode 100644
+index 0000000..3333333
+--- /dev/null
++++ b/new file.txt\t
+@@ -0,0 +1,2 @@
++first
++second
+";
+        let diffstat = Diffstat::from_diff(diff);
+        assert_eq!(
+            diffstat.render(80, None, false),
+            " a.txt        | 2 +-\n \
+             new file.txt | 2 ++\n \
+             2 files changed, 3 insertions(+), 1 deletion(-)\n \
+             create mode 100644 new file.txt\n"
+        );
+    }
+
+    #[test]
+    fn trailing_signature_is_not_counted() {
+        let diff = b"\
+--- a/a.txt
++++ b/a.txt
+@@ -1 +1 @@
+-old
++new
+--
+2.40.0
+";
+        assert_eq!(
+            Diffstat::from_diff(diff).render(80, None, false),
+            " a.txt | 2 +-\n 1 file changed, 1 insertion(+), 1 deletion(-)\n"
+        );
+    }
+
+    #[test]
+    fn renames_and_mode_changes() {
+        let diff = b"\
+diff --git a/src/old/name.rs b/src/new/name.rs
+similarity index 100%
+rename from src/old/name.rs
+rename to src/new/name.rs
+diff --git a/run.sh b/run.sh
+old mode 100644
+new mode 100755
+diff --git a/empty b/empty
+deleted file mode 100644
+index e69de29..0000000
+";
+        assert_eq!(
+            Diffstat::from_diff(diff).render(80, None, false),
+            " src/{old => new}/name.rs | 0\n \
+             run.sh                   | 0\n \
+             empty                    | 0\n \
+             3 files changed, 0 insertions(+), 0 deletions(-)\n \
+             rename src/{old => new}/name.rs (100%)\n \
+             mode change 100644 => 100755 run.sh\n \
+             delete mode 100644 empty\n"
+        );
+    }
+
+    #[test]
+    fn pprint_renames() {
+        assert_eq!(pprint_rename("a.txt", "b.txt"), "a.txt => b.txt");
+        assert_eq!(
+            pprint_rename("dir/a.txt", "dir/b.txt"),
+            "dir/{a.txt => b.txt}"
+        );
+        assert_eq!(pprint_rename("a/x/f", "b/x/f"), "{a => b}/x/f");
+        assert_eq!(pprint_rename("d/f", "d/e/f"), "d/{ => e}/f");
+    }
+
+    #[test]
+    fn wide_names_are_aligned() {
+        let diff = b"\
+diff --git \"a/\\346\\227\\245.txt\" \"b/\\346\\227\\245.txt\"
+--- \"a/\\346\\227\\245.txt\"
++++ \"b/\\346\\227\\245.txt\"
+@@ -1 +1,2 @@
+ x
++y
+diff --git a/caf\xc3\xa9.txt b/caf\xc3\xa9.txt
+--- a/caf\xc3\xa9.txt
++++ b/caf\xc3\xa9.txt
+@@ -1 +1,2 @@
+ x
++y
+";
+        assert_eq!(
+            Diffstat::from_diff(diff).render(80, None, false),
+            " \u{65e5}.txt   | 1 +\n \
+             caf\u{e9}.txt | 1 +\n \
+             2 files changed, 2 insertions(+)\n"
+        );
+    }
+
+    #[test]
+    fn narrow_width_truncates_and_scales() {
+        let mut diff = b"\
+diff --git a/some/long/directory/name/file.txt b/some/long/directory/name/file.txt
+--- a/some/long/directory/name/file.txt
++++ b/some/long/directory/name/file.txt
+@@ -0,0 +1,100 @@
+"
+        .to_vec();
+        for _ in 0..100 {
+            diff.extend_from_slice(b"+line\n");
+        }
+        assert_eq!(
+            Diffstat::from_diff(&diff).render(40, None, false),
+            " .../name/file.txt         | 100 ++++++\n \
+             1 file changed, 100 insertions(+)\n"
+        );
+    }
+
+    #[test]
+    fn count_limits_listed_files() {
+        let diff = b"\
+diff --git a/a b/a
+--- a/a
++++ b/a
+@@ -1 +1 @@
+-a
++A
+diff --git a/b b/b
+--- a/b
++++ b/b
+@@ -1 +1 @@
+-b
++B
+diff --git a/c b/c
+Binary files a/c and b/c differ
+";
+        assert_eq!(
+            Diffstat::from_diff(diff).render(80, Some(1), false),
+            " a | 2 +-\n ...\n 3 files changed, 2 insertions(+), 2 deletions(-)\n"
+        );
+        assert_eq!(
+            Diffstat::from_diff(diff).render(80, None, false),
+            " a |   2 +-\n b |   2 +-\n c | Bin\n \
+             3 files changed, 2 insertions(+), 2 deletions(-)\n"
+        );
+    }
+}
@@ -7,9 +7,7 @@ use clap::{
     Arg, ValueHint,
 };
 
-use crate::{argset, ext::TimeExtended};
-
-use super::parse::{parse_email, parse_name, parse_name_email2};
+use crate::argset;
 
 /// Add patch editing options to a StGit command.
 pub(crate) fn add_args(
@@ -32,7 +30,8 @@ pub(crate) fn add_args(
                 .short('d')
                 .help("Show diff when editing patch description")
                 .action(clap::ArgAction::SetTrue),
-        );
+        )
+        .arg(argset::editor_arg());
     let command = if add_message_opts {
         command
             .arg(
@@ -151,6 +150,24 @@ pub(crate) fn add_args(
                 .require_equals(true)
                 .action(clap::ArgAction::Append),
         )
+        .arg(
+            Arg::new("fixes")
+                .long("fixes")
+                .help("Add Fixes message trailer for <revision>")
+                .long_help(
+                    "Add \"Fixes\" message trailer referring to the commit given by \
+                     <revision>, formatted as `Fixes: <sha> (\"<subject>\")`.\n\
+                     \n\
+                     The abbreviated sha has at least 12 hex digits, more if \
+                     `core.abbrev` specifies a greater length or if needed to be \
+                     unambiguous. This option may be provided multiple times.",
+                )
+                .value_name("revision")
+                .num_args(1)
+                .action(clap::ArgAction::Append)
+                .value_parser(builder::NonEmptyStringValueParser::new())
+                .value_hint(ValueHint::Other),
+        )
         .arg(
             Arg::new("sign-by")
                 .long("sign-by")
@@ -181,50 +198,7 @@ pub(crate) fn add_args(
                 .value_name("value")
                 .value_hint(ValueHint::EmailAddress),
         )
-        .arg(
-            Arg::new("author")
-                .long("author")
-                .help("Set the author \"name <email>\"")
-                .value_name("name-and-email")
-                .num_args(1)
-                .value_parser(ValueParser::new(parse_name_email2))
-                .value_hint(ValueHint::Other),
-        )
-        .arg(
-            Arg::new("authname")
-                .long("authname")
-                .help("Set the author name")
-                .value_name("name")
-                .num_args(1)
-                .value_hint(ValueHint::Other)
-                .value_parser(ValueParser::new(parse_name))
-                .conflicts_with("author"),
-        )
-        .arg(
-            Arg::new("authemail")
-                .long("authemail")
-                .help("Set the author email")
-                .value_name("email")
-                .num_args(1)
-                .value_hint(ValueHint::EmailAddress)
-                .value_parser(ValueParser::new(parse_email))
-                .conflicts_with("author"),
-        )
-        .arg(
-            Arg::new("authdate")
-                .long("authdate")
-                .help("Set the author date")
-                .long_help(
-                    "Set the date the patch was authored.\n\
-                     \n\
-                     Use \"now\" to use the current time and date.",
-                )
-                .value_name("date")
-                .num_args(1)
-                .value_parser(ValueParser::new(git_repository::actor::Time::parse_time))
-                .value_hint(ValueHint::Other),
-        )
-        .arg(argset::committer_date_is_author_date_arg());
+        .args(argset::author_args());
     if add_save_template {
         command.arg(
             Arg::new("save-template")
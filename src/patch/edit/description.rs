@@ -7,7 +7,10 @@ use std::io::Write;
 use anyhow::{anyhow, Context, Result};
 use bstr::{BString, ByteSlice};
 
-use crate::{ext::TimeExtended, patch::PatchName};
+use crate::{
+    ext::TimeExtended,
+    patch::{meta::PatchMeta, PatchName},
+};
 
 #[derive(Clone, PartialEq, Eq)]
 pub(super) struct DiffBuffer(pub(super) Vec<u8>);
@@ -57,10 +60,23 @@ pub(super) struct EditablePatchDescription {
     ///
     /// Unlike all the other fields, the diff *does not* have to be valid UTF-8.
     pub diff: Option<DiffBuffer>,
+
+    /// Optional patch metadata to present in the metadata section.
+    ///
+    /// The metadata section is only written when this is `Some` and non-empty, such
+    /// that descriptions of patches without metadata retain their prior format.
+    pub meta: Option<PatchMeta>,
 }
 
 const CUT_LINE: &str = "# ------------------------ >8 ------------------------\n";
 
+/// Start of the metadata section, which extends to the cut line or end of file.
+const META_LINE: &str = "# ---------------------- metadata ----------------------\n";
+
+const META_INSTRUCTION: &str = "\
+    # Patch metadata as \"Key: value\" lines, e.g. \"Label: wip\". Keys may be\n\
+    # repeated. Metadata is not part of the commit message.\n";
+
 impl EditablePatchDescription {
     /// Write user-editable patch description to the provided stream.
     pub(super) fn write<S: Write>(&self, stream: &mut S) -> Result<()> {
@@ -90,6 +106,11 @@ impl EditablePatchDescription {
         } else {
             writeln!(stream)?;
         }
+        if let Some(meta) = self.meta.as_ref().filter(|meta| !meta.is_empty()) {
+            stream.write_all(META_LINE.as_bytes())?;
+            stream.write_all(META_INSTRUCTION.as_bytes())?;
+            stream.write_all(&meta.to_bytes())?;
+        }
         if let Some(diff) = self.diff.as_ref() {
             if let Some(diff_instruction) = self.diff_instruction {
                 write!(stream, "{diff_instruction}")?;
@@ -140,6 +161,12 @@ pub(super) struct EditedPatchDescription {
     ///
     /// Unlike all the other fields, the diff *does not* have to be valid UTF-8.
     pub diff: Option<DiffBuffer>,
+
+    /// Patch metadata.
+    ///
+    /// This is `None` if the metadata section is absent from the user-edited buffer,
+    /// which is the case for patch descriptions predating the metadata section.
+    pub meta: Option<PatchMeta>,
 }
 
 impl TryFrom<&[u8]> for EditedPatchDescription {
@@ -154,6 +181,10 @@ impl TryFrom<&[u8]> for EditedPatchDescription {
     /// lines of the message. This rigidity is done to allow the message, which follows
     /// these headers, to potentially contain strings such as "Patch:".
     ///
+    /// The optional metadata section starts with the metadata marker line and extends
+    /// to the cut line or the end of the buffer. Each non-comment line in the section
+    /// is a `Key: value` metadata field.
+    ///
     /// If all headers are absent and the trimmed message is empty, an error is
     /// returned. Blanking-out the headers and message is thus a mechanism for the user
     /// to abort the interactive edit.
@@ -165,6 +196,7 @@ impl TryFrom<&[u8]> for EditedPatchDescription {
         let mut consuming_message: bool = false;
         let mut consecutive_empty: usize = 0;
         let mut message = String::new();
+        let mut raw_meta: Option<String> = None;
         let mut pos: usize = 0;
 
         for (line_num, line) in buf.split_inclusive(|&b| b == b'\n').enumerate() {
@@ -172,6 +204,9 @@ impl TryFrom<&[u8]> for EditedPatchDescription {
             if line.starts_with(CUT_LINE.as_bytes()) {
                 consume_diff = true;
                 break;
+            } else if line.starts_with(META_LINE.as_bytes()) {
+                raw_meta.get_or_insert_with(String::new);
+                continue;
             } else if line.starts_with(b"#") {
                 continue;
             }
@@ -181,7 +216,9 @@ impl TryFrom<&[u8]> for EditedPatchDescription {
                 .map_err(|_| anyhow!("patch description contains non-UTF-8 data"))?;
             let trimmed = line.trim_end();
 
-            if consuming_message {
+            if let Some(raw_meta) = raw_meta.as_mut() {
+                raw_meta.push_str(line);
+            } else if consuming_message {
                 if trimmed.is_empty() {
                     if consecutive_empty == 0 {
                         message.push('\n');
@@ -273,6 +310,12 @@ impl TryFrom<&[u8]> for EditedPatchDescription {
             message.pop();
         }
 
+        let meta = if let Some(raw_meta) = raw_meta {
+            Some(PatchMeta::parse(raw_meta.as_bytes()).context("patch description metadata")?)
+        } else {
+            None
+        };
+
         if message.trim().is_empty() {
             message.clear();
         }
@@ -302,6 +345,7 @@ impl TryFrom<&[u8]> for EditedPatchDescription {
             author,
             message,
             diff,
+            meta,
         })
     }
 }
@@ -358,6 +402,7 @@ mod tests {
                 },
             );
         }
+        assert_eq!(edited.meta, editable.meta);
     }
 
     fn compare_edited_descs(desc0: &EditedPatchDescription, desc1: &EditedPatchDescription) {
@@ -394,6 +439,7 @@ mod tests {
                 if desc1.diff.is_some() { "Some" } else { "None" },
             );
         }
+        assert_eq!(desc0.meta, desc1.meta);
     }
 
     #[test]
@@ -409,6 +455,7 @@ mod tests {
             instruction: Some("# Instruction\n"),
             diff_instruction: None,
             diff: None,
+            meta: None,
         };
 
         let mut buf: Vec<u8> = vec![];
@@ -443,6 +490,7 @@ mod tests {
             instruction: Some("# Instruction\n"),
             diff_instruction: None,
             diff: None,
+            meta: None,
         };
 
         let mut buf: Vec<u8> = vec![];
@@ -484,6 +532,7 @@ mod tests {
             instruction: Some("# Instruction\n"),
             diff_instruction: None,
             diff: None,
+            meta: None,
         };
 
         let mut buf: Vec<u8> = vec![];
@@ -536,6 +585,7 @@ mod tests {
                   \\ No newline at end of file\n"
                     .to_vec(),
             )),
+            meta: None,
         };
 
         let mut buf: Vec<u8> = vec![];
@@ -571,6 +621,115 @@ mod tests {
         compare_patch_descs(&edited_desc, &pd);
     }
 
+    #[test]
+    fn with_meta_and_diff() {
+        let pd = EditablePatchDescription {
+            patchname: Some("patch".parse::<PatchName>().unwrap()),
+            author: Some(git_repository::actor::Signature {
+                name: BString::from("The Author"),
+                email: BString::from("author@example.com"),
+                time: git_repository::actor::Time::new(987654321, 21600),
+            }),
+            message: "Subject\n".to_string(),
+            instruction: Some("# Instruction\n"),
+            diff_instruction: Some("# Diff instruction\n"),
+            diff: Some(DiffBuffer(
+                b"diff --git a/foo.txt b/foo.txt\n\
+                  index ce01362..a21e91b 100644\n\
+                  --- a/foo.txt\n\
+                  +++ b/foo.txt\n\
+                  @@ -1 +1 @@\n\
+                  -hello\n\
+                  +goodbye\n"
+                    .to_vec(),
+            )),
+            meta: Some(
+                PatchMeta::parse(
+                    b"Label: wip\n\
+                      Depends: other-patch\n\
+                      To: Some One <someone@example.com>\n\
+                      X-Unknown-Key: preserved: as-is\n",
+                )
+                .unwrap(),
+            ),
+        };
+
+        let mut buf: Vec<u8> = vec![];
+        pd.write(&mut buf).unwrap();
+
+        assert_eq!(
+            std::str::from_utf8(buf.as_slice()).unwrap(),
+            "Patch:  patch\n\
+             Author: The Author <author@example.com>\n\
+             Date:   2001-04-19 10:25:21 +0600\n\
+             \n\
+             Subject\n\
+             \n\
+             # Instruction\n\
+             # ---------------------- metadata ----------------------\n\
+             # Patch metadata as \"Key: value\" lines, e.g. \"Label: wip\". Keys may be\n\
+             # repeated. Metadata is not part of the commit message.\n\
+             Label: wip\n\
+             Depends: other-patch\n\
+             To: Some One <someone@example.com>\n\
+             X-Unknown-Key: preserved: as-is\n\
+             # Diff instruction\n\
+             # ------------------------ >8 ------------------------\n\
+             # Do not modify or remove the line above.\n\
+             diff --git a/foo.txt b/foo.txt\n\
+             index ce01362..a21e91b 100644\n\
+             --- a/foo.txt\n\
+             +++ b/foo.txt\n\
+             @@ -1 +1 @@\n\
+             -hello\n\
+             +goodbye\n",
+        );
+
+        let edited_desc = EditedPatchDescription::try_from(buf.as_slice()).unwrap();
+
+        compare_patch_descs(&edited_desc, &pd);
+    }
+
+    #[test]
+    fn empty_meta_section() {
+        let description = b"\
+            Patch: patch\n\
+            \n\
+            Subject\n\
+            # ---------------------- metadata ----------------------\n\
+            \n";
+
+        let edited_desc = EditedPatchDescription::try_from(description.as_slice()).unwrap();
+        assert_eq!(edited_desc.message, "Subject\n");
+        assert!(edited_desc.meta.unwrap().is_empty());
+    }
+
+    #[test]
+    fn no_meta_section() {
+        let description = b"\
+            Patch: patch\n\
+            \n\
+            Subject\n\
+            \n\
+            Label: not metadata\n";
+
+        let edited_desc = EditedPatchDescription::try_from(description.as_slice()).unwrap();
+        assert_eq!(edited_desc.message, "Subject\n\nLabel: not metadata\n");
+        assert!(edited_desc.meta.is_none());
+    }
+
+    #[test]
+    fn invalid_meta() {
+        let description = b"\
+            Patch: patch\n\
+            \n\
+            Subject\n\
+            # ---------------------- metadata ----------------------\n\
+            not a key value line\n";
+
+        assert!(EditedPatchDescription::try_from(description.as_slice()).is_err());
+    }
+
     #[test]
     fn with_extra_comments() {
         let patch_desc = EditablePatchDescription {
@@ -590,6 +749,7 @@ mod tests {
             instruction: Some("# Instruction\n"),
             diff_instruction: None,
             diff: None,
+            meta: None,
         };
 
         let mut buf: Vec<u8> = vec![];
@@ -653,6 +813,7 @@ mod tests {
             })),
             message: "Subject\n".to_string(),
             diff: None,
+            meta: None,
         };
 
         compare_edited_descs(&edited_desc, &expected);
@@ -690,6 +851,7 @@ mod tests {
             })),
             message: "Subject\n".to_string(),
             diff: None,
+            meta: None,
         };
 
         compare_edited_descs(&edited_desc, &expected);
@@ -719,6 +881,7 @@ mod tests {
                       Subject\n"
                 .to_string(),
             diff: None,
+            meta: None,
         };
 
         compare_edited_descs(&expected, &edited_desc);
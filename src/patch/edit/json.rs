@@ -0,0 +1,297 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Patch description editing via JSON over stdin and stdout.
+//!
+//! This is an alternative to interactive editing for editor integrations. Instead of
+//! launching the user's editor, the editable patch description is written to stdout
+//! as a single JSON object and the edited description is read back from stdin as a
+//! JSON object.
+
+use std::io::Write;
+
+use anyhow::{anyhow, Context, Result};
+use bstr::{BString, ByteSlice};
+
+use super::description::{DiffBuffer, EditablePatchDescription, EditedPatchDescription};
+use crate::{
+    ext::TimeExtended,
+    patch::{meta::PatchMeta, PatchName},
+};
+
+/// Conduct patch edit session using JSON over stdin and stdout.
+///
+/// The JSON object written to stdout has the following fields:
+///
+/// - `patchname`: the patch name or `null`.
+/// - `author`: an object with `name`, `email`, and `date` fields or `null`.
+/// - `message`: the patch message.
+/// - `diff`: the patch diff or `null`. Only present when a diff is to be edited.
+/// - `diff_editable`: whether modifications to the diff will be applied.
+/// - `metadata`: the patch metadata as `Key: value` lines. Only present when the
+///   patch metadata is editable.
+///
+/// The JSON object read back from stdin may have the same fields. Absent fields
+/// retain their original values. A `null` (or empty) `patchname` causes the patch
+/// name to be generated from the message, a `null` `author` causes the default
+/// author to be used, and a `null` `metadata` removes all metadata.
+pub(super) fn edit_json(
+    patch_desc: &EditablePatchDescription,
+    diff_editable: bool,
+) -> Result<EditedPatchDescription> {
+    let diff_str = patch_desc
+        .diff
+        .as_ref()
+        .map(|diff| diff.as_ref().to_str_lossy().to_string());
+
+    let mut output = serde_json::Map::new();
+    output.insert(
+        "patchname".into(),
+        patch_desc
+            .patchname
+            .as_ref()
+            .map_or(serde_json::Value::Null, |pn| pn.to_string().into()),
+    );
+    output.insert(
+        "author".into(),
+        patch_desc
+            .author
+            .as_ref()
+            .map_or(serde_json::Value::Null, |author| {
+                serde_json::json!({
+                    "name": author.name.to_str_lossy(),
+                    "email": author.email.to_str_lossy(),
+                    "date": author.time.format(git_repository::date::time::format::ISO8601),
+                })
+            }),
+    );
+    output.insert("message".into(), patch_desc.message.clone().into());
+    if let Some(diff_str) = diff_str.as_ref() {
+        output.insert("diff".into(), diff_str.clone().into());
+        output.insert("diff_editable".into(), diff_editable.into());
+    }
+    if let Some(meta) = patch_desc.meta.as_ref() {
+        output.insert(
+            "metadata".into(),
+            meta.to_bytes().to_str_lossy().to_string().into(),
+        );
+    }
+
+    {
+        let stdout = std::io::stdout();
+        let mut stdout = stdout.lock();
+        serde_json::to_writer(&mut stdout, &output)?;
+        writeln!(stdout)?;
+        stdout.flush()?;
+    }
+
+    let stdin = std::io::stdin();
+    let input: serde_json::Value = serde_json::Deserializer::from_reader(stdin.lock())
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("aborting due to empty patch description"))?
+        .context("reading JSON patch description")?;
+
+    let mut edited = parse_edited(input, patch_desc)?;
+
+    // An unmodified diff is reported as the original diff so that a lossy UTF-8
+    // conversion of the original diff does not appear as an edit.
+    if let (Some(edited_diff), Some(diff_str)) = (edited.diff.as_ref(), diff_str.as_ref()) {
+        if edited_diff.as_ref() == diff_str.as_bytes() {
+            edited.diff = patch_desc.diff.clone();
+        }
+    }
+
+    Ok(edited)
+}
+
+/// Interpret JSON patch description read back from an editor integration.
+fn parse_edited(
+    input: serde_json::Value,
+    patch_desc: &EditablePatchDescription,
+) -> Result<EditedPatchDescription> {
+    let mut object = if let serde_json::Value::Object(object) = input {
+        object
+    } else {
+        return Err(anyhow!("JSON patch description must be an object"));
+    };
+
+    let patchname = match object.remove("patchname") {
+        None => None,
+        Some(serde_json::Value::Null) => Some(None),
+        Some(serde_json::Value::String(s)) if s.trim().is_empty() => Some(None),
+        Some(serde_json::Value::String(s)) => Some(Some(s.trim().parse::<PatchName>()?)),
+        Some(_) => {
+            return Err(anyhow!(
+                "JSON patch description `patchname` must be a string"
+            ))
+        }
+    };
+
+    let author = match object.remove("author") {
+        None => None,
+        Some(serde_json::Value::Null) => Some(None),
+        Some(serde_json::Value::Object(author)) => {
+            let get_str = |key: &str| -> Result<Option<&str>> {
+                match author.get(key) {
+                    None | Some(serde_json::Value::Null) => Ok(None),
+                    Some(serde_json::Value::String(s)) => Ok(Some(s.as_str())),
+                    Some(_) => Err(anyhow!(
+                        "JSON patch description author `{key}` must be a string"
+                    )),
+                }
+            };
+            let name = get_str("name")?
+                .ok_or_else(|| anyhow!("JSON patch description author requires `name`"))?;
+            let email = get_str("email")?
+                .ok_or_else(|| anyhow!("JSON patch description author requires `email`"))?;
+            let time = if let Some(date_str) = get_str("date")? {
+                git_repository::actor::Time::parse_time(date_str)
+                    .context("patch description date")?
+            } else {
+                git_repository::actor::Time::now_local_or_utc()
+            };
+            Some(Some(git_repository::actor::Signature {
+                name: BString::from(name),
+                email: BString::from(email),
+                time,
+            }))
+        }
+        Some(_) => return Err(anyhow!("JSON patch description `author` must be an object")),
+    };
+
+    let message = match object.remove("message") {
+        None => patch_desc.message.clone(),
+        Some(serde_json::Value::String(s)) => s,
+        Some(_) => return Err(anyhow!("JSON patch description `message` must be a string")),
+    };
+
+    let diff = match object.remove("diff") {
+        None | Some(serde_json::Value::Null) => None,
+        Some(serde_json::Value::String(s)) => {
+            if s.trim().is_empty() {
+                None
+            } else {
+                Some(DiffBuffer(s.into_bytes()))
+            }
+        }
+        Some(_) => return Err(anyhow!("JSON patch description `diff` must be a string")),
+    };
+
+    let meta = match object.remove("metadata") {
+        None => None,
+        Some(serde_json::Value::Null) => Some(PatchMeta::default()),
+        Some(serde_json::Value::String(s)) => {
+            Some(PatchMeta::parse(s.as_bytes()).context("patch description metadata")?)
+        }
+        Some(_) => {
+            return Err(anyhow!(
+                "JSON patch description `metadata` must be a string"
+            ))
+        }
+    };
+
+    let message = if message.trim().is_empty() {
+        String::new()
+    } else {
+        let mut message = message.trim_end().to_string();
+        message.push('\n');
+        message
+    };
+
+    Ok(EditedPatchDescription {
+        patchname,
+        author,
+        message,
+        diff,
+        meta,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn original() -> EditablePatchDescription {
+        EditablePatchDescription {
+            patchname: Some("original".parse::<PatchName>().unwrap()),
+            author: None,
+            message: "Original subject\n".to_string(),
+            instruction: None,
+            diff_instruction: None,
+            diff: None,
+            meta: None,
+        }
+    }
+
+    #[test]
+    fn absent_fields_keep_original() {
+        let edited = parse_edited(serde_json::json!({}), &original()).unwrap();
+        assert!(edited.patchname.is_none());
+        assert!(edited.author.is_none());
+        assert_eq!(edited.message, "Original subject\n");
+        assert!(edited.diff.is_none());
+        assert!(edited.meta.is_none());
+    }
+
+    #[test]
+    fn metadata() {
+        let edited = parse_edited(
+            serde_json::json!({"metadata": "Label: wip\nTo: dev@example.com\n"}),
+            &original(),
+        )
+        .unwrap();
+        let meta = edited.meta.unwrap();
+        assert_eq!(meta.get("label"), Some("wip"));
+        assert_eq!(meta.get("to"), Some("dev@example.com"));
+
+        let edited = parse_edited(serde_json::json!({"metadata": null}), &original()).unwrap();
+        assert!(edited.meta.unwrap().is_empty());
+
+        assert!(parse_edited(serde_json::json!({"metadata": "bad"}), &original()).is_err());
+    }
+
+    #[test]
+    fn null_fields() {
+        let edited = parse_edited(
+            serde_json::json!({"patchname": null, "author": null}),
+            &original(),
+        )
+        .unwrap();
+        assert!(matches!(edited.patchname, Some(None)));
+        assert!(matches!(edited.author, Some(None)));
+    }
+
+    #[test]
+    fn edited_fields() {
+        let edited = parse_edited(
+            serde_json::json!({
+                "patchname": "new-name",
+                "author": {
+                    "name": "A U Thor",
+                    "email": "author@example.com",
+                    "date": "2005-04-07 15:13:13 -0700",
+                },
+                "message": "New subject\n\nBody\n\n\n",
+            }),
+            &original(),
+        )
+        .unwrap();
+        assert_eq!(
+            edited.patchname.unwrap().unwrap().as_ref() as &str,
+            "new-name"
+        );
+        let author = edited.author.unwrap().unwrap();
+        assert_eq!(author.name, "A U Thor");
+        assert_eq!(author.email, "author@example.com");
+        assert_eq!(author.time.seconds(), 1112911993);
+        assert_eq!(author.time.offset_in_seconds, -7 * 60 * 60);
+        assert_eq!(edited.message, "New subject\n\nBody\n");
+    }
+
+    #[test]
+    fn invalid_fields() {
+        assert!(parse_edited(serde_json::json!([]), &original()).is_err());
+        assert!(parse_edited(serde_json::json!({"message": 1}), &original()).is_err());
+        assert!(parse_edited(serde_json::json!({"author": {"name": "x"}}), &original()).is_err());
+    }
+}
@@ -9,6 +9,7 @@
 mod args;
 mod description;
 mod interactive;
+mod json;
 mod parse;
 mod trailers;
 
@@ -26,11 +27,15 @@ use clap::ArgMatches;
 use self::{
     description::{DiffBuffer, EditablePatchDescription, EditedPatchDescription},
     interactive::edit_interactive,
+    json::edit_json,
 };
 
-pub(crate) use self::{args::add_args, interactive::call_editor, parse::parse_name_email};
+pub(crate) use self::{
+    args::add_args,
+    parse::{parse_email, parse_name, parse_name_email, parse_name_email2},
+};
 
-use super::PatchName;
+use super::{meta::PatchMeta, PatchName};
 
 use crate::{
     ext::{CommitExtended, RepositoryExtended, SignatureExtended},
@@ -57,6 +62,13 @@ pub(crate) enum EditOutcome {
         ///
         /// This is None if nothing changed during the edit.
         new_commit_id: Option<git_repository::ObjectId>,
+
+        /// New metadata for the edited patch.
+        ///
+        /// This is None if the metadata is not editable or was not changed by the edit.
+        /// The caller is responsible for recording the new metadata for the patch's
+        /// final commit.
+        new_meta: Option<PatchMeta>,
     },
 }
 
@@ -67,6 +79,8 @@ struct Overlay {
     pub(self) message: Option<String>,
     pub(self) tree_id: Option<git_repository::ObjectId>,
     pub(self) parent_id: Option<git_repository::ObjectId>,
+    pub(self) author_time: Option<git_repository::actor::Time>,
+    pub(self) committer_time: Option<git_repository::actor::Time>,
 }
 
 /// Setup and execute a patch edit session.
@@ -82,7 +96,9 @@ pub(crate) struct EditBuilder<'a, 'repo> {
     allow_autosign: bool,
     allow_diff_edit: bool,
     allow_implicit_edit: bool,
+    allow_meta_edit: bool,
     allow_template_save: bool,
+    json_io: bool,
     overlay: Overlay,
 }
 
@@ -120,6 +136,16 @@ impl<'a, 'repo> EditBuilder<'a, 'repo> {
         self
     }
 
+    /// Set whether the patch metadata may be edited.
+    ///
+    /// When true, the patch description includes a metadata section with the existing
+    /// patch's metadata. A metadata section in a patch description file or in the
+    /// interactively edited description replaces the patch's metadata.
+    pub(crate) fn allow_meta_edit(mut self, allow: bool) -> Self {
+        self.allow_meta_edit = allow;
+        self
+    }
+
     /// Set whether the patch edit template save feature may be used.
     ///
     /// This option *must match* the `add_save_template` argument passed to
@@ -129,6 +155,16 @@ impl<'a, 'repo> EditBuilder<'a, 'repo> {
         self
     }
 
+    /// Set whether the patch description is edited via JSON over stdin and stdout.
+    ///
+    /// When true, the patch description is always edited, but instead of invoking
+    /// the user's editor, the editable description is written to stdout as JSON and
+    /// the edited description is read back from stdin as JSON.
+    pub(crate) fn json_io(mut self, json_io: bool) -> Self {
+        self.json_io = json_io;
+        self
+    }
+
     /// Set the original patch name, if applicable.
     ///
     /// The original patchname will be presented to the user in the patch edit template
@@ -208,6 +244,29 @@ impl<'a, 'repo> EditBuilder<'a, 'repo> {
         self
     }
 
+    /// Set the author time for the patch.
+    ///
+    /// The author time is only overridden if `--authdate` is not used.
+    pub(crate) fn override_author_time(
+        mut self,
+        time: Option<git_repository::actor::Time>,
+    ) -> Self {
+        self.overlay.author_time = time;
+        self
+    }
+
+    /// Set the committer time for the patch instead of using the current time.
+    ///
+    /// The committer time is only overridden if `--committer-date-is-author-date` is not
+    /// used.
+    pub(crate) fn override_committer_time(
+        mut self,
+        time: Option<git_repository::actor::Time>,
+    ) -> Self {
+        self.overlay.committer_time = time;
+        self
+    }
+
     /// Perform the patch edits.
     ///
     /// The provided `matches` must come from a [`clap::Command`] that was setup with
@@ -233,13 +292,17 @@ impl<'a, 'repo> EditBuilder<'a, 'repo> {
             allow_autosign,
             allow_diff_edit,
             allow_implicit_edit,
+            allow_meta_edit,
             allow_template_save,
+            json_io,
             overlay:
                 Overlay {
                     author: overlay_author,
                     message: overlay_message,
                     tree_id: overlay_tree_id,
                     parent_id: overlay_parent_id,
+                    author_time: overlay_author_time,
+                    committer_time: overlay_committer_time,
                 },
         } = self;
 
@@ -252,6 +315,7 @@ impl<'a, 'repo> EditBuilder<'a, 'repo> {
             author: file_author,
             message: file_message,
             diff: file_diff,
+            meta: file_meta,
         } = if let Some(file_os) = matches.get_one::<PathBuf>("file") {
             if file_os.to_str() == Some("-") {
                 let mut buf: Vec<u8> = Vec::with_capacity(8192);
@@ -287,7 +351,24 @@ impl<'a, 'repo> EditBuilder<'a, 'repo> {
             }
         };
 
-        let mut need_interactive_edit = matches.get_flag("edit")
+        let original_meta = if allow_meta_edit {
+            Some(if let Some(commit) = patch_commit {
+                PatchMeta::read(repo, commit.id)?
+            } else {
+                PatchMeta::default()
+            })
+        } else {
+            None
+        };
+
+        let meta = if allow_meta_edit {
+            file_meta.or_else(|| original_meta.clone())
+        } else {
+            None
+        };
+
+        let mut need_interactive_edit = json_io
+            || matches.get_flag("edit")
             || (allow_diff_edit && matches.get_flag("diff"))
             || (allow_implicit_edit
                 && ![
@@ -296,6 +377,7 @@ impl<'a, 'repo> EditBuilder<'a, 'repo> {
                     "signoff",
                     "ack",
                     "review",
+                    "fixes",
                     "sign-by",
                     "ack-by",
                     "review-by",
@@ -434,6 +516,7 @@ impl<'a, 'repo> EditBuilder<'a, 'repo> {
                 instruction,
                 diff_instruction,
                 diff,
+                meta,
             };
             let path = matches.get_one::<PathBuf>("save-template").unwrap().clone();
             if path.to_str() == Some("-") {
@@ -446,7 +529,7 @@ impl<'a, 'repo> EditBuilder<'a, 'repo> {
             return Ok(EditOutcome::TemplateSaved(path));
         }
 
-        let (patchname, author, message, diff) = if need_interactive_edit {
+        let (patchname, author, message, diff, meta) = if need_interactive_edit {
             let mut patch_description = EditablePatchDescription {
                 patchname,
                 author,
@@ -454,6 +537,7 @@ impl<'a, 'repo> EditBuilder<'a, 'repo> {
                 instruction,
                 diff_instruction,
                 diff,
+                meta,
             };
 
             let EditedPatchDescription {
@@ -461,7 +545,16 @@ impl<'a, 'repo> EditBuilder<'a, 'repo> {
                 author: edited_author,
                 message: edited_message,
                 diff: edited_diff,
-            } = edit_interactive(&patch_description, &config)?;
+                meta: edited_meta,
+            } = if json_io {
+                edit_json(&patch_description, allow_diff_edit)?
+            } else {
+                edit_interactive(
+                    &patch_description,
+                    &crate::editor::get_editor(matches, &config)?,
+                    &config,
+                )?
+            };
 
             let patchname = match edited_patchname {
                 Some(Some(patchname)) => Some(patchname),
@@ -484,14 +577,22 @@ impl<'a, 'repo> EditBuilder<'a, 'repo> {
                 None => patch_description.author.take(),
             };
 
+            // An absent metadata section leaves the metadata unchanged.
+            let meta = if patch_description.meta.is_some() {
+                edited_meta.or_else(|| patch_description.meta.take())
+            } else {
+                None
+            };
+
             (
                 patchname,
                 author,
                 Message::from(edited_message),
                 edited_diff,
+                meta,
             )
         } else {
-            (patchname, author, message, diff)
+            (patchname, author, message, diff, meta)
         };
 
         let need_to_apply_diff =
@@ -517,6 +618,7 @@ impl<'a, 'repo> EditBuilder<'a, 'repo> {
                         instruction,
                         diff_instruction,
                         diff,
+                        meta,
                     };
                     failed_patch_description.write(&mut stream)?;
                     return Err(anyhow!(
@@ -530,7 +632,10 @@ impl<'a, 'repo> EditBuilder<'a, 'repo> {
             tree_id
         };
 
-        let author = author.unwrap();
+        let mut author = author.unwrap();
+        if let (Some(time), false) = (overlay_author_time, matches.contains_id("authdate")) {
+            author.time = time;
+        }
 
         let message = if need_commit_msg_hook {
             // TODO: Want to save patch description here too
@@ -552,6 +657,10 @@ impl<'a, 'repo> EditBuilder<'a, 'repo> {
             let mut committer = default_committer.to_owned();
             committer.time = author.time;
             committer
+        } else if let Some(time) = overlay_committer_time {
+            let mut committer = default_committer.to_owned();
+            committer.time = time;
+            committer
         } else {
             default_committer.to_owned()
         };
@@ -581,9 +690,12 @@ impl<'a, 'repo> EditBuilder<'a, 'repo> {
             Some(patchname)
         };
 
+        let new_meta = if meta != original_meta { meta } else { None };
+
         Ok(EditOutcome::Edited {
             new_patchname,
             new_commit_id,
+            new_meta,
         })
     }
 }
@@ -6,7 +6,7 @@ use anyhow::{anyhow, Result};
 use bstr::ByteSlice;
 use clap::ArgMatches;
 
-use crate::{stupid::Stupid, wrap::Message};
+use crate::{ext::CommitExtended, stupid::Stupid, wrap::Message};
 
 /// Add trailers to commit message based on user-provided command line options.
 ///
@@ -22,6 +22,16 @@ pub(crate) fn add_trailers<'a, 'b>(
     let signature = signature.into();
     let mut trailers: Vec<(usize, &str, &str)> = vec![];
 
+    let fixes_values = matches
+        .indices_of("fixes")
+        .unwrap_or_default()
+        .zip(matches.get_many::<String>("fixes").unwrap_or_default())
+        .map(|(index, spec)| Ok((index, fixes_value(repo, spec)?)))
+        .collect::<Result<Vec<_>>>()?;
+    for (index, value) in &fixes_values {
+        trailers.push((*index, "Fixes", value));
+    }
+
     for (opt_name, old_by_opt, trailer) in &[
         ("signoff", "sign-by", "Signed-off-by"),
         ("ack", "ack-by", "Acked-by"),
@@ -75,6 +85,33 @@ pub(crate) fn add_trailers<'a, 'b>(
     }
 }
 
+/// Minimum number of hex digits of the commit id in a Fixes trailer.
+const FIXES_MIN_HEX_LEN: usize = 12;
+
+/// Make the value of a Fixes trailer, e.g. `0123456789ab ("subject")`, for a revision.
+///
+/// The commit id is abbreviated to the greater of [`FIXES_MIN_HEX_LEN`], the
+/// `core.abbrev` length, and the length needed for the abbreviation to be unambiguous.
+fn fixes_value(repo: &git_repository::Repository, spec: &str) -> Result<String> {
+    let commit = crate::revspec::parse_stgit_revision(repo, Some(spec), None)?
+        .try_into_commit()
+        .map_err(|_| anyhow!("fixes target `{spec}` is not a commit"))?;
+    let abbrev_len = repo
+        .config_snapshot()
+        .string("core.abbrev")
+        .and_then(|value| value.to_str().ok().and_then(|s| s.parse::<usize>().ok()))
+        .unwrap_or_default();
+    let unique_len = commit.id().shorten()?.hex_len();
+    let hex_len = FIXES_MIN_HEX_LEN.max(abbrev_len).max(unique_len);
+    let message = commit.message_ex();
+    let message = message.decode()?;
+    let subject = message.lines().next().unwrap_or_default().trim();
+    Ok(format!(
+        "{} (\"{subject}\")",
+        commit.id.to_hex_with_len(hex_len)
+    ))
+}
+
 #[cfg(test)]
 mod test {
     use clap::Arg;
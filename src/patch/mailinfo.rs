@@ -0,0 +1,954 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Split patch emails into author information, message, and diff.
+//!
+//! This is a native implementation of the parts of git-mailinfo(1) used when
+//! importing patches, as if run with `--scissors --encoding=UTF-8`. Headers encoded
+//! as described by RFC 2047 are decoded, quoted-printable and base64 bodies are
+//! decoded, and message text is converted to UTF-8 according to its charset. The
+//! parts of multipart emails are processed in order, such that a patch may either be
+//! inline or attached.
+//!
+//! As with git-mailinfo(1), "From:", "Subject:", and "Date:" lines at the start of
+//! the body override the email's headers when the email contains a diff, a scissors line such as "-- >8 --"
+//! discards the message text before it, and the diff starts at the first line that
+//! looks like the start of a patch, such as a "---" line or a "diff -" line.
+
+use bstr::ByteSlice;
+
+/// Information split from a patch email.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct MailInfo {
+    pub(crate) author_name: Option<String>,
+    pub(crate) author_email: Option<String>,
+    pub(crate) date: Option<String>,
+    pub(crate) subject: Option<String>,
+    /// Value of the email's Message-ID header, including its angle brackets.
+    pub(crate) message_id: Option<String>,
+    /// Message text following the subject, up to the start of the diff.
+    pub(crate) message: Vec<u8>,
+    /// Diff, starting with the line that ended the message.
+    pub(crate) diff: Vec<u8>,
+}
+
+impl MailInfo {
+    /// Parse a raw email or patch file.
+    ///
+    /// Input that does not start with email headers is treated as a body, which may
+    /// still start with in-body headers.
+    pub(crate) fn parse(raw: &[u8]) -> Self {
+        Parser::new(raw).parse()
+    }
+}
+
+/// Values of the headers that determine the author and subject of a patch.
+#[derive(Default)]
+struct Fields {
+    from: Option<Vec<u8>>,
+    subject: Option<Vec<u8>>,
+    date: Option<Vec<u8>>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TransferEncoding {
+    Plain,
+    QuotedPrintable,
+    Base64,
+}
+
+/// Where a header was found.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum HeaderSource {
+    /// The headers of the email, where later values take precedence.
+    Mail,
+    /// The headers of a multipart part.
+    Part,
+    /// The start of the body, where in-body headers may override the email's headers.
+    InBody,
+}
+
+/// Result of reading a header line.
+enum HeaderLine {
+    /// A complete, unfolded header.
+    Header(Vec<u8>),
+    /// The line ending the header section, if any.
+    End(Option<Vec<u8>>),
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+    message_id: Option<Vec<u8>>,
+    headers: Fields,
+    inbody_headers: Fields,
+    inbody_pending: Vec<u8>,
+    in_header_stage: bool,
+    in_diff: bool,
+    boundaries: Vec<Vec<u8>>,
+    transfer_encoding: TransferEncoding,
+    charset: Option<String>,
+    message: Vec<u8>,
+    diff: Vec<u8>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a [u8]) -> Self {
+        Self {
+            input,
+            pos: 0,
+            message_id: None,
+            headers: Fields::default(),
+            inbody_headers: Fields::default(),
+            inbody_pending: Vec::new(),
+            in_header_stage: true,
+            in_diff: false,
+            boundaries: Vec::new(),
+            transfer_encoding: TransferEncoding::Plain,
+            charset: None,
+            message: Vec::new(),
+            diff: Vec::new(),
+        }
+    }
+
+    fn parse(mut self) -> MailInfo {
+        let first_line = loop {
+            match self.read_header_line() {
+                HeaderLine::Header(header) => self.check_header(&header, HeaderSource::Mail),
+                HeaderLine::End(line) => break line,
+            }
+        };
+        self.handle_body(first_line);
+
+        let Self {
+            headers,
+            inbody_headers,
+            message,
+            diff,
+            message_id,
+            ..
+        } = self;
+
+        // In-body headers only take effect for emails with a diff.
+        let inbody_headers = if diff.is_empty() {
+            Fields::default()
+        } else {
+            inbody_headers
+        };
+        let (author_name, author_email) = inbody_headers
+            .from
+            .or(headers.from)
+            .map(|from| parse_from(&cleanup_space(&from)))
+            .unwrap_or_default();
+        let subject = inbody_headers
+            .subject
+            .or(headers.subject)
+            .map(|subject| cleanup_space(&cleanup_subject(&subject)));
+        let date = inbody_headers
+            .date
+            .or(headers.date)
+            .map(|date| cleanup_space(&date).trim().to_vec());
+        let non_empty =
+            |value: Vec<u8>| (!value.is_empty()).then(|| value.to_str_lossy().into_owned());
+
+        MailInfo {
+            author_name: author_name.and_then(non_empty),
+            author_email: author_email.and_then(non_empty),
+            date: date.and_then(non_empty),
+            subject: subject.and_then(non_empty),
+            message_id: message_id.and_then(non_empty),
+            message,
+            diff,
+        }
+    }
+
+    /// Read the next line of input, including its line terminator.
+    fn next_line(&mut self) -> Option<Vec<u8>> {
+        let rest = &self.input[self.pos..];
+        if rest.is_empty() {
+            return None;
+        }
+        let len = rest.find_byte(b'\n').map_or(rest.len(), |pos| pos + 1);
+        self.pos += len;
+        Some(rest[..len].to_vec())
+    }
+
+    /// Read a header line, unfolding any continuation lines.
+    ///
+    /// A blank line or a line that does not look like a header ends the header
+    /// section. Like the other lines of the section, it is returned without trailing
+    /// whitespace other than its newline.
+    fn read_header_line(&mut self) -> HeaderLine {
+        let Some(line) = self.next_line() else {
+            return HeaderLine::End(None);
+        };
+        let mut header = line.trim_end().to_vec();
+        if header.is_empty() || !is_rfc2822_header(&header) {
+            header.push(b'\n');
+            return HeaderLine::End(Some(header));
+        }
+        while matches!(self.input.get(self.pos), Some(b' ' | b'\t')) {
+            let continuation = self.next_line().expect("continuation line is available");
+            header.push(b' ');
+            header.extend_from_slice(continuation[1..].trim_end());
+        }
+        HeaderLine::Header(header)
+    }
+
+    /// Record the value of a header relevant to the patch.
+    ///
+    /// In-body headers are recorded separately from the email's headers.
+    fn check_header(&mut self, header: &[u8], source: HeaderSource) {
+        let fields = if source == HeaderSource::InBody {
+            &mut self.inbody_headers
+        } else {
+            &mut self.headers
+        };
+        for (name, field) in [
+            ("From", &mut fields.from),
+            ("Subject", &mut fields.subject),
+            ("Date", &mut fields.date),
+        ] {
+            if let Some(value) = header_value(header, name) {
+                if field.is_none() || source == HeaderSource::Mail {
+                    *field = Some(decode_header(value));
+                }
+                return;
+            }
+        }
+
+        if let Some(value) = header_value(header, "Content-Type") {
+            if let Some(boundary) = attribute(value, "boundary=") {
+                let mut boundary = boundary;
+                boundary.splice(0..0, b"--".iter().copied());
+                self.boundaries.push(boundary);
+            }
+            if let Some(charset) = attribute(value, "charset=") {
+                self.charset = Some(charset.to_str_lossy().into_owned());
+            }
+        } else if let Some(value) = header_value(header, "Content-Transfer-Encoding") {
+            let value = value.to_ascii_lowercase();
+            self.transfer_encoding = if value.contains_str("base64") {
+                TransferEncoding::Base64
+            } else if value.contains_str("quoted-printable") {
+                TransferEncoding::QuotedPrintable
+            } else {
+                TransferEncoding::Plain
+            };
+        } else if let Some(value) = header_value(header, "Message-Id") {
+            if self.message_id.is_none() {
+                self.message_id = Some(value.trim().to_vec());
+            }
+        }
+    }
+
+    fn handle_body(&mut self, first_line: Option<Vec<u8>>) {
+        let mut line = if self.boundaries.is_empty() {
+            first_line
+        } else {
+            // The preamble of a multipart email is not part of any part.
+            self.find_boundary()
+        };
+        let mut partial = Vec::new();
+
+        while let Some(mut current) = line {
+            if self.is_boundary(&current) {
+                if !partial.is_empty() {
+                    self.handle_line(std::mem::take(&mut partial));
+                }
+                if let Some(next) = self.handle_boundary(current) {
+                    current = next;
+                } else {
+                    break;
+                }
+            }
+
+            match self.transfer_encoding {
+                TransferEncoding::Plain => self.handle_line(current),
+                encoding => {
+                    if encoding == TransferEncoding::Base64 {
+                        partial.extend(decode_base64(&current));
+                    } else {
+                        partial.extend(decode_quoted_printable(&current, false));
+                    }
+                    while let Some(pos) = partial.find_byte(b'\n') {
+                        let decoded_line = partial.drain(..=pos).collect();
+                        self.handle_line(decoded_line);
+                    }
+                }
+            }
+            line = self.next_line();
+        }
+
+        if !partial.is_empty() {
+            self.handle_line(partial);
+        }
+        self.flush_inbody_header();
+    }
+
+    fn is_boundary(&self, line: &[u8]) -> bool {
+        self.boundaries
+            .last()
+            .map_or(false, |boundary| line.starts_with(boundary))
+    }
+
+    /// Skip to the next line that is a boundary of the innermost multipart part.
+    fn find_boundary(&mut self) -> Option<Vec<u8>> {
+        while let Some(line) = self.next_line() {
+            if self.is_boundary(&line) {
+                return Some(line);
+            }
+        }
+        None
+    }
+
+    /// Process a boundary line and the headers of the part it starts.
+    ///
+    /// Returns the first line of the part's body, if any.
+    fn handle_boundary(&mut self, mut line: Vec<u8>) -> Option<Vec<u8>> {
+        loop {
+            let boundary_len = self.boundaries.last().map_or(0, Vec::len);
+            if !line[boundary_len..].starts_with(b"--") {
+                break;
+            }
+            // The end of a multipart part ends the current line of the message.
+            self.boundaries.pop();
+            self.handle_line(b"\n".to_vec());
+            line = self.find_boundary()?;
+        }
+
+        self.transfer_encoding = TransferEncoding::Plain;
+        self.charset = None;
+        while let HeaderLine::Header(header) = self.read_header_line() {
+            self.check_header(&header, HeaderSource::Part);
+        }
+        self.next_line()
+    }
+
+    /// Add a line of the decoded body to either the message or the diff.
+    fn handle_line(&mut self, line: Vec<u8>) {
+        if !self.in_diff && self.handle_message_line(&line) {
+            self.in_diff = true;
+        }
+        if self.in_diff {
+            self.diff.extend(line);
+        }
+    }
+
+    /// Add a line to the message, returning whether the line starts the diff.
+    fn handle_message_line(&mut self, line: &[u8]) -> bool {
+        if self.in_header_stage {
+            if line.is_empty() || line == b"\n" {
+                self.flush_inbody_header();
+                return false;
+            }
+            self.in_header_stage = self.check_inbody_header(line);
+            if self.in_header_stage {
+                return false;
+            }
+        }
+
+        let line = convert_to_utf8(line.to_vec(), self.charset.as_deref());
+
+        if is_scissors_line(&line) {
+            self.message.clear();
+            self.in_header_stage = true;
+            self.inbody_headers = Fields::default();
+            return false;
+        }
+
+        if is_patch_break(&line) {
+            return true;
+        }
+
+        self.message.extend(line);
+        false
+    }
+
+    /// Determine whether a line at the start of the body is an in-body header.
+    fn check_inbody_header(&mut self, line: &[u8]) -> bool {
+        if !self.inbody_pending.is_empty() && matches!(line.first(), Some(b' ' | b'\t')) {
+            if is_scissors_line(line) {
+                self.flush_inbody_header();
+                return false;
+            }
+            if self.inbody_pending.ends_with(b"\n") {
+                self.inbody_pending.pop();
+            }
+            self.inbody_pending.extend_from_slice(line);
+            return true;
+        }
+
+        self.flush_inbody_header();
+
+        if line.starts_with(b">From") && line.get(5).map_or(false, u8::is_ascii_whitespace) {
+            is_format_patch_separator(&line[1..])
+        } else if line.starts_with(b"[PATCH]") && line.get(7).map_or(false, u8::is_ascii_whitespace)
+        {
+            self.inbody_headers.subject = Some(line.to_vec());
+            true
+        } else if ["From", "Subject", "Date"]
+            .iter()
+            .any(|name| header_value(line, name).is_some())
+        {
+            self.inbody_pending.extend_from_slice(line);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn flush_inbody_header(&mut self) {
+        if !self.inbody_pending.is_empty() {
+            let header = std::mem::take(&mut self.inbody_pending);
+            self.check_header(header.trim_end(), HeaderSource::InBody);
+        }
+    }
+}
+
+/// Determine whether a line looks like an email header.
+///
+/// Any field name made of printable characters other than ':' is accepted, as are
+/// mbox "From " lines.
+fn is_rfc2822_header(line: &[u8]) -> bool {
+    if line.starts_with(b"From ") || line.starts_with(b">From ") {
+        return true;
+    }
+    for &c in line {
+        if c == b':' {
+            return true;
+        } else if !(33..=126).contains(&c) {
+            break;
+        }
+    }
+    false
+}
+
+/// Get the value of a header with the given case-insensitive name.
+fn header_value<'h>(header: &'h [u8], name: &str) -> Option<&'h [u8]> {
+    let value = header
+        .get(..name.len())
+        .filter(|prefix| prefix.eq_ignore_ascii_case(name.as_bytes()))
+        .and_then(|_| header[name.len()..].strip_prefix(b":"))?;
+    Some(value.trim_start())
+}
+
+/// Get the value of an attribute, such as `charset=`, of a structured header value.
+fn attribute(value: &[u8], name: &str) -> Option<Vec<u8>> {
+    let start = value
+        .to_ascii_lowercase()
+        .find(name.as_bytes())
+        .map(|pos| pos + name.len())?;
+    let rest = &value[start..];
+    let attribute = if let Some(quoted) = rest.strip_prefix(b"\"") {
+        quoted.find_byte(b'"').map_or(quoted, |end| &quoted[..end])
+    } else {
+        let end = rest
+            .iter()
+            .position(|&c| c == b';' || c.is_ascii_whitespace())
+            .unwrap_or(rest.len());
+        &rest[..end]
+    };
+    Some(attribute.to_vec())
+}
+
+/// Decode the encoded words of a header value as described by RFC 2047.
+///
+/// Whitespace between adjacent encoded words is removed. A malformed value is
+/// returned unchanged.
+fn decode_header(value: &[u8]) -> Vec<u8> {
+    let mut decoded = Vec::with_capacity(value.len());
+    let mut rest = value;
+    let mut after_word = false;
+    while let Some(start) = rest.find(b"=?") {
+        let before = &rest[..start];
+        if !after_word || !before.iter().all(u8::is_ascii_whitespace) {
+            decoded.extend_from_slice(before);
+        }
+        let word = &rest[start + 2..];
+        let Some((charset, encoding, text, len)) = split_encoded_word(word) else {
+            return value.to_vec();
+        };
+        let text = match encoding.to_ascii_lowercase() {
+            b'b' => decode_base64(text),
+            b'q' => decode_quoted_printable(text, true),
+            _ => return value.to_vec(),
+        };
+        decoded.extend(convert_to_utf8(text, Some(&charset.to_str_lossy())));
+        rest = &word[len..];
+        after_word = true;
+    }
+    decoded.extend_from_slice(rest);
+    decoded
+}
+
+/// Split an encoded word following its "=?" into charset, encoding, and text.
+///
+/// The length of the word, including the closing "?=", is also returned.
+fn split_encoded_word(word: &[u8]) -> Option<(&[u8], u8, &[u8], usize)> {
+    let charset_len = word.find_byte(b'?')?;
+    let charset = &word[..charset_len];
+    let encoding = *word.get(charset_len + 1)?;
+    if word.get(charset_len + 2) != Some(&b'?') {
+        return None;
+    }
+    let text_start = charset_len + 3;
+    let text_len = word[text_start..].find(b"?=")?;
+    Some((
+        charset,
+        encoding,
+        &word[text_start..text_start + text_len],
+        text_start + text_len + 2,
+    ))
+}
+
+/// Decode base64 text, ignoring characters outside of the base64 alphabet.
+fn decode_base64(text: &[u8]) -> Vec<u8> {
+    let mut decoded = Vec::with_capacity(text.len() * 3 / 4);
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    for &c in text {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' => break,
+            _ => continue,
+        };
+        acc = (acc << 6) | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            decoded.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    decoded
+}
+
+/// Decode quoted-printable text.
+///
+/// A "=" at the end of a line is a soft line break, which removes the line's
+/// newline. In encoded words of headers, "_" encodes a space.
+fn decode_quoted_printable(text: &[u8], is_header: bool) -> Vec<u8> {
+    let mut decoded = Vec::with_capacity(text.len());
+    let mut i = 0;
+    while i < text.len() {
+        let c = text[i];
+        if c == b'=' {
+            let rest = &text[i + 1..];
+            if rest.is_empty() || rest == b"\n" || rest == b"\r\n" {
+                break;
+            }
+            let hex = rest
+                .get(..2)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            if let Some(byte) = hex {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(if is_header && c == b'_' { b' ' } else { c });
+        i += 1;
+    }
+    decoded
+}
+
+/// Convert text in the given charset to UTF-8.
+///
+/// Text in an unknown charset or without a charset is returned unchanged.
+fn convert_to_utf8(text: Vec<u8>, charset: Option<&str>) -> Vec<u8> {
+    match charset.and_then(|charset| encoding_rs::Encoding::for_label(charset.as_bytes())) {
+        Some(encoding) if encoding != encoding_rs::UTF_8 => encoding
+            .decode_without_bom_handling(&text)
+            .0
+            .into_owned()
+            .into_bytes(),
+        _ => text,
+    }
+}
+
+/// Replace each run of whitespace with a single space.
+fn cleanup_space(value: &[u8]) -> Vec<u8> {
+    let mut cleaned = Vec::with_capacity(value.len());
+    for &c in value {
+        if c.is_ascii_whitespace() {
+            if cleaned.last() != Some(&b' ') {
+                cleaned.push(b' ');
+            }
+        } else {
+            cleaned.push(c);
+        }
+    }
+    cleaned
+}
+
+/// Remove "Re:" and bracketed prefixes, such as "[PATCH v2 1/3]", from a subject.
+fn cleanup_subject(subject: &[u8]) -> Vec<u8> {
+    let mut subject = subject;
+    loop {
+        match subject.first() {
+            Some(b'r' | b'R')
+                if subject.len() > 3
+                    && subject[1].eq_ignore_ascii_case(&b'e')
+                    && subject[2] == b':' =>
+            {
+                subject = &subject[3..];
+            }
+            Some(b' ' | b'\t' | b':') => subject = &subject[1..],
+            Some(b'[') => {
+                if let Some(end) = subject.find_byte(b']') {
+                    subject = &subject[end + 1..];
+                } else {
+                    break;
+                }
+            }
+            _ => break,
+        }
+    }
+    subject.trim().to_vec()
+}
+
+/// Split a From header value into the author's name and email address.
+///
+/// When the name is missing or implausible, the email address is also used as the
+/// name.
+fn parse_from(from: &[u8]) -> (Option<Vec<u8>>, Option<Vec<u8>>) {
+    let from = unquote(from);
+    let Some(at) = from.find_byte(b'@') else {
+        // An address without "@" is only recognized within angle brackets.
+        let start = from.find_byte(b'<');
+        let end = from.rfind_byte(b'>');
+        return match (start, end) {
+            (Some(start), Some(end)) if start < end => {
+                let email = from[start + 1..end].to_vec();
+                let name = from[..start].trim().to_vec();
+                (Some(sane_name(name, &email)), Some(email))
+            }
+            _ => (None, None),
+        };
+    };
+
+    let mut start = at;
+    let mut bracketed = false;
+    while start > 0 {
+        let c = from[start - 1];
+        if c.is_ascii_whitespace() {
+            break;
+        } else if c == b'<' {
+            bracketed = true;
+            break;
+        }
+        start -= 1;
+    }
+    let len = from[start..]
+        .iter()
+        .position(|&c| c.is_ascii_whitespace() || c == b'>')
+        .unwrap_or(from.len() - start);
+    let email = from[start..start + len].to_vec();
+
+    let name_start = if bracketed { start - 1 } else { start };
+    let name_end = (start + len + 1).min(from.len());
+    let mut name = from[..name_start].to_vec();
+    name.push(b' ');
+    name.extend_from_slice(&from[name_end..]);
+    let name = cleanup_space(&name);
+    let mut name = name.trim();
+    if name.len() >= 2 && name.starts_with(b"(") && name.ends_with(b")") {
+        name = &name[1..name.len() - 1];
+    }
+
+    (Some(sane_name(name.to_vec(), &email)), Some(email))
+}
+
+fn sane_name(name: Vec<u8>, email: &[u8]) -> Vec<u8> {
+    if name.is_empty() || name.len() > 60 || name.iter().any(|c| b"@<>".contains(c)) {
+        email.to_vec()
+    } else {
+        name
+    }
+}
+
+/// Remove the quotes of quoted strings, along with their backslash escapes.
+fn unquote(value: &[u8]) -> Vec<u8> {
+    let mut unquoted = Vec::with_capacity(value.len());
+    let mut in_quotes = false;
+    let mut chars = value.iter().copied();
+    while let Some(c) = chars.next() {
+        match c {
+            b'"' => in_quotes = !in_quotes,
+            b'\\' if in_quotes => unquoted.extend(chars.next()),
+            _ => unquoted.push(c),
+        }
+    }
+    unquoted
+}
+
+/// Determine whether a line is a scissors line, such as "-- >8 --".
+///
+/// The perforation of dashes and scissors must be at least 8 characters wide, span
+/// more than a third of the line's visible width, and be mostly dashes and scissors
+/// rather than whitespace.
+fn is_scissors_line(line: &[u8]) -> bool {
+    let mut scissors = 0;
+    let mut gap = 0;
+    let mut perforation = 0;
+    let mut in_perforation = false;
+    let mut first_visible = None;
+    let mut last_visible = 0;
+
+    let mut i = 0;
+    while i < line.len() {
+        let c = line[i];
+        if c.is_ascii_whitespace() {
+            if in_perforation {
+                perforation += 1;
+                gap += 1;
+            }
+            i += 1;
+            continue;
+        }
+        last_visible = i;
+        first_visible.get_or_insert(i);
+        if c == b'-' {
+            in_perforation = true;
+            perforation += 1;
+        } else if [b">8", b"8<", b">%", b"%<"]
+            .iter()
+            .any(|mark| line[i..].starts_with(*mark))
+        {
+            in_perforation = true;
+            perforation += 2;
+            scissors += 2;
+            last_visible = i + 1;
+            i += 1;
+        } else {
+            in_perforation = false;
+        }
+        i += 1;
+    }
+
+    let visible = first_visible.map_or(0, |first| last_visible - first + 1);
+    scissors > 0 && visible >= 8 && visible < perforation * 3 && gap * 2 < perforation
+}
+
+/// Determine whether a line marks the start of the diff.
+fn is_patch_break(line: &[u8]) -> bool {
+    if line.starts_with(b"diff -") || line.starts_with(b"Index: ") {
+        return true;
+    }
+    match line.strip_prefix(b"---") {
+        // Either "--- <filename>" or a "---" separator line.
+        Some(rest) if line.len() >= 4 => {
+            if rest[0] == b' ' && rest.get(1).map_or(false, |c| !c.is_ascii_whitespace()) {
+                true
+            } else {
+                let end = rest
+                    .iter()
+                    .position(|c| !c.is_ascii_whitespace() || *c == b'\n');
+                end.map_or(false, |end| rest[end] == b'\n')
+            }
+        }
+        _ => false,
+    }
+}
+
+/// Determine whether a line is the separator line of git-format-patch(1) output.
+fn is_format_patch_separator(line: &[u8]) -> bool {
+    const SAMPLE: &[u8] =
+        b"From e6807f3efca28b30decfecb1732a56c7db1137ee Mon Sep 17 00:00:00 2001\n";
+    line.len() == SAMPLE.len()
+        && line.starts_with(b"From ")
+        && line[5..45].iter().all(|c| b"0123456789abcdef".contains(c))
+        && line[45..] == SAMPLE[45..]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn plain_patch() {
+        let info = MailInfo::parse(
+            b"Fix the frobnicator\n\nLonger description.\n---\n foo.txt | 1 +\n\n\
+              diff --git a/foo.txt b/foo.txt\n",
+        );
+        assert_eq!(info.author_name, None);
+        assert_eq!(info.subject, None);
+        assert_eq!(
+            info.message.as_bstr(),
+            b"Fix the frobnicator\n\nLonger description.\n".as_bstr()
+        );
+        assert_eq!(
+            info.diff.as_bstr(),
+            b"---\n foo.txt | 1 +\n\ndiff --git a/foo.txt b/foo.txt\n".as_bstr()
+        );
+    }
+
+    #[test]
+    fn encoded_headers() {
+        let info = MailInfo::parse(
+            b"From: =?utf-8?q?Inge_Str=C3=B6m?= <inge@power.com>\n\
+              Subject: [PATCH v2 1/2] =?iso-8859-1?q?pum-p=F6ddelip=E5m?=\n \
+              =?utf-8?b?w6TDpMOk?=\n\
+              Date: Sat, 11 Nov 2006 11:58:14 +0100\n\
+              \n\
+              Body.\n",
+        );
+        assert_eq!(info.author_name.as_deref(), Some("Inge Ström"));
+        assert_eq!(info.author_email.as_deref(), Some("inge@power.com"));
+        assert_eq!(info.subject.as_deref(), Some("pum-pöddelipåmäää"));
+        assert_eq!(
+            info.date.as_deref(),
+            Some("Sat, 11 Nov 2006 11:58:14 +0100")
+        );
+        assert_eq!(info.message.as_bstr(), b"Body.\n".as_bstr());
+        assert!(info.diff.is_empty());
+    }
+
+    #[test]
+    fn from_forms() {
+        let cases: [(&[u8], &str, &str); 5] = [
+            (
+                b"John Doe <john@example.com>",
+                "John Doe",
+                "john@example.com",
+            ),
+            (
+                b"\"Doe, John\" <john@example.com>",
+                "Doe, John",
+                "john@example.com",
+            ),
+            (
+                b"john@example.com (John Doe)",
+                "John Doe",
+                "john@example.com",
+            ),
+            (b"john@example.com", "john@example.com", "john@example.com"),
+            (b"John Doe <john>", "John Doe", "john"),
+        ];
+        for (from, name, email) in cases {
+            let (parsed_name, parsed_email) = parse_from(from);
+            assert_eq!(parsed_name.unwrap().as_bstr(), name.as_bytes().as_bstr());
+            assert_eq!(parsed_email.unwrap().as_bstr(), email.as_bytes().as_bstr());
+        }
+    }
+
+    #[test]
+    fn subject_prefixes() {
+        assert_eq!(
+            cleanup_subject(b"Re: [PATCH v3 02/10] [net] Fix it").as_bstr(),
+            b"Fix it".as_bstr()
+        );
+        assert_eq!(
+            cleanup_subject(b"Rebase: fix").as_bstr(),
+            b"Rebase: fix".as_bstr()
+        );
+    }
+
+    #[test]
+    fn inbody_headers_and_scissors() {
+        let info = MailInfo::parse(
+            b"From: Mail Sender <sender@example.com>\n\
+              Subject: [PATCH] Mail subject\n\
+              Message-ID: <id@example.com>\n\
+              \n\
+              Discussion that is not part of the message.\n\
+              \n\
+              -- >8 --\n\
+              From: Real Author <author@example.com>\n\
+              Subject: Real subject\n\
+              \n\
+              Real message.\n\
+              ---\n\
+              diff --git a/foo b/foo\n",
+        );
+        assert_eq!(info.author_name.as_deref(), Some("Real Author"));
+        assert_eq!(info.author_email.as_deref(), Some("author@example.com"));
+        assert_eq!(info.subject.as_deref(), Some("Real subject"));
+        assert_eq!(info.message_id.as_deref(), Some("<id@example.com>"));
+        assert_eq!(info.message.as_bstr(), b"Real message.\n".as_bstr());
+        assert_eq!(
+            info.diff.as_bstr(),
+            b"---\ndiff --git a/foo b/foo\n".as_bstr()
+        );
+    }
+
+    #[test]
+    fn inbody_headers_require_diff() {
+        let info = MailInfo::parse(
+            b"From: Sender <sender@example.com>\n\
+              Subject: Question\n\
+              \n\
+              From: Someone Else <else@example.com>\n\
+              \n\
+              No patch here.\n",
+        );
+        assert_eq!(info.author_name.as_deref(), Some("Sender"));
+        assert_eq!(info.message.as_bstr(), b"No patch here.\n".as_bstr());
+    }
+
+    #[test]
+    fn scissors_lines() {
+        assert!(is_scissors_line(b"-- >8 --\n"));
+        assert!(is_scissors_line(
+            b"------------ 8< ------- cut here ------\n"
+        ));
+        assert!(!is_scissors_line(b"-- \n"));
+        assert!(!is_scissors_line(b"a >8 b\n"));
+    }
+
+    #[test]
+    fn quoted_printable_body() {
+        let info = MailInfo::parse(
+            b"Subject: test\n\
+              Content-Type: text/plain; charset=\"iso-8859-1\"\n\
+              Content-Transfer-Encoding: quoted-printable\n\
+              \n\
+              Str=F6m wrote a very long line that is wrapped with a soft line=\n \
+              break.\n\
+              ---\n\
+              +pum-p=F6ddelip=E5m\n",
+        );
+        assert_eq!(
+            info.message.as_bstr(),
+            "Ström wrote a very long line that is wrapped with a soft line break.\n"
+                .as_bytes()
+                .as_bstr()
+        );
+        // The diff is decoded, but not converted to UTF-8.
+        assert_eq!(
+            info.diff.as_bstr(),
+            b"---\n+pum-p\xf6ddelip\xe5m\n".as_bstr()
+        );
+    }
+
+    #[test]
+    fn multipart_attachment() {
+        let info = MailInfo::parse(
+            b"From: A U Thor <author@example.com>\n\
+              Subject: [PATCH] Attached\n\
+              Content-Type: multipart/mixed; boundary=\"XYZ\"\n\
+              \n\
+              This is a multi-part message in MIME format.\n\
+              --XYZ\n\
+              Content-Type: text/plain; charset=utf-8\n\
+              \n\
+              Message text.\n\
+              --XYZ\n\
+              Content-Type: text/x-patch; name=\"fix.patch\"\n\
+              Content-Transfer-Encoding: base64\n\
+              Content-Disposition: attachment; filename=\"fix.patch\"\n\
+              \n\
+              ZGlmZiAtLWdpdCBhL2ZvbyBiL2Zvbwo=\n\
+              --XYZ--\n\
+              Epilogue.\n",
+        );
+        assert_eq!(info.subject.as_deref(), Some("Attached"));
+        assert_eq!(info.message.as_bstr(), b"Message text.\n".as_bstr());
+        assert_eq!(info.diff.as_bstr(), b"diff --git a/foo b/foo\n\n".as_bstr());
+    }
+}
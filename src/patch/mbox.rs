@@ -0,0 +1,209 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Split mbox files into emails.
+//!
+//! This is a native implementation of git-mailsplit(1) that reads emails one at a
+//! time, such that large mboxes may be processed with constant memory. As with
+//! git-mailsplit(1), an email starts at a "From " line that ends with something
+//! that looks like a date, and that line is kept as the first line of the email.
+
+use std::io::BufRead;
+
+use anyhow::{anyhow, Result};
+use bstr::ByteSlice;
+
+/// Iterator over the raw emails of an mbox.
+pub(crate) struct Mbox<R: BufRead> {
+    reader: R,
+    /// Line read ahead, which starts the next email.
+    line: Vec<u8>,
+    keep_cr: bool,
+    allow_bare: bool,
+    started: bool,
+    done: bool,
+}
+
+impl<R: BufRead> Mbox<R> {
+    /// Read emails from an mbox.
+    ///
+    /// Carriage returns at the end of lines are removed unless `keep_cr` is true.
+    /// When `allow_bare` is true, input that does not start with a "From " line is
+    /// read as a single email instead of being an error.
+    pub(crate) fn new(reader: R, keep_cr: bool, allow_bare: bool) -> Self {
+        Self {
+            reader,
+            line: Vec::new(),
+            keep_cr,
+            allow_bare,
+            started: false,
+            done: false,
+        }
+    }
+
+    /// Read the next line into `self.line`, returning false at the end of the input.
+    fn read_line(&mut self) -> Result<bool> {
+        self.line.clear();
+        Ok(self.reader.read_until(b'\n', &mut self.line)? > 0)
+    }
+
+    /// Skip whitespace preceding the first email and read its first line.
+    fn start(&mut self) -> Result<bool> {
+        loop {
+            let buf = self.reader.fill_buf()?;
+            if buf.is_empty() {
+                return Ok(false);
+            }
+            let n = buf.iter().take_while(|b| b.is_ascii_whitespace()).count();
+            let at_content = n < buf.len();
+            self.reader.consume(n);
+            if at_content {
+                return self.read_line();
+            }
+        }
+    }
+
+    fn next_email(&mut self) -> Result<Option<Vec<u8>>> {
+        if !self.started {
+            self.started = true;
+            if !self.start()? {
+                self.done = true;
+            }
+        }
+        if self.done {
+            return Ok(None);
+        }
+
+        let is_bare = !is_from_line(&self.line);
+        if is_bare && !self.allow_bare {
+            self.done = true;
+            return Err(anyhow!("corrupt mailbox"));
+        }
+
+        let mut email = Vec::new();
+        loop {
+            if !self.keep_cr && self.line.ends_with(b"\r\n") {
+                email.extend_from_slice(&self.line[..self.line.len() - 2]);
+                email.push(b'\n');
+            } else {
+                email.extend_from_slice(&self.line);
+            }
+            if !self.read_line()? {
+                self.done = true;
+                break;
+            }
+            if !is_bare && is_from_line(&self.line) {
+                break;
+            }
+        }
+        Ok(Some(email))
+    }
+}
+
+impl<R: BufRead> Iterator for Mbox<R> {
+    type Item = Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_email().transpose()
+    }
+}
+
+/// Determine whether a line starts a new email.
+///
+/// Like git-mailsplit(1), the line must start with "From " and end with a time and a
+/// year, e.g. "From 1234abcd Mon Sep 17 00:00:00 2001".
+fn is_from_line(line: &[u8]) -> bool {
+    if line.len() < 20 || !line.starts_with(b"From ") {
+        return false;
+    }
+    let colon = if let Some(pos) = line[4..line.len() - 2].rfind_byte(b':') {
+        pos + 4
+    } else {
+        return false;
+    };
+    let is_digit = |i: usize| line.get(i).map_or(false, u8::is_ascii_digit);
+    if !(is_digit(colon - 4)
+        && is_digit(colon - 2)
+        && is_digit(colon - 1)
+        && is_digit(colon + 1)
+        && is_digit(colon + 2))
+    {
+        return false;
+    }
+    let year = line[colon + 3..]
+        .trim_start()
+        .iter()
+        .take_while(|b| b.is_ascii_digit())
+        .fold(0u64, |year, b| {
+            year.saturating_mul(10).saturating_add(u64::from(b - b'0'))
+        });
+    year > 90
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(input: &[u8], keep_cr: bool, allow_bare: bool) -> Result<Vec<Vec<u8>>> {
+        Mbox::new(input, keep_cr, allow_bare).collect()
+    }
+
+    #[test]
+    fn from_lines() {
+        assert!(is_from_line(
+            b"From 0123456789abcdef Mon Sep 17 00:00:00 2001\n"
+        ));
+        assert!(is_from_line(
+            b"From user@example.com Tue Jan  2 03:04:05 2024\n"
+        ));
+        assert!(!is_from_line(b"From the start of time, we did not care\n"));
+        assert!(!is_from_line(b"From a Mon Sep 17 00:00:00 70\n"));
+        assert!(!is_from_line(b"From: A U Thor <author@example.com>\n"));
+        assert!(!is_from_line(b"From x\n"));
+    }
+
+    #[test]
+    fn split_emails() {
+        let mbox = b"\
+\n\
+From 1111111 Mon Sep 17 00:00:00 2001\n\
+Subject: one\n\
+\n\
+From the body, not a new email\n\
+From 2222222 Mon Sep 17 00:00:00 2001\n\
+Subject: two\n\
+\n\
+body\r\n";
+        let emails = split(mbox, false, false).unwrap();
+        assert_eq!(
+            emails,
+            vec![
+                b"From 1111111 Mon Sep 17 00:00:00 2001\n\
+                  Subject: one\n\
+                  \n\
+                  From the body, not a new email\n"
+                    .to_vec(),
+                b"From 2222222 Mon Sep 17 00:00:00 2001\n\
+                  Subject: two\n\
+                  \n\
+                  body\n"
+                    .to_vec(),
+            ]
+        );
+
+        let emails = split(mbox, true, false).unwrap();
+        assert!(emails[1].ends_with(b"body\r\n"));
+    }
+
+    #[test]
+    fn bare_email() {
+        let email = b"Subject: bare\n\nFrom 1111111 Mon Sep 17 00:00:00 2001\n";
+        assert!(split(email, false, false).is_err());
+        assert_eq!(split(email, false, true).unwrap(), vec![email.to_vec()]);
+    }
+
+    #[test]
+    fn empty_input() {
+        assert!(split(b"", false, false).unwrap().is_empty());
+        assert!(split(b" \n\n", false, false).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,198 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Auxiliary per-patch metadata.
+//!
+//! Patch metadata is a small set of `Key: value` fields associated with a patch that
+//! is not part of the patch's commit message. The metadata is stored as a git note
+//! in [`NOTES_REF`], attached to the patch's commit. Stack transactions copy these
+//! notes whenever a patch's commit is replaced, so the metadata follows the patch
+//! through refreshes, pushes, and renames.
+
+use anyhow::{anyhow, Result};
+use bstr::ByteSlice;
+
+use crate::stupid::Stupid;
+
+/// Notes reference where patch metadata is recorded.
+pub(crate) const NOTES_REF: &str = "refs/notes/stgit";
+
+/// Metadata key for the Message-Id of the most recently formatted email for a patch.
+pub(crate) const MESSAGE_ID: &str = "Message-Id";
+
+/// Metadata key for the `<branch>:<patchname>` a patch was backported from.
+pub(crate) const BACKPORT_OF: &str = "Backport-Of";
+
+/// Metadata key for the id of the Patchwork patch a patch was downloaded from.
+pub(crate) const PATCHWORK_ID: &str = "Patchwork-Id";
+
+/// Metadata key for additional `To:` addresses of a patch's email.
+pub(crate) const TO: &str = "To";
+
+/// Metadata key for additional `Cc:` addresses of a patch's email.
+pub(crate) const CC: &str = "Cc";
+
+/// Metadata key for notes about a patch to be included in email cover letters.
+pub(crate) const COVER_NOTE: &str = "Cover-Note";
+
+/// Metadata key for the time, in seconds since the Unix epoch, a patch was hidden.
+pub(crate) const HIDDEN_AT: &str = "Hidden-At";
+
+/// Ordered collection of patch metadata fields.
+///
+/// Keys are matched case-insensitively and may be repeated.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct PatchMeta {
+    entries: Vec<(String, String)>,
+}
+
+impl PatchMeta {
+    /// Read metadata for the patch with the given commit id.
+    ///
+    /// Empty metadata is returned if the commit does not have any recorded metadata.
+    pub(crate) fn read(
+        repo: &git_repository::Repository,
+        commit_id: git_repository::ObjectId,
+    ) -> Result<Self> {
+        if repo.find_reference(NOTES_REF).is_err() {
+            return Ok(Self::default());
+        }
+        if let Some(content) = repo.stupid().notes_show(NOTES_REF, commit_id)? {
+            Self::parse(&content)
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    /// Record metadata for the patch with the given commit id.
+    ///
+    /// Writing empty metadata removes any previously recorded metadata.
+    pub(crate) fn write(
+        &self,
+        repo: &git_repository::Repository,
+        commit_id: git_repository::ObjectId,
+    ) -> Result<()> {
+        if self.is_empty() && repo.find_reference(NOTES_REF).is_err() {
+            return Ok(());
+        }
+        repo.stupid()
+            .notes_set(NOTES_REF, commit_id, &self.to_bytes())
+    }
+
+    /// Parse metadata from its serialized `Key: value` lines.
+    pub(crate) fn parse(content: &[u8]) -> Result<Self> {
+        let content = content
+            .to_str()
+            .map_err(|_| anyhow!("patch metadata is not valid UTF-8"))?;
+        let mut entries = Vec::new();
+        for line in content.lines().filter(|line| !line.trim().is_empty()) {
+            if let Some((key, value)) = line.split_once(':') {
+                let key = key.trim();
+                if key.is_empty() || key.contains(char::is_whitespace) {
+                    return Err(anyhow!("invalid patch metadata key `{key}`"));
+                }
+                entries.push((key.to_string(), value.trim().to_string()));
+            } else {
+                return Err(anyhow!("invalid patch metadata line `{line}`"));
+            }
+        }
+        Ok(Self { entries })
+    }
+
+    /// Serialize metadata to `Key: value` lines.
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut content = String::new();
+        for (key, value) in &self.entries {
+            content.push_str(key);
+            content.push_str(": ");
+            content.push_str(value);
+            content.push('\n');
+        }
+        content.into_bytes()
+    }
+
+    /// Test whether there are no metadata fields.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Get the last value for the given key.
+    pub(crate) fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Get all values for the given key, in order.
+    pub(crate) fn get_all<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a str> {
+        self.entries
+            .iter()
+            .filter(move |(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Append a value for the given key, keeping any existing values.
+    pub(crate) fn add(&mut self, key: &str, value: &str) {
+        self.entries.push((key.to_string(), value.to_string()));
+    }
+
+    /// Replace all values of the given key with a single value.
+    pub(crate) fn set(&mut self, key: &str, value: &str) {
+        self.remove(key);
+        self.add(key, value);
+    }
+
+    /// Remove all values for the given key.
+    pub(crate) fn remove(&mut self, key: &str) {
+        self.entries.retain(|(k, _)| !k.eq_ignore_ascii_case(key));
+    }
+}
+
+/// Copy any patch metadata from one commit to another.
+///
+/// This is a no-op if no patch metadata has ever been recorded in the repository.
+pub(crate) fn copy(
+    repo: &git_repository::Repository,
+    from_oid: git_repository::ObjectId,
+    to_oid: git_repository::ObjectId,
+) -> Result<()> {
+    if repo.find_reference(NOTES_REF).is_ok() {
+        repo.stupid().notes_copy_ref(NOTES_REF, from_oid, to_oid)
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let content = b"Message-Id: <abc@example.com>\nLabel: wip\nLabel: net\n";
+        let meta = PatchMeta::parse(content).unwrap();
+        assert_eq!(meta.get("message-id"), Some("<abc@example.com>"));
+        assert_eq!(meta.get("LABEL"), Some("net"));
+        assert_eq!(meta.get_all("label").collect::<Vec<_>>(), ["wip", "net"]);
+        assert_eq!(meta.to_bytes(), content.to_vec());
+    }
+
+    #[test]
+    fn set_and_remove() {
+        let mut meta = PatchMeta::default();
+        meta.add("Label", "a");
+        meta.add("Label", "b");
+        meta.set("label", "c");
+        assert_eq!(meta.to_bytes(), b"label: c\n".to_vec());
+        meta.remove("LABEL");
+        assert!(meta.is_empty());
+    }
+
+    #[test]
+    fn invalid_lines() {
+        assert!(PatchMeta::parse(b"no separator\n").is_err());
+        assert!(PatchMeta::parse(b"bad key: value\n").is_err());
+        assert!(PatchMeta::parse(b"\n\n").unwrap().is_empty());
+    }
+}
@@ -1,6 +1,10 @@
 // SPDX-License-Identifier: GPL-2.0-only
 
+pub(crate) mod diffstat;
 pub(crate) mod edit;
+pub(crate) mod mailinfo;
+pub(crate) mod mbox;
+pub(crate) mod meta;
 mod name;
 pub(crate) mod range;
 
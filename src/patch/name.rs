@@ -4,7 +4,15 @@
 
 use std::str::FromStr;
 
+use anyhow::anyhow;
 use serde::{Deserialize, Serialize};
+use unicode_normalization::{is_nfc, UnicodeNormalization};
+
+/// Device names reserved by Windows, which may not be used as file names.
+const RESERVED_NAMES: &[&str] = &[
+    "con", "prn", "aux", "nul", "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8",
+    "com9", "lpt1", "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9",
+];
 
 #[derive(thiserror::Error, Debug)]
 pub(crate) enum Error {
@@ -60,6 +68,7 @@ impl PatchName {
                 }
             })
             .unwrap_or(default_name);
+        let base: String = base.nfc().collect();
 
         let mut name = String::with_capacity(base.len());
         let mut prev = '\0';
@@ -117,6 +126,12 @@ impl PatchName {
             candidate = default_name;
         }
 
+        let portable;
+        if is_reserved(candidate) {
+            portable = format!("{candidate}_");
+            candidate = portable.as_str();
+        }
+
         if len_limit.map_or(false, |limit| limit > 0 && candidate.len() > limit) {
             let len_limit = len_limit.unwrap();
             let mut word_iter = candidate.split('-').filter_map(|w| {
@@ -141,6 +156,9 @@ impl PatchName {
 
             // Could use Self(short) here, but calling try_from()/from_str()
             // validates the generated patchname.
+            if is_reserved(&short) {
+                short.push('_');
+            }
             Self::try_from(short).expect("\"{short}\" generated from \"{base}\" should be valid")
         } else {
             Self::from_str(candidate)
@@ -197,6 +215,82 @@ impl PatchName {
         self.0.eq_ignore_ascii_case(&other.0)
     }
 
+    /// Determine whether the patch name may cause problems on some filesystems.
+    ///
+    /// Patch names are stored as git references, which may in turn be stored as
+    /// files. A valid patch name may nonetheless be problematic when it is not in
+    /// Unicode NFC form (macOS may change the form of file names), contains
+    /// characters that are invalid in Windows file names, or is a reserved Windows
+    /// device name. A description of the problem is returned, if any.
+    pub(crate) fn portability_issue(&self) -> Option<&'static str> {
+        if !is_nfc(&self.0) {
+            Some("is not in Unicode NFC form")
+        } else if self.0.contains(['<', '>', '"', '|']) {
+            Some("contains characters that are invalid in Windows file names")
+        } else if self.0.contains(char::is_whitespace) {
+            Some("contains non-ASCII whitespace")
+        } else if is_reserved(&self.0) {
+            Some("is a reserved Windows device name")
+        } else {
+            None
+        }
+    }
+
+    /// Make a portable variant of the patch name.
+    ///
+    /// The returned name is in Unicode NFC form, has characters that are invalid in
+    /// Windows file names and whitespace replaced with '-', and has reserved Windows
+    /// device names suffixed with '_'.
+    pub(crate) fn to_portable(&self) -> Self {
+        let mut name: String = self
+            .0
+            .nfc()
+            .map(|c| match c {
+                '<' | '>' | '"' | '|' => '-',
+                c if c.is_whitespace() => '-',
+                c => c,
+            })
+            .collect();
+        if is_reserved(&name) {
+            let base_len = name.find('.').unwrap_or(name.len());
+            name.insert(base_len, '_');
+        }
+        Self::try_from(name).expect("portable name of valid patch name is valid")
+    }
+
+    /// Check the patch name's portability according to the `stgit.namecheck` config.
+    ///
+    /// When `stgit.namecheck` is "error", an error is returned for non-portable
+    /// patch names. When it is "warn" (the default), a warning message is returned
+    /// for the caller to display. Otherwise, no check is performed.
+    pub(crate) fn check_portability(
+        &self,
+        config: &git_repository::config::Snapshot,
+    ) -> anyhow::Result<Option<String>> {
+        let mode = config.string("stgit.namecheck");
+        let mode = mode.as_ref().map(|mode| mode.to_ascii_lowercase());
+        match mode.as_deref() {
+            Some(b"off" | b"false" | b"no" | b"0") => Ok(None),
+            Some(b"error") => {
+                if let Some(issue) = self.portability_issue() {
+                    Err(anyhow!(
+                        "patch name `{self}` {issue}; consider `{}` instead",
+                        self.to_portable()
+                    ))
+                } else {
+                    Ok(None)
+                }
+            }
+            _ => Ok(self.portability_issue().map(|issue| {
+                format!(
+                    "patch name `{self}` {issue} and may cause problems on some \
+                     filesystems; consider `{}` instead",
+                    self.to_portable()
+                )
+            })),
+        }
+    }
+
     /// Validate a patch name `str`.
     fn validate(name: &str) -> Result<(), Error> {
         if name.is_empty() {
@@ -248,6 +342,14 @@ impl PatchName {
     }
 }
 
+/// Test whether the part of a name before the first '.' is a reserved device name.
+fn is_reserved(name: &str) -> bool {
+    let base = name.split('.').next().unwrap_or(name);
+    RESERVED_NAMES
+        .iter()
+        .any(|reserved| base.eq_ignore_ascii_case(reserved))
+}
+
 impl AsRef<str> for PatchName {
     #[inline]
     fn as_ref(&self) -> &str {
@@ -382,6 +484,10 @@ mod tests {
             // Long names are only shortened at '-' word boundaries.
             ("superlongname", "superlongname", Some(6)),
             ("super-longname", "super", Some(6)),
+            // Reserved device names and non-NFC input are made portable.
+            ("Aux", "aux_", None),
+            ("nul: fix", "nul-fix", None),
+            ("e\u{301}te\u{301}", "\u{e9}t\u{e9}", None),
         ];
 
         for (raw, expected, len_limit) in cases.iter() {
@@ -406,4 +512,28 @@ mod tests {
             assert_eq!(&unique.0, expected);
         }
     }
+
+    #[test]
+    fn portability() {
+        let cases = [
+            // name, portable name
+            ("hello", None),
+            ("caf\u{e9}", None),
+            ("cafe\u{301}", Some("caf\u{e9}")),
+            ("a<b>", Some("a-b-")),
+            ("pipe|\"quote\"", Some("pipe--quote-")),
+            ("non\u{a0}breaking", Some("non-breaking")),
+            ("CON", Some("CON_")),
+            ("com1.fix", Some("com1_.fix")),
+            ("console", None),
+        ];
+
+        for (name, portable) in cases.iter() {
+            let patchname = PatchName::from_str(name).unwrap();
+            assert_eq!(patchname.portability_issue().is_some(), portable.is_some());
+            let expected = portable.unwrap_or(name);
+            assert_eq!(patchname.to_portable().as_ref() as &str, expected);
+            assert!(patchname.to_portable().portability_issue().is_none());
+        }
+    }
 }
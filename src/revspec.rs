@@ -8,6 +8,11 @@
 //! - The special `{base}` specification may be used to refer to the base commit of the
 //!   current stack. This specification may be suffixed in the usual ways, e.g.
 //!   `{base}~` may be used to specify the base commit's parent.
+//! - The special `{state}` specification may be used to refer to the current stack
+//!   state in the stack log, as shown by `stg log`. A previous stack state may be
+//!   selected by date with `{state}@{<date>}`, e.g. `{state}@{2 hours ago}`, which
+//!   refers to the most recent stack state recorded at or before the date. Like
+//!   `{base}`, these specifications may be suffixed, e.g. `{state}~2`.
 //! - Names of patches in the current stack may be specified. E.g. a specification of
 //!   `patch` would refer to the patch `patch`'s commit. This is equivalent to
 //!   specifying `refs/stacks/<branch>/patch`.
@@ -41,7 +46,13 @@ pub(crate) fn parse_branch_and_spec<'a>(
     spec: Option<&'a str>,
 ) -> (Option<&'a str>, Option<&'a str>) {
     if let Some(spec) = spec {
-        if let Some((branch, spec)) = spec.split_once(':') {
+        // Reference names may not contain "@{", so any colon following it, e.g. in
+        // the date of a `{state}@{<date>}` spec, does not delimit a branch.
+        let branch_limit = spec.find("@{").unwrap_or(spec.len());
+        if let Some((branch, spec)) = spec[..branch_limit]
+            .find(':')
+            .map(|pos| (&spec[..pos], &spec[pos + 1..]))
+        {
             // The branch from the spec string overrides the branch argument.
             if spec.is_empty() {
                 (Some(branch), None)
@@ -69,6 +80,16 @@ pub(crate) fn parse_stgit_revision<'repo>(
         if let Some((_, spec)) = spec.split_once("{base}") {
             let revspec = format!("{}{spec}", stack.base().id);
             rev_parse_single(repo, &revspec)
+        } else if let Some(state_spec) = spec.strip_prefix("{state}") {
+            let revspec = if let Some(date_spec) = state_spec.strip_prefix("@{") {
+                let (date, state_spec) = date_spec.split_once('}').ok_or_else(|| {
+                    Error::InvalidRevision(spec.to_string(), "unterminated date".to_string())
+                })?;
+                format!("{}{state_spec}", stack.state_commit_at_date(date)?.id)
+            } else {
+                format!("{}^{state_spec}", stack.get_stack_refname())
+            };
+            rev_parse_single(repo, &revspec)
         } else {
             let patch_revspec = stack.patch_revspec(spec);
             rev_parse_single(repo, &patch_revspec).or_else(|_| rev_parse_single(repo, spec))
@@ -5,6 +5,8 @@
 
 use std::rc::Rc;
 
+use anyhow::{anyhow, Result};
+
 use super::{
     iter::{AllPatches, BothPatches},
     state::PatchState,
@@ -70,17 +72,78 @@ pub(crate) trait StackStateAccess<'repo> {
         self.all_patches().find(|pn| patchname.collides(pn))
     }
 
+    /// Check that a new patch name does not collide with an existing patch name.
+    ///
+    /// Patch names differing only by case would have their references stored in the
+    /// same file on case-insensitive filesystems, as are common on macOS and Windows.
+    /// The error for such a collision suggests a disambiguated patch name. The patch
+    /// being renamed, if any, is excluded from the check.
+    fn check_patchname_available(
+        &self,
+        patchname: &PatchName,
+        renaming: Option<&PatchName>,
+    ) -> Result<()> {
+        let mut others = self.all_patches().filter(|pn| Some(*pn) != renaming);
+        if let Some(colliding_patchname) = others.find(|pn| patchname.collides(pn)) {
+            if colliding_patchname == patchname {
+                Err(anyhow!("patch `{patchname}` already exists"))
+            } else {
+                let suggestion = self.disambiguate_patchname(patchname, renaming)?;
+                Err(anyhow!(
+                    "patch name `{patchname}` differs only in case from existing patch \
+                     `{colliding_patchname}`, which collides on case-insensitive \
+                     filesystems; use another name, e.g. `{suggestion}`"
+                ))
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Disambiguate a new patch name from existing patch names differing only by case.
+    ///
+    /// The patch name is returned unchanged if it is available. Otherwise it is
+    /// suffixed with a unique integer, e.g. "FOO-1" when patch "foo" exists. An error
+    /// is returned if the exact patch name already exists. The patch being renamed,
+    /// if any, is excluded from consideration.
+    fn disambiguate_patchname(
+        &self,
+        patchname: &PatchName,
+        renaming: Option<&PatchName>,
+    ) -> Result<PatchName> {
+        let taken: Vec<&PatchName> = self
+            .all_patches()
+            .filter(|pn| Some(*pn) != renaming)
+            .collect();
+        if taken.contains(&patchname) {
+            Err(anyhow!("patch `{patchname}` already exists"))
+        } else {
+            Ok(patchname.clone().uniquify(&[], &taken))
+        }
+    }
+
     /// Get stack's top commit, or base if no applied patches.
-    fn top(&self) -> &Rc<git_repository::Commit<'repo>>;
+    fn top(&self) -> Result<&Rc<git_repository::Commit<'repo>>>;
 
     /// Get recorded head of the stack.
     ///
     /// N.B. this is probably not what you want. See also [`crate::stack::Stack::branch_head`].
-    fn head(&self) -> &Rc<git_repository::Commit<'repo>>;
+    fn head(&self) -> Result<&Rc<git_repository::Commit<'repo>>>;
 
     /// Get the commit for the given patch name.
-    fn get_patch_commit(&self, patchname: &PatchName) -> &Rc<git_repository::Commit<'repo>> {
-        &self.get_patch(patchname).commit
+    ///
+    /// The commit object is looked-up from the repository on first access. Use
+    /// [`StackStateAccess::get_patch_commit_id()`] when only the id is needed.
+    fn get_patch_commit(
+        &self,
+        patchname: &PatchName,
+    ) -> Result<&Rc<git_repository::Commit<'repo>>> {
+        self.get_patch(patchname).commit()
+    }
+
+    /// Get the commit id for the given patch name.
+    fn get_patch_commit_id(&self, patchname: &PatchName) -> git_repository::ObjectId {
+        self.get_patch(patchname).commit_id()
     }
 
     /// Test whether given patch name is applied.
@@ -107,9 +170,4 @@ pub(crate) trait StackStateAccess<'repo> {
     fn applied_and_unapplied(&self) -> BothPatches<'_> {
         BothPatches::new(self.applied(), self.unapplied())
     }
-
-    /// Iterator over unapplied and hidden patch names.
-    fn unapplied_and_hidden(&self) -> BothPatches<'_> {
-        BothPatches::new(self.unapplied(), self.hidden())
-    }
 }
@@ -16,4 +16,24 @@ pub(crate) enum Error {
 
     #[error("{msg}")]
     TransactionHalt { msg: String, conflicts: bool },
+
+    #[error(
+        "HEAD and stack top are not the same. \
+         This can happen if you modify the branch with git. \
+         See `stg repair --help` for next steps to take."
+    )]
+    HeadTopMismatch,
+
+    #[error("StGit stack not initialized for branch `{0}`")]
+    StackNotInitialized(String),
+
+    #[error(
+        "StGit stack not initialized for branch `{branch_name}`, but the stack of the \
+         missing branch `{orphaned_branch_name}` matches this branch; migrate it with \
+         `stg branch --rename {orphaned_branch_name} {branch_name}`"
+    )]
+    OrphanedStack {
+        branch_name: String,
+        orphaned_branch_name: String,
+    },
 }
@@ -13,6 +13,8 @@ mod upgrade;
 
 pub(crate) use access::{StackAccess, StackStateAccess};
 pub(crate) use error::Error;
-pub(crate) use stack::{state_refname_from_branch_name, InitializationPolicy, Stack};
+pub(crate) use stack::{
+    patch_ref_prefix_from_branch_name, state_refname_from_branch_name, InitializationPolicy, Stack,
+};
 pub(crate) use state::{PatchState, StackState};
-pub(crate) use transaction::StackTransaction;
+pub(crate) use transaction::{StackTransaction, FAILPOINTS};
@@ -133,7 +133,7 @@ impl serde::Serialize for super::state::StackState<'_> {
             patches.insert(
                 patchname,
                 SerializablePatchState {
-                    oid: patch_state.commit.id().to_string(),
+                    oid: patch_state.commit_id().to_string(),
                 },
             );
         }
@@ -8,8 +8,8 @@ use anyhow::{anyhow, Result};
 use bstr::ByteSlice;
 
 use super::{
-    state::StackState, transaction::TransactionBuilder, upgrade::stack_upgrade, PatchState,
-    StackAccess, StackStateAccess,
+    error::Error, state::StackState, transaction::TransactionBuilder, upgrade::stack_upgrade,
+    PatchState, StackAccess, StackStateAccess,
 };
 use crate::{ext::RepositoryExtended, patch::PatchName, stupid::Stupid, wrap::Branch};
 
@@ -23,11 +23,25 @@ pub(crate) struct Stack<'repo> {
     branch: Branch<'repo>,
     branch_head: Rc<git_repository::Commit<'repo>>,
     stack_refname: String,
+    patch_ref_prefix: String,
     base: Rc<git_repository::Commit<'repo>>,
     state: StackState<'repo>,
     is_initialized: bool,
 }
 
+/// Branch config key, in the `branch.<name>.stgit` section, for recorded email series
+/// Message-Ids.
+const SERIES_MESSAGE_ID_KEY: &str = "series-message-id";
+
+/// Branch config keys, in the `branch.<name>.stgit` section, for the subject and blurb
+/// of the most recently sent cover letter.
+const COVER_SUBJECT_KEY: &str = "cover-subject";
+const COVER_BLURB_KEY: &str = "cover-blurb";
+
+/// Branch config key, in the `branch.<name>.stgit` section, for the applied patches
+/// popped by `stg switch --pop-all`.
+const SWITCH_APPLIED_KEY: &str = "switch-applied";
+
 /// Policy for stack initialization when opening/discovering a stack for a branch.
 pub(crate) enum InitializationPolicy {
     /// The stack will be initialized if it is not yet initialized.
@@ -50,8 +64,9 @@ impl<'repo> Stack<'repo> {
     /// Remove StGit stack state from the repository.
     ///
     /// This removes the reference to the stack state, i.e. `refs/stacks/<name>`, and
-    /// references to the stacks patches found in `refs/patches/<name>/`. StGit specific
-    /// configuration associated with the stack is also removed from the config.
+    /// references to the stacks patches found in `refs/patches/<name>/` (or their
+    /// namespaced equivalents, see [`ref_namespace()`]). StGit specific configuration
+    /// associated with the stack is also removed from the config.
     ///
     /// N.B. stack and patch commits that become unreferenced are subject to git's
     /// normal periodic garbage collection.
@@ -60,10 +75,10 @@ impl<'repo> Stack<'repo> {
             repo,
             branch_name,
             stack_refname,
+            patch_ref_prefix,
             ..
         } = self;
         let state_ref = repo.find_reference(&stack_refname)?;
-        let patch_ref_prefix = get_patch_refname(&branch_name, "");
         for patch_reference in
             repo.references()?
                 .all()?
@@ -100,12 +115,28 @@ impl<'repo> Stack<'repo> {
         let branch = repo.get_branch(branch_name)?;
         let branch_name = branch.get_branch_name()?.to_string();
         let branch_head = Rc::new(branch.get_commit()?);
-        let stack_refname = state_refname_from_branch_name(&branch_name);
+        let namespace = ref_namespace(repo)?;
+        let stack_refname = get_stack_refname(namespace.as_deref(), &branch_name);
+        let patch_ref_prefix = get_patch_refname(namespace.as_deref(), &branch_name, "");
         let is_initialized;
 
-        stack_upgrade(repo, &branch_name)?;
+        stack_upgrade(repo, &branch_name, &stack_refname)?;
+
+        let state_ref = repo.find_reference(&stack_refname).ok();
+
+        if state_ref.is_none() && !matches!(init_policy, InitializationPolicy::MustInitialize) {
+            if let Some(orphaned_branch_name) =
+                find_orphaned_stack(repo, namespace.as_deref(), branch_head.id)?
+            {
+                return Err(Error::OrphanedStack {
+                    branch_name: branch_name.clone(),
+                    orphaned_branch_name,
+                }
+                .into());
+            }
+        }
 
-        let (state, base) = if let Ok(state_ref) = repo.find_reference(&stack_refname) {
+        let (state, base) = if let Some(state_ref) = state_ref {
             if matches!(init_policy, InitializationPolicy::MustInitialize) {
                 return Err(anyhow!(
                     "StGit stack already initialized for branch `{branch_name}`"
@@ -118,7 +149,7 @@ impl<'repo> Stack<'repo> {
                 Rc::new(
                     repo.find_object(
                         state.patches[first_patchname]
-                            .commit
+                            .commit()?
                             .parent_ids()
                             .next()
                             .unwrap(),
@@ -130,9 +161,7 @@ impl<'repo> Stack<'repo> {
             };
             (state, base)
         } else if matches!(init_policy, InitializationPolicy::RequireInitialized) {
-            return Err(anyhow!(
-                "StGit stack not initialized for branch `{branch_name}`"
-            ));
+            return Err(Error::StackNotInitialized(branch_name).into());
         } else {
             let state = StackState::new(branch_head.clone());
             let base = branch_head.clone();
@@ -152,13 +181,14 @@ impl<'repo> Stack<'repo> {
             (state, base)
         };
 
-        ensure_patch_refs(repo, &branch_name, &state)?;
+        ensure_patch_refs(repo, &patch_ref_prefix, &state)?;
         Ok(Self {
             repo,
             branch_name,
             branch,
             branch_head,
             stack_refname,
+            patch_ref_prefix,
             base,
             state,
             is_initialized,
@@ -207,6 +237,147 @@ impl<'repo> Stack<'repo> {
         Ok(())
     }
 
+    /// Get the recorded Message-Ids of previously formatted email series.
+    ///
+    /// Each entry is a series version and the Message-Id of the first email of that
+    /// version of the series, ordered from least to most recently formatted.
+    pub(crate) fn series_message_ids(&self) -> Result<Vec<(String, String)>> {
+        let subsection = format!("{}.stgit", self.branch_name);
+        let local_config_file = self.repo.local_config_file()?;
+        let values = local_config_file
+            .raw_values(
+                "branch",
+                Some(subsection.as_str().into()),
+                SERIES_MESSAGE_ID_KEY,
+            )
+            .unwrap_or_default();
+        Ok(values
+            .iter()
+            .filter_map(|value| {
+                let value = value.to_str().ok()?;
+                let (version, message_id) = value.trim().split_once(' ')?;
+                Some((version.to_string(), message_id.trim().to_string()))
+            })
+            .collect())
+    }
+
+    /// Record the Message-Id of the first email of a formatted email series.
+    ///
+    /// Any Message-Id previously recorded for the same series version is replaced.
+    pub(crate) fn record_series_message_id(&self, version: &str, message_id: &str) -> Result<()> {
+        let mut entries = self.series_message_ids()?;
+        entries.retain(|(v, _)| v != version);
+        entries.push((version.to_string(), message_id.to_string()));
+
+        let subsection = format!("{}.stgit", self.branch_name);
+        let mut local_config_file = self.repo.local_config_file()?;
+        let mut section = local_config_file
+            .section_mut_or_create_new("branch", Some(subsection.as_str().into()))?;
+        while section.remove(SERIES_MESSAGE_ID_KEY).is_some() {}
+        for (version, message_id) in &entries {
+            section.push(
+                git_repository::config::parse::section::Key::try_from(SERIES_MESSAGE_ID_KEY)?,
+                Some(format!("{version} {message_id}").as_str().into()),
+            );
+        }
+        self.repo.write_local_config(local_config_file)?;
+        Ok(())
+    }
+
+    /// Get the subject and blurb of the most recently sent cover letter, if any.
+    pub(crate) fn previous_cover_letter(&self) -> Option<(Option<String>, String)> {
+        let subsection = format!("{}.stgit", self.branch_name);
+        let config = self.repo.config_snapshot();
+        let get = |key: &str| {
+            config
+                .plumbing()
+                .string("branch", Some(subsection.as_str().into()), key)
+                .map(|value| value.to_str_lossy().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        get(COVER_BLURB_KEY).map(|blurb| (get(COVER_SUBJECT_KEY), blurb))
+    }
+
+    /// Record the subject and blurb of a sent cover letter for reuse by later rerolls.
+    pub(crate) fn record_cover_letter(&self, subject: Option<&str>, blurb: &str) -> Result<()> {
+        let subsection = format!("{}.stgit", self.branch_name);
+        let subsection = subsection.as_str();
+        let mut local_config_file = self.repo.local_config_file()?;
+        if let Some(subject) = subject {
+            local_config_file.set_raw_value(
+                "branch",
+                Some(subsection.into()),
+                COVER_SUBJECT_KEY,
+                subject,
+            )?;
+        } else if let Ok(mut value) =
+            local_config_file.raw_value_mut("branch", Some(subsection.into()), COVER_SUBJECT_KEY)
+        {
+            value.delete();
+        }
+        local_config_file.set_raw_value(
+            "branch",
+            Some(subsection.into()),
+            COVER_BLURB_KEY,
+            blurb,
+        )?;
+        self.repo.write_local_config(local_config_file)?;
+        Ok(())
+    }
+
+    /// Get the patches that were applied when `stg switch --pop-all` left the branch.
+    pub(crate) fn switch_applied(&self) -> Result<Vec<PatchName>> {
+        let subsection = format!("{}.stgit", self.branch_name);
+        let config = self.repo.config_snapshot();
+        if let Some(value) = config.plumbing().string(
+            "branch",
+            Some(subsection.as_str().into()),
+            SWITCH_APPLIED_KEY,
+        ) {
+            Ok(value
+                .to_str_lossy()
+                .split_whitespace()
+                .map(PatchName::from_str)
+                .collect::<Result<_, _>>()?)
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    /// Record the patches popped by `stg switch --pop-all`.
+    ///
+    /// The recorded patches are cleared when `patchnames` is empty.
+    pub(crate) fn record_switch_applied(&self, patchnames: &[PatchName]) -> Result<()> {
+        let subsection = format!("{}.stgit", self.branch_name);
+        let subsection = subsection.as_str();
+        let mut local_config_file = self.repo.local_config_file()?;
+        if patchnames.is_empty() {
+            if let Ok(mut value) = local_config_file.raw_value_mut(
+                "branch",
+                Some(subsection.into()),
+                SWITCH_APPLIED_KEY,
+            ) {
+                value.delete();
+            } else {
+                return Ok(());
+            }
+        } else {
+            let value = patchnames
+                .iter()
+                .map(PatchName::as_ref)
+                .collect::<Vec<&str>>()
+                .join(" ");
+            local_config_file.set_raw_value(
+                "branch",
+                Some(subsection.into()),
+                SWITCH_APPLIED_KEY,
+                value.as_str(),
+            )?;
+        }
+        self.repo.write_local_config(local_config_file)?;
+        Ok(())
+    }
+
     /// Check whether the stack's recorded head matches the branch's head.
     pub(crate) fn is_head_top(&self) -> bool {
         self.state.head.id() == self.branch_head.id()
@@ -217,11 +388,7 @@ impl<'repo> Stack<'repo> {
         if self.state.applied.is_empty() || self.is_head_top() {
             Ok(())
         } else {
-            Err(anyhow!(
-                "HEAD and stack top are not the same. \
-                 This can happen if you modify the branch with git. \
-                 See `stg repair --help` for next steps to take."
-            ))
+            Err(Error::HeadTopMismatch.into())
         }
     }
 
@@ -289,6 +456,37 @@ impl<'repo> Stack<'repo> {
         Ok(())
     }
 
+    /// Find the stack state in effect at the given date.
+    ///
+    /// The date may be in any format accepted by git, including relative dates such as
+    /// "2 hours ago". The stack log is walked from the current state to find the most
+    /// recent state recorded at or before the date. The returned commit is from the
+    /// simplified stack log, i.e. it is one of the commits shown by `stg log`.
+    pub(crate) fn state_commit_at_date(&self, date: &str) -> Result<git_repository::Commit<'repo>> {
+        if !self.is_initialized {
+            return Err(Error::StackNotInitialized(self.branch_name.clone()).into());
+        }
+        let timestamp = self.repo.stupid().rev_parse_approxidate(date)?;
+        let state_commit = self
+            .repo
+            .find_reference(self.stack_refname.as_str())?
+            .into_fully_peeled_id()?
+            .object()?
+            .try_into_commit()?;
+        let mut next_id = state_commit.parent_ids().next().map(|id| id.detach());
+        while let Some(commit_id) = next_id {
+            let commit = self.repo.find_commit(commit_id)?;
+            if i64::from(commit.time()?.seconds_since_unix_epoch) <= timestamp {
+                return Ok(commit);
+            }
+            next_id = commit.parent_ids().next().map(|id| id.detach());
+        }
+        Err(anyhow!(
+            "no stack state for branch `{}` at or before `{date}`",
+            self.branch_name
+        ))
+    }
+
     /// Update the branch and branch head commit.
     pub(super) fn update_head(
         &mut self,
@@ -313,7 +511,7 @@ impl<'repo> Stack<'repo> {
     ///
     /// I.e. `refs/patches/<branch>/<patch_spec>`.
     pub(crate) fn patch_revspec(&self, patch_spec: &str) -> String {
-        get_patch_refname(&self.branch_name, patch_spec)
+        format!("{}{patch_spec}", self.patch_ref_prefix)
     }
 }
 
@@ -360,23 +558,146 @@ impl<'repo> StackStateAccess<'repo> for Stack<'repo> {
         self.state.has_patch(patchname)
     }
 
-    fn top(&self) -> &Rc<git_repository::Commit<'repo>> {
+    fn top(&self) -> Result<&Rc<git_repository::Commit<'repo>>> {
         self.state.top()
     }
 
-    fn head(&self) -> &Rc<git_repository::Commit<'repo>> {
+    fn head(&self) -> Result<&Rc<git_repository::Commit<'repo>>> {
         self.state.head()
     }
 }
 
+/// Get the namespace for StGit stack and patch references from the config.
+///
+/// When `stgit.refnamespace` is set, the stack state reference for a branch is
+/// `refs/stacks-ns/<namespace>/<branch>` and its patch references are found in
+/// `refs/patches-ns/<namespace>/<branch>/`. This allows several users of a shared
+/// repository to keep their own stacks for the same branch without collisions.
+///
+/// Namespaced references are kept apart from `refs/stacks/` and `refs/patches/` so
+/// that they cannot be mistaken for the stack of a branch named `<namespace>/<branch>`.
+/// For the same reason, the namespace itself may not contain a '/'.
+fn ref_namespace(repo: &git_repository::Repository) -> Result<Option<String>> {
+    if let Some(namespace) = repo.config_snapshot().string("stgit.refnamespace") {
+        let namespace = namespace
+            .to_str()
+            .ok()
+            .map(|namespace| namespace.trim_matches('/'))
+            .filter(|namespace| {
+                !namespace.is_empty()
+                    && !namespace.contains('/')
+                    && git_repository::refs::FullName::try_from(format!(
+                        "refs/stacks-ns/{namespace}"
+                    ))
+                    .is_ok()
+            })
+            .ok_or_else(|| anyhow!("invalid `stgit.refnamespace` value `{namespace}`"))?;
+        Ok(Some(namespace.to_string()))
+    } else {
+        Ok(None)
+    }
+}
+
 /// Get reference name for StGit stack state for the given branch name.
-pub(crate) fn state_refname_from_branch_name(branch_name: &str) -> String {
-    format!("refs/stacks/{branch_name}")
+///
+/// The configured reference namespace, if any, is honored.
+pub(crate) fn state_refname_from_branch_name(
+    repo: &git_repository::Repository,
+    branch_name: &str,
+) -> Result<String> {
+    Ok(get_stack_refname(
+        ref_namespace(repo)?.as_deref(),
+        branch_name,
+    ))
+}
+
+/// Get the prefix of the patch reference names for the given branch name.
+///
+/// The configured reference namespace, if any, is honored.
+pub(crate) fn patch_ref_prefix_from_branch_name(
+    repo: &git_repository::Repository,
+    branch_name: &str,
+) -> Result<String> {
+    Ok(get_patch_refname(
+        ref_namespace(repo)?.as_deref(),
+        branch_name,
+        "",
+    ))
+}
+
+/// Get reference name for StGit stack state in the given namespace and branch.
+fn get_stack_refname(namespace: Option<&str>, branch_name: &str) -> String {
+    if let Some(namespace) = namespace {
+        format!("refs/stacks-ns/{namespace}/{branch_name}")
+    } else {
+        format!("refs/stacks/{branch_name}")
+    }
+}
+
+/// Get reference name for a patch in the given namespace and branch.
+fn get_patch_refname(namespace: Option<&str>, branch_name: &str, patch_spec: &str) -> String {
+    if let Some(namespace) = namespace {
+        format!("refs/patches-ns/{namespace}/{branch_name}/{patch_spec}")
+    } else {
+        format!("refs/patches/{branch_name}/{patch_spec}")
+    }
 }
 
-/// Get reference name for a patch in the given branch.
-fn get_patch_refname(branch_name: &str, patch_spec: &str) -> String {
-    format!("refs/patches/{branch_name}/{patch_spec}")
+/// Find stack state orphaned by a branch being renamed or deleted outside of StGit.
+///
+/// Stack state is orphaned when its branch no longer exists. Only stack state in the
+/// given reference namespace is considered, and it is only matched if the stack's head
+/// is the given branch head commit. The name of the missing branch is returned.
+fn find_orphaned_stack(
+    repo: &git_repository::Repository,
+    namespace: Option<&str>,
+    branch_head_id: git_repository::ObjectId,
+) -> Result<Option<String>> {
+    let stack_ref_prefix = get_stack_refname(namespace, "");
+
+    for state_ref in repo
+        .references()?
+        .all()?
+        .filter_map(Result::ok)
+        .filter(|reference| {
+            reference
+                .name()
+                .as_bstr()
+                .starts_with(stack_ref_prefix.as_bytes())
+        })
+    {
+        let orphaned_branch_name = if let Some(name) = state_ref
+            .name()
+            .as_bstr()
+            .strip_prefix(stack_ref_prefix.as_bytes())
+            .and_then(|name| name.to_str().ok())
+        {
+            name.to_string()
+        } else {
+            continue;
+        };
+
+        if repo
+            .find_reference(format!("refs/heads/{orphaned_branch_name}").as_str())
+            .is_ok()
+        {
+            continue;
+        }
+
+        let state = state_ref
+            .id()
+            .object()
+            .ok()
+            .and_then(|object| object.try_into_commit().ok())
+            .and_then(|commit| commit.tree().ok())
+            .and_then(|tree| StackState::from_tree(repo, tree).ok());
+
+        if state.map_or(false, |state| state.head.id == branch_head_id) {
+            return Ok(Some(orphaned_branch_name));
+        }
+    }
+
+    Ok(None)
 }
 
 /// Fix-up stack's patch references.
@@ -388,10 +709,9 @@ fn get_patch_refname(branch_name: &str, patch_spec: &str) -> String {
 /// to the stack's patch refs.
 fn ensure_patch_refs(
     repo: &git_repository::Repository,
-    branch_name: &str,
+    patch_ref_prefix: &str,
     state: &StackState,
 ) -> Result<()> {
-    let patch_ref_prefix = get_patch_refname(branch_name, "");
     let mut state_patches: BTreeMap<&PatchName, &PatchState> = state.patches.iter().collect();
 
     for mut existing_ref in repo
@@ -407,16 +727,16 @@ fn ensure_patch_refs(
     {
         if let Ok(existing_refname) = existing_ref.name().as_bstr().to_str() {
             let patchname_str = existing_refname
-                .strip_prefix(&patch_ref_prefix)
+                .strip_prefix(patch_ref_prefix)
                 .expect("did starts_with above");
             if let Ok(existing_patchname) = PatchName::from_str(patchname_str) {
                 if let Some(patchdesc) = state_patches.remove(&existing_patchname) {
                     if let Some(existing_id) = existing_ref.target().try_id() {
-                        if existing_id == patchdesc.commit.id {
+                        if existing_id == patchdesc.commit_id() {
                             // Patch ref is good. Do nothing.
                         } else {
                             existing_ref
-                                .set_target_id(patchdesc.commit.id, "fixup broken patch ref")?;
+                                .set_target_id(patchdesc.commit_id(), "fixup broken patch ref")?;
                         }
                     } else {
                         // Existing ref seems to be symbolic, and not direct.
@@ -431,7 +751,7 @@ fn ensure_patch_refs(
                                     expected: git_repository::refs::transaction::PreviousValue::ExistingMustMatch(
                                         existing_ref.target().into_owned()
                                     ),
-                                    new: git_repository::refs::Target::Peeled(patchdesc.commit.id),
+                                    new: git_repository::refs::Target::Peeled(patchdesc.commit_id()),
                                 },
                                 name: existing_ref.name().into(),
                                 deref: false,
@@ -463,11 +783,10 @@ fn ensure_patch_refs(
                     message: "fixup missing patch ref".into(),
                 },
                 expected: git_repository::refs::transaction::PreviousValue::MustNotExist,
-                new: git_repository::refs::Target::Peeled(patchdesc.commit.id),
+                new: git_repository::refs::Target::Peeled(patchdesc.commit_id()),
             },
-            name: git_repository::refs::FullName::try_from(get_patch_refname(
-                branch_name,
-                patchname.as_ref(),
+            name: git_repository::refs::FullName::try_from(format!(
+                "{patch_ref_prefix}{patchname}"
             ))?,
             deref: false,
         })?;
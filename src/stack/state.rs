@@ -7,7 +7,9 @@
 
 use std::{collections::BTreeMap, io::Write, rc::Rc, str};
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
+use git_repository::odb::Find;
+use once_cell::unsync::OnceCell;
 
 use super::{access::StackStateAccess, iter::AllPatches, serde::RawStackState};
 use crate::{
@@ -48,10 +50,67 @@ pub(crate) struct StackState<'repo> {
 
 /// State associated with a patch.
 ///
-/// Currently the only state is a commit object.
-#[derive(Clone, Debug)]
+/// Currently the only state is a commit object. Since large stacks may have many
+/// patches, the commit object is only looked-up from the repository the first time it
+/// is accessed. Callers that only need the commit id should use
+/// [`PatchState::commit_id()`] to avoid this look-up.
+#[derive(Clone)]
 pub(crate) struct PatchState<'repo> {
-    pub commit: Rc<git_repository::Commit<'repo>>,
+    repo: &'repo git_repository::Repository,
+    commit_id: git_repository::ObjectId,
+    commit: OnceCell<Rc<git_repository::Commit<'repo>>>,
+}
+
+impl<'repo> PatchState<'repo> {
+    /// Instantiate patch state with a commit that will be looked-up on first access.
+    pub(crate) fn new(
+        repo: &'repo git_repository::Repository,
+        commit_id: git_repository::ObjectId,
+    ) -> Self {
+        Self {
+            repo,
+            commit_id,
+            commit: OnceCell::new(),
+        }
+    }
+
+    /// Instantiate patch state with an already looked-up commit.
+    pub(crate) fn with_commit(
+        repo: &'repo git_repository::Repository,
+        commit: Rc<git_repository::Commit<'repo>>,
+    ) -> Self {
+        Self {
+            repo,
+            commit_id: commit.id,
+            commit: OnceCell::with_value(commit),
+        }
+    }
+
+    /// Get the id of the patch's commit.
+    pub(crate) fn commit_id(&self) -> git_repository::ObjectId {
+        self.commit_id
+    }
+
+    /// Get the patch's commit object, looking it up from the repository if needed.
+    pub(crate) fn commit(&self) -> Result<&Rc<git_repository::Commit<'repo>>> {
+        self.commit.get_or_try_init(|| {
+            let commit = self
+                .repo
+                .find_object(self.commit_id)
+                .map_err(anyhow::Error::from)
+                .and_then(|object| Ok(object.try_into_commit()?))
+                .with_context(|| format!("looking up patch commit `{}`", self.commit_id))?;
+            Ok(Rc::new(commit))
+        })
+    }
+}
+
+impl<'repo> std::fmt::Debug for PatchState<'repo> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PatchState")
+            .field("commit_id", &self.commit_id)
+            .finish()
+    }
 }
 
 impl<'repo> StackStateAccess<'repo> for StackState<'repo> {
@@ -75,16 +134,16 @@ impl<'repo> StackStateAccess<'repo> for StackState<'repo> {
         self.patches.contains_key(patchname)
     }
 
-    fn top(&self) -> &Rc<git_repository::Commit<'repo>> {
+    fn top(&self) -> Result<&Rc<git_repository::Commit<'repo>>> {
         if let Some(patchname) = self.applied().last() {
-            &self.patches[patchname].commit
+            self.patches[patchname].commit()
         } else {
-            &self.head
+            Ok(&self.head)
         }
     }
 
-    fn head(&self) -> &Rc<git_repository::Commit<'repo>> {
-        &self.head
+    fn head(&self) -> Result<&Rc<git_repository::Commit<'repo>>> {
+        Ok(&self.head)
     }
 }
 
@@ -132,21 +191,22 @@ impl<'repo> StackState<'repo> {
 
     /// Convert [`RawStackState`] to [`StackState`].
     ///
-    /// Commit objects are looked-up from commit ids in the raw state. This may fail if
-    /// the raw state references commit ids not present in the repository.
+    /// Patch commit objects are looked-up lazily from the commit ids in the raw state,
+    /// but this will fail if the raw state references commit ids not present in the
+    /// repository.
     pub(super) fn from_raw_state(
         repo: &'repo git_repository::Repository,
         raw_state: RawStackState,
     ) -> Result<Self> {
         let mut patches = BTreeMap::new();
         for (patchname, raw_state) in raw_state.patches {
-            let commit = repo.find_object(raw_state.oid)?.try_into_commit()?;
-            patches.insert(
-                patchname,
-                PatchState {
-                    commit: Rc::new(commit),
-                },
-            );
+            if !repo.objects.contains(raw_state.oid) {
+                return Err(anyhow!(
+                    "commit {} for patch `{patchname}` not found",
+                    raw_state.oid
+                ));
+            }
+            patches.insert(patchname, PatchState::new(repo, raw_state.oid));
         }
         Ok(Self {
             prev: if let Some(prev_id) = raw_state.prev {
@@ -168,11 +228,11 @@ impl<'repo> StackState<'repo> {
     }
 
     /// Return commit of topmost patch, or stack base if no patches applied.
-    pub(crate) fn top(&self) -> &Rc<git_repository::Commit<'repo>> {
+    pub(crate) fn top(&self) -> Result<&Rc<git_repository::Commit<'repo>>> {
         if let Some(patchname) = self.applied.last() {
-            &self.patches[patchname].commit
+            self.patches[patchname].commit()
         } else {
-            &self.head
+            Ok(&self.head)
         }
     }
 
@@ -217,7 +277,6 @@ impl<'repo> StackState<'repo> {
             Some(prev_commit) => {
                 vec![prev_commit
                     .parent_ids()
-                    .into_iter()
                     .next()
                     .expect("prev state commit has a parent")
                     .detach()]
@@ -243,19 +302,19 @@ impl<'repo> StackState<'repo> {
 
         let mut parent_set = indexmap::IndexSet::new();
         parent_set.insert(self.head.id);
-        parent_set.insert(self.top().id);
+        parent_set.insert(self.top()?.id);
         for patchname in &self.unapplied {
-            parent_set.insert(self.patches[patchname].commit.id);
+            parent_set.insert(self.patches[patchname].commit_id());
         }
         for patchname in &self.hidden {
-            parent_set.insert(self.patches[patchname].commit.id);
+            parent_set.insert(self.patches[patchname].commit_id());
         }
 
         if let Some(prev_commit) = self.prev.as_ref() {
             parent_set.insert(prev_commit.id);
             let prev_state = prev_state.as_ref().unwrap();
             for patchname in prev_state.all_patches() {
-                parent_set.remove(&prev_state.patches[patchname].commit.id);
+                parent_set.remove(&prev_state.patches[patchname].commit_id());
             }
         }
 
@@ -390,12 +449,11 @@ impl<'repo> StackState<'repo> {
         prev_state: Option<&StackState>,
         prev_patches_tree: &Option<git_repository::Tree>,
     ) -> Result<git_repository::ObjectId> {
-        let commit = &self.patches[patchname].commit;
-        let commit_ref = commit.decode()?;
+        let patch_state = &self.patches[patchname];
 
         if let Some(prev_state) = prev_state {
             if let Some(prev_patch) = prev_state.patches.get(patchname) {
-                if prev_patch.commit.id == commit.id {
+                if prev_patch.commit_id() == patch_state.commit_id() {
                     if let Some(prev_patches_tree) = prev_patches_tree {
                         let patchname_str: &str = patchname.as_ref();
                         if let Some(prev_patch_entry) = prev_patches_tree
@@ -415,6 +473,8 @@ impl<'repo> StackState<'repo> {
             }
         }
 
+        let commit = patch_state.commit()?;
+        let commit_ref = commit.decode()?;
         let parent = commit.get_parent_commit()?;
         let mut patch_meta: Vec<u8> = Vec::with_capacity(1024);
         let patch_meta = &mut patch_meta;
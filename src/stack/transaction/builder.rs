@@ -7,7 +7,7 @@ use anyhow::Result;
 use super::{
     options::{ConflictMode, TransactionOptions},
     ui::TransactionUserInterface,
-    ExecuteContext, StackTransaction,
+    ExecuteContext, StackTransaction, TransactionStats,
 };
 use crate::stack::{Stack, StackAccess, StackStateAccess};
 
@@ -117,6 +117,14 @@ impl<'repo> TransactionBuilder<'repo> {
         self
     }
 
+    /// Determines whether the committer of a patch's existing commit is kept when the
+    /// patch's commit is rewritten by pushing it onto a different parent.
+    #[must_use]
+    pub(crate) fn stable_ids(mut self, yes: bool) -> Self {
+        self.options.stable_ids = yes;
+        self
+    }
+
     /// Perform stack transaction operations.
     ///
     /// The closure provided to this method may call various methods on the provided
@@ -130,7 +138,7 @@ impl<'repo> TransactionBuilder<'repo> {
     #[must_use]
     pub(crate) fn transact<F>(self, f: F) -> ExecuteContext<'repo>
     where
-        F: FnOnce(&mut StackTransaction) -> Result<()>,
+        F: FnOnce(&mut StackTransaction<'repo>) -> Result<()>,
     {
         let Self {
             stack,
@@ -162,6 +170,7 @@ impl<'repo> TransactionBuilder<'repo> {
             updated_base: None,
             current_tree_id,
             error: None,
+            stats: TransactionStats::default(),
         };
 
         transaction.error = f(&mut transaction).err();
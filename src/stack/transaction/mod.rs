@@ -32,20 +32,21 @@ mod ui;
 
 use std::{collections::BTreeMap, rc::Rc};
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
+use bstr::ByteSlice;
 use indexmap::IndexSet;
 
 pub(crate) use self::builder::TransactionBuilder;
 use self::{
-    options::{ConflictMode, TransactionOptions},
+    options::{ConflictMode, PushConflictPolicy, TransactionOptions},
     ui::TransactionUserInterface,
 };
 use super::{error::Error, state::StackState, StackAccess};
 use crate::{
     ext::{CommitExtended, RepositoryExtended},
     patch::PatchName,
-    stack::{PatchState, Stack, StackStateAccess},
-    stupid::{Stupid, StupidContext},
+    stack::{state_refname_from_branch_name, PatchState, Stack, StackStateAccess},
+    stupid::{status::StatusEntryKind, Stupid, StupidContext},
     wrap::Branch,
 };
 
@@ -64,6 +65,20 @@ pub(crate) struct StackTransaction<'repo> {
 
     current_tree_id: git_repository::ObjectId,
     error: Option<anyhow::Error>,
+    stats: TransactionStats,
+}
+
+/// Counts of the operations performed by a transaction.
+///
+/// These counts, along with the number of files changed in the stack head, are
+/// recorded as trailers in the stack state commit message. See
+/// [`make_state_commit_msg()`].
+#[derive(Default)]
+struct TransactionStats {
+    pushed: usize,
+    popped: usize,
+    renamed: usize,
+    conflicts: usize,
 }
 
 /// Status of a pushed patch.
@@ -83,6 +98,9 @@ enum PushStatus {
     /// The push resulted in merge conflicts.
     Conflict,
 
+    /// The patch was not pushed because it would have resulted in merge conflicts.
+    LeftUnapplied,
+
     /// The push resulted in the patch's diff becoming empty.
     Empty,
 
@@ -119,7 +137,7 @@ impl<'repo> ExecuteContext<'repo> {
         }
 
         // Need to cache a few bits of transaction state for later use.
-        let trans_head = transaction.head().clone();
+        let trans_head = transaction.head()?.clone();
         let trans_head_tree_id = trans_head.tree_id()?.detach();
         let trans_top_patchname = transaction.applied().last().cloned();
 
@@ -133,11 +151,17 @@ impl<'repo> ExecuteContext<'repo> {
             updated_patches,
             current_tree_id,
             error,
+            stats,
             ..
         } = transaction;
 
         let repo = stack.repo;
         let stack_top_patchname = stack.applied().last().cloned();
+        let prev_hidden: Vec<(PatchName, git_repository::ObjectId)> = stack
+            .hidden()
+            .iter()
+            .map(|pn| (pn.clone(), stack.get_patch_commit_id(pn)))
+            .collect();
         let rollback_tree_id = stack.get_branch_head().tree_id()?.detach();
 
         // Only proceed for halt errors
@@ -150,6 +174,55 @@ impl<'repo> ExecuteContext<'repo> {
             false
         };
 
+        // The branch reflog message names the patches changed by this transaction.
+        let branch_reflog_msg = if options.set_head {
+            let old_applied = stack.applied();
+            let mut changed_patches: Vec<&PatchName> = applied
+                .iter()
+                .chain(unapplied.iter())
+                .chain(hidden.iter())
+                .filter(|pn| {
+                    !stack.has_patch(pn)
+                        || matches!(updated_patches.get(*pn), Some(Some(_)))
+                        || old_applied.contains(pn) != applied.contains(pn)
+                })
+                .collect();
+            changed_patches.extend(
+                updated_patches
+                    .iter()
+                    .filter_map(|(pn, maybe_patch)| maybe_patch.is_none().then_some(pn)),
+            );
+            Some(make_branch_reflog_msg(
+                &repo.config_snapshot(),
+                reflog_msg,
+                &changed_patches,
+                has_conflicts,
+            )?)
+        } else {
+            None
+        };
+
+        // Local branches pointing at rewritten patch commits follow their patches when
+        // "stgit.updateRefs" is enabled.
+        let files_changed = if rollback_tree_id == trans_head_tree_id {
+            0
+        } else {
+            repo.stupid()
+                .diff_tree_files(rollback_tree_id, trans_head_tree_id)?
+                .iter()
+                .count()
+        };
+
+        let dependent_branches = if repo
+            .config_snapshot()
+            .boolean("stgit.updateRefs")
+            .unwrap_or(false)
+        {
+            find_dependent_branches(&stack, &updated_patches)?
+        } else {
+            Vec::new()
+        };
+
         // Log external modifications
         let mut stack = if stack.is_head_top() {
             stack
@@ -184,18 +257,23 @@ impl<'repo> ExecuteContext<'repo> {
             if !options.allow_bad_head {
                 stack.check_head_top_mismatch()?;
             }
-            checkout(
-                repo,
-                &options,
-                stack_top_patchname.as_ref(),
-                trans_top_patchname.as_ref(),
-                current_tree_id,
-                trans_head_tree_id,
-            )
-            .map_err(|e| rollback(current_tree_id, e))?;
+            failpoint("checkout")
+                .and_then(|()| {
+                    checkout(
+                        repo,
+                        &options,
+                        stack_top_patchname.as_ref(),
+                        trans_top_patchname.as_ref(),
+                        current_tree_id,
+                        trans_head_tree_id,
+                    )
+                })
+                .map_err(|e| rollback(current_tree_id, e))?;
         }
 
         crate::signal::critical(|| {
+            failpoint("state-commit")?;
+
             // Commit updated stack state
             let conflict_msg;
             let state_reflog_msg = if has_conflicts {
@@ -210,6 +288,11 @@ impl<'repo> ExecuteContext<'repo> {
                 .into_fully_peeled_id()?
                 .object()?
                 .try_into_commit()?;
+            let prev_branch_id = stack.get_branch_head().id;
+            let prev_patch_commit_ids: Vec<Option<git_repository::ObjectId>> = updated_patches
+                .keys()
+                .map(|pn| stack.has_patch(pn).then(|| stack.get_patch_commit_id(pn)))
+                .collect();
             let state = stack.state_mut();
             for (patchname, maybe_patch) in &updated_patches {
                 if let Some(patch) = maybe_patch {
@@ -223,69 +306,140 @@ impl<'repo> ExecuteContext<'repo> {
             state.applied = applied;
             state.unapplied = unapplied;
             state.hidden = hidden;
-            let state_commit_id = state.commit(repo, None, state_reflog_msg)?;
+            let state_commit_msg = make_state_commit_msg(state_reflog_msg, &stats, files_changed);
+            let state_commit_id = state.commit(repo, None, &state_commit_msg)?;
 
-            // Update various refs as a single transaction. This reference transaction is
-            // not quite atomic--it is possible for some, but not all references to be
-            // updated--but atomic enough in practice for our purposes.
-            let mut ref_edits = Vec::new();
+            // Update the branch reference first, then the patch and dependent branch
+            // references, and finally the stack state reference. Should any of these
+            // steps fail, the references already updated are restored to their prior
+            // values so that the stack state reference never disagrees with them.
             let log = git_repository::refs::transaction::LogChange {
                 mode: git_repository::refs::transaction::RefLog::AndReference,
                 force_create_reflog: false,
                 message: state_reflog_msg.into(),
             };
-            for (patchname, maybe_patch) in &updated_patches {
-                let change = if let Some(patch) = maybe_patch {
-                    git_repository::refs::transaction::Change::Update {
-                        log: log.clone(),
-                        expected: git_repository::refs::transaction::PreviousValue::Any, // TODO?
-                        new: git_repository::refs::Target::Peeled(patch.commit.id),
-                    }
-                } else {
-                    git_repository::refs::transaction::Change::Delete {
+            let update = |name: git_repository::refs::FullName,
+                          message: &str,
+                          expected: git_repository::refs::transaction::PreviousValue,
+                          new: git_repository::ObjectId| {
+                git_repository::refs::transaction::RefEdit {
+                    change: git_repository::refs::transaction::Change::Update {
+                        log: git_repository::refs::transaction::LogChange {
+                            message: message.into(),
+                            ..log.clone()
+                        },
+                        expected,
+                        new: git_repository::refs::Target::Peeled(new),
+                    },
+                    name,
+                    deref: false,
+                }
+            };
+            let delete =
+                |name: git_repository::refs::FullName| git_repository::refs::transaction::RefEdit {
+                    change: git_repository::refs::transaction::Change::Delete {
                         expected: git_repository::refs::transaction::PreviousValue::Any,
                         log: git_repository::refs::transaction::RefLog::AndReference,
-                    }
-                };
-                ref_edits.push(git_repository::refs::transaction::RefEdit {
-                    change,
-                    name: git_repository::refs::FullName::try_from(stack.patch_refname(patchname))
-                        .expect("patch reference name is valid"),
+                    },
+                    name,
                     deref: false,
-                });
+                };
+            let rollback_msg = format!("{state_reflog_msg} (rolled back)");
+
+            let mut branch_edits = Vec::new();
+            let mut branch_restores = Vec::new();
+            if let Some(branch_reflog_msg) = branch_reflog_msg {
+                let name = branch_ref_name.clone();
+                branch_edits.push(update(
+                    name.clone(),
+                    &branch_reflog_msg,
+                    git_repository::refs::transaction::PreviousValue::Any,
+                    trans_head.id,
+                ));
+                branch_restores.push(update(
+                    name,
+                    &rollback_msg,
+                    git_repository::refs::transaction::PreviousValue::Any,
+                    prev_branch_id,
+                ));
             }
 
-            ref_edits.push(git_repository::refs::transaction::RefEdit {
-                change: git_repository::refs::transaction::Change::Update {
-                    log: log.clone(),
-                    expected: if let Some(prev_state_commit) = stack.state_mut().prev.as_ref() {
-                        git_repository::refs::transaction::PreviousValue::ExistingMustMatch(
-                            git_repository::refs::Target::Peeled(prev_state_commit.id),
-                        )
-                    } else {
-                        git_repository::refs::transaction::PreviousValue::MustNotExist
-                    },
-                    new: git_repository::refs::Target::Peeled(state_commit_id),
-                },
-                name: git_repository::refs::FullName::try_from(stack.get_stack_refname())
+            let mut patch_edits = Vec::new();
+            let mut patch_restores = Vec::new();
+            for ((patchname, maybe_patch), prev_commit_id) in
+                updated_patches.iter().zip(prev_patch_commit_ids)
+            {
+                let name = git_repository::refs::FullName::try_from(stack.patch_refname(patchname))
+                    .expect("patch reference name is valid");
+                if let Some(patch) = maybe_patch {
+                    patch_edits.push(update(
+                        name.clone(),
+                        state_reflog_msg,
+                        git_repository::refs::transaction::PreviousValue::Any, // TODO?
+                        patch.commit_id(),
+                    ));
+                } else {
+                    patch_edits.push(delete(name.clone()));
+                }
+                if let Some(prev_commit_id) = prev_commit_id {
+                    patch_restores.push(update(
+                        name,
+                        &rollback_msg,
+                        git_repository::refs::transaction::PreviousValue::Any,
+                        prev_commit_id,
+                    ));
+                } else if maybe_patch.is_some() {
+                    patch_restores.push(delete(name));
+                }
+            }
+            for dependent in &dependent_branches {
+                patch_edits.push(update(
+                    dependent.refname.clone(),
+                    state_reflog_msg,
+                    git_repository::refs::transaction::PreviousValue::ExistingMustMatch(
+                        git_repository::refs::Target::Peeled(dependent.old_id),
+                    ),
+                    dependent.new_id,
+                ));
+                patch_restores.push(update(
+                    dependent.refname.clone(),
+                    &rollback_msg,
+                    git_repository::refs::transaction::PreviousValue::Any,
+                    dependent.old_id,
+                ));
+            }
+
+            let stack_edit = update(
+                git_repository::refs::FullName::try_from(stack.get_stack_refname())
                     .expect("stack reference name is valid"),
-                deref: false,
-            });
+                state_reflog_msg,
+                if let Some(prev_state_commit) = stack.state_mut().prev.as_ref() {
+                    git_repository::refs::transaction::PreviousValue::ExistingMustMatch(
+                        git_repository::refs::Target::Peeled(prev_state_commit.id),
+                    )
+                } else {
+                    git_repository::refs::transaction::PreviousValue::MustNotExist
+                },
+                state_commit_id,
+            );
 
-            if options.set_head {
-                ref_edits.push(git_repository::refs::transaction::RefEdit {
-                    change: git_repository::refs::transaction::Change::Update {
-                        log,
-                        expected: git_repository::refs::transaction::PreviousValue::Any,
-                        new: git_repository::refs::Target::Peeled(trans_head.id),
-                    },
-                    name: branch_ref_name.clone(),
-                    deref: false,
-                })
+            failpoint("ref-update")?;
+            repo.edit_references(branch_edits)?;
+            let mut restores = branch_restores;
+            let result = (|| -> Result<()> {
+                failpoint("branch-ref-updated")?;
+                repo.edit_references(patch_edits)?;
+                restores.extend(patch_restores);
+                failpoint("stack-ref-update")?;
+                repo.edit_references([stack_edit])?;
+                Ok(())
+            })();
+            if let Err(err) = result {
+                repo.edit_references(restores)
+                    .context("restoring references after failed update")?;
+                return Err(err);
             }
 
-            repo.edit_references(ref_edits)?;
-
             if options.set_head {
                 stack.update_head(
                     Branch::wrap(repo.find_reference(&branch_ref_name)?),
@@ -297,6 +451,12 @@ impl<'repo> ExecuteContext<'repo> {
         })
         .map_err(|e| rollback(trans_head_tree_id, e))?;
 
+        record_hidden_times(&stack, &prev_hidden)?;
+
+        for dependent in &dependent_branches {
+            ui.print_dependent_branch(dependent.refname.shorten(), &dependent.patchname)?;
+        }
+
         if let Some(err) = error {
             Err(err)
         } else {
@@ -306,11 +466,55 @@ impl<'repo> ExecuteContext<'repo> {
                 }
             }
 
+            if repo
+                .config_snapshot()
+                .boolean("stgit.adviceUndo")
+                .unwrap_or(false)
+            {
+                // Undoing an undo or redo is not done with another undo, so the stack
+                // state to reset to is suggested instead.
+                let undo_cmd = match (
+                    reflog_msg.split_whitespace().next(),
+                    stack.state_mut().prev.as_ref(),
+                ) {
+                    (Some("undo" | "redo"), Some(prev_state_commit)) => {
+                        format!("stg reset {}", prev_state_commit.id.to_hex_with_len(7))
+                    }
+                    _ => "stg undo".to_string(),
+                };
+                ui.print_undo_hint(reflog_msg, &stats.describe(files_changed), &undo_cmd)?;
+            }
+
             Ok(stack)
         }
     }
 }
 
+/// Names of the points in [`ExecuteContext::execute()`] where failures may be injected.
+///
+/// When the `STGIT_FAILPOINT` environment variable names one of these points, executing
+/// a transaction fails upon reaching that point. The points before the reference
+/// updates rely on the worktree checkout being rolled back, while the points after the
+/// branch reference is updated, but before the stack reference is updated, also rely on
+/// the already updated references being restored. This is exercised by
+/// `stg selftest transactions`.
+pub(crate) const FAILPOINTS: [&str; 5] = [
+    "checkout",
+    "state-commit",
+    "ref-update",
+    "branch-ref-updated",
+    "stack-ref-update",
+];
+
+/// Fail if a failure is to be injected at the named point.
+fn failpoint(name: &str) -> Result<()> {
+    if std::env::var_os("STGIT_FAILPOINT").map_or(false, |point| point == name) {
+        Err(anyhow!("injected failure at `{name}`"))
+    } else {
+        Ok(())
+    }
+}
+
 fn checkout(
     repo: &git_repository::Repository,
     options: &TransactionOptions,
@@ -343,6 +547,251 @@ fn checkout(
     Ok(())
 }
 
+impl TransactionStats {
+    /// Describe the nonzero counts, e.g. "2 pushed, 3 files changed".
+    fn describe(&self, files_changed: usize) -> String {
+        let plural = |n: usize| if n == 1 { "" } else { "s" };
+        let mut parts = Vec::new();
+        if self.pushed > 0 {
+            parts.push(format!("{} pushed", self.pushed));
+        }
+        if self.popped > 0 {
+            parts.push(format!("{} popped", self.popped));
+        }
+        if self.renamed > 0 {
+            parts.push(format!("{} renamed", self.renamed));
+        }
+        if files_changed > 0 {
+            parts.push(format!(
+                "{files_changed} file{} changed",
+                plural(files_changed)
+            ));
+        }
+        if self.conflicts > 0 {
+            parts.push(format!(
+                "{} conflict{}",
+                self.conflicts,
+                plural(self.conflicts)
+            ));
+        }
+        parts.join(", ")
+    }
+}
+
+/// Make the stack state commit message for a transaction.
+///
+/// The nonzero operation counts from `stats` and `files_changed` are appended to the
+/// `subject` as trailers, e.g.:
+///
+/// ```text
+/// push
+///
+/// Pushed: 2
+/// Files-Changed: 3
+/// ```
+fn make_state_commit_msg(subject: &str, stats: &TransactionStats, files_changed: usize) -> String {
+    let mut message = subject.to_string();
+    let mut has_trailers = false;
+    for (key, count) in [
+        ("Pushed", stats.pushed),
+        ("Popped", stats.popped),
+        ("Renamed", stats.renamed),
+        ("Files-Changed", files_changed),
+        ("Conflicts", stats.conflicts),
+    ] {
+        if count > 0 {
+            if !has_trailers {
+                message.push_str("\n\n");
+                has_trailers = true;
+            }
+            message.push_str(&format!("{key}: {count}\n"));
+        }
+    }
+    message
+}
+
+/// Make the reflog message for a branch update from the `stgit.reflog-template` config.
+///
+/// The template's placeholders are expanded as follows:
+///
+/// - `%o`: the operation, i.e. the first word of the StGit log message.
+/// - `%m`: the full StGit log message.
+/// - `%p`: the space-separated names of the patches changed by the operation.
+/// - `%c`: `(conflict)` if the operation resulted in conflicts, otherwise empty.
+/// - `%%`: a literal `%`.
+///
+/// Runs of whitespace in the expanded message are collapsed to a single space.
+fn make_branch_reflog_msg(
+    config: &git_repository::config::Snapshot,
+    reflog_msg: &str,
+    patchnames: &[&PatchName],
+    has_conflicts: bool,
+) -> Result<String> {
+    let template = config
+        .string("stgit.reflog-template")
+        .map(|template| template.to_str_lossy().to_string())
+        .unwrap_or_else(|| "stgit: %o %p %c".to_string());
+    let operation = reflog_msg
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches(':');
+    let mut message = String::with_capacity(template.len() + reflog_msg.len());
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c == '%' {
+            match chars.next() {
+                Some('o') => message.push_str(operation),
+                Some('m') => message.push_str(reflog_msg),
+                Some('p') => {
+                    for (i, patchname) in patchnames.iter().enumerate() {
+                        if i > 0 {
+                            message.push(' ');
+                        }
+                        message.push_str(patchname.as_ref());
+                    }
+                }
+                Some('c') => {
+                    if has_conflicts {
+                        message.push_str("(conflict)");
+                    }
+                }
+                Some('%') => message.push('%'),
+                Some(c) => {
+                    return Err(anyhow!(
+                        "unknown placeholder `%{c}` in `stgit.reflog-template`"
+                    ))
+                }
+                None => return Err(anyhow!("incomplete placeholder in `stgit.reflog-template`")),
+            }
+        } else {
+            message.push(c);
+        }
+    }
+    Ok(message.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+/// A local branch that points at a patch commit rewritten by a transaction.
+struct DependentBranch {
+    refname: git_repository::refs::FullName,
+    patchname: PatchName,
+    old_id: git_repository::ObjectId,
+    new_id: git_repository::ObjectId,
+}
+
+/// Find local branches that point at the original commits of patches updated by a
+/// transaction.
+///
+/// Branches with their own StGit stacks, including the transaction's own branch, are
+/// not dependent branches since moving them would invalidate their stack state.
+fn find_dependent_branches(
+    stack: &Stack,
+    updated_patches: &BTreeMap<PatchName, Option<PatchState>>,
+) -> Result<Vec<DependentBranch>> {
+    let mut rewritten: BTreeMap<git_repository::ObjectId, (&PatchName, git_repository::ObjectId)> =
+        BTreeMap::new();
+    for (patchname, maybe_patch) in updated_patches {
+        if let Some(patch) = maybe_patch {
+            if stack.has_patch(patchname) {
+                let old_id = stack.get_patch(patchname).commit_id();
+                if old_id != patch.commit_id() {
+                    rewritten.insert(old_id, (patchname, patch.commit_id()));
+                }
+            }
+        }
+    }
+
+    let mut dependent_branches = Vec::new();
+    if rewritten.is_empty() {
+        return Ok(dependent_branches);
+    }
+    for reference in stack
+        .repo
+        .references()?
+        .local_branches()?
+        .filter_map(Result::ok)
+    {
+        if let Some(old_id) = reference.target().try_id() {
+            if let Some((patchname, new_id)) = rewritten.get(old_id) {
+                let branchname = reference.name().shorten().to_str_lossy();
+                let stack_refname = state_refname_from_branch_name(stack.repo, &branchname)?;
+                if stack
+                    .repo
+                    .try_find_reference(stack_refname.as_str())?
+                    .is_some()
+                {
+                    continue;
+                }
+                dependent_branches.push(DependentBranch {
+                    refname: reference.name().to_owned(),
+                    patchname: (*patchname).clone(),
+                    old_id: old_id.to_owned(),
+                    new_id: *new_id,
+                });
+            }
+        }
+    }
+    Ok(dependent_branches)
+}
+
+/// Record when patches were hidden in their metadata.
+///
+/// Patches hidden by the transaction are stamped with the current time and patches that
+/// are no longer hidden have their stamp removed. A patch counts as previously hidden if
+/// either its name or its commit was hidden, such that renaming or rewriting a hidden
+/// patch keeps its stamp. Since this follows the stack state, undoing or redoing a
+/// transaction that hides or unhides patches also updates their stamps.
+fn record_hidden_times(
+    stack: &Stack,
+    prev_hidden: &[(PatchName, git_repository::ObjectId)],
+) -> Result<()> {
+    use crate::patch::meta::{self, PatchMeta};
+
+    let repo = stack.repo;
+    let was_hidden = |patchname: &PatchName, commit_id: git_repository::ObjectId| {
+        prev_hidden
+            .iter()
+            .any(|(pn, id)| pn == patchname || *id == commit_id)
+    };
+    let mut hidden_at = None;
+    for patchname in stack.hidden() {
+        let commit_id = stack.get_patch_commit_id(patchname);
+        if !was_hidden(patchname, commit_id) {
+            let hidden_at =
+                hidden_at.get_or_insert_with(|| chrono::Utc::now().timestamp().to_string());
+            let mut patch_meta = PatchMeta::read(repo, commit_id)?;
+            patch_meta.set(meta::HIDDEN_AT, hidden_at);
+            patch_meta.write(repo, commit_id)?;
+        }
+    }
+    if prev_hidden.is_empty() {
+        return Ok(());
+    }
+    for patchname in stack.applied().iter().chain(stack.unapplied()) {
+        let commit_id = stack.get_patch_commit_id(patchname);
+        if was_hidden(patchname, commit_id) {
+            let mut patch_meta = PatchMeta::read(repo, commit_id)?;
+            if patch_meta.get(meta::HIDDEN_AT).is_some() {
+                patch_meta.remove(meta::HIDDEN_AT);
+                patch_meta.write(repo, commit_id)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Copy notes, including StGit patch metadata, from a patch's old commit to its new
+/// commit.
+fn copy_notes(
+    repo: &git_repository::Repository,
+    from_oid: git_repository::ObjectId,
+    to_oid: git_repository::ObjectId,
+) {
+    // Failure to copy is okay. The old commit may not have a note to copy.
+    repo.stupid().notes_copy(from_oid, to_oid).ok();
+    crate::patch::meta::copy(repo, from_oid, to_oid).ok();
+}
+
 impl<'repo> StackTransaction<'repo> {
     /// Get an immutable reference to the original stack.
     pub(crate) fn stack(&self) -> &Stack<'repo> {
@@ -368,7 +817,7 @@ impl<'repo> StackTransaction<'repo> {
             patches,
         } = state;
         self.updated_base = Some(if let Some(pn) = applied.first() {
-            Rc::new(patches[pn].commit.get_parent_commit()?)
+            Rc::new(patches[pn].commit()?.get_parent_commit()?)
         } else {
             head.clone()
         });
@@ -410,7 +859,7 @@ impl<'repo> StackTransaction<'repo> {
             .patches
             .iter()
             .filter_map(|(pn, patch_state)| {
-                if self.has_patch(pn) && self.get_patch_commit(pn).id() == patch_state.commit.id() {
+                if self.has_patch(pn) && self.get_patch_commit_id(pn) == patch_state.commit_id() {
                     Some(pn)
                 } else {
                     None
@@ -465,18 +914,11 @@ impl<'repo> StackTransaction<'repo> {
         commit_id: git_repository::ObjectId,
     ) -> Result<()> {
         let commit = self.stack.repo.find_commit(commit_id)?;
-        let old_commit = self.get_patch_commit(patchname);
-        // Failure to copy is okay. The old commit may not have a note to copy.
-        self.stack
-            .repo
-            .stupid()
-            .notes_copy(old_commit.id, commit_id)
-            .ok();
+        let old_commit = self.get_patch_commit(patchname)?;
+        copy_notes(self.stack.repo, old_commit.id, commit_id);
         self.updated_patches.insert(
             patchname.clone(),
-            Some(PatchState {
-                commit: Rc::new(commit),
-            }),
+            Some(PatchState::with_commit(self.stack.repo, Rc::new(commit))),
         );
         self.ui.print_updated(patchname, self.applied())?;
         Ok(())
@@ -491,14 +933,13 @@ impl<'repo> StackTransaction<'repo> {
         patchname: &PatchName,
         oid: git_repository::ObjectId,
     ) -> Result<()> {
+        self.check_patchname_available(patchname, None)?;
         let commit = self.stack.repo.find_commit(oid)?;
-        assert_eq!(commit.parent_ids().next().unwrap().detach(), self.top().id);
+        assert_eq!(commit.parent_ids().next().unwrap().detach(), self.top()?.id);
         self.applied.push(patchname.clone());
         self.updated_patches.insert(
             patchname.clone(),
-            Some(PatchState {
-                commit: Rc::new(commit),
-            }),
+            Some(PatchState::with_commit(self.stack.repo, Rc::new(commit))),
         );
         self.ui.print_pushed(patchname, PushStatus::New, true)?;
         Ok(())
@@ -513,15 +954,14 @@ impl<'repo> StackTransaction<'repo> {
         commit_id: git_repository::ObjectId,
         insert_pos: usize,
     ) -> Result<()> {
+        self.check_patchname_available(patchname, None)?;
         let commit = self.stack.repo.find_commit(commit_id)?;
         self.unapplied.insert(insert_pos, patchname.clone());
         self.updated_patches.insert(
             patchname.clone(),
-            Some(PatchState {
-                commit: Rc::new(commit),
-            }),
+            Some(PatchState::with_commit(self.stack.repo, Rc::new(commit))),
         );
-        self.ui.print_popped(&[patchname.clone()])?;
+        self.ui.print_popped(std::slice::from_ref(patchname))?;
         Ok(())
     }
 
@@ -544,25 +984,18 @@ impl<'repo> StackTransaction<'repo> {
     /// commit. For this operation, instead of applying the pushed patch's diff to the
     /// topmost patch's tree, the pushed patch's tree is preserved as-is.
     pub(crate) fn push_tree(&mut self, patchname: &PatchName, is_last: bool) -> Result<()> {
-        let patch_commit = self.get_patch_commit(patchname);
+        let patch_commit = self.get_patch_commit(patchname)?;
         let repo = self.stack.repo;
         let parent = patch_commit.get_parent_commit()?;
         let is_empty = parent.tree_id()? == patch_commit.tree_id()?;
 
-        let push_status = if patch_commit.parent_ids().next().unwrap() == self.top().id() {
+        let push_status = if patch_commit.parent_ids().next().unwrap() == self.top()?.id() {
             PushStatus::Unmodified
         } else {
             let author = patch_commit.author_strict()?;
-            let default_committer = repo.get_committer()?;
-            let committer = if self.options.committer_date_is_author_date {
-                let mut committer = default_committer.to_owned();
-                committer.time = author.time;
-                committer
-            } else {
-                default_committer.to_owned()
-            };
+            let committer = self.pushed_committer(patch_commit, &author)?;
             let message = patch_commit.message_ex();
-            let parent_ids = [self.top().id];
+            let parent_ids = [self.top()?.id];
             let new_commit_id = repo.commit_ex(
                 &author,
                 &committer,
@@ -572,14 +1005,10 @@ impl<'repo> StackTransaction<'repo> {
             )?;
 
             let commit = repo.find_commit(new_commit_id)?;
-            repo.stupid()
-                .notes_copy(patch_commit.id, new_commit_id)
-                .ok();
+            copy_notes(repo, patch_commit.id, new_commit_id);
             self.updated_patches.insert(
                 patchname.clone(),
-                Some(PatchState {
-                    commit: Rc::new(commit),
-                }),
+                Some(PatchState::with_commit(self.stack.repo, Rc::new(commit))),
             );
 
             PushStatus::Modified
@@ -600,6 +1029,7 @@ impl<'repo> StackTransaction<'repo> {
         }
 
         self.applied.push(patchname.clone());
+        self.stats.pushed += 1;
 
         self.ui.print_pushed(patchname, push_status, is_last)
     }
@@ -711,7 +1141,49 @@ impl<'repo> StackTransaction<'repo> {
 
         self.ui.print_committed(to_commit)?;
 
-        self.updated_base = Some(self.get_patch_commit(to_commit.last().unwrap()).clone());
+        self.updated_base = Some(self.get_patch_commit(to_commit.last().unwrap())?.clone());
+        for patchname in to_commit {
+            self.updated_patches.insert(patchname.clone(), None);
+        }
+        self.applied = self.applied.split_off(to_commit.len());
+        self.push_patches(&to_push, false)
+    }
+
+    // Finalize patches into a single regular Git commit.
+    //
+    // Like `commit_patches()`, except that the committed patches are replaced by the
+    // commit returned by `make_base`, which is called with the topmost committed patch's
+    // commit once the patches are at the bottom of the stack. The returned commit becomes
+    // the new stack base and the remaining applied patches are pushed onto it.
+    pub(crate) fn commit_patches_squashed<F>(
+        &mut self,
+        to_commit: &[PatchName],
+        make_base: F,
+    ) -> Result<()>
+    where
+        F: FnOnce(&git_repository::Commit<'repo>) -> Result<git_repository::ObjectId>,
+    {
+        let num_common = self
+            .applied()
+            .iter()
+            .zip(to_commit.iter())
+            .take_while(|(pn0, pn1)| pn0 == pn1)
+            .count();
+
+        let to_push: Vec<PatchName> = self.applied()[num_common..]
+            .iter()
+            .filter(|pn| !to_commit.contains(*pn))
+            .cloned()
+            .collect();
+        self.pop_patches(|pn| to_push.contains(pn))?;
+        if num_common < to_commit.len() {
+            self.push_patches(&to_commit[num_common..], false)?;
+        }
+
+        self.ui.print_committed(to_commit)?;
+
+        let base_id = make_base(self.get_patch_commit(to_commit.last().unwrap())?)?;
+        self.updated_base = Some(Rc::new(self.stack.repo.find_commit(base_id)?));
         for patchname in to_commit {
             self.updated_patches.insert(patchname.clone(), None);
         }
@@ -732,9 +1204,7 @@ impl<'repo> StackTransaction<'repo> {
             let commit = self.stack.repo.find_commit(commit_id)?;
             self.updated_patches.insert(
                 patchname.clone(),
-                Some(PatchState {
-                    commit: Rc::new(commit),
-                }),
+                Some(PatchState::with_commit(self.stack.repo, Rc::new(commit))),
             );
             new_applied.push(patchname.clone());
         }
@@ -801,13 +1271,17 @@ impl<'repo> StackTransaction<'repo> {
     ) -> Result<()> {
         if new_patchname == old_patchname {
             return Ok(());
-        } else if let Some(colliding_patchname) = self.stack.collides(new_patchname) {
+        } else if let Some(colliding_patchname) = self
+            .stack
+            .all_patches()
+            .find(|pn| *pn != old_patchname && new_patchname.collides(pn))
+        {
             if self
                 .updated_patches
                 .get(colliding_patchname)
                 .map_or(true, Option::is_some)
             {
-                return Err(anyhow!("patch `{colliding_patchname}` already exists"));
+                self.check_patchname_available(new_patchname, Some(old_patchname))?;
             }
         } else if !self.stack.has_patch(old_patchname) {
             return Err(anyhow!("patch `{old_patchname}` does not exist"));
@@ -827,6 +1301,7 @@ impl<'repo> StackTransaction<'repo> {
         self.updated_patches.insert(old_patchname.clone(), None);
         self.updated_patches
             .insert(new_patchname.clone(), Some(patch));
+        self.stats.renamed += 1;
 
         self.ui.print_rename(old_patchname, new_patchname)
     }
@@ -854,6 +1329,7 @@ impl<'repo> StackTransaction<'repo> {
         let unapplied_size = incidental.len() + self.unapplied.len();
         let unapplied = std::mem::replace(&mut self.unapplied, Vec::with_capacity(unapplied_size));
         self.unapplied.append(&mut incidental.clone());
+        self.stats.popped += incidental.len();
 
         self.ui.print_popped(&all_popped)?;
 
@@ -934,6 +1410,7 @@ impl<'repo> StackTransaction<'repo> {
         self.unapplied.append(&mut incidental.clone());
         self.unapplied.append(&mut requested);
         self.unapplied.append(&mut unapplied);
+        self.stats.popped += all_popped.len();
 
         self.ui.print_popped(&all_popped)?;
 
@@ -948,6 +1425,12 @@ impl<'repo> StackTransaction<'repo> {
     /// is *not* rolled back. Instead, the conflicts will be left in the working tree
     /// and index for the user to resolve.
     ///
+    /// This default behavior may be changed with the "stgit.push.onConflict" config
+    /// variable. With `undo`, a conflicting push aborts the transaction such that all
+    /// of its changes are rolled back. With `leave-unapplied`, a conflicting patch is
+    /// left unapplied and the remaining patches are pushed; the patches left
+    /// unapplied are reported once all patches are pushed.
+    ///
     /// The `check_merged` option, when true, performs an extra check to determine
     /// whether the patches' changes have already been merged into the stack's base
     /// tree. Patches that are determined to have already been merged will still be
@@ -957,8 +1440,10 @@ impl<'repo> StackTransaction<'repo> {
         P: AsRef<PatchName>,
     {
         let stupid = self.stack.repo.stupid();
+        let conflict_policy = PushConflictPolicy::from_config(&self.stack.repo.config_snapshot())?;
         stupid.with_temp_index(|stupid_temp| {
             let mut temp_index_tree_id: Option<git_repository::ObjectId> = None;
+            let mut left_unapplied: Vec<PatchName> = Vec::new();
 
             let merged = if check_merged {
                 Some(self.check_merged(patchnames, stupid_temp, &mut temp_index_tree_id)?)
@@ -972,34 +1457,111 @@ impl<'repo> StackTransaction<'repo> {
                 let already_merged = merged
                     .as_ref()
                     .map_or(false, |merged| merged.contains(&patchname));
-                self.push_patch(
+                let pushed = self.push_patch(
                     patchname,
                     already_merged,
                     is_last,
+                    conflict_policy,
+                    stupid_temp,
+                    &mut temp_index_tree_id,
+                )?;
+                if !pushed {
+                    left_unapplied.push(patchname.clone());
+                }
+            }
+
+            if !left_unapplied.is_empty() {
+                self.ui.print_left_unapplied(&left_unapplied)?;
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Push patches in order until a patch that would conflict is encountered.
+    ///
+    /// Unlike [`StackTransaction::push_patches()`], a conflicting patch does not
+    /// halt the transaction with conflicts in the worktree. Instead, the conflicting
+    /// patch and all following patches are left unapplied and the patches pushed so
+    /// far remain applied. The "stgit.push.onConflict" config variable is not
+    /// consulted.
+    pub(crate) fn push_patches_until_conflict<P>(&mut self, patchnames: &[P]) -> Result<()>
+    where
+        P: AsRef<PatchName>,
+    {
+        let stupid = self.stack.repo.stupid();
+        stupid.with_temp_index(|stupid_temp| {
+            let mut temp_index_tree_id: Option<git_repository::ObjectId> = None;
+
+            for (i, patchname) in patchnames.iter().enumerate() {
+                let patchname = patchname.as_ref();
+                let is_last = i + 1 == patchnames.len();
+                let pushed = self.push_patch(
+                    patchname,
+                    false,
+                    is_last,
+                    PushConflictPolicy::LeaveUnapplied,
                     stupid_temp,
                     &mut temp_index_tree_id,
                 )?;
+                if !pushed {
+                    self.ui
+                        .print_stopped_at_conflict(patchname, patchnames.len() - i - 1)?;
+                    break;
+                }
             }
 
             Ok(())
         })
     }
 
+    /// Get the committer for a patch commit that is rewritten by pushing the patch.
+    ///
+    /// With stable ids, the existing commit's committer is kept as-is. Otherwise the
+    /// current committer is used, with the author date if committer date is author
+    /// date, or with the existing commit's committer date if
+    /// `stgit.push.preserve-committer-date` is set.
+    fn pushed_committer(
+        &self,
+        patch_commit: &git_repository::Commit<'_>,
+        author: &git_repository::actor::Signature,
+    ) -> Result<git_repository::actor::Signature> {
+        if self.options.stable_ids {
+            return patch_commit.committer_strict();
+        }
+        let repo = self.stack.repo;
+        let mut committer = repo.get_committer()?.to_owned();
+        if self.options.committer_date_is_author_date {
+            committer.time = author.time;
+        } else if repo
+            .config_snapshot()
+            .boolean("stgit.push.preserve-committer-date")
+            .unwrap_or(false)
+        {
+            committer.time = patch_commit.committer_strict()?.time;
+        }
+        Ok(committer)
+    }
+
+    /// Push a single patch.
+    ///
+    /// Returns `false` if the patch was left unapplied due to conflicts per the
+    /// push conflict policy.
     fn push_patch(
         &mut self,
         patchname: &PatchName,
         already_merged: bool,
         is_last: bool,
+        conflict_policy: PushConflictPolicy,
         stupid_temp: &StupidContext,
         temp_index_tree_id: &mut Option<git_repository::ObjectId>,
-    ) -> Result<()> {
+    ) -> Result<bool> {
         let repo = self.stack.repo;
         let config = repo.config_snapshot();
         let stupid = repo.stupid();
-        let default_committer = repo.get_committer()?;
-        let patch_commit = self.get_patch_commit(patchname).clone();
+        let patch_commit = self.get_patch_commit(patchname)?.clone();
         let old_parent = patch_commit.get_parent_commit()?;
-        let new_parent = self.top().clone();
+        let new_parent = self.top()?.clone();
         let patch_commit_ref = patch_commit.decode()?;
         let old_parent_ref = old_parent.decode()?;
         let new_parent_ref = new_parent.decode()?;
@@ -1042,6 +1604,19 @@ impl<'repo> StackTransaction<'repo> {
                     conflicts: false,
                 }
                 .into());
+            } else if conflict_policy == PushConflictPolicy::Undo {
+                self.ui.print_rolled_back(self.stack.applied().last())?;
+                return Err(anyhow!(
+                    "pushing patch `{patchname}` would result in conflicts;\n\
+                     command aborted (all changes rolled back)"
+                ));
+            } else if conflict_policy == PushConflictPolicy::LeaveUnapplied {
+                // The temp index may be left with unmerged entries.
+                *temp_index_tree_id = None;
+                self.stats.conflicts += 1;
+                self.ui
+                    .print_pushed(patchname, PushStatus::LeftUnapplied, false)?;
+                return Ok(false);
             } else if !self
                 .options
                 .allow_push_conflicts
@@ -1097,13 +1672,7 @@ impl<'repo> StackTransaction<'repo> {
 
         if new_tree_id != patch_commit_ref.tree() || new_parent.id != old_parent.id {
             let author = patch_commit.author_strict()?;
-            let committer = if self.options.committer_date_is_author_date {
-                let mut committer = default_committer.to_owned();
-                committer.time = author.time;
-                committer
-            } else {
-                default_committer.to_owned()
-            };
+            let committer = self.pushed_committer(&patch_commit, &author)?;
             let commit_id = repo.commit_ex(
                 &author,
                 &committer,
@@ -1112,7 +1681,7 @@ impl<'repo> StackTransaction<'repo> {
                 [new_parent.id],
             )?;
             let commit = Rc::new(repo.find_commit(commit_id)?);
-            stupid.notes_copy(patch_commit.id, commit_id).ok();
+            copy_notes(repo, patch_commit.id, commit_id);
             if push_status == PushStatus::Conflict {
                 // In the case of a conflict, update() will be called after the
                 // execute() performs the checkout. Setting the transaction head
@@ -1124,8 +1693,10 @@ impl<'repo> StackTransaction<'repo> {
                 push_status = PushStatus::Empty;
             }
 
-            self.updated_patches
-                .insert(patchname.clone(), Some(PatchState { commit }));
+            self.updated_patches.insert(
+                patchname.clone(),
+                Some(PatchState::with_commit(repo, commit)),
+            );
         }
 
         if push_status == PushStatus::Conflict {
@@ -1133,16 +1704,28 @@ impl<'repo> StackTransaction<'repo> {
             self.options.conflict_mode = ConflictMode::Allow;
         }
 
+        let conflict_blame = if push_status == PushStatus::Conflict {
+            Some(self.blame_conflicts()?)
+        } else {
+            None
+        };
+
         if let Some(pos) = self.unapplied.iter().position(|pn| pn == patchname) {
             self.unapplied.remove(pos);
         } else if let Some(pos) = self.hidden.iter().position(|pn| pn == patchname) {
             self.hidden.remove(pos);
         }
         self.applied.push(patchname.clone());
+        self.stats.pushed += 1;
+        if push_status == PushStatus::Conflict {
+            self.stats.conflicts += 1;
+        }
 
         self.ui.print_pushed(patchname, push_status, is_last)?;
 
-        if push_status == PushStatus::Conflict {
+        if let Some(conflict_blame) = conflict_blame {
+            self.ui
+                .print_conflict_blame(patchname, &conflict_blame, &self.applied)?;
             Err(Error::TransactionHalt {
                 msg: "merge conflicts; \
                       resolve conflicts manually then refresh or \
@@ -1152,7 +1735,7 @@ impl<'repo> StackTransaction<'repo> {
             }
             .into())
         } else {
-            Ok(())
+            Ok(true)
         }
     }
 
@@ -1180,7 +1763,7 @@ impl<'repo> StackTransaction<'repo> {
 
         for patchname in patchnames.iter().rev() {
             let patchname = patchname.as_ref();
-            let patch_commit = self.get_patch_commit(patchname);
+            let patch_commit = self.get_patch_commit(patchname)?;
 
             if patch_commit.is_no_change()? {
                 continue; // No change
@@ -1202,6 +1785,41 @@ impl<'repo> StackTransaction<'repo> {
 
         Ok(merged)
     }
+
+    /// Find the applied patches that change each conflicting file.
+    ///
+    /// The conflicting files are those left unmerged in the index. For each such
+    /// file, the applied patches that touch it are returned in stack order.
+    fn blame_conflicts(&self) -> Result<Vec<(String, Vec<PatchName>)>> {
+        let stupid = self.stack.repo.stupid();
+        let mut conflict_blame: Vec<(String, Vec<PatchName>)> = stupid
+            .statuses(None)?
+            .iter()
+            .filter(|entry| matches!(entry.kind(), StatusEntryKind::Unmerged))
+            .map(|entry| (entry.path_bytes().to_str_lossy().to_string(), Vec::new()))
+            .collect();
+
+        if !conflict_blame.is_empty() {
+            for patchname in &self.applied {
+                let patch_commit = self.get_patch_commit(patchname)?;
+                let parent_commit = patch_commit.get_parent_commit()?;
+                let diff_files = stupid.diff_tree_files(
+                    parent_commit.tree_id()?.detach(),
+                    patch_commit.tree_id()?.detach(),
+                )?;
+                for path in diff_files.iter() {
+                    let path = path.to_string_lossy();
+                    if let Some((_, patchnames)) =
+                        conflict_blame.iter_mut().find(|(file, _)| file == &path)
+                    {
+                        patchnames.push(patchname.clone());
+                    }
+                }
+            }
+        }
+
+        Ok(conflict_blame)
+    }
 }
 
 impl<'repo> StackAccess<'repo> for StackTransaction<'repo> {
@@ -1261,17 +1879,17 @@ impl<'repo> StackStateAccess<'repo> for StackTransaction<'repo> {
         }
     }
 
-    fn top(&self) -> &Rc<git_repository::Commit<'repo>> {
+    fn top(&self) -> Result<&Rc<git_repository::Commit<'repo>>> {
         if let Some(patchname) = self.applied.last() {
             self.get_patch_commit(patchname)
         } else {
-            self.base()
+            Ok(self.base())
         }
     }
 
-    fn head(&self) -> &Rc<git_repository::Commit<'repo>> {
+    fn head(&self) -> Result<&Rc<git_repository::Commit<'repo>>> {
         if let Some(commit) = self.updated_head.as_ref() {
-            commit
+            Ok(commit)
         } else {
             self.top()
         }
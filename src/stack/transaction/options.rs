@@ -1,5 +1,8 @@
 // SPDX-License-Identifier: GPL-2.0-only
 
+use anyhow::{anyhow, Result};
+use bstr::ByteSlice;
+
 /// Options for fine-tuning stack transaction behaviors.
 pub(super) struct TransactionOptions {
     pub(super) conflict_mode: ConflictMode,
@@ -9,6 +12,7 @@ pub(super) struct TransactionOptions {
     pub(super) set_head: bool,
     pub(super) allow_bad_head: bool,
     pub(super) committer_date_is_author_date: bool,
+    pub(super) stable_ids: bool,
 }
 
 impl Default for TransactionOptions {
@@ -21,16 +25,19 @@ impl Default for TransactionOptions {
             set_head: true,
             allow_bad_head: false,
             committer_date_is_author_date: false,
+            stable_ids: false,
         }
     }
 }
 
 /// Policies for whether a transaction may execute when conflicts emerge from the
 /// transactions operations.
+#[derive(Default)]
 pub(crate) enum ConflictMode {
     /// Transaction execution will fail if there are conflicts recorded in the index.
     ///
     /// This is the default.
+    #[default]
     Disallow,
 
     /// Transaction execution will succeed even if there are outstanding conflicts.
@@ -41,8 +48,39 @@ pub(crate) enum ConflictMode {
     AllowIfSameTop,
 }
 
-impl Default for ConflictMode {
-    fn default() -> Self {
-        Self::Disallow
+/// Policies for what a transaction does when pushing a patch would conflict.
+///
+/// The policy is configured with "stgit.push.onConflict".
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub(super) enum PushConflictPolicy {
+    /// Stop the transaction, leaving the conflicts in the index and worktree.
+    ///
+    /// This is the default.
+    #[default]
+    Stop,
+
+    /// Abort and roll back the whole transaction.
+    Undo,
+
+    /// Leave the conflicting patch unapplied and continue pushing other patches.
+    LeaveUnapplied,
+}
+
+impl PushConflictPolicy {
+    /// Get the push conflict policy from the "stgit.push.onConflict" config variable.
+    pub(super) fn from_config(config: &git_repository::config::Snapshot) -> Result<Self> {
+        if let Some(value) = config.string("stgit.push.onConflict") {
+            match value.to_str().ok() {
+                Some("stop") => Ok(Self::Stop),
+                Some("undo") => Ok(Self::Undo),
+                Some("leave-unapplied") => Ok(Self::LeaveUnapplied),
+                _ => Err(anyhow!(
+                    "invalid `stgit.push.onConflict` value `{value}`; \
+                     expected `stop`, `undo`, or `leave-unapplied`"
+                )),
+            }
+        } else {
+            Ok(Self::default())
+        }
     }
 }
@@ -38,6 +38,56 @@ impl TransactionUserInterface {
         Ok(())
     }
 
+    pub(super) fn print_dependent_branch(
+        &self,
+        branchname: &bstr::BStr,
+        patchname: &PatchName,
+    ) -> Result<()> {
+        let mut output = self.output.borrow_mut();
+        write!(output, "Updated branch ")?;
+        let mut color_spec = termcolor::ColorSpec::new();
+        output.set_color(color_spec.set_fg(Some(termcolor::Color::Cyan)))?;
+        write!(output, "{branchname}")?;
+        output.reset()?;
+        writeln!(output, " to patch {patchname}")?;
+        Ok(())
+    }
+
+    pub(super) fn print_left_unapplied(&self, patchnames: &[PatchName]) -> Result<()> {
+        let mut output = self.output.borrow_mut();
+        write!(output, "Left ")?;
+        let mut color_spec = termcolor::ColorSpec::new();
+        output.set_color(color_spec.set_fg(Some(termcolor::Color::Red)))?;
+        write!(output, "{}", patchnames.len())?;
+        output.reset()?;
+        let plural = if patchnames.len() == 1 { "" } else { "es" };
+        write!(output, " conflicting patch{plural} unapplied:")?;
+        for patchname in patchnames {
+            write!(output, " {patchname}")?;
+        }
+        writeln!(output)?;
+        Ok(())
+    }
+
+    pub(super) fn print_stopped_at_conflict(
+        &self,
+        patchname: &PatchName,
+        num_remaining: usize,
+    ) -> Result<()> {
+        let mut output = self.output.borrow_mut();
+        write!(output, "Stopped at conflicting patch ")?;
+        let mut color_spec = termcolor::ColorSpec::new();
+        output.set_color(color_spec.set_fg(Some(termcolor::Color::Red)))?;
+        write!(output, "{patchname}")?;
+        output.reset()?;
+        if num_remaining > 0 {
+            let plural = if num_remaining == 1 { "" } else { "es" };
+            write!(output, "; {num_remaining} more patch{plural} not pushed")?;
+        }
+        writeln!(output)?;
+        Ok(())
+    }
+
     pub(super) fn print_rename(
         &self,
         old_patchname: &PatchName,
@@ -156,17 +206,23 @@ impl TransactionUserInterface {
         is_last: bool,
     ) -> Result<()> {
         let mut output = self.output.borrow_mut();
-        let sigil = if is_last { '>' } else { '+' };
+        let sigil = if let PushStatus::LeftUnapplied = status {
+            '-'
+        } else if is_last {
+            '>'
+        } else {
+            '+'
+        };
         let mut color_spec = termcolor::ColorSpec::new();
-        output.set_color(
-            color_spec.set_fg(Some(if let PushStatus::Conflict = status {
+        output.set_color(color_spec.set_fg(Some(
+            if let PushStatus::Conflict | PushStatus::LeftUnapplied = status {
                 termcolor::Color::Red
             } else if is_last {
                 termcolor::Color::Blue
             } else {
                 termcolor::Color::Green
-            })),
-        )?;
+            },
+        )))?;
         write!(output, "{sigil} ")?;
         color_spec.clear();
         output.set_color(color_spec.set_bold(is_last).set_intense(!is_last))?;
@@ -177,6 +233,7 @@ impl TransactionUserInterface {
             PushStatus::New => " (new)",
             PushStatus::AlreadyMerged => " (merged)",
             PushStatus::Conflict => " (conflict)",
+            PushStatus::LeftUnapplied => " (conflict, left unapplied)",
             PushStatus::Empty => " (empty)",
             PushStatus::Modified => " (modified)",
             PushStatus::Unmodified => "",
@@ -189,6 +246,85 @@ impl TransactionUserInterface {
         Ok(())
     }
 
+    /// Print the files in conflict along with the applied patches that change them.
+    ///
+    /// A hint is printed suggesting how the conflicting patch may be reordered with
+    /// or squashed into the applied patch that changes the most conflicting files.
+    pub(super) fn print_conflict_blame(
+        &self,
+        patchname: &PatchName,
+        conflict_blame: &[(String, Vec<PatchName>)],
+        applied: &[PatchName],
+    ) -> Result<()> {
+        if conflict_blame.is_empty() {
+            return Ok(());
+        }
+        let mut output = self.output.borrow_mut();
+        let mut color_spec = termcolor::ColorSpec::new();
+        let plural = if conflict_blame.len() == 1 { "" } else { "s" };
+        writeln!(output, "{} conflicting file{plural}:", conflict_blame.len())?;
+        for (path, patchnames) in conflict_blame {
+            write!(output, "  ")?;
+            output.set_color(color_spec.set_fg(Some(termcolor::Color::Red)))?;
+            write!(output, "{path}")?;
+            output.reset()?;
+            if !patchnames.is_empty() {
+                output.set_color(color_spec.set_fg(None).set_dimmed(true))?;
+                write!(output, " (changed by ")?;
+                for (i, blamed) in patchnames.iter().enumerate() {
+                    if i > 0 {
+                        write!(output, ", ")?;
+                    }
+                    write!(output, "{blamed}")?;
+                }
+                write!(output, ")")?;
+                output.reset()?;
+                color_spec.clear();
+            }
+            writeln!(output)?;
+        }
+
+        // Prefer the patch changing the most conflicting files, and among those, the
+        // one closest to the top of the stack.
+        if let Some(culprit) = applied
+            .iter()
+            .map(|pn| {
+                let count = conflict_blame
+                    .iter()
+                    .filter(|(_, patchnames)| patchnames.contains(pn))
+                    .count();
+                (pn, count)
+            })
+            .filter(|(_, count)| *count > 0)
+            .max_by_key(|(_, count)| *count)
+            .map(|(pn, _)| pn)
+        {
+            writeln!(
+                output,
+                "hint: `{patchname}` conflicts with changes introduced by `{culprit}`; \
+                 after `stg undo --hard`, consider `stg sink {patchname} --to {culprit}` \
+                 or `stg squash {culprit} {patchname}`"
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Print a hint naming the operation, what it changed, and how to undo it.
+    pub(super) fn print_undo_hint(
+        &self,
+        operation: &str,
+        changes: &str,
+        undo_cmd: &str,
+    ) -> Result<()> {
+        let mut output = self.output.borrow_mut();
+        write!(output, "hint: {operation}")?;
+        if !changes.is_empty() {
+            write!(output, " ({changes})")?;
+        }
+        writeln!(output, "; undo with: {undo_cmd}")?;
+        Ok(())
+    }
+
     pub(super) fn print_top(&self, patchname: &PatchName) -> Result<()> {
         let mut output = self.output.borrow_mut();
         let mut color_spec = termcolor::ColorSpec::new();
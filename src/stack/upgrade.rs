@@ -15,7 +15,13 @@ use super::serde::{RawPatchState, RawStackState};
 use crate::{patch::PatchName, stack::state::StackState};
 
 /// Upgrade stack state metadata to most recent version.
-pub(crate) fn stack_upgrade(repo: &git_repository::Repository, branch_name: &str) -> Result<()> {
+///
+/// The upgraded stack state is recorded in the `stack_refname` reference.
+pub(crate) fn stack_upgrade(
+    repo: &git_repository::Repository,
+    branch_name: &str,
+    stack_refname: &str,
+) -> Result<()> {
     let refname_v4 = state_refname_from_branch_name_v4(branch_name);
 
     if let Ok(mut stack_ref_v4) = repo.find_reference(refname_v4.as_str()) {
@@ -125,14 +131,13 @@ pub(crate) fn stack_upgrade(repo: &git_repository::Repository, branch_name: &str
 
             let state = StackState::from_raw_state(repo, raw_stack_state)?;
             let new_state_commit_id = state.commit(repo, None, "stack upgrade to version 5")?;
-            let refname = format!("refs/stacks/{branch_name}");
             repo.reference(
-                refname.as_str(),
+                stack_refname,
                 new_state_commit_id,
                 git_repository::refs::transaction::PreviousValue::MustNotExist,
                 "stack upgrade to version 5",
             )
-            .with_context(|| format!("creating `{refname}`"))?;
+            .with_context(|| format!("creating `{stack_refname}`"))?;
 
             stack_ref_v4
                 .delete()
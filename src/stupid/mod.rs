@@ -131,6 +131,22 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         Ok(())
     }
 
+    /// Apply a zero-context patch (diff) to the index using `git apply --cached
+    /// --unidiff-zero`.
+    pub(crate) fn apply_unidiff_zero_to_index(&self, diff: &[u8]) -> Result<()> {
+        self.git_in_work_root()?
+            .args(["apply", "--cached", "--unidiff-zero"])
+            .stdout(Stdio::null())
+            .in_and_out(diff)?
+            .require_success("apply --unidiff-zero")?;
+        Ok(())
+    }
+
+    /// Apply a patch (diff) to the worktree and index using `git apply --index`.
+    ///
+    /// Each of `path_filters` is an `--include=<glob>` or `--exclude=<glob>` option
+    /// limiting the paths to which the patch is applied.
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn apply_to_worktree_and_index(
         &self,
         diff: &[u8],
@@ -139,6 +155,7 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         strip_level: Option<usize>,
         directory: Option<&Path>,
         context_lines: Option<usize>,
+        path_filters: &[String],
     ) -> Result<()> {
         let mut command = self.git_in_work_root()?;
         command.args(["apply", "--index"]);
@@ -158,6 +175,7 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         if let Some(context_lines) = context_lines {
             command.arg(format!("-C{context_lines}"));
         }
+        command.args(path_filters);
         command
             .stdout(Stdio::null())
             .in_and_out(diff)?
@@ -165,6 +183,72 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         Ok(())
     }
 
+    /// Apply a patch (diff) to the worktree, leaving rejected hunks in `.rej` files.
+    ///
+    /// Returns whether all hunks applied along with the paths, relative to the
+    /// repository root, touched by the patch. Since `git apply --reject` does not
+    /// update the index when any hunk is rejected, staging the touched paths is left
+    /// to the caller. The `path_filters` are as for
+    /// [`apply_to_worktree_and_index()`](Self::apply_to_worktree_and_index).
+    pub(crate) fn apply_to_worktree_with_reject(
+        &self,
+        diff: &[u8],
+        strip_level: Option<usize>,
+        directory: Option<&Path>,
+        context_lines: Option<usize>,
+        path_filters: &[String],
+    ) -> Result<(bool, Vec<OsString>)> {
+        let make_command = |extra_args: &[&str]| -> Result<Command> {
+            let mut command = self.git_in_work_root()?;
+            command.arg("apply").args(extra_args);
+            if let Some(strip_level) = strip_level {
+                command.arg(format!("-p{strip_level}"));
+            }
+            if let Some(directory) = directory {
+                command.arg("--directory");
+                command.arg(directory);
+            }
+            if let Some(context_lines) = context_lines {
+                command.arg(format!("-C{context_lines}"));
+            }
+            command.args(path_filters);
+            Ok(command)
+        };
+
+        let numstat_output = make_command(&["--numstat", "-z"])?
+            .stdout(Stdio::piped())
+            .in_and_out(diff)?
+            .require_success("apply --numstat")?;
+        // Each record is `<added>\t<deleted>\t<path>\0`, or for renames and copies,
+        // `<added>\t<deleted>\t\0<old-path>\0<new-path>\0`.
+        let mut paths = Vec::new();
+        let mut fields = numstat_output.stdout.split_str(b"\0");
+        while let Some(field) = fields.next() {
+            if field.is_empty() {
+                continue;
+            }
+            let path = field.splitn_str(3, b"\t").nth(2).unwrap_or_default();
+            if path.is_empty() {
+                for path in fields.by_ref().take(2) {
+                    paths.push(path.to_vec().into_os_string()?);
+                }
+            } else {
+                paths.push(path.to_vec().into_os_string()?);
+            }
+        }
+
+        let output = make_command(&["--reject"])?
+            .stdout(Stdio::null())
+            .in_and_out(diff)?;
+        if output.status.success() {
+            Ok((true, paths))
+        } else if output.status.code() == Some(1) && output.stderr.contains_str("Rejected hunk") {
+            Ok((false, paths))
+        } else {
+            Err(git_command_error("apply --reject", &output.stderr))
+        }
+    }
+
     /// Apply diff between two trees to specified index.
     ///
     /// Pipes `git diff-tree | git apply --cached`.
@@ -316,6 +400,122 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         }
     }
 
+    /// Create a bundle file containing the given references and revisions.
+    ///
+    /// Objects reachable from `exclude_revs` are omitted from the bundle and become the
+    /// bundle's prerequisites.
+    pub(crate) fn bundle_create<RevIter, RevArg>(
+        &self,
+        path: &Path,
+        refnames: &[&str],
+        exclude_revs: RevIter,
+    ) -> Result<()>
+    where
+        RevIter: IntoIterator<Item = RevArg>,
+        RevArg: AsRef<OsStr>,
+    {
+        self.git()
+            .args(["bundle", "create", "--quiet"])
+            .arg(path)
+            .args(refnames)
+            .arg("--not")
+            .args(exclude_revs)
+            .stdout(Stdio::null())
+            .output_git()?
+            .require_success("bundle create")?;
+        Ok(())
+    }
+
+    /// Create a bundle file containing references that do not exist in the repository.
+    ///
+    /// The references are created in a temporary bare repository that borrows the
+    /// repository's objects, such that the repository's own references are not
+    /// modified. Objects reachable from `exclude_revs` are omitted from the bundle.
+    pub(crate) fn bundle_create_with_refs<RevIter, RevArg>(
+        &self,
+        path: &Path,
+        refs: &[(&str, git_repository::ObjectId)],
+        exclude_revs: RevIter,
+    ) -> Result<()>
+    where
+        RevIter: IntoIterator<Item = RevArg>,
+        RevArg: AsRef<OsStr>,
+    {
+        let git_dir = self
+            .git_dir
+            .expect("git_dir is required for bundle creation");
+        let output = self
+            .git()
+            .args(["rev-parse", "--git-path", "objects"])
+            .output_git()?
+            .require_success("rev-parse --git-path")?;
+        let objects_dir = Path::new(output.stdout.trim().to_os_str()?).canonicalize()?;
+
+        let temp_dir = tempfile::Builder::new()
+            .prefix("bundle-temp-stg")
+            .tempdir_in(git_dir)?;
+        self.git()
+            .args(["init", "--bare", "--quiet"])
+            .arg(temp_dir.path())
+            .env_remove("GIT_DIR")
+            .env_remove("GIT_WORK_TREE")
+            .stdout(Stdio::null())
+            .output_git()?
+            .require_success("init")?;
+        let mut alternates = Vec::from_path_lossy(&objects_dir).into_owned();
+        alternates.push(b'\n');
+        std::fs::write(temp_dir.path().join("objects/info/alternates"), alternates)?;
+
+        let stupid_temp = StupidContext {
+            git_dir: Some(temp_dir.path()),
+            ..Default::default()
+        };
+        for (refname, oid) in refs {
+            stupid_temp
+                .git()
+                .args(["update-ref", refname, &oid.to_string()])
+                .output_git()?
+                .require_success("update-ref")?;
+        }
+        let refnames: Vec<&str> = refs.iter().map(|(refname, _)| *refname).collect();
+        stupid_temp.bundle_create(path, &refnames, exclude_revs)
+    }
+
+    /// Get the references recorded in a bundle file.
+    ///
+    /// The bundle is not verified and no objects are written to the repository.
+    pub(crate) fn bundle_list_heads(
+        &self,
+        path: &Path,
+    ) -> Result<Vec<(git_repository::ObjectId, String)>> {
+        let output = self
+            .git()
+            .args(["bundle", "list-heads"])
+            .arg(path)
+            .output_git()?
+            .require_success("bundle list-heads")?;
+        let mut heads = Vec::new();
+        for line in output.stdout.lines() {
+            if let Some((oid, refname)) = line.split_once_str(" ") {
+                heads.push((parse_oid(oid)?, refname.to_str_lossy().to_string()));
+            }
+        }
+        Ok(heads)
+    }
+
+    /// Verify a bundle file and write its objects to the repository.
+    ///
+    /// No references are updated.
+    pub(crate) fn bundle_unbundle(&self, path: &Path) -> Result<()> {
+        self.git()
+            .args(["bundle", "unbundle"])
+            .arg(path)
+            .stdout(Stdio::null())
+            .output_git()?
+            .require_success("bundle unbundle")?;
+        Ok(())
+    }
+
     /// Copy branch
     ///
     /// Copies branch ref, reflog, and `branch.<name>` config sections.
@@ -359,6 +559,40 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         Ok(())
     }
 
+    /// Check out files from the index into a directory.
+    ///
+    /// All files in the index are checked out if no paths are provided. Paths are
+    /// relative to the root of the work tree.
+    pub(crate) fn checkout_index_to_dir<SpecIter, SpecArg>(
+        &self,
+        dir: &Path,
+        paths: Option<SpecIter>,
+    ) -> Result<()>
+    where
+        SpecIter: IntoIterator<Item = SpecArg>,
+        SpecArg: AsRef<OsStr>,
+    {
+        let mut prefix = dir.canonicalize()?.into_os_string();
+        prefix.push(std::path::MAIN_SEPARATOR.to_string());
+        let mut command = self.git_in_work_root()?;
+        command.args(["checkout-index", "--force"]);
+        command.arg({
+            let mut arg = OsString::from("--prefix=");
+            arg.push(prefix);
+            arg
+        });
+        if let Some(paths) = paths {
+            command.arg("--").args(paths);
+        } else {
+            command.arg("--all");
+        }
+        command
+            .stdout(Stdio::null())
+            .output_git()?
+            .require_success("checkout-index")?;
+        Ok(())
+    }
+
     /// Checkout a branch.
     pub(crate) fn checkout(&self, branch_name: &str) -> Result<()> {
         self.git()
@@ -497,16 +731,6 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         Ok(())
     }
 
-    pub(crate) fn diffstat(&self, diff: &[u8]) -> Result<Vec<u8>> {
-        let output = self
-            .git()
-            .args(["apply", "--stat", "--summary"])
-            .stdout(Stdio::piped())
-            .in_and_out(diff)?
-            .require_success("apply --stat --summary")?;
-        Ok(output.stdout)
-    }
-
     /// Generate diff between specified tree and the working tree or index with `git diff-index`.
     pub(crate) fn diff_index(&self, tree_id: git_repository::ObjectId) -> Result<Vec<u8>> {
         let output = self
@@ -518,6 +742,38 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         Ok(output.stdout)
     }
 
+    /// Generate zero-context diff between specified tree and the working tree with `git
+    /// diff-index`.
+    ///
+    /// Submodules are ignored.
+    pub(crate) fn diff_index_worktree_zero<SpecIter, SpecArg>(
+        &self,
+        tree_id: git_repository::ObjectId,
+        pathspecs: Option<SpecIter>,
+    ) -> Result<Vec<u8>>
+    where
+        SpecIter: IntoIterator<Item = SpecArg>,
+        SpecArg: AsRef<OsStr>,
+    {
+        let mut command = self.git();
+        command
+            .args([
+                "diff-index",
+                "-p",
+                "-U0",
+                "--full-index",
+                "--no-color",
+                "--ignore-submodules",
+            ])
+            .arg(tree_id.to_string())
+            .arg("--");
+        if let Some(pathspecs) = pathspecs {
+            command.args(pathspecs);
+        }
+        let output = command.output_git()?.require_success("diff-index")?;
+        Ok(output.stdout)
+    }
+
     /// Get file names that differ between tree and index.
     pub(crate) fn diff_index_names(
         &self,
@@ -560,6 +816,31 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         Ok(!no_diff)
     }
 
+    /// Diff two trees returning a bool indicating whether they differ.
+    ///
+    /// Only paths matching the given pathspecs are compared.
+    pub(crate) fn diff_tree_quiet<SpecIter, SpecArg>(
+        &self,
+        tree1: git_repository::ObjectId,
+        tree2: git_repository::ObjectId,
+        pathspecs: SpecIter,
+    ) -> Result<bool>
+    where
+        SpecIter: IntoIterator<Item = SpecArg>,
+        SpecArg: AsRef<OsStr>,
+    {
+        let output = self
+            .git()
+            .args(["diff-tree", "-r", "--quiet"])
+            .args([tree1.to_string(), tree2.to_string()])
+            .arg("--")
+            .args(pathspecs)
+            .stdout(Stdio::null())
+            .output_git()?
+            .require_code_less_than("diff-tree", 2)?;
+        Ok(!output.status.success())
+    }
+
     /// Get names of files that differ between two trees.
     pub(crate) fn diff_tree_files(
         &self,
@@ -574,20 +855,36 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
             .map(|output| DiffFiles::new(output.stdout))
     }
 
+    /// Get names of files that differ between two trees, excluding deleted files.
+    pub(crate) fn diff_tree_files_present(
+        &self,
+        tree1: git_repository::ObjectId,
+        tree2: git_repository::ObjectId,
+    ) -> Result<DiffFiles> {
+        self.git()
+            .args(["diff-tree", "-r", "--name-only", "-z", "--diff-filter=d"])
+            .args([tree1.to_string(), tree2.to_string()])
+            .output_git()?
+            .require_success("diff-tree")
+            .map(|output| DiffFiles::new(output.stdout))
+    }
+
     /// Interactive diff-tree (for 'stg files').
-    pub(crate) fn diff_tree_files_status(
+    pub(crate) fn diff_tree_files_status<OptIter, OptArg>(
         &self,
         tree1: git_repository::ObjectId,
         tree2: git_repository::ObjectId,
-        stat: bool,
         name_only: bool,
         use_color: bool,
-    ) -> Result<Vec<u8>> {
+        diff_opts: OptIter,
+    ) -> Result<Vec<u8>>
+    where
+        OptIter: IntoIterator<Item = OptArg>,
+        OptArg: AsRef<OsStr>,
+    {
         let mut command = self.git();
         command.args(["diff-tree", "-r"]);
-        if stat {
-            command.args(["--stat", "--summary"]);
-        } else if name_only {
+        if name_only {
             command.arg("--name-only");
         } else {
             command.arg("--name-status");
@@ -597,6 +894,7 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         } else {
             "--color=never"
         });
+        command.args(diff_opts);
         command.args([tree1.to_string(), tree2.to_string()]);
         let output = command.output_git()?.require_success("diff-tree")?;
         Ok(output.stdout)
@@ -634,6 +932,29 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         Ok(output.stdout)
     }
 
+    /// Generate diffstat and summary between two trees using `git diff-tree`.
+    pub(crate) fn diff_tree_stat<OptIter, OptArg>(
+        &self,
+        tree1: git_repository::ObjectId,
+        tree2: git_repository::ObjectId,
+        stat_width: usize,
+        diff_opts: OptIter,
+    ) -> Result<Vec<u8>>
+    where
+        OptIter: IntoIterator<Item = OptArg>,
+        OptArg: AsRef<OsStr>,
+    {
+        let output = self
+            .git()
+            .args(["diff-tree", "-r", "--color=never", "--summary"])
+            .arg(format!("--stat={stat_width}"))
+            .args(diff_opts)
+            .args([tree1.to_string(), tree2.to_string()])
+            .output_git()?
+            .require_success("diff-tree --stat")?;
+        Ok(output.stdout)
+    }
+
     /// Get unmerged path list using `git diff --name-only --diff-filter=U`.
     ///
     /// The returned unmerged paths are relative to the work tree root regardless of the
@@ -654,23 +975,6 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         Ok(paths)
     }
 
-    /// Run `git format-patch` with arbitrary arguments.
-    pub(crate) fn format_patch<OptIter, OptArg>(&self, args: OptIter) -> Result<()>
-    where
-        OptIter: IntoIterator<Item = OptArg>,
-        OptArg: AsRef<OsStr>,
-    {
-        let mut command = self.git();
-        command.arg("format-patch");
-        command.args(args);
-        command
-            .stdin(Stdio::inherit())
-            .stdout(Stdio::inherit())
-            .output_git()?
-            .require_success("format-patch")?;
-        Ok(())
-    }
-
     /// Show log in gitk
     pub(crate) fn gitk<SpecIter, SpecArg>(
         &self,
@@ -725,9 +1029,14 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
     }
 
     /// Interactively show log
+    ///
+    /// When `no_walk` is true, only the given commits are shown instead of their
+    /// history.
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn log<SpecIter, SpecArg>(
         &self,
-        commit_id: git_repository::ObjectId,
+        commit_ids: &[git_repository::ObjectId],
+        no_walk: bool,
         pathspecs: Option<SpecIter>,
         num_commits: Option<usize>,
         use_color: bool,
@@ -751,9 +1060,16 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         if show_diff {
             command.arg("-p");
         } else if !full_index {
-            command.arg("--pretty=tformat:%C(auto)%h   %C(auto,blue)%aD   %C(auto)%s");
+            command.arg(
+                "--pretty=tformat:%C(auto)%h   %C(auto,blue)%aD   %C(auto)%s\
+                 %C(auto,dim)% (trailers:only,unfold,separator=%x2C%x20,\
+                 key=Pushed,key=Popped,key=Renamed,key=Files-Changed,key=Conflicts)%C(auto)",
+            );
         }
-        command.arg(commit_id.to_string());
+        if no_walk {
+            command.arg("--no-walk");
+        }
+        command.args(commit_ids.iter().map(ToString::to_string));
         if let Some(pathspecs) = pathspecs {
             command.arg("--");
             command.args(pathspecs);
@@ -769,104 +1085,75 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         Ok(())
     }
 
-    pub(crate) fn mailinfo(
+    /// Get unmerged index entries with `git ls-files --unmerged`.
+    ///
+    /// Pathspecs are interpreted relative to the current directory, but the returned
+    /// paths are relative to the work tree root. Each entry's stages are the blob ids of
+    /// the common ancestor (base), ours, and theirs versions of the file, any of which
+    /// may be absent.
+    pub(crate) fn ls_files_unmerged<SpecIter, SpecArg>(
         &self,
-        input: Option<std::fs::File>,
-        copy_message_id: bool,
-    ) -> Result<(Vec<u8>, Vec<u8>, Vec<u8>)> {
+        pathspecs: Option<SpecIter>,
+    ) -> Result<Vec<(OsString, [Option<git_repository::ObjectId>; 3])>>
+    where
+        SpecIter: IntoIterator<Item = SpecArg>,
+        SpecArg: AsRef<OsStr>,
+    {
         let mut command = self.git();
-        command.args(["mailinfo", "--scissors", "--encoding=UTF-8"]);
-        if copy_message_id {
-            command.arg("--message-id");
-        }
-        let dir = tempfile::tempdir()?;
-        let message_path = dir.path().join("message.txt");
-        let patch_path = dir.path().join("patch.diff");
-        command.args([message_path.as_path(), patch_path.as_path()]);
-        if let Some(input) = input {
-            command.stdin(input);
-        } else {
-            command.stdin(Stdio::inherit());
+        command.args(["ls-files", "--unmerged", "--full-name", "-z", "--"]);
+        if let Some(pathspecs) = pathspecs {
+            command.args(pathspecs);
         }
         let output = command
-            .stdout(Stdio::piped())
             .output_git()?
-            .require_success("mailinfo")?;
-        let mailinfo = output.stdout;
-        let message = std::fs::read(message_path)?;
-        let diff = std::fs::read(patch_path)?;
-        Ok((mailinfo, message, diff))
-    }
-
-    #[cfg(feature = "import-compressed")]
-    pub(crate) fn mailinfo_stream(
-        &self,
-        mut input: impl std::io::Read + Send + 'static,
-        copy_message_id: bool,
-    ) -> Result<(Vec<u8>, Vec<u8>, Vec<u8>)> {
-        let mut command = self.git();
-        command.args(["mailinfo", "--scissors", "--encoding=UTF-8"]);
-        if copy_message_id {
-            command.arg("--message-id");
-        }
-        let dir = tempfile::tempdir()?;
-        let message_path = dir.path().join("message.txt");
-        let patch_path = dir.path().join("patch.diff");
-        command.args([message_path.as_path(), patch_path.as_path()]);
-        let mut child = command
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .spawn_git()?;
-
-        let mut stdin = child.stdin.take().unwrap();
-
-        std::thread::spawn(move || -> Result<()> {
-            let mut buf = [0; 8192];
-            loop {
-                let n = input.read(&mut buf[..])?;
-                if n > 0 {
-                    stdin.write_all(&buf[..n])?;
-                } else {
-                    break Ok(());
-                }
+            .require_success("ls-files --unmerged")?;
+
+        let mut entries: Vec<(OsString, [Option<git_repository::ObjectId>; 3])> = Vec::new();
+        for record in output.stdout.split_str(b"\0").filter(|r| !r.is_empty()) {
+            let (info, path) = record
+                .split_once_str(b"\t")
+                .ok_or_else(|| anyhow!("unexpected `git ls-files --unmerged` output"))?;
+            let mut fields = info.fields();
+            let (_mode, oid, stage) = match (fields.next(), fields.next(), fields.next()) {
+                (Some(mode), Some(oid), Some(stage)) => (mode, oid, stage),
+                _ => return Err(anyhow!("unexpected `git ls-files --unmerged` output")),
+            };
+            let oid = parse_oid(oid)?;
+            let stage = match stage {
+                b"1" => 0,
+                b"2" => 1,
+                b"3" => 2,
+                _ => return Err(anyhow!("unexpected `git ls-files --unmerged` stage")),
+            };
+            let path: OsString = path
+                .to_os_str()
+                .context("getting unmerged path")?
+                .to_os_string();
+            if let Some((_, stages)) = entries.last_mut().filter(|(p, _)| p == &path) {
+                stages[stage] = Some(oid);
+            } else {
+                let mut stages = [None; 3];
+                stages[stage] = Some(oid);
+                entries.push((path, stages));
             }
-        });
-
-        let output = child.require_success("mailinfo")?;
-        let headers = output.stdout;
-        let message = std::fs::read(message_path)?;
-        let diff = std::fs::read(patch_path)?;
-        Ok((headers, message, diff))
+        }
+        Ok(entries)
     }
 
-    pub(crate) fn mailsplit(
+    /// Determine whether `ancestor` is an ancestor of, or the same commit as,
+    /// `descendant`.
+    pub(crate) fn is_ancestor(
         &self,
-        source_path: Option<&Path>,
-        out_dir: &Path,
-        keep_cr: bool,
-        missing_from_ok: bool,
-    ) -> Result<usize> {
-        let mut command = self.git();
-        command.arg("mailsplit");
-        if keep_cr {
-            command.arg("--keep-cr");
-        }
-        if missing_from_ok {
-            command.arg("-b");
-        }
-        let mut out_opt = OsString::from("-o");
-        out_opt.push(out_dir.as_os_str());
-        command.arg(out_opt);
-        if let Some(source_path) = source_path {
-            command.arg("--");
-            command.arg(source_path);
-        } else {
-            command.stdin(Stdio::inherit());
-        }
-        let output = command.output_git()?.require_success("mailsplit")?;
-        let s = output.stdout.to_str().context("parsing mailsplit output")?;
-        let num_patches = s.trim_end().parse::<usize>()?;
-        Ok(num_patches)
+        ancestor: git_repository::ObjectId,
+        descendant: git_repository::ObjectId,
+    ) -> Result<bool> {
+        let output = self
+            .git()
+            .args(["merge-base", "--is-ancestor"])
+            .args([ancestor.to_string(), descendant.to_string()])
+            .output_git()?
+            .require_code_less_than("merge-base --is-ancestor", 2)?;
+        Ok(output.status.success())
     }
 
     pub(crate) fn merge_bases(
@@ -966,6 +1253,93 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         Ok(())
     }
 
+    /// Copy notes from one object to another in a specific notes ref.
+    ///
+    /// Unlike [`StupidContext::notes_copy()`], any existing note on the destination
+    /// object is overwritten.
+    pub(crate) fn notes_copy_ref(
+        &self,
+        notes_ref: &str,
+        from_oid: git_repository::ObjectId,
+        to_oid: git_repository::ObjectId,
+    ) -> Result<()> {
+        self.git()
+            .args(["notes", "--ref", notes_ref, "copy", "--force"])
+            .arg(from_oid.to_string())
+            .arg(to_oid.to_string())
+            .stdout(Stdio::null())
+            .output_git()?
+            .require_success("notes copy")?;
+        Ok(())
+    }
+
+    /// Get note content for an object using `git notes show`.
+    ///
+    /// Returns `None` if the object does not have a note in the given notes ref.
+    pub(crate) fn notes_show(
+        &self,
+        notes_ref: &str,
+        oid: git_repository::ObjectId,
+    ) -> Result<Option<Vec<u8>>> {
+        let output = self
+            .git()
+            .args(["notes", "--ref", notes_ref, "show"])
+            .arg(oid.to_string())
+            .output_git()?;
+        if output.status.success() {
+            Ok(Some(output.stdout))
+        } else if output.stderr.starts_with(b"error: no note found") {
+            Ok(None)
+        } else {
+            Err(git_command_error("notes show", &output.stderr))
+        }
+    }
+
+    /// Set note content for an object using `git notes add --force`.
+    ///
+    /// Any existing note for the object is replaced. An empty `content` removes the
+    /// note.
+    pub(crate) fn notes_set(
+        &self,
+        notes_ref: &str,
+        oid: git_repository::ObjectId,
+        content: &[u8],
+    ) -> Result<()> {
+        if content.is_empty() {
+            self.git()
+                .args(["notes", "--ref", notes_ref, "remove", "--ignore-missing"])
+                .arg(oid.to_string())
+                .stdout(Stdio::null())
+                .output_git()?
+                .require_success("notes remove")?;
+        } else {
+            self.git()
+                .args(["notes", "--ref", notes_ref, "add", "--force", "--file=-"])
+                .arg(oid.to_string())
+                .stdout(Stdio::null())
+                .in_and_out(content)?
+                .require_success("notes add")?;
+        }
+        Ok(())
+    }
+
+    /// Create an annotated tag pointing at the given commit using `git tag`.
+    pub(crate) fn tag_annotated(
+        &self,
+        tag_name: &str,
+        commit_id: git_repository::ObjectId,
+        message: &[u8],
+    ) -> Result<()> {
+        self.git()
+            .args(["tag", "--annotate", "--file=-", "--cleanup=verbatim"])
+            .arg(tag_name)
+            .arg(commit_id.to_string())
+            .stdout(Stdio::null())
+            .in_and_out(message)?
+            .require_success("tag")?;
+        Ok(())
+    }
+
     /// Read content of a tree into specified index using `git read-tree`.
     pub(crate) fn read_tree(&self, tree_id: git_repository::ObjectId) -> Result<()> {
         self.git()
@@ -1019,6 +1393,25 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         Ok(())
     }
 
+    /// Get the full reference name of a branch's upstream, if it has one.
+    pub(crate) fn branch_upstream_refname(&self, branch_name: &str) -> Result<Option<String>> {
+        let output = self
+            .git()
+            .args(["rev-parse", "--symbolic-full-name"])
+            .arg(format!("{branch_name}@{{upstream}}"))
+            .output_git()?;
+        if output.status.success() {
+            let refname = output
+                .stdout
+                .to_str()
+                .context("parsing upstream name")?
+                .trim_end();
+            Ok((!refname.is_empty()).then(|| refname.to_string()))
+        } else {
+            Ok(None)
+        }
+    }
+
     /// Get list of revisions using `git rev-list`.
     pub(crate) fn rev_list<SpecIter, SpecArg>(
         &self,
@@ -1050,6 +1443,26 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         Ok(oids)
     }
 
+    /// Convert a date string to a unix timestamp using git's approximate date parsing.
+    ///
+    /// Any date format accepted by git, including relative dates such as "2 hours ago"
+    /// or "yesterday", may be used.
+    pub(crate) fn rev_parse_approxidate(&self, date: &str) -> Result<i64> {
+        let output = self
+            .git()
+            .arg("rev-parse")
+            .arg(format!("--before={date}"))
+            .output_git()?
+            .require_success("rev-parse --before")?;
+        output
+            .stdout
+            .trim()
+            .strip_prefix(b"--min-age=")
+            .and_then(|timestamp| timestamp.to_str().ok())
+            .and_then(|timestamp| timestamp.parse::<i64>().ok())
+            .ok_or_else(|| anyhow!("invalid date `{date}`"))
+    }
+
     /// Get cdup for current directory from `git rev-parse --show-cdup`.
     pub(crate) fn rev_parse_cdup(&self) -> Result<OsString> {
         let output = self
@@ -1066,7 +1479,16 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         }
     }
 
-    pub(crate) fn send_email<OptIter, OptArg>(&self, args: OptIter) -> Result<()>
+    /// Run `git send-email` with the given arguments.
+    ///
+    /// The `extra_config` key/value pairs are provided to `git send-email` via the
+    /// environment instead of the command line so that sensitive values, such as
+    /// passwords, are not exposed in the process list.
+    pub(crate) fn send_email<OptIter, OptArg>(
+        &self,
+        args: OptIter,
+        extra_config: &[(String, String)],
+    ) -> Result<()>
     where
         OptIter: IntoIterator<Item = OptArg>,
         OptArg: AsRef<OsStr>,
@@ -1074,6 +1496,17 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         let mut command = self.git();
         command.arg("send-email");
         command.args(args);
+        if !extra_config.is_empty() {
+            let count = std::env::var("GIT_CONFIG_COUNT")
+                .ok()
+                .and_then(|count| count.parse::<usize>().ok())
+                .unwrap_or(0);
+            for (i, (key, value)) in extra_config.iter().enumerate() {
+                command.env(format!("GIT_CONFIG_KEY_{}", count + i), key);
+                command.env(format!("GIT_CONFIG_VALUE_{}", count + i), value);
+            }
+            command.env("GIT_CONFIG_COUNT", (count + extra_config.len()).to_string());
+        }
         command
             .stdin(Stdio::inherit())
             .stdout(Stdio::inherit())
@@ -1082,14 +1515,14 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         Ok(())
     }
 
-    pub(crate) fn send_email_dump_aliases(&self) -> Result<()> {
+    /// Get the alias names known to `git send-email`, one per line.
+    pub(crate) fn send_email_dump_aliases(&self) -> Result<Vec<u8>> {
         let mut command = self.git();
         command.args(["send-email", "--dump-aliases"]);
-        command
-            .stdout(Stdio::inherit())
+        let output = command
             .output_git()?
             .require_success("send-email --dump-aliases")?;
-        Ok(())
+        Ok(output.stdout)
     }
 
     /// Show objects using `git show`.
@@ -1097,7 +1530,6 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         &self,
         oids: impl IntoIterator<Item = git_repository::ObjectId>,
         pathspecs: Option<SpecIter>,
-        stat: bool,
         use_color: bool,
         diff_opts: OptIter,
     ) -> Result<()>
@@ -1108,12 +1540,7 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         OptArg: AsRef<OsStr>,
     {
         let mut command = self.git();
-        command.arg("show");
-        if stat {
-            command.args(["--stat", "--summary"]);
-        } else {
-            command.arg("--patch");
-        }
+        command.args(["show", "--patch"]);
 
         command.arg(if use_color {
             "--color=always"
@@ -1136,6 +1563,26 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         Ok(())
     }
 
+    /// Show an object as `git show` would, but without a diff.
+    pub(crate) fn show_no_patch(
+        &self,
+        oid: git_repository::ObjectId,
+        use_color: bool,
+    ) -> Result<Vec<u8>> {
+        let output = self
+            .git()
+            .args(["show", "--no-patch"])
+            .arg(if use_color {
+                "--color=always"
+            } else {
+                "--color=never"
+            })
+            .arg(oid.to_string())
+            .output_git()?
+            .require_success("show")?;
+        Ok(output.stdout)
+    }
+
     /// Show object with custom pretty format.
     pub(crate) fn show_pretty(
         &self,
@@ -1152,6 +1599,47 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         Ok(output.stdout)
     }
 
+    /// Compute the stable patch id of a diff using `git patch-id --stable`.
+    ///
+    /// Returns `None` if the diff is empty.
+    pub(crate) fn patch_id(&self, diff: &[u8]) -> Result<Option<git_repository::ObjectId>> {
+        let output = self
+            .git()
+            .args(["patch-id", "--stable"])
+            .stdout(Stdio::piped())
+            .in_and_out(diff)?
+            .require_success("patch-id")?;
+        if let Some(patch_id) = output
+            .stdout
+            .split_str(" ")
+            .next()
+            .filter(|s| !s.is_empty())
+        {
+            Ok(Some(parse_oid(patch_id)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Compare two commit ranges using `git range-diff`.
+    pub(crate) fn range_diff(
+        &self,
+        old_range: &str,
+        new_range: &str,
+        creation_factor: Option<&str>,
+    ) -> Result<Vec<u8>> {
+        let mut command = self.git();
+        command.args(["range-diff", "--no-color"]);
+        if let Some(creation_factor) = creation_factor {
+            command.arg(format!("--creation-factor={creation_factor}"));
+        }
+        let output = command
+            .args([old_range, new_range])
+            .output_git()?
+            .require_success("range-diff")?;
+        Ok(output.stdout)
+    }
+
     /// Pop stashed changes back into working tree and index.
     ///
     /// Returns Ok(true) if stash application is successful, Ok(false) if stash
@@ -1307,6 +1795,64 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         Ok(())
     }
 
+    /// Fetch refs matching the given refspecs from a remote with `git fetch`.
+    ///
+    /// When `prune` is true, local refs matching the refspecs' destinations that no
+    /// longer exist on the remote are removed.
+    pub(crate) fn fetch_refspecs(
+        &self,
+        remote: &str,
+        refspecs: &[String],
+        prune: bool,
+    ) -> Result<()> {
+        let mut command = self.git();
+        command.args(["fetch", "--quiet", "--no-tags", "--no-write-fetch-head"]);
+        if prune {
+            command.arg("--prune");
+        }
+        command
+            .arg(remote)
+            .args(refspecs)
+            .stdout(Stdio::null())
+            .output_git()?
+            .require_success("fetch")?;
+        Ok(())
+    }
+
+    /// Push refspecs to a remote.
+    pub(crate) fn push_refspecs(&self, remote: &str, refspecs: &[String]) -> Result<()> {
+        self.git()
+            .args(["push", "--quiet", "--no-verify"])
+            .arg(remote)
+            .args(refspecs)
+            .stdout(Stdio::null())
+            .output_git()?
+            .require_success("push")?;
+        Ok(())
+    }
+
+    /// Fetch a single reference from a remote, returning the id of the fetched commit.
+    ///
+    /// The fetched reference is only recorded in `FETCH_HEAD`.
+    pub(crate) fn fetch_commit(
+        &self,
+        remote: &str,
+        refname: &str,
+    ) -> Result<git_repository::ObjectId> {
+        self.git()
+            .args(["fetch", "--quiet", "--no-tags"])
+            .args([remote, refname])
+            .stdout(Stdio::null())
+            .output_git()?
+            .require_success("fetch")?;
+        let output = self
+            .git()
+            .args(["rev-parse", "--verify", "FETCH_HEAD^{commit}"])
+            .output_git()?
+            .require_success("rev-parse")?;
+        parse_oid(&output.stdout)
+    }
+
     /// Run user-provided fetch command.
     pub(crate) fn user_fetch(&self, user_cmd_str: &str, remote_name: &str) -> Result<()> {
         let mut args = user_cmd_str.split(|c: char| c.is_ascii_whitespace());
@@ -1403,6 +1949,40 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         }
     }
 
+    /// Run user-provided git command with inherited stdin, stdout, and stderr.
+    ///
+    /// The exit status of the git command is returned.
+    pub(crate) fn user_git<I, S>(&self, args: I) -> Result<std::process::ExitStatus>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.git()
+            .args(args)
+            .stdin(Stdio::inherit())
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit())
+            .status()
+            .context("could not execute `git`")
+    }
+
+    /// Get the user's pager of choice with `git var GIT_PAGER`.
+    pub(crate) fn var_pager(&self) -> Result<OsString> {
+        let output = self
+            .git()
+            .args(["var", "GIT_PAGER"])
+            .output_git()?
+            .require_success("var")?;
+        let mut stdout = output.stdout;
+        if stdout.ends_with(b"\n") {
+            stdout.pop();
+        }
+        match stdout.into_os_string() {
+            Ok(pager) => Ok(pager),
+            Err(_) => Err(anyhow!("could not convert pager to os string")),
+        }
+    }
+
     /// Get git version with `git version`.
     pub(crate) fn version(&self) -> Result<String> {
         let output = self
@@ -120,6 +120,12 @@ test_expect_success 'Run show --stat on patches' '
     test $(grep -c -E "patch-aaa|patch-ddd" show-a-d-stat.txt) = "2"
 '
 
+test_expect_success 'Show --stat matches git show' '
+    stg show --stat patch-aaa patch-ddd >show-a-d-stat.txt &&
+    git show --stat --summary $(stg id patch-aaa) $(stg id patch-ddd) >git-a-d-stat.txt &&
+    test_cmp git-a-d-stat.txt show-a-d-stat.txt
+'
+
 test_expect_success 'Setup for path limiting' '
     stg new -m many-paths &&
     mkdir -p dir0/dir1 &&
@@ -144,4 +150,40 @@ test_expect_success 'Multiple path limits' '
     grep -e "ccc\.txt" out
 '
 
+test_expect_success 'Show patches of another branch' '
+    stg branch --clone other &&
+    stg hide patch-aaa &&
+    stg branch master &&
+    stg show other:patch-aaa >out &&
+    grep -e "patch-aaa" out &&
+    stg show other:patch-bbb..patch-ccc >out &&
+    test $(grep -c -E "\+(bbb|ccc)" out) = "2" &&
+    test $(grep -c -E "\+(aaa|ddd)" out) = "0" &&
+    stg show -b other patch-aaa >out &&
+    grep -e "patch-aaa" out &&
+    command_error stg show other:no-such-patch 2>err &&
+    grep -e "patch or revision \`other:no-such-patch\` not found" err
+'
+
+test_expect_success 'Show --stat with path limit, width, and count' '
+    stg show --stat --stat-count=1 many-paths >out &&
+    sed -n -e "/ | /,\$p" out >out.stat &&
+    cat >expected <<-\EOF &&
+	 dir0/aaa.txt | 0
+	 ...
+	 3 files changed, 0 insertions(+), 0 deletions(-)
+	 create mode 100644 dir0/aaa.txt
+	 create mode 100644 dir0/bbb.txt
+	 create mode 100644 dir0/dir1/ccc.txt
+	EOF
+    test_cmp expected out.stat &&
+    stg show --stat --stat-width=20 many-paths -- dir0/dir1 >out &&
+    grep -e "^ \.\.\./dir1/ccc\.txt | 0$" out
+'
+
+test_expect_success 'Show revisions' '
+    stg show HEAD~1 >out &&
+    test "$(cat out)" = "$(stg show $(git rev-parse HEAD~1))"
+'
+
 test_done
@@ -88,4 +88,47 @@ test_expect_success 'Moved file bare' '
     test_cmp a-d-bare.log expected-a-d-bare.log
 '
 
+test_expect_success 'Moved file with configured rename detection' '
+    test_config stgit.diff.renames true &&
+    stg files patch-a-d >a-d-renames.log &&
+    echo "R100 a.txt d.txt" >expected-a-d-renames.log &&
+    test_cmp expected-a-d-renames.log a-d-renames.log
+'
+
+test_expect_success 'Stat output aligns non-ASCII file names' '
+    stg new -m patch-unicode &&
+    echo x >"$(printf "caf\303\251.txt")" &&
+    echo y >"$(printf "\346\227\245.txt")" &&
+    stg add "$(printf "caf\303\251.txt")" "$(printf "\346\227\245.txt")" &&
+    stg refresh &&
+    stg files --stat >unicode-stat.log &&
+    printf " caf\303\251.txt | 1 +\n \346\227\245.txt   | 1 +\n" >expected-unicode-stat.log &&
+    head -n 2 unicode-stat.log >unicode-stat-lines.log &&
+    test_cmp expected-unicode-stat.log unicode-stat-lines.log
+'
+
+test_expect_success 'Stat width and count' '
+    stg files --stat --stat-count=1 patch-b-c >b-c-count.log &&
+    cat >expected-b-c-count.log <<-\EOF &&
+	 b.txt | 1 +
+	 ...
+	 2 files changed, 2 insertions(+)
+	 create mode 100644 c.txt
+	EOF
+    test_cmp expected-b-c-count.log b-c-count.log &&
+    stg new -m patch-long-name &&
+    mkdir -p some/rather/long/directory/path &&
+    test_seq 1 100 >some/rather/long/directory/path/file.txt &&
+    stg add some &&
+    stg refresh &&
+    stg files --stat --stat-width=40 >long-stat.log &&
+    echo " .../path/file.txt         | 100 ++++++" >expected-long-stat.log &&
+    head -n 1 long-stat.log >long-stat-line.log &&
+    test_cmp expected-long-stat.log long-stat-line.log
+'
+
+test_expect_success 'Stat width requires stat' '
+    general_error stg files --stat-width=40
+'
+
 test_done
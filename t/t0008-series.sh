@@ -144,6 +144,43 @@ test_expect_success 'Test short no applied' '
     test_cmp expected.txt series.txt
 '
 
+test_expect_success 'Test short with window size' '
+    stg series --short=0 >series.txt &&
+    echo "> p2" >expected.txt &&
+    test_cmp expected.txt series.txt &&
+    stg series --short=1@top >series.txt &&
+    echo "+ p1" >expected.txt &&
+    echo "> p2" >>expected.txt &&
+    echo "- p3" >>expected.txt &&
+    test_cmp expected.txt series.txt
+'
+
+test_expect_success 'Test short with anchor patch' '
+    stg series --short=1@p0 >series.txt &&
+    echo "+ p0" >expected.txt &&
+    echo "+ p1" >>expected.txt &&
+    test_cmp expected.txt series.txt &&
+    stg series --short=0@p3 >series.txt &&
+    echo "- p3" >expected.txt &&
+    test_cmp expected.txt series.txt
+'
+
+test_expect_success 'Test short with anchor and configured size' '
+    test_config stgit.shortnr 2 &&
+    stg series --noprefix --short=@p0 >series.txt &&
+    printf "p0\np1\np2\n" >expected.txt &&
+    test_cmp expected.txt series.txt
+'
+
+test_expect_success 'Test short with invalid window' '
+    general_error stg series --short=x 2>err &&
+    grep -e "window size must be a non-negative integer" err &&
+    command_error stg series --short=1@p9 2>err &&
+    grep -e "patch \`p9\` does not exist" err &&
+    command_error stg series --applied --short=1@p3 2>err &&
+    grep -e "patch \`p3\` is not among the selected patches" err
+'
+
 test_expect_success 'Test effects' '
     test_config stgit.color.applied none &&
     stg series --applied >series.txt &&
@@ -163,4 +200,79 @@ test_expect_success 'Test missing' '
     test_cmp expected.txt series.txt
 '
 
+test_expect_success 'Test duplicates' '
+    stg series --duplicates >series.txt &&
+    test_must_be_empty series.txt &&
+    echo "d" >d.txt &&
+    stg add d.txt &&
+    stg new -m "add d" d1 &&
+    stg refresh &&
+    stg pop d1 &&
+    echo "d" >d.txt &&
+    stg add d.txt &&
+    stg new -m "add d" d2 &&
+    stg refresh &&
+    stg pop d2 &&
+    echo "d" >d.txt &&
+    stg add d.txt &&
+    stg new -m "add d again" d3 &&
+    stg refresh &&
+    stg hide d2 &&
+    stg series --duplicates --color=never >series.txt &&
+    patch_id=$(git diff-tree -p $(stg id d3) | git patch-id --stable | cut -c1-12) &&
+    cat >expected.txt <<-EOF &&
+	$patch_id
+	  > d3
+	  - d1 # same changes as d3
+	  ! d2 # same changes as d3
+
+	Suggested remediation:
+	  stg delete d2
+	  stg squash --name=d3 d3 d1
+	EOF
+    test_cmp expected.txt series.txt
+'
+
+test_expect_success 'Test duplicates with selection' '
+    general_error stg series --duplicates --all &&
+    general_error stg series --duplicates p0
+'
+
+test_expect_success 'Test preview of diffstats' '
+    stg series --preview 2 --color=never d3..d1 >series.txt &&
+    cat >expected.txt <<-\EOF &&
+	> d3
+	     d.txt | 1 +
+	     1 file changed, 1 insertion(+)
+	- d1
+	     d.txt | 1 +
+	     1 file changed, 1 insertion(+)
+	EOF
+    test_cmp expected.txt series.txt
+'
+
+test_expect_success 'Test preview of message bodies' '
+    stg edit -m "add d again
+
+First body line.
+
+Third body line.
+Fourth body line." d3 &&
+    stg series --preview 3 --preview-body --no-prefix --color=never d3..d1 >series.txt &&
+    cat >expected.txt <<-\EOF &&
+	d3
+	    First body line.
+
+	    Third body line.
+	d1
+	EOF
+    test_cmp expected.txt series.txt
+'
+
+test_expect_success 'Test preview option errors' '
+    general_error stg series --preview-body &&
+    general_error stg series --preview 2 --count &&
+    general_error stg series --preview x
+'
+
 test_done
@@ -5,7 +5,7 @@ test_description='Test the log command'
 . ./test-lib.sh
 
 test_expect_success 'Attempt log on uninitialized branch' '
-    command_error stg log 2>err >/dev/null &&
+    uninitialized stg log 2>err >/dev/null &&
     grep -e "StGit stack not initialized for branch \`master\`" err
 '
 
@@ -136,6 +136,62 @@ test_expect_success 'Log with number' '
     head -n 3 log.txt | tail -n 1 | grep -e "refresh"
 '
 
+test_expect_success 'Log with files' '
+    stg log --files file1.txt >log.txt &&
+    test_line_count = 3 log.txt &&
+    head -n 1 log.txt | tail -n 1 | grep -e "refresh" &&
+    head -n 2 log.txt | tail -n 1 | grep -e "refresh" &&
+    head -n 3 log.txt | tail -n 1 | grep -e "external modifications" &&
+    stg log --files file2.txt >log.txt &&
+    test_line_count = 4 log.txt &&
+    head -n 1 log.txt | tail -n 1 | grep -e "refresh" &&
+    head -n 2 log.txt | tail -n 1 | grep -e "goto" &&
+    head -n 3 log.txt | tail -n 1 | grep -e "goto" &&
+    head -n 4 log.txt | tail -n 1 | grep -e "external modifications"
+'
+
+test_expect_success 'Log with multiple files and number' '
+    stg log --files file1.txt --files file3.txt -n 2 >log.txt &&
+    test_line_count = 2 log.txt &&
+    head -n 1 log.txt | tail -n 1 | grep -e "goto" &&
+    head -n 2 log.txt | tail -n 1 | grep -e "refresh"
+'
+
+test_expect_success 'Log with files and patch' '
+    stg log --files file2.txt p2 >log.txt &&
+    test_line_count = 1 log.txt &&
+    grep -e "goto" log.txt
+'
+
+test_expect_success 'Log with files from subdir' '
+    mkdir subdir &&
+    (cd subdir &&
+     stg log --files ../file1.txt >../log.txt
+    ) &&
+    rmdir subdir &&
+    test_line_count = 3 log.txt
+'
+
+test_expect_success 'Log with unchanged file' '
+    stg log --files file4.txt >log.txt &&
+    test_must_be_empty log.txt
+'
+
+test_expect_success 'Log operation statistics' '
+    stg pop -n 2 &&
+    stg log -n 1 | grep -e "pop Popped: 2, Files-Changed: 2$" &&
+    stg push -a &&
+    stg log -n 1 | grep -e "push Pushed: 2, Files-Changed: 2$" &&
+    stg rename p3 p3-renamed &&
+    stg log -n 1 | grep -e "rename p3 p3-renamed Renamed: 1$" &&
+    stg log --full -n 1 >log.txt &&
+    grep -e "^    Renamed: 1$" log.txt &&
+    stg undo &&
+    stg log -n 1 | grep -e "undo 1$" &&
+    stg undo &&
+    test "$(echo $(stg series --noprefix))" = "p0 p1 p2 p3"
+'
+
 test_expect_success 'Clear the log' '
     stg log --clear &&
     test "$(echo $(stg series --noprefix))" = "p0 p1 p2 p3" &&
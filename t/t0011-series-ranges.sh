@@ -95,4 +95,24 @@ test_expect_success 'Disjoint ranges' '
     grep -e "error: \`p5\.\.\` not contiguous with preceding range \`p1\.\.p2\`" err
 '
 
+test_expect_success 'Ranges of another branch' '
+    stg branch --clone cloned &&
+    stg goto p2 &&
+    stg branch master &&
+    stg series cloned:p2..p4 >series.txt &&
+    cat >expected.txt <<-\EOF &&
+	> p2
+	- p3
+	- p4
+	EOF
+    test_cmp expected.txt series.txt &&
+    stg series --branch cloned p2..p4 >series.txt &&
+    test_cmp expected.txt series.txt &&
+    stg series -d cloned:p6 >series.txt &&
+    echo "! p6 # p6" >expected.txt &&
+    test_cmp expected.txt series.txt &&
+    command_error stg series cloned:p1 master:p2 2>err &&
+    grep -e "patch ranges must be from a single branch" err
+'
+
 test_done
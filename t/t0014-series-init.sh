@@ -0,0 +1,64 @@
+#!/bin/sh
+
+test_description='Test stg series init'
+
+. ./test-lib.sh
+
+test_expect_success 'Create patches from template' '
+    cat >template <<-\EOF &&
+	# Standard series layout
+	prep-cleanup Preparatory cleanups
+
+	feature
+	docs   Update documentation
+	EOF
+    stg series init --from-template template &&
+    test "$(echo $(stg series --noprefix --applied))" = "prep-cleanup feature docs" &&
+    test "$(stg series --empty --noprefix | grep -c -e "^0")" = "3" &&
+    test "$(git log -1 --format=%s $(stg id prep-cleanup))" = "Preparatory cleanups" &&
+    test "$(git log -1 --format=%s $(stg id feature))" = "feature" &&
+    test "$(git log -1 --format=%s $(stg id docs))" = "Update documentation"
+'
+
+test_expect_success 'Template patches collide with existing patches' '
+    command_error stg series init --from-template template 2>err &&
+    grep -e "patch \`prep-cleanup\` already exists" err
+'
+
+test_expect_success 'Template patch names differing only in case' '
+    printf "Prep-Cleanup\n" >template-case &&
+    stg series init --noapply --from-template template-case 2>err &&
+    grep -e "patch name \`Prep-Cleanup\` differs only in case from an existing patch; using \`Prep-Cleanup-1\`" err &&
+    test "$(stg series --noprefix --unapplied)" = "Prep-Cleanup-1" &&
+    test "$(git log -1 --format=%s $(stg id Prep-Cleanup-1))" = "Prep-Cleanup" &&
+    stg delete Prep-Cleanup-1
+'
+
+test_expect_success 'Create unapplied patches from template' '
+    printf "tests\nbench\n" >template2 &&
+    stg series init --noapply --from-template template2 &&
+    test "$(echo $(stg series --noprefix --unapplied))" = "tests bench" &&
+    test "$(echo $(stg series --noprefix --applied))" = "prep-cleanup feature docs"
+'
+
+test_expect_success 'Invalid templates' '
+    printf "a\nb\na\n" >dup &&
+    command_error stg series init --from-template dup 2>err &&
+    grep -e "patch \`a\` appears more than once in template" err &&
+    printf "# nothing\n\n" >empty &&
+    command_error stg series init --from-template empty 2>err &&
+    grep -e "does not name any patches" err &&
+    printf "bad:name\n" >bad &&
+    command_error stg series init --from-template bad 2>err &&
+    grep -e "invalid patch name" err &&
+    command_error stg series init --from-template missing 2>err &&
+    grep -e "reading template \`missing\`" err
+'
+
+test_expect_success 'Show patch named init' '
+    stg new -m "init patch" init &&
+    stg series -- init >out &&
+    grep -e "> init" out
+'
+
+test_done
@@ -0,0 +1,75 @@
+#!/bin/sh
+
+test_description='Test patch name portability checks and stg doctor'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize stack' '
+    stg init
+'
+
+test_expect_success 'Doctor with no problematic names' '
+    stg new -m p0 &&
+    stg doctor >out &&
+    test_must_be_empty out
+'
+
+test_expect_success 'Warn about reserved device name' '
+    stg new -m aux aux 2>err &&
+    grep -e "patch name \`aux\` is a reserved Windows device name" err &&
+    grep -e "consider \`aux_\` instead" err &&
+    test "$(stg top)" = "aux"
+'
+
+test_expect_success 'Warn about invalid Windows characters on rename' '
+    stg rename p0 "a|b" 2>err &&
+    grep -e "contains characters that are invalid in Windows file names" err &&
+    stg series --noprefix >out &&
+    grep -e "a|b" out
+'
+
+test_expect_success 'Warn about non-NFC name' '
+    name="$(printf "cafe\314\201")" &&
+    stg new -m cafe "$name" 2>err &&
+    grep -e "is not in Unicode NFC form" err
+'
+
+test_expect_success 'Error with stgit.namecheck=error' '
+    test_config stgit.namecheck error &&
+    command_error stg new -m nul nul.x 2>err &&
+    grep -e "patch name \`nul.x\` is a reserved Windows device name; consider \`nul_.x\` instead" err &&
+    test "$(echo $(stg series --noprefix))" = "$(echo a\|b aux $(printf "cafe\314\201"))"
+'
+
+test_expect_success 'No warning with stgit.namecheck=off' '
+    test_config stgit.namecheck off &&
+    stg new -m prn prn 2>err &&
+    test_must_be_empty err
+'
+
+test_expect_success 'Doctor reports problematic names' '
+    command_error stg doctor >out 2>err &&
+    cat >expected <<-EOF &&
+	patch \`a|b\` contains characters that are invalid in Windows file names (portable name \`a-b\`)
+	patch \`aux\` is a reserved Windows device name (portable name \`aux_\`)
+	patch \`$(printf "cafe\314\201")\` is not in Unicode NFC form (portable name \`$(printf "caf\303\251")\`)
+	patch \`prn\` is a reserved Windows device name (portable name \`prn_\`)
+	EOF
+    test_cmp expected out &&
+    grep -e "found 4 problematic patch names; use \`stg doctor --fix\` to rename" err
+'
+
+test_expect_success 'Doctor fixes problematic names' '
+    stg new -m aux_ aux_ &&
+    stg doctor --fix &&
+    test "$(echo $(stg series --noprefix))" = "a-b aux_-1 $(printf "caf\303\251") prn_ aux_" &&
+    stg doctor >out &&
+    test_must_be_empty out
+'
+
+test_expect_success 'Undo doctor fix' '
+    stg undo &&
+    test "$(echo $(stg series --noprefix))" = "a|b aux $(printf "cafe\314\201") prn aux_"
+'
+
+test_done
@@ -0,0 +1,61 @@
+#!/bin/sh
+
+test_description='Test stg selftest'
+
+. ./test-lib.sh
+
+test_expect_success 'Transaction self-test passes' '
+    stg selftest transactions >out &&
+    test_line_count = 15 out &&
+    ! grep -e "^FAIL" out &&
+    grep -e "^ok   ref-update: stg push$" out &&
+    grep -e "^ok   branch-ref-updated: stg push$" out &&
+    grep -e "^ok   stack-ref-update: stg push$" out
+'
+
+test_expect_success 'Self-test does not touch the current repository' '
+    git for-each-ref >refs-before &&
+    stg selftest transactions &&
+    git for-each-ref >refs-after &&
+    test_cmp refs-before refs-after
+'
+
+test_expect_success 'Injected failure is rolled back' '
+    stg init &&
+    stg new -m p1 &&
+    echo "hello" >file &&
+    stg add file &&
+    stg refresh &&
+    stg pop &&
+    git rev-parse HEAD >head-before &&
+    STGIT_FAILPOINT=state-commit command_error stg push 2>err &&
+    grep -e "injected failure at \`state-commit\`" err &&
+    grep -e "command aborted (all changes rolled back)" err &&
+    git rev-parse HEAD >head-after &&
+    test_cmp head-before head-after &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p1" &&
+    test_path_is_missing file
+'
+
+test_expect_success 'Injected failures after the branch reference update are rolled back' '
+    git for-each-ref >refs-before &&
+    for point in branch-ref-updated stack-ref-update
+    do
+        STGIT_FAILPOINT=$point command_error stg push 2>err &&
+        grep -e "injected failure at \`$point\`" err &&
+        grep -e "command aborted (all changes rolled back)" err &&
+        git for-each-ref >refs-after &&
+        test_cmp refs-before refs-after &&
+        test "$(echo $(stg series --unapplied --noprefix))" = "p1" &&
+        test_path_is_missing file || return 1
+    done &&
+    stg push &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1"
+'
+
+test_expect_success 'Selftest is hidden from the command list' '
+    stg completion list commands >cmds &&
+    ! grep -e "^selftest$" cmds
+'
+
+test_done
@@ -117,4 +117,43 @@ test_expect_success 'Config section is renamed' '
     ! git config --get-regexp "branch\\.xxx\\."
 '
 
+test_expect_success 'Rename to existing branch' '
+    git branch existing &&
+    command_error stg branch --rename yyy existing 2>err &&
+    grep -e "branch \`existing\` already exists" err &&
+    _assert_branch_exists yyy &&
+    git branch -D existing
+'
+
+test_expect_success 'Detect stack orphaned by git branch rename' '
+    _assert_current_branch_name "yyy" &&
+    stg series -a >expected-patches.txt &&
+    git branch -m yyy zzz &&
+    git show-ref --verify --quiet refs/stacks/yyy &&
+    uninitialized stg series 2>err &&
+    grep -e "the stack of the missing branch \`yyy\` matches this branch" err &&
+    grep -e "stg branch --rename yyy zzz" err &&
+    uninitialized stg new -m new-patch 2>err &&
+    grep -e "stg branch --rename yyy zzz" err
+'
+
+test_expect_success 'Migrate orphaned stack' '
+    stg branch --rename yyy zzz &&
+    ! git show-ref --verify --quiet refs/stacks/yyy &&
+    test -z "$(git for-each-ref refs/patches/yyy/)" &&
+    _assert_branch_exists zzz &&
+    git config branch.zzz.stgit.autostash &&
+    ! git config --get-regexp "branch\\.yyy\\." &&
+    stg series -a >patches.txt &&
+    test_cmp expected-patches.txt patches.txt &&
+    test "$(git rev-parse refs/patches/zzz/p1)" = "$(stg id p1)"
+'
+
+test_expect_success 'Migrate orphaned stack to initialized branch' '
+    git branch -m zzz abc &&
+    stg init &&
+    command_error stg branch --rename zzz abc 2>err &&
+    grep -e "StGit stack already initialized for branch \`abc\`" err
+'
+
 test_done
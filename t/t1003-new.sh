@@ -49,6 +49,13 @@ test_expect_success 'Attempt to create patch with duplicate name' '
     grep -e "patch \`foo\` already exists" err
 '
 
+test_expect_success 'Create patch with name differing only in case' '
+    stg new FOO -m "case foo" 2>err &&
+    grep -e "patch name \`FOO\` differs only in case from an existing patch; using \`FOO-1\`" err &&
+    test "$(stg top)" = "FOO-1" &&
+    stg delete FOO-1
+'
+
 test_expect_success 'Attempt new with conflicts' '
     stg new -m p0 &&
     echo "something" >file.txt &&
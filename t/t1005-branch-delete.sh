@@ -61,4 +61,36 @@ test_expect_success 'Delete a nonexistent branch' '
    grep -e "branch \`bar\` not found" err
 '
 
+test_expect_success 'Force delete branch with backup bundle' '
+    stg branch --clone backed-up &&
+    stg new -m extra-patch &&
+    stg branch foo &&
+    stg branch --delete --force-backup backed-up 2>err &&
+    test_must_fail git rev-parse --verify -q refs/heads/backed-up &&
+    bundle=$(sed -n -e "s/.*restore with \`stg bundle apply \(.*\)\`$/\1/p" err) &&
+    test_path_is_file "$bundle" &&
+    case "$bundle" in
+    */.git/stgit-backups/backed-up-*.bundle) true ;;
+    *) false ;;
+    esac &&
+    stg bundle apply "$bundle" &&
+    test "$(echo $(stg series -b backed-up --noprefix))" = "p0 p1 extra-patch"
+'
+
+test_expect_success 'Backup directory is configurable' '
+    test_config stgit.branch.backup-dir "$(pwd)/backups" &&
+    stg branch --delete --force-backup backed-up 2>err &&
+    grep -e "Backed up \`backed-up\` to \`$(pwd)/backups/backed-up-.*\.bundle\`" err &&
+    test_path_is_dir backups
+'
+
+test_expect_success 'Protected branch is not backed up or deleted' '
+    stg branch --clone protected &&
+    stg branch --protect &&
+    stg branch foo &&
+    command_error stg branch --delete --force-backup protected 2>err &&
+    grep -e "delete not permitted: this branch is protected" err &&
+    test_path_is_missing .git/stgit-backups/protected-*
+'
+
 test_done
@@ -68,12 +68,12 @@ test_expect_success 'Cleanup unprotected' '
 '
 
 test_expect_success 'Protect uninitialized branch' '
-    command_error stg branch --protect 2>err &&
+    uninitialized stg branch --protect 2>err &&
     grep -E "StGit stack not initialized for branch \`foo\`" err
 '
 
 test_expect_success 'Protect uninitialized branch' '
-    command_error stg branch --unprotect 2>err &&
+    uninitialized stg branch --unprotect 2>err &&
     grep -E "StGit stack not initialized for branch \`foo\`" err
 '
 
@@ -0,0 +1,121 @@
+#!/bin/sh
+
+test_description='Test namespaced stack and patch references'
+
+. ./test-lib.sh
+
+test_expect_success 'Create stacks for two users' '
+    test_commit_bulk --message="c%s" 2 &&
+    git config stgit.refnamespace alice &&
+    stg init &&
+    stg new -m alice-patch p-alice &&
+    stg pop -a &&
+    git show-ref --verify --quiet refs/stacks-ns/alice/master &&
+    git show-ref --verify --quiet refs/patches-ns/alice/master/p-alice &&
+    ! git show-ref --verify --quiet refs/stacks/master &&
+    git config stgit.refnamespace bob &&
+    stg init &&
+    stg new -m bob-patch p-bob &&
+    git show-ref --verify --quiet refs/stacks-ns/bob/master &&
+    git show-ref --verify --quiet refs/patches-ns/bob/master/p-bob &&
+    ! git show-ref --verify --quiet refs/patches-ns/alice/master/p-bob
+'
+
+test_expect_success 'Stacks are independent' '
+    test "$(echo $(stg series --noprefix --all))" = "p-bob" &&
+    stg pop -a &&
+    git config stgit.refnamespace alice &&
+    test "$(echo $(stg series --noprefix --all))" = "p-alice" &&
+    stg push p-alice &&
+    test "$(stg top)" = "p-alice"
+'
+
+test_expect_success 'Stack without namespace is separate' '
+    test_unconfig stgit.refnamespace &&
+    stg series --all >out &&
+    test_must_be_empty out &&
+    ! git show-ref --verify --quiet refs/stacks/master &&
+    git config stgit.refnamespace alice
+'
+
+test_expect_success 'Patch revspecs use namespace' '
+    test "$(stg id p-alice)" = "$(git rev-parse refs/patches-ns/alice/master/p-alice)"
+'
+
+test_expect_success 'Patch refs are repaired within namespace' '
+    git update-ref -d refs/patches-ns/alice/master/p-alice &&
+    git update-ref refs/patches-ns/alice/master/bogus HEAD &&
+    stg series >/dev/null &&
+    git show-ref --verify --quiet refs/patches-ns/alice/master/p-alice &&
+    ! git show-ref --verify --quiet refs/patches-ns/alice/master/bogus &&
+    git show-ref --verify --quiet refs/patches-ns/bob/master/p-bob
+'
+
+test_expect_success 'Rename branch with namespaced stack' '
+    stg branch --rename master renamed &&
+    git show-ref --verify --quiet refs/stacks-ns/alice/renamed &&
+    ! git show-ref --verify --quiet refs/stacks-ns/alice/master &&
+    ! git show-ref --verify --quiet refs/patches-ns/alice/master/p-alice &&
+    test "$(echo $(stg series --noprefix --all))" = "p-alice" &&
+    git show-ref --verify --quiet refs/patches-ns/alice/renamed/p-alice
+'
+
+test_expect_success 'Delete branch with namespaced stack' '
+    stg branch --create other &&
+    stg new -m other-patch p-other &&
+    git show-ref --verify --quiet refs/patches-ns/alice/other/p-other &&
+    stg branch renamed &&
+    stg branch --delete --force other &&
+    ! git show-ref --verify --quiet refs/stacks-ns/alice/other &&
+    ! git show-ref --verify --quiet refs/patches-ns/alice/other/p-other
+'
+
+test_expect_success 'Namespaced stacks do not collide with slashed branch names' '
+    test_unconfig stgit.refnamespace &&
+    git branch alice/other HEAD &&
+    stg branch alice/other &&
+    stg init &&
+    stg new -m slashed-patch p-slashed &&
+    git show-ref --verify --quiet refs/stacks/alice/other &&
+    git config stgit.refnamespace alice &&
+    stg branch --create other &&
+    stg new -m other-patch p-other &&
+    git show-ref --verify --quiet refs/stacks-ns/alice/other &&
+    test_unconfig stgit.refnamespace &&
+    stg branch alice/other &&
+    test "$(echo $(stg series --noprefix --all))" = "p-slashed" &&
+    git config stgit.refnamespace alice &&
+    stg branch other &&
+    test "$(echo $(stg series --noprefix --all))" = "p-other"
+'
+
+test_expect_success 'Orphaned stacks are only found in the active namespace' '
+    git config stgit.refnamespace bob &&
+    git branch orphan-test &&
+    stg branch orphan-test &&
+    stg init &&
+    git branch -m orphan-test orphan-moved &&
+    test_unconfig stgit.refnamespace &&
+    stg series 2>err &&
+    ! grep -e "orphan-test" err &&
+    git config stgit.refnamespace alice &&
+    stg series 2>err &&
+    ! grep -e "orphan-test" err &&
+    git config stgit.refnamespace bob &&
+    uninitialized stg series 2>err &&
+    grep -e "the stack of the missing branch \`orphan-test\` matches this branch" err &&
+    git update-ref -d refs/stacks-ns/bob/orphan-test &&
+    git checkout -q other &&
+    git config stgit.refnamespace alice
+'
+
+test_expect_success 'Invalid namespace' '
+    git config stgit.refnamespace "bad..name" &&
+    command_error stg series 2>err &&
+    grep -e "invalid \`stgit.refnamespace\` value \`bad..name\`" err &&
+    git config stgit.refnamespace "alice/bob" &&
+    command_error stg series 2>err &&
+    grep -e "invalid \`stgit.refnamespace\` value \`alice/bob\`" err
+'
+
+test_done
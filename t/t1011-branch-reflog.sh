@@ -0,0 +1,68 @@
+#!/bin/sh
+
+test_description='Test branch reflog messages written by StGit'
+
+. ./test-lib.sh
+
+last_reflog () {
+    git reflog -n1 --format=%gs refs/heads/master
+}
+
+test_expect_success 'Initialize stack' '
+    stg init
+'
+
+test_expect_success 'New patch' '
+    stg new -m p0 p0 &&
+    test "$(last_reflog)" = "stgit: new p0"
+'
+
+test_expect_success 'Edit patch' '
+    echo foo >foo.txt &&
+    stg add foo.txt &&
+    stg refresh &&
+    stg edit -m "p0 edited" &&
+    test "$(last_reflog)" = "stgit: edit p0"
+'
+
+test_expect_success 'Pop and push several patches' '
+    stg new -m p1 p1 &&
+    stg new -m p2 p2 &&
+    stg pop -n 2 &&
+    test "$(last_reflog)" = "stgit: pop p1 p2" &&
+    stg push -a &&
+    test "$(last_reflog)" = "stgit: push p1 p2"
+'
+
+test_expect_success 'Delete patch' '
+    stg delete --top &&
+    test "$(last_reflog)" = "stgit: delete p2"
+'
+
+test_expect_success 'Push with conflict' '
+    stg new -m conflicting conflicting &&
+    echo bar >foo.txt &&
+    stg refresh &&
+    stg pop conflicting &&
+    stg goto p0 &&
+    echo baz >foo.txt &&
+    stg refresh &&
+    conflict stg push conflicting &&
+    test "$(last_reflog)" = "stgit: push conflicting (conflict)" &&
+    stg undo --hard
+'
+
+test_expect_success 'Custom reflog template' '
+    test_config stgit.reflog-template "StGit %%%o%%: %m [%p]" &&
+    stg new -m p3 p3 &&
+    test "$(last_reflog)" = "StGit %new%: new: p3 [p3]"
+'
+
+test_expect_success 'Invalid reflog template' '
+    test_config stgit.reflog-template "stgit: %x" &&
+    command_error stg new -m p4 p4 2>err &&
+    grep -e "unknown placeholder \`%x\` in \`stgit.reflog-template\`" err &&
+    test "$(stg top)" = "p3"
+'
+
+test_done
@@ -0,0 +1,84 @@
+#!/bin/sh
+
+test_description='Test "stg switch"'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize branches with stacks' '
+    test_commit base &&
+    git branch other &&
+    stg init &&
+    stg new -m p1 &&
+    echo p1 >p1.txt &&
+    stg add p1.txt &&
+    stg refresh &&
+    stg new -m p2 &&
+    echo p2 >p2.txt &&
+    stg add p2.txt &&
+    stg refresh &&
+    stg new -m p3 &&
+    stg pop p3 &&
+    git checkout other &&
+    stg init &&
+    stg new -m o1 &&
+    git checkout master
+'
+
+test_expect_success 'Switch requires a different branch' '
+    command_error stg switch master 2>err &&
+    grep "master is already the current branch" err
+'
+
+test_expect_success 'Switch requires clean worktree' '
+    echo dirty >>base.t &&
+    command_error stg switch other 2>err &&
+    test_when_finished "git checkout base.t" &&
+    test "$(git symbolic-ref --short HEAD)" = "master"
+'
+
+test_expect_success 'Switch refuses inconsistent target stack' '
+    git update-ref refs/heads/other refs/heads/other~1 &&
+    test_when_finished "git update-ref refs/heads/other $(stg id other:o1)" &&
+    command_error stg switch other 2>err &&
+    grep "stack on branch \`other\` is inconsistent: head-mismatch" err &&
+    test "$(git symbolic-ref --short HEAD)" = "master" &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2"
+'
+
+test_expect_success 'Switch without popping' '
+    stg switch other &&
+    test "$(git symbolic-ref --short HEAD)" = "other" &&
+    test "$(stg top)" = "o1" &&
+    stg switch master &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2"
+'
+
+test_expect_success 'Switch with --pop-all remembers applied patches' '
+    stg switch --pop-all other &&
+    test "$(git symbolic-ref --short HEAD)" = "other" &&
+    test "$(echo $(stg series -b master --applied --noprefix))" = "" &&
+    test "$(git config branch.master.stgit.switch-applied)" = "p1 p2" &&
+    stg switch master &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p3" &&
+    test_must_fail git config branch.master.stgit.switch-applied
+'
+
+test_expect_success 'Switch with --no-restore keeps remembered patches' '
+    stg switch -a other &&
+    stg switch --no-restore master &&
+    test "$(echo $(stg series --applied --noprefix))" = "" &&
+    test "$(git config branch.master.stgit.switch-applied)" = "p1 p2" &&
+    stg switch other &&
+    stg switch master &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2"
+'
+
+test_expect_success 'Switch only pushes remembered patches still unapplied' '
+    stg switch -a other &&
+    stg delete -b master p2 &&
+    stg switch master &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1"
+'
+
+test_done
@@ -0,0 +1,62 @@
+#!/bin/sh
+
+test_description='Test read-only mode'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize the StGit repository' '
+    test_commit base &&
+    stg init &&
+    stg new -m p1 &&
+    echo p1 >p1.txt &&
+    stg add p1.txt &&
+    stg refresh
+'
+
+test_expect_success 'Mutating commands are refused with STG_READONLY' '
+    STG_READONLY=1 command_error stg new -m p2 2>err &&
+    grep "\`stg new\` may modify the repository and is not allowed in read-only mode" err &&
+    grep "read-only mode is enabled by \`STG_READONLY\`" err &&
+    test "$(stg series --noprefix)" = "p1" &&
+    STG_READONLY=1 command_error stg pop &&
+    test "$(stg top)" = "p1"
+'
+
+test_expect_success 'Inspection commands work with STG_READONLY' '
+    STG_READONLY=1 stg series &&
+    STG_READONLY=1 stg show p1 &&
+    STG_READONLY=1 stg top &&
+    STG_READONLY=1 stg branch --list &&
+    STG_READONLY=1 stg log
+'
+
+test_expect_success 'Recording options of inspection commands are refused' '
+    STG_READONLY=1 command_error stg log --clear 2>err &&
+    grep "\`stg log\` may modify the repository" err
+'
+
+test_expect_success 'Creating patches with series init is refused' '
+    printf "p2\np3\n" >template &&
+    STG_READONLY=1 command_error stg series init --from-template template 2>err &&
+    grep "\`stg series\` may modify the repository" err &&
+    test "$(stg series --noprefix)" = "p1" &&
+    STG_READONLY=1 stg series --noprefix
+'
+
+test_expect_success 'STG_READONLY set to 0 or false disables read-only mode' '
+    STG_READONLY=0 stg new -m p2 &&
+    STG_READONLY=false stg delete p2 &&
+    STG_READONLY= stg new -m p2 &&
+    stg delete p2
+'
+
+test_expect_success 'Mutating commands are refused with stgit.readonly' '
+    test_config stgit.readonly true &&
+    command_error stg new -m p2 2>err &&
+    grep "read-only mode is enabled by \`stgit.readonly\`" err &&
+    stg series &&
+    STG_READONLY=0 stg new -m p2 &&
+    STG_READONLY=0 stg delete p2
+'
+
+test_done
@@ -25,7 +25,15 @@ test_expect_success 'Create the second patch' '
 '
 
 test_expect_success 'Push the first patch with conflict' '
-    conflict stg push foo
+    conflict stg push foo >out &&
+    cat >expected <<-\EOF &&
+	> foo (conflict)
+	1 conflicting file:
+	  test (changed by bar)
+	hint: `foo` conflicts with changes introduced by `bar`; after `stg undo --hard`, consider `stg sink foo --to bar` or `stg squash bar foo`
+	AA test
+	EOF
+    test_cmp expected out
 '
 
 test_expect_success 'Show the, now empty, first patch' '
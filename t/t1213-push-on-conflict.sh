@@ -0,0 +1,84 @@
+#!/bin/sh
+
+test_description='Test stgit.push.onConflict'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup patches' '
+    printf "hello\n" >foo.txt &&
+    stg add foo.txt &&
+    stg new -rm hello &&
+    printf "hello\n\n\ngoodbye" >foo.txt &&
+    stg new -rm goodbye &&
+    printf "hello\naaa\n\ngoodbye" >foo.txt &&
+    stg new -rm a-patch &&
+    stg pop &&
+    printf "hello\nbbb\n\ngoodbye" >foo.txt &&
+    stg new -rm b-patch &&
+    stg pop &&
+    echo "other" >other.txt &&
+    stg add other.txt &&
+    stg new -rm c-patch &&
+    stg pop
+'
+
+test_expect_success 'Stop on conflict' '
+    test_config stgit.push.onConflict stop &&
+    conflict stg push a-patch b-patch 2>err &&
+    grep "error: merge conflicts." err &&
+    test "$(echo $(stg series --no-prefix --applied))" = "hello goodbye a-patch b-patch" &&
+    stg undo --hard
+'
+
+test_expect_success 'Undo on conflict' '
+    test_config stgit.push.onConflict undo &&
+    command_error stg push a-patch b-patch c-patch >out 2>err &&
+    grep "error: pushing patch \`b-patch\` would result in conflicts" err &&
+    grep "command aborted (all changes rolled back)" err &&
+    grep "@ goodbye (rolled back)" out &&
+    test "$(echo $(stg series --no-prefix --applied))" = "hello goodbye" &&
+    test "$(echo $(stg series --no-prefix --unapplied))" = "c-patch b-patch a-patch" &&
+    test "$(stg log -n 1 | grep -c push)" = "0" &&
+    test_cmp_rev HEAD "$(stg id goodbye)" &&
+    test -z "$(git status --porcelain -uno)"
+'
+
+test_expect_success 'Leave conflicting patch unapplied' '
+    test_config stgit.push.onConflict leave-unapplied &&
+    stg push a-patch b-patch c-patch >out &&
+    cat >expected <<-\EOF &&
+	+ a-patch
+	- b-patch (conflict, left unapplied)
+	> c-patch
+	Left 1 conflicting patch unapplied: b-patch
+	EOF
+    test_cmp expected out &&
+    test "$(echo $(stg series --no-prefix --applied))" = "hello goodbye a-patch c-patch" &&
+    test "$(echo $(stg series --no-prefix --unapplied))" = "b-patch" &&
+    test -z "$(git status --porcelain -uno)" &&
+    stg undo --hard
+'
+
+test_expect_success 'Leave conflicting last patch unapplied' '
+    test_config stgit.push.onConflict leave-unapplied &&
+    stg push c-patch a-patch b-patch >out &&
+    cat >expected <<-\EOF &&
+	+ c-patch
+	+ a-patch
+	- b-patch (conflict, left unapplied)
+	Left 1 conflicting patch unapplied: b-patch
+	> a-patch
+	EOF
+    test_cmp expected out &&
+    test "$(stg top)" = "a-patch" &&
+    stg undo --hard
+'
+
+test_expect_success 'Invalid onConflict value' '
+    test_config stgit.push.onConflict bogus &&
+    command_error stg push a-patch 2>err &&
+    grep "invalid \`stgit.push.onConflict\` value \`bogus\`" err &&
+    test "$(echo $(stg series --no-prefix --applied))" = "hello goodbye"
+'
+
+test_done
@@ -0,0 +1,67 @@
+#!/bin/sh
+
+test_description='Test stgit.updateRefs dependent branch updating'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup patches and dependent branches' '
+    test_commit_bulk --message="patch %s" --filename=file-%s.txt --contents="line %s" 3 &&
+    stg uncommit -n 3 &&
+    git branch dep-1 "$(stg id patch-1)" &&
+    git branch dep-2 "$(stg id patch-2)" &&
+    git branch other "$(stg id {base})"
+'
+
+test_expect_success 'Branches are not updated by default' '
+    old_dep_2=$(git rev-parse dep-2) &&
+    stg edit -m "patch 1 edited" patch-1 >out &&
+    ! grep "Updated branch" out &&
+    test_cmp_rev dep-2 $old_dep_2 &&
+    test "$(git rev-parse dep-2)" != "$(stg id patch-2)" &&
+    stg undo &&
+    test_cmp_rev dep-2 "$(stg id patch-2)"
+'
+
+test_expect_success 'Branches follow rewritten patches' '
+    git config stgit.updateRefs true &&
+    stg edit -m "patch 1 edited" patch-1 >out &&
+    grep "Updated branch dep-1 to patch patch-1" out &&
+    grep "Updated branch dep-2 to patch patch-2" out &&
+    test_cmp_rev dep-1 "$(stg id patch-1)" &&
+    test_cmp_rev dep-2 "$(stg id patch-2)" &&
+    test_cmp_rev other "$(stg id {base})" &&
+    git reflog -n 1 dep-2 | grep "edit"
+'
+
+test_expect_success 'Popped patches keep their dependent branches' '
+    stg pop patch-2 patch-3 >out &&
+    ! grep "Updated branch" out &&
+    test_cmp_rev dep-2 "$(stg id patch-2)"
+'
+
+test_expect_success 'Branches follow reordered patches' '
+    stg push patch-3 patch-2 >out &&
+    grep "Updated branch dep-2 to patch patch-2" out &&
+    test_cmp_rev dep-2 "$(stg id patch-2)" &&
+    test_cmp_rev dep-2^ "$(stg id patch-3)"
+'
+
+test_expect_success 'Branches follow refreshed patch' '
+    echo "more" >>file-2.txt &&
+    stg refresh >out &&
+    grep "Updated branch dep-2 to patch patch-2" out &&
+    test_cmp_rev dep-2 "$(stg id patch-2)"
+'
+
+test_expect_success 'Branches with stacks are not dependent branches' '
+    stg branch --clone clone &&
+    echo "even more" >>file-2.txt &&
+    stg refresh >out &&
+    grep "Updated branch dep-2 to patch patch-2" out &&
+    ! grep "Updated branch clone" out &&
+    ! grep "Updated branch master" out &&
+    test_cmp_rev dep-2 "$(stg id patch-2)" &&
+    test_cmp_rev master "$(stg id master:patch-2)"
+'
+
+test_done
@@ -0,0 +1,87 @@
+#!/bin/sh
+
+test_description='Test stg resolve'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup conflicting patches' '
+    echo base >test &&
+    echo other >test2 &&
+    git add test test2 &&
+    git commit -m base &&
+    stg init &&
+    stg new foo -m foo &&
+    echo foo >test &&
+    echo foo >test2 &&
+    stg refresh &&
+    stg pop &&
+    stg new bar -m bar &&
+    echo bar >test &&
+    echo bar >test2 &&
+    stg refresh &&
+    conflict stg push foo
+'
+
+test_expect_success 'Resolve requires paths' '
+    command_error stg resolve 2>err &&
+    grep -e "no files specified" err
+'
+
+test_expect_success 'Resolve refuses non-conflicted path' '
+    echo untracked >other &&
+    command_error stg resolve other 2>err &&
+    grep -e "\`other\` is not in conflict" err
+'
+
+test_expect_success 'Resolve refuses file with conflict markers' '
+    command_error stg resolve test 2>err &&
+    grep -e "\`test\` still contains conflict markers" err &&
+    git ls-files --unmerged -- test | grep -e "test"
+'
+
+test_expect_success 'Show conflict stages' '
+    echo n | stg resolve --show test >out &&
+    cat >expected <<-\EOF &&
+	Conflicts in `test`
+	--- base
+	base
+	--- ours
+	bar
+	--- theirs
+	foo
+	Mark `test` resolved? [y/N/q] 
+	EOF
+    test_cmp expected out &&
+    git ls-files --unmerged -- test | grep -e "test"
+'
+
+test_expect_success 'Show conflict stages side-by-side' '
+    echo q | stg resolve --show --side-by-side >out &&
+    grep -e "^--- ours | theirs\$" out &&
+    grep -e "^bar *| foo\$" out &&
+    ! grep -e "Conflicts in .test2." out
+'
+
+test_expect_success 'Mark resolved from show prompt' '
+    echo resolved >test2 &&
+    printf "n\ny\n" | stg resolve --show >out 2>err &&
+    grep -e "Conflicts in .test2." out &&
+    grep -e "\`test2\` resolved" err &&
+    git ls-files --unmerged -- test | grep -e "test" &&
+    test -z "$(git ls-files --unmerged -- test2)"
+'
+
+test_expect_success 'Show prompt respects conflict markers' '
+    echo y | stg resolve --show test 2>err &&
+    grep -e "\`test\` still contains conflict markers; not resolved" err &&
+    git ls-files --unmerged -- test | grep -e "test"
+'
+
+test_expect_success 'Force resolve file with conflict markers' '
+    stg resolve --force test &&
+    test -z "$(git ls-files --unmerged)" &&
+    stg resolve --all 2>err &&
+    grep -e "no conflicts to resolve" err
+'
+
+test_done
@@ -0,0 +1,78 @@
+#!/bin/sh
+
+test_description='Test stg push --until-conflict'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup patches' '
+    printf "hello\n" >foo.txt &&
+    stg add foo.txt &&
+    stg new -rm hello &&
+    printf "hello\n\n\ngoodbye" >foo.txt &&
+    stg new -rm goodbye &&
+    printf "hello\naaa\n\ngoodbye" >foo.txt &&
+    stg new -rm a-patch &&
+    stg pop &&
+    printf "hello\nbbb\n\ngoodbye" >foo.txt &&
+    stg new -rm b-patch &&
+    stg pop &&
+    echo "other" >other.txt &&
+    stg add other.txt &&
+    stg new -rm c-patch &&
+    stg pop &&
+    stg push --noapply a-patch b-patch c-patch
+'
+
+test_expect_success 'Conflicting options' '
+    general_error stg push --until-conflict --all 2>err &&
+    grep "cannot be used with" err &&
+    general_error stg push --until-conflict --set-tree 2>err &&
+    grep "cannot be used with" err
+'
+
+test_expect_success 'Push until conflict' '
+    stg push --until-conflict >out &&
+    cat >expected <<-\EOF &&
+	+ a-patch
+	- b-patch (conflict, left unapplied)
+	Stopped at conflicting patch b-patch; 1 more patch not pushed
+	> a-patch
+	EOF
+    test_cmp expected out &&
+    test "$(echo $(stg series --no-prefix --applied))" = "hello goodbye a-patch" &&
+    test "$(echo $(stg series --no-prefix --unapplied))" = "b-patch c-patch" &&
+    test -z "$(git status --porcelain -uno)" &&
+    stg undo --hard
+'
+
+test_expect_success 'Push until conflict ignores onConflict config' '
+    test_config stgit.push.onConflict undo &&
+    stg push --until-conflict &&
+    test "$(echo $(stg series --no-prefix --applied))" = "hello goodbye a-patch" &&
+    stg undo --hard
+'
+
+test_expect_success 'Push until stop patch without conflict' '
+    stg push --until-conflict ..a-patch >out &&
+    cat >expected <<-\EOF &&
+	> a-patch
+	EOF
+    test_cmp expected out &&
+    test "$(echo $(stg series --no-prefix --unapplied))" = "b-patch c-patch" &&
+    stg undo --hard
+'
+
+test_expect_success 'Push until conflict with first patch conflicting' '
+    stg push a-patch &&
+    stg push --until-conflict >out &&
+    cat >expected <<-\EOF &&
+	- b-patch (conflict, left unapplied)
+	Stopped at conflicting patch b-patch; 1 more patch not pushed
+	> a-patch
+	EOF
+    test_cmp expected out &&
+    test "$(echo $(stg series --no-prefix --unapplied))" = "b-patch c-patch" &&
+    test -z "$(git status --porcelain -uno)"
+'
+
+test_done
@@ -130,4 +130,76 @@ test_expect_success 'Uncommit to an annotated tag' '
     stg uncommit --to testtag
 '
 
+test_expect_success 'Setup clone with upstream' '
+    git clone . clone &&
+    (
+        cd clone &&
+        test_commit_bulk --message="local%s" --filename="local%s.t" 2
+    )
+'
+
+test_expect_success 'Uncommit refuses commits already in upstream' '
+    (
+        cd clone &&
+        stg uncommit -n 2 &&
+        stg commit --all &&
+        command_error stg uncommit -n 3 2>err &&
+        grep -e "error: cannot uncommit \`$(git rev-parse HEAD~2)\` which is already in upstream \`origin/master\`" err &&
+        test "$(echo $(stg series --noprefix))" = "" &&
+        command_error stg uncommit --to HEAD~2 &&
+        test "$(echo $(stg series --noprefix))" = ""
+    )
+'
+
+test_expect_success 'Uncommit published commits with --force' '
+    (
+        cd clone &&
+        stg uncommit --force -n 3 &&
+        test "$(echo $(stg series --noprefix))" = "bar-patch local1 local2" &&
+        stg commit --all
+    )
+'
+
+test_expect_success 'Interactive uncommit lists commits with upstream status' '
+    (
+        cd clone &&
+        echo 2 | stg uncommit -i --color=never >out &&
+        cat >expected <<-EOF &&
+	Branch \`master\` is 2 ahead of and 0 behind upstream \`origin/master\`
+	1 $(git rev-parse --short=7 HEAD) local    local2
+	2 $(git rev-parse --short=7 HEAD~1) local    local1
+	3 $(git rev-parse --short=7 HEAD~2) upstream Bar Patch
+	4 $(git rev-parse --short=7 HEAD~3) upstream Foo Patch
+	5 $(git rev-parse --short=7 HEAD~4) upstream commit 1
+	Uncommit how many commits? [0-2] 
+	> local2
+	EOF
+        test_cmp expected out &&
+        test "$(echo $(stg series --noprefix))" = "local1 local2" &&
+        stg commit --all
+    )
+'
+
+test_expect_success 'Interactive uncommit of nothing' '
+    (
+        cd clone &&
+        stg uncommit -i </dev/null &&
+        echo 0 | stg uncommit -i &&
+        test "$(echo $(stg series --noprefix))" = ""
+    )
+'
+
+test_expect_success 'Interactive uncommit refuses published commits' '
+    (
+        cd clone &&
+        echo 3 | command_error stg uncommit -i 2>err &&
+        grep -e "which is already in upstream \`origin/master\`; use \`--force\`" err &&
+        echo 6 | command_error stg uncommit -i 2>err &&
+        grep -e "error: \`6\` is not a number from 0 to 2" err &&
+        echo 3 | stg uncommit -i --force >out &&
+        grep -e "Uncommit how many commits? \[0-5\]" out &&
+        test "$(echo $(stg series --noprefix))" = "bar-patch local1 local2"
+    )
+'
+
 test_done
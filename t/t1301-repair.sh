@@ -7,7 +7,7 @@ test_description='Test the repair command'
 . ./test-lib.sh
 
 test_expect_success 'Repair in a non-initialized repository' '
-    command_error stg repair 2>err &&
+    uninitialized stg repair 2>err &&
     grep -e "StGit stack not initialized for branch \`master\`" err
 '
 
@@ -38,13 +38,104 @@ test_expect_success 'Commit all patches' '
     test "$(echo $(stg series))" = ""
 '
 
+test_expect_success 'Commit with annotated tag' '
+    stg new -m "first patch" p5 &&
+    stg new -m "second patch" p6 &&
+    stg new -m "third patch" p7 &&
+    stg commit --allow-empty -n 2 --annotate=release-1 &&
+    test "$(echo $(stg series))" = "> p7" &&
+    test "$(git cat-file -t release-1)" = "tag" &&
+    test "$(git rev-parse release-1^{commit})" = "$(git rev-parse HEAD~)" &&
+    git cat-file tag release-1 | sed -e "1,/^\$/d" >actual &&
+    cat >expected <<-\EOF &&
+	Commit 2 patches from master
+
+	p5: first patch
+	p6: second patch
+	EOF
+    test_cmp expected actual
+'
+
+test_expect_success 'Commit with tag name from template' '
+    test_config stgit.commit.tag-template "%b/%p-%n-%%" &&
+    stg commit --allow-empty --annotate &&
+    test "$(git rev-parse master/p7-1-%^{commit})" = "$(git rev-parse HEAD)" &&
+    stg new -m p8 &&
+    test_config stgit.commit.tag-template "%b-%x" &&
+    command_error stg commit --allow-empty --annotate 2>err &&
+    grep -e "unknown placeholder .%x. in .stgit.commit.tag-template." err &&
+    command_error stg commit --allow-empty --annotate=release-1 2>err &&
+    grep -e "tag .release-1. already exists" err &&
+    command_error stg commit --allow-empty --annotate="bad..name" 2>err &&
+    grep -e "invalid tag name .bad..name." err &&
+    test "$(echo $(stg series))" = "> p8" &&
+    stg delete p8
+'
+
+test_expect_success 'Commit with tag name from default template' '
+    stg new -m p9 &&
+    test_tick &&
+    stg commit --allow-empty --annotate &&
+    git tag -l "master-*" >actual &&
+    test_line_count = 1 actual
+'
+
+test_expect_success 'Commit squashed patches' '
+    for i in 1 2 3; do
+        stg new -m "s$i" &&
+        echo "s$i" >s$i.txt &&
+        stg add s$i.txt &&
+        stg refresh || return 1
+    done &&
+    base=$(git rev-parse HEAD~3) &&
+    stg commit --squash s1 s3 &&
+    test "$(echo $(stg series))" = "> s2" &&
+    test "$(git rev-parse HEAD~2)" = "$base" &&
+    test_must_fail git rev-parse --verify -q HEAD~1^2 &&
+    git log -1 --format=%B HEAD~1 >actual &&
+    cat >expected <<-\EOF &&
+	Squash 2 patches from master
+
+	s1: s1
+	s3: s3
+
+	EOF
+    test_cmp expected actual &&
+    git show --name-only --format= HEAD~1 >files &&
+    test "$(echo $(cat files))" = "s1.txt s3.txt" &&
+    test_path_is_file s2.txt &&
+    stg delete s2
+'
+
+test_expect_success 'Commit patches with merge commit' '
+    for i in 1 2; do
+        stg new -m "m$i" &&
+        echo "m$i" >m$i.txt &&
+        stg add m$i.txt &&
+        stg refresh || return 1
+    done &&
+    base=$(git rev-parse HEAD~2) &&
+    top=$(stg id m2) &&
+    stg commit --merge --message "Merge topic" --all &&
+    test "$(echo $(stg series))" = "" &&
+    test "$(git rev-parse HEAD^1)" = "$base" &&
+    test "$(git rev-parse HEAD^2)" = "$top" &&
+    test "$(git log -1 --format=%s)" = "Merge topic" &&
+    test "$(git rev-parse HEAD^{tree})" = "$(git rev-parse $top^{tree})"
+'
+
+test_expect_success 'Squash and merge options' '
+    general_error stg commit --squash --merge &&
+    general_error stg commit --message foo
+'
+
 # stg commit with top != head should not succeed, since the committed
 # patches are potentially lost.
 test_expect_success 'Commit when top != head (should fail)' '
     stg new -m foo &&
     git reset --hard HEAD^ &&
     h=$(git rev-parse HEAD)
-    command_error stg commit --allow-empty &&
+    head_top_mismatch stg commit --allow-empty &&
     test "$(git rev-parse HEAD)" = "$h" &&
     test "$(echo $(stg series))" = "> foo"
 '
@@ -0,0 +1,95 @@
+#!/bin/sh
+
+test_description='Test stg verify'
+
+. ./test-lib.sh
+
+# Replace the stack state with one whose stack.json is edited with the given sed
+# arguments.
+edit_stack_json () {
+    git cat-file blob refs/stacks/master:stack.json | sed "$@" >stack.json &&
+    blob=$(git hash-object -w stack.json) &&
+    tree=$(git ls-tree refs/stacks/master |
+           sed -e "s/[0-9a-f]*	stack.json/$blob	stack.json/" |
+           git mktree) &&
+    commit=$(git commit-tree -p refs/stacks/master -m "edited state" $tree) &&
+    git update-ref refs/stacks/master $commit
+}
+
+test_expect_success 'Verify uninitialized branch' '
+    uninitialized stg verify 2>err &&
+    grep -e "StGit stack not initialized for branch \`master\`" err
+'
+
+test_expect_success 'Verify good stack' '
+    test_commit_bulk --message="p%s" 3 &&
+    stg uncommit -n 3 &&
+    stg pop &&
+    stg verify >out &&
+    test_must_be_empty out
+'
+
+test_expect_success 'Verify stack with head mismatch' '
+    git commit --allow-empty -m extra &&
+    command_error stg verify >out 2>err &&
+    grep -e "^master: head-mismatch: stack head [0-9a-f]* is not branch head [0-9a-f]*$" out &&
+    test_line_count = 1 out &&
+    grep -e "found 1 stack invariant violation$" err &&
+    git reset --hard HEAD~ &&
+    stg verify
+'
+
+test_expect_success 'Verify stack with bad patch refs' '
+    p2_id=$(stg id p2) &&
+    p3_id=$(stg id p3) &&
+    git update-ref -d refs/patches/master/p1 &&
+    git update-ref refs/patches/master/p2 $p3_id &&
+    git update-ref refs/patches/master/bogus HEAD &&
+    command_error stg verify >out 2>err &&
+    cat >expected <<-EOF &&
+	master: patch-ref-missing: patch \`p1\` does not have a patch reference
+	master: patch-ref-mismatch: patch reference for \`p2\` does not point to $p2_id
+	master: patch-ref-stray: patch reference \`refs/patches/master/bogus\` has no patch
+	EOF
+    test_cmp expected out &&
+    grep -e "found 3 stack invariant violations" err
+'
+
+test_expect_success 'Other commands repair patch refs' '
+    stg series >/dev/null &&
+    stg verify
+'
+
+test_expect_success 'Verify stack with misordered applied patches' '
+    edit_stack_json -e "s/\"p1\",/\"tmp\",/" -e "s/^    \"p2\"$/    \"p1\"/" -e "s/\"tmp\",/\"p2\",/" &&
+    command_error stg verify --branch master >out &&
+    grep -e "^master: applied-parent: parent of patch \`p1\` is [0-9a-f]*, not patch \`p2\` ([0-9a-f]*)$" out &&
+    grep -e "^master: state-head: stack head [0-9a-f]* is not topmost applied patch \`p1\` ([0-9a-f]*)$" out &&
+    test_line_count = 2 out &&
+    git update-ref refs/stacks/master refs/stacks/master^ &&
+    stg verify
+'
+
+test_expect_success 'Verify stack with inconsistent patch lists' '
+    edit_stack_json -e "s/\"unapplied\": \[/\"unapplied\": [\"p2\", \"p4\",/" &&
+    command_error stg verify >out &&
+    cat >expected <<-\EOF &&
+	master: patch-duplicate: patch `p2` is listed 2 times
+	master: patch-unknown: patch `p4` is listed without a recorded commit
+	EOF
+    test_cmp expected out &&
+    git update-ref refs/stacks/master refs/stacks/master^ &&
+    stg verify
+'
+
+test_expect_success 'Verify all branches' '
+    stg branch --create other &&
+    stg new -m other-patch &&
+    git commit --allow-empty -m extra &&
+    git branch plain master &&
+    command_error stg verify --all >out &&
+    grep -e "^other: head-mismatch" out &&
+    test_line_count = 1 out
+'
+
+test_done
@@ -0,0 +1,56 @@
+#!/bin/sh
+
+test_description='Test sink --noapply'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize the StGit repository' '
+    test_commit_bulk --message="p%s" 5 &&
+    stg uncommit -n 5 &&
+    stg pop -n 3 &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p3 p4 p5"
+'
+
+test_expect_success 'Sink single unapplied patch' '
+    stg sink --noapply p5 &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p5 p3 p4"
+'
+
+test_expect_success 'Sink unapplied patches below unapplied target' '
+    stg sink --noapply --to p4 p5 p3 &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p5 p3 p4"
+'
+
+test_expect_success 'Sink with unclean worktree' '
+    echo "foobar" >1.t &&
+    test_when_finished git checkout 1.t &&
+    stg sink --noapply --to p5 p4 &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p4 p5 p3" &&
+    test "$(stg status 1.t)" = " M 1.t"
+'
+
+test_expect_success 'Sink applied patch with --noapply' '
+    command_error stg sink --noapply p3 p2 2>err &&
+    grep -e "cannot sink applied patch \`p2\` with --noapply" err &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p4 p5 p3"
+'
+
+test_expect_success 'Sink below applied target with --noapply' '
+    command_error stg sink --noapply --to p2 p3 2>err &&
+    grep -e "cannot sink below applied \`p2\` with --noapply" err
+'
+
+test_expect_success 'Sink with --noapply requires patches' '
+    command_error stg sink --noapply 2>err &&
+    grep -e "patches to sink must be specified with --noapply" err
+'
+
+test_expect_success 'Sink with --noapply and --nopush' '
+    general_error stg sink --noapply --nopush p3 2>err &&
+    grep -e "cannot be used with" err
+'
+
+test_done
@@ -0,0 +1,131 @@
+#!/bin/sh
+
+test_description='Test "stg apply-plan"'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize StGit stack' '
+    test_commit_bulk --message="p%s" 5 &&
+    stg uncommit -n 5 &&
+    stg pop p5 &&
+    stg hide p5
+'
+
+test_expect_success 'Plan matching the stack makes no changes' '
+    cat >plan.json <<-\EOF &&
+	{"applied": ["p1", "p2", "p3", "p4"], "hidden": ["p5"]}
+	EOF
+    stg apply-plan plan.json 2>err &&
+    grep "stack already matches plan" err
+'
+
+test_expect_success 'Attempt plan with missing patch' '
+    cat >plan.json <<-\EOF &&
+	{"applied": ["p1", "p2", "p3", "p4"]}
+	EOF
+    command_error stg apply-plan plan.json 2>err &&
+    grep "patch \`p5\` is missing from plan" err
+'
+
+test_expect_success 'Attempt plan with unknown patch' '
+    cat >plan.json <<-\EOF &&
+	{"applied": ["p1", "p2", "p3", "p4", "p6"], "hidden": ["p5"]}
+	EOF
+    command_error stg apply-plan plan.json 2>err &&
+    grep "patch \`p6\` does not exist" err
+'
+
+test_expect_success 'Attempt plan with duplicate patch' '
+    cat >plan.json <<-\EOF &&
+	{"applied": ["p1", "p2", "p3", "p4"], "unapplied": ["p1"], "hidden": ["p5"]}
+	EOF
+    command_error stg apply-plan plan.json 2>err &&
+    grep "patch \`p1\` appears more than once in plan" err
+'
+
+test_expect_success 'Attempt plan with colliding new names' '
+    cat >plan.json <<-\EOF &&
+	{"applied": ["p1", {"patch": "p2", "name": "p3"}, "p3", "p4"], "hidden": ["p5"]}
+	EOF
+    command_error stg apply-plan plan.json 2>err &&
+    grep "plan names both \`p2\` and \`p3\` as \`p3\`" err
+'
+
+test_expect_success 'Attempt plan with rename cycle' '
+    cat >plan.json <<-\EOF &&
+	{
+	  "applied": [
+	    "p1", {"patch": "p2", "name": "p3"}, {"patch": "p3", "name": "p2"}, "p4"
+	  ],
+	  "hidden": ["p5"]
+	}
+	EOF
+    command_error stg apply-plan plan.json 2>err &&
+    grep "plan renames \`p2\` in a cycle" err
+'
+
+test_expect_success 'Attempt plan with invalid JSON' '
+    echo "{\"applied\": 1}" >plan.json &&
+    command_error stg apply-plan plan.json 2>err &&
+    grep "parsing plan" err
+'
+
+test_expect_success 'Reorder, hide, and unhide patches' '
+    cat >plan.json <<-\EOF &&
+	{"applied": ["p1", "p3", "p5"], "unapplied": ["p2"], "hidden": ["p4"]}
+	EOF
+    stg apply-plan plan.json &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p3 p5" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p2" &&
+    test "$(echo $(stg series --hidden --noprefix))" = "p4" &&
+    stg log -n 1 | grep "apply-plan"
+'
+
+test_expect_success 'Undo plan' '
+    stg undo &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2 p3 p4" &&
+    test "$(echo $(stg series --hidden --noprefix))" = "p5"
+'
+
+test_expect_success 'Rename patches and change messages' '
+    cat >plan.json <<-\EOF &&
+	{
+	  "applied": [
+	    "p1",
+	    {"patch": "p3", "name": "p2", "message": "new p3 subject\n\nbody\n"},
+	    {"patch": "p2", "name": "p3b"},
+	    "p4"
+	  ],
+	  "unapplied": [{"patch": "p5", "name": "p6", "message": "new p5 subject"}]
+	}
+	EOF
+    stg apply-plan plan.json 2>err &&
+    test_line_count = 0 err &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2 p3b p4" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p6" &&
+    test "$(stg series --hidden --noprefix)" = "" &&
+    test "$(git log -1 --format=%B $(stg id p2))" = "$(printf "new p3 subject\n\nbody")" &&
+    test "$(git log -1 --format=%s $(stg id p6))" = "new p5 subject" &&
+    test "$(git log -1 --format=%s $(stg id p3b))" = "p2" &&
+    test "$(stg id p2)" = "$(git rev-parse "$(stg id p3b)^")" &&
+    stg undo
+'
+
+test_expect_success 'Read plan from stdin' '
+    echo "{\"applied\": [\"p1\", \"p2\"], \"unapplied\": [\"p3\", \"p4\"], \"hidden\": [\"p5\"]}" |
+    stg apply-plan - &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p3 p4"
+'
+
+test_expect_success 'Attempt to reorder with dirty worktree' '
+    echo dirty >>1.t &&
+    cat >plan.json <<-\EOF &&
+	{"applied": ["p2", "p1"], "unapplied": ["p3", "p4"], "hidden": ["p5"]}
+	EOF
+    command_error stg apply-plan plan.json 2>err &&
+    grep "worktree not clean" err &&
+    git checkout 1.t
+'
+
+test_done
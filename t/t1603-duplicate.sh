@@ -0,0 +1,82 @@
+#!/bin/sh
+
+test_description='Test stg duplicate'
+
+. ./test-lib.sh
+
+test_expect_success 'Duplicate without patches' '
+    stg init &&
+    command_error stg duplicate 2>err &&
+    grep -e "no patches applied" err
+'
+
+test_expect_success 'Setup patches' '
+    test_commit_bulk --message="p%s" 4 &&
+    stg uncommit -n 4 &&
+    stg pop p3 p4
+'
+
+test_expect_success 'Duplicate top patch' '
+    stg duplicate &&
+    test "$(echo $(stg series --noprefix))" = "p1 p2 p2-copy p3 p4" &&
+    test "$(stg id p2^{tree})" = "$(stg id p2-copy^{tree})" &&
+    test "$(git log -1 --format=%B $(stg id p2))" = "$(git log -1 --format=%B $(stg id p2-copy))" &&
+    test "$(git log -1 --format=%an $(stg id p2))" = "$(git log -1 --format=%an $(stg id p2-copy))" &&
+    test "$(stg id p2^)" = "$(stg id p2-copy^)"
+'
+
+test_expect_success 'Duplicate unapplied patch with name' '
+    stg duplicate --name p3-alt p3 &&
+    test "$(echo $(stg series --noprefix --unapplied))" = "p2-copy p3 p3-alt p4"
+'
+
+test_expect_success 'Duplicate to existing name' '
+    command_error stg duplicate --name p4 p3 2>err &&
+    grep -e "patch \`p4\` already exists" err
+'
+
+test_expect_success 'Duplicate non-existing patch' '
+    command_error stg duplicate p9 2>err &&
+    grep -e "patch \`p9\` does not exist" err
+'
+
+test_expect_success 'Duplicate after applied target' '
+    stg delete p2-copy p3-alt &&
+    stg pop &&
+    stg duplicate --to p1 p2 &&
+    test "$(echo $(stg series --noprefix --applied))" = "p1 p2-copy" &&
+    test "$(echo $(stg series --noprefix --unapplied))" = "p2 p3 p4" &&
+    test "$(stg id p2-copy^)" = "$(stg id p1)"
+'
+
+test_expect_success 'Duplicate after unapplied target' '
+    stg duplicate --to p3 p1 &&
+    test "$(echo $(stg series --noprefix --unapplied))" = "p2 p3 p1-copy p4"
+'
+
+test_expect_success 'Swap applied patch' '
+    stg delete p1-copy p2-copy &&
+    stg push p2 &&
+    stg duplicate --swap p1 &&
+    test "$(echo $(stg series --noprefix --applied))" = "p1-copy p2" &&
+    test "$(echo $(stg series --noprefix --hidden))" = "p1" &&
+    test "$(stg id p2^)" = "$(stg id p1-copy)" &&
+    test "$(stg id p1^{tree})" = "$(stg id p1-copy^{tree})"
+'
+
+test_expect_success 'Swap unapplied patch' '
+    stg duplicate --swap --name p4-alt p4 &&
+    test "$(echo $(stg series --noprefix --unapplied))" = "p3 p4-alt" &&
+    test "$(echo $(stg series --noprefix --hidden))" = "p1 p4"
+'
+
+test_expect_success 'Swap hidden patch' '
+    command_error stg duplicate --swap p1 2>err &&
+    grep -e "cannot swap hidden patch \`p1\`" err
+'
+
+test_expect_success 'Swap conflicts with target' '
+    general_error stg duplicate --swap --to p2 p3
+'
+
+test_done
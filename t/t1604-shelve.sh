@@ -0,0 +1,69 @@
+#!/bin/sh
+
+test_description='Test stg shelve and stg unshelve'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize the StGit repository' '
+    test_commit_bulk --message="p%s" 5 &&
+    stg uncommit -n 5 &&
+    stg pop p4 &&
+    test "$(echo $(stg series))" = "+ p1 + p2 + p3 > p5 - p4"
+'
+
+test_expect_success 'Shelve patches' '
+    stg shelve wip p2 p4 &&
+    test "$(echo $(stg series))" = "+ p1 + p3 > p5" &&
+    git rev-parse --verify refs/shelves/wip &&
+    stg shelve --list >out &&
+    echo "wip (master): p2 p4" >expected &&
+    test_cmp expected out &&
+    test_path_is_missing 2.t
+'
+
+test_expect_success 'Attempt to reuse shelf name' '
+    command_error stg shelve wip p1 2>err &&
+    grep -e "shelf .wip. already exists" err &&
+    test "$(echo $(stg series))" = "+ p1 + p3 > p5"
+'
+
+test_expect_success 'Attempt invalid shelf names' '
+    command_error stg shelve "bad..name" p1 2>err &&
+    grep -e "invalid shelf name .bad..name." err &&
+    command_error stg unshelve missing 2>err &&
+    grep -e "shelf .missing. not found" err
+'
+
+test_expect_success 'Unshelve patches' '
+    stg unshelve wip &&
+    test "$(echo $(stg series))" = "+ p1 + p3 + p5 + p2 > p4" &&
+    test_must_fail git rev-parse --verify refs/shelves/wip &&
+    test_path_is_file 2.t &&
+    test_path_is_file 4.t
+'
+
+test_expect_success 'Unshelve onto another branch as unapplied' '
+    stg shelve later p5 p4 &&
+    test "$(echo $(stg series))" = "+ p1 + p3 > p2" &&
+    stg branch --create other &&
+    stg unshelve --noapply --keep later &&
+    test "$(echo $(stg series))" = "- p5 - p4" &&
+    git rev-parse --verify refs/shelves/later &&
+    stg push -a &&
+    test "$(echo $(stg series))" = "+ p5 > p4" &&
+    stg branch master
+'
+
+test_expect_success 'Attempt to unshelve over existing patches' '
+    stg new -m p4 p4 &&
+    command_error stg unshelve later 2>err &&
+    grep -e "patch .p4. already exists" err &&
+    git rev-parse --verify refs/shelves/later &&
+    stg delete p4 &&
+    stg unshelve later &&
+    test "$(echo $(stg series))" = "+ p1 + p3 + p2 + p5 > p4" &&
+    stg shelve --list >out &&
+    test_must_be_empty out
+'
+
+test_done
@@ -78,4 +78,47 @@ test_expect_success 'Goto with ambiguous patch substring' '
     grep "patch \`p\` does not exist, but is similar to \`p1\`, \`p2\`" err
 '
 
+test_expect_success 'Goto topmost applied patch touching path' '
+    echo 2 >>file2 &&
+    stg new p6 -m "patch 6" &&
+    stg add file2 &&
+    stg refresh &&
+    stg goto p4 &&
+    stg goto --touching file2 &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p3 p4 p5 p6"
+'
+
+test_expect_success 'Goto next unapplied patch touching path' '
+    stg goto --touching file4 &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2 p3 p4"
+'
+
+test_expect_success 'Goto patch touching any of several paths' '
+    stg goto --touching file2 --touching file5 &&
+    test "$(stg top)" = "p2" &&
+    stg goto --touching file5 --touching file3 &&
+    test "$(stg top)" = "p3"
+'
+
+test_expect_success 'Goto touching from subdirectory' '
+    mkdir sub &&
+    (cd sub && stg goto --touching ../file5) &&
+    test "$(stg top)" = "p5" &&
+    rmdir sub
+'
+
+test_expect_success 'Goto touching path not modified by any patch' '
+    command_error stg goto --touching nonexistent 2>err &&
+    grep -e "no patch modifies \`nonexistent\`" err &&
+    command_error stg goto --touching nonexistent --touching other 2>err &&
+    grep -e "no patch modifies the given paths" err &&
+    test "$(stg top)" = "p5"
+'
+
+test_expect_success 'Goto touching conflicts with patch argument' '
+    general_error stg goto --touching file1 p1 2>err &&
+    grep -e "cannot be used with" err
+'
+
 test_done
@@ -228,6 +228,44 @@ test_expect_success 'Import with root directory' '
     stg delete dirs from-relative
 '
 
+test_expect_success 'Import diff generated outside git with strip level and directory' '
+    mkdir -p vendor/lib/a vendor/lib/b &&
+    echo "one" >vendor/lib/a/file.c &&
+    echo "one" >vendor/lib/b/file.c &&
+    stg add vendor &&
+    stg new -rm "vendor" &&
+    cat >outside.diff <<-\EOF &&
+	--- lib.orig/a/file.c
+	+++ lib/a/file.c
+	@@ -1 +1 @@
+	-one
+	+two
+	--- lib.orig/b/file.c
+	+++ lib/b/file.c
+	@@ -1 +1 @@
+	-one
+	+two
+	EOF
+    stg import --name outside -p1 --directory=vendor/lib outside.diff &&
+    test "$(echo $(stg files outside))" = "M vendor/lib/a/file.c M vendor/lib/b/file.c" &&
+    stg delete outside
+'
+
+test_expect_success 'Import diff limited to paths with include and exclude' '
+    stg import --name excluded -p1 --directory=vendor/lib \
+        --exclude-path="vendor/lib/b/*" outside.diff &&
+    test "$(echo $(stg files excluded))" = "M vendor/lib/a/file.c" &&
+    stg delete excluded &&
+    stg import --name included -p1 --directory=vendor/lib \
+        --include-path="vendor/lib/b/*" --exclude-path="*" outside.diff &&
+    test "$(echo $(stg files included))" = "M vendor/lib/b/file.c" &&
+    stg delete included &&
+    stg import --name first-match -p1 --directory=vendor/lib \
+        --exclude-path="*" --include-path="vendor/lib/b/*" outside.diff &&
+    test -z "$(stg files first-match)" &&
+    stg delete first-match vendor
+'
+
 test_expect_success 'Import series from stdin' '
     echo "some.patch" |
     stg import --series &&
@@ -148,6 +148,40 @@ test_expect_success 'Import patches from mbox with duplicate subjects' '
     stg delete ..
 '
 
+test_expect_success 'Add Link trailers when importing from mbox' '
+    stg import -M --link "$TEST_DIRECTORY"/t1801/email-mbox &&
+    git cat-file commit $(stg id change-1) | tail -n 1 >out &&
+    echo "Link: https://lore.kernel.org/all/20061111114527.31778.12942.stgit@localhost" >expected &&
+    test_cmp expected out &&
+    git cat-file commit $(stg id change-3-colon) | tail -n 1 >out &&
+    echo "Link: https://lore.kernel.org/all/20061111114527.31778.45876.stgit@localhost" >expected &&
+    test_cmp expected out &&
+    stg delete ..
+'
+
+test_expect_success 'Add Link trailers with given archive URL' '
+    stg import -M --link=https://example.com/r/ "$TEST_DIRECTORY"/t1801/email-mbox &&
+    git cat-file commit $(stg id change-2) | tail -n 1 >out &&
+    echo "Link: https://example.com/r/20061111114527.31778.92851.stgit@localhost" >expected &&
+    test_cmp expected out &&
+    stg delete ..
+'
+
+test_expect_success 'Add Message-Id and Link trailers from config' '
+    test_config stgit.import.message-id true &&
+    test_config stgit.import.link true &&
+    test_config stgit.email.inbox-url https://inbox.example.com/list &&
+    stg import -M "$TEST_DIRECTORY"/t1801/email-mbox &&
+    git cat-file commit $(stg id change-1) | tail -n 3 >out &&
+    cat >expected <<-\EOF &&
+	Signed-off-by: Inge Ström <inge@power.com>
+	Message-Id: <20061111114527.31778.12942.stgit@localhost>
+	Link: https://inbox.example.com/list/20061111114527.31778.12942.stgit@localhost
+	EOF
+    test_cmp expected out &&
+    stg delete ..
+'
+
 test_expect_success 'Apply several patches from an mbox file from stdin' '
     test_config stgit.import.message-id "off" &&
     cat "$TEST_DIRECTORY"/t1801/email-mbox | stg import -M &&
@@ -214,4 +248,58 @@ test_expect_success 'Import from git format-patch output' '
     )
 '
 
+test_expect_success 'Import patch names from stg email format' '
+    (
+        cd downstream &&
+        stg init &&
+        stg uncommit &&
+        stg rename my-else-patch &&
+        stg email format --patch-names -o ../named my-else-patch
+    ) &&
+    test_path_is_file named/0001-my-else-patch.patch &&
+    grep -e "^X-StGit-Patch: my-else-patch\$" named/0001-my-else-patch.patch &&
+    (
+        cd upstream &&
+        stg delete --top &&
+        stg import --mbox ../named/0001-my-else-patch.patch &&
+        test "$(stg top)" = "my-else-patch" &&
+        stg delete --top &&
+        stg import ../named/0001-my-else-patch.patch &&
+        test "$(stg top)" = "my-else-patch" &&
+        stg delete --top &&
+        stg import --name other-name ../named/0001-my-else-patch.patch &&
+        test "$(stg top)" = "other-name"
+    )
+'
+
+test_expect_success 'Import a large mbox with progress' '
+    git init many &&
+    (
+        cd many &&
+        test_commit_bulk --filename=file-%s.txt 50 &&
+        git format-patch --stdout --root HEAD >../many.mbox
+    ) &&
+    (
+        cd upstream &&
+        stg delete .. &&
+        stg import -M --progress ../many.mbox >out 2>err &&
+        test "$(stg series --noprefix | wc -l)" = "50" &&
+        test "$(stg top)" = "commit-50" &&
+        test_path_is_file file-50.txt &&
+        tr "\r" "\n" <err >progress &&
+        grep -e "^Importing emails: 50, done.\$" progress &&
+        stg delete ..
+    )
+'
+
+test_expect_success 'Import corrupt mbox' '
+    (
+        cd upstream &&
+        printf "Subject: not an mbox\n\nbody\n" >corrupt.mbox &&
+        command_error stg import -M corrupt.mbox 2>err &&
+        grep -e "corrupt mailbox" err &&
+        test -z "$(stg series)"
+    )
+'
+
 test_done
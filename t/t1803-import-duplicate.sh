@@ -0,0 +1,102 @@
+#!/bin/sh
+
+test_description='Test stg import --on-duplicate'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup patches and export' '
+    for i in 1 2 3; do
+        echo "base $i" >f$i.txt || return 1
+    done &&
+    git add f1.txt f2.txt f3.txt &&
+    git commit -m base &&
+    stg init &&
+    for i in 1 2 3; do
+        stg new -m "patch $i" p$i &&
+        echo "change $i" >>f$i.txt &&
+        stg refresh || return 1
+    done &&
+    stg export -d export &&
+    test "$(echo $(stg series --noprefix))" = "p1 p2 p3"
+'
+
+test_expect_success 'Skip duplicates of applied patches' '
+    stg import --series --on-duplicate skip export/series 2>err &&
+    grep -e "info: skipping duplicate of patch \`p1\`" err &&
+    grep -e "info: skipping duplicate of patch \`p3\`" err &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2 p3" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = ""
+'
+
+test_expect_success 'Skip duplicates matched by patch id' '
+    sed -e "s/^patch 2$/renamed patch two/" export/p2 >p2-renamed &&
+    stg pop p2 &&
+    stg import --on-duplicate skip --name p2-renamed p2-renamed 2>err &&
+    grep -e "info: skipping duplicate of patch \`p2\`" err &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p3" &&
+    stg push p2
+'
+
+test_expect_success 'Replace unapplied duplicates' '
+    stg pop -a &&
+    stg import --series --on-duplicate replace export/series 2>err &&
+    grep -e "info: replacing duplicate patch \`p2\`" err &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2 p3" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = ""
+'
+
+test_expect_success 'Replace applied duplicate with updated patch' '
+    sed -e "s/^+change 2$/+updated change 2/" export/p2 >p2-updated &&
+    stg import --on-duplicate replace --name p2-updated p2-updated 2>err &&
+    grep -e "info: replacing duplicate patch \`p2\`" err &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p3 p2" &&
+    test "$(tail -n 1 f2.txt)" = "updated change 2" &&
+    test "$(git log -1 --pretty=format:%s $(stg id p2))" = "patch 2"
+'
+
+test_expect_success 'Import duplicate with new name' '
+    stg pop p2 &&
+    stg import --on-duplicate new-name export/p2 2>err &&
+    grep -e "info: importing duplicate of patch \`p2\` as \`p4\`" err &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p3 p4" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p2"
+'
+
+test_expect_success 'Non-duplicates are imported' '
+    cat >new.patch <<-\EOF &&
+	brand new patch
+	---
+	diff --git a/f1.txt b/f1.txt
+	--- a/f1.txt
+	+++ b/f1.txt
+	@@ -1,2 +1,3 @@
+	 base 1
+	 change 1
+	+another change 1
+	EOF
+    stg import --on-duplicate skip new.patch 2>err &&
+    ! grep -e "duplicate" err &&
+    test "$(stg top)" = "new.patch"
+'
+
+test_expect_success 'Invalid duplicate policy' '
+    general_error stg import --on-duplicate bogus new.patch 2>err &&
+    grep -e "invalid value .bogus." err &&
+    general_error stg import --on-duplicate skip --replace new.patch 2>err &&
+    grep -e "cannot be used with" err
+'
+
+test_expect_success 'Skip duplicates of binary patches matched by patch id' '
+    printf "\001\002\003\000binary\n" >blob.bin &&
+    stg new -m "binary patch" bin &&
+    git add blob.bin &&
+    stg refresh &&
+    stg export -d export-bin bin &&
+    sed -e "s/^binary patch$/renamed binary patch/" export-bin/bin >bin-renamed &&
+    stg pop bin &&
+    stg import --on-duplicate skip --name bin-renamed bin-renamed 2>err &&
+    grep -e "info: skipping duplicate of patch \`bin\`" err &&
+    test_path_is_missing blob.bin
+'
+
+test_done
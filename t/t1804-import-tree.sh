@@ -0,0 +1,68 @@
+#!/bin/sh
+
+test_description='Test importing a directory tree of patches'
+
+. ./test-lib.sh
+
+make_patch () {
+    echo "$2" >"$2" &&
+    git add "$2" &&
+    git diff --cached >"$1" &&
+    git rm --cached -q "$2" &&
+    rm "$2"
+}
+
+test_expect_success 'Setup patch tree' '
+    mkdir -p tree/sub tree/quilt tree/.hidden &&
+    make_patch tree/2-two.patch two &&
+    make_patch tree/10-ten.patch ten &&
+    make_patch tree/sub/1-sub.patch sub &&
+    make_patch tree/quilt/a.diff quilt-a &&
+    make_patch tree/quilt/b.diff quilt-b &&
+    make_patch tree/quilt/unlisted.patch unlisted &&
+    make_patch tree/.hidden/hidden.patch hidden &&
+    printf "# comment\nb.diff\na.diff\n" >tree/quilt/series &&
+    echo "not a patch" >tree/notes.txt &&
+    stg init
+'
+
+test_expect_success 'Import patch tree in natural order' '
+    stg import --recursive tree &&
+    cat >expected <<-\EOF &&
+	2-two.patch
+	10-ten.patch
+	b.diff
+	a.diff
+	1-sub.patch
+	EOF
+    stg series --noprefix >series &&
+    test_cmp expected series &&
+    test_path_is_file quilt-a &&
+    test_path_is_missing unlisted &&
+    test_path_is_missing hidden &&
+    stg delete ..
+'
+
+test_expect_success 'Import patch tree with include and exclude globs' '
+    stg import -R --stripname --include "*.patch" --include "quilt/*" \
+        --exclude "sub/**" --exclude "a.diff" tree &&
+    cat >expected <<-\EOF &&
+	2-two
+	10-ten
+	b
+	EOF
+    stg series --noprefix >series &&
+    test_cmp expected series &&
+    stg delete ..
+'
+
+test_expect_success 'Import patch tree errors' '
+    command_error stg import -R tree/notes.txt 2>err &&
+    grep -e "is not a directory" err &&
+    command_error stg import -R --include "*.txt" --exclude "notes*" tree 2>err &&
+    grep -e "no patch files found" err &&
+    general_error stg import --include "*.patch" tree 2>err &&
+    general_error stg import -R 2>err
+'
+
+test_done
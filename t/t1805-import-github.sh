@@ -0,0 +1,65 @@
+#!/bin/sh
+
+test_description='Test importing GitHub pull requests'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup repository with pull request' '
+    test_commit base &&
+    git clone -q . upstream &&
+    (
+        cd upstream &&
+        git checkout -q -b feature &&
+        test_commit --author "PR Author <pr@example.com>" first &&
+        test_commit --author "PR Author <pr@example.com>" second &&
+        git update-ref refs/pull/7/head feature &&
+        git checkout -q -b merged master &&
+        test_commit other &&
+        git merge -q --no-ff -m "Merge feature" feature &&
+        git update-ref refs/pull/8/head merged
+    ) &&
+    git remote add origin https://github.com/owner/repo.git &&
+    git config url."$(pwd)/upstream".insteadOf https://github.com/owner/repo.git &&
+    stg init
+'
+
+test_expect_success 'Import pull request by number' '
+    stg import --github 7 &&
+    cat >expected <<-\EOF &&
+	first
+	second
+	EOF
+    stg series --noprefix >series &&
+    test_cmp expected series &&
+    test "$(stg show --stat first | grep -c "^Author: PR Author <pr@example.com>")" = "1" &&
+    git log -1 --format=%B $(stg id second) >message &&
+    grep -e "^Link: https://github.com/owner/repo/pull/7\$" message &&
+    test "$(git log -1 --format=%ad --date=raw $(stg id second))" = \
+        "$(git -C upstream log -1 --format=%ad --date=raw feature)" &&
+    test_path_is_file second.t &&
+    stg delete ..
+'
+
+test_expect_success 'Import pull request by URL' '
+    stg import --github https://github.com/owner/repo/pull/7/commits &&
+    test "$(echo $(stg series --noprefix))" = "first second" &&
+    git log -1 --format=%B $(stg id first) >message &&
+    grep -e "^Link: https://github.com/owner/repo/pull/7\$" message &&
+    stg delete ..
+'
+
+test_expect_success 'Import pull request errors' '
+    command_error stg import --github https://example.com/owner/repo/pull/7 2>err &&
+    grep -e "invalid GitHub pull request" err &&
+    command_error stg import --github 8 2>err &&
+    grep -e "pull request #8 contains merge commit" err &&
+    command_error stg import --github 9 2>err &&
+    grep -e "fetching pull request #9" err &&
+    git config remote.origin.url "$(pwd)/upstream" &&
+    command_error stg import --github 7 2>err &&
+    grep -e "remote \`origin\` is not a GitHub repository" err &&
+    general_error stg import --github 7 some-file 2>err &&
+    test "$(stg series --noprefix | wc -l)" = "0"
+'
+
+test_done
@@ -0,0 +1,53 @@
+#!/bin/sh
+
+test_description='Test importing GitLab merge requests'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup repository with merge request' '
+    test_commit base &&
+    git clone -q . upstream &&
+    (
+        cd upstream &&
+        git checkout -q -b feature &&
+        test_commit --author "MR Author <mr@example.com>" first &&
+        test_commit --author "MR Author <mr@example.com>" second &&
+        git update-ref refs/merge-requests/3/head feature
+    ) &&
+    git remote add origin git@gitlab.example.com:group/sub/project.git &&
+    git config url."$(pwd)/upstream".insteadOf git@gitlab.example.com:group/sub/project.git &&
+    git config --add url."$(pwd)/upstream".insteadOf https://gitlab.example.com/group/sub/project.git &&
+    stg init
+'
+
+test_expect_success 'Import merge request by number' '
+    stg import --gitlab !3 &&
+    test "$(echo $(stg series --noprefix))" = "first second" &&
+    test "$(stg show --stat first | grep -c "^Author: MR Author <mr@example.com>")" = "1" &&
+    git log -1 --format=%B $(stg id second) >message &&
+    grep -e "^Link: https://gitlab.example.com/group/sub/project/-/merge_requests/3\$" message &&
+    test_path_is_file second.t &&
+    stg delete ..
+'
+
+test_expect_success 'Import merge request by URL' '
+    stg import --gitlab https://gitlab.example.com/group/sub/project/-/merge_requests/3/commits &&
+    test "$(echo $(stg series --noprefix))" = "first second" &&
+    git log -1 --format=%B $(stg id first) >message &&
+    grep -e "^Link: https://gitlab.example.com/group/sub/project/-/merge_requests/3\$" message &&
+    stg delete ..
+'
+
+test_expect_success 'Import merge request errors' '
+    command_error stg import --gitlab https://gitlab.example.com/group/project/merge_requests 2>err &&
+    grep -e "invalid GitLab merge request" err &&
+    command_error stg import --gitlab 4 2>err &&
+    grep -e "fetching merge request !4" err &&
+    git config remote.origin.url "$(pwd)/upstream" &&
+    command_error stg import --gitlab 3 2>err &&
+    grep -e "remote \`origin\` does not have a web URL" err &&
+    general_error stg import --gitlab 3 --github 3 2>err &&
+    test "$(stg series --noprefix | wc -l)" = "0"
+'
+
+test_done
@@ -0,0 +1,91 @@
+#!/bin/sh
+
+test_description='Test importing patches from a Maildir'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize the StGit repository' '
+    test_commit base &&
+    stg init &&
+    for n in 1 2 3; do
+        stg new -m "patch $n" &&
+        echo "line $n" >>file.txt &&
+        stg add file.txt &&
+        stg refresh || return 1
+    done &&
+    stg email format --maildir mail --cover-letter --all >out &&
+    test_line_count = 4 out &&
+    stg delete ..
+'
+
+test_expect_success 'Import patch emails from a Maildir' '
+    stg import --maildir mail &&
+    test "$(stg series --applied --noprefix)" = "$(printf "patch-1\npatch-2\npatch-3")" &&
+    test "$(stg top)" = "patch-3" &&
+    test_cmp file.txt - <<-\EOF &&
+	line 1
+	line 2
+	line 3
+	EOF
+    stg delete --top &&
+    stg delete --top &&
+    stg delete --top
+'
+
+test_expect_success 'Import in subject numbering order' '
+    mkdir -p reversed/cur &&
+    i=9 &&
+    for f in $(cat out); do
+        cp "$f" reversed/cur/$i-message &&
+        i=$((i - 1)) || return 1
+    done &&
+    stg import --maildir reversed &&
+    test "$(stg series --applied --noprefix)" = "$(printf "patch-1\npatch-2\npatch-3")" &&
+    stg delete --top &&
+    stg delete --top &&
+    stg delete --top
+'
+
+test_expect_success 'Import unnumbered emails in date order' '
+    mkdir -p dated/new &&
+    i=9 &&
+    for n in 1 2 3; do
+        f=$(sed -n "$((n + 1))p" out) &&
+        sed -e "s/^Subject: \[PATCH [0-9]\/3\]/Subject: [PATCH]/" \
+            -e "s/^Date: .*/Date: Thu, 7 Apr 2005 15:1$n:13 -0700/" \
+            "$f" >dated/new/$i-message &&
+        i=$((i - 1)) || return 1
+    done &&
+    ! grep "PATCH [0-9]" dated/new/* &&
+    stg import --maildir dated &&
+    test "$(stg series --applied --noprefix)" = "$(printf "patch-1\npatch-2\npatch-3")" &&
+    stg delete --top &&
+    stg delete --top &&
+    stg delete --top
+'
+
+test_expect_success 'Skip replies and emails without a diff' '
+    mkdir -p replies/cur &&
+    cp $(sed -n 2p out) replies/cur/1-patch &&
+    sed -e "s/^Subject: /Subject: Re: /" $(sed -n 3p out) >replies/cur/2-reply &&
+    cp $(sed -n 1p out) replies/cur/0-cover &&
+    stg import --maildir replies &&
+    test "$(stg series --applied --noprefix)" = "patch-1" &&
+    stg delete --top
+'
+
+test_expect_success 'Refuse directories that are not a Maildir' '
+    mkdir not-maildir &&
+    command_error stg import --maildir not-maildir 2>err &&
+    grep "\`not-maildir\` is not a Maildir" err &&
+    mkdir -p empty/new &&
+    command_error stg import --maildir empty 2>err &&
+    grep "no patch emails found in \`empty\`" err
+'
+
+test_expect_success 'Maildir import conflicts with source' '
+    general_error stg import --maildir mail foo.patch 2>err &&
+    grep "cannot be used with" err
+'
+
+test_done
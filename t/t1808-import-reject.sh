@@ -0,0 +1,95 @@
+#!/bin/sh
+
+test_description='Test stg import --reject'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize the StGit repository' '
+    printf "a\nb\nc\n" >f &&
+    printf "x\n" >g &&
+    git add f g &&
+    git commit -m base &&
+    stg init &&
+    cat >partial.diff <<-\EOF &&
+	diff --git a/f b/f
+	--- a/f
+	+++ b/f
+	@@ -1,3 +1,3 @@
+	 a
+	-B
+	+b2
+	 c
+	diff --git a/g b/g
+	--- a/g
+	+++ b/g
+	@@ -1 +1 @@
+	-x
+	+y
+	diff --git a/h b/h
+	new file mode 100644
+	--- /dev/null
+	+++ b/h
+	@@ -0,0 +1 @@
+	+new
+	EOF
+    cat >clean.diff <<-\EOF
+	diff --git a/g b/g
+	--- a/g
+	+++ b/g
+	@@ -1 +1 @@
+	-x
+	+z
+	EOF
+'
+
+test_expect_success 'Import cleanly applying patch with --reject' '
+    stg import --reject clean.diff &&
+    test "$(stg top)" = "clean.diff" &&
+    test "$(cat g)" = "z" &&
+    test -z "$(git status --porcelain --untracked-files=no)" &&
+    stg delete --top
+'
+
+test_expect_success 'Import fails without --reject' '
+    command_error stg import partial.diff 2>err &&
+    grep -e "patch failed: f:1" err &&
+    test "$(echo $(stg series --noprefix))" = "" &&
+    test ! -e f.rej
+'
+
+test_expect_success 'Import partially applying patch with --reject' '
+    command_error stg import --reject partial.diff 2>err &&
+    grep -e "patch \`partial.diff\` only partially applied; rejected hunks left in \`f.rej\`" err &&
+    test "$(stg top)" = "partial.diff" &&
+    test "$(echo $(stg files))" = "M g A h" &&
+    test -f f.rej &&
+    test "$(cat g)" = "y" &&
+    test "$(cat h)" = "new" &&
+    test -z "$(git status --porcelain --untracked-files=no)"
+'
+
+test_expect_success 'Fix up and refresh partially applied patch' '
+    printf "a\nb2\nc\n" >f &&
+    rm f.rej &&
+    stg refresh &&
+    test "$(echo $(stg files))" = "M f M g A h" &&
+    stg delete --top
+'
+
+test_expect_success 'Series import stops at partially applied patch' '
+    cat >series <<-\EOF &&
+	partial.diff
+	clean.diff
+	EOF
+    command_error stg import --reject --series series 2>err &&
+    grep -e "patch \`partial.diff\` only partially applied" err &&
+    test "$(echo $(stg series --noprefix))" = "partial.diff" &&
+    test -f f.rej
+'
+
+test_expect_success 'Reject may not be used with 3way' '
+    general_error stg import --reject --3way clean.diff 2>err &&
+    grep -e "cannot be used with" err
+'
+
+test_done
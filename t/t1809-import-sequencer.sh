@@ -0,0 +1,93 @@
+#!/bin/sh
+
+test_description='Test resuming an interrupted mail import'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize the StGit repository' '
+    test_write_lines 1 2 3 4 5 6 7 8 9 >f &&
+    git add f &&
+    git commit -m base &&
+    stg init &&
+    stg new -m p1 &&
+    test_write_lines one 2 3 4 5 6 7 8 9 >f &&
+    stg refresh &&
+    stg new -m p2 &&
+    test_write_lines one 2 3 4 five 6 7 8 9 >f &&
+    stg refresh &&
+    stg new -m p3 &&
+    test_write_lines one 2 3 4 five 6 7 8 nine >f &&
+    stg refresh &&
+    git format-patch --stdout HEAD~3 >series.mbox &&
+    stg delete p1 p2 p3 &&
+    test_write_lines 1 2 3 4 FIVE 6 7 8 9 >f &&
+    git commit -a -m upstream
+'
+
+test_expect_success 'Resuming without an interrupted import' '
+    command_error stg import --continue 2>err &&
+    grep -e "no mail import in progress" err
+'
+
+test_expect_success 'Import stops at patch that fails to apply' '
+    command_error stg import -M --signoff series.mbox 2>err &&
+    grep -e "import stopped with 2 emails remaining" err &&
+    test "$(echo $(stg series --noprefix))" = "p1" &&
+    test -d .git/stgit-sequencer
+'
+
+test_expect_success 'New import refused while import is in progress' '
+    command_error stg import -M series.mbox 2>err &&
+    grep -e "a mail import is in progress" err &&
+    general_error stg import --continue --3way 2>err &&
+    grep -e "cannot be used with" err
+'
+
+test_expect_success 'Abort interrupted import' '
+    stg import --abort &&
+    test "$(echo $(stg series --noprefix))" = "" &&
+    test ! -e .git/stgit-sequencer &&
+    test "$(echo $(cat f))" = "1 2 3 4 FIVE 6 7 8 9"
+'
+
+test_expect_success 'Skip patch that fails to apply' '
+    command_error stg import -M series.mbox &&
+    stg import --skip &&
+    test "$(echo $(stg series --noprefix))" = "p1 p3" &&
+    test "$(echo $(cat f))" = "one 2 3 4 FIVE 6 7 8 nine" &&
+    test ! -e .git/stgit-sequencer &&
+    stg delete p1 p3
+'
+
+test_expect_success 'Continue requires changes staged for failed patch' '
+    command_error stg import -M --signoff series.mbox &&
+    command_error stg import --continue 2>err &&
+    grep -e "no changes staged for the failed patch" err &&
+    test_write_lines one 2 3 4 five 6 7 8 9 >f &&
+    command_error stg import --continue 2>err &&
+    grep -e "worktree not clean" err
+'
+
+test_expect_success 'Continue after resolving failed patch' '
+    git add f &&
+    stg import --continue &&
+    test "$(echo $(stg series --noprefix))" = "p1 p2 p3" &&
+    test "$(echo $(cat f))" = "one 2 3 4 five 6 7 8 nine" &&
+    git cat-file commit "$(stg id p2)" | grep -e "^Signed-off-by: " &&
+    git cat-file commit "$(stg id p3)" | grep -e "^Signed-off-by: " &&
+    test ! -e .git/stgit-sequencer
+'
+
+test_expect_success 'Continue after partially applied patch with --reject' '
+    stg delete p1 p2 p3 &&
+    command_error stg import -M --reject series.mbox 2>err &&
+    grep -e "patch \`p2\` only partially applied" err &&
+    grep -e "import stopped with 1 email remaining" err &&
+    test "$(echo $(stg series --noprefix))" = "p1 p2" &&
+    rm f.rej &&
+    stg import --continue &&
+    test "$(echo $(stg series --noprefix))" = "p1 p2 p3" &&
+    test ! -e .git/stgit-sequencer
+'
+
+test_done
@@ -80,4 +80,527 @@ test_expect_success 'With cover letter' '
     rmdir out
 '
 
+test_expect_success 'Message-Id recorded in patch metadata' '
+    stg email format -o out p1 p2 &&
+    grep -i "^Message-Id:" out/0001-p1.patch >expected &&
+    git notes --ref=refs/notes/stgit show $(stg id p1) >actual &&
+    test_cmp expected actual &&
+    rm -r out
+'
+
+test_expect_success 'Message-Id metadata follows patch updates' '
+    git notes --ref=refs/notes/stgit show $(stg id p2) >expected &&
+    stg edit -m "p2 updated" p2 &&
+    git notes --ref=refs/notes/stgit show $(stg id p2) >actual &&
+    test_cmp expected actual
+'
+
+test_expect_success 'Message-Id recording disabled by config' '
+    test_config stgit.email.record-message-id false &&
+    git notes --ref=refs/notes/stgit remove --ignore-missing $(stg id p3) &&
+    stg email format -o out --thread p3 &&
+    test_must_fail git notes --ref=refs/notes/stgit show $(stg id p3) &&
+    rm -r out
+'
+
+test_expect_success 'Filename template with patch names' '
+    stg email format -o out --cover-letter --filename-template="%n-%p.patch" p1 p2 &&
+    test_path_exists out/0000-cover-letter.patch &&
+    test_path_exists out/0001-p1.patch &&
+    test_path_exists out/0002-p2.patch &&
+    rm -r out
+'
+
+test_expect_success 'Filename template with reroll and subject' '
+    stg email format -o out -v 3 --start-number=5 \
+        --filename-template="%v_%n_%s.txt" p1 >filenames &&
+    test_path_exists out/v3_0005_p1.txt &&
+    printf "out/v3_0005_p1.txt\n" >expected &&
+    test_cmp expected filenames &&
+    rm -r out
+'
+
+test_expect_success 'Filename template from config' '
+    test_config stgit.email.filename-template "%p" &&
+    stg email format -o out p1 &&
+    test_path_exists out/p1 &&
+    rm -r out
+'
+
+test_expect_success 'Filename template errors' '
+    command_error stg email format -o out --filename-template="%x" p1 2>err &&
+    grep -e "unknown placeholder \`%x\`" err &&
+    test_path_is_missing out &&
+    command_error stg email format -o out --filename-template="same" p1 p2 2>err &&
+    grep -e "used for more than one email" err &&
+    test_path_is_missing out
+'
+
+test_expect_success 'Email headers and body' '
+    stg email format -o out --to=to@example.com --cc=cc1@example.com \
+        --cc=cc2@example.com --add-header="X-Test: yes" p1 p2 &&
+    grep -e "^From $(stg id p1) Mon Sep 17 00:00:00 2001\$" out/0001-p1.patch &&
+    grep -e "^From: =?UTF-8?q?A=20=C3=9A=20Thor?= <author@example.com>\$" out/0001-p1.patch &&
+    grep -e "^Subject: \[PATCH 1/2\] p1\$" out/0001-p1.patch &&
+    grep -e "^Subject: \[PATCH 2/2\] p2 updated\$" out/0002-p2-updated.patch &&
+    grep -e "^X-Test: yes\$" out/0001-p1.patch &&
+    grep -e "^To: to@example.com\$" out/0001-p1.patch &&
+    grep -e "^Cc: cc1@example.com,\$" out/0001-p1.patch &&
+    grep -e "^    cc2@example.com\$" out/0001-p1.patch &&
+    grep -e "^diff --git a/1.t b/1.t\$" out/0001-p1.patch &&
+    grep -e "^-- \$" out/0001-p1.patch &&
+    grep -e "^Message-Id: <$(stg id p1)\." out/0001-p1.patch &&
+    ! grep -e "^In-Reply-To:" out/0002-p2-updated.patch &&
+    rm -r out
+'
+
+test_expect_success 'Discard headers with --no-add-header' '
+    test_config format.headers "X-Config: yes" &&
+    test_config format.to config-to@example.com &&
+    stg email format -o out --add-header="X-Before: yes" --no-add-header \
+        --add-header="X-After: yes" --cc=cc@example.com p1 &&
+    ! grep -e "^X-Config:" out/0001-p1.patch &&
+    ! grep -e "^X-Before:" out/0001-p1.patch &&
+    ! grep -e "config-to@example.com" out/0001-p1.patch &&
+    grep -e "^X-After: yes\$" out/0001-p1.patch &&
+    grep -e "^Cc: cc@example.com\$" out/0001-p1.patch &&
+    rm -r out &&
+    stg email format -o out p1 &&
+    grep -e "^X-Config: yes\$" out/0001-p1.patch &&
+    grep -e "^To: config-to@example.com\$" out/0001-p1.patch &&
+    rm -r out
+'
+
+test_expect_success 'Subject prefix options' '
+    stg email format -o out -N --rfc -v 2 p1 p2 &&
+    grep -e "^Subject: \[RFC PATCH v2\] p1\$" out/v2-0001-p1.patch &&
+    rm -r out &&
+    stg email format -o out -n --subject-prefix=FOO p1 &&
+    grep -e "^Subject: \[FOO 1/1\] p1\$" out/0001-p1.patch &&
+    rm -r out &&
+    stg email format -o out -k p1 &&
+    grep -e "^Subject: p1\$" out/0001-p1.patch &&
+    rm -r out &&
+    general_error stg email format -o out -k --rfc p1 2>err &&
+    grep -e "cannot be used with" err
+'
+
+test_expect_success 'Branch subject prefix' '
+    test_config branch.master.stgit.subjectprefix "PATCH net-next" &&
+    test_config format.subjectPrefix "PATCH other" &&
+    stg email format -o out -N p1 &&
+    grep -e "^Subject: \[PATCH net-next\] p1\$" out/0001-p1.patch &&
+    rm -r out &&
+    stg email format -o out -N --rfc -v 3 p1 &&
+    grep -e "^Subject: \[RFC PATCH net-next v3\] p1\$" out/v3-0001-p1.patch &&
+    rm -r out &&
+    stg email format -o out -N --rfc --subject-prefix=FOO p1 &&
+    grep -e "^Subject: \[RFC FOO\] p1\$" out/0001-p1.patch &&
+    rm -r out &&
+    test_unconfig branch.master.stgit.subjectprefix &&
+    stg email format -o out -N p1 &&
+    grep -e "^Subject: \[PATCH other\] p1\$" out/0001-p1.patch &&
+    rm -r out
+'
+
+test_expect_success 'Cover letter content' '
+    test_config branch.master.description "Series description" &&
+    stg email format -o out --cover-letter p1 p2 &&
+    grep -e "^Subject: \[PATCH 0/2\] \*\*\* SUBJECT HERE \*\*\*\$" out/0000-cover-letter.patch &&
+    grep -e "^Series description\$" out/0000-cover-letter.patch &&
+    grep -e "^A Ú Thor (2):\$" out/0000-cover-letter.patch &&
+    grep -e "^  \[1/2\] p1\$" out/0000-cover-letter.patch &&
+    grep -e "^  \[2/2\] p2 updated\$" out/0000-cover-letter.patch &&
+    grep -e "^  p2 updated\$" out/0000-cover-letter.patch &&
+    grep -e "^ 2 files changed, 2 insertions(+)\$" out/0000-cover-letter.patch &&
+    rm -r out
+'
+
+test_expect_success 'Cover letter notes from patch metadata' '
+    git notes --ref=refs/notes/stgit append -m "Cover-Note: Reworked since v1" $(stg id p2) &&
+    stg email format -o out --cover-letter p1 p2 &&
+    sed -n -e "/^  \[2\/2\] p2 updated\$/{n;p;}" out/0000-cover-letter.patch >note &&
+    echo "        Reworked since v1" >expected &&
+    test_cmp expected note &&
+    ! grep -e "Cover-Note" out/0002-p2-updated.patch &&
+    rm -r out
+'
+
+test_expect_success 'Cover letter from description subject' '
+    test_config branch.master.description "Series subject
+
+Series body" &&
+    stg email format -o out --cover-letter --cover-from-description=subject p1 p2 &&
+    grep -e "^Subject: \[PATCH 0/2\] Series subject\$" out/0000-cover-letter.patch &&
+    grep -e "^Series body\$" out/0000-cover-letter.patch &&
+    ! grep -e "BLURB HERE" out/0000-cover-letter.patch &&
+    rm -r out
+'
+
+test_expect_success 'Cover letter from description auto' '
+    test_config branch.master.description "Series subject
+
+Series body" &&
+    stg email format -o out --cover-letter --cover-from-description=auto p1 p2 &&
+    grep -e "^Subject: \[PATCH 0/2\] Series subject\$" out/0000-cover-letter.patch &&
+    rm -r out &&
+    test_config branch.master.description "$(printf "long%.0s" $(test_seq 30))" &&
+    stg email format -o out --cover-letter --cover-from-description=auto p1 p2 &&
+    grep -e "^Subject: \[PATCH 0/2\] \*\*\* SUBJECT HERE \*\*\*\$" out/0000-cover-letter.patch &&
+    grep -e "^longlong" out/0000-cover-letter.patch &&
+    rm -r out
+'
+
+test_expect_success 'Cover letter from description none' '
+    test_config branch.master.description "Series description" &&
+    stg email format -o out --cover-letter --cover-from-description=none p1 p2 &&
+    grep -e "^Subject: \[PATCH 0/2\] \*\*\* SUBJECT HERE \*\*\*\$" out/0000-cover-letter.patch &&
+    grep -e "^\*\*\* BLURB HERE \*\*\*\$" out/0000-cover-letter.patch &&
+    ! grep -e "Series description" out/0000-cover-letter.patch &&
+    rm -r out
+'
+
+test_expect_success 'Cover letter from description config' '
+    test_config branch.master.description "Series subject" &&
+    test_config format.coverFromDescription subject &&
+    stg email format -o out --cover-letter p1 p2 &&
+    grep -e "^Subject: \[PATCH 0/2\] Series subject\$" out/0000-cover-letter.patch &&
+    grep -e "^\*\*\* BLURB HERE \*\*\*\$" out/0000-cover-letter.patch &&
+    rm -r out &&
+    stg email format -o out --cover-letter --cover-from-description=message p1 p2 &&
+    grep -e "^Subject: \[PATCH 0/2\] \*\*\* SUBJECT HERE \*\*\*\$" out/0000-cover-letter.patch &&
+    grep -e "^Series subject\$" out/0000-cover-letter.patch &&
+    rm -r out &&
+    test_config format.coverFromDescription bogus &&
+    command_error stg email format -o out --cover-letter p1 p2 2>err &&
+    grep -e "invalid cover-from-description mode .bogus." err
+'
+
+test_expect_success 'Cover letter template' '
+    test_config branch.master.description "Series description" &&
+    printf "Template subject\n\nTemplate body\n" >template &&
+    stg email format -o out --cover-template=template --cover-from-description=subject p1 p2 &&
+    grep -e "^Subject: \[PATCH 0/2\] Template subject\$" out/0000-cover-letter.patch &&
+    grep -e "^Template body\$" out/0000-cover-letter.patch &&
+    ! grep -e "Series description" out/0000-cover-letter.patch &&
+    rm -r out &&
+    command_error stg email format -o out --cover-template=missing p1 p2 2>err &&
+    grep -e "reading cover template" err &&
+    command_error stg email format -o out --reuse-cover p1 p2 2>err &&
+    grep -e "no previously sent cover letter recorded for branch .master." err
+'
+
+test_expect_success 'Shallow threading with in-reply-to' '
+    stg email format -o out --thread --cover-letter --in-reply-to=orig@example.com p1 p2 &&
+    grep -e "^In-Reply-To: <orig@example.com>\$" out/0000-cover-letter.patch &&
+    cover_id=$(sed -n -e "s/^Message-Id: //p" out/0000-cover-letter.patch) &&
+    grep -e "^In-Reply-To: $cover_id\$" out/0001-p1.patch &&
+    grep -e "^In-Reply-To: $cover_id\$" out/0002-p2-updated.patch &&
+    rm -r out
+'
+
+test_expect_success 'Deep threading' '
+    stg email format -o out --thread=deep p1 p2 p3 &&
+    ! grep -e "^In-Reply-To:" out/0001-p1.patch &&
+    p1_id=$(sed -n -e "s/^Message-Id: //p" out/0001-p1.patch) &&
+    p2_id=$(sed -n -e "s/^Message-Id: //p" out/0002-p2-updated.patch) &&
+    grep -e "^In-Reply-To: $p1_id\$" out/0002-p2-updated.patch &&
+    grep -e "^In-Reply-To: $p2_id\$" out/0003-p3.patch &&
+    grep -e "^References: $p1_id\$" out/0003-p3.patch &&
+    rm -r out
+'
+
+test_expect_success 'Reply to previous version of series' '
+    test_might_fail git config --unset-all branch.master.stgit.series-message-id &&
+    command_error stg email format -o out --thread --reply-to-previous p1 p2 2>err &&
+    grep -e "no previously formatted version of the series to reply to" err &&
+    stg email format -o out --thread --cover-letter p1 p2 &&
+    v1_id=$(sed -n -e "s/^Message-Id: //p" out/0000-cover-letter.patch) &&
+    rm -r out &&
+    stg email format -o out --thread --cover-letter -v 2 --reply-to-previous p1 p2 &&
+    grep -e "^In-Reply-To: $v1_id\$" out/v2-0000-cover-letter.patch &&
+    rm -r out &&
+    stg email format -o out --thread --cover-letter -v 2 --reply-to-previous p1 p2 &&
+    grep -e "^In-Reply-To: $v1_id\$" out/v2-0000-cover-letter.patch &&
+    v2_id=$(sed -n -e "s/^Message-Id: //p" out/v2-0000-cover-letter.patch) &&
+    rm -r out &&
+    stg email format -o out --thread -v 3 --reply-to-previous p1 p2 &&
+    grep -e "^In-Reply-To: $v2_id\$" out/v3-0001-p1.patch &&
+    git config --get-all branch.master.stgit.series-message-id >ids &&
+    test_line_count = 3 ids &&
+    rm -r out
+'
+
+test_expect_success 'Reply to previous conflicts with in-reply-to' '
+    general_error stg email format -o out --reply-to-previous --in-reply-to=orig@example.com p1
+'
+
+test_expect_success 'Sign-off, signature, and base' '
+    test_config format.signature "my signature" &&
+    stg email format -o out --signoff --base=auto p2 &&
+    grep -e "^Signed-off-by: C Ó Mitter <committer@example.com>\$" out/0001-p2-updated.patch &&
+    grep -e "^my signature\$" out/0001-p2-updated.patch &&
+    grep -e "^base-commit: $(stg id master:{base})\$" out/0001-p2-updated.patch &&
+    test $(grep -c -e "^prerequisite-patch-id:" out/0001-p2-updated.patch) = 1 &&
+    rm -r out &&
+    stg email format -o out --no-signature p2 &&
+    ! grep -e "^-- \$" out/0001-p2-updated.patch &&
+    rm -r out
+'
+
+test_expect_success 'Attach patch' '
+    stg email format -o out --attach p1 &&
+    grep -e "^Content-Type: multipart/mixed" out/0001-p1.patch &&
+    grep -e "^Content-Disposition: attachment; filename=\"0001-p1.patch\"\$" out/0001-p1.patch &&
+    rm -r out
+'
+
+test_expect_success 'Format series to stdout as single mbox' '
+    stg email format --stdout --cover-letter p1..p3 >series.mbox &&
+    test "$(grep -c "^From [0-9a-f]* Mon Sep 17 00:00:00 2001\$" series.mbox)" = "4" &&
+    test_path_is_missing 0001-p1.patch &&
+    mkdir split &&
+    test "$(git mailsplit -osplit series.mbox)" = "4" &&
+    rm -r split series.mbox
+'
+
+test_expect_success 'Format series to mbox file' '
+    stg email format --mbox series.mbox --no-signature p2 p3 >out &&
+    echo series.mbox >expected &&
+    test_cmp expected out &&
+    git checkout -b mbox-apply $(stg id p1) &&
+    git am series.mbox &&
+    git diff --exit-code $(stg id p3) &&
+    git checkout master &&
+    rm series.mbox out
+'
+
+test_expect_success 'Format series to Maildir' '
+    stg email format --maildir mail --cover-letter p1 p2 >out &&
+    test_path_is_dir mail/cur &&
+    test_path_is_dir mail/tmp &&
+    ls mail/new >names &&
+    test_line_count = 3 names &&
+    sed -e "s,^,mail/new/," names >expected &&
+    test_cmp expected out &&
+    test "$(ls mail/tmp | wc -l)" = "0" &&
+    grep -l -e "^Subject: \[PATCH 0/2\]" mail/new/* >cover &&
+    test "$(cat cover)" = "mail/new/$(sed -n 1p names)" &&
+    grep -l -e "^Subject: \[PATCH 2/2\] p2 updated\$" mail/new/* >last &&
+    test "$(cat last)" = "mail/new/$(sed -n 3p names)" &&
+    ! grep -e "^From [0-9a-f]* Mon Sep 17" mail/new/* &&
+    general_error stg email format --maildir mail -o out p1 2>err &&
+    grep "cannot be used with" err &&
+    rm -r mail out names expected cover last
+'
+
+test_expect_success 'Attempt single mbox with output directory' '
+    general_error stg email format --stdout -o out p1 2>err &&
+    grep "cannot be used with" err &&
+    general_error stg email format --mbox series.mbox --numbered-files p1 2>err &&
+    grep "cannot be used with" err
+'
+
+test_expect_success 'Expand address aliases and groups' '
+    cat >aliases <<-\EOF &&
+	alias alice Alice <alice@example.com>
+	alias team alice, bob@example.com
+	EOF
+    test_config sendemail.aliasesFile "$(pwd)/aliases" &&
+    test_config sendemail.aliasFileType mutt &&
+    git config --add stgit.email.group.reviewers team &&
+    git config --add stgit.email.group.reviewers carol@example.com &&
+    stg email format -o out --to reviewers --cc "alice, dave@example.com" p1 &&
+    cat >expected <<-\EOF &&
+	To: Alice <alice@example.com>,
+	    bob@example.com,
+	    carol@example.com
+	Cc: Alice <alice@example.com>,
+	    dave@example.com
+	EOF
+    sed -n -e "/^To:/,/^\$/{/^\$/!p;}" out/0001-p1.patch >headers &&
+    test_cmp expected headers &&
+    rm -r out &&
+    git config --unset-all stgit.email.group.reviewers
+'
+
+test_expect_success 'Cc addresses from patch trailers' '
+    stg edit p6 --ack="A Cker <acker@example.com>" &&
+    stg edit p6 --review="R Viewer <reviewer@example.com>" &&
+    stg edit p6 --sign="C Ó Mitter <committer@example.com>" &&
+    stg email format -o out --cc "R Viewer <reviewer@example.com>" p6 &&
+    cat >expected <<-\EOF &&
+	Cc: R Viewer <reviewer@example.com>,
+	    A Cker <acker@example.com>
+	EOF
+    sed -n -e "/^Cc: /{p;n;p;}" out/0001-p6.patch >actual &&
+    test_cmp expected actual &&
+    test "$(grep -c -e "<committer@example.com>" out/0001-p6.patch)" = "1" &&
+    rm -r out
+'
+
+test_expect_success 'Cc cover letter with addresses from all patches' '
+    stg email format -o out --cover-letter p5 p6 &&
+    grep -e "^Cc: A Cker <acker@example.com>" out/0000-cover-letter.patch &&
+    grep -e "R Viewer <reviewer@example.com>" out/0000-cover-letter.patch &&
+    ! grep -e "^Cc:" out/0001-p5.patch &&
+    rm -r out
+'
+
+test_expect_success 'Disable Cc from patch trailers' '
+    stg email format -o out --no-auto-cc p6 &&
+    ! grep -e "^Cc:" out/0001-p6.patch &&
+    rm -r out &&
+    test_config stgit.email.auto-cc false &&
+    stg email format -o out p6 &&
+    ! grep -e "^Cc:" out/0001-p6.patch &&
+    rm -r out
+'
+
+test_expect_success 'Suppress Cc from selected patch trailers' '
+    stg email format -o out --suppress-cc=Acked-by p6 &&
+    sed -e "/^\$/q" out/0001-p6.patch >headers &&
+    grep -e "^Cc: R Viewer <reviewer@example.com>\$" headers &&
+    ! grep -e "acker@example.com" headers &&
+    rm -r out &&
+    stg email format -o out --suppress-cc=misc-by p6 &&
+    ! grep -e "^Cc:" out/0001-p6.patch &&
+    rm -r out &&
+    test_config stgit.email.suppress-cc reviewed-by &&
+    stg email format -o out p6 &&
+    sed -e "/^\$/q" out/0001-p6.patch >headers &&
+    grep -e "^Cc: A Cker <acker@example.com>\$" headers &&
+    ! grep -e "reviewer@example.com" headers &&
+    rm -r out &&
+    stg email format -o out --suppress-cc=acked-by p6 &&
+    sed -e "/^\$/q" out/0001-p6.patch >headers &&
+    grep -e "^Cc: R Viewer <reviewer@example.com>\$" headers &&
+    ! grep -e "acker@example.com" headers &&
+    rm -r out
+'
+
+test_expect_success 'Recipients from to and cc commands' '
+    write_script maintainers <<-\EOF &&
+	echo "Maint One <one@example.com> (maintainer:FOO)"
+	grep -q "^p6\$" "$1" && echo "Sub Two <two@example.com>"
+	echo
+	EOF
+    stg email format -o out --cover-letter --cc-cmd=./maintainers p5 p6 &&
+    grep -e "^Cc: Maint One <one@example.com>\$" out/0001-p5.patch &&
+    ! grep -e "two@example.com" out/0001-p5.patch &&
+    grep -e "Sub Two <two@example.com>" out/0002-p6.patch &&
+    grep -e "Sub Two <two@example.com>" out/0000-cover-letter.patch &&
+    sed -e "/^\$/q" out/0000-cover-letter.patch >headers &&
+    test "$(grep -c "one@example.com" headers)" = "1" &&
+    grep -e "^  \[1/2\] Maint One <one@example.com>\$" out/0000-cover-letter.patch &&
+    grep -e "^  \[2/2\] Maint One <one@example.com>\$" out/0000-cover-letter.patch &&
+    grep -e "^        Sub Two <two@example.com>\$" out/0000-cover-letter.patch &&
+    rm -r out &&
+    test_config stgit.email.tocmd ./maintainers &&
+    stg email format -o out --cc one@example.com p6 &&
+    grep -e "^To: Sub Two <two@example.com>\$" out/0001-p6.patch &&
+    test "$(grep -c "one@example.com" out/0001-p6.patch)" = "1" &&
+    rm -r out
+'
+
+test_expect_success 'Recipients from patch metadata' '
+    git notes --ref=refs/notes/stgit append -m "Cc: Meta Cc <metacc@example.com>" $(stg id p5) &&
+    git notes --ref=refs/notes/stgit append -m "To: meta-to@example.com" $(stg id p6) &&
+    stg email format -o out --cover-letter --to=to@example.com p5 p6 &&
+    grep -e "^Cc: Meta Cc <metacc@example.com>\$" out/0001-p5.patch &&
+    ! grep -e "meta-to@example.com" out/0001-p5.patch &&
+    grep -e "^To: to@example.com,\$" out/0002-p6.patch &&
+    grep -e "^    meta-to@example.com\$" out/0002-p6.patch &&
+    sed -e "/^\$/q" out/0000-cover-letter.patch >headers &&
+    grep -e "^    meta-to@example.com\$" headers &&
+    grep -e "^Cc: Meta Cc <metacc@example.com>,\$" headers &&
+    grep -e "^Per-patch recipients:\$" out/0000-cover-letter.patch &&
+    grep -e "^  \[1/2\] Meta Cc <metacc@example.com>\$" out/0000-cover-letter.patch &&
+    grep -e "^  \[2/2\] meta-to@example.com\$" out/0000-cover-letter.patch &&
+    rm -r out &&
+    stg email format -o out --cover-letter p1 p2 &&
+    ! grep -e "Per-patch recipients" out/0000-cover-letter.patch &&
+    rm -r out &&
+    git notes --ref=refs/notes/stgit remove $(stg id p5) $(stg id p6)
+'
+
+test_expect_success 'Failing recipients command' '
+    command_error stg email format -o out --to-cmd=false p6 2>err &&
+    grep -e "\`false\` failed" err
+'
+
+test_expect_success 'Versioned output directories' '
+    test_might_fail git config --unset-all branch.master.stgit.series-message-id &&
+    stg email format --auto-dir p1 p2 &&
+    test_path_is_file outgoing/master/v1/0001-p1.patch &&
+    grep -e "^Subject: \[PATCH 1/2\] p1" outgoing/master/v1/0001-p1.patch &&
+    stg email format --auto-dir p1 p2 &&
+    test_path_is_file outgoing/master/v1/0002-p2-updated.patch &&
+    grep -e "^Subject: \[PATCH v2 1/2\] p1" outgoing/master/v2/v2-0001-p1.patch &&
+    test_config stgit.email.record-message-id false &&
+    stg email format --auto-dir --no-thread p1 &&
+    test_path_is_file outgoing/master/v3/v3-0001-p1.patch &&
+    stg email format --auto-dir -v 7 p1 &&
+    test_path_is_file outgoing/master/v7/v7-0001-p1.patch &&
+    general_error stg email format --auto-dir -o out p1 &&
+    rm -r outgoing
+'
+
+test_expect_success 'Embed patch names' '
+    stg email format -o out --cover-letter --patch-names p1 p2 &&
+    test_path_is_file out/0001-p1.patch &&
+    test_path_is_file out/0002-p2.patch &&
+    grep -e "^X-StGit-Patch: p2\$" out/0002-p2.patch &&
+    ! grep -e "^X-StGit-Patch:" out/0000-cover-letter.patch &&
+    rm -r out &&
+    test_config stgit.email.patch-names true &&
+    stg email format --stdout p1 >out &&
+    grep -e "^X-StGit-Patch: p1\$" out &&
+    rm out
+'
+
+test_expect_success 'Diff options' '
+    stg email format -o out -O --src-prefix=SRC/ -O --dst-prefix=DST/ p1 &&
+    grep -e "^diff --git SRC/" out/0001-p1.patch &&
+    rm -r out &&
+    test_config stgit.diff-opts "--stat-width=30 --function-context" &&
+    stg email format -o out -O -U0 p2 &&
+    grep -e "^@@ -[0-9]*,0 " out/0001-p2-updated.patch &&
+    rm -r out
+'
+
+test_expect_success 'Git format-patch options are rejected' '
+    command_error stg email format -o out -G --thread p1 2>err &&
+    grep -e "\`--git-opt --thread\`: passing options to \`git format-patch\` is no longer supported" err &&
+    grep -e "use the equivalent \`stg email format\` option" err &&
+    test_path_is_missing out
+'
+
+test_expect_success 'Format non-contiguous patches' '
+    stg email format -o out --cover-letter p3 p1 &&
+    test_path_is_file out/0001-p1.patch &&
+    grep -e "^Subject: \[PATCH 2/2\] p3" out/0002-p3.patch &&
+    grep -e " 1.t " out/0000-cover-letter.patch &&
+    grep -e " 3.t " out/0000-cover-letter.patch &&
+    ! grep -e " 2.t " out/0000-cover-letter.patch &&
+    rm -r out &&
+    command_error stg email format -o out --cover-letter --range-diff=HEAD p1 p3 2>err &&
+    grep -e "requires the formatted patches to be contiguous" err &&
+    test_path_is_missing out
+'
+
+test_expect_success 'Skip empty patches' '
+    stg new -m empty-patch &&
+    test_when_finished "stg delete empty-patch" &&
+    command_error stg email format -o out p4 empty-patch 2>err &&
+    grep -e "cannot format empty patch \`empty-patch\`" err &&
+    test_path_is_missing out &&
+    stg email format -o out --skip-empty p4 empty-patch 2>err &&
+    grep -e "skipping empty patch \`empty-patch\`" err &&
+    test "$(ls out)" = "0001-p4.patch" &&
+    rm -r out &&
+    command_error stg email format -o out --skip-empty empty-patch 2>err &&
+    grep -e "no patches to format" err
+'
+
 test_done
@@ -61,4 +61,275 @@ test_expect_success GITSENDEMAIL 'Send patches from other branch' '
     test_cmp expected subjects
 '
 
+test_expect_success GITSENDEMAIL 'Send to address group' '
+    test_config stgit.email.group.team "a@example.com, b@example.com" &&
+    stg email send --dry-run --to team p7 >out &&
+    grep -e "^To: a@example.com" out &&
+    grep -e "b@example.com" out
+'
+
+test_expect_success GITSENDEMAIL 'Send without Cc from patch trailers' '
+    stg edit p6 --ack="A Cker <acker@example.com>" &&
+    stg email send --dry-run --to someone@example.com p6 >out &&
+    grep -e "acker@example.com" out &&
+    stg email send --dry-run --to someone@example.com --no-auto-cc p6 >out &&
+    ! grep -e "acker@example.com" out
+'
+
+test_expect_success 'Setup fake git send-email' '
+    mkdir fake-exec-path &&
+    write_script fake-exec-path/git-send-email <<-\EOF
+	echo "$@" >send-email-args &&
+	{ git config --get sendemail.smtpPass >send-email-pass || true; } &&
+	{ git config --get sendemail.work.smtpPass >>send-email-pass || true; }
+	EOF
+'
+
+test_expect_success 'Send with OAuth2 token command' '
+    GIT_EXEC_PATH="$(pwd)/fake-exec-path" \
+        stg email send --to someone@example.com --oauth-token-cmd="echo my-token" p7 &&
+    grep -e "--smtp-auth=XOAUTH2" send-email-args &&
+    ! grep -e "my-token" send-email-args &&
+    echo my-token >expected &&
+    test_cmp expected send-email-pass
+'
+
+test_expect_success 'Send with OAuth2 token command from config and identity' '
+    test_config stgit.email.oauth-token-cmd "printf \"config-token\\nignored\\n\"" &&
+    GIT_EXEC_PATH="$(pwd)/fake-exec-path" \
+        stg email send --to someone@example.com --identity=work p7 &&
+    grep -e "--smtp-auth=XOAUTH2" send-email-args &&
+    printf "config-token\nconfig-token\n" >expected &&
+    test_cmp expected send-email-pass
+'
+
+test_expect_success 'OAuth2 token command failures' '
+    command_error stg email send --to someone@example.com --oauth-token-cmd=false p7 2>err &&
+    grep -e "OAuth2 token command .false. failed" err &&
+    command_error stg email send --to someone@example.com --oauth-token-cmd=true p7 2>err &&
+    grep -e "OAuth2 token command .true. did not output a token" err
+'
+
+test_expect_success 'Send with recipient commands from config' '
+    test_config stgit.email.tocmd "echo to@example.com" &&
+    test_config stgit.email.cccmd "echo cc@example.com" &&
+    GIT_EXEC_PATH="$(pwd)/fake-exec-path" \
+        stg email send --to someone@example.com p7 &&
+    grep -e "--to-cmd=echo to@example.com" send-email-args &&
+    grep -e "--cc-cmd=echo cc@example.com" send-email-args &&
+    GIT_EXEC_PATH="$(pwd)/fake-exec-path" \
+        stg email send --to someone@example.com --cc-cmd=other p7 &&
+    grep -e "--cc-cmd=other" send-email-args &&
+    ! grep -e "--cc-cmd=echo" send-email-args
+'
+
+test_expect_success 'Send newest versioned output directory' '
+    command_error stg email send --to someone@example.com 2>err &&
+    grep -e "no versioned output directory found" err &&
+    stg email format --auto-dir p6 &&
+    stg email format --auto-dir p7 &&
+    GIT_EXEC_PATH="$(pwd)/fake-exec-path" \
+        stg email send --to someone@example.com &&
+    grep -e "outgoing/master/v2\$" send-email-args &&
+    ! grep -e "outgoing/master/v1" send-email-args &&
+    rm -r outgoing
+'
+
+test_expect_success 'Reuse previously sent cover letter' '
+    stg email format -o out --cover-letter p6 p7 &&
+    sed -e "s/\*\*\* SUBJECT HERE \*\*\*/Sent subject/" \
+        -e "s/\*\*\* BLURB HERE \*\*\*/Sent intro\\
+\\
+Second paragraph/" \
+        out/0000-cover-letter.patch >cover &&
+    mv cover out/0000-cover-letter.patch &&
+    GIT_EXEC_PATH="$(pwd)/fake-exec-path" \
+        stg email send --to someone@example.com --dry-run out &&
+    test_must_fail git config branch.master.stgit.cover-blurb &&
+    GIT_EXEC_PATH="$(pwd)/fake-exec-path" \
+        stg email send --to someone@example.com out &&
+    rm -r out &&
+    echo "Sent subject" >expected &&
+    git config branch.master.stgit.cover-subject >actual &&
+    test_cmp expected actual &&
+    stg email format -o out --reuse-cover p6 p7 &&
+    grep -e "^Subject: \[PATCH 0/2\] Sent subject\$" out/0000-cover-letter.patch &&
+    grep -e "^Sent intro\$" out/0000-cover-letter.patch &&
+    grep -e "^Second paragraph\$" out/0000-cover-letter.patch &&
+    ! grep -e "BLURB HERE" out/0000-cover-letter.patch &&
+    rm -r out
+'
+
+test_expect_success 'Setup git send-email that records sent emails' '
+    mkdir record-exec-path &&
+    write_script record-exec-path/git-send-email <<-\EOF
+	echo "$@" >send-email-args &&
+	echo "$@" >>send-email-log &&
+	for arg in "$@"
+	do
+	    case "$arg" in
+	    -*) ;;
+	    *) cat "$arg" >>sent-emails ;;
+	    esac
+	done
+	EOF
+'
+
+test_expect_success 'Interactively confirm emails' '
+    stg email format -o out --cover-letter --to=a@example.com p6 p7 &&
+    write_script recipients-editor <<-\EOF &&
+	printf "# comment\nTo: new@example.com\nCc: c@example.com, d@example.com\n" >"$1"
+	EOF
+    printf "s\ne\ny\ny\n" |
+    GIT_EDITOR="\"$(pwd)/recipients-editor\"" GIT_EXEC_PATH="$(pwd)/record-exec-path" \
+        stg email send --confirm=interactive out >output &&
+    grep -e "^Email 1 of 3: out/0000-cover-letter.patch\$" output &&
+    grep -e "^To: a@example.com\$" output &&
+    grep -e "^To: new@example.com\$" output &&
+    grep -e "1 file changed" output &&
+    grep -e "--confirm=never" send-email-args &&
+    ! grep -e "--confirm=interactive" send-email-args &&
+    ! grep -e "PATCH 0/2" sent-emails &&
+    test "$(grep -c "^Subject: " sent-emails)" = "2" &&
+    grep -e "^To: new@example.com\$" sent-emails &&
+    grep -e "^Cc: c@example.com,\$" sent-emails &&
+    grep -e "^    d@example.com\$" sent-emails &&
+    test "$(grep -c "^To: a@example.com\$" sent-emails)" = "1" &&
+    grep -e "^To: a@example.com\$" out/0001-* &&
+    rm sent-emails send-email-args send-email-log
+'
+
+test_expect_success 'Abort interactive confirmation' '
+    printf "y\na\n" |
+    GIT_EXEC_PATH="$(pwd)/record-exec-path" \
+        command_error stg email send --confirm=interactive out >output 2>err &&
+    grep -e "sending aborted; no emails were sent" err &&
+    test_path_is_missing send-email-args &&
+    GIT_EXEC_PATH="$(pwd)/record-exec-path" \
+        command_error stg email send --confirm=interactive out </dev/null 2>err &&
+    grep -e "sending aborted" err &&
+    printf "s\ns\ns\n" |
+    GIT_EXEC_PATH="$(pwd)/record-exec-path" \
+        stg email send --confirm=interactive out >output 2>err &&
+    grep -e "no emails to send" err &&
+    test_path_is_missing send-email-args &&
+    command_error stg email send --confirm=interactive p7 2>err &&
+    grep -e "requires emails formatted as files or directories" err &&
+    rm -r out
+'
+
+test_expect_success 'Send emails in batches' '
+    stg email format -o out --cover-letter p6 p7 &&
+    GIT_EXEC_PATH="$(pwd)/record-exec-path" \
+        stg email send --batch-size=2 --relogin-delay=0 out/* &&
+    test_line_count = 1 send-email-log &&
+    grep -e "--batch-size=2 --relogin-delay=0" send-email-args &&
+    general_error stg email send --batch-size=0 out 2>err &&
+    general_error stg email send --batch-size=2 --send-delay=1 out 2>err &&
+    grep -e "cannot be used with" err &&
+    rm send-email-log send-email-args sent-emails
+'
+
+test_expect_success 'Send emails with a delay' '
+    GIT_EXEC_PATH="$(pwd)/record-exec-path" \
+        stg email send --dry-run --send-delay=5 out &&
+    test_line_count = 3 send-email-log &&
+    test "$(grep -c "^Subject: " sent-emails)" = "3" &&
+    sed -n -e "s/^Message-Id: //p" out/0000-cover-letter.patch >id &&
+    test "$(grep -c "^In-Reply-To: $(cat id)\$" sent-emails)" = "2" &&
+    test "$(grep -c "^References: $(cat id)\$" sent-emails)" = "2" &&
+    rm send-email-log send-email-args sent-emails
+'
+
+test_expect_success 'Send emails lacking a Message-Id with a delay' '
+    for f in out/*
+    do
+        sed -e "/^Message-Id: /d" "$f" >tmp &&
+        mv tmp "$f" || return 1
+    done &&
+    GIT_EXEC_PATH="$(pwd)/record-exec-path" \
+        stg email send --dry-run --send-delay=5 out &&
+    test_line_count = 3 send-email-log &&
+    grep -e "^Message-Id: <.*\.stgit@" sent-emails >message-id &&
+    test_line_count = 1 message-id &&
+    sed -e "s/^Message-Id: //" message-id >id &&
+    test "$(grep -c "^In-Reply-To: $(cat id)\$" sent-emails)" = "2" &&
+    test "$(grep -c "^References: $(cat id)\$" sent-emails)" = "2" &&
+    ! grep -e "^Message-Id: " out/* &&
+    rm send-email-log send-email-args sent-emails
+'
+
+test_expect_success 'Send emails with a configured delay and no threading' '
+    test_config stgit.email.send-delay 5 &&
+    GIT_EXEC_PATH="$(pwd)/record-exec-path" \
+        stg email send --dry-run --no-thread out &&
+    test_line_count = 3 send-email-log &&
+    ! grep -e "^Message-Id: " sent-emails &&
+    ! grep -e "^In-Reply-To: " sent-emails &&
+    rm send-email-log send-email-args sent-emails &&
+    GIT_EXEC_PATH="$(pwd)/record-exec-path" \
+        stg email send --dry-run --batch-size=3 out/* &&
+    test_line_count = 1 send-email-log &&
+    command_error stg email send --send-delay=1 p7 2>err &&
+    grep -e "sending with a delay requires emails formatted as files or directories" err &&
+    rm send-email-log send-email-args sent-emails &&
+    rm -r out
+'
+
+test_expect_success 'Setup git send-email that fails transiently' '
+    mkdir failing-exec-path &&
+    write_script failing-exec-path/git-send-email <<-\EOF
+	echo "$@" >>send-email-log &&
+	count=$(cat fail-count 2>/dev/null || echo 0)
+	if test "$count" -gt 0
+	then
+	    echo $((count - 1)) >fail-count
+	    cat fail-message >&2
+	    exit 1
+	fi
+	EOF
+'
+
+test_expect_success 'Retry emails after transient SMTP errors' '
+    stg email format -o out p6 p7 &&
+    echo "451 4.7.1 Try again later" >fail-message &&
+    echo 2 >fail-count &&
+    GIT_EXEC_PATH="$(pwd)/failing-exec-path" \
+        stg email send --dry-run --smtp-pause=5 out 2>err &&
+    test_line_count = 4 send-email-log &&
+    test "$(grep -c "transient SMTP error sending" err)" = "2" &&
+    grep -e "retrying in 20 seconds (2 of 3)" err &&
+    rm send-email-log &&
+    echo "4.2.1 Mailbox temporarily unavailable" >fail-message &&
+    echo 1 >fail-count &&
+    GIT_EXEC_PATH="$(pwd)/failing-exec-path" \
+        stg email send --dry-run --send-delay=5 out 2>err &&
+    test_line_count = 3 send-email-log &&
+    rm send-email-log
+'
+
+test_expect_success 'Do not retry permanent SMTP errors or beyond the limit' '
+    echo "550 5.7.1 Message rejected" >fail-message &&
+    echo 1 >fail-count &&
+    GIT_EXEC_PATH="$(pwd)/failing-exec-path" \
+        command_error stg email send --dry-run --send-delay=5 out 2>err &&
+    test_line_count = 1 send-email-log &&
+    grep -e "550 5.7.1 Message rejected" err &&
+    ! grep -e "retrying" err &&
+    rm send-email-log &&
+    echo "421 Too many messages" >fail-message &&
+    echo 5 >fail-count &&
+    GIT_EXEC_PATH="$(pwd)/failing-exec-path" \
+        command_error stg email send --dry-run --send-delay=5 --retries=1 out 2>err &&
+    test_line_count = 2 send-email-log &&
+    rm send-email-log &&
+    echo 5 >fail-count &&
+    test_config stgit.email.retries 0 &&
+    GIT_EXEC_PATH="$(pwd)/failing-exec-path" \
+        command_error stg email send --dry-run --send-delay=5 out 2>err &&
+    test_line_count = 1 send-email-log &&
+    rm send-email-log fail-count &&
+    rm -r out
+'
+
 test_done
@@ -0,0 +1,54 @@
+#!/bin/sh
+
+test_description="Test 'stg email preview'"
+
+. ./test-lib.sh
+
+test_expect_success 'Setup StGit stack' '
+    test_commit_bulk --message="p%s" 7 &&
+    stg uncommit -n 7 &&
+    stg goto p4
+'
+
+test_expect_success 'Preview all applied patches' '
+    stg email preview --all >out &&
+    grep "^Subject: " out >subjects &&
+    cat >expected <<-\EOF &&
+	Subject: [PATCH 1/4] p1
+	Subject: [PATCH 2/4] p2
+	Subject: [PATCH 3/4] p3
+	Subject: [PATCH 4/4] p4
+	EOF
+    test_cmp expected subjects &&
+    test_path_is_missing 0001-p1.patch
+'
+
+test_expect_success 'Preview matches formatted mbox' '
+    stg email format --stdout --cover-letter --to someone@example.com p2..p5 >formatted &&
+    stg email preview --cover-letter --to someone@example.com p2..p5 >out &&
+    grep -v "^Message-Id: " formatted >expected &&
+    grep -v "^Message-Id: " out >actual &&
+    test_cmp expected actual &&
+    grep "^To: someone@example.com" out
+'
+
+test_expect_success 'Preview does not record Message-Id' '
+    stg email preview --thread p6 >out &&
+    grep -i "^Message-Id:" out &&
+    test_must_fail git notes --ref=refs/notes/stgit show $(stg id p6)
+'
+
+test_expect_success 'Preview uses configured pager only for terminals' '
+    test_config core.pager "sed s/^/paged:/" &&
+    stg email preview p1 >out &&
+    ! grep "^paged:" out
+'
+
+test_expect_success 'Output options are not accepted' '
+    general_error stg email preview -o out p1 2>err &&
+    grep -e "unexpected argument .-o. found" err &&
+    general_error stg email preview --stdout p1 2>err &&
+    grep -e "unexpected argument .--stdout. found" err
+'
+
+test_done
@@ -0,0 +1,127 @@
+#!/bin/sh
+
+test_description="Test 'stg email ingest'"
+
+. ./test-lib.sh
+
+test_expect_success 'Setup StGit stack' '
+    test_commit_bulk --message="p%s" 3 &&
+    stg uncommit -n 3 &&
+    stg email format -o emails --thread --cover-letter --all &&
+    p1_id=$(sed -n -e "s/^Message-Id: //p" emails/0001-p1.patch) &&
+    p2_id=$(sed -n -e "s/^Message-Id: //p" emails/0002-p2.patch) &&
+    cover_id=$(sed -n -e "s/^Message-Id: //p" emails/0000-cover-letter.patch) &&
+    cat >replies.mbox <<-EOF
+	From reviewer Mon Sep 17 00:00:00 2001
+	From: Jane Reviewer <jane@example.com>
+	Subject: Re: [PATCH 1/3] p1
+	Message-Id: <reply1@example.com>
+	In-Reply-To: $p1_id
+	References: $cover_id
+	 $p1_id
+
+	> p1
+	> Acked-by: Quoted Person <quoted@example.com>
+
+	Looks good.
+
+	Reviewed-by: Jane Reviewer <jane@example.com>
+
+	From tester Mon Sep 17 00:00:00 2001
+	From: Tom Tester <tom@example.com>
+	Subject: Re: [PATCH 1/3] p1
+	Message-Id: <reply2@example.com>
+	In-Reply-To: <reply1@example.com>
+	References: $cover_id
+	 $p1_id
+	 <reply1@example.com>
+
+	tested-by: Tom Tester <tom@example.com>
+	Reviewed-by: Jane Reviewer <jane@example.com>
+
+	From acker Mon Sep 17 00:00:00 2001
+	From: Al Acker <al@example.com>
+	Subject: Re: [PATCH 3/3] p3
+	Message-Id: <reply3@example.com>
+
+	Acked-by: Al Acker <al@example.com>
+
+	From other Mon Sep 17 00:00:00 2001
+	From: Other <other@example.com>
+	Subject: Re: [PATCH] unrelated
+	Message-Id: <reply4@example.com>
+
+	Reviewed-by: Other <other@example.com>
+
+	From chatter Mon Sep 17 00:00:00 2001
+	From: Chatter <chatter@example.com>
+	Subject: Re: [PATCH 2/3] p2
+	Message-Id: <reply5@example.com>
+	In-Reply-To: $p2_id
+
+	Nice.
+	EOF
+'
+
+test_expect_success 'Mbox must exist' '
+    command_error stg email ingest missing.mbox 2>err &&
+    grep "mbox \`missing.mbox\` not found" err
+'
+
+test_expect_success 'Show review status' '
+    stg email ingest replies.mbox >out 2>err &&
+    cat >expected <<-\EOF &&
+	p1
+	  Reviewed-by: Jane Reviewer <jane@example.com>
+	  Tested-by: Tom Tester <tom@example.com>
+	p3
+	  Acked-by: Al Acker <al@example.com>
+	EOF
+    test_cmp expected out &&
+    grep "no patch found for reply \`Re: \[PATCH\] unrelated\`" err &&
+    ! git log -1 --format=%B $(stg id p1) | grep -e "-by:"
+'
+
+test_expect_success 'Apply review trailers' '
+    stg email ingest --apply replies.mbox &&
+    git log -1 --format=%B $(stg id p1) >msg &&
+    grep "^Reviewed-by: Jane Reviewer <jane@example.com>$" msg &&
+    grep "^Tested-by: Tom Tester <tom@example.com>$" msg &&
+    ! grep "Quoted Person" msg &&
+    git log -1 --format=%B $(stg id p3) >msg &&
+    grep "^Acked-by: Al Acker <al@example.com>$" msg &&
+    test "$(echo $(stg series --no-prefix --applied))" = "p1 p2 p3" &&
+    test_cmp_rev HEAD $(stg id p3) &&
+    git notes --ref=refs/notes/stgit show $(stg id p1) | grep -i "^Message-Id: $p1_id"
+'
+
+test_expect_success 'Trailers already present are not added again' '
+    stg email ingest --apply replies.mbox >out &&
+    grep "Reviewed-by: Jane Reviewer <jane@example.com> (already present)" out &&
+    git log -1 --format=%B $(stg id p1) >msg &&
+    test "$(grep -c "^Reviewed-by:" msg)" = "1"
+'
+
+test_expect_success 'Collect trailers from encoded replies' '
+    cat >encoded.mbox <<-EOF &&
+	From encoded Mon Sep 17 00:00:00 2001
+	From: =?UTF-8?q?Ren=C3=A9_Reviewer?= <rene@example.com>
+	Subject: =?UTF-8?q?Re=3A_=5BPATCH_2/3=5D_p2?=
+	Message-Id: <reply6@example.com>
+	In-Reply-To: $p2_id
+	MIME-Version: 1.0
+	Content-Type: text/plain; charset=UTF-8
+	Content-Transfer-Encoding: quoted-printable
+
+	Reviewed-by: Ren=C3=A9 Reviewer <rene@example.=
+	com>
+	EOF
+    stg email ingest encoded.mbox >out &&
+    cat >expected <<-\EOF &&
+	p2
+	  Reviewed-by: René Reviewer <rene@example.com>
+	EOF
+    test_cmp expected out
+'
+
+test_done
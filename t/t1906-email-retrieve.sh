@@ -0,0 +1,91 @@
+#!/bin/sh
+
+test_description="Test 'stg email retrieve'"
+
+. ./test-lib.sh
+
+test_expect_success 'Setup thread archive' '
+    test_commit_bulk --message="p%s" 3 &&
+    stg uncommit -n 3 &&
+    stg email format --stdout --thread --cover-letter --all >thread.mbox &&
+    cover_id=$(sed -n -e "s/^Message-Id: <\(.*\)>$/\1/p" thread.mbox | head -n 1) &&
+    p1_id=$(sed -n -e "s/^Message-Id: <\(.*\)>$/\1/p" thread.mbox | sed -n 2p) &&
+    cat >>thread.mbox <<-EOF &&
+	From reviewer Mon Sep 17 00:00:00 2001
+	From: Jane Reviewer <jane@example.com>
+	Subject: Re: [PATCH 1/3] p1
+	Message-Id: <reply1@example.com>
+	In-Reply-To: <$p1_id>
+
+	> diff --git a/1.t b/1.t
+	Looks good.
+	EOF
+    mkdir -p "inbox/$cover_id" &&
+    gzip -c thread.mbox >"inbox/$cover_id/t.mbox.gz" &&
+    stg pop -a &&
+    stg delete p1 p2 p3 &&
+    git ls-files >files &&
+    test_must_be_empty files &&
+    git notes --ref=refs/notes/stgit prune
+'
+
+test_expect_success 'Retrieve by Message-Id' '
+    stg email retrieve --inbox-url "file://$(pwd)/inbox" "<$cover_id>" &&
+    test "$(echo $(stg series --no-prefix --applied))" = "p1 p2 p3" &&
+    test_path_is_file 1.t &&
+    test_path_is_file 3.t &&
+    git log -1 --format=%an%n%ae $(stg id p1) >actual &&
+    printf "A Ú Thor\nauthor@example.com\n" >expected &&
+    test_cmp expected actual &&
+    git log -1 --format=%B $(stg id p1) >msg &&
+    grep "^Link: file://.*/inbox/$p1_id$" msg &&
+    test -z "$(git status --porcelain -uno)"
+'
+
+test_expect_success 'Retrieve by archive URL without Link trailers' '
+    stg delete p1 p2 p3 &&
+    stg email retrieve --no-link "file://$(pwd)/inbox/$cover_id/" &&
+    test "$(echo $(stg series --no-prefix --applied))" = "p1 p2 p3" &&
+    ! git log -1 --format=%B $(stg id p2) | grep "^Link:"
+'
+
+test_expect_success 'Retrieve with existing patch names' '
+    stg pop -a &&
+    stg email retrieve --inbox-url "file://$(pwd)/inbox" "$cover_id" &&
+    test "$(echo $(stg series --no-prefix --applied))" = "p4 p5 p6" &&
+    test "$(echo $(stg series --no-prefix --unapplied))" = "p1 p2 p3"
+'
+
+test_expect_success 'Retrieve patches that do not apply' '
+    command_error stg email retrieve --inbox-url "file://$(pwd)/inbox" "$cover_id" 2>err &&
+    grep "applying patch \"p1\"" err &&
+    test "$(echo $(stg series --no-prefix --applied))" = "p4 p5 p6"
+'
+
+test_expect_success 'Retrieve unknown thread' '
+    command_error stg email retrieve --inbox-url "file://$(pwd)/inbox" bogus@example.com 2>err &&
+    grep "retrieving thread for <bogus@example.com>" err
+'
+
+test_expect_success 'Archive URL without Message-Id' '
+    command_error stg email retrieve "file://$(pwd)/inbox/" 2>err &&
+    grep "could not determine Message-Id" err
+'
+
+test_expect_success 'Retrieve thread with CRLF line endings' '
+    stg pop -a &&
+    stg delete p1 p2 p3 p4 p5 p6 &&
+    mkdir -p "inbox-crlf/$cover_id" &&
+    sed -e "s/\$/\r/" thread.mbox | gzip -c >"inbox-crlf/$cover_id/t.mbox.gz" &&
+    stg email retrieve --no-link --inbox-url "file://$(pwd)/inbox-crlf" "$cover_id" &&
+    test "$(echo $(stg series --no-prefix --applied))" = "p1 p2 p3" &&
+    git log -1 --format=%an $(stg id p1) >actual &&
+    echo "A Ú Thor" >expected &&
+    test_cmp expected actual &&
+    git log -1 --format=%B $(stg id p3) >msg &&
+    ! grep -q "$(printf "\r")" msg &&
+    test_path_is_file 3.t &&
+    test -z "$(git status --porcelain -uno)"
+'
+
+test_done
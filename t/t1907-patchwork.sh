@@ -0,0 +1,118 @@
+#!/bin/sh
+
+test_description="Test 'stg patchwork'"
+
+. ./test-lib.sh
+
+test_lazy_prereq PYTHON3 '
+    python3 -c "import http.server"
+'
+
+test_expect_success 'Missing Patchwork instance' '
+    stg init &&
+    command_error stg patchwork download 1 2>err &&
+    grep -e "no Patchwork instance specified" err &&
+    general_error stg patchwork download not-a-number
+'
+
+test_expect_success PYTHON3 'Setup Patchwork server' '
+    test_commit base &&
+    test_commit_bulk --message="p%s" 2 &&
+    stg uncommit -n 2 &&
+    mkdir pw &&
+    stg email format -o pw --numbered-files p1 p2 &&
+    stg delete p1 p2 &&
+    cat >server.py <<-\EOF &&
+	import http.server, json, os
+
+	class Handler(http.server.BaseHTTPRequestHandler):
+	    def log_message(self, *args):
+	        pass
+
+	    def reply(self, code, body):
+	        self.send_response(code)
+	        self.end_headers()
+	        self.wfile.write(body)
+
+	    def do_GET(self):
+	        base = "http://127.0.0.1:%d" % self.server.server_port
+	        if self.path == "/api/series/5/":
+	            patches = [
+	                {"id": 100 + n, "name": "[%d/2] p%d" % (n, n),
+	                 "mbox": "%s/patch/%d/mbox/" % (base, 100 + n),
+	                 "web_url": "%s/patch/%d/" % (base, 100 + n)}
+	                for n in (1, 2)
+	            ]
+	            body = {"id": 5, "name": "Two patches", "version": 1, "patches": patches}
+	            self.reply(200, json.dumps(body).encode())
+	        elif self.path in ("/patch/101/mbox/", "/patch/102/mbox/"):
+	            with open(os.path.join("pw", self.path[9]), "rb") as f:
+	                self.reply(200, f.read())
+	        else:
+	            self.reply(404, b"{}")
+
+	    def do_PATCH(self):
+	        length = int(self.headers["Content-Length"])
+	        state = json.loads(self.rfile.read(length))["state"]
+	        with open("updates", "a") as f:
+	            f.write("%s %s %s\n" % (self.path, state, self.headers["Authorization"]))
+	        self.reply(200, b"{}")
+
+	server = http.server.HTTPServer(("127.0.0.1", 0), Handler)
+	with open("server.pid", "w") as f:
+	    f.write(str(os.getpid()))
+	with open("port.tmp", "w") as f:
+	    f.write(str(server.server_port))
+	os.rename("port.tmp", "port")
+	server.serve_forever()
+	EOF
+    { python3 server.py & } &&
+    test_atexit "kill \$(cat \"$(pwd)/server.pid\")" &&
+    for i in $(test_seq 50); do test -f port && break; sleep 0.1; done &&
+    git config stgit.patchwork.url "http://127.0.0.1:$(cat port)/"
+'
+
+test_expect_success PYTHON3 'Download series' '
+    stg patchwork download 5 &&
+    test "$(echo $(stg series --noprefix))" = "p1 p2" &&
+    git log -1 --format=%B $(stg id p2) >message &&
+    grep -e "^Link: http://127.0.0.1:$(cat port)/patch/102/\$" message &&
+    test "$(git notes --ref=refs/notes/stgit show $(stg id p1))" = "Patchwork-Id: 101" &&
+    test "$(git log -1 --format=%an $(stg id p1))" = "A Ú Thor" &&
+    test "$(git log -1 --format=%s $(stg id p1))" = "p1" &&
+    test_path_is_file 2.t
+'
+
+test_expect_success PYTHON3 'Download missing series' '
+    command_error stg patchwork download 6 2>err &&
+    grep -e "fetching Patchwork series 6" err
+'
+
+test_expect_success PYTHON3 'Update requires token' '
+    command_error stg patchwork update 2>err &&
+    grep -e "no Patchwork API token configured" err &&
+    test_path_is_missing updates
+'
+
+test_expect_success PYTHON3 'Update patch states' '
+    git config stgit.patchwork.token secret &&
+    echo "more" >>2.t &&
+    stg refresh &&
+    stg rename p2 renamed &&
+    stg patchwork update >out &&
+    cat >expected <<-\EOF &&
+	/api/patches/101/ superseded Token secret
+	/api/patches/102/ superseded Token secret
+	EOF
+    test_cmp expected updates &&
+    grep -e "^renamed: Patchwork patch 102 set to superseded\$" out &&
+    rm updates &&
+    stg new -m "local" local &&
+    stg patchwork update --state accepted p1 local &&
+    echo "/api/patches/101/ accepted Token secret" >expected &&
+    test_cmp expected updates &&
+    stg patchwork update local 2>err &&
+    grep -e "no patches with Patchwork ids to update" err
+'
+
+test_done
@@ -0,0 +1,123 @@
+#!/bin/sh
+
+test_description='Test stg fetch-stack'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup upstream with stacks' '
+    test_create_repo upstream &&
+    (
+        cd upstream &&
+        test_commit_bulk --message="patch %s" --filename=foo.txt --contents="line %s" 3 &&
+        stg uncommit -n 3 &&
+        stg branch --create feature &&
+        stg new -m feature-patch feature-patch &&
+        stg branch --create old &&
+        stg branch master
+    ) &&
+    test_create_repo cloned &&
+    (
+        cd cloned &&
+        git remote add upstream ../upstream
+    )
+'
+
+test_expect_success 'Fetch new stacks' '
+    (
+        cd cloned &&
+        stg fetch-stack upstream >out &&
+        cat >expected <<-\EOF &&
+	new stack `feature` (1 patch)
+	new stack `master` (3 patches)
+	new stack `old` (0 patches)
+	EOF
+        test_cmp expected out &&
+        git show-ref --verify --quiet refs/remote-stacks/upstream/master &&
+        git show-ref --verify --quiet refs/remote-patches/upstream/master/patch-1 &&
+        git show-ref --verify --quiet refs/remote-patches/upstream/feature/feature-patch &&
+        test "$(git rev-parse refs/remote-patches/upstream/master/patch-2)" = \
+             "$(cd ../upstream && stg id patch-2)"
+    )
+'
+
+test_expect_success 'Fetch with no changes' '
+    (
+        cd cloned &&
+        stg fetch-stack upstream >out &&
+        test_must_be_empty out
+    )
+'
+
+test_expect_success 'Report stack updates' '
+    (
+        cd upstream &&
+        stg delete patch-3 &&
+        stg new -m patch-4 patch-4 &&
+        stg edit -m "patch 1 changed" patch-1
+    ) &&
+    (
+        cd cloned &&
+        stg fetch-stack upstream >out &&
+        cat >expected <<-\EOF &&
+	updated stack `master`
+	  ~ patch-1
+	  + patch-4
+	  - patch-3
+	EOF
+        test_cmp expected out
+    )
+'
+
+test_expect_success 'Deleted stacks remain without prune' '
+    (
+        cd upstream &&
+        stg branch --delete --force old &&
+        stg delete patch-4
+    ) &&
+    (
+        cd cloned &&
+        stg fetch-stack upstream >out &&
+        cat >expected <<-\EOF &&
+	updated stack `master`
+	  - patch-4
+	EOF
+        test_cmp expected out &&
+        git show-ref --verify --quiet refs/remote-stacks/upstream/old &&
+        git show-ref --verify --quiet refs/remote-patches/upstream/master/patch-4
+    )
+'
+
+test_expect_success 'Prune deleted stacks and patches' '
+    (
+        cd cloned &&
+        stg fetch-stack --prune upstream >out &&
+        cat >expected <<-\EOF &&
+	pruned stack `old`
+	EOF
+        test_cmp expected out &&
+        ! git show-ref --verify --quiet refs/remote-stacks/upstream/old &&
+        ! git show-ref --verify --quiet refs/remote-patches/upstream/master/patch-4 &&
+        git show-ref --verify --quiet refs/remote-patches/upstream/master/patch-1
+    )
+'
+
+test_expect_success 'Default remote from branch config' '
+    (
+        cd upstream &&
+        stg branch feature &&
+        stg new -m another another
+    ) &&
+    (
+        cd cloned &&
+        test_commit init &&
+        git config branch.master.remote upstream &&
+        stg fetch-stack >out &&
+        cat >expected <<-\EOF &&
+	updated stack `feature`
+	  + another
+	EOF
+        test_cmp expected out
+    )
+'
+
+test_done
@@ -0,0 +1,134 @@
+#!/bin/sh
+
+test_description='Test stg bundle'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup stack and clone' '
+    test_commit_bulk --message="base %s" --filename=base.txt --contents="base %s" 2 &&
+    git clone -q . cloned &&
+    stg branch --create feature &&
+    test_commit_bulk --message="patch %s" --filename=foo.txt --contents="line %s" 3 &&
+    stg uncommit -n 3 &&
+    stg pop &&
+    stg new -m hidden-patch hidden-patch &&
+    stg hide hidden-patch &&
+    stg branch master
+'
+
+test_expect_success 'Create bundle' '
+    stg bundle create --branch feature feature.bundle &&
+    git bundle list-heads feature.bundle >heads &&
+    grep -e "refs/heads/feature$" heads &&
+    grep -e "refs/stacks/feature$" heads &&
+    test_line_count = 2 heads
+'
+
+test_expect_success 'Create bundle requires initialized stack' '
+    git branch plain &&
+    uninitialized stg bundle create --branch plain plain.bundle 2>err &&
+    grep -e "StGit stack not initialized for branch .plain." err
+'
+
+test_expect_success 'Apply bundle in clone' '
+    (
+        cd cloned &&
+        stg bundle apply ../feature.bundle 2>err &&
+        grep -e "Created branch .feature. with 4 patches" err &&
+        test "$(git symbolic-ref --short HEAD)" = "master" &&
+        test "$(echo $(stg series -b feature --all))" = "+ patch-1 > patch-2 - patch-3 ! hidden-patch" &&
+        test "$(git rev-parse refs/stacks/feature)" = \
+             "$(cd .. && git rev-parse refs/stacks/feature)" &&
+        test "$(git rev-parse refs/patches/feature/patch-3)" = \
+             "$(cd .. && stg id feature:patch-3)" &&
+        stg log -b feature >log &&
+        grep -e "uncommit" log
+    )
+'
+
+test_expect_success 'Applied stack is usable' '
+    (
+        cd cloned &&
+        stg branch feature &&
+        stg push &&
+        test "$(echo $(stg series --applied --noprefix))" = "patch-1 patch-2 patch-3" &&
+        test "$(cat foo.txt)" = "line 3" &&
+        stg undo &&
+        stg branch master
+    )
+'
+
+test_expect_success 'Apply bundle to existing branch' '
+    (
+        cd cloned &&
+        command_error stg bundle apply ../feature.bundle 2>err &&
+        grep -e "branch .feature. already exists" err
+    )
+'
+
+test_expect_success 'Apply bundle as new branch name' '
+    (
+        cd cloned &&
+        stg bundle apply ../feature.bundle other &&
+        test "$(echo $(stg series -b other --all))" = "+ patch-1 > patch-2 - patch-3 ! hidden-patch" &&
+        git show-ref --verify --quiet refs/stacks/other
+    )
+'
+
+test_expect_success 'Apply non-stack bundle' '
+    git bundle create plain.bundle plain &&
+    (
+        cd cloned &&
+        command_error stg bundle apply ../plain.bundle 2>err &&
+        grep -e "is not a StGit stack bundle" err
+    )
+'
+
+test_expect_success 'Apply bundle missing prerequisites' '
+    test_create_repo unrelated &&
+    (
+        cd unrelated &&
+        test_commit unrelated &&
+        command_error stg bundle apply ../feature.bundle 2>err &&
+        grep -e "prerequisite" err &&
+        ! git show-ref --verify --quiet refs/heads/feature
+    )
+'
+
+test_expect_success 'Export patches to bundle' '
+    stg export --branch feature --bundle export.bundle &&
+    git bundle list-heads export.bundle >heads &&
+    grep -e "refs/heads/feature$" heads &&
+    grep -e "refs/stacks/feature$" heads &&
+    test_line_count = 2 heads &&
+    test "$(git rev-parse refs/stacks/feature)" != "$(grep refs/stacks/feature heads | cut -d" " -f1)" &&
+    (
+        cd cloned &&
+        stg bundle apply ../export.bundle exported &&
+        test "$(echo $(stg series -b exported --all))" = "+ patch-1 > patch-2" &&
+        test "$(git rev-parse exported)" = "$(cd .. && stg id feature:patch-2)" &&
+        stg log -b exported >log &&
+        ! grep -e "uncommit" log
+    )
+'
+
+test_expect_success 'Export single patch to bundle' '
+    stg export --branch feature --bundle part.bundle patch-2 &&
+    git bundle list-heads part.bundle >heads &&
+    test_line_count = 2 heads &&
+    (
+        cd cloned &&
+        stg bundle apply ../part.bundle part &&
+        test "$(echo $(stg series -b part --all))" = "> patch-2" &&
+        test "$(stg id part:{base})" = "$(stg id exported:patch-1)"
+    )
+'
+
+test_expect_success 'Export bundle requires consecutive applied patches' '
+    command_error stg export --branch feature --bundle bad.bundle patch-3 2>err &&
+    grep -e "only consecutive applied patches may be exported to a bundle" err &&
+    test_path_is_missing bad.bundle &&
+    general_error stg export --branch feature --bundle bad.bundle --stdout
+'
+
+test_done
@@ -0,0 +1,119 @@
+#!/bin/sh
+
+test_description='Test stg mirror'
+
+. ./test-lib.sh
+
+remote_refs () {
+    git --git-dir=backup.git for-each-ref --format="%(refname) %(objectname)" \
+        refs/stacks refs/patches refs/shelves refs/notes
+}
+
+local_refs () {
+    git for-each-ref --format="%(refname) %(objectname)" \
+        refs/stacks refs/patches refs/shelves refs/notes
+}
+
+test_expect_success 'Setup stack and backup remote' '
+    test_commit_bulk --message="p%s" 3 &&
+    stg uncommit -n 3 &&
+    git init --bare backup.git &&
+    git remote add backup backup.git
+'
+
+test_expect_success 'Attempt mirror without remote' '
+    command_error stg mirror 2>err &&
+    grep -e "no mirror remote; specify a remote or set \`stgit.mirror.remote\`" err
+'
+
+test_expect_success 'Dry run does not push' '
+    stg mirror --dry-run backup >out &&
+    cat >expected <<-\EOF &&
+	+ refs/patches/master/p1
+	+ refs/patches/master/p2
+	+ refs/patches/master/p3
+	+ refs/stacks/master
+	EOF
+    test_cmp expected out &&
+    test -z "$(remote_refs)"
+'
+
+test_expect_success 'Mirror stack to remote' '
+    stg mirror backup >out &&
+    test_cmp expected out &&
+    local_refs >expected-refs &&
+    remote_refs >remote-refs &&
+    test_cmp expected-refs remote-refs
+'
+
+test_expect_success 'Mirror is up to date' '
+    stg mirror backup >out &&
+    echo "mirror \`backup\` is up to date" >expected &&
+    test_cmp expected out
+'
+
+test_expect_success 'Mirror only pushes changes' '
+    test_config stgit.mirror.remote backup &&
+    stg new -m p4 &&
+    stg delete p2 &&
+    stg shelve wip p1 &&
+    stg mirror >out &&
+    cat >expected <<-\EOF &&
+	- refs/patches/master/p1
+	- refs/patches/master/p2
+	~ refs/patches/master/p3
+	+ refs/patches/master/p4
+	+ refs/shelves/wip
+	~ refs/stacks/master
+	EOF
+    test_cmp expected out &&
+    local_refs >expected-refs &&
+    remote_refs >remote-refs &&
+    test_cmp expected-refs remote-refs
+'
+
+test_expect_success 'Mirror automatically after commands' '
+    test_config stgit.mirror.remote backup &&
+    test_config stgit.mirror.auto true &&
+    stg new -m p5 &&
+    stg branch --create other &&
+    stg new -m other-patch &&
+    local_refs >expected-refs &&
+    remote_refs >remote-refs &&
+    test_cmp expected-refs remote-refs &&
+    git --git-dir=backup.git rev-parse --verify refs/patches/other/other-patch &&
+    stg branch master
+'
+
+test_expect_success 'Automatic mirror failure is a warning' '
+    test_config stgit.mirror.remote nonexistent &&
+    test_config stgit.mirror.auto true &&
+    stg new -m p6 2>err &&
+    test "$(stg top)" = "p6" &&
+    grep -e "warning: mirroring stacks to \`nonexistent\`" err &&
+    test_config stgit.mirror.remote "" &&
+    stg new -m p7 2>err &&
+    grep -e "\`stgit.mirror.auto\` is enabled, but \`stgit.mirror.remote\` is not set" err
+'
+
+test_expect_success 'Read-only commands do not mirror' '
+    test_config stgit.mirror.remote nonexistent &&
+    test_config stgit.mirror.auto true &&
+    stg series 2>err &&
+    test_must_be_empty err
+'
+
+test_expect_success 'Full mirror pushes all references' '
+    stg mirror backup &&
+    git --git-dir=backup.git update-ref -d refs/stacks/master &&
+    stg mirror backup >out &&
+    echo "mirror \`backup\` is up to date" >expected &&
+    test_cmp expected out &&
+    stg mirror --full backup >out &&
+    grep -e "^+ refs/stacks/master$" out &&
+    local_refs >expected-refs &&
+    remote_refs >remote-refs &&
+    test_cmp expected-refs remote-refs
+'
+
+test_done
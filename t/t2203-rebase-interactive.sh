@@ -272,6 +272,33 @@ test_expect_success 'Fixup after hide works correctly' '
     git diff-index --quiet HEAD
 '
 
+test_expect_success 'Sequence editor preferred for instructions' '
+    stg delete $(stg series --all --noprefix --no-description) &&
+    stg new -m p0 &&
+    stg new -m p1 &&
+    write_script seq-editor <<-\EOF &&
+	printf "keep p0\nhide p1\n" >"$1"
+	EOF
+    test_set_editor false &&
+    GIT_SEQUENCE_EDITOR="\"$(pwd)/seq-editor\"" stg rebase --interactive &&
+    test "$(echo $(stg series --noprefix --hidden))" = "p1" &&
+    stg unhide p1 &&
+    stg push p1 &&
+    test_config sequence.editor "\"$(pwd)/seq-editor\"" &&
+    stg rebase --interactive &&
+    test "$(echo $(stg series --noprefix --hidden))" = "p1"
+'
+
+test_expect_success 'Editor option overrides sequence editor' '
+    stg unhide p1 &&
+    stg push p1 &&
+    write_script fake-editor <<-\EOF &&
+	printf "keep p0\ndelete p1\n" >"$1"
+	EOF
+    GIT_SEQUENCE_EDITOR=false stg rebase --interactive --editor "\"$(pwd)/fake-editor\"" &&
+    test "$(echo $(stg series --noprefix --all))" = "p0"
+'
+
 test_expect_success 'No patches exits early' '
     stg delete $(stg series --all --noprefix --no-description) &&
     stg rebase --interactive
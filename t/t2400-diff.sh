@@ -51,6 +51,21 @@ test_expect_success 'Diff with multiple diff-opt' '
     grep -e "A B C foo.txt" out
 '
 
+test_expect_success 'Diff with configured diff options' '
+    test_config stgit.diff.algorithm histogram &&
+    test_config stgit.diff.renames copies &&
+    test_config stgit.diff.renameLimit 10 &&
+    stg diff >configured.diff &&
+    test_cmp add-foo.diff configured.diff &&
+    test_config stgit.diff.algorithm bogus &&
+    command_error stg diff 2>err &&
+    grep -e "invalid \`stgit.diff.algorithm\` value \`bogus\`" err &&
+    test_config stgit.diff.algorithm patience &&
+    test_config stgit.diff.renames sometimes &&
+    command_error stg diff 2>err &&
+    grep -e "invalid \`stgit.diff.renames\` value \`sometimes\`" err
+'
+
 test_expect_success 'Refresh patch' '
     stg refresh
 '
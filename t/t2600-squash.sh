@@ -89,7 +89,7 @@ test_expect_success 'Squash with top != head' '
 	EOF
     echo blahonga >>foo.txt &&
     git commit -a -m "a new commit" &&
-    EDITOR=./fake-editor command_error stg squash --name=r0 p0 q1 &&
+    EDITOR=./fake-editor head_top_mismatch stg squash --name=r0 p0 q1 &&
     test "$(echo $(stg series))" = "+ p0 > q1" &&
     test_path_is_missing editor-invoked
 '
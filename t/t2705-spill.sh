@@ -345,4 +345,66 @@ test_expect_success 'Spill removed file' '
     stg delete --top
 '
 
+test_expect_success 'Setup patches for range spill' '
+    echo q1 >q1.txt &&
+    stg add q1.txt &&
+    stg new -rm q1 &&
+    echo q2 >q2.txt &&
+    stg add q2.txt &&
+    stg new -rm q2 &&
+    echo q3 >q3.txt &&
+    echo q3 >>q1.txt &&
+    stg add q3.txt q1.txt &&
+    stg new -rm q3 &&
+    stg status >status.txt &&
+    test_must_be_empty status.txt
+'
+
+test_expect_success 'Spill range of topmost patches' '
+    stg spill --patches q2..q3 &&
+    test "$(echo $(stg series --applied --noprefix | tail -n 3))" = "q1 q2 q3" &&
+    test "$(stg top)" = "q3" &&
+    stg status >status.txt &&
+    cat >expected-status.txt <<-\EOF &&
+	M  q1.txt
+	A  q2.txt
+	A  q3.txt
+	EOF
+    test_cmp expected-status.txt status.txt &&
+    stg files q1 >files.txt &&
+    echo "A q1.txt" >expected-files.txt &&
+    test_cmp expected-files.txt files.txt &&
+    test -z "$(stg files q2)" &&
+    test -z "$(stg files q3)" &&
+    test "$(git log -1 --pretty=format:%B $(stg id q2))" = "q2" &&
+    stg log -n1 | grep -e "spill q2 q3" &&
+    stg undo &&
+    stg status >status.txt &&
+    test_must_be_empty status.txt &&
+    test "$(stg files q3)" = "$(printf "M q1.txt\nA q3.txt")"
+'
+
+test_expect_success 'Spill range with pathspec and reset' '
+    stg spill -r --patches q1.. q1.txt &&
+    stg status >status.txt &&
+    cat >expected-status.txt <<-\EOF &&
+	?? q1.txt
+	EOF
+    test_cmp expected-status.txt status.txt &&
+    test -z "$(stg files q1)" &&
+    test "$(stg files q2)" = "A q2.txt" &&
+    test "$(stg files q3)" = "A q3.txt" &&
+    grep q3 q1.txt &&
+    stg undo --hard
+'
+
+test_expect_success 'Spill non-topmost patches' '
+    command_error stg spill --patches q1..q2 2>err &&
+    grep -e "can only spill topmost applied patches" err &&
+    stg pop q3 &&
+    command_error stg spill --patches q3 2>err &&
+    grep -e "patch \`q3\` is not allowed" err &&
+    stg push q3
+'
+
 test_done
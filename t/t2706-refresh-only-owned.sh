@@ -0,0 +1,91 @@
+#!/bin/sh
+
+test_description='Test "stg refresh --only-owned"'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize StGit stack' '
+    test_seq 1 20 >file.txt &&
+    git add file.txt &&
+    git commit -m "base" &&
+    stg init &&
+    stg new -m p1 &&
+    sed -e "s/^5$/five/" file.txt >tmp && mv tmp file.txt &&
+    stg refresh &&
+    stg new -m p2 &&
+    sed -e "s/^15$/fifteen/" file.txt >tmp && mv tmp file.txt &&
+    stg refresh
+'
+
+test_expect_success 'Refresh only hunks owned by the top patch' '
+    sed -e "s/^five$/FIVE/" -e "s/^fifteen$/FIFTEEN/" file.txt >tmp &&
+    mv tmp file.txt &&
+    stg refresh --only-owned 2>err &&
+    grep "leaving 1 hunk not owned by \`p2\` in the worktree" err &&
+    stg show p2 >show.txt &&
+    grep "^+FIFTEEN$" show.txt &&
+    ! grep "FIVE" show.txt &&
+    git diff >diff.txt &&
+    grep "^-five$" diff.txt &&
+    grep "^+FIVE$" diff.txt &&
+    ! grep "FIFTEEN" diff.txt
+'
+
+test_expect_success 'Refresh remaining hunk into the owning patch' '
+    git stash &&
+    stg goto p1 &&
+    git stash pop &&
+    stg refresh --only-owned &&
+    git diff --quiet &&
+    stg show p1 >show.txt &&
+    grep "^+FIVE$" show.txt &&
+    stg push &&
+    test "$(sed -n -e 5p -e 15p file.txt | tr "\n" " ")" = "FIVE FIFTEEN "
+'
+
+test_expect_success 'Hunks adjoining owned lines are owned' '
+    sed -e "s/^16$/sixteen/" file.txt >tmp && mv tmp file.txt &&
+    stg refresh --only-owned &&
+    git diff --quiet &&
+    stg show p2 >show.txt &&
+    grep "^+sixteen$" show.txt
+'
+
+test_expect_success 'Kept hunks follow foreign hunks that change line counts' '
+    sed -e "/^2$/a\\
+extra1\\
+extra2" -e "s/^FIFTEEN$/Fifteen/" file.txt >tmp && mv tmp file.txt &&
+    stg refresh --only-owned &&
+    stg show p2 >show.txt &&
+    grep "^+Fifteen$" show.txt &&
+    ! grep "extra" show.txt &&
+    git diff >diff.txt &&
+    grep "^+extra1$" diff.txt &&
+    git checkout file.txt
+'
+
+test_expect_success 'Nothing owned leaves patch unchanged' '
+    sed -e "s/^1$/one/" file.txt >tmp && mv tmp file.txt &&
+    stg id p2 >before.txt &&
+    stg refresh --only-owned &&
+    stg id p2 >after.txt &&
+    test_cmp before.txt after.txt &&
+    git diff --stat >diff.txt &&
+    grep "file.txt" diff.txt &&
+    git checkout file.txt
+'
+
+test_expect_success 'Dirty index is refused' '
+    sed -e "s/^Fifteen$/FIFTEEN/" file.txt >tmp && mv tmp file.txt &&
+    git add file.txt &&
+    command_error stg refresh --only-owned 2>err &&
+    grep "the index is dirty" err &&
+    git reset --hard
+'
+
+test_expect_success 'Incompatible options' '
+    general_error stg refresh --only-owned --index 2>err &&
+    grep "cannot be used with" err
+'
+
+test_done
@@ -0,0 +1,88 @@
+#!/bin/sh
+
+test_description='Test "stg refresh --when"'
+
+. ./test-lib.sh
+
+dates () {
+    git log -1 --format="%at %ct" "$(stg id "$1")"
+}
+
+test_expect_success 'Initialize StGit stack' '
+    test_commit base &&
+    stg init &&
+    test_tick &&
+    echo one >file.txt &&
+    stg add file.txt &&
+    stg new -m p1 &&
+    stg refresh &&
+    dates p1 >dates-orig &&
+    test "$(cut -d" " -f1 dates-orig)" = "$test_tick"
+'
+
+test_expect_success 'Refresh keeps author date by default' '
+    test_tick &&
+    echo two >>file.txt &&
+    stg refresh &&
+    echo "$(cut -d" " -f1 dates-orig) $test_tick" >expected &&
+    dates p1 >actual &&
+    test_cmp expected actual
+'
+
+test_expect_success 'Refresh --when=committer keeps both dates' '
+    dates p1 >expected &&
+    test_tick &&
+    echo three >>file.txt &&
+    stg refresh --when=committer &&
+    dates p1 >actual &&
+    test_cmp expected actual &&
+    stg show p1 | grep "^+three$"
+'
+
+test_expect_success 'Refresh --when=now sets both dates to now' '
+    test_tick &&
+    echo four >>file.txt &&
+    stg refresh --when now &&
+    echo "$test_tick $test_tick" >expected &&
+    dates p1 >actual &&
+    test_cmp expected actual
+'
+
+test_expect_success 'Refresh --when=<commit> takes dates from commit' '
+    test_tick &&
+    echo five >>file.txt &&
+    stg refresh --when base &&
+    git log -1 --format="%at %ct" base >expected &&
+    dates p1 >actual &&
+    test_cmp expected actual
+'
+
+test_expect_success 'Refresh date handling from stgit.refresh.when' '
+    test_config stgit.refresh.when committer &&
+    dates p1 >expected &&
+    test_tick &&
+    echo six >>file.txt &&
+    stg refresh &&
+    dates p1 >actual &&
+    test_cmp expected actual
+'
+
+test_expect_success 'Command line overrides stgit.refresh.when' '
+    test_config stgit.refresh.when committer &&
+    test_tick &&
+    echo seven >>file.txt &&
+    stg refresh --when=now &&
+    echo "$test_tick $test_tick" >expected &&
+    dates p1 >actual &&
+    test_cmp expected actual
+'
+
+test_expect_success 'Invalid --when' '
+    echo eight >>file.txt &&
+    command_error stg refresh --when=bogus 2>err &&
+    grep "invalid refresh date source \`bogus\`" err &&
+    general_error stg refresh --when=now --authdate=now &&
+    general_error stg refresh --when=now --committer-date-is-author-date
+'
+
+test_done
@@ -51,6 +51,20 @@ test_expect_success 'Rename to same name' '
    grep -e "patch \`foo\` already exists" err
 '
 
+test_expect_success 'Rename to name differing only in case' '
+   stg rename foo BAZ 2>err &&
+   grep -e "patch name \`BAZ\` differs only in case from an existing patch; using \`BAZ-1\`" err &&
+   test "$(stg series --noprefix --all | grep -c -e "^BAZ-1\$")" = "1" &&
+   stg rename BAZ-1 foo
+'
+
+test_expect_success 'Rename patch to differ only in case' '
+   stg rename foo FOO 2>err &&
+   test_must_be_empty err &&
+   test "$(stg series --noprefix --all | grep -c -e "^FOO\$")" = "1" &&
+   stg rename FOO foo
+'
+
 test_expect_success 'Rename top-most when others exist' '
    stg rename bar
 '
@@ -0,0 +1,83 @@
+#!/bin/sh
+
+test_description='Select stack states by date'
+
+. ./test-lib.sh
+
+# Times are relative to the first test_tick, 1112911993 -0700, which is
+# 2005-04-07 15:13:13 -0700.
+
+test_expect_success 'Initialize StGit stack with three patches' '
+    test_tick &&
+    stg init &&
+    test_tick &&
+    stg new -m p1 &&
+    test_tick &&
+    stg new -m p2 &&
+    test_tick &&
+    stg new -m p3 &&
+    test "$(echo $(stg series --all))" = "+ p1 + p2 > p3"
+'
+
+test_expect_success 'Resolve {state} revision' '
+    test "$(stg id {state})" = "$(git rev-parse refs/stacks/master^)" &&
+    test "$(stg id {state}~1)" = "$(git rev-parse refs/stacks/master^~1)"
+'
+
+test_expect_success 'Resolve {state}@{date} revision' '
+    test "$(stg id "{state}@{2005-04-07 15:15:30 -0700}")" = "$(git rev-parse refs/stacks/master^~1)" &&
+    test "$(stg id "{state}@{2005-04-07 15:16:13 -0700}")" = "$(git rev-parse refs/stacks/master^)" &&
+    test "$(stg id "{state}@{2005-04-07 15:15:30 -0700}~1")" = "$(git rev-parse refs/stacks/master^~2)" &&
+    test "$(stg id "master:{state}@{2005-04-07 15:14:13 -0700}")" = "$(git rev-parse refs/stacks/master^~2)"
+'
+
+test_expect_success 'Date before any stack state' '
+    command_error stg id "{state}@{2005-04-07 15:00:00 -0700}" 2>err &&
+    grep -e "no stack state for branch .master. at or before .2005-04-07 15:00:00 -0700." err
+'
+
+test_expect_success 'Unterminated date' '
+    command_error stg id "{state}@{yesterday" 2>err &&
+    grep -e "unterminated date" err
+'
+
+test_expect_success 'Reset with --before' '
+    test_tick &&
+    stg pop -a &&
+    test "$(echo $(stg series --all))" = "- p1 - p2 - p3" &&
+    stg reset --before "2005-04-07 15:15:30 -0700" &&
+    test "$(echo $(stg series --all))" = "+ p1 > p2"
+'
+
+test_expect_success 'Reset with {state}@{date}' '
+    test_tick &&
+    stg reset "{state}@{2005-04-07 15:16:30 -0700}" &&
+    test "$(echo $(stg series --all))" = "+ p1 + p2 > p3"
+'
+
+test_expect_success 'Partial reset with {state}@{date}' '
+    test_tick &&
+    stg delete --top &&
+    test "$(echo $(stg series --all))" = "+ p1 > p2" &&
+    stg reset "{state}@{2005-04-07 15:16:30 -0700}" p3 &&
+    test "$(echo $(stg series --all))" = "+ p1 > p2 - p3"
+'
+
+test_expect_success 'Reset --before conflicts with committish' '
+    general_error stg reset --before yesterday {state}
+'
+
+test_expect_success 'Log with --before' '
+    stg log --before "2005-04-07 15:15:30 -0700" >log.txt &&
+    test_line_count = 3 log.txt &&
+    head -n 1 log.txt | grep -e "new: p2" &&
+    stg log -n 1 --before "2005-04-07 15:17:30 -0700" >log.txt &&
+    grep -e "reset" log.txt
+'
+
+test_expect_success 'Relative dates are accepted' '
+    stg log --before "1 hour ago" >log.txt &&
+    test_line_count = $(stg log | wc -l) log.txt
+'
+
+test_done
@@ -0,0 +1,68 @@
+#!/bin/sh
+
+test_description='Test stgit.adviceUndo hints'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize StGit stack' '
+    stg init &&
+    for i in 1 2 3; do
+        stg new p$i -m "patch $i" &&
+        echo "$i" >file$i &&
+        stg add file$i &&
+        stg refresh
+    done
+'
+
+test_expect_success 'No hint by default' '
+    stg pop >out &&
+    ! grep -e "^hint:" out &&
+    stg push
+'
+
+test_expect_success 'Hint after pop' '
+    test_config stgit.adviceUndo true &&
+    stg pop -n 2 >out &&
+    tail -n 1 out >hint &&
+    echo "hint: pop (2 popped, 2 files changed); undo with: stg undo" >expected &&
+    test_cmp expected hint
+'
+
+test_expect_success 'Hint after push' '
+    test_config stgit.adviceUndo true &&
+    stg push >out &&
+    cat >expected <<-\EOF &&
+	> p2
+	hint: push (1 pushed, 1 file changed); undo with: stg undo
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Hint after rename' '
+    test_config stgit.adviceUndo true &&
+    stg rename p1 p1-renamed >out &&
+    grep -e "^hint: rename p1 p1-renamed (1 renamed); undo with: stg undo$" out
+'
+
+test_expect_success 'Hint after undo suggests stack state' '
+    test_config stgit.adviceUndo true &&
+    state=$(git rev-parse --short=7 refs/stacks/master) &&
+    stg undo >out &&
+    grep -e "^hint: undo 1; undo with: stg reset $state$" out &&
+    test "$(echo $(stg series --noprefix))" = "p1 p2 p3" &&
+    stg reset $state &&
+    test "$(echo $(stg series --noprefix))" = "p1-renamed p2 p3"
+'
+
+test_expect_success 'No hint when command halts on conflicts' '
+    test_config stgit.adviceUndo true &&
+    stg goto p1-renamed &&
+    stg new conflicting -m conflicting &&
+    echo "conflict" >file2 &&
+    stg add file2 &&
+    stg refresh &&
+    conflict stg push p2 >out &&
+    ! grep -e "^hint: push" out
+'
+
+test_done
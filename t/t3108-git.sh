@@ -0,0 +1,60 @@
+#!/bin/sh
+
+test_description='Test stg git passthrough'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize the StGit repository' '
+    test_commit_bulk --message="p%s" 2 &&
+    stg uncommit -n 2 &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2"
+'
+
+test_expect_success 'Run git command that does not modify the branch' '
+    stg log -n1 --full >log-before &&
+    stg git log --oneline -1 >out &&
+    grep -e "p2" out &&
+    stg log -n1 --full >log-after &&
+    test_cmp log-before log-after
+'
+
+test_expect_success 'Run git command that modifies the branch' '
+    echo "external" >external.txt &&
+    git add external.txt &&
+    stg git commit -m external 2>err &&
+    grep -e "Recorded modifications to \`master\` by \`git commit -m external\`" err &&
+    stg log -n1 | grep -e "git commit -m external"
+'
+
+test_expect_success 'Undo git command' '
+    stg undo --hard &&
+    test "$(git log -1 --pretty=format:%s)" = "p2" &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2" &&
+    test_path_is_missing external.txt
+'
+
+test_expect_success 'Repair after git command' '
+    echo "external" >external.txt &&
+    git add external.txt &&
+    stg git commit -m external &&
+    stg repair &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2 external"
+'
+
+test_expect_success 'Failed git command exit code is propagated' '
+    test_expect_code 128 stg git rev-parse --verify not-a-rev
+'
+
+test_expect_success 'Refuse to run with head and top mismatch' '
+    git reset --hard HEAD~ &&
+    head_top_mismatch stg git status 2>err &&
+    grep -e "HEAD and stack top are not the same" err &&
+    stg repair
+'
+
+test_expect_success 'Git command is required' '
+    general_error stg git 2>err &&
+    grep -e "required arguments were not provided" err
+'
+
+test_done
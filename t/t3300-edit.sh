@@ -126,6 +126,7 @@ test_expect_success 'Save template to stdout' '
 # Test the various ways of invoking the interactive editor. The
 # preference order should be
 #
+#   0. --editor
 #   1. GIT_EDITOR
 #   2. stgit.editor (legacy)
 #   3. core.editor
@@ -192,6 +193,27 @@ test_expect_success 'Edit commit message interactively (GIT_EDITOR)' '
     test "$(msg HEAD)" = "$m//e5"
 '
 
+test_expect_success 'Edit commit message interactively (--editor)' '
+    mkeditor e6 &&
+    mkeditor e7 &&
+    test_when_finished "rm -f e6 e7" &&
+    m=$(msg HEAD) &&
+    GIT_EDITOR=./e5 PATH=.:$PATH stg edit --editor ./e6 p2 &&
+    test "$(msg HEAD)" = "$m//e6" &&
+    GIT_EDITOR=./e5 PATH=.:$PATH stg edit --edit-cmd ./e7 p2 &&
+    test "$(msg HEAD)" = "$m//e6//e7"
+'
+
+test_expect_success 'Editor command with arguments' '
+    write_script "editor with space" <<-\EOF &&
+	printf "$1" >>"$2"
+	EOF
+    test_when_finished "rm -f \"editor with space\"" &&
+    m=$(msg HEAD) &&
+    stg edit --editor "\"./editor with space\" arg" p2 &&
+    test "$(msg HEAD)" = "$m//arg"
+'
+
 test_expect_success 'Reset editors' '
     git config --unset core.editor &&
     git config --unset stgit.editor
@@ -49,4 +49,29 @@ test_expect_success 'Sign patch and tack on a custom ack and review' '
     test "$(msg refs/patches/master/p5)" = "$m//Signed-off-by: Someone <someone@example.com>/Acked-by: ACKKER/Reviewed-by: best friend"
 '
 
+test_expect_success 'Add Fixes trailer' '
+    m=$(msg refs/patches/master/p6) &&
+    stg edit --fixes p1 p6 &&
+    test "$(msg refs/patches/master/p6)" = "$m/Fixes: $(git rev-parse --short=12 refs/patches/master/p1) (\"p1\")"
+'
+
+test_expect_success 'Add Fixes trailer with core.abbrev' '
+    test_config core.abbrev 16 &&
+    stg edit --fixes p1 p4 &&
+    git cat-file -p refs/patches/master/p4 >msg &&
+    grep -e "^Fixes: $(git rev-parse --short=16 refs/patches/master/p1) (\"p1\")\$" msg
+'
+
+test_expect_success 'Add Fixes trailer for invalid revision' '
+    command_error stg edit --fixes bogus-rev p3 2>err &&
+    grep -e "bogus-rev" err &&
+    command_error stg edit --fixes "HEAD^{tree}" p3 2>err &&
+    grep -e "fixes target .HEAD^{tree}. is not a commit" err
+'
+
+test_expect_success 'New patch with Fixes trailer' '
+    stg new --fixes p2 -m "fix p2" fix-p2 &&
+    test "$(msg refs/patches/master/fix-p2)" = "fix p2//Fixes: $(git rev-parse --short=12 refs/patches/master/p2) (\"p2\")"
+'
+
 test_done
@@ -97,4 +97,31 @@ test_expect_success 'Sink without --committer-date-is-author-date' '
     test "$test_tick" -eq "$(committer_time foo)"
 '
 
+test_expect_success 'Preserve committer date when pushing' '
+    test_config stgit.push.preserve-committer-date true &&
+    committer_time foo >foo_ctime &&
+    committer_time bar >bar_ctime &&
+    test_tick &&
+    stg sink bar &&
+    test "$(cat bar_ctime)" -eq "$(committer_time bar)" &&
+    test "$(cat foo_ctime)" -eq "$(committer_time foo)"
+'
+
+test_expect_success 'Stable ids when moving patches back and forth' '
+    stg id foo >foo_id &&
+    stg id bar >bar_id &&
+    test_tick &&
+    GIT_COMMITTER_NAME="Other Committer" stg float --stable-ids bar &&
+    ! test "$(cat bar_id)" = "$(stg id bar)" &&
+    test_tick &&
+    GIT_COMMITTER_NAME="Other Committer" stg sink --stable-ids bar &&
+    test "$(cat bar_id)" = "$(stg id bar)" &&
+    test "$(cat foo_id)" = "$(stg id foo)"
+'
+
+test_expect_success 'Stable ids conflicts with --committer-date-is-author-date' '
+    general_error stg float --stable-ids --committer-date-is-author-date bar 2>err &&
+    grep -e "cannot be used with" err
+'
+
 test_done
@@ -0,0 +1,78 @@
+#!/bin/sh
+
+test_description='Test stg edit --json-io'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup patches' '
+    echo "foo" >foo.txt &&
+    stg add foo.txt &&
+    stg new -m "patch one" p1 &&
+    stg refresh &&
+    echo "bar" >>foo.txt &&
+    stg new -m "patch two
+
+Body of two." p2 &&
+    stg refresh
+'
+
+test_expect_success 'Write patch description as JSON' '
+    echo "{}" | GIT_EDITOR=false stg edit --json-io p1 >out &&
+    head -n 1 out >desc.json &&
+    grep -e "\"patchname\":\"p1\"" desc.json &&
+    grep -e "\"name\":\"A Ú Thor\"" desc.json &&
+    grep -e "\"email\":\"author@example.com\"" desc.json &&
+    grep -e "\"date\":\"2005-04-01 13:14:15 +0200\"" desc.json &&
+    grep -e "\"message\":\"patch one\\\\n\"" desc.json &&
+    ! grep -e "\"diff\"" desc.json &&
+    test "$(git log -1 --pretty=format:%B $(stg id p1))" = "patch one"
+'
+
+test_expect_success 'Edit message and author' '
+    printf "%s" "{\"message\": \"edited one\\n\\nNew body.\", \"author\": {\"name\": \"Other Person\", \"email\": \"other@example.com\", \"date\": \"2010-01-02 03:04:05 +0100\"}}" |
+    stg edit --json-io p1 &&
+    test "$(git log -1 --pretty=format:%s $(stg id p1))" = "edited one" &&
+    test "$(git log -1 --pretty=format:%b $(stg id p1))" = "New body." &&
+    test "$(git log -1 --pretty=format:"%an <%ae> %ai" $(stg id p1))" = \
+         "Other Person <other@example.com> 2010-01-02 03:04:05 +0100"
+'
+
+test_expect_success 'Rename patch' '
+    echo "{\"patchname\": \"renamed\"}" | stg edit --json-io p2 &&
+    test "$(echo $(stg series --noprefix))" = "p1 renamed" &&
+    test "$(git log -1 --pretty=format:%s $(stg id renamed))" = "patch two"
+'
+
+test_expect_success 'Generate patch name from message' '
+    echo "{\"patchname\": null, \"message\": \"Another name\"}" |
+    stg edit --json-io renamed &&
+    test "$(echo $(stg series --noprefix))" = "p1 another-name"
+'
+
+test_expect_success 'Edit diff' '
+    echo "{}" | stg edit --json-io --diff >out &&
+    head -n 1 out >desc.json &&
+    grep -e "\"diff_editable\":true" desc.json &&
+    grep -e "+bar" desc.json &&
+    sed -e "s/+bar/+baz/" desc.json | stg edit --json-io --diff &&
+    test "$(tail -n 1 foo.txt)" = "baz"
+'
+
+test_expect_success 'Empty input aborts' '
+    command_error stg edit --json-io </dev/null 2>err &&
+    grep -e "aborting due to empty patch description" err
+'
+
+test_expect_success 'Invalid JSON input' '
+    echo "not json" | command_error stg edit --json-io 2>err &&
+    grep -e "reading JSON patch description" err &&
+    echo "{\"author\": \"bogus\"}" | command_error stg edit --json-io 2>err &&
+    grep -e "\`author\` must be an object" err
+'
+
+test_expect_success 'JSON I/O conflicts with other edit modes' '
+    general_error stg edit --json-io --edit &&
+    general_error stg edit --json-io -m message
+'
+
+test_done
@@ -0,0 +1,118 @@
+#!/bin/sh
+
+test_description='Test patch metadata section in stg edit'
+
+. ./test-lib.sh
+
+show_meta () {
+    git notes --ref refs/notes/stgit show "$(stg id "$1")"
+}
+
+test_expect_success 'Setup patches' '
+    echo "foo" >foo.txt &&
+    stg add foo.txt &&
+    stg new -m "patch one" p1 &&
+    stg refresh &&
+    echo "bar" >>foo.txt &&
+    stg new -m "patch two" p2 &&
+    stg refresh
+'
+
+test_expect_success 'No metadata section without metadata' '
+    stg edit --save-template tmpl p1 &&
+    ! grep -e "metadata" tmpl
+'
+
+test_expect_success 'Add metadata with --file' '
+    stg edit --save-template tmpl p1 &&
+    cat >>tmpl <<-\EOF &&
+	# ---------------------- metadata ----------------------
+	Label: wip
+	Depends: p0
+	X-Custom: kept: verbatim
+	EOF
+    stg edit --file tmpl p1 &&
+    cat >expected <<-\EOF &&
+	Label: wip
+	Depends: p0
+	X-Custom: kept: verbatim
+	EOF
+    show_meta p1 >actual &&
+    test_cmp expected actual &&
+    test "$(git log -1 --pretty=format:%B $(stg id p1))" = "patch one"
+'
+
+test_expect_success 'Metadata section round trips' '
+    stg edit --save-template tmpl p1 &&
+    grep -e "^# ---------------------- metadata ----------------------$" tmpl &&
+    grep -e "^Label: wip$" tmpl &&
+    stg edit --file tmpl p1 &&
+    show_meta p1 >actual &&
+    test_cmp expected actual
+'
+
+test_expect_success 'Description without metadata section keeps metadata' '
+    stg edit -m "patch one updated" p1 &&
+    show_meta p1 >actual &&
+    test_cmp expected actual &&
+    printf "Patch: p1\n\npatch one\n" >desc &&
+    stg edit --file desc p1 &&
+    show_meta p1 >actual &&
+    test_cmp expected actual
+'
+
+test_expect_success 'Remove metadata fields' '
+    stg edit --save-template tmpl p1 &&
+    grep -v -e "^Depends:" tmpl >tmpl2 &&
+    stg edit --file tmpl2 p1 &&
+    cat >expected <<-\EOF &&
+	Label: wip
+	X-Custom: kept: verbatim
+	EOF
+    show_meta p1 >actual &&
+    test_cmp expected actual
+'
+
+test_expect_success 'Edit metadata with patch rename' '
+    stg edit --save-template tmpl p1 &&
+    sed -e "s/^Patch:.*/Patch: p1-renamed/" -e "s/^Label: wip/Label: ready/" tmpl >tmpl2 &&
+    stg edit --file tmpl2 p1 &&
+    test "$(echo $(stg series --noprefix))" = "p1-renamed p2" &&
+    cat >expected <<-\EOF &&
+	Label: ready
+	X-Custom: kept: verbatim
+	EOF
+    show_meta p1-renamed >actual &&
+    test_cmp expected actual
+'
+
+test_expect_success 'Edit metadata interactively' '
+    write_script editor <<-\EOF &&
+	sed -e "s/^Label: ready/Label: done/" "$1" >"$1".tmp &&
+	mv "$1".tmp "$1"
+	EOF
+    GIT_EDITOR=./editor stg edit p1-renamed &&
+    show_meta p1-renamed >actual &&
+    grep -e "^Label: done$" actual
+'
+
+test_expect_success 'Edit metadata with JSON' '
+    cat >edited.json <<-\EOF &&
+	{"metadata": "To: dev@example.com\n"}
+	EOF
+    stg edit --json-io p1-renamed <edited.json >out &&
+    head -n 1 out >desc.json &&
+    grep -e "\"metadata\":\"Label: done\\\\nX-Custom: kept: verbatim\\\\n\"" desc.json &&
+    echo "To: dev@example.com" >expected &&
+    show_meta p1-renamed >actual &&
+    test_cmp expected actual
+'
+
+test_expect_success 'Invalid metadata line' '
+    stg edit --save-template tmpl p2 &&
+    printf "# ---------------------- metadata ----------------------\nnot valid\n" >>tmpl &&
+    command_error stg edit --file tmpl p2 2>err &&
+    grep -e "invalid patch metadata line" err
+'
+
+test_done
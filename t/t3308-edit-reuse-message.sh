@@ -0,0 +1,79 @@
+#!/bin/sh
+
+test_description='Test stg edit --reuse-message and --reset-message'
+
+. ./test-lib.sh
+
+msg () { git cat-file -p "$1" | sed '1,/^$/d' | tr '\n' / | sed 's,/*$,,'; }
+
+test_expect_success 'Setup patches' '
+    test_commit_bulk --message="p%s" 2 &&
+    stg uncommit -n 2 &&
+    git commit --allow-empty -m "Subject from commit
+
+Body from commit." &&
+    git tag msg-commit &&
+    git reset --hard HEAD~ &&
+    stg edit -m "p2 subject
+
+p2 body" p2
+'
+
+test_expect_success 'Reuse message from commit' '
+    stg edit --reuse-message msg-commit p1 &&
+    test "$(msg $(stg id p1))" = "Subject from commit//Body from commit." &&
+    test "$(echo $(stg series --noprefix))" = "p1 p2"
+'
+
+test_expect_success 'Reuse message from patch' '
+    stg edit --reuse-message p2 p1 &&
+    test "$(msg $(stg id p1))" = "p2 subject//p2 body"
+'
+
+test_expect_success 'Reuse message with added trailer' '
+    stg edit --reuse-message msg-commit --signoff p1 &&
+    test "$(msg $(stg id p1))" = "Subject from commit//Body from commit.//Signed-off-by: C Ó Mitter <committer@example.com>"
+'
+
+test_expect_success 'Reuse message runs commit-msg hook' '
+    write_script .git/hooks/commit-msg <<-\EOF &&
+	echo "Hooked: yes" >>"$1"
+	EOF
+    test_when_finished rm .git/hooks/commit-msg &&
+    stg edit --reuse-message p2 p1 &&
+    test "$(msg $(stg id p1))" = "p2 subject//p2 body/Hooked: yes" &&
+    stg edit --no-verify --reuse-message p2 p1 &&
+    test "$(msg $(stg id p1))" = "p2 subject//p2 body"
+'
+
+test_expect_success 'Reuse message from bad revision' '
+    command_error stg edit --reuse-message not-a-rev p1 2>err &&
+    grep -e "parsing \`--reuse-message\` value" err
+'
+
+test_expect_success 'Reset message to template' '
+    echo "Template subject" >.git/patchdescr.tmpl &&
+    test_when_finished rm .git/patchdescr.tmpl &&
+    write_script editor <<-\EOF &&
+	sed -e "s/Template subject/Edited template subject/" "$1" >"$1".tmp &&
+	mv "$1".tmp "$1"
+	EOF
+    GIT_EDITOR=./editor stg edit --reset-message p1 &&
+    test "$(msg $(stg id p1))" = "Edited template subject"
+'
+
+test_expect_success 'Reset message without editing' '
+    echo "Template subject" >.git/patchdescr.tmpl &&
+    test_when_finished rm .git/patchdescr.tmpl &&
+    GIT_EDITOR=false stg edit --reset-message --ack p1 &&
+    test "$(msg $(stg id p1))" = "Template subject//Acked-by: C Ó Mitter <committer@example.com>"
+'
+
+test_expect_success 'Conflicting message options' '
+    general_error stg edit --reuse-message p2 -m foo p1 2>err &&
+    grep -e "cannot be used with" err &&
+    general_error stg edit --reuse-message p2 --reset-message p1 2>err &&
+    grep -e "cannot be used with" err
+'
+
+test_done
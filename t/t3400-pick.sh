@@ -85,6 +85,36 @@ test_expect_success 'Pick --noapply several patches' '
     test "$(echo $(stg series --unapplied --noprefix))" = "D"
 '
 
+test_expect_success 'Pick --noapply --after places patches after unapplied patch' '
+    stg pick -B foo --noapply --name E1 E &&
+    stg pick -B foo --noapply --after D --name E2 E &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "E1 D E2" &&
+    stg pick -B foo --noapply --after E1 --name E3 E &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "E1 E3 D E2"
+'
+
+test_expect_success 'Pick --noapply --last places patches at end of unapplied list' '
+    stg pick -B foo --noapply --last --name E4 E &&
+    test "$(echo $(stg series --applied --noprefix))" = "A B C" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "E1 E3 D E2 E4" &&
+    stg delete E1 E2 E3 E4
+'
+
+test_expect_success 'Pick --after requires --noapply' '
+    general_error stg pick -B foo --after D E 2>err &&
+    grep "the following required arguments were not provided" err &&
+    general_error stg pick -B foo --noapply --after D --last E 2>err &&
+    grep "cannot be used with" err
+'
+
+test_expect_success 'Pick --after with applied or missing patch' '
+    command_error stg pick -B foo --noapply --after B E 2>err &&
+    grep "cannot place picked patches after \`B\`; it is not unapplied" err &&
+    command_error stg pick -B foo --noapply --after nonexistent E 2>err &&
+    grep "patch \`nonexistent\` does not exist" err &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "D"
+'
+
 test_expect_success 'Pick --file without --fold' '
     general_error stg pick --file d D 2>err &&
     grep "the following required arguments were not provided" err &&
@@ -221,4 +251,24 @@ stg pick -B master A &&
 test "$(echo $(stg series -A --noprefix))" = "A"
 '
 
+test_expect_success 'Pick with author overrides' '
+    stg pick --author "Other Author <other@example.com>" \
+        --authdate "2005-04-07 22:13:13 +0200" foo:D-foo &&
+    test "$(git log -1 --format="%an <%ae>")" = "Other Author <other@example.com>" &&
+    test "$(git log -1 --format=%ai)" = "2005-04-07 22:13:13 +0200" &&
+    test "$(git log -1 --format=%cn)" = "$GIT_COMMITTER_NAME"
+'
+
+test_expect_success 'Pick with author name and committer date is author date' '
+    stg pick --authname "Name Only" --authdate "2006-01-02 03:04:05 +0000" \
+        --committer-date-is-author-date foo:Fancy@name &&
+    test "$(git log -1 --format="%an <%ae>")" = "Name Only <$GIT_AUTHOR_EMAIL>" &&
+    test "$(git log -1 --format=%ci)" = "2006-01-02 03:04:05 +0000"
+'
+
+test_expect_success 'Author overrides conflict with fold' '
+    general_error stg pick --fold --authname "Name" foo:E 2>err &&
+    grep -e "cannot be used with" err
+'
+
 test_done
@@ -0,0 +1,93 @@
+#!/bin/sh
+
+test_description='Test the backport command'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize branches' '
+    test_commit_bulk --message="base" --filename=f.txt 1 &&
+    git branch stable &&
+    stg new -m p1 p1 &&
+    echo a >a.txt &&
+    stg add a.txt &&
+    stg refresh &&
+    stg new -m p2 p2 &&
+    echo master >>f.txt &&
+    stg refresh &&
+    stg new -m p3 p3 &&
+    echo c >c.txt &&
+    stg add c.txt &&
+    stg refresh &&
+    stg new -m p4 p4 &&
+    echo d >d.txt &&
+    stg add d.txt &&
+    stg refresh &&
+    git checkout stable &&
+    echo stable >>f.txt &&
+    git commit -a -m "stable change" &&
+    echo d >d.txt &&
+    git add d.txt &&
+    git commit -m "upstream p4" &&
+    git checkout master
+'
+
+test_expect_success 'Attempt backport onto own branch' '
+    command_error stg backport --onto master p1 2>err &&
+    grep "cannot backport patches onto their own branch \`master\`" err
+'
+
+test_expect_success 'Attempt backport of nonexistent patch' '
+    command_error stg backport --onto stable p9 2>err &&
+    grep "patch \`p9\` does not exist" err
+'
+
+test_expect_success 'Backport patches onto another branch' '
+    stg backport --onto stable p1..p4 2>err &&
+    grep "skipping \`p4\`; already present on \`stable\`" err &&
+    grep "\`p2\` does not apply cleanly onto \`stable\`; left unapplied" err &&
+    test "$(echo $(stg series -b stable --applied --noprefix))" = "p1 p3" &&
+    test "$(echo $(stg series -b stable --unapplied --noprefix))" = "p2" &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2 p3 p4" &&
+    test "$(git symbolic-ref --short HEAD)" = "master" &&
+    git diff --exit-code
+'
+
+test_expect_success 'Backported patches record their origin' '
+    git log -1 --format=%B $(stg id stable:p1) >msg &&
+    grep "^(cherry picked from commit $(stg id p1))$" msg &&
+    echo "Backport-Of: master:p1" >expected &&
+    git notes --ref=refs/notes/stgit show $(stg id stable:p1) >actual &&
+    test_cmp expected actual &&
+    git notes --ref=refs/notes/stgit show $(stg id stable:p3) >actual &&
+    grep "Backport-Of: master:p3" actual
+'
+
+test_expect_success 'Backport skips patches already on target stack' '
+    stg backport --onto stable p1 p2 p3 2>err &&
+    grep "skipping \`p1\`" err &&
+    grep "skipping \`p2\`" err &&
+    grep "skipping \`p3\`" err &&
+    grep "no patches to backport" err &&
+    test "$(echo $(stg series -b stable --noprefix))" = "p1 p3 p2"
+'
+
+test_expect_success 'Backport with noapply' '
+    stg new -m p5 p5 &&
+    echo e >e.txt &&
+    stg add e.txt &&
+    stg refresh &&
+    stg backport --noapply --onto stable p5 &&
+    test "$(echo $(stg series -b stable --unapplied --noprefix))" = "p5 p2" &&
+    stg delete -b stable p5
+'
+
+test_expect_success 'Backport onto current branch from another branch' '
+    stg branch stable &&
+    stg backport -B master --onto stable p5 &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p3 p5" &&
+    test_path_is_file e.txt &&
+    git diff --exit-code &&
+    stg branch master
+'
+
+test_done
@@ -0,0 +1,130 @@
+#!/bin/sh
+
+test_description='Test expiry of hidden patches'
+
+. ./test-lib.sh
+
+hidden_at () {
+    git notes --ref stgit show "$(stg id "$1")" 2>/dev/null | sed -n -e "s/^Hidden-At: //p"
+}
+
+set_hidden_at () {
+    git notes --ref stgit add -f -m "Hidden-At: $2" "$(stg id "$1")"
+}
+
+test_expect_success 'Initialize stack' '
+    stg init &&
+    stg new -m p0 p0 &&
+    stg new -m p1 p1 &&
+    stg new -m p2 p2 &&
+    stg new -m p3 p3
+'
+
+test_expect_success 'Hide records the time the patch was hidden' '
+    stg hide p1 &&
+    test -n "$(hidden_at p1)" &&
+    now=$(date +%s) &&
+    test "$(hidden_at p1)" -le "$now" &&
+    test "$(hidden_at p1)" -gt "$(($now - 600))"
+'
+
+test_expect_success 'Hidden patches do not expire by default' '
+    set_hidden_at p1 1000000000 &&
+    stg series 2>err &&
+    test_must_be_empty err &&
+    stg doctor >out &&
+    test_must_be_empty out
+'
+
+test_expect_success 'Series warns about expired hidden patches' '
+    test_config stgit.hidden.expire-days 30 &&
+    stg series 2>err &&
+    grep -e "patch \`p1\` has been hidden for [0-9]* days" err
+'
+
+test_expect_success 'Recently hidden patches are not expired' '
+    test_config stgit.hidden.expire-days 30 &&
+    stg hide p2 &&
+    stg series 2>err &&
+    test_line_count = 1 err &&
+    ! grep -e "p2" err
+'
+
+test_expect_success 'Unhide removes the hidden time' '
+    stg unhide p2 &&
+    test -z "$(hidden_at p2)"
+'
+
+test_expect_success 'Doctor reports expired hidden patches' '
+    test_config stgit.hidden.expire-days 30 &&
+    command_error stg doctor >out 2>err &&
+    grep -e "^patch \`p1\` has been hidden for [0-9]* days$" out &&
+    grep -e "found 1 expired hidden patch; use \`stg doctor --fix\` to shelve" err
+'
+
+test_expect_success 'Doctor fix shelves expired hidden patches' '
+    test_config stgit.hidden.expire-days 30 &&
+    stg doctor --fix 2>err &&
+    grep -e "shelved 1 expired hidden patch onto \`hidden/master/" err &&
+    test "$(echo $(stg series --all --noprefix))" = "p0 p3 p2" &&
+    stg shelve --list >out &&
+    grep -e "^hidden/master/[0-9-]* (master): p1$" out &&
+    stg doctor >out &&
+    test_must_be_empty out
+'
+
+test_expect_success 'Restore shelved expired patch' '
+    stg unshelve "$(stg shelve --list | sed -e "s/ .*//")" &&
+    test "$(echo $(stg series --all --noprefix))" = "p0 p3 p1 p2"
+'
+
+test_expect_success 'Missing hidden time is recorded when first seen' '
+    test_config stgit.hidden.expire-days 30 &&
+    stg hide p1 &&
+    git notes --ref stgit remove "$(stg id p1)" &&
+    stg doctor >out &&
+    test_must_be_empty out &&
+    now=$(date +%s) &&
+    test "$(hidden_at p1)" -le "$now" &&
+    test "$(hidden_at p1)" -gt "$(($now - 600))"
+'
+
+test_expect_success 'Missing hidden time is not recorded in read-only mode' '
+    test_config stgit.hidden.expire-days 30 &&
+    git notes --ref stgit remove "$(stg id p1)" &&
+    STG_READONLY=1 stg series 2>err &&
+    test_must_be_empty err &&
+    test -z "$(hidden_at p1)" &&
+    stg series &&
+    test -n "$(hidden_at p1)"
+'
+
+test_expect_success 'Undoing hide removes the hidden time' '
+    stg hide p2 &&
+    test -n "$(hidden_at p2)" &&
+    stg undo &&
+    test "$(echo $(stg series --hidden --noprefix))" = "p1" &&
+    test -z "$(hidden_at p2)" &&
+    stg redo &&
+    test -n "$(hidden_at p2)" &&
+    stg unhide p2 &&
+    test -z "$(hidden_at p2)"
+'
+
+test_expect_success 'Renaming a hidden patch keeps its hidden time' '
+    set_hidden_at p1 1000000000 &&
+    stg rename p1 p1-renamed &&
+    test "$(hidden_at p1-renamed)" = "1000000000" &&
+    stg rename p1-renamed p1
+'
+
+test_expect_success 'Hide auto-shelves expired hidden patches' '
+    test_config stgit.hidden.expire-days 30 &&
+    test_config stgit.hidden.auto-shelve true &&
+    stg hide p3 2>err &&
+    grep -e "shelved 1 expired hidden patch onto \`hidden/master/" err &&
+    test "$(echo $(stg series --all --noprefix))" = "p0 p2 p3" &&
+    test "$(echo $(stg series --hidden --noprefix))" = "p3"
+'
+
+test_done
@@ -49,6 +49,41 @@ test_expect_success 'Export to stdout' '
     grep -e "^----------------------------"
 '
 
+test_expect_success 'Export to stdout has series section' '
+    sed -n -e "2p" export2.txt >actual &&
+    echo series >expected &&
+    test_cmp expected actual &&
+    grep -e "^patch-5$" export2.txt &&
+    stg export --stdout patch-2 >export2-single.txt &&
+    head -n1 export2-single.txt | grep -e "^patch-2$"
+'
+
+test_expect_success 'Export to combined file' '
+    stg export --combined combined.txt -n -p patch-2 patch-3 &&
+    cat >expected <<-EOF &&
+	-------------------------------------------------------------------------------
+	series
+	-------------------------------------------------------------------------------
+	# This series applies on Git commit $(stg id {base})
+	01-patch-2.patch
+	02-patch-3.patch
+	-------------------------------------------------------------------------------
+	01-patch-2.patch
+	-------------------------------------------------------------------------------
+	EOF
+    head -n 9 combined.txt >actual &&
+    test_cmp expected actual &&
+    grep -e "^02-patch-3.patch$" combined.txt &&
+    grep -e "^+line 3$" combined.txt &&
+    stg export --combined single.txt patch-4 &&
+    sed -n -e "5p;7p" single.txt >actual &&
+    printf "patch-4\npatch-4\n" >expected &&
+    test_cmp expected actual &&
+    general_error stg export --combined both.txt --stdout &&
+    general_error stg export --combined both.txt --dir both &&
+    test_path_is_missing both.txt
+'
+
 test_expect_success 'Export with none applied' '
     stg pop -a &&
     command_error stg export --dir export3 2>err &&
@@ -87,4 +122,195 @@ test_expect_success 'Export series with empty patch' '
     stg import -S export6/series
 '
 
+test_expect_success 'Export worktree snapshots' '
+    echo "bar" >bar.txt &&
+    stg add bar.txt &&
+    stg new -m add-bar &&
+    stg refresh &&
+    stg export --worktree snapshots patch-1 add-bar &&
+    test_path_is_file snapshots/series &&
+    grep -e "^patch-1$" snapshots/series &&
+    grep -e "^add-bar$" snapshots/series &&
+    test_path_is_missing snapshots/patch-1/bar.txt &&
+    test "$(cat snapshots/patch-1/foo.txt)" = "$(git show $(stg id patch-1):foo.txt)" &&
+    test_cmp foo.txt snapshots/add-bar/foo.txt &&
+    test_cmp bar.txt snapshots/add-bar/bar.txt
+'
+
+test_expect_success 'Export numbered worktree snapshots of changed files' '
+    echo "stale" >stale.txt &&
+    mkdir -p snapshots2/01-patch-1 &&
+    cp stale.txt snapshots2/01-patch-1/ &&
+    stg export --worktree snapshots2 --changed-only -n patch-1 add-bar &&
+    test_path_is_file snapshots2/01-patch-1/foo.txt &&
+    test_path_is_missing snapshots2/01-patch-1/stale.txt &&
+    test_path_is_missing snapshots2/02-add-bar/foo.txt &&
+    test_cmp bar.txt snapshots2/02-add-bar/bar.txt &&
+    grep -e "^02-add-bar$" snapshots2/series
+'
+
+test_expect_success 'Worktree export conflicts with patch file options' '
+    general_error stg export --worktree snapshots3 --stdout &&
+    general_error stg export --changed-only &&
+    test_path_is_missing snapshots3
+'
+
+test_expect_success 'Export with filename template' '
+    stg export -d tmpl --filename-template "%n-%p.diff" patch-1 add-bar &&
+    test_path_is_file tmpl/01-patch-1.diff &&
+    test_path_is_file tmpl/02-add-bar.diff &&
+    grep -e "^02-add-bar.diff$" tmpl/series &&
+    stg export -d tmpl-width --number-width 4 --filename-template "%n_%s.patch" add-bar &&
+    test_path_is_file tmpl-width/0001_add-bar.patch
+'
+
+test_expect_success 'Export with filename template subdirectories' '
+    stg export -d tmpl-dirs --filename-template "%p/%%%n.patch" patch-1 add-bar &&
+    test_path_is_file tmpl-dirs/patch-1/%01.patch &&
+    test_path_is_file tmpl-dirs/add-bar/%02.patch &&
+    grep -e "^add-bar/%02.patch$" tmpl-dirs/series &&
+    stg export -d numbered-width -n --number-width 3 add-bar &&
+    test_path_is_file numbered-width/001-add-bar
+'
+
+test_expect_success 'Invalid filename templates' '
+    command_error stg export -d bad --filename-template "%x" add-bar 2>err &&
+    grep -e "unknown placeholder \`%x\`" err &&
+    command_error stg export -d bad --filename-template "../%p" add-bar 2>err &&
+    grep -e "expands to invalid file name \`../add-bar\`" err &&
+    command_error stg export -d bad --filename-template "same.patch" patch-1 add-bar 2>err &&
+    grep -e "names both \`patch-1\` and \`add-bar\` \`same.patch\`" err &&
+    general_error stg export --filename-template "%p" --numbered &&
+    test_path_is_missing bad
+'
+
+test_expect_success 'Export quilt series' '
+    stg export --quilt patch-1 add-bar &&
+    test_path_is_file patches/patch-1.patch &&
+    test_path_is_file patches/add-bar.patch &&
+    grep -e "^add-bar.patch$" patches/series &&
+    grep -e "^+++ b/bar.txt$" patches/add-bar.patch &&
+    git init quilt-target &&
+    test_when_finished "rm -rf quilt-target" &&
+    git -C quilt-target apply -p1 --check "$(pwd)/patches/add-bar.patch"
+'
+
+test_expect_success 'Export quilt series with strip level 0 and DEP-3 headers' '
+    stg export --quilt -d quilt0 --strip-level 0 --quilt-header dep3 add-bar &&
+    grep -e "^add-bar.patch -p0$" quilt0/series &&
+    grep -e "^+++ bar.txt$" quilt0/add-bar.patch &&
+    grep -E "^Last-Update: [0-9]{4}-[0-9]{2}-[0-9]{2}$" quilt0/add-bar.patch &&
+    head -n 4 quilt0/add-bar.patch | grep -v "^Last-Update:" >header &&
+    cat >expected <<-\EOF &&
+	Description: add-bar
+	Author: A Ú Thor <author@example.com>
+	---
+	EOF
+    test_cmp expected header
+'
+
+test_expect_success 'Export quilt series without headers' '
+    stg export --quilt -d quilt-bare --quilt-header none add-bar &&
+    head -n 1 quilt-bare/add-bar.patch | grep -e "^diff --git" &&
+    general_error stg export --strip-level 0 &&
+    general_error stg export --quilt --stdout
+'
+
+test_expect_success 'Export to tar.gz archive' '
+    stg export --archive tar.gz series.tar.gz patch-1 add-bar &&
+    tar -tzf series.tar.gz | sort >files &&
+    cat >expected <<-\EOF &&
+	patches-master/add-bar
+	patches-master/patch-1
+	patches-master/series
+	EOF
+    test_cmp expected files &&
+    mkdir archive-tgz &&
+    tar -xzf series.tar.gz -C archive-tgz &&
+    stg export -d dir-export patch-1 add-bar &&
+    test_cmp dir-export/series archive-tgz/patches-master/series &&
+    test_cmp dir-export/add-bar archive-tgz/patches-master/add-bar
+'
+
+test_expect_success 'Reimport tar.gz archive' '
+    stg branch --create archive-import "$(git rev-parse "$(stg id patch-1)^")" &&
+    stg import --series "$(pwd)/series.tar.gz" &&
+    test "$(echo $(stg series --noprefix))" = "patch-1 add-bar" &&
+    stg branch master
+'
+
+test_lazy_prereq UNZIP '
+    unzip -v
+'
+
+test_expect_success UNZIP 'Export to zip archive' '
+    stg export --archive zip series.zip --numbered --patch patch-1 add-bar &&
+    mkdir archive-zip &&
+    (cd archive-zip && unzip -q ../series.zip) &&
+    stg export -d dir-zip --numbered --patch patch-1 add-bar &&
+    test_cmp dir-zip/series archive-zip/patches-master/series &&
+    test_cmp dir-zip/01-patch-1.patch archive-zip/patches-master/01-patch-1.patch &&
+    test_cmp dir-zip/02-add-bar.patch archive-zip/patches-master/02-add-bar.patch
+'
+
+test_expect_success 'Invalid archive arguments' '
+    command_error stg export --archive rar series.rar 2>err &&
+    grep -e "unsupported archive format \`rar\`; use \`tar.gz\` or \`zip\`" err &&
+    test_path_is_missing series.rar &&
+    general_error stg export --archive zip &&
+    general_error stg export --archive zip out.zip --stdout &&
+    general_error stg export --archive zip out.zip -d dir
+'
+
+test_expect_success 'Export binary patches' '
+    printf "\001\002\003\000binary\n" >blob.bin &&
+    stg new -m "add-blob" &&
+    git add blob.bin &&
+    stg refresh &&
+    printf "\001\002\003\000changed\n" >blob.bin &&
+    stg new -m "change-blob" &&
+    stg refresh &&
+    stg export -d binary-export add-blob change-blob &&
+    grep -e "^GIT binary patch\$" binary-export/add-blob &&
+    grep -e "^GIT binary patch\$" binary-export/change-blob &&
+    grep -E "^index [0-9a-f]{40}\.\.[0-9a-f]{40}" binary-export/change-blob &&
+    stg export --no-binary -d no-binary-export add-blob change-blob &&
+    grep -e "^Binary files /dev/null and b/blob.bin differ\$" no-binary-export/add-blob &&
+    ! grep -e "GIT binary patch" no-binary-export/change-blob &&
+    grep -E "^index [0-9a-f]{40}\.\.[0-9a-f]{40}" no-binary-export/change-blob &&
+    stg export --no-binary --binary -d binary-export2 add-blob change-blob &&
+    test_cmp binary-export/change-blob binary-export2/change-blob
+'
+
+test_expect_success 'Reimport binary patches into another repository' '
+    cp blob.bin blob.expected &&
+    git init binary-repo &&
+    (
+        cd binary-repo &&
+        git commit --allow-empty -m "initial" &&
+        stg import -S ../binary-export/series &&
+        test "$(echo $(stg series --noprefix))" = "add-blob change-blob" &&
+        test_cmp ../blob.expected blob.bin
+    )
+'
+
+test_expect_success 'Round-trip binary patches through export and import' '
+    stg export --binary -d roundtrip-export add-blob change-blob &&
+    stg delete add-blob change-blob &&
+    test_path_is_missing blob.bin &&
+    stg import -S roundtrip-export/series &&
+    test "$(echo $(stg series --noprefix --applied | tail -n 2))" = "add-blob change-blob" &&
+    test_cmp blob.expected blob.bin &&
+    stg export --binary -d roundtrip-export2 add-blob change-blob &&
+    test_cmp roundtrip-export/change-blob roundtrip-export2/change-blob
+'
+
+test_expect_success 'Reimport patches exported without binary diffs' '
+    stg delete add-blob change-blob &&
+    test_path_is_missing blob.bin &&
+    stg import -S no-binary-export/series &&
+    test "$(stg top)" = "change-blob" &&
+    test_cmp blob.expected blob.bin
+'
+
 test_done
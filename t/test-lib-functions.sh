@@ -1840,6 +1840,8 @@ test_cmp_config_output () {
 general_error () { test_expect_code 1 "$@" ; }
 command_error () { test_expect_code 2 "$@" ; }
 conflict () { test_expect_code 3 "$@" ; }
+head_top_mismatch () { test_expect_code 4 "$@" ; }
+uninitialized () { test_expect_code 5 "$@" ; }
 
 # Fake implementation of the "test-tool" helper tool from Git's test infrastructure.
 # Only the bare minimum of subcommands are implemented.